@@ -125,6 +125,7 @@ if __name__ == "__main__":
     cfm_state_ob_r1_serialized, cfm_msg1_serialized = ffi_cfm_create_msg1(
         session_id_new, big_l, big_x, 
         hash_single,
+        ot_seeds_ob_serialized,
         shares_ob_serialized,
         rng
     )
@@ -136,7 +137,7 @@ if __name__ == "__main__":
 
     cfm_state_cb_r1_serialized, cfm_msg2_serialized = ffi_cfm_process_msg1(
         session_id_new, big_l, hash_list,
-        big_z, shares_cb, cfm_msg1_serialized, rng
+        big_z, ot_seeds_cb_serialized, shares_cb, cfm_msg1_serialized, rng
     )
 
     print(f"CMF STATE CB R1 size: {len(cfm_state_cb_r1_serialized)}",  " ---------- ", len(zlib.compress(cfm_state_cb_r1_serialized)))
@@ -7,61 +7,141 @@ import gc
 lib_path = os.path.join(os.getcwd(), "cfm_lib/target/release/libcfm_lib.so")
 rust_lib = ctypes.CDLL(lib_path)
 
+# Must match CFM_ABI_VERSION in cfm_lib/include/cfm_lib.h, checked before any
+# other call so a stale library fails here instead of on a struct layout.
+CFM_ABI_VERSION = 10
+rust_lib.cfm_abi_version.argtypes = []
+rust_lib.cfm_abi_version.restype = ctypes.c_uint32
+if rust_lib.cfm_abi_version() != CFM_ABI_VERSION:
+    raise ImportError(
+        f"{lib_path} has ABI version {rust_lib.cfm_abi_version()}, "
+        f"cfm_ffi.py expects {CFM_ABI_VERSION}"
+    )
+
+# Every entry point returns a CFMStatus (0 = Ok), the reason of a failure
+# is kept in a thread-local last error message.
+rust_lib.cfm_last_error_code.argtypes = []
+rust_lib.cfm_last_error_code.restype = ctypes.c_int
+rust_lib.cfm_last_error_message.argtypes = []
+rust_lib.cfm_last_error_message.restype = ctypes.c_char_p
+
+CFM_STATUS_OK = 0
+# null pointer, empty buffer, invalid argument or undecodable input bytes
+CFM_INPUT_STATUS = {1, 2}
+# the other party failed an open, commitment, MAC or proof check (CFMStatus::is_cheating)
+CFM_CHEATING_STATUS = {6, 102, 103, 104, 105, 112, 203, 204, 205, 303, 304, 401, 502, 602, 701, 702, 703, 704}
+# the session handle has already accepted this round, resend its last_outbound()
+CFM_DUPLICATE_MESSAGE_STATUS = 14
+# the abort report or receipt is not signed under the given verifying key
+CFM_INVALID_SIGNATURE_STATUS = 7
+
+# OutputPolicy of CFM, who learns the result bit. Both parties pass the same one.
+OUTPUT_BOTH = 0
+OUTPUT_OB_ONLY = 1
+OUTPUT_CB_ONLY = 2
+# neither party learns b, each keeps an authenticated share of it
+OUTPUT_SHARED = 3
+
+
+class CFMError(ValueError):
+    """Failure reported by the Rust library, `status` is the CFMStatus code."""
+
+    def __init__(self, status, message):
+        super().__init__(f"{message} (status {status})")
+        self.status = status
+        self.message = message
+
+
+class CFMInputError(CFMError):
+    """The call was made with invalid input."""
+
+
+class CFMCheatingError(CFMError):
+    """The other party cheated, abort the protocol and ban it."""
+
+
+class CFMDuplicateMessageError(CFMError):
+    """The message repeats an accepted round, the session is unchanged."""
+
+
+def check_status(status):
+    """Raises the CFMError matching `status` with the last error message."""
+    if status == CFM_STATUS_OK:
+        return
+    message = rust_lib.cfm_last_error_message()
+    message = message.decode("utf-8", "replace") if message else "unknown error"
+    if status in CFM_CHEATING_STATUS:
+        raise CFMCheatingError(status, message)
+    if status in CFM_INPUT_STATUS:
+        raise CFMInputError(status, message)
+    if status == CFM_DUPLICATE_MESSAGE_STATUS:
+        raise CFMDuplicateMessageError(status, message)
+    raise CFMError(status, message)
+
+
 # Define function signatures for Rust FFI calls
 rust_lib.create_rng.argtypes = []
 rust_lib.create_rng.restype = ctypes.c_void_p  # Returns an opaque pointer to RngHolder
 
-rust_lib.free_rng.argtypes = [ctypes.POINTER(ctypes.c_void_p)]
+rust_lib.create_seeded_rng.argtypes = [ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t, ctypes.POINTER(ctypes.c_void_p)]
+rust_lib.create_seeded_rng.restype = ctypes.c_int
+
+rust_lib.free_rng.argtypes = [ctypes.c_void_p]
 rust_lib.free_rng.restype = None
 
-rust_lib.generate_init_session_id.argtypes = [ctypes.c_void_p, ctypes.POINTER(ctypes.c_size_t)]
-rust_lib.generate_init_session_id.restype = ctypes.POINTER(ctypes.c_ubyte)
+rust_lib.generate_init_session_id.argtypes = [ctypes.c_void_p, ctypes.POINTER(ctypes.POINTER(ctypes.c_ubyte)), ctypes.POINTER(ctypes.c_size_t)]
+rust_lib.generate_init_session_id.restype = ctypes.c_int
 
-rust_lib.create_msg.argtypes = [ctypes.c_char_p, ctypes.POINTER(ctypes.c_size_t)]
-rust_lib.create_msg.restype = ctypes.POINTER(ctypes.c_ubyte)
+rust_lib.create_msg.argtypes = [ctypes.c_char_p, ctypes.POINTER(ctypes.POINTER(ctypes.c_ubyte)), ctypes.POINTER(ctypes.c_size_t)]
+rust_lib.create_msg.restype = ctypes.c_int
 
 rust_lib.ffi_cfm_init_create_msg1.argtypes = [
     ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # Session ID (serialized)
     ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # Msg1 (serialized)
     ctypes.c_void_p,  # RNG pointer
+    ctypes.POINTER(ctypes.POINTER(ctypes.c_ubyte)),  # Output state
     ctypes.POINTER(ctypes.c_size_t)  # Output state size
 ]
-rust_lib.ffi_cfm_init_create_msg1.restype = ctypes.POINTER(ctypes.c_ubyte)
+rust_lib.ffi_cfm_init_create_msg1.restype = ctypes.c_int
 
 rust_lib.ffi_cfm_init_process_msg1.argtypes = [
     ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # Session ID (serialized)
     ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # Msg1 (serialized)
     ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # Msg2 (serialized)
     ctypes.c_void_p,  # RNG pointer
+    ctypes.POINTER(ctypes.POINTER(ctypes.c_ubyte)),  # Output state
     ctypes.POINTER(ctypes.c_size_t)  # Output state size
 ]
-rust_lib.ffi_cfm_init_process_msg1.restype = ctypes.POINTER(ctypes.c_ubyte)
+rust_lib.ffi_cfm_init_process_msg1.restype = ctypes.c_int
 
 
 rust_lib.ffi_cfm_init_process_msg2.argtypes = [
     ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # state_ob (serialized)
     ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # msg2 (serialized)
     ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # msg3 (mutable, serialized)
-    ctypes.c_void_p, ctypes.POINTER(ctypes.c_size_t)  # RNG pointer, output size
+    ctypes.c_void_p,  # RNG pointer
+    ctypes.POINTER(ctypes.POINTER(ctypes.c_ubyte)), ctypes.POINTER(ctypes.c_size_t)  # output ot_seeds_ob and size
 ]
 
 # Define return type for ffi_cfm_init_process_msg2
-rust_lib.ffi_cfm_init_process_msg2.restype = ctypes.POINTER(ctypes.c_ubyte)
+rust_lib.ffi_cfm_init_process_msg2.restype = ctypes.c_int
 
 
 # Define argument types for ffi_cfm_init_process_msg3
 rust_lib.ffi_cfm_init_process_msg3.argtypes = [
     ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # state_cb (serialized)
     ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # msg3 (serialized)
+    ctypes.POINTER(ctypes.POINTER(ctypes.c_ubyte)),  # Output ot_seeds_cb
     ctypes.POINTER(ctypes.c_size_t)                   # Output ot_seeds_cb size
 ]
 
 # Define return type for ffi_cfm_init_process_msg3
-rust_lib.ffi_cfm_init_process_msg3.restype = ctypes.POINTER(ctypes.c_ubyte)
+rust_lib.ffi_cfm_init_process_msg3.restype = ctypes.c_int
 
 
 class FFI_AbtCreateMsg1Result(ctypes.Structure):
     _fields_ = [
+        ("status", ctypes.c_int),
         ("state_cb_r1_ptr", ctypes.POINTER(ctypes.c_ubyte)),
         ("state_cb_r1_size", ctypes.c_size_t),
         ("msg1_ptr", ctypes.POINTER(ctypes.c_ubyte)),
@@ -78,6 +158,7 @@ rust_lib.ffi_abt_create_msg1.restype = FFI_AbtCreateMsg1Result
 
 class FFI_AbtProcessMsg1Result(ctypes.Structure):
     _fields_ = [
+        ("status", ctypes.c_int),
         ("state_ob_r1_ptr", ctypes.POINTER(ctypes.c_ubyte)),
         ("state_ob_r1_size", ctypes.c_size_t),
         ("shares_ob_ptr", ctypes.POINTER(ctypes.c_ubyte)),
@@ -100,6 +181,7 @@ rust_lib.ffi_abt_process_msg1.restype = FFI_AbtProcessMsg1Result
 # Define the FFI result structure
 class FFI_AbtProcessMsg2Result(ctypes.Structure):
     _fields_ = [
+        ("status", ctypes.c_int),
         ("state_cb_r2_ptr", ctypes.POINTER(ctypes.c_ubyte)),
         ("state_cb_r2_size", ctypes.c_size_t),
         ("shares_cb_ptr", ctypes.POINTER(ctypes.c_ubyte)),
@@ -122,6 +204,7 @@ rust_lib.ffi_abt_process_msg2.restype = FFI_AbtProcessMsg2Result
 
 class FFI_AbtProcessMsg3Result(ctypes.Structure):
     _fields_ = [
+        ("status", ctypes.c_int),
         ("state_ob_r2_ptr", ctypes.POINTER(ctypes.c_ubyte)),
         ("state_ob_r2_size", ctypes.c_size_t),
         ("shares_ob_ptr", ctypes.POINTER(ctypes.c_ubyte)),
@@ -143,6 +226,7 @@ rust_lib.ffi_abt_process_msg3.restype = FFI_AbtProcessMsg3Result
 
 class FFI_AbtProcessMsg4Result(ctypes.Structure):
     _fields_ = [
+        ("status", ctypes.c_int),
         ("msg5_ptr", ctypes.POINTER(ctypes.c_ubyte)),
         ("msg5_size", ctypes.c_size_t),
     ]
@@ -161,16 +245,17 @@ rust_lib.ffi_abt_process_msg5.argtypes = [
     ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # state_ob_r2
     ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # msg5
 ]
-rust_lib.ffi_abt_process_msg5.restype = None  # No return value
+rust_lib.ffi_abt_process_msg5.restype = ctypes.c_int
 
 
 # Define function signatures
-rust_lib.ffi_hash_customers.argtypes = [ctypes.c_char_p, ctypes.c_bool, ctypes.POINTER(ctypes.c_size_t)]
-rust_lib.ffi_hash_customers.restype = ctypes.POINTER(ctypes.c_ubyte)
+rust_lib.ffi_hash_customers.argtypes = [ctypes.c_char_p, ctypes.c_bool, ctypes.POINTER(ctypes.POINTER(ctypes.c_ubyte)), ctypes.POINTER(ctypes.c_size_t)]
+rust_lib.ffi_hash_customers.restype = ctypes.c_int
 
 
 class FFI_CfmCreateMsg1Result(ctypes.Structure):
     _fields_ = [
+        ("status", ctypes.c_int),
         ("cfm_state_ob_r1_ptr", ctypes.POINTER(ctypes.c_ubyte)),
         ("cfm_state_ob_r1_size", ctypes.c_size_t),
         ("msg1_ptr", ctypes.POINTER(ctypes.c_ubyte)),
@@ -180,8 +265,10 @@ class FFI_CfmCreateMsg1Result(ctypes.Structure):
 # Define function argument types for the Rust function
 rust_lib.ffi_cfm_create_msg1.argtypes = [
     ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # session_id
-    ctypes.c_uint64, ctypes.c_uint64,  # big_l, big_x
+    ctypes.c_uint8,  # output_policy
+    ctypes.c_uint64, ctypes.c_bool, ctypes.c_uint64,  # big_l, private_limit, big_x
     ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # customer_y_bytes
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # ot_seeds_ob
     ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # shares_ob (mutable)
     ctypes.c_void_p,  # rng_ptr
 ]
@@ -192,6 +279,7 @@ rust_lib.ffi_cfm_create_msg1.restype = FFI_CfmCreateMsg1Result
 
 class FFI_CfmProcessMsg1Result(ctypes.Structure):
     _fields_ = [
+        ("status", ctypes.c_int),
         ("cfm_state_cb_r1_ptr", ctypes.POINTER(ctypes.c_ubyte)),
         ("cfm_state_cb_r1_size", ctypes.c_size_t),
         ("msg2_ptr", ctypes.POINTER(ctypes.c_ubyte)),
@@ -200,9 +288,12 @@ class FFI_CfmProcessMsg1Result(ctypes.Structure):
 
 rust_lib.ffi_cfm_process_msg1.argtypes = [
     ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # session_id
-    ctypes.c_uint64,  # big_l
+    ctypes.c_uint8,  # output_policy
+    ctypes.c_uint64, ctypes.c_bool,  # big_l, private_limit
     ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # big_y_bytes
     ctypes.POINTER(ctypes.c_uint64), ctypes.c_size_t,  # big_z
+    ctypes.POINTER(ctypes.c_uint64), ctypes.c_size_t,  # big_l_y, NULL if all have limit big_l
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # ot_seeds_cb
     ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # shares_cb
     ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # msg1
     ctypes.c_void_p,  # rng_ptr
@@ -212,6 +303,7 @@ rust_lib.ffi_cfm_process_msg1.restype = FFI_CfmProcessMsg1Result
 
 class FFI_CfmProcessMsg2Result(ctypes.Structure):
     _fields_ = [
+        ("status", ctypes.c_int),
         ("cfm_state_ob_r2_ptr", ctypes.POINTER(ctypes.c_ubyte)),
         ("cfm_state_ob_r2_size", ctypes.c_size_t),
         ("msg3_ptr", ctypes.POINTER(ctypes.c_ubyte)),
@@ -231,6 +323,7 @@ rust_lib.ffi_cfm_process_msg2.restype = FFI_CfmProcessMsg2Result  # Struct retur
 
 class FFI_CfmProcessMsg3Result(ctypes.Structure):
     _fields_ = [
+        ("status", ctypes.c_int),
         ("cfm_state_cb_r2_ptr", ctypes.POINTER(ctypes.c_ubyte)),
         ("cfm_state_cb_r2_size", ctypes.c_size_t),
         ("msg4_ptr", ctypes.POINTER(ctypes.c_ubyte)),
@@ -249,6 +342,7 @@ rust_lib.ffi_cfm_process_msg3.restype = FFI_CfmProcessMsg3Result
 
 class FFI_CfmProcessMsg4Result(ctypes.Structure):
     _fields_ = [
+        ("status", ctypes.c_int),
         ("cfm_state_ob_r3_ptr", ctypes.POINTER(ctypes.c_ubyte)),
         ("cfm_state_ob_r3_size", ctypes.c_size_t),
         ("msg5_ptr", ctypes.POINTER(ctypes.c_ubyte)),
@@ -265,6 +359,7 @@ rust_lib.ffi_cfm_process_msg4.restype = FFI_CfmProcessMsg4Result
 
 class FFI_CfmProcessMsg5Result(ctypes.Structure):
     _fields_ = [
+        ("status", ctypes.c_int),
         ("cfm_state_cb_r3_ptr", ctypes.POINTER(ctypes.c_ubyte)),
         ("cfm_state_cb_r3_size", ctypes.c_size_t),
         ("msg6_ptr", ctypes.POINTER(ctypes.c_ubyte)),
@@ -282,6 +377,7 @@ rust_lib.ffi_cfm_process_msg5.restype = FFI_CfmProcessMsg5Result
 
 class FFI_CfmProcessMsg6Result(ctypes.Structure):
     _fields_ = [
+        ("status", ctypes.c_int),
         ("cfm_state_ob_r4_ptr", ctypes.POINTER(ctypes.c_ubyte)),
         ("cfm_state_ob_r4_size", ctypes.c_size_t),
         ("msg7_ptr", ctypes.POINTER(ctypes.c_ubyte)),
@@ -299,6 +395,7 @@ rust_lib.ffi_cfm_process_msg6.restype = FFI_CfmProcessMsg6Result
 
 class FFI_CfmProcessMsg7Result(ctypes.Structure):
     _fields_ = [
+        ("status", ctypes.c_int),
         ("cfm_state_cb_r4_ptr", ctypes.POINTER(ctypes.c_ubyte)),
         ("cfm_state_cb_r4_size", ctypes.c_size_t),
         ("msg8_ptr", ctypes.POINTER(ctypes.c_ubyte)),
@@ -316,6 +413,7 @@ rust_lib.ffi_cfm_process_msg7.restype = FFI_CfmProcessMsg7Result
 
 class FFI_CfmProcessMsg8Result(ctypes.Structure):
     _fields_ = [
+        ("status", ctypes.c_int),
         ("cfm_state_ob_r5_ptr", ctypes.POINTER(ctypes.c_ubyte)),
         ("cfm_state_ob_r5_size", ctypes.c_size_t),
         ("msg9_ptr", ctypes.POINTER(ctypes.c_ubyte)),
@@ -333,6 +431,7 @@ rust_lib.ffi_cfm_process_msg8.restype = FFI_CfmProcessMsg8Result
 
 class FFI_CfmProcessMsg9Result(ctypes.Structure):
     _fields_ = [
+        ("status", ctypes.c_int),
         ("cfm_state_cb_r5_ptr", ctypes.POINTER(ctypes.c_ubyte)),
         ("cfm_state_cb_r5_size", ctypes.c_size_t),
         ("msg10_ptr", ctypes.POINTER(ctypes.c_ubyte)),
@@ -350,6 +449,7 @@ rust_lib.ffi_cfm_process_msg9.restype = FFI_CfmProcessMsg9Result
 
 class FFI_CfmProcessMsg10Result(ctypes.Structure):
     _fields_ = [
+        ("status", ctypes.c_int),
         ("cfm_state_ob_r6_ptr", ctypes.POINTER(ctypes.c_ubyte)),
         ("cfm_state_ob_r6_size", ctypes.c_size_t),
         ("msg11_ptr", ctypes.POINTER(ctypes.c_ubyte)),
@@ -367,6 +467,7 @@ rust_lib.ffi_cfm_process_msg10.restype = FFI_CfmProcessMsg10Result
 
 class FFI_CfmProcessMsg11Result(ctypes.Structure):
     _fields_ = [
+        ("status", ctypes.c_int),
         ("cfm_state_cb_r6_ptr", ctypes.POINTER(ctypes.c_ubyte)),
         ("cfm_state_cb_r6_size", ctypes.c_size_t),
         ("msg12_ptr", ctypes.POINTER(ctypes.c_ubyte)),
@@ -383,6 +484,7 @@ rust_lib.ffi_cfm_process_msg11.restype = FFI_CfmProcessMsg11Result
 
 class FFI_CfmProcessMsg12Result(ctypes.Structure):
     _fields_ = [
+        ("status", ctypes.c_int),
         ("cfm_state_ob_r7_ptr", ctypes.POINTER(ctypes.c_ubyte)),
         ("cfm_state_ob_r7_size", ctypes.c_size_t),
         ("msg13_ptr", ctypes.POINTER(ctypes.c_ubyte)),
@@ -400,6 +502,7 @@ rust_lib.ffi_cfm_process_msg12.restype = FFI_CfmProcessMsg12Result
 
 class FFI_CfmProcessMsg13Result(ctypes.Structure):
     _fields_ = [
+        ("status", ctypes.c_int),
         ("cfm_state_cb_r7_ptr", ctypes.POINTER(ctypes.c_ubyte)),
         ("cfm_state_cb_r7_size", ctypes.c_size_t),
         ("msg14_ptr", ctypes.POINTER(ctypes.c_ubyte)),
@@ -417,6 +520,7 @@ rust_lib.ffi_cfm_process_msg13.restype = FFI_CfmProcessMsg13Result
 
 class FFI_CfmProcessMsg14Result(ctypes.Structure):
     _fields_ = [
+        ("status", ctypes.c_int),
         ("cfm_state_ob_r8_ptr", ctypes.POINTER(ctypes.c_ubyte)),
         ("cfm_state_ob_r8_size", ctypes.c_size_t),
         ("msg15_ptr", ctypes.POINTER(ctypes.c_ubyte)),
@@ -433,6 +537,7 @@ rust_lib.ffi_cfm_process_msg14.restype = FFI_CfmProcessMsg14Result
 
 class FFI_CfmProcessMsg15Result(ctypes.Structure):
     _fields_ = [
+        ("status", ctypes.c_int),
         ("cfm_state_cb_r8_ptr", ctypes.POINTER(ctypes.c_ubyte)),
         ("cfm_state_cb_r8_size", ctypes.c_size_t),
         ("msg16_ptr", ctypes.POINTER(ctypes.c_ubyte)),
@@ -448,6 +553,7 @@ rust_lib.ffi_cfm_process_msg15.restype = FFI_CfmProcessMsg15Result
 
 class FFI_CfmProcessMsg16Result(ctypes.Structure):
     _fields_ = [
+        ("status", ctypes.c_int),
         ("cfm_state_ob_r9_ptr", ctypes.POINTER(ctypes.c_ubyte)),
         ("cfm_state_ob_r9_size", ctypes.c_size_t),
         ("msg17_ptr", ctypes.POINTER(ctypes.c_ubyte)),
@@ -464,6 +570,7 @@ rust_lib.ffi_cfm_process_msg16.restype = FFI_CfmProcessMsg16Result
 
 class FFI_CfmProcessMsg17Result(ctypes.Structure):
     _fields_ = [
+        ("status", ctypes.c_int),
         ("b_cb_value_ptr", ctypes.POINTER(ctypes.c_ubyte)),
         ("b_cb_value_size", ctypes.c_size_t),
         ("msg18_ptr", ctypes.POINTER(ctypes.c_ubyte)),
@@ -480,6 +587,7 @@ rust_lib.ffi_cfm_process_msg17.restype = FFI_CfmProcessMsg17Result
 
 class FFI_CfmProcessMsg18Result(ctypes.Structure):
     _fields_ = [
+        ("status", ctypes.c_int),
         ("b_ob_value_ptr", ctypes.POINTER(ctypes.c_ubyte)),
         ("b_ob_value_size", ctypes.c_size_t),
     ]
@@ -492,6 +600,193 @@ rust_lib.ffi_cfm_process_msg18.argtypes = [
 rust_lib.ffi_cfm_process_msg18.restype = FFI_CfmProcessMsg18Result
 
 
+class FFI_SessionResult(ctypes.Structure):
+    _fields_ = [
+        ("status", ctypes.c_int),
+        ("msg_ptr", ctypes.POINTER(ctypes.c_ubyte)),
+        ("msg_size", ctypes.c_size_t),
+    ]
+
+rust_lib.ffi_session_new.argtypes = [ctypes.c_uint8, ctypes.POINTER(ctypes.c_void_p)]  # role: 1 = OB, 2 = CB
+rust_lib.ffi_session_new.restype = ctypes.c_int
+
+rust_lib.ffi_session_free.argtypes = [ctypes.c_void_p]
+rust_lib.ffi_session_free.restype = None
+
+rust_lib.ffi_session_start_init.argtypes = [
+    ctypes.c_void_p,  # handle
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # session_id
+    ctypes.c_void_p,  # rng_ptr
+]
+rust_lib.ffi_session_start_init.restype = FFI_SessionResult
+
+rust_lib.ffi_session_start_abt.argtypes = [
+    ctypes.c_void_p,  # handle
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # session_id
+    ctypes.c_void_p,  # rng_ptr
+]
+rust_lib.ffi_session_start_abt.restype = FFI_SessionResult
+
+rust_lib.ffi_session_start_next_abt.argtypes = [ctypes.c_void_p, ctypes.c_void_p]  # handle, rng_ptr
+rust_lib.ffi_session_start_next_abt.restype = FFI_SessionResult
+
+rust_lib.ffi_session_set_rotation_policy.argtypes = [
+    ctypes.c_void_p,  # handle
+    ctypes.c_uint64, ctypes.c_uint64,  # max_age_secs, max_abt_sessions
+]
+rust_lib.ffi_session_set_rotation_policy.restype = ctypes.c_int
+
+rust_lib.ffi_session_settle_transfer.argtypes = [ctypes.c_void_p, ctypes.c_bool]  # handle, approved
+rust_lib.ffi_session_settle_transfer.restype = ctypes.c_int
+
+rust_lib.ffi_session_start_cfm_ob.argtypes = [
+    ctypes.c_void_p,  # handle
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # session_id
+    ctypes.c_uint8,  # output_policy
+    ctypes.c_uint64, ctypes.c_bool, ctypes.c_uint64,  # big_l, private_limit, big_x
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # customer_y_bytes
+    ctypes.c_void_p,  # rng_ptr
+]
+rust_lib.ffi_session_start_cfm_ob.restype = FFI_SessionResult
+
+rust_lib.ffi_session_start_cfm_cb.argtypes = [
+    ctypes.c_void_p,  # handle
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # session_id
+    ctypes.c_uint8,  # output_policy
+    ctypes.c_uint64, ctypes.c_bool,  # big_l, private_limit
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # big_y_bytes
+    ctypes.POINTER(ctypes.c_uint64), ctypes.c_size_t,  # big_z
+    ctypes.POINTER(ctypes.c_uint64), ctypes.c_size_t,  # big_l_y, NULL if all have limit big_l
+]
+rust_lib.ffi_session_start_cfm_cb.restype = FFI_SessionResult
+
+rust_lib.ffi_session_step.argtypes = [
+    ctypes.c_void_p,  # handle
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # envelope encoded message
+    ctypes.c_void_p,  # rng_ptr
+]
+rust_lib.ffi_session_step.restype = FFI_SessionResult
+
+rust_lib.ffi_session_step_signed.argtypes = [
+    ctypes.c_void_p,  # handle
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # envelope encoded message
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # signature of the other party
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # 32 byte signing key
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # 32 byte verifying key of the other party
+    ctypes.c_void_p,  # rng_ptr
+]
+rust_lib.ffi_session_step_signed.restype = FFI_SessionResult
+
+rust_lib.ffi_session_start_fair_release.argtypes = [
+    ctypes.c_void_p,  # handle
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # 32 byte signing key
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # 32 byte verifying key of the other party
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # 32 byte verifying key of the arbiter
+    ctypes.c_void_p,  # rng_ptr
+]
+rust_lib.ffi_session_start_fair_release.restype = FFI_SessionResult
+
+rust_lib.ffi_session_fair_evidence.argtypes = [
+    ctypes.c_void_p,  # handle
+    ctypes.POINTER(ctypes.POINTER(ctypes.c_ubyte)), ctypes.POINTER(ctypes.c_size_t),  # evidence
+]
+rust_lib.ffi_session_fair_evidence.restype = ctypes.c_int
+
+rust_lib.ffi_arbiter_resolve.argtypes = [
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # 32 byte signing key of the arbiter
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # CFM session id
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # 32 byte verifying key of OB
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # 32 byte verifying key of CB
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # evidence
+    ctypes.POINTER(ctypes.POINTER(ctypes.c_ubyte)), ctypes.POINTER(ctypes.c_size_t),  # resolution
+]
+rust_lib.ffi_arbiter_resolve.restype = ctypes.c_int
+
+rust_lib.ffi_session_resolve_fair_release.argtypes = [
+    ctypes.c_void_p,  # handle
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # resolution
+]
+rust_lib.ffi_session_resolve_fair_release.restype = ctypes.c_int
+
+rust_lib.ffi_session_sign_outbound.argtypes = [
+    ctypes.c_void_p,  # handle
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # 32 byte signing key
+    ctypes.c_void_p,  # rng_ptr
+    ctypes.POINTER(ctypes.POINTER(ctypes.c_ubyte)), ctypes.POINTER(ctypes.c_size_t),  # signature
+]
+rust_lib.ffi_session_sign_outbound.restype = ctypes.c_int
+
+rust_lib.ffi_session_result.argtypes = [ctypes.c_void_p]
+rust_lib.ffi_session_result.restype = ctypes.c_int32
+
+rust_lib.ffi_session_output.argtypes = [
+    ctypes.c_void_p,  # handle
+    ctypes.POINTER(ctypes.POINTER(ctypes.c_ubyte)), ctypes.POINTER(ctypes.c_size_t),  # output
+]
+rust_lib.ffi_session_output.restype = ctypes.c_int
+
+rust_lib.ffi_session_last_outbound.argtypes = [ctypes.c_void_p]
+rust_lib.ffi_session_last_outbound.restype = FFI_SessionResult
+
+rust_lib.ffi_session_snapshot.argtypes = [
+    ctypes.c_void_p,  # handle
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # 32 byte key
+    ctypes.c_void_p,  # rng_ptr
+    ctypes.POINTER(ctypes.POINTER(ctypes.c_ubyte)), ctypes.POINTER(ctypes.c_size_t),  # snapshot
+]
+rust_lib.ffi_session_snapshot.restype = ctypes.c_int
+
+rust_lib.ffi_session_restore.argtypes = [
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # snapshot
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # 32 byte key
+    ctypes.POINTER(ctypes.c_void_p),  # out_handle
+]
+rust_lib.ffi_session_restore.restype = ctypes.c_int
+
+rust_lib.ffi_verifying_key.argtypes = [
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # 32 byte signing key
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # 32 byte verifying key out
+]
+rust_lib.ffi_verifying_key.restype = ctypes.c_int
+
+rust_lib.ffi_session_abort_report.argtypes = [
+    ctypes.c_void_p,  # handle
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # 32 byte signing key
+    ctypes.c_void_p,  # rng_ptr
+    ctypes.POINTER(ctypes.POINTER(ctypes.c_ubyte)), ctypes.POINTER(ctypes.c_size_t),  # report
+]
+rust_lib.ffi_session_abort_report.restype = ctypes.c_int
+
+rust_lib.ffi_abort_report_verify.argtypes = [
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # signed report
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # 32 byte verifying key
+]
+rust_lib.ffi_abort_report_verify.restype = ctypes.c_int
+
+rust_lib.ffi_session_sign_receipt.argtypes = [
+    ctypes.c_void_p,  # handle
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # 32 byte signing key
+    ctypes.c_void_p,  # rng_ptr
+    ctypes.POINTER(ctypes.POINTER(ctypes.c_ubyte)), ctypes.POINTER(ctypes.c_size_t),  # signature
+]
+rust_lib.ffi_session_sign_receipt.restype = ctypes.c_int
+
+rust_lib.ffi_session_signed_receipt.argtypes = [
+    ctypes.c_void_p,  # handle
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # own signature
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # signature of the other party
+    ctypes.POINTER(ctypes.POINTER(ctypes.c_ubyte)), ctypes.POINTER(ctypes.c_size_t),  # receipt
+]
+rust_lib.ffi_session_signed_receipt.restype = ctypes.c_int
+
+rust_lib.ffi_receipt_verify.argtypes = [
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # signed receipt
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # 32 byte verifying key of OB
+    ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t,  # 32 byte verifying key of CB
+]
+rust_lib.ffi_receipt_verify.restype = ctypes.c_int
+
+
 rust_lib.free_buffer.argtypes = [ctypes.POINTER(ctypes.c_ubyte), ctypes.c_size_t]
 rust_lib.free_buffer.restype = None
 
@@ -505,6 +800,13 @@ def create_rng():
         raise ValueError("Failed to create RNG in Rust.")
     return rng_ptr
 
+def create_seeded_rng(seed):
+    """Creates a deterministic ChaCha20 RNG from a 32 byte seed, to replay a transcript."""
+    seed_buf = (ctypes.c_ubyte * len(seed)).from_buffer_copy(seed)
+    rng_ptr = ctypes.c_void_p()
+    check_status(rust_lib.create_seeded_rng(seed_buf, len(seed), ctypes.byref(rng_ptr)))
+    return rng_ptr.value
+
 def free_rng(rng_ptr):
     if rng_ptr:
         rust_lib.free_rng(rng_ptr)
@@ -514,10 +816,9 @@ def generate_init_session_id(rng_ptr):
     """Generates a serialized session ID using Rust."""
     size_var = ctypes.c_size_t()
 
-    session_id_ptr = rust_lib.generate_init_session_id(rng_ptr, ctypes.byref(size_var))
+    session_id_ptr = ctypes.POINTER(ctypes.c_ubyte)()
 
-    if not session_id_ptr:
-        raise ValueError("Failed to generate session ID in Rust.")
+    check_status(rust_lib.generate_init_session_id(rng_ptr, ctypes.byref(session_id_ptr), ctypes.byref(size_var)))
 
     size = size_var.value
     serialized_session_id = ctypes.string_at(session_id_ptr, size)
@@ -534,10 +835,8 @@ def create_msg(msg_type: str):
     """Creates a serialized Msg1 using Rust."""
     msg_type = msg_type.encode("utf-8")
     size_var = ctypes.c_size_t()
-    msg1_ptr = rust_lib.create_msg(ctypes.c_char_p(msg_type), ctypes.byref(size_var))
-
-    if not msg1_ptr:
-        raise ValueError("Failed to create Msg1 in Rust.")
+    msg1_ptr = ctypes.POINTER(ctypes.c_ubyte)()
+    check_status(rust_lib.create_msg(ctypes.c_char_p(msg_type), ctypes.byref(msg1_ptr), ctypes.byref(size_var)))
 
     size = size_var.value
     serialized_msg1 = ctypes.string_at(msg1_ptr, size)
@@ -564,17 +863,16 @@ def ffi_cfm_init_create_msg1(session_id, msg1_serialized, rng_ptr):
     session_id_ptr = ctypes.cast(session_id_array, ctypes.POINTER(ctypes.c_ubyte))
     msg1_ptr = ctypes.cast(msg1_buffer, ctypes.POINTER(ctypes.c_ubyte))  # Mutable buffer
 
+    state_ptr = ctypes.POINTER(ctypes.c_ubyte)()
     state_size = ctypes.c_size_t()
 
     # Call Rust FFI function
-    state_ptr = rust_lib.ffi_cfm_init_create_msg1(
+    status = rust_lib.ffi_cfm_init_create_msg1(
         session_id_ptr, len(session_id),
         msg1_ptr, len(msg1_serialized),  # Mutable msg1
-        rng_ptr, ctypes.byref(state_size)
+        rng_ptr, ctypes.byref(state_ptr), ctypes.byref(state_size)
     )
-
-    if not state_ptr:
-        raise ValueError("Failed to generate state_ob in Rust.")
+    check_status(status)
 
     # Read serialized state_ob
     state_ob_serialized = ctypes.string_at(state_ptr, state_size.value)
@@ -603,18 +901,17 @@ def ffi_cfm_init_process_msg1(session_id, msg1_serialized, msg2_serialized, rng_
     msg1_ptr = ctypes.cast(msg1_array, ctypes.POINTER(ctypes.c_ubyte))
     msg2_ptr = ctypes.cast(msg2_array, ctypes.POINTER(ctypes.c_ubyte))
 
+    state_ptr = ctypes.POINTER(ctypes.c_ubyte)()
     state_size = ctypes.c_size_t()
 
     # Call Rust function
-    state_ptr = rust_lib.ffi_cfm_init_process_msg1(
+    status = rust_lib.ffi_cfm_init_process_msg1(
         session_id_ptr, len(session_id),
         msg1_ptr, len(msg1_serialized),
         msg2_ptr, len(msg2_serialized),
-        rng_ptr, ctypes.byref(state_size)
+        rng_ptr, ctypes.byref(state_ptr), ctypes.byref(state_size)
     )
-
-    if not state_ptr:
-        raise ValueError("Failed to generate state_cb in Rust.")
+    check_status(status)
 
     # Read serialized state_cb
     state_cb_serialized = ctypes.string_at(state_ptr, state_size.value)
@@ -643,18 +940,17 @@ def ffi_cfm_init_process_msg2(state_ob, msg2_serialized, msg3_serialized, rng_pt
     msg2_ptr = ctypes.cast(msg2_array, ctypes.POINTER(ctypes.c_ubyte))
     msg3_ptr = ctypes.cast(msg3_array, ctypes.POINTER(ctypes.c_ubyte))
 
+    ot_seeds_ptr = ctypes.POINTER(ctypes.c_ubyte)()
     ot_seeds_size = ctypes.c_size_t()
 
     # Call Rust function
-    ot_seeds_ptr = rust_lib.ffi_cfm_init_process_msg2(
+    status = rust_lib.ffi_cfm_init_process_msg2(
         state_ob_ptr, len(state_ob),
         msg2_ptr, len(msg2_serialized),
         msg3_ptr, len(msg3_serialized),
-        rng_ptr, ctypes.byref(ot_seeds_size)
+        rng_ptr, ctypes.byref(ot_seeds_ptr), ctypes.byref(ot_seeds_size)
     )
-
-    if not ot_seeds_ptr:
-        raise ValueError("Failed to generate ot_seeds_ob in Rust.")
+    check_status(status)
 
     # Read serialized ot_seeds_ob
     ot_seeds_ob_serialized = ctypes.string_at(ot_seeds_ptr, ot_seeds_size.value)
@@ -681,16 +977,15 @@ def ffi_cfm_init_process_msg3(state_cb, msg3_serialized):
     state_cb_ptr = ctypes.cast(state_cb_array, ctypes.POINTER(ctypes.c_ubyte))
     msg3_ptr = ctypes.cast(msg3_array, ctypes.POINTER(ctypes.c_ubyte))
 
+    ot_seeds_ptr = ctypes.POINTER(ctypes.c_ubyte)()
     ot_seeds_size = ctypes.c_size_t()
 
-    ot_seeds_ptr = rust_lib.ffi_cfm_init_process_msg3(
+    status = rust_lib.ffi_cfm_init_process_msg3(
         state_cb_ptr, len(state_cb),
         msg3_ptr, len(msg3_serialized),
-        ctypes.byref(ot_seeds_size)
+        ctypes.byref(ot_seeds_ptr), ctypes.byref(ot_seeds_size)
     )
-
-    if not ot_seeds_ptr:
-        raise ValueError("Failed to generate ot_seeds_cb in Rust.")
+    check_status(status)
 
     # Read serialized ot_seeds_cb
     ot_seeds_cb_serialized = ctypes.string_at(ot_seeds_ptr, ot_seeds_size.value)
@@ -713,6 +1008,7 @@ def ffi_abt_create_msg1(session_id, ot_seeds_cb_serialized, rng_ptr):
         ot_seeds_cb_array, len(ot_seeds_cb_serialized),
         rng_ptr
     )
+    check_status(result.status)
 
     state_cb_r1_serialized = ctypes.string_at(result.state_cb_r1_ptr, result.state_cb_r1_size)
     msg1_serialized = ctypes.string_at(result.msg1_ptr, result.msg1_size)
@@ -750,8 +1046,7 @@ def ffi_abt_process_msg1(session_id, ot_seeds_ob, msg1_serialized, rng_ptr):
         rng_ptr
     )
 
-    if not result.state_ob_r1_ptr or result.state_ob_r1_size == 0:
-        raise ValueError("Failed to generate state_ob_r1.")
+    check_status(result.status)
 
     # Extract and read serialized data from Rust
     state_ob_r1_serialized = ctypes.string_at(result.state_ob_r1_ptr, result.state_ob_r1_size)
@@ -792,8 +1087,7 @@ def ffi_abt_process_msg2(state_cb_r1, ot_seeds_cb, msg2_serialized, rng_ptr):
         rng_ptr
     )
 
-    if not result.state_cb_r2_ptr:
-        raise ValueError("Failed to generate state_cb_r2.")
+    check_status(result.status)
 
     # Read serialized results
     state_cb_r2_serialized = ctypes.string_at(result.state_cb_r2_ptr, result.state_cb_r2_size)
@@ -836,8 +1130,7 @@ def ffi_abt_process_msg3(state_ob_r1, shares_ob, auth_triples_ob, msg3_serialize
         msg3_ptr, len(msg3_serialized)
     )
 
-    if not result.state_ob_r2_ptr:
-        raise ValueError("Failed to process abt_process_msg3 in Rust.")
+    check_status(result.status)
 
     # Read serialized outputs
     state_ob_r2_serialized = ctypes.string_at(result.state_ob_r2_ptr, result.state_ob_r2_size)
@@ -876,8 +1169,7 @@ def ffi_abt_process_msg4(state_cb_r2, auth_triples_cb, msg4_serialized):
         msg4_ptr, len(msg4_serialized)
     )
 
-    if not result.msg5_ptr:
-        raise ValueError("Failed to process abt_process_msg4 in Rust.")
+    check_status(result.status)
 
     # Read serialized msg5
     msg5_serialized = ctypes.string_at(result.msg5_ptr, result.msg5_size)
@@ -901,8 +1193,8 @@ def ffi_abt_process_msg5(state_ob_r2, msg5_serialized):
     state_ob_r2_ptr = ctypes.cast(state_ob_r2_array, ctypes.POINTER(ctypes.c_ubyte))
     msg5_ptr = ctypes.cast(msg5_array, ctypes.POINTER(ctypes.c_ubyte))
 
-    # Call Rust function (no return value)
-    rust_lib.ffi_abt_process_msg5(state_ob_r2_ptr, len(state_ob_r2), msg5_ptr, len(msg5_serialized))
+    # Call Rust function, raises CFMCheatingError if the MAC check fails
+    check_status(rust_lib.ffi_abt_process_msg5(state_ob_r2_ptr, len(state_ob_r2), msg5_ptr, len(msg5_serialized)))
 
 
 def hash_customers(customers, is_list):
@@ -911,14 +1203,15 @@ def hash_customers(customers, is_list):
     # Convert input data to JSON string
     customers_json = json.dumps(customers).encode('utf-8')
 
-    # Allocate memory for output size
+    # Allocate memory for output pointer and size
+    hash_ptr = ctypes.POINTER(ctypes.c_ubyte)()
     out_size = ctypes.c_size_t()
 
     # Call Rust function
-    hash_ptr = rust_lib.ffi_hash_customers(ctypes.c_char_p(customers_json), is_list, ctypes.byref(out_size))
-
-    if not hash_ptr:
-        raise ValueError("Failed to hash customer(s) in Rust.")
+    status = rust_lib.ffi_hash_customers(
+        ctypes.c_char_p(customers_json), is_list, ctypes.byref(hash_ptr), ctypes.byref(out_size)
+    )
+    check_status(status)
 
     # Read the binary hash output
     hashed_data = ctypes.string_at(hash_ptr, out_size.value)
@@ -929,15 +1222,17 @@ def hash_customers(customers, is_list):
     return hashed_data
 
 
-def ffi_cfm_create_msg1(session_id, big_l, big_x, customer_y_bytes, shares_ob_serialized, rng_ptr):
-    """Calls Rust function to process inputs and returns state_ob_r1 and msg1."""
+def ffi_cfm_create_msg1(session_id, big_l, big_x, customer_y_bytes, ot_seeds_ob, shares_ob_serialized, rng_ptr, output_policy=OUTPUT_BOTH):
+    """Calls Rust function to process inputs and returns state_ob_r1 and msg1.
+    `big_l` is None if L is a private input of CB. Runs on an empty ledger."""
 
-    if not session_id or not customer_y_bytes or not shares_ob_serialized or not rng_ptr:
+    if not session_id or not customer_y_bytes or not ot_seeds_ob or not shares_ob_serialized or not rng_ptr:
         raise ValueError("Invalid inputs to ffi_cfm_create_msg1.")
 
     # Convert Python bytes to ctypes-compatible format
     session_id_array = (ctypes.c_ubyte * len(session_id))(*session_id)
     customer_y_bytes_array = (ctypes.c_ubyte * len(customer_y_bytes))(*customer_y_bytes)
+    ot_seeds_ob_array = (ctypes.c_ubyte * len(ot_seeds_ob))(*ot_seeds_ob)
     
     # Allocate shares_ob as a mutable buffer
     shares_ob_array = (ctypes.c_ubyte * len(shares_ob_serialized))(*shares_ob_serialized)
@@ -950,15 +1245,16 @@ def ffi_cfm_create_msg1(session_id, big_l, big_x, customer_y_bytes, shares_ob_se
     # Call Rust function
     result = rust_lib.ffi_cfm_create_msg1(
         session_id_ptr, len(session_id),
-        big_l, big_x,
+        output_policy,
+        big_l or 0, big_l is None, big_x,
         customer_y_bytes_ptr, len(customer_y_bytes),
+        ot_seeds_ob_array, len(ot_seeds_ob),
         shares_ob_ptr, len(shares_ob_serialized),
         rng_ptr
     )
 
     # Check if we received valid pointers
-    if not result.cfm_state_ob_r1_ptr or not result.msg1_ptr:
-        raise ValueError("Failed to process ffi_cfm_create_msg1 in Rust.")
+    check_status(result.status)
 
     # Read serialized outputs
     cfm_state_ob_r1_serialized = ctypes.string_at(result.cfm_state_ob_r1_ptr, result.cfm_state_ob_r1_size)
@@ -971,25 +1267,30 @@ def ffi_cfm_create_msg1(session_id, big_l, big_x, customer_y_bytes, shares_ob_se
     return cfm_state_ob_r1_serialized, msg1_serialized
 
 
-def ffi_cfm_process_msg1(session_id, big_l, big_y_bytes, big_z, shares_cb, msg1_serialized, rng_ptr):
-    if not session_id or not big_y_bytes or not big_z or not shares_cb or not msg1_serialized or not rng_ptr:
+def ffi_cfm_process_msg1(session_id, big_l, big_y_bytes, big_z, ot_seeds_cb, shares_cb, msg1_serialized, rng_ptr, output_policy=OUTPUT_BOTH, private_limit=False, big_l_y=None):
+    if not session_id or not big_y_bytes or not big_z or not ot_seeds_cb or not shares_cb or not msg1_serialized or not rng_ptr:
         raise ValueError("Invalid inputs to ffi_cfm_process_msg1.")
 
     session_id_array = (ctypes.c_ubyte * len(session_id))(*session_id)
     big_y_bytes_array = (ctypes.c_ubyte * len(big_y_bytes))(*big_y_bytes)
     big_z_array = (ctypes.c_uint64 * len(big_z))(*big_z)  # Convert Python list to C uint64 array
+    ot_seeds_cb_array = (ctypes.c_ubyte * len(ot_seeds_cb))(*ot_seeds_cb)
     shares_cb_array = (ctypes.c_ubyte * len(shares_cb))(*shares_cb)
     msg1_array = (ctypes.c_ubyte * len(msg1_serialized))(*msg1_serialized)
 
     result = rust_lib.ffi_cfm_process_msg1(
         session_id_array, len(session_id),
-        big_l,
+        output_policy,
+        big_l, private_limit,
         big_y_bytes_array, len(big_y_bytes),
         big_z_array, len(big_z),
+        *_limits_array(big_l_y),
+        ot_seeds_cb_array, len(ot_seeds_cb),
         shares_cb_array, len(shares_cb),
         msg1_array, len(msg1_serialized),
         rng_ptr
     )
+    check_status(result.status)
 
     state_cb_r1 = ctypes.string_at(result.cfm_state_cb_r1_ptr, result.cfm_state_cb_r1_size)
     msg2 = ctypes.string_at(result.msg2_ptr, result.msg2_size)
@@ -1027,8 +1328,7 @@ def ffi_cfm_process_msg2(cfm_state_ob_r1, shares_ob, auth_triples_ob, msg2_seria
         rng_ptr
     )
 
-    if not result.cfm_state_ob_r2_ptr or not result.msg3_ptr:
-        raise ValueError("Failed to process ffi_cfm_process_msg2 in Rust.")
+    check_status(result.status)
 
     # Read serialized outputs
     cfm_state_ob_r2_serialized = ctypes.string_at(result.cfm_state_ob_r2_ptr, result.cfm_state_ob_r2_size)
@@ -1067,8 +1367,7 @@ def ffi_cfm_process_msg3(cfm_state_cb_r1, shares_cb, auth_triples_cb, msg3_seria
         msg3_ptr, len(msg3_serialized)
     )
 
-    if not result.cfm_state_cb_r2_ptr or result.cfm_state_cb_r2_size == 0:
-        raise ValueError("Failed to process cfm_process_msg3 in Rust.")
+    check_status(result.status)
 
     # Read serialized outputs
     cfm_state_cb_r2_serialized = ctypes.string_at(result.cfm_state_cb_r2_ptr, result.cfm_state_cb_r2_size)
@@ -1101,8 +1400,7 @@ def ffi_cfm_process_msg4(cfm_state_ob_r2, msg4_serialized):
         msg4_ptr, len(msg4_serialized)
     )
 
-    if not result.cfm_state_ob_r3_ptr or result.cfm_state_ob_r3_size == 0:
-        raise ValueError("Failed to process cfm_process_msg4 in Rust.")
+    check_status(result.status)
 
     # Read serialized outputs
     cfm_state_ob_r3_serialized = ctypes.string_at(result.cfm_state_ob_r3_ptr, result.cfm_state_ob_r3_size)
@@ -1137,8 +1435,7 @@ def ffi_cfm_process_msg5(cfm_state_cb_r2, auth_triples_cb, msg5_serialized):
         msg5_ptr, len(msg5_serialized)
     )
 
-    if not result.cfm_state_cb_r3_ptr or not result.msg6_ptr:
-        raise ValueError("Failed to process cfm_process_msg5 in Rust.")
+    check_status(result.status)
 
     # Extract serialized outputs
     cfm_state_cb_r3_serialized = ctypes.string_at(result.cfm_state_cb_r3_ptr, result.cfm_state_cb_r3_size)
@@ -1174,8 +1471,7 @@ def ffi_cfm_process_msg6(cfm_state_ob_r3, auth_triples_ob, msg6_serialized):
         msg6_ptr, len(msg6_serialized)
     )
 
-    if not result.cfm_state_ob_r4_ptr:
-        raise ValueError("Failed to process cfm_process_msg6 in Rust.")
+    check_status(result.status)
 
     # Read serialized outputs
     cfm_state_ob_r4_serialized = ctypes.string_at(result.cfm_state_ob_r4_ptr, result.cfm_state_ob_r4_size)
@@ -1211,8 +1507,7 @@ def ffi_cfm_process_msg7(cfm_state_cb_r3, auth_triples_cb, msg7_serialized):
         msg7_ptr, len(msg7_serialized)
     )
 
-    if not result.cfm_state_cb_r4_ptr:
-        raise ValueError("Failed to process cfm_process_msg7 in Rust.")
+    check_status(result.status)
 
     # Read serialized outputs
     cfm_state_cb_r4_serialized = ctypes.string_at(result.cfm_state_cb_r4_ptr, result.cfm_state_cb_r4_size)
@@ -1248,8 +1543,7 @@ def ffi_cfm_process_msg8(cfm_state_ob_r4, auth_triples_ob, msg8_serialized):
         msg8_ptr, len(msg8_serialized)
     )
 
-    if not result.cfm_state_ob_r5_ptr:
-        raise ValueError("Failed to process cfm_process_msg8 in Rust.")
+    check_status(result.status)
 
     # Read serialized outputs
     cfm_state_ob_r5_serialized = ctypes.string_at(result.cfm_state_ob_r5_ptr, result.cfm_state_ob_r5_size)
@@ -1285,8 +1579,7 @@ def ffi_cfm_process_msg9(cfm_state_cb_r4, auth_triples_cb, msg9_serialized):
         msg9_ptr, len(msg9_serialized)
     )
 
-    if not result.cfm_state_cb_r5_ptr:
-        raise ValueError("Failed to process cfm_process_msg9 in Rust.")
+    check_status(result.status)
 
     # Read serialized outputs
     cfm_state_cb_r5_serialized = ctypes.string_at(result.cfm_state_cb_r5_ptr, result.cfm_state_cb_r5_size)
@@ -1321,8 +1614,7 @@ def ffi_cfm_process_msg10(cfm_state_ob_r5, auth_triples_ob, msg10_serialized):
         msg10_ptr, len(msg10_serialized)
     )
 
-    if not result.cfm_state_ob_r6_ptr:
-        raise ValueError("Failed to process cfm_process_msg10 in Rust.")
+    check_status(result.status)
 
     # Read serialized outputs
     cfm_state_ob_r6_serialized = ctypes.string_at(result.cfm_state_ob_r6_ptr, result.cfm_state_ob_r6_size)
@@ -1358,8 +1650,7 @@ def ffi_cfm_process_msg11(cfm_state_cb_r5, auth_triples_cb, msg11_serialized):
         msg11_ptr, len(msg11_serialized)
     )
 
-    if not result.cfm_state_cb_r6_ptr:
-        raise ValueError("Failed to process cfm_process_msg11 in Rust.")
+    check_status(result.status)
 
     # Read serialized outputs
     cfm_state_cb_r6_serialized = ctypes.string_at(result.cfm_state_cb_r6_ptr, result.cfm_state_cb_r6_size)
@@ -1395,8 +1686,7 @@ def ffi_cfm_process_msg12(cfm_state_ob_r6, auth_triples_ob, msg12_serialized):
         msg12_ptr, len(msg12_serialized)
     )
 
-    if not result.cfm_state_ob_r7_ptr:
-        raise ValueError("Failed to process cfm_process_msg12 in Rust.")
+    check_status(result.status)
 
     # Read serialized outputs
     cfm_state_ob_r7_serialized = ctypes.string_at(result.cfm_state_ob_r7_ptr, result.cfm_state_ob_r7_size)
@@ -1432,8 +1722,7 @@ def ffi_cfm_process_msg13(cfm_state_cb_r6, auth_triples_cb, msg13_serialized):
         msg13_ptr, len(msg13_serialized)
     )
 
-    if not result.cfm_state_cb_r7_ptr:
-        raise ValueError("Failed to process cfm_process_msg13 in Rust.")
+    check_status(result.status)
 
     # Read serialized outputs
     cfm_state_cb_r7_serialized = ctypes.string_at(result.cfm_state_cb_r7_ptr, result.cfm_state_cb_r7_size)
@@ -1466,8 +1755,7 @@ def ffi_cfm_process_msg14(cfm_state_ob_r7, msg14_serialized):
         msg14_ptr, len(msg14_serialized)
     )
 
-    if not result.cfm_state_ob_r8_ptr:
-        raise ValueError("Failed to process cfm_process_msg14 in Rust.")
+    check_status(result.status)
 
     # Read serialized outputs
     cfm_state_ob_r8_serialized = ctypes.string_at(result.cfm_state_ob_r8_ptr, result.cfm_state_ob_r8_size)
@@ -1500,8 +1788,7 @@ def ffi_cfm_process_msg15(cfm_state_cb_r7, msg15_serialized):
         msg15_ptr, len(msg15_serialized)
     )
 
-    if not result.cfm_state_cb_r8_ptr:
-        raise ValueError("Failed to process cfm_process_msg15 in Rust.")
+    check_status(result.status)
 
     # Read serialized outputs
     cfm_state_cb_r8_serialized = ctypes.string_at(result.cfm_state_cb_r8_ptr, result.cfm_state_cb_r8_size)
@@ -1534,8 +1821,7 @@ def ffi_cfm_process_msg16(cfm_state_ob_r8, msg16_serialized):
         msg16_ptr, len(msg16_serialized)
     )
 
-    if not result.cfm_state_ob_r9_ptr:
-        raise ValueError("Failed to process cfm_process_msg16 in Rust.")
+    check_status(result.status)
 
     # Read serialized outputs
     cfm_state_ob_r9_serialized = ctypes.string_at(result.cfm_state_ob_r9_ptr, result.cfm_state_ob_r9_size)
@@ -1567,8 +1853,7 @@ def ffi_cfm_process_msg17(cfm_state_cb_r8, msg17_serialized):
         msg17_ptr, len(msg17_serialized)
     )
 
-    if not result.b_cb_value_ptr:
-        raise ValueError("Failed to process cfm_process_msg17 in Rust.")
+    check_status(result.status)
 
     # Read serialized outputs
     b_cb_value_serialized = ctypes.string_at(result.b_cb_value_ptr, result.b_cb_value_size)
@@ -1601,8 +1886,7 @@ def ffi_cfm_process_msg18(cfm_state_ob_r9, msg18_serialized):
         msg18_ptr, len(msg18_serialized)
     )
 
-    if not result.b_ob_value_ptr:
-        raise ValueError("Failed to process cfm_process_msg18 in Rust.")
+    check_status(result.status)
 
     # Read serialized outputs
     b_ob_value_serialized = ctypes.string_at(result.b_ob_value_ptr, result.b_ob_value_size)
@@ -1611,3 +1895,276 @@ def ffi_cfm_process_msg18(cfm_state_ob_r9, msg18_serialized):
     rust_lib.free_buffer(result.b_ob_value_ptr, result.b_ob_value_size)
 
     return b_ob_value_serialized
+
+
+### **Session handles: secrets stay in Rust, only wire messages cross the FFI**
+def _bytes_array(data):
+    return (ctypes.c_ubyte * len(data))(*data)
+
+
+def _limits_array(big_l_y):
+    """Pointer and size of the customer limits, NULL if there are none."""
+    if big_l_y is None:
+        return None, 0
+    return (ctypes.c_uint64 * len(big_l_y))(*big_l_y), len(big_l_y)
+
+
+def _session_message(result):
+    """Returns the envelope encoded message of an FFI_SessionResult, None if there is none."""
+    check_status(result.status)
+    if not result.msg_ptr:
+        return None
+    msg = ctypes.string_at(result.msg_ptr, result.msg_size)
+    rust_lib.free_buffer(result.msg_ptr, result.msg_size)
+    return msg
+
+
+class Session:
+    """CFM party for role "ob" or "cb", backed by a Rust session handle."""
+
+    def __init__(self, role):
+        self.handle = None
+        handle = ctypes.c_void_p()
+        check_status(rust_lib.ffi_session_new({"ob": 1, "cb": 2}[role], ctypes.byref(handle)))
+        self.handle = handle.value
+
+    def close(self):
+        if self.handle:
+            rust_lib.ffi_session_free(self.handle)
+            self.handle = None
+
+    def __del__(self):
+        self.close()
+
+    def start_init(self, session_id, rng_ptr):
+        result = rust_lib.ffi_session_start_init(
+            self.handle, _bytes_array(session_id), len(session_id), rng_ptr
+        )
+        return _session_message(result)
+
+    def start_abt(self, session_id, rng_ptr):
+        result = rust_lib.ffi_session_start_abt(
+            self.handle, _bytes_array(session_id), len(session_id), rng_ptr
+        )
+        return _session_message(result)
+
+    def start_next_abt(self, rng_ptr):
+        """Starts ABT under the next session id derived from the OT seeds."""
+        return _session_message(rust_lib.ffi_session_start_next_abt(self.handle, rng_ptr))
+
+    def set_rotation_policy(self, max_age_secs, max_abt_sessions):
+        """Limits the reuse of the OT seeds, CFM init has to run again after it."""
+        check_status(rust_lib.ffi_session_set_rotation_policy(self.handle, max_age_secs, max_abt_sessions))
+
+    def start_cfm_ob(self, session_id, big_l, big_x, customer_y_bytes, rng_ptr, output_policy=OUTPUT_BOTH):
+        # big_l is None if L is a private input of CB
+        result = rust_lib.ffi_session_start_cfm_ob(
+            self.handle, _bytes_array(session_id), len(session_id),
+            output_policy,
+            big_l or 0, big_l is None, big_x,
+            _bytes_array(customer_y_bytes), len(customer_y_bytes),
+            rng_ptr
+        )
+        return _session_message(result)
+
+    def start_cfm_cb(self, session_id, big_l, big_y_bytes, big_z, output_policy=OUTPUT_BOTH, private_limit=False, big_l_y=None):
+        # big_l_y holds the limit of each customer, other than big_l only
+        # with private_limit
+        big_z_array = (ctypes.c_uint64 * len(big_z))(*big_z)
+        result = rust_lib.ffi_session_start_cfm_cb(
+            self.handle, _bytes_array(session_id), len(session_id),
+            output_policy,
+            big_l, private_limit,
+            _bytes_array(big_y_bytes), len(big_y_bytes),
+            big_z_array, len(big_z),
+            *_limits_array(big_l_y)
+        )
+        return _session_message(result)
+
+    def settle_transfer(self, approved):
+        """Settles the pending ledger update of this party with b, when it did not learn it from CFM or its fair release."""
+        check_status(rust_lib.ffi_session_settle_transfer(self.handle, approved))
+
+    def step(self, msg, rng_ptr):
+        result = rust_lib.ffi_session_step(self.handle, _bytes_array(msg), len(msg), rng_ptr)
+        return _session_message(result)
+
+    def step_signed(self, msg, signature, signing_key, peer_key, rng_ptr):
+        """Like step, for a message sent with the signature of the other party.
+        The fair release signs its escrows and acceptances with `signing_key`."""
+        result = rust_lib.ffi_session_step_signed(
+            self.handle, _bytes_array(msg), len(msg),
+            _bytes_array(signature), len(signature),
+            _bytes_array(signing_key), len(signing_key),
+            _bytes_array(peer_key), len(peer_key), rng_ptr
+        )
+        return _session_message(result)
+
+    def start_fair_release(self, signing_key, peer_key, arbiter_key, rng_ptr):
+        """Starts the fair release of b after CFM under OUTPUT_SHARED, FairMsg1 for OB."""
+        result = rust_lib.ffi_session_start_fair_release(
+            self.handle, _bytes_array(signing_key), len(signing_key),
+            _bytes_array(peer_key), len(peer_key),
+            _bytes_array(arbiter_key), len(arbiter_key), rng_ptr
+        )
+        return _session_message(result)
+
+    def fair_evidence(self):
+        """Bincode FairEvidence for the arbiter, once both escrows are accepted."""
+        evidence_ptr = ctypes.POINTER(ctypes.c_ubyte)()
+        evidence_size = ctypes.c_size_t()
+        check_status(rust_lib.ffi_session_fair_evidence(
+            self.handle, ctypes.byref(evidence_ptr), ctypes.byref(evidence_size)
+        ))
+        evidence = ctypes.string_at(evidence_ptr, evidence_size.value)
+        rust_lib.free_buffer(evidence_ptr, evidence_size.value)
+        return evidence
+
+    def resolve_fair_release(self, resolution):
+        """Finishes the fair release with the resolution of the arbiter, returns b."""
+        check_status(rust_lib.ffi_session_resolve_fair_release(
+            self.handle, _bytes_array(resolution), len(resolution)
+        ))
+        return self.result()
+
+    def sign_outbound(self, signing_key, rng_ptr):
+        """Signature of this party over the transcript hash after the message it has just sent."""
+        signature_ptr = ctypes.POINTER(ctypes.c_ubyte)()
+        signature_size = ctypes.c_size_t()
+        check_status(rust_lib.ffi_session_sign_outbound(
+            self.handle, _bytes_array(signing_key), len(signing_key), rng_ptr,
+            ctypes.byref(signature_ptr), ctypes.byref(signature_size)
+        ))
+        signature = ctypes.string_at(signature_ptr, signature_size.value)
+        rust_lib.free_buffer(signature_ptr, signature_size.value)
+        return signature
+
+    def result(self):
+        """Result bit of the last CFM session, None if it has not finished or is hidden from this party."""
+        b = rust_lib.ffi_session_result(self.handle)
+        return None if b < 0 else bool(b)
+
+    def output(self):
+        """Bincode CFMOutput of the last CFM session: the result bit, hidden or a share of it."""
+        output_ptr = ctypes.POINTER(ctypes.c_ubyte)()
+        output_size = ctypes.c_size_t()
+        check_status(rust_lib.ffi_session_output(
+            self.handle, ctypes.byref(output_ptr), ctypes.byref(output_size)
+        ))
+        output = ctypes.string_at(output_ptr, output_size.value)
+        rust_lib.free_buffer(output_ptr, output_size.value)
+        return output
+
+    def last_outbound(self):
+        """Last message sent in the running sub-protocol, to resend after a restart."""
+        return _session_message(rust_lib.ffi_session_last_outbound(self.handle))
+
+    def snapshot(self, key, rng_ptr):
+        """Whole session state encrypted under the 32 byte `key`."""
+        snapshot_ptr = ctypes.POINTER(ctypes.c_ubyte)()
+        snapshot_size = ctypes.c_size_t()
+        check_status(rust_lib.ffi_session_snapshot(
+            self.handle, _bytes_array(key), len(key), rng_ptr,
+            ctypes.byref(snapshot_ptr), ctypes.byref(snapshot_size)
+        ))
+        snapshot = ctypes.string_at(snapshot_ptr, snapshot_size.value)
+        rust_lib.free_buffer(snapshot_ptr, snapshot_size.value)
+        return snapshot
+
+    def abort_report(self, signing_key, rng_ptr):
+        """Signed report blaming the other party for the last CFM abort."""
+        report_ptr = ctypes.POINTER(ctypes.c_ubyte)()
+        report_size = ctypes.c_size_t()
+        check_status(rust_lib.ffi_session_abort_report(
+            self.handle, _bytes_array(signing_key), len(signing_key), rng_ptr,
+            ctypes.byref(report_ptr), ctypes.byref(report_size)
+        ))
+        report = ctypes.string_at(report_ptr, report_size.value)
+        rust_lib.free_buffer(report_ptr, report_size.value)
+        return report
+
+    def sign_receipt(self, signing_key, rng_ptr):
+        """Signature of this party over the receipt of the last CFM session."""
+        signature_ptr = ctypes.POINTER(ctypes.c_ubyte)()
+        signature_size = ctypes.c_size_t()
+        check_status(rust_lib.ffi_session_sign_receipt(
+            self.handle, _bytes_array(signing_key), len(signing_key), rng_ptr,
+            ctypes.byref(signature_ptr), ctypes.byref(signature_size)
+        ))
+        signature = ctypes.string_at(signature_ptr, signature_size.value)
+        rust_lib.free_buffer(signature_ptr, signature_size.value)
+        return signature
+
+    def signed_receipt(self, own_signature, other_signature):
+        """Receipt of the last CFM session signed by both parties."""
+        receipt_ptr = ctypes.POINTER(ctypes.c_ubyte)()
+        receipt_size = ctypes.c_size_t()
+        check_status(rust_lib.ffi_session_signed_receipt(
+            self.handle, _bytes_array(own_signature), len(own_signature),
+            _bytes_array(other_signature), len(other_signature),
+            ctypes.byref(receipt_ptr), ctypes.byref(receipt_size)
+        ))
+        receipt = ctypes.string_at(receipt_ptr, receipt_size.value)
+        rust_lib.free_buffer(receipt_ptr, receipt_size.value)
+        return receipt
+
+    @classmethod
+    def restore(cls, snapshot, key):
+        """Session restored from a snapshot taken with the same key."""
+        session = cls.__new__(cls)
+        session.handle = None
+        handle = ctypes.c_void_p()
+        snapshot_array = (ctypes.c_ubyte * len(snapshot)).from_buffer_copy(snapshot)
+        check_status(rust_lib.ffi_session_restore(
+            snapshot_array, len(snapshot), _bytes_array(key), len(key), ctypes.byref(handle)
+        ))
+        session.handle = handle.value
+        return session
+
+
+def verifying_key(signing_key):
+    """32 byte verifying key of a 32 byte signing key."""
+    key = (ctypes.c_ubyte * 32)()
+    check_status(rust_lib.ffi_verifying_key(_bytes_array(signing_key), len(signing_key), key, 32))
+    return bytes(key)
+
+
+def arbiter_resolve(arbiter_key, session_id, ob_verifying_key, cb_verifying_key, evidence):
+    """Resolution of the arbiter for Session.fair_evidence, sent to both OB and CB."""
+    resolution_ptr = ctypes.POINTER(ctypes.c_ubyte)()
+    resolution_size = ctypes.c_size_t()
+    check_status(rust_lib.ffi_arbiter_resolve(
+        _bytes_array(arbiter_key), len(arbiter_key),
+        _bytes_array(session_id), len(session_id),
+        _bytes_array(ob_verifying_key), len(ob_verifying_key),
+        _bytes_array(cb_verifying_key), len(cb_verifying_key),
+        _bytes_array(evidence), len(evidence),
+        ctypes.byref(resolution_ptr), ctypes.byref(resolution_size)
+    ))
+    resolution = ctypes.string_at(resolution_ptr, resolution_size.value)
+    rust_lib.free_buffer(resolution_ptr, resolution_size.value)
+    return resolution
+
+
+def verify_abort_report(report, verifying_key):
+    """True if `report` of Session.abort_report is signed under `verifying_key`."""
+    status = rust_lib.ffi_abort_report_verify(
+        _bytes_array(report), len(report), _bytes_array(verifying_key), len(verifying_key)
+    )
+    if status == CFM_INVALID_SIGNATURE_STATUS:
+        return False
+    check_status(status)
+    return True
+
+
+def verify_receipt(receipt, ob_verifying_key, cb_verifying_key):
+    """True if `receipt` of Session.signed_receipt is signed by both OB and CB."""
+    status = rust_lib.ffi_receipt_verify(
+        _bytes_array(receipt), len(receipt),
+        _bytes_array(ob_verifying_key), len(ob_verifying_key),
+        _bytes_array(cb_verifying_key), len(cb_verifying_key)
+    )
+    if status == CFM_INVALID_SIGNATURE_STATUS:
+        return False
+    check_status(status)
+    return True
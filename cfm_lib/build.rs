@@ -0,0 +1,37 @@
+//! Generates `include/cfm_lib.h` from the `#[no_mangle]` functions and
+//! `#[repr(C)]` types of the FFI modules, see `cbindgen.toml`.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+fn main() {
+    let crate_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let mut config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml"))
+        .expect("Failed to read cbindgen.toml");
+
+    let lib_rs =
+        fs::read_to_string(crate_dir.join("src/lib.rs")).expect("Failed to read src/lib.rs");
+    let abi_version = lib_rs
+        .lines()
+        .find_map(|line| line.strip_prefix("pub const CFM_ABI_VERSION: u32 = "))
+        .and_then(|rest| rest.strip_suffix(';'))
+        .expect("CFM_ABI_VERSION not found in src/lib.rs");
+
+    let after_includes = config.after_includes.take().unwrap_or_default();
+    config.after_includes = Some(format!(
+        "{}\n\n#define CFM_ABI_VERSION {}",
+        after_includes, abi_version
+    ));
+
+    // cbindgen follows the `mod` items of lib.rs into the other modules
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(crate_dir.join("src/lib.rs"))
+        .generate()
+        .expect("Failed to generate C header")
+        .write_to_file(crate_dir.join("include").join("cfm_lib.h"));
+}
@@ -0,0 +1,893 @@
+//! Python extension module for `cfm_lib`.
+//!
+//! `OBSession` and `CBSession` own a `SessionHandle` and a ChaCha20 RNG.
+//! Wire messages are envelope encoded `bytes`, protocol errors are raised
+//! as `CFMError` with the `CFMStatus` code in `status`, cheating and input
+//! errors as the `CFMCheatingError` and `CFMInputError` subclasses. A
+//! retransmitted message raises `CFMDuplicateMessageError`, answer it by
+//! resending `last_outbound`.
+//!
+//! `snapshot(key)` encrypts the whole session state, a new process resumes
+//! it with `OBSession.restore(snapshot, key)` or `CBSession.restore`.
+//!
+//! Each message can be sent with `sign_outbound(signing_key)`, its sender's
+//! signature over the transcript, and processed with `step_signed(msg,
+//! signature, signing_key, peer_key)`. After CFM under the shared output
+//! policy, `start_fair_release(signing_key, peer_key, arbiter_key)` starts
+//! the fair release of b, whose messages go through `step_signed`. If the
+//! other party stops, `fair_evidence()` goes to `arbiter_resolve` and the
+//! resolution to `resolve_fair_release`. After a `CFMCheatingError` in CFM,
+//! `abort_report(signing_key)` returns the signed report blaming the other
+//! party, with its signed message, `verify_abort_report` checks it.
+//! After CFM, each party sends `sign_receipt(signing_key)` to the other and
+//! `signed_receipt(own, other)` is the receipt both signed, checked with
+//! `verify_receipt`.
+//!
+//! The rounds run without the GIL, `step_async` runs `step` in the default
+//! executor of the running asyncio loop.
+
+use std::fmt::Display;
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use cfm_lib::abort_report::SignedAbortReport;
+use cfm_lib::cfm_protocol::OutputPolicy;
+use cfm_lib::envelope::{decode, encode, Role};
+use cfm_lib::errors::{EnvelopeError, SessionError, SnapshotError};
+use cfm_lib::fair_release::{arbiter_resolve as resolve, FairEvidence, Resolution};
+use cfm_lib::ffi_error::CFMStatus;
+use cfm_lib::identity::PartyIdentity;
+use cfm_lib::receipt::SignedReceipt;
+use cfm_lib::schnorr::{Signature, SigningKey, VerifyingKey};
+use cfm_lib::seed_store::RotationPolicy;
+use cfm_lib::session_handle::SessionHandle;
+use cfm_lib::utils::Customer;
+use cfm_lib::P;
+use crypto_bigint::U64;
+use pyo3::create_exception;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+
+create_exception!(cfm_py, CFMError, PyValueError, "CFM protocol error");
+create_exception!(
+    cfm_py,
+    CFMInputError,
+    CFMError,
+    "Invalid argument or undecodable message"
+);
+create_exception!(
+    cfm_py,
+    CFMCheatingError,
+    CFMError,
+    "The other party failed an open, commitment, MAC or proof check"
+);
+create_exception!(
+    cfm_py,
+    CFMDuplicateMessageError,
+    CFMError,
+    "The message repeats an accepted round, the session is unchanged"
+);
+
+/// Output policy from its FFI value, 0 for `Both`
+fn output_policy(value: u8) -> PyResult<OutputPolicy> {
+    OutputPolicy::try_from(value)
+        .map_err(|e| status_err((&e).into(), format!("Invalid output policy {}", value)))
+}
+
+fn status_err(status: CFMStatus, message: String) -> PyErr {
+    let err = match status {
+        CFMStatus::InvalidInput | CFMStatus::Deserialization => CFMInputError::new_err(message),
+        CFMStatus::SessionDuplicateMessage => CFMDuplicateMessageError::new_err(message),
+        _ if status.is_cheating() => CFMCheatingError::new_err(message),
+        _ => CFMError::new_err(message),
+    };
+    Python::with_gil(|py| {
+        // best effort, the message is kept either way
+        let _ = err.value(py).setattr("status", status as i32);
+    });
+    err
+}
+
+fn party_err<E: Display>(e: E) -> PyErr
+where
+    for<'a> &'a E: Into<CFMStatus>,
+{
+    status_err((&e).into(), e.to_string())
+}
+
+fn key_bytes(bytes: &[u8], name: &str) -> PyResult<[u8; 32]> {
+    bytes.try_into().map_err(|_| {
+        status_err(
+            CFMStatus::InvalidInput,
+            format!("Invalid {} size {}, expected 32", name, bytes.len()),
+        )
+    })
+}
+
+/// Identity from the 32 byte `signing_key` of this party and the 32 byte
+/// verifying key `peer_key` of the other party
+fn party_identity(signing_key: &[u8], peer_key: &[u8]) -> PyResult<PartyIdentity> {
+    Ok(PartyIdentity {
+        key: SigningKey::from_bytes(&key_bytes(signing_key, "signing key")?),
+        peer_key: VerifyingKey(key_bytes(peer_key, "verifying key")?),
+    })
+}
+
+fn session_id(bytes: &[u8]) -> PyResult<[u8; 32]> {
+    bytes.try_into().map_err(|_| {
+        status_err(
+            CFMStatus::InvalidInput,
+            format!("Invalid session id size {}, expected 32", bytes.len()),
+        )
+    })
+}
+
+fn new_rng(seed: Option<&[u8]>) -> PyResult<ChaCha20Rng> {
+    match seed {
+        None => Ok(ChaCha20Rng::from_entropy()),
+        Some(seed) => {
+            let seed: [u8; 32] = seed.try_into().map_err(|_| {
+                status_err(
+                    CFMStatus::InvalidInput,
+                    format!("Invalid seed size {}, expected 32", seed.len()),
+                )
+            })?;
+            Ok(ChaCha20Rng::from_seed(seed))
+        }
+    }
+}
+
+/// Session handle and RNG of one party, locked while a round runs
+struct Party {
+    handle: SessionHandle,
+    rng: ChaCha20Rng,
+}
+
+impl Party {
+    fn new(role: Role, seed: Option<&[u8]>) -> PyResult<Mutex<Party>> {
+        Ok(Mutex::new(Party {
+            handle: SessionHandle::new(role, P),
+            rng: new_rng(seed)?,
+        }))
+    }
+
+    fn start_init(&mut self, session_id: &[u8; 32]) -> Result<Option<Vec<u8>>, SessionError> {
+        let msg = self.handle.start_init(session_id, &mut self.rng)?;
+        Ok(msg.map(|msg| encode(&msg)).transpose()?)
+    }
+
+    fn start_abt(&mut self, session_id: &[u8; 32]) -> Result<Option<Vec<u8>>, SessionError> {
+        let msg = self.handle.start_abt(session_id, &mut self.rng)?;
+        Ok(msg.map(|msg| encode(&msg)).transpose()?)
+    }
+
+    fn start_next_abt(&mut self) -> Result<Option<Vec<u8>>, SessionError> {
+        let msg = self.handle.start_next_abt(&mut self.rng)?;
+        Ok(msg.map(|msg| encode(&msg)).transpose()?)
+    }
+
+    fn set_rotation_policy(&mut self, max_age_secs: u64, max_abt_sessions: u64) {
+        self.handle.set_rotation_policy(RotationPolicy {
+            max_age: Duration::from_secs(max_age_secs),
+            max_abt_sessions,
+        });
+    }
+
+    fn step(&mut self, msg: &[u8]) -> Result<Option<Vec<u8>>, SessionError> {
+        let incoming = decode(msg)?.message;
+        let outgoing = self.handle.step(incoming, &mut self.rng)?;
+        Ok(outgoing.map(|msg| encode(&msg)).transpose()?)
+    }
+
+    fn step_signed(
+        &mut self,
+        msg: &[u8],
+        signature: &[u8],
+        identity: &PartyIdentity,
+    ) -> Result<Option<Vec<u8>>, SessionError> {
+        let incoming = decode(msg)?.message;
+        let signature: Signature = bincode::deserialize(signature).map_err(EnvelopeError::from)?;
+        let outgoing = self
+            .handle
+            .step_signed(incoming, &signature, identity, &mut self.rng)?;
+        Ok(outgoing.map(|msg| encode(&msg)).transpose()?)
+    }
+
+    fn start_fair_release(
+        &mut self,
+        identity: &PartyIdentity,
+        arbiter: &VerifyingKey,
+    ) -> Result<Option<Vec<u8>>, SessionError> {
+        let msg = self
+            .handle
+            .start_fair_release(identity, arbiter, &mut self.rng)?;
+        Ok(msg.map(|msg| encode(&msg)).transpose()?)
+    }
+
+    fn fair_evidence(&self) -> Result<Option<Vec<u8>>, SessionError> {
+        Ok(self
+            .handle
+            .fair_evidence()
+            .map(bincode::serialize)
+            .transpose()
+            .map_err(EnvelopeError::from)?)
+    }
+
+    fn resolve_fair_release(&mut self, resolution: &[u8]) -> Result<bool, SessionError> {
+        let resolution: Resolution =
+            bincode::deserialize(resolution).map_err(EnvelopeError::from)?;
+        self.handle.resolve_fair_release(&resolution)
+    }
+
+    fn sign_outbound(&mut self, key: &SigningKey) -> Result<Vec<u8>, SessionError> {
+        let signature = self.handle.sign_outbound(key, &mut self.rng);
+        Ok(bincode::serialize(&signature).map_err(EnvelopeError::from)?)
+    }
+
+    fn last_outbound(&self) -> Result<Option<Vec<u8>>, SessionError> {
+        Ok(self.handle.last_outbound().map(encode).transpose()?)
+    }
+
+    fn snapshot(&mut self, key: &[u8; 32]) -> Result<Vec<u8>, SnapshotError> {
+        self.handle.snapshot(key, &mut self.rng)
+    }
+
+    fn abort_report(&mut self, key: &SigningKey) -> Result<Option<Vec<u8>>, SessionError> {
+        let report = self.handle.abort_report().cloned();
+        let signed = report.map(|report| report.sign(key, &mut self.rng));
+        Ok(signed
+            .map(|signed| bincode::serialize(&signed))
+            .transpose()
+            .map_err(EnvelopeError::from)?)
+    }
+
+    fn sign_receipt(&mut self, key: &SigningKey) -> Result<Option<Vec<u8>>, SessionError> {
+        let receipt = self.handle.receipt();
+        let signature = receipt.map(|receipt| receipt.sign(key, &mut self.rng));
+        Ok(signature
+            .map(|signature| bincode::serialize(&signature))
+            .transpose()
+            .map_err(EnvelopeError::from)?)
+    }
+
+    fn signed_receipt(&self, own: &[u8], other: &[u8]) -> Result<Option<Vec<u8>>, SessionError> {
+        let own: Signature = bincode::deserialize(own).map_err(EnvelopeError::from)?;
+        let other: Signature = bincode::deserialize(other).map_err(EnvelopeError::from)?;
+        let signed = self.handle.signed_receipt(own, other);
+        Ok(signed
+            .map(|signed| bincode::serialize(&signed))
+            .transpose()
+            .map_err(EnvelopeError::from)?)
+    }
+
+    /// Party of `role` from a snapshot, with a fresh RNG
+    fn restore(
+        py: Python<'_>,
+        role: Role,
+        snapshot: &[u8],
+        key: &[u8],
+        seed: Option<&[u8]>,
+    ) -> PyResult<Mutex<Party>> {
+        let key = key_bytes(key, "snapshot key")?;
+        let rng = new_rng(seed)?;
+        let handle = py
+            .allow_threads(|| SessionHandle::restore(snapshot, &key))
+            .map_err(party_err)?;
+        if handle.role() != role {
+            return Err(status_err(
+                CFMStatus::InvalidInput,
+                format!("Snapshot is of role {:?}", handle.role()),
+            ));
+        }
+        Ok(Mutex::new(Party { handle, rng }))
+    }
+}
+
+/// Runs `f` on the locked party without the GIL
+fn with_party<T, E, F>(py: Python<'_>, party: &Mutex<Party>, f: F) -> PyResult<T>
+where
+    T: Send,
+    E: Display + Send,
+    for<'a> &'a E: Into<CFMStatus>,
+    F: FnOnce(&mut Party) -> Result<T, E> + Send,
+{
+    py.allow_threads(|| {
+        let mut party = party.lock().unwrap_or_else(PoisonError::into_inner);
+        f(&mut party)
+    })
+    .map_err(party_err)
+}
+
+/// Awaitable that runs `session.step(msg)` in the default executor of the
+/// running loop, `step` releases the GIL so the loop keeps running
+fn step_async<'py>(
+    session: &Bound<'py, PyAny>,
+    msg: Bound<'py, PyAny>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let py = session.py();
+    let event_loop = py.import("asyncio")?.call_method0("get_running_loop")?;
+    event_loop.call_method1(
+        "run_in_executor",
+        (py.None(), session.getattr("step")?, msg),
+    )
+}
+
+/// Originating bank. Sends the first message of CFM init and of CFM.
+#[pyclass(module = "cfm_py")]
+struct OBSession {
+    party: Mutex<Party>,
+}
+
+#[pymethods]
+impl OBSession {
+    /// A 32 byte `seed` makes the session deterministic, for replaying a
+    /// transcript in tests only.
+    #[new]
+    #[pyo3(signature = (seed=None))]
+    fn new(seed: Option<&[u8]>) -> PyResult<Self> {
+        Ok(Self {
+            party: Party::new(Role::OB, seed)?,
+        })
+    }
+
+    /// Start CFM init, returns CFMInitMsg1
+    fn start_init(&self, py: Python<'_>, session_id: &[u8]) -> PyResult<Option<Vec<u8>>> {
+        let session_id = self::session_id(session_id)?;
+        with_party(py, &self.party, |party| party.start_init(&session_id))
+    }
+
+    /// Start ABT, OB waits for ABTMsg1
+    fn start_abt(&self, py: Python<'_>, session_id: &[u8]) -> PyResult<Option<Vec<u8>>> {
+        let session_id = self::session_id(session_id)?;
+        with_party(py, &self.party, |party| party.start_abt(&session_id))
+    }
+
+    /// Start ABT under the next session id derived from the OT seeds
+    fn start_next_abt(&self, py: Python<'_>) -> PyResult<Option<Vec<u8>>> {
+        with_party(py, &self.party, |party| party.start_next_abt())
+    }
+
+    /// Limit the reuse of the OT seeds, CFM init has to run again after
+    /// `max_abt_sessions` ABT sessions or `max_age_secs` seconds
+    fn set_rotation_policy(&self, max_age_secs: u64, max_abt_sessions: u64) {
+        self.party
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .set_rotation_policy(max_age_secs, max_abt_sessions);
+    }
+
+    /// Start CFM for the customer hash `y` and amount `big_x`, returns CFMMsg1.
+    /// `output_policy` says who learns the result: 0 both, 1 OB, 2 CB,
+    /// 3 neither, each keeps a share of it. `big_l` is None if L is a private
+    /// input of CB
+    #[pyo3(signature = (session_id, big_l, big_x, y, output_policy=0))]
+    fn start_cfm(
+        &self,
+        py: Python<'_>,
+        session_id: &[u8],
+        big_l: Option<u64>,
+        big_x: u64,
+        y: &[u8],
+        output_policy: u8,
+    ) -> PyResult<Vec<u8>> {
+        let session_id = self::session_id(session_id)?;
+        let output_policy = self::output_policy(output_policy)?;
+        let y: [u8; 32] = y.try_into().map_err(|_| {
+            status_err(
+                CFMStatus::InvalidInput,
+                format!("Invalid customer hash size {}, expected 32", y.len()),
+            )
+        })?;
+        with_party(py, &self.party, |party| {
+            let Party { handle, rng } = party;
+            let msg1 = handle.start_cfm_ob(
+                &session_id,
+                output_policy,
+                big_l.map(U64::from_u64),
+                U64::from_u64(big_x),
+                &y,
+                rng,
+            )?;
+            Ok::<_, SessionError>(encode(&msg1)?)
+        })
+    }
+
+    /// Process a message of CB, returns the answer if there is one
+    fn step(&self, py: Python<'_>, msg: &[u8]) -> PyResult<Option<Vec<u8>>> {
+        with_party(py, &self.party, |party| party.step(msg))
+    }
+
+    /// `step` for a message sent with the `signature` of CB, checked
+    /// against its 32 byte verifying key `peer_key`. The fair release signs
+    /// with the 32 byte `signing_key` of this party.
+    fn step_signed(
+        &self,
+        py: Python<'_>,
+        msg: &[u8],
+        signature: &[u8],
+        signing_key: &[u8],
+        peer_key: &[u8],
+    ) -> PyResult<Option<Vec<u8>>> {
+        let identity = party_identity(signing_key, peer_key)?;
+        with_party(py, &self.party, |party| {
+            party.step_signed(msg, signature, &identity)
+        })
+    }
+
+    /// Start the fair release of b after CFM under the shared output policy,
+    /// with escrows to the 32 byte verifying key `arbiter_key`. Returns FairMsg1.
+    fn start_fair_release(
+        &self,
+        py: Python<'_>,
+        signing_key: &[u8],
+        peer_key: &[u8],
+        arbiter_key: &[u8],
+    ) -> PyResult<Option<Vec<u8>>> {
+        let identity = party_identity(signing_key, peer_key)?;
+        let arbiter = VerifyingKey(key_bytes(arbiter_key, "verifying key")?);
+        with_party(py, &self.party, |party| {
+            party.start_fair_release(&identity, &arbiter)
+        })
+    }
+
+    /// Evidence of the fair release for `arbiter_resolve`, None unless both
+    /// escrows are accepted and CB has not released its share of b
+    fn fair_evidence(&self, py: Python<'_>) -> PyResult<Option<Vec<u8>>> {
+        with_party(py, &self.party, |party| party.fair_evidence())
+    }
+
+    /// Finish the fair release with the `resolution` of `arbiter_resolve`,
+    /// returns b
+    fn resolve_fair_release(&self, py: Python<'_>, resolution: &[u8]) -> PyResult<bool> {
+        with_party(py, &self.party, |party| {
+            party.resolve_fair_release(resolution)
+        })
+    }
+
+    /// Signature of this party over the transcript after the message it has
+    /// just sent, to send along with it
+    fn sign_outbound(&self, py: Python<'_>, signing_key: &[u8]) -> PyResult<Vec<u8>> {
+        let key = SigningKey::from_bytes(&key_bytes(signing_key, "signing key")?);
+        with_party(py, &self.party, |party| party.sign_outbound(&key))
+    }
+
+    /// Settle the pending ledger update with the result bit b, when the
+    /// output policy hid it from this party and no fair release gave it
+    fn settle_transfer(&self, py: Python<'_>, approved: bool) -> PyResult<()> {
+        with_party(py, &self.party, |party| {
+            party.handle.settle_transfer(approved)
+        })
+    }
+
+    /// Awaitable `step`, runs in the default executor of the running loop
+    fn step_async<'py>(
+        slf: &Bound<'py, Self>,
+        msg: Bound<'py, PyAny>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        self::step_async(slf.as_any(), msg)
+    }
+
+    /// Result bit of the last CFM session, None if it has not finished or
+    /// the output policy hides it from this party
+    #[getter]
+    fn result(&self) -> Option<bool> {
+        self.party
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .handle
+            .result()
+    }
+
+    /// Last message sent in the running sub-protocol, resent when CB
+    /// retransmits its message after a restart
+    fn last_outbound(&self, py: Python<'_>) -> PyResult<Option<Vec<u8>>> {
+        with_party(py, &self.party, |party| party.last_outbound())
+    }
+
+    /// Whole session state encrypted under the 32 byte `key`
+    fn snapshot(&self, py: Python<'_>, key: &[u8]) -> PyResult<Vec<u8>> {
+        let key = key_bytes(key, "snapshot key")?;
+        with_party(py, &self.party, |party| party.snapshot(&key))
+    }
+
+    /// Signed report blaming the other party for the last CFM abort, None if
+    /// no message of it failed a check
+    fn abort_report(&self, py: Python<'_>, signing_key: &[u8]) -> PyResult<Option<Vec<u8>>> {
+        let key = SigningKey::from_bytes(&key_bytes(signing_key, "signing key")?);
+        with_party(py, &self.party, |party| party.abort_report(&key))
+    }
+
+    /// Signature of this party over the receipt of the last CFM session, None
+    /// if it has not finished
+    fn sign_receipt(&self, py: Python<'_>, signing_key: &[u8]) -> PyResult<Option<Vec<u8>>> {
+        let key = SigningKey::from_bytes(&key_bytes(signing_key, "signing key")?);
+        with_party(py, &self.party, |party| party.sign_receipt(&key))
+    }
+
+    /// Receipt of the last CFM session signed with the `own` signature of this
+    /// party and the `other` of the other party, None if it has not finished
+    fn signed_receipt(
+        &self,
+        py: Python<'_>,
+        own: &[u8],
+        other: &[u8],
+    ) -> PyResult<Option<Vec<u8>>> {
+        with_party(py, &self.party, |party| party.signed_receipt(own, other))
+    }
+
+    /// Session resumed from a `snapshot` taken with the same key
+    #[staticmethod]
+    #[pyo3(signature = (snapshot, key, seed=None))]
+    fn restore(py: Python<'_>, snapshot: &[u8], key: &[u8], seed: Option<&[u8]>) -> PyResult<Self> {
+        Ok(Self {
+            party: Party::restore(py, Role::OB, snapshot, key, seed)?,
+        })
+    }
+}
+
+/// Correspondent bank. Sends the first message of ABT.
+#[pyclass(module = "cfm_py")]
+struct CBSession {
+    party: Mutex<Party>,
+}
+
+#[pymethods]
+impl CBSession {
+    /// A 32 byte `seed` makes the session deterministic, for replaying a
+    /// transcript in tests only.
+    #[new]
+    #[pyo3(signature = (seed=None))]
+    fn new(seed: Option<&[u8]>) -> PyResult<Self> {
+        Ok(Self {
+            party: Party::new(Role::CB, seed)?,
+        })
+    }
+
+    /// Start CFM init, CB waits for CFMInitMsg1
+    fn start_init(&self, py: Python<'_>, session_id: &[u8]) -> PyResult<Option<Vec<u8>>> {
+        let session_id = self::session_id(session_id)?;
+        with_party(py, &self.party, |party| party.start_init(&session_id))
+    }
+
+    /// Start ABT, returns ABTMsg1
+    fn start_abt(&self, py: Python<'_>, session_id: &[u8]) -> PyResult<Option<Vec<u8>>> {
+        let session_id = self::session_id(session_id)?;
+        with_party(py, &self.party, |party| party.start_abt(&session_id))
+    }
+
+    /// Start ABT under the next session id derived from the OT seeds
+    fn start_next_abt(&self, py: Python<'_>) -> PyResult<Option<Vec<u8>>> {
+        with_party(py, &self.party, |party| party.start_next_abt())
+    }
+
+    /// Limit the reuse of the OT seeds, CFM init has to run again after
+    /// `max_abt_sessions` ABT sessions or `max_age_secs` seconds
+    fn set_rotation_policy(&self, max_age_secs: u64, max_abt_sessions: u64) {
+        self.party
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .set_rotation_policy(max_age_secs, max_abt_sessions);
+    }
+
+    /// Start CFM for the customer hashes `big_y` with amounts `big_z`,
+    /// CB waits for CFMMsg1. `output_policy` must be the one of OB, with
+    /// `private_limit` OB starts with `big_l` None and does not learn L.
+    /// `big_l_y` holds the limit of each customer, other than `big_l` only
+    /// with `private_limit`
+    #[pyo3(signature = (session_id, big_l, big_y, big_z, output_policy=0, private_limit=false, big_l_y=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn start_cfm(
+        &self,
+        py: Python<'_>,
+        session_id: &[u8],
+        big_l: u64,
+        big_y: Vec<Vec<u8>>,
+        big_z: Vec<u64>,
+        output_policy: u8,
+        private_limit: bool,
+        big_l_y: Option<Vec<u64>>,
+    ) -> PyResult<()> {
+        let session_id = self::session_id(session_id)?;
+        let output_policy = self::output_policy(output_policy)?;
+        let big_y = big_y
+            .iter()
+            .map(|y| <[u8; 32]>::try_from(y.as_slice()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| {
+                status_err(
+                    CFMStatus::InvalidInput,
+                    "Invalid customer hash size, expected 32".to_string(),
+                )
+            })?;
+        let big_z = big_z.into_iter().map(U64::from_u64).collect();
+        let big_l_y = big_l_y.map(|big_l_y| big_l_y.into_iter().map(U64::from_u64).collect());
+        with_party(py, &self.party, |party| {
+            party.handle.start_cfm_cb(
+                &session_id,
+                output_policy,
+                U64::from_u64(big_l),
+                private_limit,
+                big_y,
+                big_z,
+                big_l_y,
+            )
+        })
+    }
+
+    /// Process a message of OB, returns the answer if there is one
+    fn step(&self, py: Python<'_>, msg: &[u8]) -> PyResult<Option<Vec<u8>>> {
+        with_party(py, &self.party, |party| party.step(msg))
+    }
+
+    /// `step` for a message sent with the `signature` of OB, checked
+    /// against its 32 byte verifying key `peer_key`. The fair release signs
+    /// with the 32 byte `signing_key` of this party.
+    fn step_signed(
+        &self,
+        py: Python<'_>,
+        msg: &[u8],
+        signature: &[u8],
+        signing_key: &[u8],
+        peer_key: &[u8],
+    ) -> PyResult<Option<Vec<u8>>> {
+        let identity = party_identity(signing_key, peer_key)?;
+        with_party(py, &self.party, |party| {
+            party.step_signed(msg, signature, &identity)
+        })
+    }
+
+    /// Start the fair release of b after CFM under the shared output policy,
+    /// with escrows to the 32 byte verifying key `arbiter_key`. Waits for FairMsg1 of OB.
+    fn start_fair_release(
+        &self,
+        py: Python<'_>,
+        signing_key: &[u8],
+        peer_key: &[u8],
+        arbiter_key: &[u8],
+    ) -> PyResult<Option<Vec<u8>>> {
+        let identity = party_identity(signing_key, peer_key)?;
+        let arbiter = VerifyingKey(key_bytes(arbiter_key, "verifying key")?);
+        with_party(py, &self.party, |party| {
+            party.start_fair_release(&identity, &arbiter)
+        })
+    }
+
+    /// Evidence of the fair release for `arbiter_resolve`, None unless both
+    /// escrows are accepted and OB has not released its share of b
+    fn fair_evidence(&self, py: Python<'_>) -> PyResult<Option<Vec<u8>>> {
+        with_party(py, &self.party, |party| party.fair_evidence())
+    }
+
+    /// Finish the fair release with the `resolution` of `arbiter_resolve`,
+    /// returns b
+    fn resolve_fair_release(&self, py: Python<'_>, resolution: &[u8]) -> PyResult<bool> {
+        with_party(py, &self.party, |party| {
+            party.resolve_fair_release(resolution)
+        })
+    }
+
+    /// Signature of this party over the transcript after the message it has
+    /// just sent, to send along with it
+    fn sign_outbound(&self, py: Python<'_>, signing_key: &[u8]) -> PyResult<Vec<u8>> {
+        let key = SigningKey::from_bytes(&key_bytes(signing_key, "signing key")?);
+        with_party(py, &self.party, |party| party.sign_outbound(&key))
+    }
+
+    /// Awaitable `step`, runs in the default executor of the running loop
+    fn step_async<'py>(
+        slf: &Bound<'py, Self>,
+        msg: Bound<'py, PyAny>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        self::step_async(slf.as_any(), msg)
+    }
+
+    /// Result bit of the last CFM session, None if it has not finished or
+    /// the output policy hides it from this party
+    #[getter]
+    fn result(&self) -> Option<bool> {
+        self.party
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .handle
+            .result()
+    }
+
+    /// Last message sent in the running sub-protocol, resent when OB
+    /// retransmits its message after a restart
+    fn last_outbound(&self, py: Python<'_>) -> PyResult<Option<Vec<u8>>> {
+        with_party(py, &self.party, |party| party.last_outbound())
+    }
+
+    /// Whole session state encrypted under the 32 byte `key`
+    fn snapshot(&self, py: Python<'_>, key: &[u8]) -> PyResult<Vec<u8>> {
+        let key = key_bytes(key, "snapshot key")?;
+        with_party(py, &self.party, |party| party.snapshot(&key))
+    }
+
+    /// Signed report blaming the other party for the last CFM abort, None if
+    /// no message of it failed a check
+    fn abort_report(&self, py: Python<'_>, signing_key: &[u8]) -> PyResult<Option<Vec<u8>>> {
+        let key = SigningKey::from_bytes(&key_bytes(signing_key, "signing key")?);
+        with_party(py, &self.party, |party| party.abort_report(&key))
+    }
+
+    /// Signature of this party over the receipt of the last CFM session, None
+    /// if it has not finished
+    fn sign_receipt(&self, py: Python<'_>, signing_key: &[u8]) -> PyResult<Option<Vec<u8>>> {
+        let key = SigningKey::from_bytes(&key_bytes(signing_key, "signing key")?);
+        with_party(py, &self.party, |party| party.sign_receipt(&key))
+    }
+
+    /// Receipt of the last CFM session signed with the `own` signature of this
+    /// party and the `other` of the other party, None if it has not finished
+    fn signed_receipt(
+        &self,
+        py: Python<'_>,
+        own: &[u8],
+        other: &[u8],
+    ) -> PyResult<Option<Vec<u8>>> {
+        with_party(py, &self.party, |party| party.signed_receipt(own, other))
+    }
+
+    /// Session resumed from a `snapshot` taken with the same key
+    #[staticmethod]
+    #[pyo3(signature = (snapshot, key, seed=None))]
+    fn restore(py: Python<'_>, snapshot: &[u8], key: &[u8], seed: Option<&[u8]>) -> PyResult<Self> {
+        Ok(Self {
+            party: Party::restore(py, Role::CB, snapshot, key, seed)?,
+        })
+    }
+}
+
+/// 32 byte customer hash, the `y` of OB and the entries of `big_y` of CB
+#[pyfunction]
+fn hash_customer(name: &str, passport_number: &str, address: &str) -> Vec<u8> {
+    Customer::new(name, passport_number, address)
+        .to_hash_bytes()
+        .to_vec()
+}
+
+/// 32 byte verifying key of a 32 byte signing key
+#[pyfunction]
+fn verifying_key(signing_key: &[u8]) -> PyResult<Vec<u8>> {
+    let key = SigningKey::from_bytes(&key_bytes(signing_key, "signing key")?);
+    Ok(key.verifying_key().0.to_vec())
+}
+
+/// Resolution of the arbiter with the 32 byte signing key `arbiter_key` for
+/// the `evidence` of `fair_evidence` of CFM session `session_id`, checked
+/// against the verifying keys of OB and CB. Raises `CFMCheatingError` naming
+/// the party whose escrow or acceptance fails.
+#[pyfunction]
+fn arbiter_resolve(
+    arbiter_key: &[u8],
+    session_id: &[u8],
+    ob_verifying_key: &[u8],
+    cb_verifying_key: &[u8],
+    evidence: &[u8],
+) -> PyResult<Vec<u8>> {
+    let arbiter = SigningKey::from_bytes(&key_bytes(arbiter_key, "signing key")?);
+    let session_id = self::session_id(session_id)?;
+    let ob_key = VerifyingKey(key_bytes(ob_verifying_key, "verifying key")?);
+    let cb_key = VerifyingKey(key_bytes(cb_verifying_key, "verifying key")?);
+    let evidence: FairEvidence = bincode::deserialize(evidence).map_err(|e| {
+        status_err(
+            CFMStatus::Deserialization,
+            format!("Invalid fair release evidence: {}", e),
+        )
+    })?;
+    let resolution =
+        resolve(&arbiter, &session_id, &ob_key, &cb_key, &evidence).map_err(party_err)?;
+    bincode::serialize(&resolution).map_err(|e| {
+        status_err(
+            CFMStatus::Serialization,
+            format!("Failed to serialize resolution: {}", e),
+        )
+    })
+}
+
+/// True if `report` of `abort_report` is signed under `verifying_key`
+#[pyfunction]
+fn verify_abort_report(report: &[u8], verifying_key: &[u8]) -> PyResult<bool> {
+    let key = VerifyingKey(key_bytes(verifying_key, "verifying key")?);
+    let report: SignedAbortReport = bincode::deserialize(report).map_err(|e| {
+        status_err(
+            CFMStatus::Deserialization,
+            format!("Invalid abort report: {}", e),
+        )
+    })?;
+    Ok(report.verify(&key))
+}
+
+/// True if `receipt` of `signed_receipt` is signed under both `ob_verifying_key`
+/// and `cb_verifying_key`
+#[pyfunction]
+fn verify_receipt(
+    receipt: &[u8],
+    ob_verifying_key: &[u8],
+    cb_verifying_key: &[u8],
+) -> PyResult<bool> {
+    let ob_key = VerifyingKey(key_bytes(ob_verifying_key, "verifying key")?);
+    let cb_key = VerifyingKey(key_bytes(cb_verifying_key, "verifying key")?);
+    let receipt: SignedReceipt = bincode::deserialize(receipt).map_err(|e| {
+        status_err(
+            CFMStatus::Deserialization,
+            format!("Invalid receipt: {}", e),
+        )
+    })?;
+    Ok(receipt.verify(&ob_key, &cb_key))
+}
+
+#[pymodule]
+fn cfm_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<OBSession>()?;
+    m.add_class::<CBSession>()?;
+    m.add_function(wrap_pyfunction!(hash_customer, m)?)?;
+    m.add_function(wrap_pyfunction!(verifying_key, m)?)?;
+    m.add_function(wrap_pyfunction!(verify_abort_report, m)?)?;
+    m.add_function(wrap_pyfunction!(verify_receipt, m)?)?;
+    m.add_function(wrap_pyfunction!(arbiter_resolve, m)?)?;
+    m.add("CFMError", m.py().get_type::<CFMError>())?;
+    m.add("CFMInputError", m.py().get_type::<CFMInputError>())?;
+    m.add("CFMCheatingError", m.py().get_type::<CFMCheatingError>())?;
+    m.add(
+        "CFMDuplicateMessageError",
+        m.py().get_type::<CFMDuplicateMessageError>(),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pyo3::types::PyBytes;
+
+    #[test]
+    fn test_init_and_errors() {
+        Python::with_gil(|py| {
+            let ob = OBSession::new(Some(&[1; 32])).unwrap();
+            let cb = CBSession::new(None).unwrap();
+            let session_id = [7u8; 32];
+
+            assert!(cb.start_init(py, &session_id).unwrap().is_none());
+            let msg1 = ob.start_init(py, &session_id).unwrap().unwrap();
+            let msg2 = cb.step(py, &msg1).unwrap().unwrap();
+
+            // a retransmitted message is answered from last_outbound
+            let err = cb.step(py, &msg1).unwrap_err();
+            assert!(err.is_instance_of::<CFMDuplicateMessageError>(py));
+            assert_eq!(cb.last_outbound(py).unwrap(), Some(msg2.clone()));
+
+            // OB resumes from a snapshot
+            let key = [3u8; 32];
+            let snapshot = ob.snapshot(py, &key).unwrap();
+            let ob = OBSession::restore(py, &snapshot, &key, None).unwrap();
+            let err = CBSession::restore(py, &snapshot, &key, None).err().unwrap();
+            assert!(err.is_instance_of::<CFMInputError>(py));
+
+            let msg3 = ob.step(py, &msg2).unwrap().unwrap();
+            assert!(cb.step(py, &msg3).unwrap().is_none());
+            assert_eq!(ob.result(), None);
+
+            // wire messages are bytes on the Python side
+            let msg = ob.start_next_abt(py).unwrap().into_pyobject(py);
+            assert!(msg.unwrap().is_none());
+            cb.set_rotation_policy(60, 1);
+            let msg1 = cb.start_next_abt(py).unwrap().unwrap();
+            assert!(msg1.into_pyobject(py).unwrap().is_instance_of::<PyBytes>());
+
+            let err = ob.step(py, b"not an envelope").unwrap_err();
+            assert!(err.is_instance_of::<CFMError>(py));
+            let status: i32 = err.value(py).getattr("status").unwrap().extract().unwrap();
+            assert_eq!(status, CFMStatus::InvalidEnvelope as i32);
+
+            let err = ob.start_init(py, &[0; 16]).unwrap_err();
+            assert!(err.is_instance_of::<CFMInputError>(py));
+
+            let err = CBSession::new(Some(&[0; 3])).err().unwrap();
+            assert!(err.is_instance_of::<CFMInputError>(py));
+        });
+    }
+}
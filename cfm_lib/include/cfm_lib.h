@@ -0,0 +1,1085 @@
+#ifndef CFM_LIB_H
+#define CFM_LIB_H
+
+/* Generated from the Rust source by cbindgen (build.rs), do not edit. */
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+
+#define CFM_ABI_VERSION 10
+
+/**
+ * Status code returned by every FFI entry point.
+ *
+ * Codes below 100 are raised by the FFI layer itself, the other ranges map
+ * one to one to the variants of the protocol error enums:
+ * 100 `CFMError`, 200 `ABTOBError`, 300 `ABTCBError`, 400 `CompError`,
+ * 500 `PSITOBError`, 600 `PSCOBError` and 700 `FairReleaseError`.
+ */
+typedef enum CFMStatus {
+  /**
+   * success
+   */
+  CFM_STATUS_OK = 0,
+  /**
+   * null pointer, empty buffer or invalid argument
+   */
+  CFM_STATUS_INVALID_INPUT = 1,
+  /**
+   * input bytes could not be deserialized
+   */
+  CFM_STATUS_DESERIALIZATION = 2,
+  /**
+   * output could not be serialized
+   */
+  CFM_STATUS_SERIALIZATION = 3,
+  /**
+   * memory allocation failed
+   */
+  CFM_STATUS_ALLOCATION_FAILED = 4,
+  /**
+   * invalid message envelope
+   */
+  CFM_STATUS_INVALID_ENVELOPE = 5,
+  /**
+   * CFM init message of the other party failed an OT or PPRF check
+   */
+  CFM_STATUS_CFM_INIT_FAILED = 6,
+  /**
+   * signature does not verify under the given key
+   */
+  CFM_STATUS_INVALID_SIGNATURE = 7,
+  /**
+   * call is not valid for the role or stage of the session handle
+   */
+  CFM_STATUS_SESSION_INVALID_STATE = 10,
+  /**
+   * message session id does not match the session handle
+   */
+  CFM_STATUS_SESSION_INVALID_SESSION_ID = 11,
+  /**
+   * message is not expected at the current stage of the session handle
+   */
+  CFM_STATUS_SESSION_UNEXPECTED_MESSAGE = 12,
+  /**
+   * session handle has aborted on an earlier error
+   */
+  CFM_STATUS_SESSION_ABORTED = 13,
+  /**
+   * message repeats a round the session handle has already accepted
+   */
+  CFM_STATUS_SESSION_DUPLICATE_MESSAGE = 14,
+  /**
+   * session snapshot could not be written or restored
+   */
+  CFM_STATUS_SESSION_SNAPSHOT_FAILED = 15,
+  /**
+   * `SeedStoreError::Expired`, run CFM init again
+   */
+  CFM_STATUS_SESSION_SEEDS_EXPIRED = 16,
+  /**
+   * `SeedStoreError::Exhausted`, run CFM init again
+   */
+  CFM_STATUS_SESSION_SEEDS_EXHAUSTED = 17,
+  /**
+   * `SeedStoreError::SessionIdReused`, start ABT under a fresh session id
+   */
+  CFM_STATUS_SESSION_SEEDS_SESSION_ID_REUSED = 18,
+  /**
+   * `LedgerError::AmountTooLarge`
+   */
+  CFM_STATUS_SESSION_LEDGER_AMOUNT_TOO_LARGE = 19,
+  /**
+   * `LedgerError::PendingTransfer`, settle the last transfer first
+   */
+  CFM_STATUS_SESSION_LEDGER_PENDING_TRANSFER = 20,
+  /**
+   * `LedgerError::NoPendingTransfer`
+   */
+  CFM_STATUS_SESSION_LEDGER_NO_PENDING_TRANSFER = 21,
+  /**
+   * `CFMError::InvalidSessionID`
+   */
+  CFM_STATUS_CFM_INVALID_SESSION_ID = 100,
+  /**
+   * `CFMError::InvalidMessage`
+   */
+  CFM_STATUS_CFM_INVALID_MESSAGE = 101,
+  /**
+   * `CFMError::InvalidOpen`
+   */
+  CFM_STATUS_CFM_INVALID_OPEN = 102,
+  /**
+   * `CFMError::PSITError`
+   */
+  CFM_STATUS_CFMPSIT_ERROR = 103,
+  /**
+   * `CFMError::AbortProtocol`
+   */
+  CFM_STATUS_CFM_ABORT_PROTOCOL = 104,
+  /**
+   * `CFMError::Comparison`
+   */
+  CFM_STATUS_CFM_COMPARISON = 105,
+  /**
+   * `CFMError::UnexpectedMessage`
+   */
+  CFM_STATUS_CFM_UNEXPECTED_MESSAGE = 106,
+  /**
+   * `CFMError::SessionClosed`
+   */
+  CFM_STATUS_CFM_SESSION_CLOSED = 107,
+  /**
+   * `CFMError::OutputPolicyMismatch`
+   */
+  CFM_STATUS_CFM_OUTPUT_POLICY_MISMATCH = 108,
+  /**
+   * `CFMError::LimitModeMismatch`
+   */
+  CFM_STATUS_CFM_LIMIT_MODE_MISMATCH = 109,
+  /**
+   * `CFMError::InvalidInput`
+   */
+  CFM_STATUS_CFM_INVALID_INPUT = 110,
+  /**
+   * `CFMError::InvalidModulus`
+   */
+  CFM_STATUS_CFM_INVALID_MODULUS = 111,
+  /**
+   * `CFMError::LedgerVOLE`
+   */
+  CFM_STATUS_CFM_LEDGER_VOLE = 112,
+  /**
+   * `ABTOBError::InvalidSessionID`
+   */
+  CFM_STATUS_ABTOB_INVALID_SESSION_ID = 200,
+  /**
+   * `ABTOBError::InvalidMessage`
+   */
+  CFM_STATUS_ABTOB_INVALID_MESSAGE = 201,
+  /**
+   * `ABTOBError::InvalidState`
+   */
+  CFM_STATUS_ABTOB_INVALID_STATE = 202,
+  /**
+   * `ABTOBError::InvalidCommitment`
+   */
+  CFM_STATUS_ABTOB_INVALID_COMMITMENT = 203,
+  /**
+   * `ABTOBError::InvalidOpen`
+   */
+  CFM_STATUS_ABTOB_INVALID_OPEN = 204,
+  /**
+   * `ABTOBError::AbortProtocolAndBanOtherParty`
+   */
+  CFM_STATUS_ABTOB_ABORT_PROTOCOL_AND_BAN_OTHER_PARTY = 205,
+  /**
+   * `ABTOBError::InvalidModulus`
+   */
+  CFM_STATUS_ABTOB_INVALID_MODULUS = 206,
+  /**
+   * `ABTOBError::ModulusMismatch`
+   */
+  CFM_STATUS_ABTOB_MODULUS_MISMATCH = 207,
+  /**
+   * `ABTOBError::InvalidFrameSize`
+   */
+  CFM_STATUS_ABTOB_INVALID_FRAME_SIZE = 208,
+  /**
+   * `ABTCBError::InvalidSessionID`
+   */
+  CFM_STATUS_ABTCB_INVALID_SESSION_ID = 300,
+  /**
+   * `ABTCBError::InvalidMessage`
+   */
+  CFM_STATUS_ABTCB_INVALID_MESSAGE = 301,
+  /**
+   * `ABTCBError::InvalidState`
+   */
+  CFM_STATUS_ABTCB_INVALID_STATE = 302,
+  /**
+   * `ABTCBError::InvalidOpen`
+   */
+  CFM_STATUS_ABTCB_INVALID_OPEN = 303,
+  /**
+   * `ABTCBError::AbortProtocolAndBanOtherParty`
+   */
+  CFM_STATUS_ABTCB_ABORT_PROTOCOL_AND_BAN_OTHER_PARTY = 304,
+  /**
+   * `ABTCBError::InvalidModulus`
+   */
+  CFM_STATUS_ABTCB_INVALID_MODULUS = 305,
+  /**
+   * `ABTCBError::InvalidFrameSize`
+   */
+  CFM_STATUS_ABTCB_INVALID_FRAME_SIZE = 306,
+  /**
+   * `CompError::InvalidSessionID`
+   */
+  CFM_STATUS_COMP_INVALID_SESSION_ID = 400,
+  /**
+   * `CompError::InvalidOpen`
+   */
+  CFM_STATUS_COMP_INVALID_OPEN = 401,
+  /**
+   * `PSITOBError::InvalidSessionID`
+   */
+  CFM_STATUS_PSITOB_INVALID_SESSION_ID = 500,
+  /**
+   * `PSITOBError::InvalidMessage`
+   */
+  CFM_STATUS_PSITOB_INVALID_MESSAGE = 501,
+  /**
+   * `PSITOBError::InvalidDLogProof`
+   */
+  CFM_STATUS_PSITOB_INVALID_D_LOG_PROOF = 502,
+  /**
+   * `PSITOBError::NotInList`
+   */
+  CFM_STATUS_PSITOB_NOT_IN_LIST = 503,
+  /**
+   * `PSCOBError::InvalidSessionID`
+   */
+  CFM_STATUS_PSCOB_INVALID_SESSION_ID = 600,
+  /**
+   * `PSCOBError::InvalidMessage`
+   */
+  CFM_STATUS_PSCOB_INVALID_MESSAGE = 601,
+  /**
+   * `PSCOBError::InvalidDLogProof`
+   */
+  CFM_STATUS_PSCOB_INVALID_D_LOG_PROOF = 602,
+  /**
+   * `FairReleaseError::InvalidSessionID`
+   */
+  CFM_STATUS_FAIR_INVALID_SESSION_ID = 700,
+  /**
+   * `FairReleaseError::InvalidSignature`
+   */
+  CFM_STATUS_FAIR_INVALID_SIGNATURE = 701,
+  /**
+   * `FairReleaseError::InvalidReveal`
+   */
+  CFM_STATUS_FAIR_INVALID_REVEAL = 702,
+  /**
+   * `FairReleaseError::InvalidEscrow`
+   */
+  CFM_STATUS_FAIR_INVALID_ESCROW = 703,
+  /**
+   * `FairReleaseError::InvalidOpen`
+   */
+  CFM_STATUS_FAIR_INVALID_OPEN = 704,
+} CFMStatus;
+
+/**
+ * Random number generator behind the `rng_ptr` argument of the FFI
+ * entry points, either `ThreadRng` or a seeded `ChaCha20Rng`.
+ *
+ * The trait object is boxed inside the holder so that the pointer handed
+ * out to C is a thin pointer.
+ */
+typedef struct RngHolder RngHolder;
+
+/**
+ * CFM party state kept across FFI calls
+ */
+typedef struct SessionHandle SessionHandle;
+
+typedef struct FFI_AbtCreateMsg1Result {
+  enum CFMStatus status;
+  uint8_t *state_cb_r1_ptr;
+  size_t state_cb_r1_size;
+  uint8_t *msg1_ptr;
+  size_t msg1_size;
+} FFI_AbtCreateMsg1Result;
+
+typedef struct FFI_AbtProcessMsg1Result {
+  enum CFMStatus status;
+  uint8_t *state_ob_r1_ptr;
+  size_t state_ob_r1_size;
+  uint8_t *shares_ob_ptr;
+  size_t shares_ob_size;
+  uint8_t *auth_triples_ob_ptr;
+  size_t auth_triples_ob_size;
+  uint8_t *msg2_ptr;
+  size_t msg2_size;
+} FFI_AbtProcessMsg1Result;
+
+typedef struct FFI_AbtProcessMsg2Result {
+  enum CFMStatus status;
+  uint8_t *state_cb_r2_ptr;
+  size_t state_cb_r2_size;
+  uint8_t *shares_cb_ptr;
+  size_t shares_cb_size;
+  uint8_t *auth_triples_cb_ptr;
+  size_t auth_triples_cb_size;
+  uint8_t *msg3_ptr;
+  size_t msg3_size;
+} FFI_AbtProcessMsg2Result;
+
+typedef struct FFI_AbtProcessMsg3Result {
+  enum CFMStatus status;
+  uint8_t *state_ob_r2_ptr;
+  size_t state_ob_r2_size;
+  uint8_t *shares_ob_ptr;
+  size_t shares_ob_size;
+  uint8_t *auth_triples_ob_ptr;
+  size_t auth_triples_ob_size;
+  uint8_t *msg4_ptr;
+  size_t msg4_size;
+} FFI_AbtProcessMsg3Result;
+
+typedef struct FFI_AbtProcessMsg4Result {
+  enum CFMStatus status;
+  uint8_t *msg5_ptr;
+  size_t msg5_size;
+} FFI_AbtProcessMsg4Result;
+
+typedef struct FFI_CfmCreateMsg1Result {
+  enum CFMStatus status;
+  uint8_t *cfm_state_ob_r1_ptr;
+  size_t cfm_state_ob_r1_size;
+  uint8_t *msg1_ptr;
+  size_t msg1_size;
+} FFI_CfmCreateMsg1Result;
+
+typedef struct FFI_CfmProcessMsg1Result {
+  enum CFMStatus status;
+  uint8_t *cfm_state_cb_r1_ptr;
+  size_t cfm_state_cb_r1_size;
+  uint8_t *msg2_ptr;
+  size_t msg2_size;
+} FFI_CfmProcessMsg1Result;
+
+typedef struct FFI_CfmProcessMsg2Result {
+  enum CFMStatus status;
+  uint8_t *cfm_state_ob_r2_ptr;
+  size_t cfm_state_ob_r2_size;
+  uint8_t *msg3_ptr;
+  size_t msg3_size;
+} FFI_CfmProcessMsg2Result;
+
+typedef struct FFI_CfmProcessMsg3Result {
+  enum CFMStatus status;
+  uint8_t *cfm_state_cb_r2_ptr;
+  size_t cfm_state_cb_r2_size;
+  uint8_t *msg4_ptr;
+  size_t msg4_size;
+} FFI_CfmProcessMsg3Result;
+
+typedef struct FFI_CfmProcessMsg4Result {
+  enum CFMStatus status;
+  uint8_t *cfm_state_ob_r3_ptr;
+  size_t cfm_state_ob_r3_size;
+  uint8_t *msg5_ptr;
+  size_t msg5_size;
+} FFI_CfmProcessMsg4Result;
+
+typedef struct FFI_CfmProcessMsg5Result {
+  enum CFMStatus status;
+  uint8_t *cfm_state_cb_r3_ptr;
+  size_t cfm_state_cb_r3_size;
+  uint8_t *msg6_ptr;
+  size_t msg6_size;
+} FFI_CfmProcessMsg5Result;
+
+typedef struct FFI_CfmProcessMsg6Result {
+  enum CFMStatus status;
+  uint8_t *cfm_state_ob_r4_ptr;
+  size_t cfm_state_ob_r4_size;
+  uint8_t *msg7_ptr;
+  size_t msg7_size;
+} FFI_CfmProcessMsg6Result;
+
+typedef struct FFI_CfmProcessMsg7Result {
+  enum CFMStatus status;
+  uint8_t *cfm_state_cb_r4_ptr;
+  size_t cfm_state_cb_r4_size;
+  uint8_t *msg8_ptr;
+  size_t msg8_size;
+} FFI_CfmProcessMsg7Result;
+
+typedef struct FFI_CfmProcessMsg8Result {
+  enum CFMStatus status;
+  uint8_t *cfm_state_ob_r5_ptr;
+  size_t cfm_state_ob_r5_size;
+  uint8_t *msg9_ptr;
+  size_t msg9_size;
+} FFI_CfmProcessMsg8Result;
+
+typedef struct FFI_CfmProcessMsg9Result {
+  enum CFMStatus status;
+  uint8_t *cfm_state_cb_r5_ptr;
+  size_t cfm_state_cb_r5_size;
+  uint8_t *msg10_ptr;
+  size_t msg10_size;
+} FFI_CfmProcessMsg9Result;
+
+typedef struct FFI_CfmProcessMsg10Result {
+  enum CFMStatus status;
+  uint8_t *cfm_state_ob_r6_ptr;
+  size_t cfm_state_ob_r6_size;
+  uint8_t *msg11_ptr;
+  size_t msg11_size;
+} FFI_CfmProcessMsg10Result;
+
+typedef struct FFI_CfmProcessMsg11Result {
+  enum CFMStatus status;
+  uint8_t *cfm_state_cb_r6_ptr;
+  size_t cfm_state_cb_r6_size;
+  uint8_t *msg12_ptr;
+  size_t msg12_size;
+} FFI_CfmProcessMsg11Result;
+
+typedef struct FFI_CfmProcessMsg12Result {
+  enum CFMStatus status;
+  uint8_t *cfm_state_ob_r7_ptr;
+  size_t cfm_state_ob_r7_size;
+  uint8_t *msg13_ptr;
+  size_t msg13_size;
+} FFI_CfmProcessMsg12Result;
+
+typedef struct FFI_CfmProcessMsg13Result {
+  enum CFMStatus status;
+  uint8_t *cfm_state_cb_r7_ptr;
+  size_t cfm_state_cb_r7_size;
+  uint8_t *msg14_ptr;
+  size_t msg14_size;
+} FFI_CfmProcessMsg13Result;
+
+typedef struct FFI_CfmProcessMsg14Result {
+  enum CFMStatus status;
+  uint8_t *cfm_state_ob_r8_ptr;
+  size_t cfm_state_ob_r8_size;
+  uint8_t *msg15_ptr;
+  size_t msg15_size;
+} FFI_CfmProcessMsg14Result;
+
+typedef struct FFI_CfmProcessMsg15Result {
+  enum CFMStatus status;
+  uint8_t *cfm_state_cb_r8_ptr;
+  size_t cfm_state_cb_r8_size;
+  uint8_t *msg16_ptr;
+  size_t msg16_size;
+} FFI_CfmProcessMsg15Result;
+
+typedef struct FFI_CfmProcessMsg16Result {
+  enum CFMStatus status;
+  uint8_t *cfm_state_ob_r9_ptr;
+  size_t cfm_state_ob_r9_size;
+  uint8_t *msg17_ptr;
+  size_t msg17_size;
+} FFI_CfmProcessMsg16Result;
+
+typedef struct FFI_CfmProcessMsg17Result {
+  enum CFMStatus status;
+  uint8_t *b_cb_value_ptr;
+  size_t b_cb_value_size;
+  uint8_t *msg18_ptr;
+  size_t msg18_size;
+} FFI_CfmProcessMsg17Result;
+
+typedef struct FFI_CfmProcessMsg18Result {
+  enum CFMStatus status;
+  uint8_t *b_ob_value_ptr;
+  size_t b_ob_value_size;
+} FFI_CfmProcessMsg18Result;
+
+/**
+ * Result of the session handle functions.
+ * `msg_ptr` is null on error or when there is no message to send,
+ * otherwise it holds an envelope encoded message to be freed with `free_buffer`.
+ */
+typedef struct FFI_SessionResult {
+  enum CFMStatus status;
+  uint8_t *msg_ptr;
+  size_t msg_size;
+} FFI_SessionResult;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * ABI version of the loaded library, bindings compare it with the
+ * `CFM_ABI_VERSION` they were built against before any other call.
+ */
+uint32_t cfm_abi_version(void);
+
+/**
+ * **FFI Wrapper function that accepts type name and deserializes accordingly**
+ * The JSON string is written to `out_json`, free it with `free_string`.
+ */
+enum CFMStatus deserialize_msg(const unsigned char *serialized_ptr,
+                               size_t size,
+                               const char *type_name_ptr,
+                               char **out_json);
+
+void free_buffer(uint8_t *ptr, size_t size);
+
+/**
+ * Free allocated memory for C strings
+ */
+void free_string(char *ptr);
+
+enum CFMStatus generate_init_session_id(void *rng_ptr, uint8_t **out_ptr, size_t *out_size);
+
+enum CFMStatus create_msg(const char *msg_type, uint8_t **out_ptr, size_t *out_size);
+
+enum CFMStatus ffi_cfm_init_create_msg1(const uint8_t *session_id_bytes_ptr,
+                                        size_t session_id_size,
+                                        uint8_t *msg1_bytes_ptr,
+                                        size_t msg1_size,
+                                        void *rng_ptr,
+                                        uint8_t **out_state_ptr,
+                                        size_t *out_state_size);
+
+enum CFMStatus ffi_cfm_init_process_msg1(const uint8_t *session_id_bytes_ptr,
+                                         size_t session_id_size,
+                                         const uint8_t *msg1_bytes_ptr,
+                                         size_t msg1_size,
+                                         uint8_t *msg2_bytes_ptr,
+                                         size_t msg2_size,
+                                         void *rng_ptr,
+                                         uint8_t **out_state_ptr,
+                                         size_t *out_state_size);
+
+enum CFMStatus ffi_cfm_init_process_msg2(const uint8_t *state_ob_ptr,
+                                         size_t state_ob_size,
+                                         const uint8_t *msg2_ptr,
+                                         size_t msg2_size,
+                                         uint8_t *msg3_ptr,
+                                         size_t msg3_size,
+                                         void *rng_ptr,
+                                         uint8_t **out_ot_seeds_ptr,
+                                         size_t *out_ot_seeds_size);
+
+enum CFMStatus ffi_cfm_init_process_msg3(const uint8_t *state_cb_ptr,
+                                         size_t state_cb_size,
+                                         const uint8_t *msg3_ptr,
+                                         size_t msg3_size,
+                                         uint8_t **out_ot_seeds_ptr,
+                                         size_t *out_ot_seeds_size);
+
+struct FFI_AbtCreateMsg1Result ffi_abt_create_msg1(const uint8_t *session_id_ptr,
+                                                   size_t session_id_size,
+                                                   const uint8_t *ot_seeds_cb_ptr,
+                                                   size_t ot_seeds_cb_size,
+                                                   void *rng_ptr);
+
+struct FFI_AbtProcessMsg1Result ffi_abt_process_msg1(const uint8_t *session_id_ptr,
+                                                     size_t session_id_size,
+                                                     const uint8_t *ot_seeds_ob_ptr,
+                                                     size_t ot_seeds_ob_size,
+                                                     const uint8_t *msg1_ptr,
+                                                     size_t msg1_size,
+                                                     void *rng_ptr);
+
+struct FFI_AbtProcessMsg2Result ffi_abt_process_msg2(const uint8_t *state_cb_r1_ptr,
+                                                     size_t state_cb_r1_size,
+                                                     const uint8_t *ot_seeds_cb_ptr,
+                                                     size_t ot_seeds_cb_size,
+                                                     const uint8_t *msg2_ptr,
+                                                     size_t msg2_size,
+                                                     void *rng_ptr);
+
+struct FFI_AbtProcessMsg3Result ffi_abt_process_msg3(const uint8_t *state_ob_r1_ptr,
+                                                     size_t state_ob_r1_size,
+                                                     uint8_t *shares_ob_ptr,
+                                                     size_t shares_ob_size,
+                                                     uint8_t *auth_triples_ob_ptr,
+                                                     size_t auth_triples_ob_size,
+                                                     const uint8_t *msg3_ptr,
+                                                     size_t msg3_size);
+
+struct FFI_AbtProcessMsg4Result ffi_abt_process_msg4(const uint8_t *state_cb_r2_ptr,
+                                                     size_t state_cb_r2_size,
+                                                     const uint8_t *auth_triples_cb_ptr,
+                                                     size_t auth_triples_cb_size,
+                                                     const uint8_t *msg4_ptr,
+                                                     size_t msg4_size);
+
+enum CFMStatus ffi_abt_process_msg5(const uint8_t *state_ob_r2_ptr,
+                                    size_t state_ob_r2_size,
+                                    const uint8_t *msg5_ptr,
+                                    size_t msg5_size);
+
+enum CFMStatus ffi_hash_customers(const char *customers_json_ptr,
+                                  bool is_list,
+                                  uint8_t **out_ptr,
+                                  size_t *out_size);
+
+struct FFI_CfmCreateMsg1Result ffi_cfm_create_msg1(const uint8_t *session_id_ptr,
+                                                   size_t session_id_size,
+                                                   uint8_t output_policy,
+                                                   uint64_t big_l,
+                                                   bool private_limit,
+                                                   uint64_t big_x,
+                                                   const uint8_t *customer_y_bytes_ptr,
+                                                   size_t customer_y_bytes_size,
+                                                   const uint8_t *ot_seeds_ob_ptr,
+                                                   size_t ot_seeds_ob_size,
+                                                   const uint8_t *shares_ob_ptr,
+                                                   size_t shares_ob_size,
+                                                   void *rng_ptr);
+
+struct FFI_CfmProcessMsg1Result ffi_cfm_process_msg1(const uint8_t *session_id_ptr,
+                                                     size_t session_id_size,
+                                                     uint8_t output_policy,
+                                                     uint64_t big_l,
+                                                     bool private_limit,
+                                                     const uint8_t *big_y_bytes_ptr,
+                                                     size_t big_y_bytes_size,
+                                                     const uint64_t *big_z_ptr,
+                                                     size_t big_z_size,
+                                                     const uint64_t *big_l_y_ptr,
+                                                     size_t big_l_y_size,
+                                                     const uint8_t *ot_seeds_cb_ptr,
+                                                     size_t ot_seeds_cb_size,
+                                                     const uint8_t *shares_cb_ptr,
+                                                     size_t shares_cb_size,
+                                                     const uint8_t *msg1_ptr,
+                                                     size_t msg1_size,
+                                                     void *rng_ptr);
+
+struct FFI_CfmProcessMsg2Result ffi_cfm_process_msg2(const uint8_t *cfm_state_ob_r1_ptr,
+                                                     size_t cfm_state_ob_r1_size,
+                                                     const uint8_t *shares_ob_ptr,
+                                                     size_t shares_ob_size,
+                                                     const uint8_t *auth_triples_ob_ptr,
+                                                     size_t auth_triples_ob_size,
+                                                     const uint8_t *msg2_ptr,
+                                                     size_t msg2_size,
+                                                     void *rng_ptr);
+
+struct FFI_CfmProcessMsg3Result ffi_cfm_process_msg3(const uint8_t *cfm_state_cb_r1_ptr,
+                                                     size_t cfm_state_cb_r1_size,
+                                                     const uint8_t *shares_cb_ptr,
+                                                     size_t shares_cb_size,
+                                                     const uint8_t *auth_triples_cb_ptr,
+                                                     size_t auth_triples_cb_size,
+                                                     const uint8_t *msg3_ptr,
+                                                     size_t msg3_size);
+
+struct FFI_CfmProcessMsg4Result ffi_cfm_process_msg4(const uint8_t *cfm_state_ob_r2_ptr,
+                                                     size_t cfm_state_ob_r2_size,
+                                                     const uint8_t *msg4_ptr,
+                                                     size_t msg4_size);
+
+struct FFI_CfmProcessMsg5Result ffi_cfm_process_msg5(const uint8_t *cfm_state_cb_r2_ptr,
+                                                     size_t cfm_state_cb_r2_size,
+                                                     const uint8_t *auth_triples_cb_ptr,
+                                                     size_t auth_triples_cb_size,
+                                                     const uint8_t *msg5_ptr,
+                                                     size_t msg5_size);
+
+struct FFI_CfmProcessMsg6Result ffi_cfm_process_msg6(const uint8_t *cfm_state_ob_r3_ptr,
+                                                     size_t cfm_state_ob_r3_size,
+                                                     const uint8_t *auth_triples_ob_ptr,
+                                                     size_t auth_triples_ob_size,
+                                                     const uint8_t *msg6_ptr,
+                                                     size_t msg6_size);
+
+struct FFI_CfmProcessMsg7Result ffi_cfm_process_msg7(const uint8_t *cfm_state_cb_r3_ptr,
+                                                     size_t cfm_state_cb_r3_size,
+                                                     const uint8_t *auth_triples_cb_ptr,
+                                                     size_t auth_triples_cb_size,
+                                                     const uint8_t *msg7_ptr,
+                                                     size_t msg7_size);
+
+struct FFI_CfmProcessMsg8Result ffi_cfm_process_msg8(const uint8_t *cfm_state_ob_r4_ptr,
+                                                     size_t cfm_state_ob_r4_size,
+                                                     const uint8_t *auth_triples_ob_ptr,
+                                                     size_t auth_triples_ob_size,
+                                                     const uint8_t *msg8_ptr,
+                                                     size_t msg8_size);
+
+struct FFI_CfmProcessMsg9Result ffi_cfm_process_msg9(const uint8_t *cfm_state_cb_r4_ptr,
+                                                     size_t cfm_state_cb_r4_size,
+                                                     const uint8_t *auth_triples_cb_ptr,
+                                                     size_t auth_triples_cb_size,
+                                                     const uint8_t *msg9_ptr,
+                                                     size_t msg9_size);
+
+struct FFI_CfmProcessMsg10Result ffi_cfm_process_msg10(const uint8_t *cfm_state_ob_r5_ptr,
+                                                       size_t cfm_state_ob_r5_size,
+                                                       const uint8_t *auth_triples_ob_ptr,
+                                                       size_t auth_triples_ob_size,
+                                                       const uint8_t *msg10_ptr,
+                                                       size_t msg10_size);
+
+struct FFI_CfmProcessMsg11Result ffi_cfm_process_msg11(const uint8_t *cfm_state_cb_r5_ptr,
+                                                       size_t cfm_state_cb_r5_size,
+                                                       const uint8_t *auth_triples_cb_ptr,
+                                                       size_t auth_triples_cb_size,
+                                                       const uint8_t *msg11_ptr,
+                                                       size_t msg11_size);
+
+struct FFI_CfmProcessMsg12Result ffi_cfm_process_msg12(const uint8_t *cfm_state_ob_r6_ptr,
+                                                       size_t cfm_state_ob_r6_size,
+                                                       const uint8_t *auth_triples_ob_ptr,
+                                                       size_t auth_triples_ob_size,
+                                                       const uint8_t *msg12_ptr,
+                                                       size_t msg12_size);
+
+struct FFI_CfmProcessMsg13Result ffi_cfm_process_msg13(const uint8_t *cfm_state_cb_r6_ptr,
+                                                       size_t cfm_state_cb_r6_size,
+                                                       const uint8_t *auth_triples_cb_ptr,
+                                                       size_t auth_triples_cb_size,
+                                                       const uint8_t *msg13_ptr,
+                                                       size_t msg13_size);
+
+struct FFI_CfmProcessMsg14Result ffi_cfm_process_msg14(const uint8_t *cfm_state_ob_r7_ptr,
+                                                       size_t cfm_state_ob_r7_size,
+                                                       const uint8_t *msg14_ptr,
+                                                       size_t msg14_size);
+
+struct FFI_CfmProcessMsg15Result ffi_cfm_process_msg15(const uint8_t *cfm_state_cb_r7_ptr,
+                                                       size_t cfm_state_cb_r7_size,
+                                                       const uint8_t *msg15_ptr,
+                                                       size_t msg15_size);
+
+struct FFI_CfmProcessMsg16Result ffi_cfm_process_msg16(const uint8_t *cfm_state_ob_r8_ptr,
+                                                       size_t cfm_state_ob_r8_size,
+                                                       const uint8_t *msg16_ptr,
+                                                       size_t msg16_size);
+
+struct FFI_CfmProcessMsg17Result ffi_cfm_process_msg17(const uint8_t *cfm_state_cb_r8_ptr,
+                                                       size_t cfm_state_cb_r8_size,
+                                                       const uint8_t *msg17_ptr,
+                                                       size_t msg17_size);
+
+struct FFI_CfmProcessMsg18Result ffi_cfm_process_msg18(const uint8_t *cfm_state_ob_r9_ptr,
+                                                       size_t cfm_state_ob_r9_size,
+                                                       const uint8_t *msg18_ptr,
+                                                       size_t msg18_size);
+
+/**
+ * Creates a session handle for `role` (1 = OB, 2 = CB) in `out_handle`.
+ * OT seeds, shares, triples and round states stay inside the handle.
+ * Free it with `ffi_session_free`.
+ */
+enum CFMStatus ffi_session_new(uint8_t role, struct SessionHandle **out_handle);
+
+/**
+ * Frees a session handle and the secrets it holds
+ */
+void ffi_session_free(struct SessionHandle *handle);
+
+/**
+ * Starts CFM init. Returns CFMInitMsg1 for OB, no message for CB.
+ */
+struct FFI_SessionResult ffi_session_start_init(struct SessionHandle *handle,
+                                                const uint8_t *session_id_ptr,
+                                                size_t session_id_size,
+                                                void *rng_ptr);
+
+/**
+ * Starts ABT for one CFM session. Returns ABTMsg1 for CB, no message for OB.
+ */
+struct FFI_SessionResult ffi_session_start_abt(struct SessionHandle *handle,
+                                               const uint8_t *session_id_ptr,
+                                               size_t session_id_size,
+                                               void *rng_ptr);
+
+/**
+ * Starts CFM for OB with the shares and triples of the last ABT run.
+ * Returns CFMMsg1.
+ */
+struct FFI_SessionResult ffi_session_start_cfm_ob(struct SessionHandle *handle,
+                                                  const uint8_t *session_id_ptr,
+                                                  size_t session_id_size,
+                                                  uint8_t output_policy,
+                                                  uint64_t big_l,
+                                                  bool private_limit,
+                                                  uint64_t big_x,
+                                                  const uint8_t *customer_y_bytes_ptr,
+                                                  size_t customer_y_bytes_size,
+                                                  void *rng_ptr);
+
+/**
+ * Starts CFM for CB with the shares and triples of the last ABT run.
+ * `big_l_y_ptr` holds the limit of each customer, null if all have limit
+ * `big_l`. CB waits for CFMMsg1, no message is returned.
+ */
+struct FFI_SessionResult ffi_session_start_cfm_cb(struct SessionHandle *handle,
+                                                  const uint8_t *session_id_ptr,
+                                                  size_t session_id_size,
+                                                  uint8_t output_policy,
+                                                  uint64_t big_l,
+                                                  bool private_limit,
+                                                  const uint8_t *big_y_bytes_ptr,
+                                                  size_t big_y_bytes_size,
+                                                  const uint64_t *big_z_ptr,
+                                                  size_t big_z_size,
+                                                  const uint64_t *big_l_y_ptr,
+                                                  size_t big_l_y_size);
+
+/**
+ * Processes an envelope encoded message of the other party.
+ * Returns the answer, or no message when the sub-protocol is done on this side.
+ */
+struct FFI_SessionResult ffi_session_step(struct SessionHandle *handle,
+                                          const uint8_t *msg_ptr,
+                                          size_t msg_size,
+                                          void *rng_ptr);
+
+/**
+ * Processes an envelope encoded message of the other party sent with its
+ * bincode `Signature` of `ffi_session_sign_outbound`, checked against the 32
+ * byte verifying key `peer_key_ptr`. A message whose signature does not
+ * verify fails with `InvalidSignature` and leaves the handle unchanged.
+ * The 32 byte signing key `key_ptr` of this party signs the escrows and
+ * acceptances of the fair release.
+ */
+struct FFI_SessionResult ffi_session_step_signed(struct SessionHandle *handle,
+                                                 const uint8_t *msg_ptr,
+                                                 size_t msg_size,
+                                                 const uint8_t *signature_ptr,
+                                                 size_t signature_size,
+                                                 const uint8_t *key_ptr,
+                                                 size_t key_size,
+                                                 const uint8_t *peer_key_ptr,
+                                                 size_t peer_key_size,
+                                                 void *rng_ptr);
+
+/**
+ * Signs the transcript hash after the message this party has just sent with
+ * the 32 byte signing key `key_ptr` and writes the bincode `Signature` to
+ * `out_ptr`, to send along with the message. Free it with `free_buffer`.
+ */
+enum CFMStatus ffi_session_sign_outbound(const struct SessionHandle *handle,
+                                         const uint8_t *key_ptr,
+                                         size_t key_size,
+                                         void *rng_ptr,
+                                         uint8_t **out_ptr,
+                                         size_t *out_size);
+
+/**
+ * Starts ABT under the next session id derived from the OT seeds of the
+ * last CFM init. Returns ABTMsg1 for CB, no message for OB.
+ */
+struct FFI_SessionResult ffi_session_start_next_abt(struct SessionHandle *handle, void *rng_ptr);
+
+/**
+ * Sets the rotation policy of the OT seeds: at most `max_abt_sessions` ABT
+ * sessions within `max_age_secs` seconds of CFM init
+ */
+enum CFMStatus ffi_session_set_rotation_policy(struct SessionHandle *handle,
+                                               uint64_t max_age_secs,
+                                               uint64_t max_abt_sessions);
+
+/**
+ * Settles the pending ledger update of the party with the result bit b, when
+ * it did not learn b from CFM, or drops it. OB and CB must settle the same
+ * way. Fails with `SessionLedgerNoPendingTransfer` unless a finished CFM session
+ * left an update pending.
+ */
+enum CFMStatus ffi_session_settle_transfer(struct SessionHandle *handle, bool approved);
+
+/**
+ * Starts the fair release of b after a CFM session under the `Shared` output
+ * policy, with the 32 byte signing key `key_ptr`, the 32 byte verifying key
+ * `peer_key_ptr` of the other party and the 32 byte verifying key
+ * `arbiter_ptr` of the arbiter. Returns FairMsg1 for OB, no message for CB.
+ * Later messages go through `ffi_session_step_signed` with the same keys.
+ */
+struct FFI_SessionResult ffi_session_start_fair_release(struct SessionHandle *handle,
+                                                        const uint8_t *key_ptr,
+                                                        size_t key_size,
+                                                        const uint8_t *peer_key_ptr,
+                                                        size_t peer_key_size,
+                                                        const uint8_t *arbiter_ptr,
+                                                        size_t arbiter_size,
+                                                        void *rng_ptr);
+
+/**
+ * Writes the bincode `FairEvidence` of a fair release to `out_ptr`, for the
+ * arbiter. Free it with `free_buffer`. Fails with `SessionInvalidState`
+ * unless both escrows are accepted and the other party has not released its
+ * share of b.
+ */
+enum CFMStatus ffi_session_fair_evidence(const struct SessionHandle *handle,
+                                         uint8_t **out_ptr,
+                                         size_t *out_size);
+
+/**
+ * The arbiter checks a `FairEvidence` of `ffi_session_fair_evidence` for the
+ * 32 byte `session_id` against the 32 byte verifying keys of OB and CB and
+ * writes the bincode `Resolution` to `out_ptr`, decrypted with its 32 byte
+ * signing key `arbiter_ptr`. Free it with `free_buffer`. A failed check is
+ * `FairInvalidEscrow` or `FairInvalidSignature`, the message names the party.
+ * The arbiter sends the resolution to both OB and CB, whichever brought the
+ * evidence.
+ */
+enum CFMStatus ffi_arbiter_resolve(const uint8_t *arbiter_ptr,
+                                   size_t arbiter_size,
+                                   const uint8_t *session_id_ptr,
+                                   size_t session_id_size,
+                                   const uint8_t *ob_key_ptr,
+                                   size_t ob_key_size,
+                                   const uint8_t *cb_key_ptr,
+                                   size_t cb_key_size,
+                                   const uint8_t *evidence_ptr,
+                                   size_t evidence_size,
+                                   uint8_t **out_ptr,
+                                   size_t *out_size);
+
+/**
+ * Finishes a fair release with the bincode `Resolution` of
+ * `ffi_arbiter_resolve`, b is then in `ffi_session_result`. Fails with
+ * `FairInvalidEscrow` if no pair of the other party passes the MAC check.
+ */
+enum CFMStatus ffi_session_resolve_fair_release(struct SessionHandle *handle,
+                                                const uint8_t *resolution_ptr,
+                                                size_t resolution_size);
+
+/**
+ * Result bit of the last CFM session, from CFM or its fair release: 1 or
+ * 0, -1 if it has not finished or the output policy hides b from this party
+ */
+int32_t ffi_session_result(const struct SessionHandle *handle);
+
+/**
+ * Writes the bincode `CFMOutput` of the last CFM session to `out_ptr`, the
+ * result bit, `Hidden` or this party's share of b. Free it with `free_buffer`.
+ * Fails with `SessionInvalidState` if the session has not finished.
+ */
+enum CFMStatus ffi_session_output(const struct SessionHandle *handle,
+                                  uint8_t **out_ptr,
+                                  size_t *out_size);
+
+/**
+ * Resend buffer of the session handle: the last message it sent in the
+ * running sub-protocol, no message if it has nothing to resend.
+ */
+struct FFI_SessionResult ffi_session_last_outbound(const struct SessionHandle *handle);
+
+/**
+ * Encrypts the whole session handle under the 32 byte `key` into `out_ptr`.
+ * Free the snapshot with `free_buffer`.
+ */
+enum CFMStatus ffi_session_snapshot(const struct SessionHandle *handle,
+                                    const uint8_t *key_ptr,
+                                    size_t key_size,
+                                    void *rng_ptr,
+                                    uint8_t **out_ptr,
+                                    size_t *out_size);
+
+/**
+ * Restores a session handle from a snapshot of `ffi_session_snapshot` into
+ * `out_handle`. Free it with `ffi_session_free`.
+ */
+enum CFMStatus ffi_session_restore(const uint8_t *snapshot_ptr,
+                                   size_t snapshot_size,
+                                   const uint8_t *key_ptr,
+                                   size_t key_size,
+                                   struct SessionHandle **out_handle);
+
+/**
+ * Writes the 32 byte verifying key of the 32 byte signing key `key_ptr`
+ * to the 32 byte buffer `out_ptr`.
+ */
+enum CFMStatus ffi_verifying_key(const uint8_t *key_ptr,
+                                 size_t key_size,
+                                 uint8_t *out_ptr,
+                                 size_t out_size);
+
+/**
+ * Signs the abort report of the last CFM session with the 32 byte signing
+ * key `key_ptr` and writes the bincode `SignedAbortReport` to `out_ptr`.
+ * Free it with `free_buffer`. Fails with `SessionInvalidState` if no message
+ * of the other party failed a check.
+ */
+enum CFMStatus ffi_session_abort_report(const struct SessionHandle *handle,
+                                        const uint8_t *key_ptr,
+                                        size_t key_size,
+                                        void *rng_ptr,
+                                        uint8_t **out_ptr,
+                                        size_t *out_size);
+
+/**
+ * Checks a `SignedAbortReport` of `ffi_session_abort_report` against the 32
+ * byte verifying key of the reporting party, `InvalidSignature` if it does
+ * not verify.
+ */
+enum CFMStatus ffi_abort_report_verify(const uint8_t *report_ptr,
+                                       size_t report_size,
+                                       const uint8_t *key_ptr,
+                                       size_t key_size);
+
+/**
+ * Signs the receipt of the last CFM session with the 32 byte signing key
+ * `key_ptr` and writes the bincode `Signature` to `out_ptr`, for the other
+ * party. Free it with `free_buffer`. Fails with `SessionInvalidState` if the
+ * session has not finished.
+ */
+enum CFMStatus ffi_session_sign_receipt(const struct SessionHandle *handle,
+                                        const uint8_t *key_ptr,
+                                        size_t key_size,
+                                        void *rng_ptr,
+                                        uint8_t **out_ptr,
+                                        size_t *out_size);
+
+/**
+ * Writes the bincode `SignedReceipt` of the last CFM session to `out_ptr`,
+ * from the signature `own_ptr` of this party and `other_ptr` of the other
+ * party, both of `ffi_session_sign_receipt`. Free it with `free_buffer`.
+ */
+enum CFMStatus ffi_session_signed_receipt(const struct SessionHandle *handle,
+                                          const uint8_t *own_ptr,
+                                          size_t own_size,
+                                          const uint8_t *other_ptr,
+                                          size_t other_size,
+                                          uint8_t **out_ptr,
+                                          size_t *out_size);
+
+/**
+ * Checks a `SignedReceipt` of `ffi_session_signed_receipt` against the 32
+ * byte verifying keys of OB and CB, `InvalidSignature` if it does not verify.
+ */
+enum CFMStatus ffi_receipt_verify(const uint8_t *receipt_ptr,
+                                  size_t receipt_size,
+                                  const uint8_t *ob_key_ptr,
+                                  size_t ob_key_size,
+                                  const uint8_t *cb_key_ptr,
+                                  size_t cb_key_size);
+
+/**
+ * Status of the last failed FFI call on this thread, `Ok` if none failed.
+ */
+enum CFMStatus cfm_last_error_code(void);
+
+/**
+ * Message of the last failed FFI call on this thread, null if none failed.
+ * The string is owned by the library and stays valid until the next
+ * failing call on the same thread, do not free it.
+ */
+const char *cfm_last_error_message(void);
+
+/**
+ * Creates a non-deterministic RNG, free it with `free_rng`.
+ */
+struct RngHolder *create_rng(void);
+
+/**
+ * Creates a ChaCha20 RNG from a `RNG_SEED_SIZE` byte seed, free it with
+ * `free_rng`. Only meant for tests and for replaying a transcript, the
+ * output of two RNGs with the same seed is identical.
+ */
+enum CFMStatus create_seeded_rng(const uint8_t *seed_ptr,
+                                 size_t seed_size,
+                                 struct RngHolder **out_rng);
+
+/**
+ * Frees an RNG created by `create_rng` or `create_seeded_rng`.
+ */
+void free_rng(struct RngHolder *rng_ptr);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CFM_LIB_H */
@@ -0,0 +1,267 @@
+//! Signed reports of identifiable aborts.
+//!
+//! Every MAC check and value check of CFM names the opened value it failed on,
+//! see [`FailedCheck`]. When such a check fails on a message of the other
+//! party, the `SessionHandle` records an [`AbortReport`]: the session, the CFM
+//! round of the message, the sub-protocol, the opened field and its index, the
+//! accused party and the transcript hash up to and including that message.
+//!
+//! Each party signs the transcript hash after every message it sends, see
+//! [`TranscriptHash::sign`], and sends the signature along. The report carries
+//! the failed message, the transcript hash before it and the signature of its
+//! sender, so the accused party cannot deny having sent it. With the
+//! [`Opening`] the check failed on, the MAC key or share of the reporting
+//! party, the arbitrator recomputes the check.
+//!
+//! The reporting party signs the report with its key. An arbitrator checks
+//! that signature, then [`AbortReport::verify_message`] against the key of the
+//! accused party and [`Opening::fails`], and finds the opened value in
+//! `field[index]` of the decoded message.
+
+use crate::constants::ABORT_REPORT_LABEL;
+use crate::envelope::{decode_header, ProtocolId, Role};
+use crate::schnorr::{Signature, SigningKey, VerifyingKey};
+use crate::transcript::TranscriptHash;
+use crypto_bigint::modular::runtime_mod::{DynResidue, DynResidueParams};
+use crypto_bigint::{Encoding, Integer, U128};
+use merlin::Transcript;
+use rand_core::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Part of CFM a failed check belongs to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SubProtocol {
+    /// inputs and openings of CFM itself
+    CFM,
+    /// comparison instance 0 or 1
+    Comparison(u8),
+    /// multiplication with an authenticated beaver triple
+    ABTSacrifice,
+    /// private set intersection and transfer
+    PSIT,
+}
+
+impl fmt::Display for SubProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubProtocol::CFM => write!(f, "CFM"),
+            SubProtocol::Comparison(instance) => write!(f, "comparison {}", instance),
+            SubProtocol::ABTSacrifice => write!(f, "ABT sacrifice"),
+            SubProtocol::PSIT => write!(f, "PSIT"),
+        }
+    }
+}
+
+/// Opened value a check failed on, `field[index]` of the message
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FailedCheck {
+    /// sub-protocol of the check
+    pub sub_protocol: SubProtocol,
+    /// message field holding the opened value
+    pub field: &'static str,
+    /// index of the opened value in the field, 0 for a single value
+    pub index: usize,
+}
+
+impl FailedCheck {
+    /// Check of a CFM input or opening
+    pub(crate) const fn cfm(field: &'static str, index: usize) -> Self {
+        FailedCheck {
+            sub_protocol: SubProtocol::CFM,
+            field,
+            index,
+        }
+    }
+
+    /// Check of a multiplication with an authenticated beaver triple
+    pub(crate) const fn abt_sacrifice(field: &'static str, index: usize) -> Self {
+        FailedCheck {
+            sub_protocol: SubProtocol::ABTSacrifice,
+            field,
+            index,
+        }
+    }
+}
+
+impl fmt::Display for FailedCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}[{}]", self.sub_protocol, self.field, self.index)
+    }
+}
+
+/// How an opening failed its check
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OpeningFailure {
+    /// M + delta != value * Delta under the MAC keys of the checking party
+    Mac {
+        /// global MAC key Delta of the checking party
+        big_delta: U128,
+        /// MAC key delta of the checking party for the opened value
+        delta: U128,
+    },
+    /// the MAC holds, but the share of the checking party and the opened
+    /// value add up to another value than `expected`
+    Value {
+        /// share of the checking party
+        share: U128,
+        /// value the opening must add up to
+        expected: U128,
+    },
+}
+
+/// Opened value and MAC of a message that failed a check, with the part of
+/// the share of the checking party that shows it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Opening {
+    /// prime p of the session
+    pub p: U128,
+    /// opened value, as in the message
+    pub value: U128,
+    /// opened MAC, as in the message
+    pub mac: U128,
+    /// the check that failed
+    pub failure: OpeningFailure,
+}
+
+impl Opening {
+    /// True if the opening fails its check
+    pub fn fails(&self) -> bool {
+        if !bool::from(self.p.is_odd()) {
+            return false;
+        }
+        let params = DynResidueParams::new(&self.p);
+        let residue = |value: &U128| DynResidue::new(value, params);
+        match self.failure {
+            OpeningFailure::Mac { big_delta, delta } => {
+                residue(&self.mac).add(&residue(&delta)).retrieve()
+                    != residue(&self.value).mul(&residue(&big_delta)).retrieve()
+            }
+            OpeningFailure::Value { share, expected } => {
+                residue(&share).add(&residue(&self.value)).retrieve() != expected
+            }
+        }
+    }
+}
+
+/// Blame record of an aborted CFM session
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AbortReport {
+    /// session id of the CFM session
+    pub session_id: [u8; 32],
+    /// CFM round of the message that failed the check
+    pub round: u8,
+    /// sub-protocol of the check
+    pub sub_protocol: SubProtocol,
+    /// message field holding the opened value
+    pub field: String,
+    /// index of the opened value in the field
+    pub index: u64,
+    /// sender of the message, the party that deviated
+    pub accused: Role,
+    /// error message of the check
+    pub reason: String,
+    /// transcript hash up to and including the message
+    pub transcript_hash: [u8; 32],
+    /// transcript hash before the message
+    pub previous_hash: [u8; 32],
+    /// envelope encoding of the message
+    pub message: Vec<u8>,
+    /// signature of the accused party over `transcript_hash`, None if the
+    /// message came without one
+    pub message_signature: Option<Signature>,
+    /// opening the check failed on, None for a message that does not parse
+    pub opening: Option<Opening>,
+}
+
+/// Abort report signed by the reporting party
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedAbortReport {
+    /// the report
+    pub report: AbortReport,
+    /// signature of the reporting party over the report digest
+    pub signature: Signature,
+}
+
+impl AbortReport {
+    /// Digest of the report, the signed message
+    pub fn digest(&self) -> [u8; 32] {
+        let mut t = Transcript::new(ABORT_REPORT_LABEL.as_ref());
+        t.append_message(b"session-id", &self.session_id);
+        t.append_u64(b"round", self.round as u64);
+        let (sub_protocol, instance) = match self.sub_protocol {
+            SubProtocol::CFM => (0, 0),
+            SubProtocol::Comparison(instance) => (1, instance),
+            SubProtocol::ABTSacrifice => (2, 0),
+            SubProtocol::PSIT => (3, 0),
+        };
+        t.append_u64(b"sub-protocol", sub_protocol);
+        t.append_u64(b"instance", instance as u64);
+        t.append_message(b"field", self.field.as_bytes());
+        t.append_u64(b"index", self.index);
+        t.append_u64(b"accused", self.accused as u64);
+        t.append_message(b"reason", self.reason.as_bytes());
+        t.append_message(b"transcript-hash", &self.transcript_hash);
+        t.append_message(b"previous-hash", &self.previous_hash);
+        t.append_message(b"message", &self.message);
+        // empty without a signature
+        let signature = self
+            .message_signature
+            .map(|signature| signature.to_bytes().to_vec());
+        t.append_message(b"message-signature", &signature.unwrap_or_default());
+        match self.opening {
+            Some(opening) => {
+                t.append_message(b"p", &opening.p.to_be_bytes());
+                t.append_message(b"value", &opening.value.to_be_bytes());
+                t.append_message(b"mac", &opening.mac.to_be_bytes());
+                let (failure, a, b) = match opening.failure {
+                    OpeningFailure::Mac { big_delta, delta } => (1, big_delta, delta),
+                    OpeningFailure::Value { share, expected } => (2, share, expected),
+                };
+                t.append_u64(b"failure", failure);
+                t.append_message(b"failure-a", &a.to_be_bytes());
+                t.append_message(b"failure-b", &b.to_be_bytes());
+            }
+            None => t.append_u64(b"failure", 0),
+        }
+
+        let mut digest = [0u8; 32];
+        t.challenge_bytes(b"abort-report-digest", &mut digest);
+        digest
+    }
+
+    /// True if the accused party holding `key` sent the message: it carries
+    /// the session id, round and sender of the report, takes the transcript
+    /// from `previous_hash` to `transcript_hash` and its signature verifies
+    pub fn verify_message(&self, key: &VerifyingKey) -> bool {
+        let Ok(header) = decode_header(&self.message) else {
+            return false;
+        };
+        let Some(signature) = &self.message_signature else {
+            return false;
+        };
+        let transcript = TranscriptHash::from_bytes(self.previous_hash).append_bytes(&self.message);
+        header.session_id == self.session_id
+            && header.protocol == ProtocolId::CFM
+            && header.round == self.round
+            && header.sender == self.accused
+            && transcript.as_bytes() == &self.transcript_hash
+            && transcript.verify(key, signature)
+    }
+
+    /// Sign the report with the key of the reporting party
+    pub fn sign<R: CryptoRng + RngCore>(self, key: &SigningKey, rng: &mut R) -> SignedAbortReport {
+        let signature = key.sign(&self.digest(), rng);
+        SignedAbortReport {
+            report: self,
+            signature,
+        }
+    }
+}
+
+impl SignedAbortReport {
+    /// True if the report is signed by the holder of `key`
+    pub fn verify(&self, key: &VerifyingKey) -> bool {
+        key.verify(&self.report.digest(), &self.signature)
+    }
+}
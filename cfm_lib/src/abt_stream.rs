@@ -0,0 +1,711 @@
+//! Streamed ABTMsg1 and ABTMsg2.
+//!
+//! ABTMsg1 and ABTMsg2 carry one RVOLE message per vole_0 instance and grow to
+//! hundreds of MB. Here they are sent as frames of bounded size, produced while
+//! the message is computed and consumed as they arrive, so that neither party
+//! holds a whole message.
+//!
+//! The parties run in lockstep. CB creates the vole_0 first messages of one
+//! frame and sends it, OB answers with one frame holding their second
+//! messages, which CB processes before it creates the next frame. After the
+//! last vole_0 frame CB sends the rest of ABTMsg1 and OB answers with the
+//! vole_1 a_tilde rows and the rest of ABTMsg2. Only the vole_1 second message
+//! is kept whole by CB, since the RVOLE consistency check needs all its rows.
+//! The same holds for the vole_2 second message in ABTMsg3, which CB sends as
+//! frames of its a_tilde rows and the rest of the message.
+//!
+//! Given the same rng, both parties end in the same state and produce the same
+//! output as abt_create_msg1, abt_process_msg1 and abt_process_msg2.
+
+use crate::auth_beaver_triples::{
+    ABTMsg1Builder, ABTMsg1Processor, ABTMsg2, ABTMsg2Processor, ABTMsg3, ABTStateCBR2,
+    ABTStateOBR1, MulSharesOpen, Share, TripleShare,
+};
+use crate::cfm_init_protocol::{CFMInitOTSeedsCB, CFMInitOTSeedsOB};
+use crate::errors::{ABTCBError, ABTOBError};
+use crate::proto::ZS;
+use crate::sl_oblivious::params::consts::RHO;
+use crate::sl_oblivious::rvole::{RVOLEOutput, RVOLEOutputATilde, RVOLEOutputCheck};
+use crate::sl_oblivious::soft_spoken::Round1Output;
+use crypto_bigint::U128;
+use rand_core::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+/// Maximum size of an encoded frame
+pub const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// Encoded size of a frame without its items:
+/// variant tag, session id, prime, start index and vector length
+const FRAME_OVERHEAD: usize = 4 + 32 + 16 + 4 + 8;
+
+#[derive(Serialize, Deserialize)]
+enum ABTFrame {
+    /// vole_0_msg1[start..start + items.len()] of ABTMsg1
+    Msg1Vole0 {
+        session_id: [u8; 32],
+        p: U128,
+        start: u32,
+        items: Vec<ZS<Round1Output>>,
+    },
+
+    /// Last frame of ABTMsg1
+    Msg1Final {
+        session_id: [u8; 32],
+        vole_1_msg1: ZS<Round1Output>,
+        commitment: [u8; 32],
+    },
+
+    /// vole_0_msg2[start..start + items.len()] of ABTMsg2, the answer to the
+    /// Msg1Vole0 frame with the same start
+    Msg2Vole0 {
+        session_id: [u8; 32],
+        start: u32,
+        items: Vec<RVOLEOutput>,
+    },
+
+    /// a_tilde rows start..start + rows.len() of vole_1_msg2
+    Msg2Vole1 {
+        session_id: [u8; 32],
+        start: u32,
+        rows: Vec<RVOLEOutputATilde>,
+    },
+
+    /// Last frame of ABTMsg2
+    Msg2Final {
+        session_id: [u8; 32],
+        vole_1_check: RVOLEOutputCheck,
+        vole_2_msg1: ZS<Round1Output>,
+        gamma_ob: Vec<U128>,
+        rho2: [u8; 32],
+    },
+
+    /// a_tilde rows start..start + rows.len() of vole_2_msg2
+    Msg3Vole2 {
+        session_id: [u8; 32],
+        start: u32,
+        rows: Vec<RVOLEOutputATilde>,
+    },
+
+    /// Last frame of ABTMsg3
+    Msg3Final {
+        session_id: [u8; 32],
+        vole_2_check: RVOLEOutputCheck,
+        gamma_cb: Vec<U128>,
+        blind_factor: [u8; 32],
+        rho1: [u8; 32],
+        mul_shares_open: Vec<MulSharesOpen>,
+    },
+}
+
+impl ABTFrame {
+    fn session_id(&self) -> &[u8; 32] {
+        match self {
+            ABTFrame::Msg1Vole0 { session_id, .. }
+            | ABTFrame::Msg1Final { session_id, .. }
+            | ABTFrame::Msg2Vole0 { session_id, .. }
+            | ABTFrame::Msg2Vole1 { session_id, .. }
+            | ABTFrame::Msg2Final { session_id, .. }
+            | ABTFrame::Msg3Vole2 { session_id, .. }
+            | ABTFrame::Msg3Final { session_id, .. } => session_id,
+        }
+    }
+
+    /// Encoded frame, None if it does not fit in `frame_size` bytes
+    fn encode(&self, frame_size: usize) -> Option<Vec<u8>> {
+        let bytes = bincode::serialize(self).ok()?;
+        (bytes.len() <= frame_size).then_some(bytes)
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() > MAX_FRAME_SIZE {
+            return None;
+        }
+        let mut payload = bytes;
+        let frame = bincode::deserialize_from(&mut payload).ok()?;
+        if !payload.is_empty() {
+            return None;
+        }
+        Some(frame)
+    }
+}
+
+/// Number of items of the encoded size of `item` that fit in one frame
+fn items_per_frame<T: Serialize>(item: &T, frame_size: usize) -> usize {
+    match bincode::serialized_size(item) {
+        Ok(size) => frame_size.saturating_sub(FRAME_OVERHEAD) / (size as usize).max(1),
+        Err(_) => 0,
+    }
+}
+
+/// Encoded frames of a_tilde `rows`, built by `frame` from the start index
+/// and rows of each chunk
+fn row_frames<F>(rows: Vec<RVOLEOutputATilde>, frame_size: usize, frame: F) -> Option<Vec<Vec<u8>>>
+where
+    F: Fn(u32, Vec<RVOLEOutputATilde>) -> ABTFrame,
+{
+    let per_frame = items_per_frame(&RVOLEOutputATilde::default(), frame_size);
+    if per_frame == 0 {
+        return None;
+    }
+    let mut frames = vec![];
+    let mut rows = rows.into_iter();
+    let mut start = 0;
+    loop {
+        let chunk: Vec<RVOLEOutputATilde> = rows.by_ref().take(per_frame).collect();
+        if chunk.is_empty() {
+            return Some(frames);
+        }
+        let next = start + chunk.len();
+        frames.push(frame(start as u32, chunk).encode(frame_size)?);
+        start = next;
+    }
+}
+
+/// Encoded frames of the vole_1 a_tilde rows and the last frame of ABTMsg2,
+/// for an ABTMsg2 without vole_0 second messages
+fn msg2_tail_frames(msg2: ABTMsg2, frame_size: usize) -> Option<Vec<Vec<u8>>> {
+    let ABTMsg2 {
+        session_id,
+        vole_1_msg2,
+        vole_2_msg1,
+        gamma_ob,
+        rho2,
+        ..
+    } = msg2;
+    let (vole_1_rows, vole_1_check) = vole_1_msg2.into_parts();
+
+    let mut frames = row_frames(vole_1_rows, frame_size, |start, rows| ABTFrame::Msg2Vole1 {
+        session_id,
+        start,
+        rows,
+    })?;
+    let last = ABTFrame::Msg2Final {
+        session_id,
+        vole_1_check,
+        vole_2_msg1,
+        gamma_ob,
+        rho2,
+    };
+    frames.push(last.encode(frame_size)?);
+    Some(frames)
+}
+
+/// Encoded frames of ABTMsg3, each at most `frame_size` bytes
+pub fn abt_msg3_frames(msg3: &ABTMsg3, frame_size: usize) -> Result<Vec<Vec<u8>>, ABTCBError> {
+    if frame_size > MAX_FRAME_SIZE {
+        return Err(ABTCBError::InvalidFrameSize);
+    }
+    let session_id = msg3.session_id;
+    let (vole_2_rows, vole_2_check) = msg3.vole_2_msg2.clone().into_parts();
+
+    let mut frames = row_frames(vole_2_rows, frame_size, |start, rows| ABTFrame::Msg3Vole2 {
+        session_id,
+        start,
+        rows,
+    })
+    .ok_or(ABTCBError::InvalidFrameSize)?;
+    let last = ABTFrame::Msg3Final {
+        session_id,
+        vole_2_check,
+        gamma_cb: msg3.gamma_cb.clone(),
+        blind_factor: msg3.blind_factor,
+        rho1: msg3.rho1,
+        mul_shares_open: msg3.mul_shares_open.clone(),
+    };
+    frames.push(
+        last.encode(frame_size)
+            .ok_or(ABTCBError::InvalidFrameSize)?,
+    );
+    Ok(frames)
+}
+
+/// CB side of the streamed ABTMsg1 and ABTMsg2, the counterpart of
+/// abt_create_msg1 followed by abt_process_msg2
+pub struct ABTStreamCB<'a> {
+    ot_seeds_cb: &'a CFMInitOTSeedsCB,
+    builder: ABTMsg1Builder,
+    processor: ABTMsg2Processor,
+    frame_size: usize,
+    per_frame: usize,
+    final_sent: bool,
+    vole_1_rows: Vec<RVOLEOutputATilde>,
+    last: Option<ABTFrame>,
+}
+
+impl<'a> ABTStreamCB<'a> {
+    /// Takes the same arguments as abt_create_msg1 and the maximum size of a
+    /// frame. Uses `rng` the same way abt_create_msg1 does.
+    pub fn new<R: CryptoRng + RngCore>(
+        session_id: &[u8; 32],
+        ot_seeds_cb: &'a CFMInitOTSeedsCB,
+        p: U128,
+        eta_i: usize,
+        eta_m: usize,
+        frame_size: usize,
+        rng: &mut R,
+    ) -> Result<Self, ABTCBError> {
+        // a vole_0 frame of either message holds the same instances
+        let per_frame = items_per_frame(&ZS::<Round1Output>::default(), frame_size)
+            .min(items_per_frame(&RVOLEOutput::new(1), frame_size));
+        if frame_size > MAX_FRAME_SIZE || per_frame == 0 {
+            return Err(ABTCBError::InvalidFrameSize);
+        }
+
+        Ok(ABTStreamCB {
+            ot_seeds_cb,
+            builder: ABTMsg1Builder::new(session_id, ot_seeds_cb, p, eta_i, eta_m, rng)?,
+            processor: ABTMsg2Processor::new(eta_m),
+            frame_size,
+            per_frame,
+            final_sent: false,
+            vole_1_rows: vec![],
+            last: None,
+        })
+    }
+
+    /// Next frame of ABTMsg1. None while OB has not answered the last vole_0
+    /// frame and once the last frame has been sent.
+    pub fn next_frame(&mut self) -> Result<Option<Vec<u8>>, ABTCBError> {
+        if self.final_sent || self.processor.received() < self.builder.created() {
+            return Ok(None);
+        }
+
+        let session_id = *self.builder.session_id();
+        let frame = if self.builder.created() < 4 * self.builder.eta_m() {
+            let start = self.builder.created() as u32;
+            let items = self.builder.create_vole_0(self.ot_seeds_cb, self.per_frame);
+            ABTFrame::Msg1Vole0 {
+                session_id,
+                p: *self.builder.p(),
+                start,
+                items,
+            }
+        } else {
+            self.final_sent = true;
+            ABTFrame::Msg1Final {
+                session_id,
+                vole_1_msg1: self.builder.vole_1_msg1().clone(),
+                commitment: self.builder.commitment(),
+            }
+        };
+
+        match frame.encode(self.frame_size) {
+            Some(bytes) => Ok(Some(bytes)),
+            None => Err(ABTCBError::InvalidFrameSize),
+        }
+    }
+
+    /// Process the next frame of ABTMsg2
+    pub fn push(&mut self, frame: &[u8]) -> Result<(), ABTCBError> {
+        if self.last.is_some() {
+            return Err(ABTCBError::InvalidMessage);
+        }
+        let frame = ABTFrame::decode(frame).ok_or(ABTCBError::InvalidMessage)?;
+        if frame.session_id() != self.builder.session_id() {
+            return Err(ABTCBError::InvalidSessionID);
+        }
+
+        match frame {
+            ABTFrame::Msg2Vole0 { start, items, .. } => {
+                // answers all instances of the last vole_0 frame
+                let start = start as usize;
+                if start != self.processor.received()
+                    || start + items.len() != self.builder.created()
+                {
+                    return Err(ABTCBError::InvalidMessage);
+                }
+                self.processor
+                    .process_vole_0(self.builder.vole_0_receivers(), &items)
+            }
+            ABTFrame::Msg2Vole1 {
+                start, mut rows, ..
+            } => {
+                let l_batch = 6 * self.builder.eta_m() + self.builder.eta_i();
+                if !self.final_sent
+                    || start as usize != self.vole_1_rows.len()
+                    || self.vole_1_rows.len() + rows.len() > l_batch + RHO
+                {
+                    return Err(ABTCBError::InvalidMessage);
+                }
+                self.vole_1_rows.append(&mut rows);
+                Ok(())
+            }
+            frame @ ABTFrame::Msg2Final { .. } if self.final_sent => {
+                self.last = Some(frame);
+                Ok(())
+            }
+            _ => Err(ABTCBError::InvalidMessage),
+        }
+    }
+
+    /// True once the last frame of ABTMsg2 has been pushed
+    pub fn is_complete(&self) -> bool {
+        self.last.is_some()
+    }
+
+    /// Same output as abt_process_msg2
+    pub fn finish<R: CryptoRng + RngCore>(
+        self,
+        rng: &mut R,
+    ) -> Result<(ABTStateCBR2, Vec<Share>, Vec<TripleShare>, ABTMsg3), ABTCBError> {
+        let (vole_1_check, vole_2_msg1, gamma_ob, rho2) = match self.last {
+            Some(ABTFrame::Msg2Final {
+                vole_1_check,
+                vole_2_msg1,
+                gamma_ob,
+                rho2,
+                ..
+            }) => (vole_1_check, vole_2_msg1, gamma_ob, rho2),
+            _ => return Err(ABTCBError::InvalidMessage),
+        };
+
+        let (state_cb, _, _) = self.builder.finish()?;
+        let vole_1_msg2 = RVOLEOutput::from_parts(
+            self.vole_1_rows,
+            vole_1_check,
+            6 * state_cb.eta_m + state_cb.eta_i,
+        )
+        .ok_or(ABTCBError::InvalidMessage)?;
+
+        self.processor.finish(
+            &state_cb,
+            self.ot_seeds_cb,
+            &vole_1_msg2,
+            &vole_2_msg1,
+            &gamma_ob,
+            &rho2,
+            rng,
+        )
+    }
+}
+
+/// OB side of the streamed ABTMsg1 and ABTMsg2, the counterpart of
+/// abt_process_msg1
+pub struct ABTStreamOB<'a> {
+    ot_seeds_ob: &'a CFMInitOTSeedsOB,
+    processor: ABTMsg1Processor,
+    frame_size: usize,
+    last: Option<(ZS<Round1Output>, [u8; 32])>,
+}
+
+impl<'a> ABTStreamOB<'a> {
+    /// Takes the same arguments as abt_process_msg1, without the message, and
+    /// the maximum size of a frame. Uses `rng` the same way abt_process_msg1
+    /// does before its vole_0 instances.
+    pub fn new<R: CryptoRng + RngCore>(
+        session_id: &[u8; 32],
+        ot_seeds_ob: &'a CFMInitOTSeedsOB,
+        p: U128,
+        eta_i: usize,
+        eta_m: usize,
+        frame_size: usize,
+        rng: &mut R,
+    ) -> Result<Self, ABTOBError> {
+        if frame_size > MAX_FRAME_SIZE {
+            return Err(ABTOBError::InvalidFrameSize);
+        }
+
+        Ok(ABTStreamOB {
+            ot_seeds_ob,
+            processor: ABTMsg1Processor::new(session_id, p, eta_i, eta_m, rng)?,
+            frame_size,
+            last: None,
+        })
+    }
+
+    /// Process the next frame of ABTMsg1, returns the answer to a vole_0 frame
+    pub fn push(&mut self, frame: &[u8]) -> Result<Option<Vec<u8>>, ABTOBError> {
+        if self.last.is_some() {
+            return Err(ABTOBError::InvalidMessage);
+        }
+        let frame = ABTFrame::decode(frame).ok_or(ABTOBError::InvalidMessage)?;
+        if frame.session_id() != self.processor.session_id() {
+            return Err(ABTOBError::InvalidSessionID);
+        }
+
+        match frame {
+            ABTFrame::Msg1Vole0 {
+                session_id,
+                p,
+                start,
+                items,
+            } => {
+                if p != *self.processor.p() {
+                    return Err(ABTOBError::ModulusMismatch);
+                }
+                if start as usize != self.processor.received() {
+                    return Err(ABTOBError::InvalidMessage);
+                }
+                let items = self.processor.process_vole_0(self.ot_seeds_ob, &items)?;
+                let answer = ABTFrame::Msg2Vole0 {
+                    session_id,
+                    start,
+                    items,
+                };
+                // CB put more instances into the frame than fit into the answer
+                match answer.encode(self.frame_size) {
+                    Some(bytes) => Ok(Some(bytes)),
+                    None => Err(ABTOBError::InvalidMessage),
+                }
+            }
+            ABTFrame::Msg1Final {
+                vole_1_msg1,
+                commitment,
+                ..
+            } => {
+                self.last = Some((vole_1_msg1, commitment));
+                Ok(None)
+            }
+            _ => Err(ABTOBError::InvalidMessage),
+        }
+    }
+
+    /// True once the last frame of ABTMsg1 has been pushed
+    pub fn is_complete(&self) -> bool {
+        self.last.is_some()
+    }
+
+    /// Same output as abt_process_msg1, with the frames of ABTMsg2 that
+    /// follow the vole_0 answers instead of the message
+    #[allow(clippy::type_complexity)]
+    pub fn finish<R: CryptoRng + RngCore>(
+        self,
+        rng: &mut R,
+    ) -> Result<(ABTStateOBR1, Vec<Share>, Vec<TripleShare>, Vec<Vec<u8>>), ABTOBError> {
+        let (vole_1_msg1, commitment) = self.last.ok_or(ABTOBError::InvalidMessage)?;
+        let (state, shares, auth_triples, msg2) =
+            self.processor
+                .finish(self.ot_seeds_ob, &vole_1_msg1, commitment, rng)?;
+        let frames = msg2_tail_frames(msg2, self.frame_size).ok_or(ABTOBError::InvalidFrameSize)?;
+        Ok((state, shares, auth_triples, frames))
+    }
+}
+
+/// OB consumer of the frames of ABTMsg3
+pub struct ABTMsg3Consumer {
+    session_id: [u8; 32],
+    l_batch: usize,
+    vole_2_rows: Vec<RVOLEOutputATilde>,
+    last: Option<ABTFrame>,
+}
+
+impl ABTMsg3Consumer {
+    /// Consumer for the ABTMsg3 of an ABT run with `eta_i` and `eta_m`
+    pub fn new(session_id: &[u8; 32], eta_i: usize, eta_m: usize) -> Self {
+        ABTMsg3Consumer {
+            session_id: *session_id,
+            l_batch: 6 * eta_m + eta_i,
+            vole_2_rows: vec![],
+            last: None,
+        }
+    }
+
+    /// Process the next frame
+    pub fn push(&mut self, frame: &[u8]) -> Result<(), ABTOBError> {
+        if self.last.is_some() {
+            return Err(ABTOBError::InvalidMessage);
+        }
+        let frame = ABTFrame::decode(frame).ok_or(ABTOBError::InvalidMessage)?;
+        if *frame.session_id() != self.session_id {
+            return Err(ABTOBError::InvalidSessionID);
+        }
+
+        match frame {
+            ABTFrame::Msg3Vole2 {
+                start, mut rows, ..
+            } => {
+                if start as usize != self.vole_2_rows.len()
+                    || self.vole_2_rows.len() + rows.len() > self.l_batch + RHO
+                {
+                    return Err(ABTOBError::InvalidMessage);
+                }
+                self.vole_2_rows.append(&mut rows);
+                Ok(())
+            }
+            frame @ ABTFrame::Msg3Final { .. } => {
+                self.last = Some(frame);
+                Ok(())
+            }
+            _ => Err(ABTOBError::InvalidMessage),
+        }
+    }
+
+    /// True once the last frame of ABTMsg3 has been pushed
+    pub fn is_complete(&self) -> bool {
+        self.last.is_some()
+    }
+
+    /// The whole ABTMsg3
+    pub fn finish(self) -> Result<ABTMsg3, ABTOBError> {
+        match self.last {
+            Some(ABTFrame::Msg3Final {
+                session_id,
+                vole_2_check,
+                gamma_cb,
+                blind_factor,
+                rho1,
+                mul_shares_open,
+            }) => Ok(ABTMsg3 {
+                session_id,
+                vole_2_msg2: RVOLEOutput::from_parts(self.vole_2_rows, vole_2_check, self.l_batch)
+                    .ok_or(ABTOBError::InvalidMessage)?,
+                gamma_cb,
+                blind_factor,
+                rho1,
+                mul_shares_open,
+            }),
+            _ => Err(ABTOBError::InvalidMessage),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth_beaver_triples::{
+        abt_create_msg1, abt_process_msg1, abt_process_msg2, ABTStateOBR1,
+    };
+    use crate::cfm_init_protocol::generate_cfm_ot_seeds_for_test;
+    use crate::P;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha20Rng;
+
+    #[test]
+    fn test_abt_stream() {
+        let mut rng = rand::thread_rng();
+        let init_session_id: [u8; 32] = rng.gen();
+        let (ot_seeds_cb, ot_seeds_ob) = generate_cfm_ot_seeds_for_test(&init_session_id, &mut rng);
+        let session_id: [u8; 32] = rng.gen();
+        let (eta_i, eta_m) = (2, 4);
+        // several items per frame, several frames per vector
+        let frame_size = 32 * 1024;
+        let (seed_cb, seed_ob): ([u8; 32], [u8; 32]) = (rng.gen(), rng.gen());
+
+        // monolithic messages
+        let mut rng_cb = ChaCha20Rng::from_seed(seed_cb);
+        let (state_cb, msg1) =
+            abt_create_msg1(&session_id, &ot_seeds_cb, P, eta_i, eta_m, &mut rng_cb).unwrap();
+        let monolithic_ob = abt_process_msg1(
+            &session_id,
+            &ot_seeds_ob,
+            P,
+            eta_i,
+            eta_m,
+            &msg1,
+            &mut ChaCha20Rng::from_seed(seed_ob),
+        )
+        .unwrap();
+        let monolithic_cb =
+            abt_process_msg2(&state_cb, &ot_seeds_cb, &monolithic_ob.3, &mut rng_cb).unwrap();
+
+        // streamed messages
+        let mut rng_cb = ChaCha20Rng::from_seed(seed_cb);
+        let mut rng_ob = ChaCha20Rng::from_seed(seed_ob);
+        let mut stream_cb = ABTStreamCB::new(
+            &session_id,
+            &ot_seeds_cb,
+            P,
+            eta_i,
+            eta_m,
+            frame_size,
+            &mut rng_cb,
+        )
+        .unwrap();
+        let mut stream_ob = ABTStreamOB::new(
+            &session_id,
+            &ot_seeds_ob,
+            P,
+            eta_i,
+            eta_m,
+            frame_size,
+            &mut rng_ob,
+        )
+        .unwrap();
+
+        let mut vole_0_frames = 0;
+        while let Some(frame) = stream_cb.next_frame().unwrap() {
+            assert!(frame.len() <= frame_size);
+            if let Some(answer) = stream_ob.push(&frame).unwrap() {
+                assert!(answer.len() <= frame_size);
+                // CB waits for the answer before the next frame
+                assert!(stream_cb.next_frame().unwrap().is_none());
+                // answers out of order are rejected
+                if vole_0_frames == 1 {
+                    assert!(stream_ob.push(&frame).is_err());
+                }
+                stream_cb.push(&answer).unwrap();
+                vole_0_frames += 1;
+            }
+        }
+        assert!(vole_0_frames > 1);
+        assert!(stream_ob.is_complete());
+
+        let (state_ob, shares_ob, triples_ob, frames) = stream_ob.finish(&mut rng_ob).unwrap();
+        assert!(frames.len() > 2);
+        // the last frame of ABTMsg2 comes after the vole_1 rows
+        assert!(stream_cb.push(&frames[1]).is_err());
+        for frame in &frames {
+            assert!(frame.len() <= frame_size);
+            stream_cb.push(frame).unwrap();
+        }
+        assert!(stream_cb.is_complete());
+        assert!(stream_cb.push(&frames[0]).is_err());
+        let streamed_cb = stream_cb.finish(&mut rng_cb).unwrap();
+
+        let ser_ob = |v: (&ABTStateOBR1, &Vec<Share>, &Vec<TripleShare>)| {
+            (
+                bincode::serialize(v.0).unwrap(),
+                bincode::serialize(v.1).unwrap(),
+                bincode::serialize(v.2).unwrap(),
+            )
+        };
+        assert!(
+            ser_ob((&monolithic_ob.0, &monolithic_ob.1, &monolithic_ob.2))
+                == ser_ob((&state_ob, &shares_ob, &triples_ob))
+        );
+
+        let ser_cb = |v: &(ABTStateCBR2, Vec<Share>, Vec<TripleShare>, ABTMsg3)| {
+            (
+                bincode::serialize(&v.0).unwrap(),
+                bincode::serialize(&v.1).unwrap(),
+                bincode::serialize(&v.2).unwrap(),
+                bincode::serialize(&v.3).unwrap(),
+            )
+        };
+        assert!(ser_cb(&monolithic_cb) == ser_cb(&streamed_cb));
+
+        // ABTMsg3 goes as frames of its vole_2 rows and the rest
+        let msg3 = streamed_cb.3;
+        let frames = abt_msg3_frames(&msg3, frame_size).unwrap();
+        assert!(frames.len() > 2);
+        let mut consumer = ABTMsg3Consumer::new(&session_id, eta_i, eta_m);
+        assert!(consumer.push(&frames[1]).is_err());
+        for frame in &frames {
+            assert!(frame.len() <= frame_size);
+            consumer.push(frame).unwrap();
+        }
+        assert!(consumer.is_complete());
+        assert_eq!(
+            bincode::serialize(&consumer.finish().unwrap()).unwrap(),
+            bincode::serialize(&msg3).unwrap()
+        );
+
+        // a frame size that cannot hold one vole_0 message is refused
+        assert!(matches!(
+            ABTStreamCB::new(&session_id, &ot_seeds_cb, P, eta_i, eta_m, 1024, &mut rng),
+            Err(ABTCBError::InvalidFrameSize)
+        ));
+        assert!(matches!(
+            ABTStreamOB::new(
+                &session_id,
+                &ot_seeds_ob,
+                P,
+                eta_i,
+                eta_m,
+                MAX_FRAME_SIZE + 1,
+                &mut rng
+            ),
+            Err(ABTOBError::InvalidFrameSize)
+        ));
+    }
+}
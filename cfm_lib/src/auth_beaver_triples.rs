@@ -1,18 +1,22 @@
 //! Authenticated Beaver Triples protocol 4.9 implementation
 
+use crate::abort_report::{Opening, OpeningFailure};
 use crate::cfm_init_protocol::{CFMInitOTSeedsCB, CFMInitOTSeedsOB};
-use crate::errors::{ABTCBError, ABTOBError};
+use crate::errors::{ABTCBError, ABTOBError, VOLEError};
 use crate::proto::ZS;
 use crate::sl_oblivious::constants::{AUTH_BEAVER_TRIPLES_LABEL, COMMIT_RHO_LABEL, RO_RHO_LABEL};
 use crate::sl_oblivious::params::consts::KAPPA_BYTES;
-use crate::sl_oblivious::rvole::{RVOLEOutput, RVOLEReceiver, RVOLESender};
+use crate::sl_oblivious::rvole::{is_valid_modulus, RVOLEOutput, RVOLEReceiver, RVOLESender};
 use crate::sl_oblivious::soft_spoken::Round1Output;
 use crate::sl_oblivious::utils::scalar_from_bytes;
 use crypto_bigint::modular::runtime_mod::{DynResidue, DynResidueParams};
 use crypto_bigint::U128;
 use merlin::Transcript;
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
 use rand_core::{CryptoRng, RngCore};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::ops::Mul;
 
@@ -22,6 +26,9 @@ pub struct ABTMsg1 {
     /// session id
     pub session_id: [u8; 32],
 
+    /// prime p of CB, OB checks it against its own before any VOLE work
+    pub p: U128,
+
     /// First messages for F_VOLE(p, 1), (sid|0|i, gen-vole, 0), i /in [4*eta_m]
     pub vole_0_msg1: Vec<ZS<Round1Output>>, // 4 * eta_m elements
 
@@ -275,7 +282,7 @@ impl Share {
         open_value: &U128,
         open_mac: &U128,
         params: DynResidueParams<2>,
-    ) -> Result<U128, &'static str> {
+    ) -> Result<U128, VOLEError> {
         let big_delta_dyn_res = DynResidue::new(&self.big_delta, params);
         let delta = &self.delta;
         let left = DynResidue::new(open_mac, params)
@@ -285,7 +292,15 @@ impl Share {
             .mul(&big_delta_dyn_res)
             .retrieve();
         if left != right {
-            return Err("Invalid Open");
+            return Err(VOLEError::InvalidMAC(Box::new(Opening {
+                p: *params.modulus(),
+                value: *open_value,
+                mac: *open_mac,
+                failure: OpeningFailure::Mac {
+                    big_delta: self.big_delta,
+                    delta: self.delta,
+                },
+            })));
         }
         Ok(DynResidue::new(&self.value, params)
             .add(&DynResidue::new(open_value, params))
@@ -299,12 +314,9 @@ impl Share {
         open_x: &(U128, U128),
         y: &U128,
         params: DynResidueParams<2>,
-    ) -> Result<(Share, U128), &'static str> {
+    ) -> Result<(Share, U128), VOLEError> {
         let (value, mac) = open_x;
-        let x = match self.validate_open(value, mac, params) {
-            Ok(v) => v,
-            Err(_) => return Err("Invalid Open"),
-        };
+        let x = self.validate_open(value, mac, params)?;
         let d = DynResidue::new(y, params)
             .sub(&DynResidue::new(&x, params))
             .retrieve();
@@ -319,12 +331,9 @@ impl Share {
         open_x: &(U128, U128),
         y: &U128,
         params: DynResidueParams<2>,
-    ) -> Result<(Share, U128), &'static str> {
+    ) -> Result<(Share, U128), VOLEError> {
         let (value, mac) = open_x;
-        let x = match self.validate_open(value, mac, params) {
-            Ok(v) => v,
-            Err(_) => return Err("Invalid Open"),
-        };
+        let x = self.validate_open(value, mac, params)?;
         let d = DynResidue::new(y, params)
             .sub(&DynResidue::new(&x, params))
             .retrieve();
@@ -428,7 +437,7 @@ pub fn multiply_shares_output(
     mul_shares_open: &MulSharesOpen,
     is_cb_side: bool,
     params: DynResidueParams<2>,
-) -> Result<Share, &'static str> {
+) -> Result<Share, VOLEError> {
     // validate Open(d)
     let d_value = state
         .d
@@ -488,7 +497,7 @@ pub fn test_bit_output(
     mul_shares_open: &MulSharesOpen,
     is_cb_side: bool,
     params: DynResidueParams<2>,
-) -> Result<Share, &'static str> {
+) -> Result<Share, VOLEError> {
     let share = multiply_shares_output(state, mul_shares_open, is_cb_side, params)?;
 
     let result = if is_cb_side {
@@ -511,6 +520,52 @@ pub struct TripleShare {
     pub z: Share,
 }
 
+/// Shares and triples of one session of a batched ABT run
+pub struct ABTBatchItem {
+    /// eta_i input shares
+    pub shares: Vec<Share>,
+    /// eta_m authenticated beaver triples
+    pub auth_triples: Vec<TripleShare>,
+}
+
+/// ABT parameters (eta_i, eta_m) of one run producing eta_i shares and eta_m
+/// triples for each of `sessions` sessions.
+///
+/// One run pays the SoftSpoken rounds, RHO padding rows and theta/mu checks
+/// of vole_1 and vole_2, the rho1 commitment and the message rounds once for
+/// all sessions. vole_0 stays one RVOLE instance per factor: an RVOLE has a
+/// single receiver scalar, and sharing it between sessions would let the
+/// other party learn differences of the values masked by the triples.
+pub fn abt_batch_sizes(sessions: usize, eta_i: usize, eta_m: usize) -> (usize, usize) {
+    assert!(sessions > 0);
+    (sessions * eta_i, sessions * eta_m)
+}
+
+/// Split the output of a batched ABT run into `sessions` equal parts.
+/// Both parties get matching parts, as shares and triples are in the same
+/// order on both sides.
+pub fn abt_split_batch(
+    mut shares: Vec<Share>,
+    mut auth_triples: Vec<TripleShare>,
+    sessions: usize,
+) -> Vec<ABTBatchItem> {
+    assert!(sessions > 0);
+    assert!(shares.len().is_multiple_of(sessions));
+    assert!(auth_triples.len().is_multiple_of(sessions));
+    let eta_i = shares.len() / sessions;
+    let eta_m = auth_triples.len() / sessions;
+
+    let mut items = Vec::with_capacity(sessions);
+    for _ in 0..sessions {
+        items.push(ABTBatchItem {
+            shares: shares.drain(..eta_i).collect(),
+            auth_triples: auth_triples.drain(..eta_m).collect(),
+        });
+    }
+
+    items
+}
+
 fn commit_rho_value(session_id: &[u8], blind_factor: &[u8], rho1: &[u8]) -> [u8; 32] {
     let mut t = Transcript::new(&COMMIT_RHO_LABEL);
     t.append_message(b"session-id", session_id);
@@ -520,7 +575,57 @@ fn commit_rho_value(session_id: &[u8], blind_factor: &[u8], rho1: &[u8]) -> [u8;
     t.challenge_bytes(b"commitment-rho1", commitment.as_mut());
     commitment
 }
-use std::time::Instant;
+/// Session ids of vole_0 instances, derived from the ABT transcript
+fn vole_0_session_ids(session_id: &[u8; 32], count: usize) -> Vec<[u8; 32]> {
+    let mut t = Transcript::new(&AUTH_BEAVER_TRIPLES_LABEL);
+    t.append_message(b"session-id", session_id);
+    t.append_u64(b"vole-instance", 0u64);
+    (0..count)
+        .map(|i| {
+            t.append_u64(b"vole-index", i as u64);
+            let mut vole_sid = [0u8; 32];
+            t.challenge_bytes(b"vole-sid", vole_sid.as_mut());
+            vole_sid
+        })
+        .collect()
+}
+
+/// Seeds for forked RNGs, drawn from `rng` in index order
+fn fork_seeds<R: CryptoRng + RngCore>(count: usize, rng: &mut R) -> Vec<[u8; 32]> {
+    (0..count).map(|_| rng.gen()).collect()
+}
+
+/// Runs `f(start + i, rng_i)` for every seeds[i], where rng_i is seeded with seeds[i].
+/// The result does not depend on whether the `parallel` feature is enabled.
+fn map_seeded<T, F>(start: usize, seeds: &[[u8; 32]], f: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(usize, &mut ChaCha20Rng) -> T + Send + Sync,
+{
+    #[cfg(feature = "parallel")]
+    let seeds = seeds.par_iter();
+    #[cfg(not(feature = "parallel"))]
+    let seeds = seeds.iter();
+
+    seeds
+        .enumerate()
+        .map(|(i, seed)| f(start + i, &mut ChaCha20Rng::from_seed(*seed)))
+        .collect()
+}
+
+/// Runs `f(i)` for i in 0..count, in parallel with the `parallel` feature
+fn map_indices<T, F>(count: usize, f: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(usize) -> T + Send + Sync,
+{
+    #[cfg(feature = "parallel")]
+    let indices = (0..count).into_par_iter();
+    #[cfg(not(feature = "parallel"))]
+    let indices = 0..count;
+
+    indices.map(f).collect()
+}
 
 /// CB creates ABTMsg1 for OB
 pub fn abt_create_msg1<R: CryptoRng + RngCore>(
@@ -530,91 +635,182 @@ pub fn abt_create_msg1<R: CryptoRng + RngCore>(
     eta_i: usize,
     eta_m: usize,
     rng: &mut R,
-) -> (ABTStateCBR1, ABTMsg1) {
-    let mut vole_0_msg1 = vec![ZS::<Round1Output>::default(); 4 * eta_m];
-    let mut vole_0_receivers = vec![];
+) -> Result<(ABTStateCBR1, ABTMsg1), ABTCBError> {
+    let mut builder = ABTMsg1Builder::new(session_id, ot_seeds_cb, p, eta_i, eta_m, rng)?;
+    let vole_0_msg1 = builder.create_vole_0(ot_seeds_cb, 4 * eta_m);
+    let (state, vole_1_msg1, commitment) = builder.finish()?;
 
-    let mut count_i = 0;
+    let msg1 = ABTMsg1 {
+        session_id: *session_id,
+        p,
+        vole_0_msg1,
+        vole_1_msg1,
+        commitment,
+    };
 
-    // println!("Started {:?}", vole_0_msg1.len());
+    Ok((state, msg1))
+}
 
-    let mut t = Transcript::new(&AUTH_BEAVER_TRIPLES_LABEL);
+/// CB side of abt_create_msg1, split so that the vole_0 first messages
+/// can be created in parts and sent as they are ready
+pub(crate) struct ABTMsg1Builder {
+    session_id: [u8; 32],
+    p: U128,
+    eta_i: usize,
+    eta_m: usize,
+    vole_0_sids: Vec<[u8; 32]>,
+    vole_0_seeds: Vec<[u8; 32]>,
+    vole_0_receivers: Vec<(Box<RVOLEReceiver>, U128)>,
+    vole_1_receiver: (Box<RVOLEReceiver>, U128),
+    vole_1_msg1: ZS<Round1Output>,
+    blind_factor: [u8; 32],
+    rho1: [u8; 32],
+    commitment: [u8; 32],
+}
 
+impl ABTMsg1Builder {
+    /// Draws all values from `rng` in the same order as abt_create_msg1
+    pub(crate) fn new<R: CryptoRng + RngCore>(
+        session_id: &[u8; 32],
+        ot_seeds_cb: &CFMInitOTSeedsCB,
+        p: U128,
+        eta_i: usize,
+        eta_m: usize,
+        rng: &mut R,
+    ) -> Result<Self, ABTCBError> {
+        if !is_valid_modulus(&p) {
+            return Err(ABTCBError::InvalidModulus);
+        }
 
-    // Measure the loop time
-    let loop_start = Instant::now();
+        let vole_0_seeds = fork_seeds(4 * eta_m, rng);
 
-    t.append_message(b"session-id", session_id);
-    t.append_u64(b"vole-instance", 0u64);
-    for (i, vole_0_msg1_i) in vole_0_msg1.iter_mut().enumerate().take(4 * eta_m) {
-        t.append_u64(b"vole-index", i as u64);
+        let mut t = Transcript::new(&AUTH_BEAVER_TRIPLES_LABEL);
+        t.append_message(b"session-id", session_id);
+        t.append_u64(b"vole-instance", 1u64);
         let mut vole_sid = [0u8; 32];
         t.challenge_bytes(b"vole-sid", vole_sid.as_mut());
 
-        let (receiver, beta) = RVOLEReceiver::new(
+        let mut vole_1_msg1 = ZS::<Round1Output>::default();
+        let vole_1_receiver = RVOLEReceiver::new(
             vole_sid,
+            p,
             &ot_seeds_cb.sender_ot_seed_0,
-            vole_0_msg1_i,
-            1,
+            &mut vole_1_msg1,
+            6 * eta_m + eta_i,
             &mut *rng,
         );
-        vole_0_receivers.push((receiver, beta));
-        count_i += 1;
-        // println!("pushed {:?}", count_i);
+
+        let mut blind_factor = [0u8; 32];
+        rng.fill_bytes(blind_factor.as_mut());
+
+        let mut rho1 = [0u8; 32];
+        rng.fill_bytes(rho1.as_mut());
+
+        let commitment = commit_rho_value(session_id, &blind_factor, &rho1);
+
+        Ok(Self {
+            session_id: *session_id,
+            p,
+            eta_i,
+            eta_m,
+            vole_0_sids: vole_0_session_ids(session_id, 4 * eta_m),
+            vole_0_seeds,
+            vole_0_receivers: Vec::with_capacity(4 * eta_m),
+            vole_1_receiver,
+            vole_1_msg1,
+            blind_factor,
+            rho1,
+            commitment,
+        })
     }
 
-    let loop_duration = loop_start.elapsed();
+    pub(crate) fn session_id(&self) -> &[u8; 32] {
+        &self.session_id
+    }
 
-    // Measure time for code outside the loop
-    let outside_loop_start = Instant::now();
+    pub(crate) fn p(&self) -> &U128 {
+        &self.p
+    }
 
-    let mut t = Transcript::new(&AUTH_BEAVER_TRIPLES_LABEL);
-    t.append_message(b"session-id", session_id);
-    t.append_u64(b"vole-instance", 1u64);
-    let mut vole_sid = [0u8; 32];
-    t.challenge_bytes(b"vole-sid", vole_sid.as_mut());
+    pub(crate) fn eta_i(&self) -> usize {
+        self.eta_i
+    }
 
-    let mut vole_1_msg1 = ZS::<Round1Output>::default();
-    let vole_1_receiver = RVOLEReceiver::new(
-        vole_sid,
-        &ot_seeds_cb.sender_ot_seed_0,
-        &mut vole_1_msg1,
-        6 * eta_m + eta_i,
-        &mut *rng,
-    );
+    pub(crate) fn eta_m(&self) -> usize {
+        self.eta_m
+    }
 
-    let mut blind_factor = [0u8; 32];
-    rng.fill_bytes(blind_factor.as_mut());
+    /// Receivers of the vole_0 instances created so far
+    pub(crate) fn vole_0_receivers(&self) -> &[(Box<RVOLEReceiver>, U128)] {
+        &self.vole_0_receivers
+    }
 
-    let mut rho1 = [0u8; 32];
-    rng.fill_bytes(rho1.as_mut());
+    /// Number of vole_0 first messages created so far
+    pub(crate) fn created(&self) -> usize {
+        self.vole_0_receivers.len()
+    }
 
-    let commitment = commit_rho_value(session_id, &blind_factor, &rho1);
+    /// First message of vole_1
+    pub(crate) fn vole_1_msg1(&self) -> &ZS<Round1Output> {
+        &self.vole_1_msg1
+    }
 
-    let state = ABTStateCBR1 {
-        session_id: *session_id,
-        p,
-        eta_i,
-        eta_m,
-        vole_0_receivers,
-        vole_1_receiver,
-        blind_factor,
-        rho1,
-    };
-    let msg1 = ABTMsg1 {
-        session_id: *session_id,
-        vole_0_msg1,
-        vole_1_msg1,
-        commitment,
-    };
+    /// Commitment to rho1
+    pub(crate) fn commitment(&self) -> [u8; 32] {
+        self.commitment
+    }
 
+    /// Creates the next vole_0 first messages, at most `count` of them
+    pub(crate) fn create_vole_0(
+        &mut self,
+        ot_seeds_cb: &CFMInitOTSeedsCB,
+        count: usize,
+    ) -> Vec<ZS<Round1Output>> {
+        let start = self.created();
+        let end = (start + count).min(4 * self.eta_m);
+
+        let (vole_0_msg1, mut vole_0_receivers): (Vec<_>, Vec<_>) =
+            map_seeded(start, &self.vole_0_seeds[start..end], |i, rng| {
+                let mut vole_0_msg1_i = ZS::<Round1Output>::default();
+                let receiver = RVOLEReceiver::new(
+                    self.vole_0_sids[i],
+                    self.p,
+                    &ot_seeds_cb.sender_ot_seed_0,
+                    &mut vole_0_msg1_i,
+                    1,
+                    rng,
+                );
+                (vole_0_msg1_i, receiver)
+            })
+            .into_iter()
+            .unzip();
+
+        self.vole_0_receivers.append(&mut vole_0_receivers);
+        vole_0_msg1
+    }
+
+    /// State of CB once all vole_0 first messages are created, with the
+    /// first message of vole_1 and the commitment to rho1
+    pub(crate) fn finish(
+        self,
+    ) -> Result<(ABTStateCBR1, ZS<Round1Output>, [u8; 32]), ABTCBError> {
+        if self.created() != 4 * self.eta_m {
+            return Err(ABTCBError::InvalidState);
+        }
 
-    let outside_loop_duration = outside_loop_start.elapsed();
-    // println!("Loop iterations: {}", count_i);
-    // println!("Loop duration: {:?}", loop_duration);
-    // println!("Time for code outside the loop: {:?}", outside_loop_duration);
+        let state = ABTStateCBR1 {
+            session_id: self.session_id,
+            p: self.p,
+            eta_i: self.eta_i,
+            eta_m: self.eta_m,
+            vole_0_receivers: self.vole_0_receivers,
+            vole_1_receiver: self.vole_1_receiver,
+            blind_factor: self.blind_factor,
+            rho1: self.rho1,
+        };
 
-    (state, msg1)
+        Ok((state, self.vole_1_msg1, self.commitment))
+    }
 }
 
 /// OB processes ABTMsg1 from CB
@@ -630,169 +826,274 @@ pub fn abt_process_msg1<R: CryptoRng + RngCore>(
     if *session_id != msg1.session_id {
         return Err(ABTOBError::InvalidSessionID);
     }
+    if msg1.p != p {
+        return Err(ABTOBError::ModulusMismatch);
+    }
     if msg1.vole_0_msg1.len() != 4 * eta_m {
         return Err(ABTOBError::InvalidMessage);
     }
 
-    let params = DynResidueParams::new(&p);
+    let mut processor = ABTMsg1Processor::new(session_id, p, eta_i, eta_m, rng)?;
+    let vole_0_msg2 = processor.process_vole_0(ot_seeds_ob, &msg1.vole_0_msg1)?;
+    let (state, shares, auth_triples, mut msg2) =
+        processor.finish(ot_seeds_ob, &msg1.vole_1_msg1, msg1.commitment, rng)?;
+    msg2.vole_0_msg2 = vole_0_msg2;
+    Ok((state, shares, auth_triples, msg2))
+}
 
-    let mut vole_0_msg2 = vec![RVOLEOutput::new(1); 4 * eta_m];
-    let mut vole_0_sender_shares = vec![];
-    let vole_0_alpha_values = vec![[scalar_from_bytes(params, rng.gen())]; 4 * eta_m];
+/// OB side of abt_process_msg1, split so that the vole_0 first messages
+/// can be processed in parts as they arrive
+pub(crate) struct ABTMsg1Processor {
+    session_id: [u8; 32],
+    p: U128,
+    eta_i: usize,
+    eta_m: usize,
+    vole_0_sids: Vec<[u8; 32]>,
+    vole_0_alpha_values: Vec<[U128; 1]>,
+    vole_0_seeds: Vec<[u8; 32]>,
+    vole_0_sender_shares: Vec<Vec<U128>>,
+}
 
-    let mut t = Transcript::new(&AUTH_BEAVER_TRIPLES_LABEL);
-    t.append_message(b"session-id", session_id);
-    t.append_u64(b"vole-instance", 0u64);
-    for i in 0..4 * eta_m {
-        t.append_u64(b"vole-index", i as u64);
+impl ABTMsg1Processor {
+    /// Draws the vole_0 values from `rng` in the same order as abt_process_msg1
+    pub(crate) fn new<R: CryptoRng + RngCore>(
+        session_id: &[u8; 32],
+        p: U128,
+        eta_i: usize,
+        eta_m: usize,
+        rng: &mut R,
+    ) -> Result<Self, ABTOBError> {
+        if !is_valid_modulus(&p) {
+            return Err(ABTOBError::InvalidModulus);
+        }
+
+        let params = DynResidueParams::new(&p);
+        let vole_0_alpha_values = vec![[scalar_from_bytes(params, rng.gen())]; 4 * eta_m];
+
+        Ok(Self {
+            session_id: *session_id,
+            p,
+            eta_i,
+            eta_m,
+            vole_0_sids: vole_0_session_ids(session_id, 4 * eta_m),
+            vole_0_alpha_values,
+            vole_0_seeds: fork_seeds(4 * eta_m, rng),
+            vole_0_sender_shares: Vec::with_capacity(4 * eta_m),
+        })
+    }
+
+    pub(crate) fn session_id(&self) -> &[u8; 32] {
+        &self.session_id
+    }
+
+    pub(crate) fn p(&self) -> &U128 {
+        &self.p
+    }
+
+    /// Number of vole_0 first messages processed so far
+    pub(crate) fn received(&self) -> usize {
+        self.vole_0_sender_shares.len()
+    }
+
+    /// Processes the next vole_0 first messages, in order, and returns
+    /// their second messages
+    pub(crate) fn process_vole_0(
+        &mut self,
+        ot_seeds_ob: &CFMInitOTSeedsOB,
+        vole_0_msg1: &[ZS<Round1Output>],
+    ) -> Result<Vec<RVOLEOutput>, ABTOBError> {
+        let start = self.received();
+        let end = start + vole_0_msg1.len();
+        if end > 4 * self.eta_m {
+            return Err(ABTOBError::InvalidMessage);
+        }
+
+        let vole_0_outputs = map_seeded(start, &self.vole_0_seeds[start..end], |i, rng| {
+            let mut vole_0_msg2_i = RVOLEOutput::new(1);
+            RVOLESender::process(
+                &self.vole_0_sids[i],
+                &self.p,
+                &ot_seeds_ob.receiver_ot_seed_0,
+                &self.vole_0_alpha_values[i],
+                &vole_0_msg1[i - start],
+                &mut vole_0_msg2_i,
+                1,
+                rng,
+            )
+            .map(|sender_shares| (vole_0_msg2_i, sender_shares))
+        });
+
+        let mut vole_0_msg2 = Vec::with_capacity(vole_0_msg1.len());
+        for output in vole_0_outputs {
+            let (vole_0_msg2_i, sender_shares) = match output {
+                Ok(v) => v,
+                Err(e) => return Err(ABTOBError::AbortProtocolAndBanOtherParty(e)),
+            };
+            vole_0_msg2.push(vole_0_msg2_i);
+            self.vole_0_sender_shares.push(sender_shares);
+        }
+
+        Ok(vole_0_msg2)
+    }
+
+    /// Processes the rest of ABTMsg1 once all vole_0 first messages are in.
+    /// The returned ABTMsg2 holds no vole_0 second messages, those were
+    /// returned by process_vole_0.
+    pub(crate) fn finish<R: CryptoRng + RngCore>(
+        self,
+        ot_seeds_ob: &CFMInitOTSeedsOB,
+        vole_1_msg1: &Round1Output,
+        commitment: [u8; 32],
+        rng: &mut R,
+    ) -> Result<(ABTStateOBR1, Vec<Share>, Vec<TripleShare>, ABTMsg2), ABTOBError> {
+        if self.received() != 4 * self.eta_m {
+            return Err(ABTOBError::InvalidMessage);
+        }
+
+        let ABTMsg1Processor {
+            session_id,
+            p,
+            eta_i,
+            eta_m,
+            vole_0_alpha_values,
+            vole_0_sender_shares,
+            ..
+        } = self;
+        let session_id = &session_id;
+        let params = DynResidueParams::new(&p);
+
+        let mut t = Transcript::new(&AUTH_BEAVER_TRIPLES_LABEL);
+        t.append_message(b"session-id", session_id);
+        t.append_u64(b"vole-instance", 1u64);
         let mut vole_sid = [0u8; 32];
         t.challenge_bytes(b"vole-sid", vole_sid.as_mut());
 
-        let sender_shares = match RVOLESender::process(
+        let mut vole_1_msg2 = RVOLEOutput::new(6 * eta_m + eta_i);
+        let vole_1_alpha_values = vec![scalar_from_bytes(params, rng.gen()); 6 * eta_m + eta_i];
+        let vole_1_sender_shares = match RVOLESender::process(
             &vole_sid,
+            &p,
             &ot_seeds_ob.receiver_ot_seed_0,
-            &vole_0_alpha_values[i],
-            &msg1.vole_0_msg1[i],
-            &mut vole_0_msg2[i],
-            1,
+            &vole_1_alpha_values,
+            vole_1_msg1,
+            &mut vole_1_msg2,
+            6 * eta_m + eta_i,
             &mut *rng,
         ) {
             Ok(v) => v,
-            Err(_) => return Err(ABTOBError::AbortProtocolAndBanOtherParty),
+            Err(e) => return Err(ABTOBError::AbortProtocolAndBanOtherParty(e)),
         };
 
-        vole_0_sender_shares.push(sender_shares);
-    }
+        let mut t = Transcript::new(&AUTH_BEAVER_TRIPLES_LABEL);
+        t.append_message(b"session-id", session_id);
+        t.append_u64(b"vole-instance", 2u64);
+        let mut vole_sid = [0u8; 32];
+        t.challenge_bytes(b"vole-sid", vole_sid.as_mut());
 
-    let mut t = Transcript::new(&AUTH_BEAVER_TRIPLES_LABEL);
-    t.append_message(b"session-id", session_id);
-    t.append_u64(b"vole-instance", 1u64);
-    let mut vole_sid = [0u8; 32];
-    t.challenge_bytes(b"vole-sid", vole_sid.as_mut());
-
-    let mut vole_1_msg2 = RVOLEOutput::new(6 * eta_m + eta_i);
-    let vole_1_alpha_values = vec![scalar_from_bytes(params, rng.gen()); 6 * eta_m + eta_i];
-    let vole_1_sender_shares = match RVOLESender::process(
-        &vole_sid,
-        &ot_seeds_ob.receiver_ot_seed_0,
-        &vole_1_alpha_values,
-        &msg1.vole_1_msg1,
-        &mut vole_1_msg2,
-        6 * eta_m + eta_i,
-        &mut *rng,
-    ) {
-        Ok(v) => v,
-        Err(_) => return Err(ABTOBError::AbortProtocolAndBanOtherParty),
-    };
+        let mut vole_2_msg1 = ZS::<Round1Output>::default();
+        let vole_2_receiver = RVOLEReceiver::new(
+            vole_sid,
+            p,
+            &ot_seeds_ob.sender_ot_seed_1,
+            &mut vole_2_msg1,
+            6 * eta_m + eta_i,
+            &mut *rng,
+        );
 
-    let mut t = Transcript::new(&AUTH_BEAVER_TRIPLES_LABEL);
-    t.append_message(b"session-id", session_id);
-    t.append_u64(b"vole-instance", 2u64);
-    let mut vole_sid = [0u8; 32];
-    t.challenge_bytes(b"vole-sid", vole_sid.as_mut());
-
-    let mut vole_2_msg1 = ZS::<Round1Output>::default();
-    let vole_2_receiver = RVOLEReceiver::new(
-        vole_sid,
-        &ot_seeds_ob.sender_ot_seed_1,
-        &mut vole_2_msg1,
-        6 * eta_m + eta_i,
-        &mut *rng,
-    );
-
-    // pre-create mul_shares_ob with big_delta = 0 and delta = 0
-    let mut mul_shares_ob: Vec<TripleShare> = vec![];
-
-    let mut gamma_ob = vec![U128::ZERO; 6 * eta_m + eta_i];
-    for i in 0..(2 * eta_m) {
-        let x_ob_i = vole_0_alpha_values[2 * eta_m + i][0];
-        let y_ob_i = vole_0_alpha_values[i][0];
-        let z_ob_i = DynResidue::new(&vole_0_sender_shares[i][0], params);
-        let z_ob_i = z_ob_i.add(&DynResidue::new(
-            &vole_0_sender_shares[2 * eta_m + i][0],
-            params,
-        ));
-        let z_ob_i =
-            z_ob_i.add(&DynResidue::new(&y_ob_i, params).mul(&DynResidue::new(&x_ob_i, params)));
-
-        let p_ob_i = &vole_1_alpha_values[i];
-        let p_ob_2i = &vole_1_alpha_values[2 * eta_m + i];
-        let p_ob_4i = &vole_1_alpha_values[4 * eta_m + i];
-
-        let x_ob_i_dyn_res = DynResidue::new(&x_ob_i, params);
-        let y_ob_i_dyn_res = DynResidue::new(&y_ob_i, params);
-        let p_ob_i_dyn_res = DynResidue::new(p_ob_i, params);
-        let p_ob_2i_dyn_res = DynResidue::new(p_ob_2i, params);
-        let p_ob_4i_dyn_res = DynResidue::new(p_ob_4i, params);
-
-        gamma_ob[i] = x_ob_i_dyn_res.sub(&p_ob_i_dyn_res).retrieve();
-        gamma_ob[eta_m * 2 + i] = y_ob_i_dyn_res.sub(&p_ob_2i_dyn_res).retrieve();
-        gamma_ob[eta_m * 4 + i] = z_ob_i.sub(&p_ob_4i_dyn_res).retrieve();
-
-        mul_shares_ob.push(TripleShare {
-            x: Share {
-                value: x_ob_i,
-                big_m: vole_1_sender_shares[i],
-                big_delta: U128::ZERO,
-                delta: U128::ZERO,
-            },
-            y: Share {
-                value: y_ob_i,
-                big_m: vole_1_sender_shares[2 * eta_m + i],
-                big_delta: U128::ZERO,
-                delta: U128::ZERO,
-            },
-            z: Share {
-                value: z_ob_i.retrieve(),
-                big_m: vole_1_sender_shares[4 * eta_m + i],
-                big_delta: U128::ZERO,
-                delta: U128::ZERO,
-            },
-        });
-    }
+        // pre-create mul_shares_ob with big_delta = 0 and delta = 0
+        let mut mul_shares_ob: Vec<TripleShare> = vec![];
+
+        let mut gamma_ob = vec![U128::ZERO; 6 * eta_m + eta_i];
+        for i in 0..(2 * eta_m) {
+            let x_ob_i = vole_0_alpha_values[2 * eta_m + i][0];
+            let y_ob_i = vole_0_alpha_values[i][0];
+            let z_ob_i = DynResidue::new(&vole_0_sender_shares[i][0], params);
+            let z_ob_i = z_ob_i.add(&DynResidue::new(
+                &vole_0_sender_shares[2 * eta_m + i][0],
+                params,
+            ));
+            let z_ob_i =
+                z_ob_i.add(&DynResidue::new(&y_ob_i, params).mul(&DynResidue::new(&x_ob_i, params)));
+
+            let p_ob_i = &vole_1_alpha_values[i];
+            let p_ob_2i = &vole_1_alpha_values[2 * eta_m + i];
+            let p_ob_4i = &vole_1_alpha_values[4 * eta_m + i];
+
+            let x_ob_i_dyn_res = DynResidue::new(&x_ob_i, params);
+            let y_ob_i_dyn_res = DynResidue::new(&y_ob_i, params);
+            let p_ob_i_dyn_res = DynResidue::new(p_ob_i, params);
+            let p_ob_2i_dyn_res = DynResidue::new(p_ob_2i, params);
+            let p_ob_4i_dyn_res = DynResidue::new(p_ob_4i, params);
+
+            gamma_ob[i] = x_ob_i_dyn_res.sub(&p_ob_i_dyn_res).retrieve();
+            gamma_ob[eta_m * 2 + i] = y_ob_i_dyn_res.sub(&p_ob_2i_dyn_res).retrieve();
+            gamma_ob[eta_m * 4 + i] = z_ob_i.sub(&p_ob_4i_dyn_res).retrieve();
+
+            mul_shares_ob.push(TripleShare {
+                x: Share {
+                    value: x_ob_i,
+                    big_m: vole_1_sender_shares[i],
+                    big_delta: U128::ZERO,
+                    delta: U128::ZERO,
+                },
+                y: Share {
+                    value: y_ob_i,
+                    big_m: vole_1_sender_shares[2 * eta_m + i],
+                    big_delta: U128::ZERO,
+                    delta: U128::ZERO,
+                },
+                z: Share {
+                    value: z_ob_i.retrieve(),
+                    big_m: vole_1_sender_shares[4 * eta_m + i],
+                    big_delta: U128::ZERO,
+                    delta: U128::ZERO,
+                },
+            });
+        }
 
-    // pre-create input_shares_ob with big_delta = 0 and delta = 0
-    let mut input_shares_ob: Vec<Share> = vec![];
-    for i in 6 * eta_m..6 * eta_m + eta_i {
-        let share_ob_i = scalar_from_bytes(params, rng.gen());
-        let share_p_ob_i = &vole_1_alpha_values[i];
+        // pre-create input_shares_ob with big_delta = 0 and delta = 0
+        let mut input_shares_ob: Vec<Share> = vec![];
+        for i in 6 * eta_m..6 * eta_m + eta_i {
+            let share_ob_i = scalar_from_bytes(params, rng.gen());
+            let share_p_ob_i = &vole_1_alpha_values[i];
 
-        let share_ob_i_dyn_res = DynResidue::new(&share_ob_i, params);
-        let share_p_ob_i_dyn_res = DynResidue::new(share_p_ob_i, params);
+            let share_ob_i_dyn_res = DynResidue::new(&share_ob_i, params);
+            let share_p_ob_i_dyn_res = DynResidue::new(share_p_ob_i, params);
 
-        gamma_ob[i] = share_ob_i_dyn_res.sub(&share_p_ob_i_dyn_res).retrieve();
+            gamma_ob[i] = share_ob_i_dyn_res.sub(&share_p_ob_i_dyn_res).retrieve();
 
-        input_shares_ob.push(Share {
-            value: share_ob_i,
-            big_m: vole_1_sender_shares[i],
-            big_delta: U128::ZERO,
-            delta: U128::ZERO,
-        });
-    }
+            input_shares_ob.push(Share {
+                value: share_ob_i,
+                big_m: vole_1_sender_shares[i],
+                big_delta: U128::ZERO,
+                delta: U128::ZERO,
+            });
+        }
 
-    let mut rho2 = [0u8; 32];
-    rng.fill_bytes(rho2.as_mut());
+        let mut rho2 = [0u8; 32];
+        rng.fill_bytes(rho2.as_mut());
 
-    let state = ABTStateOBR1 {
-        session_id: *session_id,
-        p,
-        eta_i,
-        eta_m,
-        vole_2_receiver,
-        commitment: msg1.commitment,
-        rho2,
-    };
+        let state = ABTStateOBR1 {
+            session_id: *session_id,
+            p,
+            eta_i,
+            eta_m,
+            vole_2_receiver,
+            commitment,
+            rho2,
+        };
 
-    let msg2 = ABTMsg2 {
-        session_id: *session_id,
-        vole_0_msg2,
-        vole_1_msg2,
-        vole_2_msg1,
-        gamma_ob,
-        rho2,
-    };
+        let msg2 = ABTMsg2 {
+            session_id: *session_id,
+            vole_0_msg2: vec![],
+            vole_1_msg2,
+            vole_2_msg1,
+            gamma_ob,
+            rho2,
+        };
 
-    Ok((state, input_shares_ob, mul_shares_ob, msg2))
+        Ok((state, input_shares_ob, mul_shares_ob, msg2))
+    }
 }
 
 /// CB processes ABTMsg2 from OB
@@ -802,202 +1103,278 @@ pub fn abt_process_msg2<R: CryptoRng + RngCore>(
     msg2: &ABTMsg2,
     rng: &mut R,
 ) -> Result<(ABTStateCBR2, Vec<Share>, Vec<TripleShare>, ABTMsg3), ABTCBError> {
-    let params = DynResidueParams::new(&state_cb.p);
-    let eta_m = state_cb.eta_m;
-    let eta_i = state_cb.eta_i;
-
     if state_cb.session_id != msg2.session_id {
         return Err(ABTCBError::InvalidSessionID);
     }
-    if state_cb.vole_0_receivers.len() != 4 * eta_m {
-        return Err(ABTCBError::InvalidState);
-    }
-    if msg2.vole_0_msg2.len() != 4 * eta_m {
+    if msg2.vole_0_msg2.len() != 4 * state_cb.eta_m {
         return Err(ABTCBError::InvalidMessage);
     }
-    if msg2.gamma_ob.len() != 6 * eta_m + eta_i {
+    if msg2.gamma_ob.len() != 6 * state_cb.eta_m + state_cb.eta_i {
         return Err(ABTCBError::InvalidMessage);
     }
 
-    let mut t = Transcript::new(&AUTH_BEAVER_TRIPLES_LABEL);
-    t.append_message(b"session-id", &state_cb.session_id);
-    t.append_u64(b"vole-instance", 2u64);
-    let mut vole_sid = [0u8; 32];
-    t.challenge_bytes(b"vole-sid", vole_sid.as_mut());
-
-    let mut vole_0_receiver_shares = vec![];
-    let mut vole_0_beta_values = vec![];
-    for i in 0..4 * eta_m {
-        let (receiver, beta) = &state_cb.vole_0_receivers[i];
-        let receiver_shares = match receiver.process(&msg2.vole_0_msg2[i], 1) {
-            Ok(v) => v,
-            Err(_) => return Err(ABTCBError::AbortProtocolAndBanOtherParty),
-        };
-        vole_0_receiver_shares.push(receiver_shares);
-        vole_0_beta_values.push(*beta);
+    if state_cb.vole_0_receivers.len() != 4 * state_cb.eta_m {
+        return Err(ABTCBError::InvalidState);
     }
 
-    let (receiver, beta) = &state_cb.vole_1_receiver;
-    let vole_1_receiver_shares = match receiver.process(&msg2.vole_1_msg2, 6 * eta_m + eta_i) {
-        Ok(v) => v,
-        Err(_) => return Err(ABTCBError::AbortProtocolAndBanOtherParty),
-    };
-    let vole_1_beta_value = *beta;
-
-    let mut vole_2_msg2 = RVOLEOutput::new(6 * eta_m + eta_i);
-    let vole_2_alpha_values = vec![scalar_from_bytes(params, rng.gen()); 6 * eta_m + eta_i];
-    let vole_2_sender_shares = match RVOLESender::process(
-        &vole_sid,
-        &ot_seeds_cb.receiver_ot_seed_1,
-        &vole_2_alpha_values,
+    let mut processor = ABTMsg2Processor::new(state_cb.eta_m);
+    processor.process_vole_0(&state_cb.vole_0_receivers, &msg2.vole_0_msg2)?;
+    processor.finish(
+        state_cb,
+        ot_seeds_cb,
+        &msg2.vole_1_msg2,
         &msg2.vole_2_msg1,
-        &mut vole_2_msg2,
-        6 * eta_m + eta_i,
-        &mut *rng,
-    ) {
-        Ok(v) => v,
-        Err(_) => return Err(ABTCBError::AbortProtocolAndBanOtherParty),
-    };
+        &msg2.gamma_ob,
+        &msg2.rho2,
+        rng,
+    )
+}
 
-    // create mul_shares for CB
-    let mut mul_shares_cb: Vec<TripleShare> = vec![];
-    let big_delta = vole_1_beta_value;
-    let big_delta_dyn_res = DynResidue::new(&big_delta, params);
+/// CB side of abt_process_msg2, split so that the vole_0 second messages
+/// can be processed in parts as they arrive
+pub(crate) struct ABTMsg2Processor {
+    vole_0_receiver_shares: Vec<Vec<U128>>,
+}
 
-    let mut gamma_cb = vec![U128::ZERO; 6 * eta_m + eta_i];
-    for i in 0..(2 * eta_m) {
-        let x_cb_i = vole_0_beta_values[i];
-        let y_cb_i = vole_0_beta_values[2 * eta_m + i];
-        let z_cb_i = DynResidue::new(&vole_0_receiver_shares[i][0], params);
-        let z_cb_i = z_cb_i.add(&DynResidue::new(
-            &vole_0_receiver_shares[2 * eta_m + i][0],
-            params,
-        ));
-        let z_cb_i =
-            z_cb_i.add(&DynResidue::new(&x_cb_i, params).mul(&DynResidue::new(&y_cb_i, params)));
+impl ABTMsg2Processor {
+    pub(crate) fn new(eta_m: usize) -> Self {
+        Self {
+            vole_0_receiver_shares: Vec::with_capacity(4 * eta_m),
+        }
+    }
 
-        let p_cb_i = vole_2_alpha_values[i];
-        let p_cb_2i = vole_2_alpha_values[2 * eta_m + i];
-        let p_cb_4i = vole_2_alpha_values[4 * eta_m + i];
+    /// Number of vole_0 second messages processed so far
+    pub(crate) fn received(&self) -> usize {
+        self.vole_0_receiver_shares.len()
+    }
 
-        let x_cb_i_dyn_res = DynResidue::new(&x_cb_i, params);
-        let y_cb_i_dyn_res = DynResidue::new(&y_cb_i, params);
-        let p_cb_i_dyn_res = DynResidue::new(&p_cb_i, params);
-        let p_cb_2i_dyn_res = DynResidue::new(&p_cb_2i, params);
-        let p_cb_4i_dyn_res = DynResidue::new(&p_cb_4i, params);
+    /// Processes the next vole_0 second messages, in order, with the
+    /// receivers of their instances among `vole_0_receivers`
+    pub(crate) fn process_vole_0(
+        &mut self,
+        vole_0_receivers: &[(Box<RVOLEReceiver>, U128)],
+        vole_0_msg2: &[RVOLEOutput],
+    ) -> Result<(), ABTCBError> {
+        let start = self.received();
+        if start + vole_0_msg2.len() > vole_0_receivers.len() {
+            return Err(ABTCBError::InvalidMessage);
+        }
 
-        gamma_cb[i] = x_cb_i_dyn_res.sub(&p_cb_i_dyn_res).retrieve();
-        gamma_cb[eta_m * 2 + i] = y_cb_i_dyn_res.sub(&p_cb_2i_dyn_res).retrieve();
-        gamma_cb[eta_m * 4 + i] = z_cb_i.sub(&p_cb_4i_dyn_res).retrieve();
+        let vole_0_outputs = map_indices(vole_0_msg2.len(), |i| {
+            let (receiver, _) = &vole_0_receivers[start + i];
+            receiver.process(&vole_0_msg2[i], 1)
+        });
 
-        let delta_x = DynResidue::new(&vole_1_receiver_shares[i], params)
-            .add(&big_delta_dyn_res.mul(&DynResidue::new(&msg2.gamma_ob[i], params)));
+        for output in vole_0_outputs {
+            let receiver_shares = match output {
+                Ok(v) => v,
+                Err(e) => return Err(ABTCBError::AbortProtocolAndBanOtherParty(e)),
+            };
+            self.vole_0_receiver_shares.push(receiver_shares);
+        }
 
-        let delta_y = DynResidue::new(&vole_1_receiver_shares[eta_m * 2 + i], params)
-            .add(&big_delta_dyn_res.mul(&DynResidue::new(&msg2.gamma_ob[eta_m * 2 + i], params)));
+        Ok(())
+    }
 
-        let delta_z = DynResidue::new(&vole_1_receiver_shares[eta_m * 4 + i], params)
-            .add(&big_delta_dyn_res.mul(&DynResidue::new(&msg2.gamma_ob[eta_m * 4 + i], params)));
+    /// Processes the rest of ABTMsg2 once all vole_0 second messages are in
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn finish<R: CryptoRng + RngCore>(
+        self,
+        state_cb: &ABTStateCBR1,
+        ot_seeds_cb: &CFMInitOTSeedsCB,
+        vole_1_msg2: &RVOLEOutput,
+        vole_2_msg1: &Round1Output,
+        gamma_ob: &[U128],
+        rho2: &[u8; 32],
+        rng: &mut R,
+    ) -> Result<(ABTStateCBR2, Vec<Share>, Vec<TripleShare>, ABTMsg3), ABTCBError> {
+        let params = DynResidueParams::new(&state_cb.p);
+        let eta_m = state_cb.eta_m;
+        let eta_i = state_cb.eta_i;
+
+        if state_cb.vole_0_receivers.len() != 4 * eta_m {
+            return Err(ABTCBError::InvalidState);
+        }
+        if self.received() != 4 * eta_m {
+            return Err(ABTCBError::InvalidMessage);
+        }
+        if gamma_ob.len() != 6 * eta_m + eta_i {
+            return Err(ABTCBError::InvalidMessage);
+        }
 
-        mul_shares_cb.push(TripleShare {
-            x: Share {
-                value: x_cb_i,
-                big_m: vole_2_sender_shares[i],
-                big_delta,
-                delta: delta_x.retrieve(),
-            },
-            y: Share {
-                value: y_cb_i,
-                big_m: vole_2_sender_shares[eta_m * 2 + i],
-                big_delta,
-                delta: delta_y.retrieve(),
-            },
-            z: Share {
-                value: z_cb_i.retrieve(),
-                big_m: vole_2_sender_shares[eta_m * 4 + i],
-                big_delta,
-                delta: delta_z.retrieve(),
-            },
-        });
-    }
+        let vole_0_receiver_shares = self.vole_0_receiver_shares;
+        let vole_0_beta_values: Vec<U128> =
+            state_cb.vole_0_receivers.iter().map(|(_, beta)| *beta).collect();
 
-    // create input_shares for CB
-    let mut input_shares_cb: Vec<Share> = vec![];
-    for i in 6 * eta_m..6 * eta_m + eta_i {
-        let share_cb_i = scalar_from_bytes(params, rng.gen());
-        let p_cb_i = vole_2_alpha_values[i];
+        let mut t = Transcript::new(&AUTH_BEAVER_TRIPLES_LABEL);
+        t.append_message(b"session-id", &state_cb.session_id);
+        t.append_u64(b"vole-instance", 2u64);
+        let mut vole_sid = [0u8; 32];
+        t.challenge_bytes(b"vole-sid", vole_sid.as_mut());
 
-        let share_cb_i_dyn_res = DynResidue::new(&share_cb_i, params);
-        let p_cb_i_dyn_res = DynResidue::new(&p_cb_i, params);
+        let (receiver, beta) = &state_cb.vole_1_receiver;
+        let vole_1_receiver_shares = match receiver.process(vole_1_msg2, 6 * eta_m + eta_i) {
+            Ok(v) => v,
+            Err(e) => return Err(ABTCBError::AbortProtocolAndBanOtherParty(e)),
+        };
+        let vole_1_beta_value = *beta;
 
-        gamma_cb[i] = share_cb_i_dyn_res.sub(&p_cb_i_dyn_res).retrieve();
+        let mut vole_2_msg2 = RVOLEOutput::new(6 * eta_m + eta_i);
+        let vole_2_alpha_values = vec![scalar_from_bytes(params, rng.gen()); 6 * eta_m + eta_i];
+        let vole_2_sender_shares = match RVOLESender::process(
+            &vole_sid,
+            &state_cb.p,
+            &ot_seeds_cb.receiver_ot_seed_1,
+            &vole_2_alpha_values,
+            vole_2_msg1,
+            &mut vole_2_msg2,
+            6 * eta_m + eta_i,
+            &mut *rng,
+        ) {
+            Ok(v) => v,
+            Err(e) => return Err(ABTCBError::AbortProtocolAndBanOtherParty(e)),
+        };
 
-        let delta_x = DynResidue::new(&vole_1_receiver_shares[i], params)
-            .add(&big_delta_dyn_res.mul(&DynResidue::new(&msg2.gamma_ob[i], params)));
+        // create mul_shares for CB
+        let mut mul_shares_cb: Vec<TripleShare> = vec![];
+        let big_delta = vole_1_beta_value;
+        let big_delta_dyn_res = DynResidue::new(&big_delta, params);
+
+        let mut gamma_cb = vec![U128::ZERO; 6 * eta_m + eta_i];
+        for i in 0..(2 * eta_m) {
+            let x_cb_i = vole_0_beta_values[i];
+            let y_cb_i = vole_0_beta_values[2 * eta_m + i];
+            let z_cb_i = DynResidue::new(&vole_0_receiver_shares[i][0], params);
+            let z_cb_i = z_cb_i.add(&DynResidue::new(
+                &vole_0_receiver_shares[2 * eta_m + i][0],
+                params,
+            ));
+            let z_cb_i =
+                z_cb_i.add(&DynResidue::new(&x_cb_i, params).mul(&DynResidue::new(&y_cb_i, params)));
+
+            let p_cb_i = vole_2_alpha_values[i];
+            let p_cb_2i = vole_2_alpha_values[2 * eta_m + i];
+            let p_cb_4i = vole_2_alpha_values[4 * eta_m + i];
+
+            let x_cb_i_dyn_res = DynResidue::new(&x_cb_i, params);
+            let y_cb_i_dyn_res = DynResidue::new(&y_cb_i, params);
+            let p_cb_i_dyn_res = DynResidue::new(&p_cb_i, params);
+            let p_cb_2i_dyn_res = DynResidue::new(&p_cb_2i, params);
+            let p_cb_4i_dyn_res = DynResidue::new(&p_cb_4i, params);
+
+            gamma_cb[i] = x_cb_i_dyn_res.sub(&p_cb_i_dyn_res).retrieve();
+            gamma_cb[eta_m * 2 + i] = y_cb_i_dyn_res.sub(&p_cb_2i_dyn_res).retrieve();
+            gamma_cb[eta_m * 4 + i] = z_cb_i.sub(&p_cb_4i_dyn_res).retrieve();
+
+            let delta_x = DynResidue::new(&vole_1_receiver_shares[i], params)
+                .add(&big_delta_dyn_res.mul(&DynResidue::new(&gamma_ob[i], params)));
+
+            let delta_y = DynResidue::new(&vole_1_receiver_shares[eta_m * 2 + i], params)
+                .add(&big_delta_dyn_res.mul(&DynResidue::new(&gamma_ob[eta_m * 2 + i], params)));
+
+            let delta_z = DynResidue::new(&vole_1_receiver_shares[eta_m * 4 + i], params)
+                .add(&big_delta_dyn_res.mul(&DynResidue::new(&gamma_ob[eta_m * 4 + i], params)));
+
+            mul_shares_cb.push(TripleShare {
+                x: Share {
+                    value: x_cb_i,
+                    big_m: vole_2_sender_shares[i],
+                    big_delta,
+                    delta: delta_x.retrieve(),
+                },
+                y: Share {
+                    value: y_cb_i,
+                    big_m: vole_2_sender_shares[eta_m * 2 + i],
+                    big_delta,
+                    delta: delta_y.retrieve(),
+                },
+                z: Share {
+                    value: z_cb_i.retrieve(),
+                    big_m: vole_2_sender_shares[eta_m * 4 + i],
+                    big_delta,
+                    delta: delta_z.retrieve(),
+                },
+            });
+        }
 
-        input_shares_cb.push(Share {
-            value: share_cb_i,
-            big_m: vole_2_sender_shares[i],
-            big_delta,
-            delta: delta_x.retrieve(),
-        });
-    }
+        // create input_shares for CB
+        let mut input_shares_cb: Vec<Share> = vec![];
+        for i in 6 * eta_m..6 * eta_m + eta_i {
+            let share_cb_i = scalar_from_bytes(params, rng.gen());
+            let p_cb_i = vole_2_alpha_values[i];
 
-    // rho value
-    let mut t = Transcript::new(&RO_RHO_LABEL);
-    t.append_message(b"session-id", &state_cb.session_id);
-    t.append_message(b"rho1", &state_cb.rho1);
-    t.append_message(b"rho2", &msg2.rho2);
-    let mut rho_bytes = [0u8; KAPPA_BYTES];
-    t.challenge_bytes(b"rho-value", rho_bytes.as_mut());
-    let rho = scalar_from_bytes(params, rho_bytes);
+            let share_cb_i_dyn_res = DynResidue::new(&share_cb_i, params);
+            let p_cb_i_dyn_res = DynResidue::new(&p_cb_i, params);
 
-    // Open(e), Open(d)
-    let mut mul_shares_state_cb = vec![MulSharesState::default(); eta_m];
-    let mut mul_shares_open = vec![MulSharesOpen::default(); eta_m];
-    for i in 0..eta_m {
-        let triple_share_i = &mul_shares_cb[i];
-        let triple_share_eta_plus_i = &mul_shares_cb[eta_m + i];
-        let x_i = &triple_share_i.x;
-        let y_i = &triple_share_i.y;
-        let x_eta_plus_i_mul_rho = &triple_share_eta_plus_i.x.mul_const(&rho, params);
-        let y_eta_plus_i_mul_rho = &triple_share_eta_plus_i.y.mul_const(&rho, params);
-        let z_eta_plus_i_mul_rho_square = &triple_share_eta_plus_i
-            .z
-            .mul_const(&rho, params)
-            .mul_const(&rho, params);
+            gamma_cb[i] = share_cb_i_dyn_res.sub(&p_cb_i_dyn_res).retrieve();
 
-        let (mul_state, mul_open) = multiply_shares_open(
-            x_i,
-            y_i,
-            x_eta_plus_i_mul_rho,
-            y_eta_plus_i_mul_rho,
-            z_eta_plus_i_mul_rho_square,
-            params,
-        );
-        mul_shares_state_cb[i] = mul_state;
-        mul_shares_open[i] = mul_open;
-    }
+            let delta_x = DynResidue::new(&vole_1_receiver_shares[i], params)
+                .add(&big_delta_dyn_res.mul(&DynResidue::new(&gamma_ob[i], params)));
 
-    let msg3 = ABTMsg3 {
-        session_id: state_cb.session_id,
-        vole_2_msg2,
-        gamma_cb,
-        blind_factor: state_cb.blind_factor,
-        rho1: state_cb.rho1,
-        mul_shares_open,
-    };
+            input_shares_cb.push(Share {
+                value: share_cb_i,
+                big_m: vole_2_sender_shares[i],
+                big_delta,
+                delta: delta_x.retrieve(),
+            });
+        }
 
-    let state = ABTStateCBR2 {
-        session_id: state_cb.session_id,
-        p: state_cb.p,
-        eta_m,
-        mul_shares_state: mul_shares_state_cb,
-    };
+        // rho value
+        let mut t = Transcript::new(&RO_RHO_LABEL);
+        t.append_message(b"session-id", &state_cb.session_id);
+        t.append_message(b"rho1", &state_cb.rho1);
+        t.append_message(b"rho2", rho2);
+        let mut rho_bytes = [0u8; KAPPA_BYTES];
+        t.challenge_bytes(b"rho-value", rho_bytes.as_mut());
+        let rho = scalar_from_bytes(params, rho_bytes);
+
+        // Open(e), Open(d)
+        let mut mul_shares_state_cb = vec![MulSharesState::default(); eta_m];
+        let mut mul_shares_open = vec![MulSharesOpen::default(); eta_m];
+        for i in 0..eta_m {
+            let triple_share_i = &mul_shares_cb[i];
+            let triple_share_eta_plus_i = &mul_shares_cb[eta_m + i];
+            let x_i = &triple_share_i.x;
+            let y_i = &triple_share_i.y;
+            let x_eta_plus_i_mul_rho = &triple_share_eta_plus_i.x.mul_const(&rho, params);
+            let y_eta_plus_i_mul_rho = &triple_share_eta_plus_i.y.mul_const(&rho, params);
+            let z_eta_plus_i_mul_rho_square = &triple_share_eta_plus_i
+                .z
+                .mul_const(&rho, params)
+                .mul_const(&rho, params);
+
+            let (mul_state, mul_open) = multiply_shares_open(
+                x_i,
+                y_i,
+                x_eta_plus_i_mul_rho,
+                y_eta_plus_i_mul_rho,
+                z_eta_plus_i_mul_rho_square,
+                params,
+            );
+            mul_shares_state_cb[i] = mul_state;
+            mul_shares_open[i] = mul_open;
+        }
+
+        let msg3 = ABTMsg3 {
+            session_id: state_cb.session_id,
+            vole_2_msg2,
+            gamma_cb,
+            blind_factor: state_cb.blind_factor,
+            rho1: state_cb.rho1,
+            mul_shares_open,
+        };
 
-    mul_shares_cb.truncate(eta_m);
+        let state = ABTStateCBR2 {
+            session_id: state_cb.session_id,
+            p: state_cb.p,
+            eta_m,
+            mul_shares_state: mul_shares_state_cb,
+        };
 
-    Ok((state, input_shares_cb, mul_shares_cb, msg3))
+        mul_shares_cb.truncate(eta_m);
+
+        Ok((state, input_shares_cb, mul_shares_cb, msg3))
+    }
 }
 
 /// OB processes ABTMsg3 from CB
@@ -1030,7 +1407,7 @@ pub fn abt_process_msg3(
     let (receiver, beta) = &state_ob.vole_2_receiver;
     let vole_2_receiver_shares = match receiver.process(&msg3.vole_2_msg2, 6 * eta_m + eta_i) {
         Ok(v) => v,
-        Err(_) => return Err(ABTOBError::AbortProtocolAndBanOtherParty),
+        Err(e) => return Err(ABTOBError::AbortProtocolAndBanOtherParty(e)),
     };
 
     let vole_2_beta_value = *beta;
@@ -1242,6 +1619,115 @@ mod tests {
     use crypto_bigint::modular::runtime_mod::{DynResidue, DynResidueParams};
     use rand::Rng;
 
+    #[test]
+    fn test_auth_triples_reproducible() {
+        let mut rng = rand::thread_rng();
+        let init_session_id: [u8; 32] = rng.gen();
+        let (ot_seeds_cb, _) = generate_cfm_ot_seeds_for_test(&init_session_id, &mut rng);
+        let session_id: [u8; 32] = rng.gen();
+        let seed: [u8; 32] = rng.gen();
+
+        // same seed gives the same message, also with the parallel feature
+        let msg1_bytes: Vec<Vec<u8>> = (0..2)
+            .map(|_| {
+                let mut rng = ChaCha20Rng::from_seed(seed);
+                let (_, msg1) =
+                    abt_create_msg1(&session_id, &ot_seeds_cb, P, 2, 4, &mut rng).unwrap();
+                bincode::serialize(&msg1).unwrap()
+            })
+            .collect();
+        assert_eq!(msg1_bytes[0], msg1_bytes[1]);
+    }
+
+    #[test]
+    fn test_auth_triples_modulus() {
+        let mut rng = rand::thread_rng();
+        let init_session_id: [u8; 32] = rng.gen();
+        let (ot_seeds_cb, ot_seeds_ob) = generate_cfm_ot_seeds_for_test(&init_session_id, &mut rng);
+        let session_id: [u8; 32] = rng.gen();
+
+        // a bad modulus is an error, not a panic
+        let invalid = abt_create_msg1(&session_id, &ot_seeds_cb, U128::MAX, 2, 4, &mut rng);
+        assert!(matches!(invalid, Err(ABTCBError::InvalidModulus)));
+
+        // OB runs ABT over 2^128 - 159, CB over P
+        let (_, msg1) = abt_create_msg1(&session_id, &ot_seeds_cb, P, 2, 4, &mut rng).unwrap();
+        let second_prime = U128::from_be_hex("ffffffffffffffffffffffffffffff61");
+        let mismatch =
+            abt_process_msg1(&session_id, &ot_seeds_ob, second_prime, 2, 4, &msg1, &mut rng);
+        assert!(matches!(mismatch, Err(ABTOBError::ModulusMismatch)));
+    }
+
+    #[test]
+    fn test_auth_triples_batch() {
+        let mut rng = rand::thread_rng();
+        let init_session_id: [u8; 32] = rng.gen();
+
+        let (ot_seeds_cb, ot_seeds_ob) = generate_cfm_ot_seeds_for_test(&init_session_id, &mut rng);
+
+        let session_id: [u8; 32] = rng.gen();
+        let params = DynResidueParams::new(&P);
+        let sessions = 3;
+        let (eta_i, eta_m) = abt_batch_sizes(sessions, 5, 10);
+
+        let (state_cb_r1, msg1) =
+            abt_create_msg1(&session_id, &ot_seeds_cb, P, eta_i, eta_m, &mut rng).unwrap();
+        let (state_ob_r1, mut input_shares_ob, mut triple_shares_ob, msg2) =
+            abt_process_msg1(&session_id, &ot_seeds_ob, P, eta_i, eta_m, &msg1, &mut rng)
+                .unwrap();
+        let (state_cb_r2, input_shares_cb, triple_shares_cb, msg3) =
+            abt_process_msg2(&state_cb_r1, &ot_seeds_cb, &msg2, &mut rng).unwrap();
+        let (state_ob_r2, msg4) = abt_process_msg3(
+            &state_ob_r1,
+            &mut input_shares_ob,
+            &mut triple_shares_ob,
+            &msg3,
+        )
+        .unwrap();
+        let msg5 = abt_process_msg4(&state_cb_r2, &triple_shares_cb, &msg4).unwrap();
+        abt_process_msg5(&state_ob_r2, &msg5).unwrap();
+
+        // one vole_1 and one vole_2 instance for the whole batch
+        assert_eq!(msg1.vole_0_msg1.len(), 4 * eta_m);
+        assert_eq!(msg2.gamma_ob.len(), 6 * eta_m + eta_i);
+
+        let batch_cb = abt_split_batch(input_shares_cb, triple_shares_cb, sessions);
+        let batch_ob = abt_split_batch(input_shares_ob, triple_shares_ob, sessions);
+        assert_eq!(batch_cb.len(), sessions);
+
+        for (item_cb, item_ob) in batch_cb.iter().zip(&batch_ob) {
+            assert_eq!(item_cb.shares.len(), 5);
+            assert_eq!(item_ob.auth_triples.len(), 10);
+
+            for (share_cb, share_ob) in item_cb.shares.iter().zip(&item_ob.shares) {
+                // M^{x,CB} = x^CB * Delta^{OB} - delta^{x,OB}
+                let left = DynResidue::new(&share_cb.big_m, params)
+                    .add(&DynResidue::new(&share_ob.delta, params));
+                let right = DynResidue::new(&share_cb.value, params)
+                    .mul(&DynResidue::new(&share_ob.big_delta, params));
+                assert_eq!(left.retrieve(), right.retrieve());
+            }
+
+            for (triple_cb, triple_ob) in item_cb.auth_triples.iter().zip(&item_ob.auth_triples) {
+                // x * y = z
+                let x = DynResidue::new(&triple_cb.x.value, params)
+                    .add(&DynResidue::new(&triple_ob.x.value, params));
+                let y = DynResidue::new(&triple_cb.y.value, params)
+                    .add(&DynResidue::new(&triple_ob.y.value, params));
+                let z = DynResidue::new(&triple_cb.z.value, params)
+                    .add(&DynResidue::new(&triple_ob.z.value, params));
+                assert_eq!(x.mul(&y).retrieve(), z.retrieve());
+
+                // M^{z,CB} = z^CB * Delta^{OB} - delta^{z,OB}
+                let left = DynResidue::new(&triple_cb.z.big_m, params)
+                    .add(&DynResidue::new(&triple_ob.z.delta, params));
+                let right = DynResidue::new(&triple_cb.z.value, params)
+                    .mul(&DynResidue::new(&triple_ob.z.big_delta, params));
+                assert_eq!(left.retrieve(), right.retrieve());
+            }
+        }
+    }
+
     #[test]
     fn test_auth_triples() {
         let mut rng = rand::thread_rng();
@@ -1256,7 +1742,7 @@ mod tests {
         let eta_m = 10;
 
         let (state_cb_r1, msg1) =
-            abt_create_msg1(&session_id, &ot_seeds_cb, p_prime, eta_i, eta_m, &mut rng);
+            abt_create_msg1(&session_id, &ot_seeds_cb, p_prime, eta_i, eta_m, &mut rng).unwrap();
         let (state_ob_r1, mut input_shares_ob, mut triple_shares_ob, msg2) = abt_process_msg1(
             &session_id,
             &ot_seeds_ob,
@@ -12,9 +12,10 @@ use cfm_lib::cfm_protocol::{
     cfm_create_msg1, cfm_process_msg1, cfm_process_msg10, cfm_process_msg11, cfm_process_msg12,
     cfm_process_msg13, cfm_process_msg14, cfm_process_msg15, cfm_process_msg16, cfm_process_msg17,
     cfm_process_msg18, cfm_process_msg2, cfm_process_msg3, cfm_process_msg4, cfm_process_msg5,
-    cfm_process_msg6, cfm_process_msg7, cfm_process_msg8, cfm_process_msg9,
+    cfm_process_msg6, cfm_process_msg7, cfm_process_msg8, cfm_process_msg9, OutputPolicy,
     NUMBER_OF_AUTH_BEAVER_TRIPLES, NUMBER_OF_SHARES,
 };
+use cfm_lib::ledger::{Ledger, LedgerKeys};
 use cfm_lib::utils::Customer;
 use cfm_lib::P;
 use rand::Rng;
@@ -124,7 +125,7 @@ fn main() {
 
     let start_abt_create_msg1 = std::time::Instant::now();
     let (state_cb_r1, msg1) =
-        abt_create_msg1(&session_id, &ot_seeds_cb, p, eta_i, eta_m, &mut rng); // cb DONE
+        abt_create_msg1(&session_id, &ot_seeds_cb, p, eta_i, eta_m, &mut rng).unwrap(); // cb DONE
 
     // let serialized_state_cb_r1 = bincode::serialize(&state_cb_r1).unwrap();
     // let serialized_msg1 = bincode::serialize(&msg1).unwrap();
@@ -279,12 +280,16 @@ fn main() {
     let (cfm_state_ob_r1, msg1) = cfm_create_msg1(
         &session_id,
         p,
-        big_l,
+        OutputPolicy::Both,
+        Some(big_l),
         big_x,
         &customer_y_bytes,
+        &Ledger::new(),
+        &ot_seeds_ob,
         &shares_ob,
         &mut rng,
-    );
+    )
+    .unwrap();
 
     // let serialized_cfm_state_ob_r1 = bincode::serialize(&cfm_state_ob_r1).unwrap();
     // let serialized_msg1 = bincode::serialize(&msg1).unwrap();
@@ -296,13 +301,22 @@ fn main() {
     // println!("CFM Step 1 time: {:?}", start_cfm_step.elapsed());
 
 
+    // CB gives each customer a ledger slot
+    let mut ledger_keys = LedgerKeys::new(&p, &mut rng);
+    ledger_keys.assign_slots(&big_y_bytes);
+
     // CB processes msg1
     let (cfm_state_cb_r1, msg2) = cfm_process_msg1(
         &session_id,
         p,
+        OutputPolicy::Both,
         big_l,
+        false,
         big_y_bytes,
         big_z,
+        None,
+        &ledger_keys,
+        &ot_seeds_cb,
         &shares_cb,
         &msg1,
         &mut rng,
@@ -525,6 +539,7 @@ fn main() {
 
     // OB processes msg18
     let b_ob_value = cfm_process_msg18(cfm_state_ob_r9, &msg18).unwrap();
+    let (b_cb_value, b_ob_value) = (b_cb_value.result().unwrap(), b_ob_value.result().unwrap());
 
     // let serialized_b_ob_value = bincode::serialize(&b_ob_value).unwrap();
 
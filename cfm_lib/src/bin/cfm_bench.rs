@@ -1,7 +1,7 @@
 use crypto_bigint::U64;
 use cfm_lib::auth_beaver_triples::{
-    abt_create_msg1, abt_process_msg1, abt_process_msg2, abt_process_msg3, abt_process_msg4,
-    abt_process_msg5, Share,
+    abt_batch_sizes, abt_create_msg1, abt_process_msg1, abt_process_msg2, abt_process_msg3,
+    abt_process_msg4, abt_process_msg5, abt_split_batch, Share,
 };
 use cfm_lib::cfm_init_protocol::{
     cfm_init_create_msg1, cfm_init_process_msg1, cfm_init_process_msg2, cfm_init_process_msg3,
@@ -11,9 +11,10 @@ use cfm_lib::cfm_protocol::{
     cfm_create_msg1, cfm_process_msg1, cfm_process_msg10, cfm_process_msg11, cfm_process_msg12,
     cfm_process_msg13, cfm_process_msg14, cfm_process_msg15, cfm_process_msg16, cfm_process_msg17,
     cfm_process_msg18, cfm_process_msg2, cfm_process_msg3, cfm_process_msg4, cfm_process_msg5,
-    cfm_process_msg6, cfm_process_msg7, cfm_process_msg8, cfm_process_msg9,
+    cfm_process_msg6, cfm_process_msg7, cfm_process_msg8, cfm_process_msg9, OutputPolicy,
     NUMBER_OF_AUTH_BEAVER_TRIPLES, NUMBER_OF_SHARES,
 };
+use cfm_lib::ledger::{Ledger, LedgerKeys};
 use cfm_lib::utils::Customer;
 use cfm_lib::P;
 use rand::Rng;
@@ -93,7 +94,8 @@ fn benchmark_cfm_with_sanction_list(size: usize) -> Vec<BenchmarkLog> {
 
     let start = Instant::now();
     let (state_cb_r1, abt_msg1) =
-        abt_create_msg1(&session_id, &ot_seeds_cb, p, eta_i, eta_m, &mut rng);
+        abt_create_msg1(&session_id, &ot_seeds_cb, p, eta_i, eta_m, &mut rng)
+            .expect("abt_create_msg1 failed");
     log_step(&mut log, "abt_create_msg1", start.elapsed(), &abt_msg1);
 
     let start = Instant::now();
@@ -168,17 +170,36 @@ fn benchmark_cfm_with_sanction_list(size: usize) -> Vec<BenchmarkLog> {
 
     let start_cfm = Instant::now();
     let start = Instant::now();
-    let (cfm_state_ob_r1, cfm_msg1) =
-        cfm_create_msg1(&session_id, p, big_l, big_x, &customer_y_bytes, &shares_ob, &mut rng);
+    let (cfm_state_ob_r1, cfm_msg1) = cfm_create_msg1(
+        &session_id,
+        p,
+        OutputPolicy::Both,
+        Some(big_l),
+        big_x,
+        &customer_y_bytes,
+        &Ledger::new(),
+        &ot_seeds_ob,
+        &shares_ob,
+        &mut rng,
+    )
+    .expect("cfm_create_msg1 failed");
     log_step(&mut log, "cfm_create_msg1", start.elapsed(), &cfm_msg1);
 
+    let mut ledger_keys = LedgerKeys::new(&p, &mut rng);
+    ledger_keys.assign_slots(&big_y_bytes);
+
     let start = Instant::now();
     let (cfm_state_cb_r1, cfm_msg2) = cfm_process_msg1(
         &session_id,
         p,
+        OutputPolicy::Both,
         big_l,
+        false,
         big_y_bytes,
         big_z,
+        None,
+        &ledger_keys,
+        &ot_seeds_cb,
         &shares_cb,
         &cfm_msg1,
         &mut rng,
@@ -328,6 +349,7 @@ fn benchmark_cfm_with_sanction_list(size: usize) -> Vec<BenchmarkLog> {
     .expect("cfm_process_msg18 failed");
     log_step(&mut log, "cfm_process_msg18", start.elapsed(), &b_ob_value);
 
+    let (b_cb_value, b_ob_value) = (b_cb_value.result().unwrap(), b_ob_value.result().unwrap());
     assert_eq!(b_cb_value, b_ob_value);
     let cfm_time = start_cfm.elapsed();
     println!("CFM protocol total time: {:?}", cfm_time);
@@ -336,7 +358,99 @@ fn benchmark_cfm_with_sanction_list(size: usize) -> Vec<BenchmarkLog> {
     log
 }
 
+/// Runs one ABT execution producing shares and triples for `sessions` CFM sessions.
+/// Reports total and per-triple time and bytes.
+fn benchmark_abt_batch(sessions: usize) {
+    let mut rng = rand::thread_rng();
+    println!("\n=== Batched ABT benchmark for {} CFM sessions ===", sessions);
+
+    let init_session_id: [u8; 32] = rng.gen();
+    let mut init_msg1 = CFMInitMsg1::default();
+    let state_ob = cfm_init_create_msg1(&init_session_id, &mut init_msg1, &mut rng);
+    let mut init_msg2 = CFMInitMsg2::default();
+    let state_cb = cfm_init_process_msg1(&init_session_id, &init_msg1, &mut init_msg2, &mut rng)
+        .expect("cfm_init_process_msg1 failed");
+    let mut init_msg3 = CFMInitMsg3::default();
+    let ot_seeds_ob = cfm_init_process_msg2(state_ob, &init_msg2, &mut init_msg3, &mut rng)
+        .expect("cfm_init_process_msg2 failed");
+    let ot_seeds_cb = cfm_init_process_msg3(state_cb, &init_msg3)
+        .expect("cfm_init_process_msg3 failed");
+
+    let mut log: Vec<BenchmarkLog> = Vec::new();
+    let session_id: [u8; 32] = rng.gen();
+    let p = P;
+    let (eta_i, eta_m) = abt_batch_sizes(sessions, NUMBER_OF_SHARES, NUMBER_OF_AUTH_BEAVER_TRIPLES);
+
+    let start_abt = Instant::now();
+    let start = Instant::now();
+    let (state_cb_r1, abt_msg1) =
+        abt_create_msg1(&session_id, &ot_seeds_cb, p, eta_i, eta_m, &mut rng)
+            .expect("abt_create_msg1 failed");
+    log_step(&mut log, "abt_create_msg1", start.elapsed(), &abt_msg1);
+
+    let start = Instant::now();
+    let (state_ob_r1, mut shares_ob, mut auth_triples_ob, abt_msg2) =
+        abt_process_msg1(&session_id, &ot_seeds_ob, p, eta_i, eta_m, &abt_msg1, &mut rng)
+            .expect("abt_process_msg1 failed");
+    log_step(&mut log, "abt_process_msg1", start.elapsed(), &abt_msg2);
+
+    let start = Instant::now();
+    let (state_cb_r2, shares_cb, auth_triples_cb, abt_msg3) =
+        abt_process_msg2(&state_cb_r1, &ot_seeds_cb, &abt_msg2, &mut rng)
+            .expect("abt_process_msg2 failed");
+    log_step(&mut log, "abt_process_msg2", start.elapsed(), &abt_msg3);
+
+    let start = Instant::now();
+    let (state_ob_r2, abt_msg4) =
+        abt_process_msg3(&state_ob_r1, &mut shares_ob, &mut auth_triples_ob, &abt_msg3)
+            .expect("abt_process_msg3 failed");
+    log_step(&mut log, "abt_process_msg3", start.elapsed(), &abt_msg4);
+
+    let start = Instant::now();
+    let abt_msg5 = abt_process_msg4(&state_cb_r2, &auth_triples_cb, &abt_msg4)
+        .expect("abt_process_msg4 failed");
+    log_step(&mut log, "abt_process_msg4", start.elapsed(), &abt_msg5);
+
+    let start = Instant::now();
+    abt_process_msg5(&state_ob_r2, &abt_msg5).expect("abt_process_msg5 failed");
+    log_step(&mut log, "abt_process_msg5", start.elapsed(), &());
+    let abt_time = start_abt.elapsed();
+
+    let batch_ob = abt_split_batch(shares_ob, auth_triples_ob, sessions);
+    let batch_cb = abt_split_batch(shares_cb, auth_triples_cb, sessions);
+    assert_eq!(batch_ob.len(), batch_cb.len());
+
+    let total_bytes: usize = log.iter().map(|entry| entry.size).sum();
+    println!("ABT total time: {:?} | Total size: {} bytes", abt_time, total_bytes);
+    println!(
+        "Per CFM session: {:?} | {} bytes",
+        abt_time / sessions as u32,
+        total_bytes / sessions
+    );
+    println!(
+        "Per triple: {:?} | {} bytes",
+        abt_time / eta_m as u32,
+        total_bytes / eta_m
+    );
+}
+
 fn main() {
+    // `cfm_bench abt-batch [N...]` benchmarks batched ABT for N CFM sessions per run
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("abt-batch") {
+        let mut batch_sizes: Vec<usize> = args[2..]
+            .iter()
+            .map(|n| n.parse().expect("batch size must be a number"))
+            .collect();
+        if batch_sizes.is_empty() {
+            batch_sizes = vec![1, 2, 4];
+        }
+        for sessions in batch_sizes {
+            benchmark_abt_batch(sessions);
+        }
+        return;
+    }
+
     // Run benchmarks for sanction list sizes: 1,000; 10,000; 100,000; 1,000,000.
     let sizes = [1000, 10_000, 100_000, 1_000_000];
     for &size in sizes.iter() {
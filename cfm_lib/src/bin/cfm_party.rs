@@ -0,0 +1,289 @@
+//! Runs one party of CFM init, ABT and CFM against a peer process.
+//!
+//! Usage: cfm_party --role ob|cb --config FILE (--listen ADDR | --connect ADDR)
+//!                  [--timeout SECS] [--key FILE --peer-key FILE [--arbiter FILE]]
+//!
+//! ADDR is `host:port` for TCP or `unix:PATH` for a Unix socket. Either role
+//! can listen. The run is driven by `runner::run_ob` or `runner::run_cb`
+//! over a framed transport, `--timeout` bounds the wait for each message
+//! (600 s by default). See `config/ob.json` and `config/cb.json` for the
+//! config. The optional `output_policy` of the config, `both`, `ob`, `cb` or
+//! `shared`, must be the same for both parties.
+//!
+//! With `--key` and `--peer-key`, hex files holding the 32 byte signing key of
+//! this party and the verifying key of the other, the run starts with the
+//! channel binding handshake of `runner::run_ob_authenticated`. Under the
+//! `shared` output policy, `--arbiter`, a hex file holding the verifying key
+//! of the arbiter, ends the run with the fair release of b.
+
+use cfm_lib::cfm_protocol::{CFMOutput, OutputPolicy};
+use cfm_lib::envelope::Role;
+use cfm_lib::frame::LENGTH_PREFIX_SIZE;
+use cfm_lib::identity::PartyIdentity;
+use cfm_lib::runner::{
+    run_cb, run_cb_authenticated, run_ob, run_ob_authenticated, CBInputs, OBInputs, RunReport,
+};
+use cfm_lib::schnorr::{SigningKey, VerifyingKey};
+#[cfg(unix)]
+use cfm_lib::transport::UnixTransport;
+use cfm_lib::transport::{TcpTransport, Transport};
+use cfm_lib::utils::Customer;
+use cfm_lib::P;
+use crypto_bigint::U64;
+use serde::Deserialize;
+use std::error::Error;
+use std::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const USAGE: &str = "usage: cfm_party --role ob|cb --config FILE (--listen ADDR | --connect ADDR) \
+                     [--timeout SECS] [--key FILE --peer-key FILE [--arbiter FILE]]";
+
+/// Party inputs, OB needs `customer` and `big_x`, CB `customers` and `big_z`.
+/// `big_l` is needed by CB, and by OB unless `private_limit` is set. With
+/// `private_limit` CB can give each customer its own limit in `big_l_y`
+#[derive(Deserialize)]
+struct Config {
+    big_l: Option<u64>,
+    private_limit: Option<bool>,
+    output_policy: Option<String>,
+    customer: Option<Customer>,
+    big_x: Option<u64>,
+    customers: Option<Vec<Customer>>,
+    big_z: Option<Vec<u64>>,
+    big_l_y: Option<Vec<u64>>,
+}
+
+struct Args {
+    role: Role,
+    config: PathBuf,
+    listen: bool,
+    addr: String,
+    timeout: Duration,
+    keys: Option<(PathBuf, PathBuf)>,
+    arbiter: Option<PathBuf>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut role = None;
+    let mut config = None;
+    let mut endpoint = None;
+    let mut timeout = Duration::from_secs(600);
+    let mut key = None;
+    let mut peer_key = None;
+    let mut arbiter = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("missing value for {}", arg));
+        match arg.as_str() {
+            "--role" => {
+                role = Some(match value()?.as_str() {
+                    "ob" => Role::OB,
+                    "cb" => Role::CB,
+                    other => return Err(format!("unknown role {}", other)),
+                })
+            }
+            "--config" => config = Some(PathBuf::from(value()?)),
+            "--listen" => endpoint = Some((true, value()?)),
+            "--connect" => endpoint = Some((false, value()?)),
+            "--timeout" => {
+                let secs = value()?;
+                let secs = secs
+                    .parse()
+                    .map_err(|_| format!("invalid timeout {}", secs))?;
+                timeout = Duration::from_secs(secs);
+            }
+            "--key" => key = Some(PathBuf::from(value()?)),
+            "--peer-key" => peer_key = Some(PathBuf::from(value()?)),
+            "--arbiter" => arbiter = Some(PathBuf::from(value()?)),
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+
+    let (listen, addr) = endpoint.ok_or("missing --listen or --connect")?;
+    let keys = match (key, peer_key) {
+        (Some(key), Some(peer_key)) => Some((key, peer_key)),
+        (None, None) => None,
+        _ => return Err("--key and --peer-key go together".to_string()),
+    };
+    if arbiter.is_some() && keys.is_none() {
+        return Err("--arbiter needs --key and --peer-key".to_string());
+    }
+    Ok(Args {
+        role: role.ok_or("missing --role")?,
+        config: config.ok_or("missing --config")?,
+        listen,
+        addr,
+        timeout,
+        keys,
+        arbiter,
+    })
+}
+
+fn open_transport(listen: bool, addr: &str) -> Result<Box<dyn Transport>, Box<dyn Error>> {
+    #[cfg(unix)]
+    if let Some(path) = addr.strip_prefix("unix:") {
+        if !listen {
+            return Ok(Box::new(UnixTransport::new(UnixStream::connect(path)?)));
+        }
+        // stale socket file of an earlier run
+        let _ = std::fs::remove_file(path);
+        let (stream, _) = UnixListener::bind(path)?.accept()?;
+        return Ok(Box::new(UnixTransport::new(stream)));
+    }
+
+    let stream = if listen {
+        TcpListener::bind(addr)?.accept()?.0
+    } else {
+        TcpStream::connect(addr)?
+    };
+    stream.set_nodelay(true)?;
+    Ok(Box::new(TcpTransport::new(stream)))
+}
+
+/// Output policy of the config, `both` if it has none
+fn output_policy(name: Option<&str>) -> Result<OutputPolicy, String> {
+    match name {
+        None | Some("both") => Ok(OutputPolicy::Both),
+        Some("ob") => Ok(OutputPolicy::OBOnly),
+        Some("cb") => Ok(OutputPolicy::CBOnly),
+        Some("shared") => Ok(OutputPolicy::Shared),
+        Some(other) => Err(format!("unknown output policy {}", other)),
+    }
+}
+
+/// 32 bytes from a hex file
+fn read_key(path: &Path) -> Result<[u8; 32], Box<dyn Error>> {
+    let bytes = hex::decode(std::fs::read_to_string(path)?.trim())?;
+    bytes
+        .try_into()
+        .map_err(|_| format!("{} does not hold a 32 byte key", path.display()).into())
+}
+
+/// Prints the time and the network bytes of each sub-protocol, messages
+/// plus frame prefixes. The session ids message of OB and the handshake
+/// are not counted.
+fn print_report(report: &RunReport) {
+    let wire = |bytes: usize, messages: usize| bytes + messages * LENGTH_PREFIX_SIZE;
+    let (mut total_sent, mut total_received) = (0, 0);
+    for stats in &report.stats {
+        let sent = wire(stats.bytes_sent, stats.messages_sent);
+        let received = wire(stats.bytes_received, stats.messages_received);
+        println!(
+            "{:<10} | Time: {:?} | Sent: {} bytes | Received: {} bytes",
+            format!("{:?}", stats.protocol),
+            stats.duration,
+            sent,
+            received
+        );
+        total_sent += sent;
+        total_received += received;
+    }
+    println!(
+        "{:<10} | Sent: {} bytes | Received: {} bytes",
+        "Total", total_sent, total_received
+    );
+    match report.output {
+        CFMOutput::Result(b) => println!("CFM result: {}", b),
+        CFMOutput::Hidden => println!("CFM result: hidden"),
+        CFMOutput::Shared(_) => match report.released {
+            Some(b) => println!("CFM result: {} (fair release)", b),
+            None => println!("CFM result: shared"),
+        },
+    }
+}
+
+fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    let config: Config = serde_json::from_slice(&std::fs::read(&args.config)?)?;
+    let private_limit = config.private_limit.unwrap_or(false);
+    let big_l = config.big_l.map(U64::from_u64);
+    let output_policy = output_policy(config.output_policy.as_deref())?;
+    let mut rng = rand::thread_rng();
+    let identity = match &args.keys {
+        Some((key, peer_key)) => Some(PartyIdentity {
+            key: SigningKey::from_bytes(&read_key(key)?),
+            peer_key: VerifyingKey(read_key(peer_key)?),
+        }),
+        None => None,
+    };
+    let arbiter = match &args.arbiter {
+        Some(path) => Some(VerifyingKey(read_key(path)?)),
+        None => None,
+    };
+    let mut transport = open_transport(args.listen, &args.addr)?;
+
+    let report = match args.role {
+        Role::OB => {
+            let customer = config.customer.ok_or("config needs customer for OB")?;
+            let big_x = config.big_x.ok_or("config needs big_x for OB")?;
+            let big_l = match private_limit {
+                true => None,
+                false => Some(big_l.ok_or("config needs big_l for OB")?),
+            };
+            let inputs = OBInputs {
+                output_policy,
+                big_l,
+                big_x: U64::from_u64(big_x),
+                y: customer.to_hash_bytes(),
+                arbiter,
+            };
+            match &identity {
+                Some(identity) => run_ob_authenticated(
+                    &mut transport,
+                    identity,
+                    P,
+                    &inputs,
+                    args.timeout,
+                    &mut rng,
+                )?,
+                None => run_ob(&mut transport, P, &inputs, args.timeout, &mut rng)?,
+            }
+        }
+        Role::CB => {
+            let customers = config.customers.ok_or("config needs customers for CB")?;
+            let big_z = config.big_z.ok_or("config needs big_z for CB")?;
+            let inputs = CBInputs {
+                output_policy,
+                big_l: big_l.ok_or("config needs big_l for CB")?,
+                private_limit,
+                big_y: customers.iter().map(Customer::to_hash_bytes).collect(),
+                big_z: big_z.into_iter().map(U64::from_u64).collect(),
+                big_l_y: config
+                    .big_l_y
+                    .map(|big_l_y| big_l_y.into_iter().map(U64::from_u64).collect()),
+                arbiter,
+            };
+            match &identity {
+                Some(identity) => run_cb_authenticated(
+                    &mut transport,
+                    identity,
+                    P,
+                    &inputs,
+                    args.timeout,
+                    &mut rng,
+                )?,
+                None => run_cb(&mut transport, P, &inputs, args.timeout, &mut rng)?,
+            }
+        }
+    };
+
+    print_report(&report);
+    Ok(())
+}
+
+fn main() {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+    if let Err(e) = run(args) {
+        eprintln!("cfm_party: {}", e);
+        std::process::exit(1);
+    }
+}
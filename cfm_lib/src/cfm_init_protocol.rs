@@ -1,3 +1,4 @@
+use crate::errors::CFMInitError;
 use crate::proto::ZS;
 use crate::sl_oblivious::endemic_ot::{
     EndemicOTMsg1, EndemicOTMsg2, EndemicOTReceiver, EndemicOTSender,
@@ -111,10 +112,10 @@ pub fn cfm_init_process_msg1<R: CryptoRng + RngCore>(
     msg1: &CFMInitMsg1,
     msg2: &mut CFMInitMsg2,
     rng: &mut R,
-) -> Result<CFMInitStateCB, &'static str> {
+) -> Result<CFMInitStateCB, CFMInitError> {
     msg2.session_id = *session_id;
-    let sender_output_0 =
-        EndemicOTSender::process(session_id, &msg1.msg1_a, &mut msg2.msg2_a, rng)?;
+    let sender_output_0 = EndemicOTSender::process(session_id, &msg1.msg1_a, &mut msg2.msg2_a, rng)
+        .map_err(CFMInitError::InvalidMsg1)?;
     let receiver = EndemicOTReceiver::new(session_id, &mut msg2.msg1_b, rng);
 
     let mut sender_ot_seed_0 = ZS::<SenderOTSeed>::default();
@@ -138,19 +139,24 @@ pub fn cfm_init_process_msg2<R: CryptoRng + RngCore>(
     msg2: &CFMInitMsg2,
     msg3: &mut CFMInitMsg3,
     rng: &mut R,
-) -> Result<CFMInitOTSeedsOB, &'static str> {
+) -> Result<CFMInitOTSeedsOB, CFMInitError> {
     msg3.session_id = state.session_id;
-    let receiver_output = state.receiver.process(&msg2.msg2_a).unwrap();
+    let receiver_output = state
+        .receiver
+        .process(&msg2.msg2_a)
+        .map_err(CFMInitError::InvalidMsg2)?;
     let mut receiver_ot_seed_0 = ZS::<ReceiverOTSeed>::default();
     eval_pprf(
         &state.session_id,
         &receiver_output,
         &msg2.pprf_output_a,
         &mut receiver_ot_seed_0,
-    )?;
+    )
+    .map_err(CFMInitError::InvalidMsg2)?;
 
     let sender_output_1 =
-        EndemicOTSender::process(&state.session_id, &msg2.msg1_b, &mut msg3.msg2_b, rng)?;
+        EndemicOTSender::process(&state.session_id, &msg2.msg1_b, &mut msg3.msg2_b, rng)
+            .map_err(CFMInitError::InvalidMsg2)?;
     let mut sender_ot_seed_1 = ZS::<SenderOTSeed>::default();
     build_pprf(
         &state.session_id,
@@ -169,8 +175,11 @@ pub fn cfm_init_process_msg2<R: CryptoRng + RngCore>(
 pub fn cfm_init_process_msg3(
     state: CFMInitStateCB,
     msg3: &CFMInitMsg3,
-) -> Result<CFMInitOTSeedsCB, &'static str> {
-    let receiver_output_1 = state.receiver.process(&msg3.msg2_b)?;
+) -> Result<CFMInitOTSeedsCB, CFMInitError> {
+    let receiver_output_1 = state
+        .receiver
+        .process(&msg3.msg2_b)
+        .map_err(CFMInitError::InvalidMsg3)?;
 
     let mut receiver_ot_seed_1 = ZS::<ReceiverOTSeed>::default();
     eval_pprf(
@@ -178,7 +187,8 @@ pub fn cfm_init_process_msg3(
         &receiver_output_1,
         &msg3.pprf_output_b,
         &mut receiver_ot_seed_1,
-    )?;
+    )
+    .map_err(CFMInitError::InvalidMsg3)?;
 
     Ok(CFMInitOTSeedsCB {
         sender_ot_seed_0: state.sender_ot_seed_0,
@@ -214,4 +224,33 @@ mod tests {
         let session_id: [u8; 32] = rng.gen();
         let (_ot_seeds_cb, _ot_seeds_ob) = generate_cfm_ot_seeds_for_test(&session_id, &mut rng);
     }
+
+    #[test]
+    fn test_cfm_init_invalid_msg2() {
+        use crate::errors::OTError;
+        use std::error::Error;
+
+        let mut rng = rand::thread_rng();
+        let session_id: [u8; 32] = rng.gen();
+        let mut msg1 = CFMInitMsg1::default();
+        let state_ob = cfm_init_create_msg1(&session_id, &mut msg1, &mut rng);
+        let mut msg2 = CFMInitMsg2::default();
+        cfm_init_process_msg1(&session_id, &msg1, &mut msg2, &mut rng).unwrap();
+
+        // CB sends a PPRF output that does not match its proof
+        msg2.pprf_output_a = ZS::default();
+        let mut msg3 = CFMInitMsg3::default();
+        let e = cfm_init_process_msg2(state_ob, &msg2, &mut msg3, &mut rng)
+            .err()
+            .unwrap();
+        assert!(matches!(
+            e,
+            CFMInitError::InvalidMsg2(OTError::InvalidPPRFProof)
+        ));
+        assert_eq!(
+            e.to_string(),
+            "Invalid CFMInitMsg2 from CB: Invalid PPRF proof"
+        );
+        assert!(e.source().unwrap().is::<OTError>());
+    }
 }
@@ -1,8 +1,36 @@
 //! Private Capital Flow Management 4.13 implementation
 //! with fixed bit length parameter l = 128, B = 47
-//! Protocol gets 4l+B+5 = 4 * 128 + 47 + 5 = 564 shares and
-//! 4l+B + 2*375 + 2 = 559 + 2*375 + 3 = 1312 authenticated beaver triples
-
+//! Protocol gets 4l+B+16 = 4 * 128 + 47 + 16 = 575 shares and
+//! 4l+B + 2*375 + 8 = 559 + 2*375 + 8 = 1317 authenticated beaver triples
+//!
+//! The `OutputPolicy` agreed in CFMMsg1 decides who opens [b] in CFMMsg17
+//! and CFMMsg18: both parties, only OB, only CB, or neither, in which case
+//! each party keeps its authenticated share of b. Under `Shared` the shares
+//! can be opened with the fair release of `fair_release`, where neither party
+//! can stop after learning b.
+//!
+//! The PSIT record of customer y carries the flow word Z_CB + Z_y, the limit
+//! word K + 2^B - L_y and one MAC over both, under a second mask K of CB.
+//! OB inputs U = X + flow + limit and CB inputs W = Z_CB + K + 2^B, so the
+//! comparison of W with U checks X + Z_y <= L_y, while OB learns neither Z_y
+//! nor L_y. The limit word is taken as [U] - [X] - [flow] inside the MAC
+//! check, which thereby also binds U to the record.
+//!
+//! The limit L is public by default, every customer then has limit L and the
+//! check T = W - Z_CB - limit - L = 0 holds CB to it. With a private limit
+//! OB does not know L and T is left out: CB can give each customer its own
+//! limit L_y, with L only the default for customers without one.
+//!
+//! The amount of OB is a transfer plus its share Z_L of the authenticated
+//! ledger, see `ledger`. The record also carries the slot of y, the key μ_y
+//! of the slot and a weight ω_y, both masked by CB. OB inputs Z_L and the tag
+//! of that slot, and λ·Z_L + μ_y = tag goes into the MAC check. A VOLE
+//! refreshes the keys of all n slots with δ, the transfer at slot y, and CB
+//! opens the weights only once OB has sent its VOLE output. The checks
+//! S = Σ ω_i·δ_i = ω_y·(X - Z_L) and λ·S = Σ ω_i·(c_i + d_i) then bind the
+//! VOLE to the transfer and to y. The VOLE costs about 6kB per slot.
+
+use crate::abort_report::{FailedCheck, Opening, OpeningFailure};
 use crate::auth_beaver_triples::{
     multiply_shares_open, multiply_shares_output, test_bit_open, test_bit_output, MulSharesOpen,
     MulSharesState, Share, TripleShare,
@@ -14,12 +42,20 @@ use crate::comparison::{
     CompStateP1R0, CompStateP1R2, CompStateP1R4, CompStateP1R6, CompStateP2R1, CompStateP2R3,
     CompStateP2R5, CompStateP2R7,
 };
-use crate::constants::{B_PARAMETER, CFM_LABEL};
+use crate::cfm_init_protocol::{CFMInitOTSeedsCB, CFMInitOTSeedsOB};
+use crate::constants::{B_PARAMETER, CFM_LABEL, FAIR_RELEASE_PAIRS, PSIT_RECORD_WORDS};
 
 use crate::errors::CFMError;
 use crate::psit_protocol::{
-    psit_create_msg1, psit_process_msg1, psit_process_msg2, PSITMsg1, PSITMsg2, PSITStateOB,
+    psit_create_msg1, psit_process_msg1, psit_process_msg2, PSITMsg1, PSITMsg2, PSITRecord,
+    PSITStateOB,
 };
+use crate::ledger::{KeyUpdate, Ledger, LedgerKeys, TagUpdate};
+use crate::proto::ZS;
+use crate::sl_oblivious::params::consts::KAPPA_BYTES;
+use crate::sl_oblivious::rvole::{is_valid_modulus, RVOLEOutput, RVOLEReceiver, RVOLESender};
+use crate::sl_oblivious::soft_spoken::{ReceiverOTSeed, Round1Output};
+use crate::sl_oblivious::utils::scalar_from_bytes;
 use crypto_bigint::modular::runtime_mod::{DynResidue, DynResidueParams};
 use crypto_bigint::{Random, U128, U64};
 use merlin::Transcript;
@@ -29,11 +65,88 @@ use serde::{Deserialize, Serialize};
 use subtle::Choice;
 
 const L: usize = 128;
-pub const NUMBER_OF_SHARES: usize = 4 * L + B_PARAMETER + 5;
+pub const NUMBER_OF_SHARES: usize = 4 * L + B_PARAMETER + 16;
+/// Shares of each ABT run, those of CFM followed by the masks of the fair
+/// release
+pub const ABT_SHARES: usize = NUMBER_OF_SHARES + FAIR_RELEASE_PAIRS;
 const AUTH_TRIPLES_OFFSET: usize = 4 * L + B_PARAMETER;
-pub const NUMBER_OF_AUTH_BEAVER_TRIPLES: usize = AUTH_TRIPLES_OFFSET + 2 * 375 + 3;
-use std::sync::{Arc, Mutex};
+pub const NUMBER_OF_AUTH_BEAVER_TRIPLES: usize = AUTH_TRIPLES_OFFSET + 2 * 375 + 8;
+/// first of the four triples of the ledger checks
+const LEDGER_TRIPLES_OFFSET: usize = AUTH_TRIPLES_OFFSET + 2 * 375 + 4;
+/// word of the PSIT record holding the masked flow total
+const RECORD_FLOW: usize = 0;
+/// word of the PSIT record holding the masked limit L_y
+const RECORD_LIMIT: usize = 1;
+/// word of the PSIT record holding the MAC of the flow, limit and weight words
+const RECORD_MAC: usize = 2;
+/// word of the PSIT record holding the ledger slot of y
+const RECORD_SLOT: usize = 3;
+/// word of the PSIT record holding the masked key μ_y of the slot
+const RECORD_KEY: usize = 4;
+/// word of the PSIT record holding the masked weight ω_y of the slot
+const RECORD_WEIGHT: usize = 5;
+
+/// Who learns the result bit b
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum OutputPolicy {
+    /// both parties open [b]
+    #[default]
+    Both = 0,
+    /// only OB learns b, CB opens its share to OB in CFMMsg18
+    OBOnly = 1,
+    /// only CB learns b, OB opens its share to CB in CFMMsg17
+    CBOnly = 2,
+    /// nobody opens [b], both keep their share
+    Shared = 3,
+}
+
+impl OutputPolicy {
+    /// True if OB learns b
+    pub fn ob_learns(&self) -> bool {
+        matches!(self, OutputPolicy::Both | OutputPolicy::OBOnly)
+    }
+
+    /// True if CB learns b
+    pub fn cb_learns(&self) -> bool {
+        matches!(self, OutputPolicy::Both | OutputPolicy::CBOnly)
+    }
+}
+
+impl TryFrom<u8> for OutputPolicy {
+    type Error = CFMError;
 
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(OutputPolicy::Both),
+            1 => Ok(OutputPolicy::OBOnly),
+            2 => Ok(OutputPolicy::CBOnly),
+            3 => Ok(OutputPolicy::Shared),
+            _ => Err(CFMError::OutputPolicyMismatch),
+        }
+    }
+}
+
+/// Output of a party at the end of CFM
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum CFMOutput {
+    /// result bit b
+    Result(bool),
+    /// only the other party learns b
+    Hidden,
+    /// authenticated share of b, for a downstream computation
+    Shared(Share),
+}
+
+impl CFMOutput {
+    /// Result bit, if this party learns it
+    pub fn result(&self) -> Option<bool> {
+        match self {
+            CFMOutput::Result(b) => Some(*b),
+            _ => None,
+        }
+    }
+}
 
 /// CFMMsg1
 #[derive(Clone, Serialize, Deserialize)]
@@ -41,6 +154,12 @@ pub struct CFMMsg1 {
     /// session id
     pub session_id: [u8; 32],
 
+    /// output policy of OB
+    pub output_policy: OutputPolicy,
+
+    /// true if L is a private input of CB
+    pub private_limit: bool,
+
     /// PSITMsg1
     pub psit_msg1: PSITMsg1,
 
@@ -60,10 +179,28 @@ pub struct CFMMsg1 {
 
     /// Open for CB-Input([/gamma_3], r_CB)
     pub open_4: (U128, U128),
+
+    /// Open for CB-Input([/gamma_5], /alpha_L)
+    pub open_5: (U128, U128),
+
+    /// Open for CB-Input([/gamma_6], /lambda)
+    pub open_6: (U128, U128),
+
+    /// Open for CB-Input([/gamma_7], /sigma_mu)
+    pub open_7: (U128, U128),
+
+    /// Open for CB-Input([/gamma_8], /sigma_omega)
+    pub open_8: (U128, U128),
+
+    /// Open for CB-Input([/gamma_9], /alpha_omega)
+    pub open_9: (U128, U128),
+
+    /// Open for CB-Input([/gamma_10], D)
+    pub open_10: (U128, U128),
 }
 
 /// CFMMsg2
-#[derive(Clone, Serialize, Deserialize, Debug)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct CFMMsg2 {
     /// session id
     pub session_id: [u8; 32],
@@ -88,6 +225,21 @@ pub struct CFMMsg2 {
     /// d from CB-Input([/gamma_3], r_CB)
     pub d_4: U128,
 
+    /// d from CB-Input([/gamma_5], /alpha_L)
+    pub d_5: U128,
+
+    /// d from CB-Input([/gamma_6], /lambda)
+    pub d_6: U128,
+
+    /// d from CB-Input([/gamma_7], /sigma_mu)
+    pub d_7: U128,
+
+    /// d from CB-Input([/gamma_8], /sigma_omega)
+    pub d_8: U128,
+
+    /// d from CB-Input([/gamma_9], /alpha_omega)
+    pub d_9: U128,
+
     /// Open for OB-Input([/gamma_2], M_Y)
     pub open_0: (U128, U128),
 
@@ -106,8 +258,32 @@ pub struct CFMMsg2 {
     /// Open for OB-Input([/gamma_4], r_OB)
     pub open_4: (U128, U128),
 
+    /// Open for OB-Input([/gamma_11], Z_L)
+    pub open_5: (U128, U128),
+
+    /// Open for OB-Input([/gamma_12], V = tag - key word)
+    pub open_6: (U128, U128),
+
+    /// Open for OB-Input([/gamma_13], weight word)
+    pub open_7: (U128, U128),
+
+    /// Open for OB-Input([/gamma_14], S)
+    pub open_8: (U128, U128),
+
+    /// Open for OB-Input([/gamma_15], C)
+    pub open_9: (U128, U128),
+
     /// Open Z_{l-1}^CB
     pub open_z_l_minus_1: (U128, U128),
+
+    /// number n of ledger slots
+    pub ledger_size: u64,
+
+    /// RVOLE round 1 of the ledger key refresh
+    pub ledger_vole_msg1: ZS<Round1Output>,
+
+    /// /lambda - b, b the RVOLE scalar of CB
+    pub ledger_e: U128,
 }
 
 /// CFMMsg3
@@ -135,10 +311,22 @@ pub struct CFMMsg3 {
     /// d from OB-Input([/gamma_4], r_OB)
     pub d_4: U128,
 
+    /// d from OB-Input([/gamma_11], Z_L)
+    pub d_5: U128,
+
+    /// d from OB-Input([/gamma_12], V)
+    pub d_6: U128,
+
+    /// d from OB-Input([/gamma_13], weight word)
+    pub d_7: U128,
+
+    /// RVOLE output of OB on the transfer δ
+    pub ledger_vole_msg2: RVOLEOutput,
+
     /// mul_open for TestBit([Z_i^CB]), TestBit([W_i]),
     /// TestBit([Z_{Y,i}^OB]), TestBit([X_i]), TestBit([U_i])
     #[serde(with = "serde_arrays")]
-    pub mul_open_test_bit: [MulSharesOpen; NUMBER_OF_SHARES - 3],
+    pub mul_open_test_bit: [MulSharesOpen; NUMBER_OF_SHARES - 14],
 }
 
 /// CFMMsg4
@@ -150,11 +338,17 @@ pub struct CFMMsg4 {
     /// mul_open for TestBit([Z_i^CB]), TestBit([W_i]),
     /// TestBit([Z_{Y,i}^OB]), TestBit([X_i]), TestBit([U_i])
     #[serde(with = "serde_arrays")]
-    pub mul_open_test_bit: [MulSharesOpen; NUMBER_OF_SHARES - 3],
+    pub mul_open_test_bit: [MulSharesOpen; NUMBER_OF_SHARES - 14],
 
     /// Open([t_i]), i /in [0, 5l-1]
     #[serde(with = "serde_arrays")]
-    pub open_t_i: [(U128, U128); NUMBER_OF_SHARES - 3],
+    pub open_t_i: [(U128, U128); NUMBER_OF_SHARES - 14],
+
+    /// d from CB-Input([/gamma_10], D)
+    pub d_0: U128,
+
+    /// seed of the slot weights ω_i
+    pub ledger_weight_seed: [u8; 32],
 }
 
 /// CFMMsg5
@@ -165,13 +359,23 @@ pub struct CFMMsg5 {
 
     /// Open([t_i]), i /in [0, 5l-1]
     #[serde(with = "serde_arrays")]
-    pub open_t_i: [(U128, U128); NUMBER_OF_SHARES - 3],
+    pub open_t_i: [(U128, U128); NUMBER_OF_SHARES - 14],
+
+    /// Open([T]), if L is public
+    pub open_t: Option<(U128, U128)>,
 
-    /// Open([V])
-    pub open_v: (U128, U128),
+    /// mul_open for [a_L] = Multiply([/alpha_L], [L_Y^OB])
+    pub mul_open_a_limit: MulSharesOpen,
+
+    /// d from OB-Input([/gamma_14], S)
+    pub d_0: U128,
 
-    /// Open([T])
-    pub open_t: (U128, U128),
+    /// d from OB-Input([/gamma_15], C)
+    pub d_1: U128,
+
+    /// mul_open for Multiply([/lambda], [Z_L]), Multiply([/alpha_omega],
+    /// [weight word]), Multiply([ω_Y], [X] - [Z_L]) and Multiply([/lambda], [S])
+    pub mul_open_ledger: [MulSharesOpen; 4],
 }
 
 /// CFMMsg6
@@ -180,11 +384,18 @@ pub struct CFMMsg6 {
     /// session id
     pub session_id: [u8; 32],
 
-    /// Open([V])
-    pub open_v: (U128, U128),
+    /// Open([T]), if L is public
+    pub open_t: Option<(U128, U128)>,
+
+    /// mul_open for [a_L] = Multiply([/alpha_L], [L_Y^OB])
+    pub mul_open_a_limit: MulSharesOpen,
+
+    /// mul_open for Multiply([/lambda], [Z_L]), Multiply([/alpha_omega],
+    /// [weight word]), Multiply([ω_Y], [X] - [Z_L]) and Multiply([/lambda], [S])
+    pub mul_open_ledger: [MulSharesOpen; 4],
 
-    /// Open([T])
-    pub open_t: (U128, U128),
+    /// Open([S] - [ω_Y]·([X] - [Z_L])) and Open([/lambda]·[S] - [C] - [D])
+    pub open_ledger: [(U128, U128); 2],
 
     /// CompMsg1
     pub comp_msg1_c: CompMsg1,
@@ -199,6 +410,9 @@ pub struct CFMMsg7 {
     /// session id
     pub session_id: [u8; 32],
 
+    /// Open([S] - [ω_Y]·([X] - [Z_L])) and Open([/lambda]·[S] - [C] - [D])
+    pub open_ledger: [(U128, U128); 2],
+
     /// CompMsg2
     pub comp_msg2_c: CompMsg2,
 
@@ -341,8 +555,8 @@ pub struct CFMMsg17 {
     /// Open([f])
     pub open_f: (U128, U128),
 
-    /// Open([b])
-    pub open_b: (U128, U128),
+    /// Open([b]), if CB learns b
+    pub open_b: Option<(U128, U128)>,
 }
 
 /// CFMMsg18
@@ -351,8 +565,8 @@ pub struct CFMMsg18 {
     /// session id
     pub session_id: [u8; 32],
 
-    /// Open([b])
-    pub open_b: (U128, U128),
+    /// Open([b]), if OB learns b
+    pub open_b: Option<(U128, U128)>,
 }
 
 /// CFM State for OB round1
@@ -364,14 +578,23 @@ pub struct CFMStateOBR1 {
     /// p
     pub p: U128,
 
-    /// L
-    pub big_l: U64,
+    /// who learns the result bit
+    pub output_policy: OutputPolicy,
+
+    /// L, None if it is a private input of CB
+    pub big_l: Option<U64>,
 
-    /// X
+    /// transfer X
     pub big_x: U64,
 
     /// PSITStateOB
     pub psit_state_ob: PSITStateOB,
+
+    /// ledger of OB
+    pub ledger: Ledger,
+
+    /// OT seed of the ledger VOLE
+    pub ot_seed: ZS<ReceiverOTSeed>,
 }
 
 /// CFM State for CB round1
@@ -383,8 +606,11 @@ pub struct CFMStateCBR1 {
     /// p
     pub p: U128,
 
-    /// L
-    pub big_l: U64,
+    /// who learns the result bit
+    pub output_policy: OutputPolicy,
+
+    /// L, None if it is a private input of CB
+    pub big_l: Option<U64>,
 
     /// z_i_cb shares
     #[serde(with = "serde_arrays")]
@@ -397,11 +623,38 @@ pub struct CFMStateCBR1 {
     /// alpha share
     pub alpha_share: Share,
 
+    /// alpha_limit share
+    pub alpha_limit_share: Share,
+
     /// beta share
     pub beta_share: Share,
 
     /// r_cb share
     pub r_cb_share: Share,
+
+    /// lambda share
+    pub lambda_share: Share,
+
+    /// sigma_key share
+    pub sigma_key_share: Share,
+
+    /// sigma_weight share
+    pub sigma_weight_share: Share,
+
+    /// alpha_weight share
+    pub alpha_weight_share: Share,
+
+    /// open of OB for CB-Input([/gamma_10], D)
+    pub open_d: (U128, U128),
+
+    /// number n of ledger slots
+    pub ledger_size: usize,
+
+    /// seed of the slot weights
+    pub ledger_weight_seed: [u8; 32],
+
+    /// RVOLE receiver of the ledger key refresh
+    pub ledger_vole_receiver: Box<RVOLEReceiver>,
 }
 
 /// CFM State for OB round2
@@ -413,8 +666,11 @@ pub struct CFMStateOBR2 {
     /// p
     pub p: U128,
 
-    /// L
-    pub big_l: U64,
+    /// who learns the result bit
+    pub output_policy: OutputPolicy,
+
+    /// L, None if it is a private input of CB
+    pub big_l: Option<U64>,
 
     /// r share
     pub r_share: Share,
@@ -422,6 +678,9 @@ pub struct CFMStateOBR2 {
     /// alpha share
     pub alpha_share: Share,
 
+    /// alpha_limit share
+    pub alpha_limit_share: Share,
+
     /// beta share
     pub beta_share: Share,
 
@@ -450,7 +709,43 @@ pub struct CFMStateOBR2 {
 
     /// mul_state_test_bit
     #[serde(with = "serde_arrays")]
-    pub mul_state_test_bit: [MulSharesState; NUMBER_OF_SHARES - 3],
+    pub mul_state_test_bit: [MulSharesState; NUMBER_OF_SHARES - 14],
+
+    /// auth_triple_a_limit
+    pub auth_triple_a_limit: TripleShare,
+
+    /// lambda share
+    pub lambda_share: Share,
+
+    /// sigma_key share
+    pub sigma_key_share: Share,
+
+    /// sigma_weight share
+    pub sigma_weight_share: Share,
+
+    /// alpha_weight share
+    pub alpha_weight_share: Share,
+
+    /// z_l share
+    pub z_l_share: Share,
+
+    /// v share
+    pub v_share: Share,
+
+    /// weight share
+    pub weight_share: Share,
+
+    /// /gamma_10, /gamma_14 and /gamma_15 for D, S and C
+    pub ledger_gammas: [Share; 3],
+
+    /// opens of CB for OB-Input([/gamma_14], S) and OB-Input([/gamma_15], C)
+    pub ledger_opens: [(U128, U128); 2],
+
+    /// tag updates of the transfer
+    pub ledger_update: TagUpdate,
+
+    /// auth_triples_ledger
+    pub auth_triples_ledger: [TripleShare; 4],
 }
 
 /// CFM State for CB round2
@@ -462,8 +757,11 @@ pub struct CFMStateCBR2 {
     /// p
     pub p: U128,
 
-    /// L
-    pub big_l: U64,
+    /// who learns the result bit
+    pub output_policy: OutputPolicy,
+
+    /// L, None if it is a private input of CB
+    pub big_l: Option<U64>,
 
     /// r share
     pub r_share: Share,
@@ -471,6 +769,9 @@ pub struct CFMStateCBR2 {
     /// alpha share
     pub alpha_share: Share,
 
+    /// alpha_limit share
+    pub alpha_limit_share: Share,
+
     /// beta share
     pub beta_share: Share,
 
@@ -499,7 +800,37 @@ pub struct CFMStateCBR2 {
 
     /// t_i shares
     #[serde(with = "serde_arrays")]
-    pub t_i_shares: [Share; NUMBER_OF_SHARES - 3],
+    pub t_i_shares: [Share; NUMBER_OF_SHARES - 14],
+
+    /// lambda share
+    pub lambda_share: Share,
+
+    /// sigma_key share
+    pub sigma_key_share: Share,
+
+    /// sigma_weight share
+    pub sigma_weight_share: Share,
+
+    /// alpha_weight share
+    pub alpha_weight_share: Share,
+
+    /// d share
+    pub d_share: Share,
+
+    /// z_l share
+    pub z_l_share: Share,
+
+    /// v share
+    pub v_share: Share,
+
+    /// weight share
+    pub weight_share: Share,
+
+    /// /gamma_14 and /gamma_15 for S and C
+    pub ledger_gammas: [Share; 2],
+
+    /// key updates of the ledger
+    pub ledger_update: KeyUpdate,
 }
 
 /// CFM State for OB round3
@@ -511,6 +842,9 @@ pub struct CFMStateOBR3 {
     /// p
     pub p: U128,
 
+    /// who learns the result bit
+    pub output_policy: OutputPolicy,
+
     /// r share
     pub r_share: Share,
 
@@ -546,11 +880,29 @@ pub struct CFMStateOBR3 {
     #[serde(with = "serde_arrays")]
     pub u_i_shares: [Share; L],
 
-    /// big_v share
-    pub big_v: Share,
+    /// big_t share, if L is public
+    pub big_t: Option<Share>,
+
+    /// mul_state_a_limit
+    pub mul_state_a_limit: MulSharesState,
+
+    /// sigma_key share
+    pub sigma_key_share: Share,
+
+    /// v share
+    pub v_share: Share,
 
-    /// big_t share
-    pub big_t: Share,
+    /// s share
+    pub s_share: Share,
+
+    /// c share
+    pub c_share: Share,
+
+    /// d share
+    pub d_share: Share,
+
+    /// mul_state_ledger
+    pub mul_state_ledger: [MulSharesState; 4],
 }
 
 /// CFM State for CB round3
@@ -562,6 +914,9 @@ pub struct CFMStateCBR3 {
     /// p
     pub p: U128,
 
+    /// who learns the result bit
+    pub output_policy: OutputPolicy,
+
     /// r share
     pub r_share: Share,
 
@@ -598,6 +953,9 @@ pub struct CFMStateCBR3 {
     /// u_i shares
     #[serde(with = "serde_arrays")]
     pub u_i_shares: [Share; L],
+
+    /// shares of the ledger checks S - ω_Y·(X - Z_L) and λ·S - C - D
+    pub ledger_check_shares: [Share; 2],
 }
 
 /// CFM State for OB round4
@@ -609,6 +967,9 @@ pub struct CFMStateOBR4 {
     /// p
     pub p: U128,
 
+    /// who learns the result bit
+    pub output_policy: OutputPolicy,
+
     /// r share
     pub r_share: Share,
 
@@ -640,6 +1001,9 @@ pub struct CFMStateCBR4 {
     /// p
     pub p: U128,
 
+    /// who learns the result bit
+    pub output_policy: OutputPolicy,
+
     /// r share
     pub r_share: Share,
 
@@ -671,6 +1035,9 @@ pub struct CFMStateOBR5 {
     /// p
     pub p: U128,
 
+    /// who learns the result bit
+    pub output_policy: OutputPolicy,
+
     /// r share
     pub r_share: Share,
 
@@ -702,6 +1069,9 @@ pub struct CFMStateCBR5 {
     /// p
     pub p: U128,
 
+    /// who learns the result bit
+    pub output_policy: OutputPolicy,
+
     /// r share
     pub r_share: Share,
 
@@ -733,6 +1103,9 @@ pub struct CFMStateOBR6 {
     /// p
     pub p: U128,
 
+    /// who learns the result bit
+    pub output_policy: OutputPolicy,
+
     /// r share
     pub r_share: Share,
 
@@ -764,6 +1137,9 @@ pub struct CFMStateCBR6 {
     /// p
     pub p: U128,
 
+    /// who learns the result bit
+    pub output_policy: OutputPolicy,
+
     /// r share
     pub r_share: Share,
 
@@ -795,6 +1171,9 @@ pub struct CFMStateOBR7 {
     /// p
     pub p: U128,
 
+    /// who learns the result bit
+    pub output_policy: OutputPolicy,
+
     /// r share
     pub r_share: Share,
 
@@ -829,6 +1208,9 @@ pub struct CFMStateCBR7 {
     /// p
     pub p: U128,
 
+    /// who learns the result bit
+    pub output_policy: OutputPolicy,
+
     /// r share
     pub r_share: Share,
 
@@ -854,6 +1236,9 @@ pub struct CFMStateOBR8 {
     /// p
     pub p: U128,
 
+    /// who learns the result bit
+    pub output_policy: OutputPolicy,
+
     /// b_share share
     pub b_share: Share,
 
@@ -870,6 +1255,9 @@ pub struct CFMStateCBR8 {
     /// p
     pub p: U128,
 
+    /// who learns the result bit
+    pub output_policy: OutputPolicy,
+
     /// b share
     pub b_share: Share,
 
@@ -886,37 +1274,216 @@ pub struct CFMStateOBR9 {
     /// p
     pub p: U128,
 
+    /// who learns the result bit
+    pub output_policy: OutputPolicy,
+
     /// b_share share
     pub b_share: Share,
 }
 
-/// MAC function for CFM protocol
-fn mac_function(alpha: &U128, beta: &U128, z_y_ob: &U128, params: DynResidueParams<2>) -> U128 {
+/// MAC function for CFM protocol, over the flow, limit and weight words of
+/// a record
+#[allow(clippy::too_many_arguments)]
+fn mac_function(
+    alpha: &U128,
+    alpha_limit: &U128,
+    alpha_weight: &U128,
+    beta: &U128,
+    z_y_ob: &U128,
+    l_y_ob: &U128,
+    weight: &U128,
+    params: DynResidueParams<2>,
+) -> U128 {
     DynResidue::new(alpha, params)
         .mul(&DynResidue::new(z_y_ob, params))
+        .add(&DynResidue::new(alpha_limit, params).mul(&DynResidue::new(l_y_ob, params)))
+        .add(&DynResidue::new(alpha_weight, params).mul(&DynResidue::new(weight, params)))
         .add(&DynResidue::new(beta, params))
         .retrieve()
 }
 
-/// OB creates CFMMsg1 for CB
+/// Session id of the RVOLE that refreshes the ledger keys
+fn ledger_vole_session_id(session_id: &[u8; 32]) -> [u8; 32] {
+    let mut t = Transcript::new(CFM_LABEL.as_ref());
+    t.append_message(b"session-id", session_id);
+    let mut vole_session_id = [0u8; 32];
+    t.challenge_bytes(b"ledger-vole-session-id", &mut vole_session_id);
+    vole_session_id
+}
+
+/// Weights ω_i of the n ledger slots, drawn from the seed CB opens in CFMMsg4
+fn ledger_weights(
+    session_id: &[u8; 32],
+    seed: &[u8; 32],
+    n: usize,
+    params: DynResidueParams<2>,
+) -> Vec<U128> {
+    let mut t = Transcript::new(CFM_LABEL.as_ref());
+    t.append_message(b"session-id", session_id);
+    t.append_message(b"ledger-weight-seed", seed);
+    (0..n)
+        .map(|_| {
+            let mut bytes = [0u8; KAPPA_BYTES];
+            t.challenge_bytes(b"ledger-weight", &mut bytes);
+            scalar_from_bytes(params, bytes)
+        })
+        .collect()
+}
+
+/// Σ a_i·b_i mod p
+fn inner_product(a: &[U128], b: &[U128], params: DynResidueParams<2>) -> U128 {
+    a.iter()
+        .zip(b)
+        .fold(DynResidue::new(&U128::ZERO, params), |sum, (a, b)| {
+            sum.add(&DynResidue::new(a, params).mul(&DynResidue::new(b, params)))
+        })
+        .retrieve()
+}
+
+/// mul_open of the ledger checks: [/lambda]·[Z_L], [/alpha_omega]·[weight
+/// word], [ω_Y]·([A] - [Z_L]) with [ω_Y] = [weight word] - [/sigma_omega]
+/// and [A] = [X] + [Z_L] the amount, and [/lambda]·[S]
+#[allow(clippy::too_many_arguments)]
+fn ledger_mul_open(
+    lambda: &Share,
+    sigma_weight: &Share,
+    alpha_weight: &Share,
+    z_l: &Share,
+    weight: &Share,
+    big_a: &Share,
+    big_s: &Share,
+    triples: &[TripleShare; 4],
+    params: DynResidueParams<2>,
+) -> ([MulSharesState; 4], [MulSharesOpen; 4]) {
+    let omega_y = weight.sub_share(sigma_weight, params);
+    let big_x = big_a.sub_share(z_l, params);
+    let pairs = [
+        (lambda, z_l),
+        (alpha_weight, weight),
+        (&omega_y, &big_x),
+        (lambda, big_s),
+    ];
+    let mut mul_state = [MulSharesState::default(); 4];
+    let mut mul_open = [MulSharesOpen::default(); 4];
+    for (i, (x, y)) in pairs.into_iter().enumerate() {
+        let triple = &triples[i];
+        (mul_state[i], mul_open[i]) =
+            multiply_shares_open(x, y, &triple.x, &triple.y, &triple.z, params);
+    }
+    (mul_state, mul_open)
+}
+
+/// Shares of [/lambda]·[Z_L], [/alpha_omega]·[weight word],
+/// [ω_Y]·([A] - [Z_L]) and [/lambda]·[S] from both mul_open
+fn ledger_mul_output(
+    mul_state: &[MulSharesState; 4],
+    mul_open: &[MulSharesOpen; 4],
+    is_cb_side: bool,
+    params: DynResidueParams<2>,
+) -> Result<[Share; 4], CFMError> {
+    let mut shares = [Share::default(); 4];
+    for i in 0..4 {
+        shares[i] = match multiply_shares_output(&mul_state[i], &mul_open[i], is_cb_side, params) {
+            Ok(v) => v,
+            Err(e) => {
+                let check = FailedCheck::abt_sacrifice("mul_open_ledger", i);
+                return Err(CFMError::InvalidOpen(check, e));
+            }
+        };
+    }
+    Ok(shares)
+}
+
+/// [/beta] + [a_L] + [/alpha_omega]·[weight word] + [/lambda]·[Z_L] - [V] -
+/// [/sigma_mu]. The MAC check of the record then fails for a wrong limit
+/// or weight word and for a share Z_L without its tag.
+fn ledger_beta_share(
+    beta: &Share,
+    a_limit: &Share,
+    products: &[Share; 2],
+    v: &Share,
+    sigma_key: &Share,
+    params: DynResidueParams<2>,
+) -> Share {
+    beta.add_share(a_limit, params)
+        .add_share(&products[1], params)
+        .add_share(&products[0], params)
+        .sub_share(v, params)
+        .sub_share(sigma_key, params)
+}
+
+/// Check that the other party opens both ledger checks to zero
+fn check_open_ledger(
+    shares: &[Share; 2],
+    open_ledger: &[(U128, U128); 2],
+    params: DynResidueParams<2>,
+) -> Result<(), CFMError> {
+    for (i, (share, open)) in shares.iter().zip(open_ledger).enumerate() {
+        let value = match share.validate_open(&open.0, &open.1, params) {
+            Ok(v) => v,
+            Err(e) => return Err(CFMError::InvalidOpen(FailedCheck::cfm("open_ledger", i), e)),
+        };
+        if value != U128::ZERO {
+            let check = FailedCheck::cfm("open_ledger", i);
+            return Err(wrong_value(check, share, open, U128::ZERO, params));
+        }
+    }
+    Ok(())
+}
+
+/// p must be a prime above 2^(L-1) + 2^(B+2), so that the masked values
+/// of CFM do not wrap around
+fn check_modulus(p: &U128) -> Result<(), CFMError> {
+    let min = U128::ONE
+        .shl(L - 1)
+        .saturating_add(&U128::ONE.shl(B_PARAMETER + 2));
+    if *p <= min || !is_valid_modulus(p) {
+        return Err(CFMError::InvalidModulus);
+    }
+    Ok(())
+}
+
+/// True if L and the limits `big_l_y` of the customers of `big_y` are below
+/// 2^B, and with a public L every customer has limit L
+pub(crate) fn valid_limits(
+    big_l: U64,
+    private_limit: bool,
+    big_y: &[[u8; 32]],
+    big_l_y: Option<&[U64]>,
+) -> bool {
+    let two_pow_b = U64::ONE.shl(B_PARAMETER);
+    big_l < two_pow_b
+        && big_l_y.is_none_or(|big_l_y| {
+            big_l_y.len() == big_y.len()
+                && big_l_y
+                    .iter()
+                    .all(|big_l_y| *big_l_y < two_pow_b && (private_limit || *big_l_y == big_l))
+        })
+}
+
+/// OB creates CFMMsg1 for CB. `big_x` is the transfer, CFM adds the share of
+/// y in `ledger` to it.
+#[allow(clippy::too_many_arguments)]
 pub fn cfm_create_msg1<R: CryptoRng + RngCore>(
     session_id: &[u8; 32],
     p: U128,
-    big_l: U64,
+    output_policy: OutputPolicy,
+    big_l: Option<U64>,
     big_x: U64,
     y: &[u8; 32],
+    ledger: &Ledger,
+    ot_seeds: &CFMInitOTSeedsOB,
     x_shares: &[Share],
     rng: &mut R,
-) -> (CFMStateOBR1, Box<CFMMsg1>) {
-    assert!(
-        p > U128::ONE
-            .shl(L - 1)
-            .saturating_add(&U128::ONE.shl(B_PARAMETER + 1))
-    );
+) -> Result<(CFMStateOBR1, Box<CFMMsg1>), CFMError> {
+    check_modulus(&p)?;
     let two_pow_b = U64::ONE.shl(B_PARAMETER);
-    assert!(big_l < two_pow_b);
-    assert!(big_x < two_pow_b);
-    assert_eq!(x_shares.len(), NUMBER_OF_SHARES);
+    if big_l.is_some_and(|big_l| big_l >= two_pow_b)
+        || big_x >= two_pow_b
+        || x_shares.len() != NUMBER_OF_SHARES
+    {
+        return Err(CFMError::InvalidInput);
+    }
 
     let mut t = Transcript::new(CFM_LABEL.as_ref());
     t.append_message(b"session-id", session_id);
@@ -934,85 +1501,161 @@ pub fn cfm_create_msg1<R: CryptoRng + RngCore>(
         open_1[i] = x_share_1.open();
     }
 
-    let gamma_0 = x_shares[NUMBER_OF_SHARES - 5];
-    let gamma_1 = x_shares[NUMBER_OF_SHARES - 4];
-    let gamma_3 = x_shares[NUMBER_OF_SHARES - 2];
+    let gamma_0 = x_shares[NUMBER_OF_SHARES - 16];
+    let gamma_1 = x_shares[NUMBER_OF_SHARES - 15];
+    let gamma_3 = x_shares[NUMBER_OF_SHARES - 13];
+    let gamma_5 = x_shares[NUMBER_OF_SHARES - 11];
     let open_2 = gamma_0.open();
     let open_3 = gamma_1.open();
     let open_4 = gamma_3.open();
+    let open_5 = gamma_5.open();
+    let open_6 = x_shares[NUMBER_OF_SHARES - 10].open();
+    let open_7 = x_shares[NUMBER_OF_SHARES - 9].open();
+    let open_8 = x_shares[NUMBER_OF_SHARES - 8].open();
+    let open_9 = x_shares[NUMBER_OF_SHARES - 7].open();
+    let open_10 = x_shares[NUMBER_OF_SHARES - 6].open();
 
     let state = CFMStateOBR1 {
         session_id: *session_id,
         p,
+        output_policy,
         big_l,
         big_x,
         psit_state_ob,
+        ledger: ledger.clone(),
+        ot_seed: ot_seeds.receiver_ot_seed_0.clone(),
     };
     let msg1 = Box::new(CFMMsg1 {
         session_id: *session_id,
+        output_policy,
+        private_limit: big_l.is_none(),
         psit_msg1,
         open_0,
         open_1,
         open_2,
         open_3,
         open_4,
+        open_5,
+        open_6,
+        open_7,
+        open_8,
+        open_9,
+        open_10,
     });
 
-    (state, msg1)
+    Ok((state, msg1))
 }
 
-/// CB processes CFMMsg1 from OB
+/// CB processes CFMMsg1 from OB. `big_l_y` holds the limit of each customer
+/// of `big_y`, None if every customer has limit L. With a public L every
+/// limit must be L. Every customer needs a slot in `ledger`, see
+/// [`LedgerKeys::assign_slots`].
 #[allow(clippy::too_many_arguments)]
 pub fn cfm_process_msg1<R: CryptoRng + RngCore>(
     session_id: &[u8; 32],
     p: U128,
+    output_policy: OutputPolicy,
     big_l: U64,
+    private_limit: bool,
     big_y: Vec<[u8; 32]>,
     big_z: Vec<U64>,
+    big_l_y: Option<Vec<U64>>,
+    ledger: &LedgerKeys,
+    ot_seeds: &CFMInitOTSeedsCB,
     x_shares: &[Share],
     msg1: &CFMMsg1,
     rng: &mut R,
 ) -> Result<(Box<CFMStateCBR1>, Box<CFMMsg2>), CFMError> {
-    assert!(
-        p > U128::ONE
-            .shl(L - 1)
-            .saturating_add(&U128::ONE.shl(B_PARAMETER + 1))
-    );
-    let two_pow_b = U64::ONE.shl(B_PARAMETER);
-    assert!(big_l < two_pow_b);
-    assert_eq!(x_shares.len(), NUMBER_OF_SHARES);
+    check_modulus(&p)?;
+    let slots = big_y
+        .iter()
+        .map(|y| ledger.slot(y))
+        .collect::<Option<Vec<usize>>>()
+        .ok_or(CFMError::InvalidInput)?;
+    if !valid_limits(big_l, private_limit, &big_y, big_l_y.as_deref())
+        || x_shares.len() != NUMBER_OF_SHARES
+    {
+        return Err(CFMError::InvalidInput);
+    }
+    let big_l_y = big_l_y.unwrap_or_else(|| vec![big_l; big_y.len()]);
 
     if *session_id != msg1.session_id {
         return Err(CFMError::InvalidSessionID);
     }
+    if msg1.output_policy != output_policy {
+        return Err(CFMError::OutputPolicyMismatch);
+    }
+    if msg1.private_limit != private_limit {
+        return Err(CFMError::LimitModeMismatch);
+    }
     let params = DynResidueParams::new(&p);
 
-    //  Z_CB ← Z_{2^{ℓ−1}}
+    //  Z_CB ← Z_{2^{ℓ−2}}, K ← Z_{2^{ℓ−2}}
     let mut big_z_cb_bytes: [u8; 16] = rng.gen();
-    big_z_cb_bytes[15] &= 0x7f;
+    big_z_cb_bytes[15] &= 0x3f;
     let big_z_cb = U128::from_le_slice(&big_z_cb_bytes);
+    let mut big_k_bytes: [u8; 16] = rng.gen();
+    big_k_bytes[15] &= 0x3f;
+    let big_k = U128::from_le_slice(&big_k_bytes);
 
+    // K + 2^B
+    let big_k_dyn_res = DynResidue::new(&big_k, params)
+        .add(&DynResidue::new(&U128::ONE.shl(B_PARAMETER), params));
     let big_z_cb_dyn_res = DynResidue::new(&big_z_cb, params);
-    let big_w = big_z_cb_dyn_res
-        .add(&DynResidue::new(&big_l.resize(), params))
-        .retrieve();
-
-    let big_z_ob = big_z
-        .iter()
-        .map(|x| {
-            big_z_cb_dyn_res
-                .add(&DynResidue::new(&x.resize(), params))
-                .retrieve()
-        })
-        .collect::<Vec<U128>>();
+    let big_w = big_z_cb_dyn_res.add(&big_k_dyn_res).retrieve();
 
     let alpha = DynResidue::new(&U128::random(&mut *rng), params).retrieve();
+    let alpha_limit = DynResidue::new(&U128::random(&mut *rng), params).retrieve();
     let beta = DynResidue::new(&U128::random(&mut *rng), params).retrieve();
 
-    let big_mac = big_z_ob
+    // masks of the key and weight words, weights of the slots and the MAC
+    // key of the weight word
+    let sigma_key = DynResidue::new(&U128::random(&mut *rng), params);
+    let sigma_weight = DynResidue::new(&U128::random(&mut *rng), params);
+    let alpha_weight = DynResidue::new(&U128::random(&mut *rng), params).retrieve();
+    let ledger_size = ledger.len();
+    let mut ledger_weight_seed = [0u8; 32];
+    rng.fill_bytes(&mut ledger_weight_seed);
+    let weights = ledger_weights(session_id, &ledger_weight_seed, ledger_size, params);
+
+    let records = big_z
         .iter()
-        .map(|x| mac_function(&alpha, &beta, x, params))
-        .collect::<Vec<U128>>();
+        .zip(&big_l_y)
+        .zip(&slots)
+        .map(|((z_y, big_l_y), slot)| {
+            // Z_CB + Z_y and K + 2^B - L_y
+            let flow = big_z_cb_dyn_res
+                .add(&DynResidue::new(&z_y.resize(), params))
+                .retrieve();
+            let limit = big_k_dyn_res
+                .sub(&DynResidue::new(&big_l_y.resize(), params))
+                .retrieve();
+            // μ_y + σ_μ and ω_y + σ_ω
+            let key = DynResidue::new(&ledger.key(*slot), params)
+                .add(&sigma_key)
+                .retrieve();
+            let weight = DynResidue::new(&weights[*slot], params)
+                .add(&sigma_weight)
+                .retrieve();
+            let mut record = PSITRecord::default();
+            record.0[RECORD_FLOW] = flow;
+            record.0[RECORD_LIMIT] = limit;
+            record.0[RECORD_MAC] = mac_function(
+                &alpha,
+                &alpha_limit,
+                &alpha_weight,
+                &beta,
+                &flow,
+                &limit,
+                &weight,
+                params,
+            );
+            record.0[RECORD_SLOT] = U128::from_u64(*slot as u64);
+            record.0[RECORD_KEY] = key;
+            record.0[RECORD_WEIGHT] = weight;
+            record
+        })
+        .collect::<Vec<PSITRecord>>();
 
     let mut t = Transcript::new(CFM_LABEL.as_ref());
     t.append_message(b"session-id", session_id);
@@ -1022,13 +1665,12 @@ pub fn cfm_process_msg1<R: CryptoRng + RngCore>(
     let psit_msg2 = match psit_process_msg1(
         &psit_session_id,
         big_y,
-        big_z_ob,
-        big_mac,
+        records,
         &msg1.psit_msg1,
         &mut *rng,
     ) {
         Ok(v) => v,
-        Err(_) => return Err(CFMError::PSITError),
+        Err(e) => return Err(CFMError::PSITError(e)),
     };
 
     let mut d_0 = [U128::ZERO; L];
@@ -1041,7 +1683,7 @@ pub fn cfm_process_msg1<R: CryptoRng + RngCore>(
         let y_0 = U128::from_u8(Choice::from(big_z_cb.bit(i)).unwrap_u8());
         (z_i_cb_shares[i], d_0[i]) = match x_share_0.cb_input(&msg1.open_0[i], &y_0, params) {
             Ok(v) => v,
-            Err(_) => return Err(CFMError::InvalidOpen),
+            Err(e) => return Err(CFMError::InvalidOpen(FailedCheck::cfm("open_0", i), e)),
         };
 
         // CB-Input([x_{l+i}], W_i^CB)
@@ -1049,35 +1691,78 @@ pub fn cfm_process_msg1<R: CryptoRng + RngCore>(
         let y_1 = U128::from_u8(Choice::from(big_w.bit(i)).unwrap_u8());
         (w_i_shares[i], d_1[i]) = match x_share_1.cb_input(&msg1.open_1[i], &y_1, params) {
             Ok(v) => v,
-            Err(_) => return Err(CFMError::InvalidOpen),
+            Err(e) => return Err(CFMError::InvalidOpen(FailedCheck::cfm("open_1", i), e)),
         };
     }
 
-    let gamma_0 = x_shares[NUMBER_OF_SHARES - 5];
-    let gamma_1 = x_shares[NUMBER_OF_SHARES - 4];
-    let gamma_2 = x_shares[NUMBER_OF_SHARES - 3];
-    let gamma_3 = x_shares[NUMBER_OF_SHARES - 2];
-    let gamma_4 = x_shares[NUMBER_OF_SHARES - 1];
+    let gamma_0 = x_shares[NUMBER_OF_SHARES - 16];
+    let gamma_1 = x_shares[NUMBER_OF_SHARES - 15];
+    let gamma_2 = x_shares[NUMBER_OF_SHARES - 14];
+    let gamma_3 = x_shares[NUMBER_OF_SHARES - 13];
+    let gamma_4 = x_shares[NUMBER_OF_SHARES - 12];
+    let gamma_5 = x_shares[NUMBER_OF_SHARES - 11];
 
     // CB-Input([/gamma_0], /alpha)
     let (alpha_share, d_2) = match gamma_0.cb_input(&msg1.open_2, &alpha, params) {
         Ok(v) => v,
-        Err(_) => return Err(CFMError::InvalidOpen),
+        Err(e) => return Err(CFMError::InvalidOpen(FailedCheck::cfm("open_2", 0), e)),
     };
 
     // CB-Input([/gamma_1], /beta)
     let (beta_share, d_3) = match gamma_1.cb_input(&msg1.open_3, &beta, params) {
         Ok(v) => v,
-        Err(_) => return Err(CFMError::InvalidOpen),
+        Err(e) => return Err(CFMError::InvalidOpen(FailedCheck::cfm("open_3", 0), e)),
     };
 
     // CB-Input([/gamma_3], r_CB)
     let r_cb = DynResidue::new(&U128::random(&mut *rng), params).retrieve();
     let (r_cb_share, d_4) = match gamma_3.cb_input(&msg1.open_4, &r_cb, params) {
         Ok(v) => v,
-        Err(_) => return Err(CFMError::InvalidOpen),
+        Err(e) => return Err(CFMError::InvalidOpen(FailedCheck::cfm("open_4", 0), e)),
+    };
+
+    // CB-Input([/gamma_5], /alpha_L)
+    let (alpha_limit_share, d_5) = match gamma_5.cb_input(&msg1.open_5, &alpha_limit, params) {
+        Ok(v) => v,
+        Err(e) => return Err(CFMError::InvalidOpen(FailedCheck::cfm("open_5", 0), e)),
     };
 
+    // CB-Input([/gamma_6], /lambda), CB-Input([/gamma_7], /sigma_mu),
+    // CB-Input([/gamma_8], /sigma_omega) and CB-Input([/gamma_9], /alpha_omega)
+    let lambda = ledger.lambda();
+    let inputs = [
+        (&msg1.open_6, lambda),
+        (&msg1.open_7, sigma_key.retrieve()),
+        (&msg1.open_8, sigma_weight.retrieve()),
+        (&msg1.open_9, alpha_weight),
+    ];
+    let mut ledger_shares = [Share::default(); 4];
+    let mut ledger_d = [U128::ZERO; 4];
+    for (i, (open, value)) in inputs.iter().enumerate() {
+        let gamma = x_shares[NUMBER_OF_SHARES - 10 + i];
+        (ledger_shares[i], ledger_d[i]) = match gamma.cb_input(open, value, params) {
+            Ok(v) => v,
+            Err(e) => return Err(CFMError::InvalidOpen(FailedCheck::cfm("open_6", i), e)),
+        };
+    }
+    let [lambda_share, sigma_key_share, sigma_weight_share, alpha_weight_share] = ledger_shares;
+    let [d_6, d_7, d_8, d_9] = ledger_d;
+
+    // RVOLE of the ledger key refresh, OB gets /lambda - b for the scalar b
+    // of CB
+    let mut ledger_vole_msg1 = ZS::<Round1Output>::default();
+    let (ledger_vole_receiver, vole_b) = RVOLEReceiver::new(
+        ledger_vole_session_id(session_id),
+        p,
+        &ot_seeds.sender_ot_seed_0,
+        &mut ledger_vole_msg1,
+        ledger_size,
+        &mut *rng,
+    );
+    let ledger_e = DynResidue::new(&lambda, params)
+        .sub(&DynResidue::new(&vole_b, params))
+        .retrieve();
+
     let open_0 = gamma_2.open();
     let open_4 = gamma_4.open();
     let mut open_1 = [(U128::ZERO, U128::ZERO); L];
@@ -1098,16 +1783,28 @@ pub fn cfm_process_msg1<R: CryptoRng + RngCore>(
         let x_share_3 = x_shares[offset + i];
         open_3[i] = x_share_3.open();
     }
+    let ledger_opens: [(U128, U128); 5] =
+        core::array::from_fn(|i| x_shares[NUMBER_OF_SHARES - 5 + i].open());
 
     let state = Box::new(CFMStateCBR1 {
         session_id: *session_id,
         p,
-        big_l,
+        output_policy,
+        big_l: (!private_limit).then_some(big_l),
         z_i_cb_shares,
         w_i_shares,
         alpha_share,
+        alpha_limit_share,
         beta_share,
         r_cb_share,
+        lambda_share,
+        sigma_key_share,
+        sigma_weight_share,
+        alpha_weight_share,
+        open_d: msg1.open_10,
+        ledger_size,
+        ledger_weight_seed,
+        ledger_vole_receiver,
     });
 
     let msg2 = Box::new(CFMMsg2 {
@@ -1118,12 +1815,25 @@ pub fn cfm_process_msg1<R: CryptoRng + RngCore>(
         d_2,
         d_3,
         d_4,
+        d_5,
+        d_6,
+        d_7,
+        d_8,
+        d_9,
         open_0,
         open_1,
         open_2,
         open_3,
         open_4,
+        open_5: ledger_opens[0],
+        open_6: ledger_opens[1],
+        open_7: ledger_opens[2],
+        open_8: ledger_opens[3],
+        open_9: ledger_opens[4],
         open_z_l_minus_1: z_i_cb_shares[L - 1].open(),
+        ledger_size: ledger_size as u64,
+        ledger_vole_msg1,
+        ledger_e,
     });
 
     Ok((state, msg2))
@@ -1174,37 +1884,72 @@ pub fn cfm_process_msg2<R: CryptoRng + RngCore>(
         params,
     ) {
         Ok(v) => v,
-        Err(_) => return Err(CFMError::InvalidOpen),
+        Err(e) => return Err(CFMError::InvalidOpen(FailedCheck::cfm("open_z_l_minus_1", 0), e)),
     };
     if z_l_minus_1_value != U128::ZERO {
-        return Err(CFMError::AbortProtocol);
+        let check = FailedCheck::cfm("open_z_l_minus_1", 0);
+        let open = &msg2.open_z_l_minus_1;
+        return Err(wrong_value(check, &z_i_cb_shares[L - 1], open, U128::ZERO, params));
     }
 
-    let gamma_0 = x_shares[NUMBER_OF_SHARES - 5];
-    let gamma_1 = x_shares[NUMBER_OF_SHARES - 4];
-    let gamma_2 = x_shares[NUMBER_OF_SHARES - 3];
-    let gamma_3 = x_shares[NUMBER_OF_SHARES - 2];
-    let gamma_4 = x_shares[NUMBER_OF_SHARES - 1];
+    let gamma_0 = x_shares[NUMBER_OF_SHARES - 16];
+    let gamma_1 = x_shares[NUMBER_OF_SHARES - 15];
+    let gamma_2 = x_shares[NUMBER_OF_SHARES - 14];
+    let gamma_3 = x_shares[NUMBER_OF_SHARES - 13];
+    let gamma_4 = x_shares[NUMBER_OF_SHARES - 12];
+    let gamma_5 = x_shares[NUMBER_OF_SHARES - 11];
 
     // CB-Input([/gamma_0], /alpha)
     let alpha_share = gamma_0.add_const_ob(&msg2.d_2, params);
 
+    // CB-Input([/gamma_5], /alpha_L)
+    let alpha_limit_share = gamma_5.add_const_ob(&msg2.d_5, params);
+
     // CB-Input([/gamma_1], /beta)
     let beta_share = gamma_1.add_const_ob(&msg2.d_3, params);
 
     // CB-Input([/gamma_3], r_CB)
     let r_cb_share = gamma_3.add_const_ob(&msg2.d_4, params);
 
-    let (z_y_ob, big_m_y) = match psit_process_msg2(&state.psit_state_ob, &msg2.psit_msg2) {
-        Ok(v) => v,
-        Err(_) => (
-            U128::ZERO,
-            DynResidue::new(&U128::random(&mut *rng), params).retrieve(),
-        ),
+    // CB-Input([/gamma_6], /lambda), CB-Input([/gamma_7], /sigma_mu),
+    // CB-Input([/gamma_8], /sigma_omega), CB-Input([/gamma_9], /alpha_omega)
+    let lambda_share = x_shares[NUMBER_OF_SHARES - 10].add_const_ob(&msg2.d_6, params);
+    let sigma_key_share = x_shares[NUMBER_OF_SHARES - 9].add_const_ob(&msg2.d_7, params);
+    let sigma_weight_share = x_shares[NUMBER_OF_SHARES - 8].add_const_ob(&msg2.d_8, params);
+    let alpha_weight_share = x_shares[NUMBER_OF_SHARES - 7].add_const_ob(&msg2.d_9, params);
+
+    let record = match psit_process_msg2(&state.psit_state_ob, &msg2.psit_msg2) {
+        Ok(record) => record.0,
+        Err(_) => {
+            let mut words = [U128::ZERO; PSIT_RECORD_WORDS];
+            words[RECORD_MAC] = DynResidue::new(&U128::random(&mut *rng), params).retrieve();
+            words
+        }
+    };
+    let z_y_ob = record[RECORD_FLOW];
+    let l_y_ob = record[RECORD_LIMIT];
+    let big_m_y = record[RECORD_MAC];
+
+    // share and tag of the ledger slot of y, A = X + Z_L is the amount
+    let ledger_size = usize::try_from(msg2.ledger_size).map_err(|_| CFMError::InvalidMessage)?;
+    let slot = match usize::try_from(record[RECORD_SLOT].as_words()[0]) {
+        Ok(slot) if record[RECORD_SLOT] < U128::from_u64(ledger_size as u64) => slot,
+        _ => return Err(CFMError::InvalidMessage),
     };
+    let ledger_slot = state.ledger.slot(slot);
+    let big_a = state.big_x.wrapping_add(&ledger_slot.share);
+    if big_a >= U64::ONE.shl(B_PARAMETER) {
+        return Err(CFMError::InvalidInput);
+    }
+    let z_l = ledger_slot.share.resize();
+    let v = DynResidue::new(&ledger_slot.tag, params)
+        .sub(&DynResidue::new(&record[RECORD_KEY], params))
+        .retrieve();
+    let weight = record[RECORD_WEIGHT];
 
-    let big_u = DynResidue::new(&state.big_x.resize(), params)
+    let big_u = DynResidue::new(&big_a.resize(), params)
         .add(&DynResidue::new(&z_y_ob, params))
+        .add(&DynResidue::new(&l_y_ob, params))
         .retrieve();
 
     // println!("22222222222222");
@@ -1213,7 +1958,7 @@ pub fn cfm_process_msg2<R: CryptoRng + RngCore>(
     // OB-Input([/gamma_2], /M_Y)
     let (big_m_y_share, d_0) = match gamma_2.ob_input(&msg2.open_0, &big_m_y, params) {
         Ok(v) => v,
-        Err(_) => return Err(CFMError::InvalidOpen),
+        Err(e) => return Err(CFMError::InvalidOpen(FailedCheck::cfm("open_0", 0), e)),
     };
 
     // println!("33333333333333");
@@ -1223,11 +1968,62 @@ pub fn cfm_process_msg2<R: CryptoRng + RngCore>(
     let r_ob = DynResidue::new(&U128::random(&mut *rng), params).retrieve();
     let (r_ob_share, d_4) = match gamma_4.ob_input(&msg2.open_4, &r_ob, params) {
         Ok(v) => v,
-        Err(_) => return Err(CFMError::InvalidOpen),
+        Err(e) => return Err(CFMError::InvalidOpen(FailedCheck::cfm("open_4", 0), e)),
     };
 
     let r_share = r_cb_share.add_share(&r_ob_share, params);
 
+    // OB-Input([/gamma_11], Z_L), OB-Input([/gamma_12], V),
+    // OB-Input([/gamma_13], weight word)
+    let inputs = [(&msg2.open_5, z_l), (&msg2.open_6, v), (&msg2.open_7, weight)];
+    let mut ledger_shares = [Share::default(); 3];
+    let mut ledger_d = [U128::ZERO; 3];
+    for (i, (open, value)) in inputs.iter().enumerate() {
+        let gamma = x_shares[NUMBER_OF_SHARES - 5 + i];
+        (ledger_shares[i], ledger_d[i]) = match gamma.ob_input(open, value, params) {
+            Ok(v) => v,
+            Err(e) => return Err(CFMError::InvalidOpen(FailedCheck::cfm("open_5", i), e)),
+        };
+    }
+    let [z_l_share, v_share, weight_share] = ledger_shares;
+    let [d_5, d_6, d_7] = ledger_d;
+
+    // RVOLE on δ = X at the slot of y and 0 elsewhere, c'_i = c_i + δ_i·e so
+    // that c'_i + d_i = /lambda·δ_i
+    let mut delta = vec![U128::ZERO; ledger_size];
+    if ledger_size > 0 {
+        delta[slot] = state.big_x.resize();
+    }
+    let mut ledger_vole_msg2 = RVOLEOutput::new(ledger_size);
+    let c = match RVOLESender::process(
+        &ledger_vole_session_id(&state.session_id),
+        &state.p,
+        &state.ot_seed,
+        &delta,
+        &msg2.ledger_vole_msg1,
+        &mut ledger_vole_msg2,
+        ledger_size,
+        &mut *rng,
+    ) {
+        Ok(c) => c,
+        Err(e) => return Err(CFMError::LedgerVOLE(FailedCheck::cfm("ledger_vole_msg1", 0), e)),
+    };
+    let tags = c
+        .iter()
+        .zip(&delta)
+        .map(|(c, delta)| {
+            DynResidue::new(c, params)
+                .add(&DynResidue::new(delta, params).mul(&DynResidue::new(&msg2.ledger_e, params)))
+                .retrieve()
+        })
+        .collect();
+    let ledger_update = TagUpdate {
+        p: state.p,
+        slot,
+        big_x: state.big_x,
+        tags,
+    };
+
     let mut d_1 = [U128::ZERO; L];
     let mut d_2 = [U128::ZERO; B_PARAMETER];
     let mut d_3 = [U128::ZERO; L];
@@ -1243,19 +2039,19 @@ pub fn cfm_process_msg2<R: CryptoRng + RngCore>(
 
         (z_y_i_ob_shares[i], d_1[i]) = match x_share.ob_input(&msg2.open_1[i], &y, params) {
             Ok(v) => v,
-            Err(_) => return Err(CFMError::InvalidOpen),
+            Err(e) => return Err(CFMError::InvalidOpen(FailedCheck::cfm("open_1", i), e)),
         };
     }
     let offset = 3 * L;
     for i in 0..B_PARAMETER {
         // OB-Input([x_{3l+i}], X_i)
         let x_share = x_shares[offset + i];
-        let y = U128::from_u8(Choice::from(state.big_x.bit(i)).unwrap_u8());
+        let y = U128::from_u8(Choice::from(big_a.bit(i)).unwrap_u8());
         // println!("55555555555555 {:?}", i);
 
         (x_i_shares[i], d_2[i]) = match x_share.ob_input(&msg2.open_2[i], &y, params) {
             Ok(v) => v,
-            Err(_) => return Err(CFMError::InvalidOpen),
+            Err(e) => return Err(CFMError::InvalidOpen(FailedCheck::cfm("open_2", i), e)),
         };
     }
     let offset = 3 * L + B_PARAMETER;
@@ -1267,12 +2063,12 @@ pub fn cfm_process_msg2<R: CryptoRng + RngCore>(
 
         (u_i_shares[i], d_3[i]) = match x_share.ob_input(&msg2.open_3[i], &y, params) {
             Ok(v) => v,
-            Err(_) => return Err(CFMError::InvalidOpen),
+            Err(e) => return Err(CFMError::InvalidOpen(FailedCheck::cfm("open_3", i), e)),
         };
     }
 
-    let mut mul_state_test_bit = [MulSharesState::default(); NUMBER_OF_SHARES - 3];
-    let mut mul_open_test_bit = [MulSharesOpen::default(); NUMBER_OF_SHARES - 3];
+    let mut mul_state_test_bit = [MulSharesState::default(); NUMBER_OF_SHARES - 14];
+    let mut mul_open_test_bit = [MulSharesOpen::default(); NUMBER_OF_SHARES - 14];
     for i in 0..L {
         let auth_triple = &auth_triples[i];
         (mul_state_test_bit[i], mul_open_test_bit[i]) = test_bit_open(
@@ -1343,9 +2139,11 @@ pub fn cfm_process_msg2<R: CryptoRng + RngCore>(
     let state = Box::new(CFMStateOBR2 {
         session_id: state.session_id,
         p: state.p,
+        output_policy: state.output_policy,
         big_l: state.big_l,
         r_share,
         alpha_share,
+        alpha_limit_share,
         beta_share,
         big_m_y_share,
         z_i_cb_shares,
@@ -1354,6 +2152,22 @@ pub fn cfm_process_msg2<R: CryptoRng + RngCore>(
         x_i_shares,
         u_i_shares,
         mul_state_test_bit,
+        auth_triple_a_limit: auth_triples[AUTH_TRIPLES_OFFSET + 2 * 375 + 3],
+        lambda_share,
+        sigma_key_share,
+        sigma_weight_share,
+        alpha_weight_share,
+        z_l_share,
+        v_share,
+        weight_share,
+        ledger_gammas: [
+            x_shares[NUMBER_OF_SHARES - 6],
+            x_shares[NUMBER_OF_SHARES - 2],
+            x_shares[NUMBER_OF_SHARES - 1],
+        ],
+        ledger_opens: [msg2.open_8, msg2.open_9],
+        ledger_update,
+        auth_triples_ledger: core::array::from_fn(|i| auth_triples[LEDGER_TRIPLES_OFFSET + i]),
     });
 
     let msg3 = Box::new(CFMMsg3 {
@@ -1363,6 +2177,10 @@ pub fn cfm_process_msg2<R: CryptoRng + RngCore>(
         d_2,
         d_3,
         d_4,
+        d_5,
+        d_6,
+        d_7,
+        ledger_vole_msg2,
         mul_open_test_bit,
     });
 
@@ -1384,8 +2202,8 @@ pub fn cfm_process_msg3(
     }
     let params = DynResidueParams::new(&state.p);
 
-    let gamma_2 = x_shares[NUMBER_OF_SHARES - 3];
-    let gamma_4 = x_shares[NUMBER_OF_SHARES - 1];
+    let gamma_2 = x_shares[NUMBER_OF_SHARES - 14];
+    let gamma_4 = x_shares[NUMBER_OF_SHARES - 12];
 
     // OB-Input([/gamma_2], /M_Y)
     let big_m_y_share = gamma_2.add_const_cb(&msg3.d_0, params);
@@ -1395,6 +2213,36 @@ pub fn cfm_process_msg3(
 
     let r_share = state.r_cb_share.add_share(&r_ob_share, params);
 
+    // OB-Input([/gamma_11], Z_L), OB-Input([/gamma_12], V),
+    // OB-Input([/gamma_13], weight word)
+    let z_l_share = x_shares[NUMBER_OF_SHARES - 5].add_const_cb(&msg3.d_5, params);
+    let v_share = x_shares[NUMBER_OF_SHARES - 4].add_const_cb(&msg3.d_6, params);
+    let weight_share = x_shares[NUMBER_OF_SHARES - 3].add_const_cb(&msg3.d_7, params);
+
+    // key updates d_i of the RVOLE and D = Σ ω_i·d_i
+    let keys = match state
+        .ledger_vole_receiver
+        .process(&msg3.ledger_vole_msg2, state.ledger_size)
+    {
+        Ok(keys) => keys,
+        Err(e) => return Err(CFMError::LedgerVOLE(FailedCheck::cfm("ledger_vole_msg2", 0), e)),
+    };
+    let weights = ledger_weights(
+        &state.session_id,
+        &state.ledger_weight_seed,
+        state.ledger_size,
+        params,
+    );
+    let big_d = inner_product(&weights, &keys, params);
+
+    // CB-Input([/gamma_10], D)
+    let gamma_10 = x_shares[NUMBER_OF_SHARES - 6];
+    let (d_share, d_0) = match gamma_10.cb_input(&state.open_d, &big_d, params) {
+        Ok(v) => v,
+        Err(e) => return Err(CFMError::InvalidOpen(FailedCheck::cfm("open_10", 0), e)),
+    };
+    let ledger_weight_seed = state.ledger_weight_seed;
+
     let mut z_y_i_ob_shares = [Share::default(); L];
     let mut x_i_shares = [Share::default(); B_PARAMETER];
     let mut u_i_shares = [Share::default(); L];
@@ -1414,9 +2262,9 @@ pub fn cfm_process_msg3(
         u_i_shares[i] = x_shares[offset + i].add_const_cb(&msg3.d_3[i], params);
     }
 
-    let mut t_i_shares = [Share::default(); NUMBER_OF_SHARES - 3];
-    let mut open_t_i = [(U128::ZERO, U128::ZERO); NUMBER_OF_SHARES - 3];
-    let mut mul_open_test_bit = [MulSharesOpen::default(); NUMBER_OF_SHARES - 3];
+    let mut t_i_shares = [Share::default(); NUMBER_OF_SHARES - 14];
+    let mut open_t_i = [(U128::ZERO, U128::ZERO); NUMBER_OF_SHARES - 14];
+    let mut mul_open_test_bit = [MulSharesOpen::default(); NUMBER_OF_SHARES - 14];
     for i in 0..L {
         let auth_triple = &auth_triples[i];
         let (mul_state, mul_open) = test_bit_open(
@@ -1432,7 +2280,10 @@ pub fn cfm_process_msg3(
         t_i_shares[i] = match test_bit_output(&mul_state, &msg3.mul_open_test_bit[i], true, params)
         {
             Ok(v) => v,
-            Err(_) => return Err(CFMError::InvalidOpen),
+            Err(e) => {
+                let check = FailedCheck::abt_sacrifice("mul_open_test_bit", i);
+                return Err(CFMError::InvalidOpen(check, e));
+            }
         };
         open_t_i[i] = t_i_shares[i].open();
     }
@@ -1456,7 +2307,10 @@ pub fn cfm_process_msg3(
             params,
         ) {
             Ok(v) => v,
-            Err(_) => return Err(CFMError::InvalidOpen),
+            Err(e) => {
+                let check = FailedCheck::abt_sacrifice("mul_open_test_bit", offset + i);
+                return Err(CFMError::InvalidOpen(check, e));
+            }
         };
         open_t_i[offset + i] = t_i_shares[offset + i].open();
     }
@@ -1480,7 +2334,10 @@ pub fn cfm_process_msg3(
             params,
         ) {
             Ok(v) => v,
-            Err(_) => return Err(CFMError::InvalidOpen),
+            Err(e) => {
+                let check = FailedCheck::abt_sacrifice("mul_open_test_bit", offset + i);
+                return Err(CFMError::InvalidOpen(check, e));
+            }
         };
         open_t_i[offset + i] = t_i_shares[offset + i].open();
     }
@@ -1504,7 +2361,10 @@ pub fn cfm_process_msg3(
             params,
         ) {
             Ok(v) => v,
-            Err(_) => return Err(CFMError::InvalidOpen),
+            Err(e) => {
+                let check = FailedCheck::abt_sacrifice("mul_open_test_bit", offset + i);
+                return Err(CFMError::InvalidOpen(check, e));
+            }
         };
         open_t_i[offset + i] = t_i_shares[offset + i].open();
     }
@@ -1528,7 +2388,10 @@ pub fn cfm_process_msg3(
             params,
         ) {
             Ok(v) => v,
-            Err(_) => return Err(CFMError::InvalidOpen),
+            Err(e) => {
+                let check = FailedCheck::abt_sacrifice("mul_open_test_bit", offset + i);
+                return Err(CFMError::InvalidOpen(check, e));
+            }
         };
         open_t_i[offset + i] = t_i_shares[offset + i].open();
     }
@@ -1536,9 +2399,11 @@ pub fn cfm_process_msg3(
     let state = Box::new(CFMStateCBR2 {
         session_id: state.session_id,
         p: state.p,
+        output_policy: state.output_policy,
         big_l: state.big_l,
         r_share,
         alpha_share: state.alpha_share,
+        alpha_limit_share: state.alpha_limit_share,
         beta_share: state.beta_share,
         big_m_y_share,
         z_i_cb_shares: state.z_i_cb_shares,
@@ -1547,12 +2412,24 @@ pub fn cfm_process_msg3(
         x_i_shares,
         u_i_shares,
         t_i_shares,
+        lambda_share: state.lambda_share,
+        sigma_key_share: state.sigma_key_share,
+        sigma_weight_share: state.sigma_weight_share,
+        alpha_weight_share: state.alpha_weight_share,
+        d_share,
+        z_l_share,
+        v_share,
+        weight_share,
+        ledger_gammas: [x_shares[NUMBER_OF_SHARES - 2], x_shares[NUMBER_OF_SHARES - 1]],
+        ledger_update: KeyUpdate { p: state.p, keys },
     });
 
     let msg4 = Box::new(CFMMsg4 {
         session_id: state.session_id,
         mul_open_test_bit,
         open_t_i,
+        d_0,
+        ledger_weight_seed,
     });
 
     Ok((state, msg4))
@@ -1568,9 +2445,9 @@ pub fn cfm_process_msg4(
     }
     let params = DynResidueParams::new(&state.p);
 
-    let mut open_t_i = [(U128::ZERO, U128::ZERO); NUMBER_OF_SHARES - 3];
+    let mut open_t_i = [(U128::ZERO, U128::ZERO); NUMBER_OF_SHARES - 14];
     #[allow(clippy::needless_range_loop)]
-    for i in 0..(NUMBER_OF_SHARES - 3) {
+    for i in 0..(NUMBER_OF_SHARES - 14) {
         let t_i_share = match test_bit_output(
             &state.mul_state_test_bit[i],
             &msg4.mul_open_test_bit[i],
@@ -1578,16 +2455,20 @@ pub fn cfm_process_msg4(
             params,
         ) {
             Ok(v) => v,
-            Err(_) => return Err(CFMError::InvalidOpen),
+            Err(e) => {
+                let check = FailedCheck::abt_sacrifice("mul_open_test_bit", i);
+                return Err(CFMError::InvalidOpen(check, e));
+            }
         };
         open_t_i[i] = t_i_share.open();
         let t_i_value =
             match t_i_share.validate_open(&msg4.open_t_i[i].0, &msg4.open_t_i[i].1, params) {
                 Ok(v) => v,
-                Err(_) => return Err(CFMError::InvalidOpen),
+                Err(e) => return Err(CFMError::InvalidOpen(FailedCheck::cfm("open_t_i", i), e)),
             };
         if t_i_value != U128::ONE {
-            return Err(CFMError::AbortProtocol);
+            let check = FailedCheck::cfm("open_t_i", i);
+            return Err(wrong_value(check, &t_i_share, &msg4.open_t_i[i], U128::ONE, params));
         }
     }
 
@@ -1623,19 +2504,76 @@ pub fn cfm_process_msg4(
         );
     }
 
-    let big_v = big_u_share
+    // [L_Y^OB] = [U] - [X] - [Z_Y^OB], the limit word of the record
+    let big_l_y_ob_share = big_u_share
         .sub_share(&big_x_share, params)
         .sub_share(&big_z_y_ob_share, params);
 
-    let share_w = big_z_cb_share.add_const_ob(&state.big_l.resize(), params);
-    let big_t = big_w_share.sub_share(&share_w, params);
+    let big_t = state.big_l.map(|big_l| {
+        let share_w = big_z_cb_share
+            .add_share(&big_l_y_ob_share, params)
+            .add_const_ob(&big_l.resize(), params);
+        big_w_share.sub_share(&share_w, params)
+    });
+
+    let open_t = big_t.as_ref().map(Share::open);
+
+    let (mul_state_a_limit, mul_open_a_limit) = multiply_shares_open(
+        &state.alpha_limit_share,
+        &big_l_y_ob_share,
+        &state.auth_triple_a_limit.x,
+        &state.auth_triple_a_limit.y,
+        &state.auth_triple_a_limit.z,
+        params,
+    );
 
-    let open_v = big_v.open();
-    let open_t = big_t.open();
+    // CB-Input([/gamma_10], D)
+    let d_share = state.ledger_gammas[0].add_const_ob(&msg4.d_0, params);
+
+    // S = ω_Y·X and C = Σ ω_i·c'_i
+    let update = &state.ledger_update;
+    let weights = ledger_weights(
+        &state.session_id,
+        &msg4.ledger_weight_seed,
+        update.tags.len(),
+        params,
+    );
+    let big_s = match weights.get(update.slot) {
+        Some(weight) => DynResidue::new(weight, params)
+            .mul(&DynResidue::new(&update.big_x.resize(), params))
+            .retrieve(),
+        None => U128::ZERO,
+    };
+    let big_c = inner_product(&weights, &update.tags, params);
+
+    // OB-Input([/gamma_14], S) and OB-Input([/gamma_15], C)
+    let (s_share, d_0) =
+        match state.ledger_gammas[1].ob_input(&state.ledger_opens[0], &big_s, params) {
+            Ok(v) => v,
+            Err(e) => return Err(CFMError::InvalidOpen(FailedCheck::cfm("open_8", 0), e)),
+        };
+    let (c_share, d_1) =
+        match state.ledger_gammas[2].ob_input(&state.ledger_opens[1], &big_c, params) {
+            Ok(v) => v,
+            Err(e) => return Err(CFMError::InvalidOpen(FailedCheck::cfm("open_9", 0), e)),
+        };
+
+    let (mul_state_ledger, mul_open_ledger) = ledger_mul_open(
+        &state.lambda_share,
+        &state.sigma_weight_share,
+        &state.alpha_weight_share,
+        &state.z_l_share,
+        &state.weight_share,
+        &big_x_share,
+        &s_share,
+        &state.auth_triples_ledger,
+        params,
+    );
 
     let state = Box::new(CFMStateOBR3 {
         session_id: state.session_id,
         p: state.p,
+        output_policy: state.output_policy,
         r_share: state.r_share,
         alpha_share: state.alpha_share,
         beta_share: state.beta_share,
@@ -1646,15 +2584,24 @@ pub fn cfm_process_msg4(
         z_y_i_ob_shares: state.z_y_i_ob_shares,
         x_i_shares: state.x_i_shares,
         u_i_shares: state.u_i_shares,
-        big_v,
         big_t,
+        mul_state_a_limit,
+        sigma_key_share: state.sigma_key_share,
+        v_share: state.v_share,
+        s_share,
+        c_share,
+        d_share,
+        mul_state_ledger,
     });
 
     let msg5 = Box::new(CFMMsg5 {
         session_id: state.session_id,
         open_t_i,
-        open_v,
         open_t,
+        mul_open_a_limit,
+        d_0,
+        d_1,
+        mul_open_ledger,
     });
 
     Ok((state, msg5))
@@ -1673,17 +2620,19 @@ pub fn cfm_process_msg5(
     }
     let params = DynResidueParams::new(&state.p);
 
-    for i in 0..(NUMBER_OF_SHARES - 3) {
+    for i in 0..(NUMBER_OF_SHARES - 14) {
         let t_i_value = match state.t_i_shares[i].validate_open(
             &msg5.open_t_i[i].0,
             &msg5.open_t_i[i].1,
             params,
         ) {
             Ok(v) => v,
-            Err(_) => return Err(CFMError::InvalidOpen),
+            Err(e) => return Err(CFMError::InvalidOpen(FailedCheck::cfm("open_t_i", i), e)),
         };
         if t_i_value != U128::ONE {
-            return Err(CFMError::AbortProtocol);
+            let check = FailedCheck::cfm("open_t_i", i);
+            let share = &state.t_i_shares[i];
+            return Err(wrong_value(check, share, &msg5.open_t_i[i], U128::ONE, params));
         }
     }
 
@@ -1719,30 +2668,73 @@ pub fn cfm_process_msg5(
         );
     }
 
-    let big_v = big_u_share
+    // [L_Y^OB] = [U] - [X] - [Z_Y^OB], the limit word of the record
+    let big_l_y_ob_share = big_u_share
         .sub_share(&big_x_share, params)
         .sub_share(&big_z_y_ob_share, params);
 
-    let share_w = big_z_cb_share.add_const_cb(&state.big_l.resize(), params);
-    let big_t = big_w_share.sub_share(&share_w, params);
+    let big_t = state.big_l.map(|big_l| {
+        let share_w = big_z_cb_share
+            .add_share(&big_l_y_ob_share, params)
+            .add_const_cb(&big_l.resize(), params);
+        big_w_share.sub_share(&share_w, params)
+    });
 
-    let open_v = big_v.open();
-    let open_t = big_t.open();
-    let big_v_value = match big_v.validate_open(&msg5.open_v.0, &msg5.open_v.1, params) {
-        Ok(v) => v,
-        Err(_) => return Err(CFMError::InvalidOpen),
-    };
-    let big_t_value = match big_t.validate_open(&msg5.open_t.0, &msg5.open_t.1, params) {
-        Ok(v) => v,
-        Err(_) => return Err(CFMError::InvalidOpen),
-    };
+    let open_t = big_t.as_ref().map(Share::open);
+    check_open_t(&big_t, &msg5.open_t, params)?;
+
+    // [beta] + [alpha_L] * [L_Y^OB], the part of the MAC over the limit word
+    let auth_triple = &auth_triples[AUTH_TRIPLES_OFFSET + 2 * 375 + 3];
+    let (mul_state_a_limit, mul_open_a_limit) = multiply_shares_open(
+        &state.alpha_limit_share,
+        &big_l_y_ob_share,
+        &auth_triple.x,
+        &auth_triple.y,
+        &auth_triple.z,
+        params,
+    );
+    let a_limit_share =
+        match multiply_shares_output(&mul_state_a_limit, &msg5.mul_open_a_limit, true, params) {
+            Ok(v) => v,
+            Err(e) => {
+                let check = FailedCheck::abt_sacrifice("mul_open_a_limit", 0);
+                return Err(CFMError::InvalidOpen(check, e));
+            }
+        };
 
-    if big_v_value != U128::ZERO {
-        return Err(CFMError::AbortProtocol);
-    }
-    if big_t_value != U128::ZERO {
-        return Err(CFMError::AbortProtocol);
-    }
+    // OB-Input([/gamma_14], S) and OB-Input([/gamma_15], C)
+    let s_share = state.ledger_gammas[0].add_const_cb(&msg5.d_0, params);
+    let c_share = state.ledger_gammas[1].add_const_cb(&msg5.d_1, params);
+
+    let auth_triples_ledger = core::array::from_fn(|i| auth_triples[LEDGER_TRIPLES_OFFSET + i]);
+    let (mul_state_ledger, mul_open_ledger) = ledger_mul_open(
+        &state.lambda_share,
+        &state.sigma_weight_share,
+        &state.alpha_weight_share,
+        &state.z_l_share,
+        &state.weight_share,
+        &big_x_share,
+        &s_share,
+        &auth_triples_ledger,
+        params,
+    );
+    let [m_0, m_1, m_2, m_3] =
+        ledger_mul_output(&mul_state_ledger, &msg5.mul_open_ledger, true, params)?;
+
+    let beta_share = ledger_beta_share(
+        &state.beta_share,
+        &a_limit_share,
+        &[m_0, m_1],
+        &state.v_share,
+        &state.sigma_key_share,
+        params,
+    );
+    // [S] - [ω_Y]·([A] - [Z_L]) and [/lambda]·[S] - [C] - [D], both zero
+    let ledger_check_shares = [
+        s_share.sub_share(&m_2, params),
+        m_3.sub_share(&c_share, params).sub_share(&state.d_share, params),
+    ];
+    let open_ledger = ledger_check_shares.map(|share| share.open());
 
     let mut t = Transcript::new(CFM_LABEL.as_ref());
     t.append_message(b"session-id", &state.session_id);
@@ -1769,9 +2761,10 @@ pub fn cfm_process_msg5(
     let state = CFMStateCBR3 {
         session_id: state.session_id,
         p: state.p,
+        output_policy: state.output_policy,
         r_share: state.r_share,
         alpha_share: state.alpha_share,
-        beta_share: state.beta_share,
+        beta_share,
         big_m_y_share: state.big_m_y_share,
         big_z_y_ob_share,
         comp_0_state_cb_r0,
@@ -1780,12 +2773,15 @@ pub fn cfm_process_msg5(
         w_i_shares: state.w_i_shares,
         z_y_i_ob_shares: state.z_y_i_ob_shares,
         u_i_shares: state.u_i_shares,
+        ledger_check_shares,
     };
 
     let msg6 = Box::new(CFMMsg6 {
         session_id: state.session_id,
-        open_v,
         open_t,
+        mul_open_a_limit,
+        mul_open_ledger,
+        open_ledger,
         comp_msg1_c: comp_0_msg1,
         comp_msg1_b: comp_1_msg1,
     });
@@ -1806,26 +2802,40 @@ pub fn cfm_process_msg6(
     }
 
     let params = DynResidueParams::new(&state.p);
-    let big_v_value = match state
-        .big_v
-        .validate_open(&msg6.open_v.0, &msg6.open_v.1, params)
-    {
-        Ok(v) => v,
-        Err(_) => return Err(CFMError::InvalidOpen),
-    };
-    let big_t_value = match state
-        .big_t
-        .validate_open(&msg6.open_t.0, &msg6.open_t.1, params)
-    {
+    check_open_t(&state.big_t, &msg6.open_t, params)?;
+
+    // [beta] + [alpha_L] * [L_Y^OB], the part of the MAC over the limit word
+    let a_limit_share = match multiply_shares_output(
+        &state.mul_state_a_limit,
+        &msg6.mul_open_a_limit,
+        false,
+        params,
+    ) {
         Ok(v) => v,
-        Err(_) => return Err(CFMError::InvalidOpen),
+        Err(e) => {
+            let check = FailedCheck::abt_sacrifice("mul_open_a_limit", 0);
+            return Err(CFMError::InvalidOpen(check, e));
+        }
     };
-    if big_v_value != U128::ZERO {
-        return Err(CFMError::AbortProtocol);
-    }
-    if big_t_value != U128::ZERO {
-        return Err(CFMError::AbortProtocol);
-    }
+    let [m_0, m_1, m_2, m_3] =
+        ledger_mul_output(&state.mul_state_ledger, &msg6.mul_open_ledger, false, params)?;
+    let beta_share = ledger_beta_share(
+        &state.beta_share,
+        &a_limit_share,
+        &[m_0, m_1],
+        &state.v_share,
+        &state.sigma_key_share,
+        params,
+    );
+
+    // [S] - [ω_Y]·([A] - [Z_L]) and [/lambda]·[S] - [C] - [D], both zero
+    let ledger_check_shares = [
+        state.s_share.sub_share(&m_2, params),
+        m_3.sub_share(&state.c_share, params)
+            .sub_share(&state.d_share, params),
+    ];
+    check_open_ledger(&ledger_check_shares, &msg6.open_ledger, params)?;
+    let open_ledger = ledger_check_shares.map(|share| share.open());
 
     let mut t = Transcript::new(CFM_LABEL.as_ref());
     t.append_message(b"session-id", &state.session_id);
@@ -1843,7 +2853,7 @@ pub fn cfm_process_msg6(
         &msg6.comp_msg1_c,
     ) {
         Ok(v) => v,
-        Err(_) => return Err(CFMError::Comparison),
+        Err(e) => return Err(CFMError::Comparison(0, e)),
     };
 
     let (comp_1_state_ob_r1, comp_1_msg2) = match comp_process_msg1(
@@ -1855,15 +2865,16 @@ pub fn cfm_process_msg6(
         &msg6.comp_msg1_b,
     ) {
         Ok(v) => v,
-        Err(_) => return Err(CFMError::Comparison),
+        Err(e) => return Err(CFMError::Comparison(1, e)),
     };
 
     let state = CFMStateOBR4 {
         session_id: state.session_id,
         p: state.p,
+        output_policy: state.output_policy,
         r_share: state.r_share,
         alpha_share: state.alpha_share,
-        beta_share: state.beta_share,
+        beta_share,
         big_m_y_share: state.big_m_y_share,
         big_z_y_ob_share: state.big_z_y_ob_share,
         comp_0_state_ob_r1,
@@ -1872,6 +2883,7 @@ pub fn cfm_process_msg6(
 
     let msg7 = Box::new(CFMMsg7 {
         session_id: state.session_id,
+        open_ledger,
         comp_msg2_c: comp_0_msg2,
         comp_msg2_b: comp_1_msg2,
     });
@@ -1890,6 +2902,8 @@ pub fn cfm_process_msg7(
     if state.session_id != msg7.session_id {
         return Err(CFMError::InvalidSessionID);
     }
+    let params = DynResidueParams::new(&state.p);
+    check_open_ledger(&state.ledger_check_shares, &msg7.open_ledger, params)?;
 
     let (comp_0_state_cb_r2, comp_0_msg3) = match comp_process_msg2(
         &state.comp_0_state_cb_r0,
@@ -1899,7 +2913,7 @@ pub fn cfm_process_msg7(
         &msg7.comp_msg2_c,
     ) {
         Ok(v) => v,
-        Err(_) => return Err(CFMError::Comparison),
+        Err(e) => return Err(CFMError::Comparison(0, e)),
     };
 
     let (comp_1_state_cb_r2, comp_1_msg3) = match comp_process_msg2(
@@ -1910,12 +2924,13 @@ pub fn cfm_process_msg7(
         &msg7.comp_msg2_b,
     ) {
         Ok(v) => v,
-        Err(_) => return Err(CFMError::Comparison),
+        Err(e) => return Err(CFMError::Comparison(1, e)),
     };
 
     let state = CFMStateCBR4 {
         session_id: state.session_id,
         p: state.p,
+        output_policy: state.output_policy,
         r_share: state.r_share,
         alpha_share: state.alpha_share,
         beta_share: state.beta_share,
@@ -1952,7 +2967,7 @@ pub fn cfm_process_msg8(
         &msg8.comp_msg3_c,
     ) {
         Ok(v) => v,
-        Err(_) => return Err(CFMError::Comparison),
+        Err(e) => return Err(CFMError::Comparison(0, e)),
     };
 
     let (comp_1_state_ob_r3, comp_1_msg4) = match comp_process_msg3(
@@ -1961,12 +2976,13 @@ pub fn cfm_process_msg8(
         &msg8.comp_msg3_b,
     ) {
         Ok(v) => v,
-        Err(_) => return Err(CFMError::Comparison),
+        Err(e) => return Err(CFMError::Comparison(1, e)),
     };
 
     let state = CFMStateOBR5 {
         session_id: state.session_id,
         p: state.p,
+        output_policy: state.output_policy,
         r_share: state.r_share,
         alpha_share: state.alpha_share,
         beta_share: state.beta_share,
@@ -2003,7 +3019,7 @@ pub fn cfm_process_msg9(
         &msg9.comp_msg4_c,
     ) {
         Ok(v) => v,
-        Err(_) => return Err(CFMError::Comparison),
+        Err(e) => return Err(CFMError::Comparison(0, e)),
     };
 
     let (comp_1_state_cb_r4, comp_1_msg5) = match comp_process_msg4(
@@ -2012,12 +3028,13 @@ pub fn cfm_process_msg9(
         &msg9.comp_msg4_b,
     ) {
         Ok(v) => v,
-        Err(_) => return Err(CFMError::Comparison),
+        Err(e) => return Err(CFMError::Comparison(1, e)),
     };
 
     let state = CFMStateCBR5 {
         session_id: state.session_id,
         p: state.p,
+        output_policy: state.output_policy,
         r_share: state.r_share,
         alpha_share: state.alpha_share,
         beta_share: state.beta_share,
@@ -2054,7 +3071,7 @@ pub fn cfm_process_msg10(
         &msg10.comp_msg5_c,
     ) {
         Ok(v) => v,
-        Err(_) => return Err(CFMError::Comparison),
+        Err(e) => return Err(CFMError::Comparison(0, e)),
     };
 
     let (comp_1_state_ob_r5, comp_1_msg6) = match comp_process_msg5(
@@ -2063,12 +3080,13 @@ pub fn cfm_process_msg10(
         &msg10.comp_msg5_b,
     ) {
         Ok(v) => v,
-        Err(_) => return Err(CFMError::Comparison),
+        Err(e) => return Err(CFMError::Comparison(1, e)),
     };
 
     let state = CFMStateOBR6 {
         session_id: state.session_id,
         p: state.p,
+        output_policy: state.output_policy,
         r_share: state.r_share,
         alpha_share: state.alpha_share,
         beta_share: state.beta_share,
@@ -2105,7 +3123,7 @@ pub fn cfm_process_msg11(
         &msg11.comp_msg6_c,
     ) {
         Ok(v) => v,
-        Err(_) => return Err(CFMError::Comparison),
+        Err(e) => return Err(CFMError::Comparison(0, e)),
     };
 
     let (comp_1_state_cb_r6, comp_1_msg7) = match comp_process_msg6(
@@ -2114,12 +3132,13 @@ pub fn cfm_process_msg11(
         &msg11.comp_msg6_b,
     ) {
         Ok(v) => v,
-        Err(_) => return Err(CFMError::Comparison),
+        Err(e) => return Err(CFMError::Comparison(1, e)),
     };
 
     let state = CFMStateCBR6 {
         session_id: state.session_id,
         p: state.p,
+        output_policy: state.output_policy,
         r_share: state.r_share,
         alpha_share: state.alpha_share,
         beta_share: state.beta_share,
@@ -2156,7 +3175,7 @@ pub fn cfm_process_msg12(
         &msg12.comp_msg7_c,
     ) {
         Ok(v) => v,
-        Err(_) => return Err(CFMError::Comparison),
+        Err(e) => return Err(CFMError::Comparison(0, e)),
     };
 
     let (comp_1_state_ob_r7, comp_1_msg8) = match comp_process_msg7(
@@ -2165,7 +3184,7 @@ pub fn cfm_process_msg12(
         &msg12.comp_msg7_b,
     ) {
         Ok(v) => v,
-        Err(_) => return Err(CFMError::Comparison),
+        Err(e) => return Err(CFMError::Comparison(1, e)),
     };
 
     let params = DynResidueParams::new(&state.p);
@@ -2185,6 +3204,7 @@ pub fn cfm_process_msg12(
     let state = CFMStateOBR7 {
         session_id: state.session_id,
         p: state.p,
+        output_policy: state.output_policy,
         r_share: state.r_share,
         beta_share: state.beta_share,
         big_m_y_share: state.big_m_y_share,
@@ -2223,7 +3243,7 @@ pub fn cfm_process_msg13(
         &msg13.comp_msg8_c,
     ) {
         Ok(v) => v,
-        Err(_) => return Err(CFMError::Comparison),
+        Err(e) => return Err(CFMError::Comparison(0, e)),
     };
 
     let (b_share, comp_1_msg9) = match comp_process_msg8(
@@ -2232,7 +3252,7 @@ pub fn cfm_process_msg13(
         &msg13.comp_msg8_b,
     ) {
         Ok(v) => v,
-        Err(_) => return Err(CFMError::Comparison),
+        Err(e) => return Err(CFMError::Comparison(1, e)),
     };
 
     let params = DynResidueParams::new(&state.p);
@@ -2248,7 +3268,7 @@ pub fn cfm_process_msg13(
 
     let a_share = match multiply_shares_output(&mul_state_a, &msg13.mul_open_a, true, params) {
         Ok(v) => v,
-        Err(_) => return Err(CFMError::InvalidOpen),
+        Err(e) => return Err(CFMError::InvalidOpen(FailedCheck::abt_sacrifice("mul_open_a", 0), e)),
     };
 
     let z_share = a_share
@@ -2273,6 +3293,7 @@ pub fn cfm_process_msg13(
     let state = CFMStateCBR7 {
         session_id: state.session_id,
         p: state.p,
+        output_policy: state.output_policy,
         r_share: state.r_share,
         c_share,
         b_share,
@@ -2302,19 +3323,19 @@ pub fn cfm_process_msg14(
 
     let c_share = match comp_process_msg9(&state.comp_0_state_ob_r7, &msg14.comp_msg9_c) {
         Ok(v) => v,
-        Err(_) => return Err(CFMError::Comparison),
+        Err(e) => return Err(CFMError::Comparison(0, e)),
     };
 
     let b_share = match comp_process_msg9(&state.comp_1_state_ob_r7, &msg14.comp_msg9_b) {
         Ok(v) => v,
-        Err(_) => return Err(CFMError::Comparison),
+        Err(e) => return Err(CFMError::Comparison(1, e)),
     };
 
     let params = DynResidueParams::new(&state.p);
     let a_share = match multiply_shares_output(&state.mul_state_a, &msg14.mul_open_a, false, params)
     {
         Ok(v) => v,
-        Err(_) => return Err(CFMError::InvalidOpen),
+        Err(e) => return Err(CFMError::InvalidOpen(FailedCheck::abt_sacrifice("mul_open_a", 0), e)),
     };
 
     let z_share = a_share
@@ -2333,7 +3354,10 @@ pub fn cfm_process_msg14(
     );
     let f1_share = match multiply_shares_output(&mul_state_f1, &msg14.mul_open_f1, false, params) {
         Ok(v) => v,
-        Err(_) => return Err(CFMError::InvalidOpen),
+        Err(e) => {
+            let check = FailedCheck::abt_sacrifice("mul_open_f1", 0);
+            return Err(CFMError::InvalidOpen(check, e));
+        }
     };
 
     // [f2] = (1 - [f1])
@@ -2353,6 +3377,7 @@ pub fn cfm_process_msg14(
     let state = CFMStateOBR8 {
         session_id: state.session_id,
         p: state.p,
+        output_policy: state.output_policy,
         b_share,
         mul_state_f,
     };
@@ -2381,7 +3406,10 @@ pub fn cfm_process_msg15(
     let f1_share =
         match multiply_shares_output(&state.mul_state_f1, &msg15.mul_open_f1, true, params) {
             Ok(v) => v,
-            Err(_) => return Err(CFMError::InvalidOpen),
+            Err(e) => {
+                let check = FailedCheck::abt_sacrifice("mul_open_f1", 0);
+                return Err(CFMError::InvalidOpen(check, e));
+            }
         };
 
     // [f2] = (1 - [f1])
@@ -2401,7 +3429,7 @@ pub fn cfm_process_msg15(
 
     let f_share = match multiply_shares_output(&mul_state_f, &msg15.mul_open_f, true, params) {
         Ok(v) => v,
-        Err(_) => return Err(CFMError::InvalidOpen),
+        Err(e) => return Err(CFMError::InvalidOpen(FailedCheck::abt_sacrifice("mul_open_f", 0), e)),
     };
 
     let open_f = f_share.open();
@@ -2409,6 +3437,7 @@ pub fn cfm_process_msg15(
     let state = CFMStateCBR8 {
         session_id: state.session_id,
         p: state.p,
+        output_policy: state.output_policy,
         b_share: state.b_share,
         f_share,
     };
@@ -2436,24 +3465,26 @@ pub fn cfm_process_msg16(
     let f_share = match multiply_shares_output(&state.mul_state_f, &msg16.mul_open_f, false, params)
     {
         Ok(v) => v,
-        Err(_) => return Err(CFMError::InvalidOpen),
+        Err(e) => return Err(CFMError::InvalidOpen(FailedCheck::abt_sacrifice("mul_open_f", 0), e)),
     };
 
     let open_f = f_share.open();
     let f_value = match f_share.validate_open(&msg16.open_f.0, &msg16.open_f.1, params) {
         Ok(v) => v,
-        Err(_) => return Err(CFMError::InvalidOpen),
+        Err(e) => return Err(CFMError::InvalidOpen(FailedCheck::cfm("open_f", 0), e)),
     };
 
     if f_value != U128::ZERO {
-        return Err(CFMError::AbortProtocol);
+        let check = FailedCheck::cfm("open_f", 0);
+        return Err(wrong_value(check, &f_share, &msg16.open_f, U128::ZERO, params));
     }
 
-    let open_b = state.b_share.open();
+    let open_b = state.output_policy.cb_learns().then(|| state.b_share.open());
 
     let state = CFMStateOBR9 {
         session_id: state.session_id,
         p: state.p,
+        output_policy: state.output_policy,
         b_share: state.b_share,
     };
 
@@ -2470,7 +3501,7 @@ pub fn cfm_process_msg16(
 pub fn cfm_process_msg17(
     state: CFMStateCBR8,
     msg17: &CFMMsg17,
-) -> Result<(bool, CFMMsg18), CFMError> {
+) -> Result<(CFMOutput, CFMMsg18), CFMError> {
     if state.session_id != msg17.session_id {
         return Err(CFMError::InvalidSessionID);
     }
@@ -2482,49 +3513,599 @@ pub fn cfm_process_msg17(
         .validate_open(&msg17.open_f.0, &msg17.open_f.1, params)
     {
         Ok(v) => v,
-        Err(_) => return Err(CFMError::InvalidOpen),
+        Err(e) => return Err(CFMError::InvalidOpen(FailedCheck::cfm("open_f", 0), e)),
     };
 
     if f_value != U128::ZERO {
-        return Err(CFMError::AbortProtocol);
+        let check = FailedCheck::cfm("open_f", 0);
+        return Err(wrong_value(check, &state.f_share, &msg17.open_f, U128::ZERO, params));
     }
 
-    let open_b = state.b_share.open();
-    let b_value = match state
-        .b_share
-        .validate_open(&msg17.open_b.0, &msg17.open_b.1, params)
-    {
-        Ok(v) => v,
-        Err(_) => return Err(CFMError::InvalidOpen),
-    };
-
-    let b = b_value == U128::ONE;
+    let output_policy = state.output_policy;
+    let output = output_b(
+        &state.b_share,
+        &msg17.open_b,
+        output_policy,
+        output_policy.cb_learns(),
+        params,
+    )?;
 
     let msg18 = CFMMsg18 {
         session_id: state.session_id,
-        open_b,
+        open_b: state.output_policy.ob_learns().then(|| state.b_share.open()),
     };
 
-    Ok((b, msg18))
+    Ok((output, msg18))
 }
 
 /// OB processes CFMMsg18 from CB
-pub fn cfm_process_msg18(state: CFMStateOBR9, msg18: &CFMMsg18) -> Result<bool, CFMError> {
+pub fn cfm_process_msg18(state: CFMStateOBR9, msg18: &CFMMsg18) -> Result<CFMOutput, CFMError> {
     if state.session_id != msg18.session_id {
         return Err(CFMError::InvalidSessionID);
     }
 
     let params = DynResidueParams::new(&state.p);
 
-    let b_value = match state
-        .b_share
-        .validate_open(&msg18.open_b.0, &msg18.open_b.1, params)
-    {
-        Ok(v) => v,
-        Err(_) => return Err(CFMError::InvalidOpen),
+    let output_policy = state.output_policy;
+    output_b(
+        &state.b_share,
+        &msg18.open_b,
+        output_policy,
+        output_policy.ob_learns(),
+        params,
+    )
+}
+
+/// Checks that [T] opens to zero. The opening is present exactly if L is
+/// public, then both parties hold [T].
+fn check_open_t(
+    big_t: &Option<Share>,
+    open_t: &Option<(U128, U128)>,
+    params: DynResidueParams<2>,
+) -> Result<(), CFMError> {
+    match (big_t, open_t) {
+        (Some(big_t), Some((value, mac))) => match big_t.validate_open(value, mac, params) {
+            Ok(v) if v == U128::ZERO => Ok(()),
+            Ok(_) => {
+                let check = FailedCheck::cfm("open_t", 0);
+                Err(wrong_value(check, big_t, &(*value, *mac), U128::ZERO, params))
+            }
+            Err(e) => Err(CFMError::InvalidOpen(FailedCheck::cfm("open_t", 0), e)),
+        },
+        (None, None) => Ok(()),
+        _ => Err(CFMError::LimitModeMismatch),
+    }
+}
+
+/// Output of a party from its share of b and the opening of the other party,
+/// which is present exactly if the party learns b under `output_policy`
+fn output_b(
+    b_share: &Share,
+    open_b: &Option<(U128, U128)>,
+    output_policy: OutputPolicy,
+    learns: bool,
+    params: DynResidueParams<2>,
+) -> Result<CFMOutput, CFMError> {
+    match (open_b, learns) {
+        (Some((value, mac)), true) => match b_share.validate_open(value, mac, params) {
+            Ok(b_value) => Ok(CFMOutput::Result(b_value == U128::ONE)),
+            Err(e) => Err(CFMError::InvalidOpen(FailedCheck::cfm("open_b", 0), e)),
+        },
+        (None, false) if output_policy == OutputPolicy::Shared => Ok(CFMOutput::Shared(*b_share)),
+        (None, false) => Ok(CFMOutput::Hidden),
+        _ => Err(CFMError::OutputPolicyMismatch),
+    }
+}
+
+/// Error for an opening of `share` that holds its MAC but does not add up to
+/// `expected`
+fn wrong_value(
+    check: FailedCheck,
+    share: &Share,
+    open: &(U128, U128),
+    expected: U128,
+    params: DynResidueParams<2>,
+) -> CFMError {
+    let opening = Opening {
+        p: *params.modulus(),
+        value: open.0,
+        mac: open.1,
+        failure: OpeningFailure::Value {
+            share: share.value,
+            expected,
+        },
     };
+    CFMError::AbortProtocol(check, Box::new(opening))
+}
 
-    Ok(b_value == U128::ONE)
+/// CFM protocol message, tagged with its round number
+#[derive(Clone, Serialize, Deserialize)]
+pub enum CFMMessage {
+    /// CFMMsg1 from OB
+    Msg1(Box<CFMMsg1>),
+    /// CFMMsg2 from CB
+    Msg2(Box<CFMMsg2>),
+    /// CFMMsg3 from OB
+    Msg3(Box<CFMMsg3>),
+    /// CFMMsg4 from CB
+    Msg4(Box<CFMMsg4>),
+    /// CFMMsg5 from OB
+    Msg5(Box<CFMMsg5>),
+    /// CFMMsg6 from CB
+    Msg6(Box<CFMMsg6>),
+    /// CFMMsg7 from OB
+    Msg7(Box<CFMMsg7>),
+    /// CFMMsg8 from CB
+    Msg8(Box<CFMMsg8>),
+    /// CFMMsg9 from OB
+    Msg9(Box<CFMMsg9>),
+    /// CFMMsg10 from CB
+    Msg10(Box<CFMMsg10>),
+    /// CFMMsg11 from OB
+    Msg11(Box<CFMMsg11>),
+    /// CFMMsg12 from CB
+    Msg12(Box<CFMMsg12>),
+    /// CFMMsg13 from OB
+    Msg13(Box<CFMMsg13>),
+    /// CFMMsg14 from CB
+    Msg14(Box<CFMMsg14>),
+    /// CFMMsg15 from OB
+    Msg15(CFMMsg15),
+    /// CFMMsg16 from CB
+    Msg16(CFMMsg16),
+    /// CFMMsg17 from OB
+    Msg17(CFMMsg17),
+    /// CFMMsg18 from CB
+    Msg18(CFMMsg18),
+}
+
+impl CFMMessage {
+    /// Round number of the message, from 1 to 18
+    pub fn round(&self) -> u8 {
+        match self {
+            CFMMessage::Msg1(_) => 1,
+            CFMMessage::Msg2(_) => 2,
+            CFMMessage::Msg3(_) => 3,
+            CFMMessage::Msg4(_) => 4,
+            CFMMessage::Msg5(_) => 5,
+            CFMMessage::Msg6(_) => 6,
+            CFMMessage::Msg7(_) => 7,
+            CFMMessage::Msg8(_) => 8,
+            CFMMessage::Msg9(_) => 9,
+            CFMMessage::Msg10(_) => 10,
+            CFMMessage::Msg11(_) => 11,
+            CFMMessage::Msg12(_) => 12,
+            CFMMessage::Msg13(_) => 13,
+            CFMMessage::Msg14(_) => 14,
+            CFMMessage::Msg15(_) => 15,
+            CFMMessage::Msg16(_) => 16,
+            CFMMessage::Msg17(_) => 17,
+            CFMMessage::Msg18(_) => 18,
+        }
+    }
+}
+
+/// Result of handling one incoming CFM message
+pub enum CFMStep {
+    /// Message to send to the other party
+    Outgoing(CFMMessage),
+
+    /// Protocol finished with the output of this party.
+    /// CB still has to send CFMMsg18 to OB.
+    Finished {
+        /// result bit, or share of b, depending on the output policy
+        output: CFMOutput,
+        /// last message to send, if any
+        outgoing: Option<CFMMessage>,
+    },
+}
+
+/// Internal round state of OB
+#[derive(Clone, Serialize, Deserialize)]
+enum OBRound {
+    R1(Box<CFMStateOBR1>),
+    R2(Box<CFMStateOBR2>),
+    R3(Box<CFMStateOBR3>),
+    R4(Box<CFMStateOBR4>),
+    R5(Box<CFMStateOBR5>),
+    R6(Box<CFMStateOBR6>),
+    R7(Box<CFMStateOBR7>),
+    R8(Box<CFMStateOBR8>),
+    R9(Box<CFMStateOBR9>),
+    Finished(CFMOutput),
+    Aborted,
+}
+
+impl OBRound {
+    /// Round number of the next expected message
+    fn expected(&self) -> Option<u8> {
+        match self {
+            OBRound::R1(_) => Some(2),
+            OBRound::R2(_) => Some(4),
+            OBRound::R3(_) => Some(6),
+            OBRound::R4(_) => Some(8),
+            OBRound::R5(_) => Some(10),
+            OBRound::R6(_) => Some(12),
+            OBRound::R7(_) => Some(14),
+            OBRound::R8(_) => Some(16),
+            OBRound::R9(_) => Some(18),
+            OBRound::Finished(_) | OBRound::Aborted => None,
+        }
+    }
+}
+
+/// CFM protocol driver for OB
+///
+/// Holds the current round state together with the shares and
+/// authenticated beaver triples of the session.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct OBSession {
+    x_shares: Vec<Share>,
+    auth_triples: Vec<TripleShare>,
+    round: OBRound,
+    ledger_update: Option<TagUpdate>,
+}
+
+impl OBSession {
+    /// Start the CFM protocol for OB, returns the session and CFMMsg1 for CB
+    #[allow(clippy::too_many_arguments)]
+    pub fn new<R: CryptoRng + RngCore>(
+        session_id: &[u8; 32],
+        p: U128,
+        output_policy: OutputPolicy,
+        big_l: Option<U64>,
+        big_x: U64,
+        y: &[u8; 32],
+        ledger: &Ledger,
+        ot_seeds: &CFMInitOTSeedsOB,
+        x_shares: Vec<Share>,
+        auth_triples: Vec<TripleShare>,
+        rng: &mut R,
+    ) -> Result<(Self, CFMMessage), CFMError> {
+        if auth_triples.len() != NUMBER_OF_AUTH_BEAVER_TRIPLES {
+            return Err(CFMError::InvalidInput);
+        }
+        let (state, msg1) = cfm_create_msg1(
+            session_id,
+            p,
+            output_policy,
+            big_l,
+            big_x,
+            y,
+            ledger,
+            ot_seeds,
+            &x_shares,
+            rng,
+        )?;
+        let session = OBSession {
+            x_shares,
+            auth_triples,
+            round: OBRound::R1(Box::new(state)),
+            ledger_update: None,
+        };
+        Ok((session, CFMMessage::Msg1(msg1)))
+    }
+
+    /// Round number of the next expected message, None if the session is closed
+    pub fn expected_round(&self) -> Option<u8> {
+        self.round.expected()
+    }
+
+    /// Result bit, if the protocol has finished and OB learns it
+    pub fn result(&self) -> Option<bool> {
+        self.output().and_then(CFMOutput::result)
+    }
+
+    /// Output of OB, if the protocol has finished
+    pub fn output(&self) -> Option<&CFMOutput> {
+        match &self.round {
+            OBRound::Finished(output) => Some(output),
+            _ => None,
+        }
+    }
+
+    /// Tag updates of the ledger, once CFMMsg2 is processed. They hold for
+    /// the ledger the session started from and apply only once b is known.
+    pub(crate) fn ledger_update(&self) -> Option<&TagUpdate> {
+        self.ledger_update.as_ref()
+    }
+
+    /// Process a message from CB.
+    /// A message for the wrong round is rejected and leaves the session unchanged,
+    /// any other error aborts the session.
+    pub fn handle<R: CryptoRng + RngCore>(
+        &mut self,
+        incoming: &CFMMessage,
+        rng: &mut R,
+    ) -> Result<CFMStep, CFMError> {
+        let expected = self.round.expected().ok_or(CFMError::SessionClosed)?;
+        if incoming.round() != expected {
+            return Err(CFMError::UnexpectedMessage {
+                expected,
+                received: incoming.round(),
+            });
+        }
+
+        let round = std::mem::replace(&mut self.round, OBRound::Aborted);
+        let triples = &self.auth_triples;
+        let (next, step) = match (round, incoming) {
+            (OBRound::R1(state), CFMMessage::Msg2(msg)) => {
+                let (state, msg) = cfm_process_msg2(&state, &self.x_shares, triples, msg, rng)?;
+                self.ledger_update = Some(state.ledger_update.clone());
+                (OBRound::R2(state), CFMStep::Outgoing(CFMMessage::Msg3(msg)))
+            }
+            (OBRound::R2(state), CFMMessage::Msg4(msg)) => {
+                let (state, msg) = cfm_process_msg4(state, msg)?;
+                (OBRound::R3(state), CFMStep::Outgoing(CFMMessage::Msg5(msg)))
+            }
+            (OBRound::R3(state), CFMMessage::Msg6(msg)) => {
+                let (state, msg) = cfm_process_msg6(state, triples, msg)?;
+                (OBRound::R4(Box::new(state)), CFMStep::Outgoing(CFMMessage::Msg7(msg)))
+            }
+            (OBRound::R4(state), CFMMessage::Msg8(msg)) => {
+                let (state, msg) = cfm_process_msg8(*state, triples, msg)?;
+                (OBRound::R5(Box::new(state)), CFMStep::Outgoing(CFMMessage::Msg9(msg)))
+            }
+            (OBRound::R5(state), CFMMessage::Msg10(msg)) => {
+                let (state, msg) = cfm_process_msg10(*state, triples, msg)?;
+                (OBRound::R6(Box::new(state)), CFMStep::Outgoing(CFMMessage::Msg11(msg)))
+            }
+            (OBRound::R6(state), CFMMessage::Msg12(msg)) => {
+                let (state, msg) = cfm_process_msg12(*state, triples, msg)?;
+                (OBRound::R7(Box::new(state)), CFMStep::Outgoing(CFMMessage::Msg13(msg)))
+            }
+            (OBRound::R7(state), CFMMessage::Msg14(msg)) => {
+                let (state, msg) = cfm_process_msg14(*state, msg)?;
+                (OBRound::R8(Box::new(state)), CFMStep::Outgoing(CFMMessage::Msg15(msg)))
+            }
+            (OBRound::R8(state), CFMMessage::Msg16(msg)) => {
+                let (state, msg) = cfm_process_msg16(*state, msg)?;
+                (OBRound::R9(Box::new(state)), CFMStep::Outgoing(CFMMessage::Msg17(msg)))
+            }
+            (OBRound::R9(state), CFMMessage::Msg18(msg)) => {
+                let output = cfm_process_msg18(*state, msg)?;
+                (
+                    OBRound::Finished(output),
+                    CFMStep::Finished {
+                        output,
+                        outgoing: None,
+                    },
+                )
+            }
+            _ => unreachable!("round checked above"),
+        };
+        self.round = next;
+
+        Ok(step)
+    }
+}
+
+/// Private inputs of CB, kept until CFMMsg1 arrives
+#[derive(Clone, Serialize, Deserialize)]
+struct CBInputs {
+    session_id: [u8; 32],
+    p: U128,
+    output_policy: OutputPolicy,
+    big_l: U64,
+    private_limit: bool,
+    big_y: Vec<[u8; 32]>,
+    big_z: Vec<U64>,
+    big_l_y: Option<Vec<U64>>,
+    ledger: LedgerKeys,
+    ot_seeds: CFMInitOTSeedsCB,
+}
+
+/// Internal round state of CB
+#[derive(Clone, Serialize, Deserialize)]
+enum CBRound {
+    R0(Box<CBInputs>),
+    R1(Box<CFMStateCBR1>),
+    R2(Box<CFMStateCBR2>),
+    R3(Box<CFMStateCBR3>),
+    R4(Box<CFMStateCBR4>),
+    R5(Box<CFMStateCBR5>),
+    R6(Box<CFMStateCBR6>),
+    R7(Box<CFMStateCBR7>),
+    R8(Box<CFMStateCBR8>),
+    Finished(CFMOutput),
+    Aborted,
+}
+
+impl CBRound {
+    /// Round number of the next expected message
+    fn expected(&self) -> Option<u8> {
+        match self {
+            CBRound::R0(_) => Some(1),
+            CBRound::R1(_) => Some(3),
+            CBRound::R2(_) => Some(5),
+            CBRound::R3(_) => Some(7),
+            CBRound::R4(_) => Some(9),
+            CBRound::R5(_) => Some(11),
+            CBRound::R6(_) => Some(13),
+            CBRound::R7(_) => Some(15),
+            CBRound::R8(_) => Some(17),
+            CBRound::Finished(_) | CBRound::Aborted => None,
+        }
+    }
+}
+
+/// CFM protocol driver for CB
+///
+/// Holds the current round state together with the shares and
+/// authenticated beaver triples of the session.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CBSession {
+    x_shares: Vec<Share>,
+    auth_triples: Vec<TripleShare>,
+    round: CBRound,
+    ledger_update: Option<KeyUpdate>,
+}
+
+impl CBSession {
+    /// Create the CB session, waiting for CFMMsg1 from OB. Every customer of
+    /// `big_y` needs a slot in `ledger`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        session_id: &[u8; 32],
+        p: U128,
+        output_policy: OutputPolicy,
+        big_l: U64,
+        private_limit: bool,
+        big_y: Vec<[u8; 32]>,
+        big_z: Vec<U64>,
+        big_l_y: Option<Vec<U64>>,
+        ledger: &LedgerKeys,
+        ot_seeds: &CFMInitOTSeedsCB,
+        x_shares: Vec<Share>,
+        auth_triples: Vec<TripleShare>,
+    ) -> Result<Self, CFMError> {
+        if x_shares.len() != NUMBER_OF_SHARES
+            || auth_triples.len() != NUMBER_OF_AUTH_BEAVER_TRIPLES
+            || big_y.is_empty()
+            || big_y.len() != big_z.len()
+            || !valid_limits(big_l, private_limit, &big_y, big_l_y.as_deref())
+            || big_y.iter().any(|y| ledger.slot(y).is_none())
+        {
+            return Err(CFMError::InvalidInput);
+        }
+        let inputs = CBInputs {
+            session_id: *session_id,
+            p,
+            output_policy,
+            big_l,
+            private_limit,
+            big_y,
+            big_z,
+            big_l_y,
+            ledger: ledger.clone(),
+            ot_seeds: ot_seeds.clone(),
+        };
+        Ok(CBSession {
+            x_shares,
+            auth_triples,
+            round: CBRound::R0(Box::new(inputs)),
+            ledger_update: None,
+        })
+    }
+
+    /// Round number of the next expected message, None if the session is closed
+    pub fn expected_round(&self) -> Option<u8> {
+        self.round.expected()
+    }
+
+    /// Result bit, if the protocol has finished and CB learns it
+    pub fn result(&self) -> Option<bool> {
+        self.output().and_then(CFMOutput::result)
+    }
+
+    /// Output of CB, if the protocol has finished
+    pub fn output(&self) -> Option<&CFMOutput> {
+        match &self.round {
+            CBRound::Finished(output) => Some(output),
+            _ => None,
+        }
+    }
+
+    /// Key updates of the ledger, once CFMMsg3 is processed. They hold for
+    /// the ledger the session started from and apply only once b is known.
+    pub(crate) fn ledger_update(&self) -> Option<&KeyUpdate> {
+        self.ledger_update.as_ref()
+    }
+
+    /// Process a message from OB.
+    /// A message for the wrong round is rejected and leaves the session unchanged,
+    /// any other error aborts the session.
+    pub fn handle<R: CryptoRng + RngCore>(
+        &mut self,
+        incoming: &CFMMessage,
+        rng: &mut R,
+    ) -> Result<CFMStep, CFMError> {
+        let expected = self.round.expected().ok_or(CFMError::SessionClosed)?;
+        if incoming.round() != expected {
+            return Err(CFMError::UnexpectedMessage {
+                expected,
+                received: incoming.round(),
+            });
+        }
+
+        let round = std::mem::replace(&mut self.round, CBRound::Aborted);
+        let triples = &self.auth_triples;
+        let (next, step) = match (round, incoming) {
+            (CBRound::R0(inputs), CFMMessage::Msg1(msg)) => {
+                let CBInputs {
+                    session_id,
+                    p,
+                    output_policy,
+                    big_l,
+                    private_limit,
+                    big_y,
+                    big_z,
+                    big_l_y,
+                    ledger,
+                    ot_seeds,
+                } = *inputs;
+                let (state, msg) = cfm_process_msg1(
+                    &session_id,
+                    p,
+                    output_policy,
+                    big_l,
+                    private_limit,
+                    big_y,
+                    big_z,
+                    big_l_y,
+                    &ledger,
+                    &ot_seeds,
+                    &self.x_shares,
+                    msg,
+                    rng,
+                )?;
+                (CBRound::R1(state), CFMStep::Outgoing(CFMMessage::Msg2(msg)))
+            }
+            (CBRound::R1(state), CFMMessage::Msg3(msg)) => {
+                let (state, msg) = cfm_process_msg3(state, &self.x_shares, triples, msg)?;
+                self.ledger_update = Some(state.ledger_update.clone());
+                (CBRound::R2(state), CFMStep::Outgoing(CFMMessage::Msg4(msg)))
+            }
+            (CBRound::R2(state), CFMMessage::Msg5(msg)) => {
+                let (state, msg) = cfm_process_msg5(state, triples, msg)?;
+                (CBRound::R3(Box::new(state)), CFMStep::Outgoing(CFMMessage::Msg6(msg)))
+            }
+            (CBRound::R3(state), CFMMessage::Msg7(msg)) => {
+                let (state, msg) = cfm_process_msg7(*state, triples, msg)?;
+                (CBRound::R4(Box::new(state)), CFMStep::Outgoing(CFMMessage::Msg8(msg)))
+            }
+            (CBRound::R4(state), CFMMessage::Msg9(msg)) => {
+                let (state, msg) = cfm_process_msg9(*state, triples, msg)?;
+                (CBRound::R5(Box::new(state)), CFMStep::Outgoing(CFMMessage::Msg10(msg)))
+            }
+            (CBRound::R5(state), CFMMessage::Msg11(msg)) => {
+                let (state, msg) = cfm_process_msg11(*state, triples, msg)?;
+                (CBRound::R6(Box::new(state)), CFMStep::Outgoing(CFMMessage::Msg12(msg)))
+            }
+            (CBRound::R6(state), CFMMessage::Msg13(msg)) => {
+                let (state, msg) = cfm_process_msg13(*state, triples, msg)?;
+                (CBRound::R7(Box::new(state)), CFMStep::Outgoing(CFMMessage::Msg14(msg)))
+            }
+            (CBRound::R7(state), CFMMessage::Msg15(msg)) => {
+                let (state, msg) = cfm_process_msg15(*state, msg)?;
+                (CBRound::R8(Box::new(state)), CFMStep::Outgoing(CFMMessage::Msg16(msg)))
+            }
+            (CBRound::R8(state), CFMMessage::Msg17(msg)) => {
+                let (output, msg) = cfm_process_msg17(*state, msg)?;
+                (
+                    CBRound::Finished(output),
+                    CFMStep::Finished {
+                        output,
+                        outgoing: Some(CFMMessage::Msg18(msg)),
+                    },
+                )
+            }
+            _ => unreachable!("round checked above"),
+        };
+        self.round = next;
+
+        Ok(step)
+    }
 }
 
 #[cfg(test)]
@@ -2533,20 +4114,23 @@ mod tests {
         abt_create_msg1, abt_process_msg1, abt_process_msg2, abt_process_msg3, abt_process_msg4,
         abt_process_msg5,
     };
+    use crate::auth_beaver_triples::{Share, TripleShare};
     use crate::cfm_init_protocol::generate_cfm_ot_seeds_for_test;
     use crate::cfm_protocol::{
         cfm_create_msg1, cfm_process_msg1, cfm_process_msg10, cfm_process_msg11, cfm_process_msg12,
         cfm_process_msg13, cfm_process_msg14, cfm_process_msg15, cfm_process_msg16,
         cfm_process_msg17, cfm_process_msg18, cfm_process_msg2, cfm_process_msg3, cfm_process_msg4,
         cfm_process_msg5, cfm_process_msg6, cfm_process_msg7, cfm_process_msg8, cfm_process_msg9,
+        CBSession, CFMMessage, CFMOutput, CFMStep, OBSession, OutputPolicy,
         NUMBER_OF_AUTH_BEAVER_TRIPLES, NUMBER_OF_SHARES,
     };
+    use crate::errors::CFMError;
+    use crate::ledger::{Ledger, LedgerKeys};
     use crate::utils::Customer;
     use crate::P;
-    use crypto_bigint::U64;
+    use crypto_bigint::{U128, U64};
     use rand::Rng;
     use std::vec;
-    use std::sync::{Arc, Mutex};
     use rand::rngs::OsRng;
 
 
@@ -2565,7 +4149,7 @@ mod tests {
         let eta_i = NUMBER_OF_SHARES;
         let eta_m = NUMBER_OF_AUTH_BEAVER_TRIPLES;
         let (state_cb_r1, msg1) =
-            abt_create_msg1(&session_id, &ot_seeds_cb, p, eta_i, eta_m, &mut rng);
+            abt_create_msg1(&session_id, &ot_seeds_cb, p, eta_i, eta_m, &mut rng).unwrap();
         let (state_ob_r1, mut shares_ob, mut auth_triples_ob, msg2) =
             abt_process_msg1(&session_id, &ot_seeds_ob, p, eta_i, eta_m, &msg1, &mut rng).unwrap();
         let (state_cb_r2, shares_cb, auth_triples_cb, msg3) =
@@ -2621,20 +4205,31 @@ mod tests {
         let (cfm_state_ob_r1, msg1) = cfm_create_msg1(
             &session_id,
             p,
-            big_l,
+            OutputPolicy::Both,
+            Some(big_l),
             big_x,
             &customer_y_bytes,
+            &Ledger::new(),
+            &ot_seeds_ob,
             &shares_ob,
             &mut rng,
-        );
+        )
+        .unwrap();
 
         // CB processes msg1
+        let mut ledger_keys = LedgerKeys::new(&p, &mut rng);
+        ledger_keys.assign_slots(&big_y_bytes);
         let (cfm_state_cb_r1, msg2) = cfm_process_msg1(
             &session_id,
             p,
+            OutputPolicy::Both,
             big_l,
+            false,
             big_y_bytes,
             big_z,
+            None,
+            &ledger_keys,
+            &ot_seeds_cb,
             &shares_cb,
             &msg1,
             &mut rng,
@@ -2708,6 +4303,7 @@ mod tests {
 
         // OB processes msg18
         let b_ob_value = cfm_process_msg18(cfm_state_ob_r9, &msg18).unwrap();
+        let (b_cb_value, b_ob_value) = (b_cb_value.result(), b_ob_value.result());
 
         println!("{:#?} --- {:#?}", b_cb_value, b_ob_value);
 
@@ -2715,6 +4311,347 @@ mod tests {
 
         // X = 100, Z_Y = 3, L = 104
         // (X + Z_Y) < L
-        assert_eq!(b_cb_value, true);
+        assert_eq!(b_cb_value, Some(true));
+    }
+    #[test]
+    fn test_cfm_session() {
+        assert!(matches!(
+            run_cfm_session(P, OutputPolicy::Both, 104, None, false),
+            (CFMOutput::Result(true), CFMOutput::Result(true))
+        ));
+    }
+
+    #[test]
+    fn test_cfm_session_invalid_input() {
+        let mut rng = OsRng;
+        let session_id: [u8; 32] = rng.gen();
+        let y = [3u8; 32];
+        let (ot_seeds_cb, ot_seeds_ob) = generate_cfm_ot_seeds_for_test(&session_id, &mut rng);
+        let mut ledger_keys = LedgerKeys::new(&P, &mut rng);
+        ledger_keys.assign_slots(&[y]);
+
+        // shares and triples of a different count are rejected, not asserted
+        let ob = OBSession::new(
+            &session_id,
+            P,
+            OutputPolicy::Both,
+            Some(U64::from_u32(104)),
+            U64::from_u32(100),
+            &y,
+            &Ledger::new(),
+            &ot_seeds_ob,
+            vec![],
+            vec![],
+            &mut rng,
+        );
+        assert!(matches!(ob, Err(CFMError::InvalidInput)));
+        let cb = CBSession::new(
+            &session_id,
+            P,
+            OutputPolicy::Both,
+            U64::from_u32(104),
+            false,
+            vec![y],
+            vec![U64::ONE],
+            None,
+            &ledger_keys,
+            &ot_seeds_cb,
+            vec![],
+            vec![],
+        );
+        assert!(matches!(cb, Err(CFMError::InvalidInput)));
+
+        // with a public L every customer has limit L
+        let share = Share::default();
+        let triple = TripleShare {
+            x: share,
+            y: share,
+            z: share,
+        };
+        let cb = CBSession::new(
+            &session_id,
+            P,
+            OutputPolicy::Both,
+            U64::from_u32(104),
+            false,
+            vec![y],
+            vec![U64::ONE],
+            Some(vec![U64::from_u32(50)]),
+            &ledger_keys,
+            &ot_seeds_cb,
+            vec![share; NUMBER_OF_SHARES],
+            vec![triple; NUMBER_OF_AUTH_BEAVER_TRIPLES],
+        );
+        assert!(matches!(cb, Err(CFMError::InvalidInput)));
+
+        // as is a customer without a ledger slot
+        let cb = CBSession::new(
+            &session_id,
+            P,
+            OutputPolicy::Both,
+            U64::from_u32(104),
+            false,
+            vec![[4u8; 32]],
+            vec![U64::ONE],
+            None,
+            &ledger_keys,
+            &ot_seeds_cb,
+            vec![share; NUMBER_OF_SHARES],
+            vec![triple; NUMBER_OF_AUTH_BEAVER_TRIPLES],
+        );
+        assert!(matches!(cb, Err(CFMError::InvalidInput)));
+
+        // so is a modulus that is not a large enough prime
+        let invalid = cfm_create_msg1(
+            &session_id,
+            U128::MAX,
+            OutputPolicy::Both,
+            Some(U64::from_u32(104)),
+            U64::from_u32(100),
+            &y,
+            &Ledger::new(),
+            &ot_seeds_ob,
+            &[],
+            &mut rng,
+        );
+        assert!(matches!(invalid, Err(CFMError::InvalidModulus)));
+    }
+
+    #[test]
+    fn test_cfm_second_prime() {
+        // 2^128 - 159
+        let p = U128::from_be_hex("ffffffffffffffffffffffffffffff61");
+        assert!(matches!(
+            run_cfm_session(p, OutputPolicy::Both, 104, None, false),
+            (CFMOutput::Result(true), CFMOutput::Result(true))
+        ));
+    }
+
+    #[test]
+    fn test_cfm_output_policy() {
+        assert!(matches!(
+            run_cfm_session(P, OutputPolicy::OBOnly, 104, None, false),
+            (CFMOutput::Result(true), CFMOutput::Hidden)
+        ));
+        assert!(matches!(
+            run_cfm_session(P, OutputPolicy::CBOnly, 104, None, false),
+            (CFMOutput::Hidden, CFMOutput::Result(true))
+        ));
+
+        let (ob, cb) = run_cfm_session(P, OutputPolicy::Shared, 104, None, false);
+        match (ob, cb) {
+            (CFMOutput::Shared(ob), CFMOutput::Shared(cb)) => {
+                // the value shares of b add up to 1
+                let b = ob.value.add_mod(&cb.value, &P);
+                assert_eq!(b, U128::ONE);
+            }
+            other => panic!("expected shared outputs, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_cfm_private_limit() {
+        // Z_y + X = 103, OB does not know L
+        assert!(matches!(
+            run_cfm_session(P, OutputPolicy::Both, 104, None, true),
+            (CFMOutput::Result(true), CFMOutput::Result(true))
+        ));
+        assert!(matches!(
+            run_cfm_session(P, OutputPolicy::Both, 102, None, true),
+            (CFMOutput::Result(false), CFMOutput::Result(false))
+        ));
+    }
+
+    #[test]
+    fn test_cfm_customer_limits() {
+        // Z_y + X = 103, L_y of Customer3 decides, above or below L
+        assert!(matches!(
+            run_cfm_session(P, OutputPolicy::Both, 50, Some(104), true),
+            (CFMOutput::Result(true), CFMOutput::Result(true))
+        ));
+        assert!(matches!(
+            run_cfm_session(P, OutputPolicy::Both, 1000, Some(102), true),
+            (CFMOutput::Result(false), CFMOutput::Result(false))
+        ));
+    }
+
+    #[test]
+    fn test_cfm_ledger() {
+        let mut ledger = Ledger::new();
+        let mut ledger_keys = LedgerKeys::new(&P, &mut OsRng);
+        let run = |big_l, ledger: &mut Ledger, ledger_keys: &mut LedgerKeys| {
+            run_ledger_session(P, OutputPolicy::Both, big_l, None, false, ledger, ledger_keys)
+        };
+
+        // the approved X = 100 of Customer3 counts towards its next transfer
+        assert!(matches!(
+            run(104, &mut ledger, &mut ledger_keys),
+            Ok((CFMOutput::Result(true), CFMOutput::Result(true)))
+        ));
+        ledger.settle(true).unwrap();
+        ledger_keys.settle(true).unwrap();
+        assert!(matches!(
+            run(104, &mut ledger, &mut ledger_keys),
+            Ok((CFMOutput::Result(false), CFMOutput::Result(false)))
+        ));
+        ledger.settle(false).unwrap();
+        ledger_keys.settle(false).unwrap();
+
+        // OB drops an approved transfer, its stale tag fails the MAC check
+        assert!(matches!(
+            run(1000, &mut ledger, &mut ledger_keys),
+            Ok((CFMOutput::Result(true), CFMOutput::Result(true)))
+        ));
+        ledger.settle(false).unwrap();
+        ledger_keys.settle(true).unwrap();
+        let dropped = run(1000, &mut ledger, &mut ledger_keys);
+        assert!(dropped.is_err_and(|e| e.failed_check().is_some()));
+    }
+
+    /// Run OT seeds creation, ABT and CFM sessions under prime p with limit
+    /// `big_l`, private to CB if `private_limit`. `big_l_3` is the limit of
+    /// Customer3, the customer of OB, the others keep L. Returns the outputs
+    /// of OB and CB
+    fn run_cfm_session(
+        p: U128,
+        output_policy: OutputPolicy,
+        big_l: u32,
+        big_l_3: Option<u32>,
+        private_limit: bool,
+    ) -> (CFMOutput, CFMOutput) {
+        let mut ledger_keys = LedgerKeys::new(&p, &mut OsRng);
+        run_ledger_session(
+            p,
+            output_policy,
+            big_l,
+            big_l_3,
+            private_limit,
+            &mut Ledger::new(),
+            &mut ledger_keys,
+        )
+        .unwrap()
+    }
+
+    /// Run a CFM session of `run_cfm_session` on the ledger of OB and the
+    /// ledger keys of CB, holding back their updates
+    fn run_ledger_session(
+        p: U128,
+        output_policy: OutputPolicy,
+        big_l: u32,
+        big_l_3: Option<u32>,
+        private_limit: bool,
+        ledger: &mut Ledger,
+        ledger_keys: &mut LedgerKeys,
+    ) -> Result<(CFMOutput, CFMOutput), CFMError> {
+        let mut rng = OsRng;
+
+        // create OT seeds
+        let init_session_id: [u8; 32] = rng.gen();
+        let (ot_seeds_cb, ot_seeds_ob) = generate_cfm_ot_seeds_for_test(&init_session_id, &mut rng);
+
+        // create auth beaver triples
+        let session_id: [u8; 32] = rng.gen();
+        let eta_i = NUMBER_OF_SHARES;
+        let eta_m = NUMBER_OF_AUTH_BEAVER_TRIPLES;
+        let (state_cb_r1, msg1) =
+            abt_create_msg1(&session_id, &ot_seeds_cb, p, eta_i, eta_m, &mut rng).unwrap();
+        let (state_ob_r1, mut shares_ob, mut auth_triples_ob, msg2) =
+            abt_process_msg1(&session_id, &ot_seeds_ob, p, eta_i, eta_m, &msg1, &mut rng).unwrap();
+        let (state_cb_r2, shares_cb, auth_triples_cb, msg3) =
+            abt_process_msg2(&state_cb_r1, &ot_seeds_cb, &msg2, &mut rng).unwrap();
+        let (state_ob_r2, msg4) =
+            abt_process_msg3(&state_ob_r1, &mut shares_ob, &mut auth_triples_ob, &msg3).unwrap();
+        let msg5 = abt_process_msg4(&state_cb_r2, &auth_triples_cb, &msg4).unwrap();
+        abt_process_msg5(&state_ob_r2, &msg5).unwrap();
+
+        let big_l = U64::from_u32(big_l);
+        let big_y: Vec<[u8; 32]> = (1..=10)
+            .map(|i| {
+                Customer::new(&format!("Customer{}", i), &format!("P{}", i), "Main St")
+                    .to_hash_bytes()
+            })
+            .collect();
+        let big_z: Vec<U64> = (1..=10).map(U64::from_u8).collect();
+        let big_l_y = big_l_3.map(|big_l_3| {
+            let mut big_l_y = vec![big_l; 10];
+            big_l_y[2] = U64::from_u32(big_l_3);
+            big_l_y
+        });
+        let customer_y = Customer::new("Customer3", "P3", "Main St").to_hash_bytes();
+        let big_x = U64::from_u32(100);
+        ledger_keys.assign_slots(&big_y);
+
+        let (mut ob, msg1) = OBSession::new(
+            &session_id,
+            p,
+            output_policy,
+            (!private_limit).then_some(big_l),
+            big_x,
+            &customer_y,
+            ledger,
+            &ot_seeds_ob,
+            shares_ob,
+            auth_triples_ob,
+            &mut rng,
+        )
+        .unwrap();
+        let mut cb = CBSession::new(
+            &session_id,
+            p,
+            output_policy,
+            big_l,
+            private_limit,
+            big_y,
+            big_z,
+            big_l_y,
+            ledger_keys,
+            &ot_seeds_cb,
+            shares_cb,
+            auth_triples_cb,
+        )
+        .unwrap();
+
+        // OB does not accept its own message
+        assert!(matches!(
+            ob.handle(&msg1, &mut rng),
+            Err(CFMError::UnexpectedMessage {
+                expected: 2,
+                received: 1
+            })
+        ));
+
+        let mut to_cb = msg1;
+        let (b_cb_value, msg18) = loop {
+            let to_ob = match cb.handle(&to_cb, &mut rng)? {
+                CFMStep::Outgoing(msg) => msg,
+                CFMStep::Finished { output, outgoing } => break (output, outgoing.unwrap()),
+            };
+            // replaying the previous message is rejected
+            assert!(cb.handle(&to_cb, &mut rng).is_err());
+            to_cb = match ob.handle(&to_ob, &mut rng)? {
+                CFMStep::Outgoing(msg) => msg,
+                CFMStep::Finished { .. } => panic!("OB finished before CB"),
+            };
+        };
+
+        assert!(matches!(msg18, CFMMessage::Msg18(_)));
+        let b_ob_value = match ob.handle(&msg18, &mut rng)? {
+            CFMStep::Finished { output, outgoing } => {
+                assert!(outgoing.is_none());
+                output
+            }
+            CFMStep::Outgoing(_) => panic!("OB expected to finish"),
+        };
+
+        assert_eq!(ob.result(), b_ob_value.result());
+        assert_eq!(cb.result(), b_cb_value.result());
+        assert!(matches!(
+            ob.handle(&msg18, &mut rng),
+            Err(CFMError::SessionClosed)
+        ));
+
+        ledger.hold(&customer_y, ob.ledger_update().unwrap().clone());
+        ledger_keys.hold(cb.ledger_update().unwrap().clone());
+        Ok((b_ob_value, b_cb_value))
     }
 }
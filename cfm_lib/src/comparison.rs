@@ -385,7 +385,7 @@ pub fn comp_process_msg1(
         let x_i_mul_y_i = match multiply_shares_output(&mul_state, &msg1.mul_open[i], false, params)
         {
             Ok(v) => v,
-            Err(_) => return Err(CompError::InvalidOpen),
+            Err(e) => return Err(CompError::InvalidOpen("mul_open", i, e)),
         };
         // [t_{i,1}] = [X_i] - [X_i*Y_i]
         t_shares[i] = x[i].sub_share(&x_i_mul_y_i, params);
@@ -472,7 +472,7 @@ pub fn comp_process_msg2(
         let x_i_mul_y_i =
             match multiply_shares_output(&state.mul_state[i], &msg2.mul_open[i], true, params) {
                 Ok(v) => v,
-                Err(_) => return Err(CompError::InvalidOpen),
+                Err(e) => return Err(CompError::InvalidOpen("mul_open", i, e)),
             };
         // [t_{i,1}] = [X_i] - [X_i*Y_i]
         t_shares[i] = x[i].sub_share(&x_i_mul_y_i, params);
@@ -506,7 +506,7 @@ pub fn comp_process_msg2(
         let z_share =
             match multiply_shares_output(&mul_state, &msg2.mul_open_z_r1[i - 1], true, params) {
                 Ok(v) => v,
-                Err(_) => return Err(CompError::InvalidOpen),
+                Err(e) => return Err(CompError::InvalidOpen("mul_open_z_r1", i - 1, e)),
             };
         z_shares_next[i] = z_share;
     }
@@ -526,7 +526,7 @@ pub fn comp_process_msg2(
         let t_mul_share =
             match multiply_shares_output(&mul_state, &msg2.mul_open_t_r1[i], true, params) {
                 Ok(v) => v,
-                Err(_) => return Err(CompError::InvalidOpen),
+                Err(e) => return Err(CompError::InvalidOpen("mul_open_t_r1", i, e)),
             };
         t_shares_next[i] = t_mul_share.add_share(&t_shares[i * 2 + 1], params);
     }
@@ -609,7 +609,7 @@ pub fn comp_process_msg3(
             params,
         ) {
             Ok(v) => v,
-            Err(_) => return Err(CompError::InvalidOpen),
+            Err(e) => return Err(CompError::InvalidOpen("mul_open_z_r1", i - 1, e)),
         };
         z_shares[i] = z_share;
     }
@@ -623,7 +623,7 @@ pub fn comp_process_msg3(
             params,
         ) {
             Ok(v) => v,
-            Err(_) => return Err(CompError::InvalidOpen),
+            Err(e) => return Err(CompError::InvalidOpen("mul_open_t_r1", i, e)),
         };
         t_shares[i] = t_share.add_share(&state.t_shares[i * 2 + 1], params);
     }
@@ -648,7 +648,7 @@ pub fn comp_process_msg3(
         let z_share =
             match multiply_shares_output(&mul_state, &msg3.mul_open_z_r2[i - 1], false, params) {
                 Ok(v) => v,
-                Err(_) => return Err(CompError::InvalidOpen),
+                Err(e) => return Err(CompError::InvalidOpen("mul_open_z_r2", i - 1, e)),
             };
         z_shares_next[i] = z_share;
     }
@@ -668,7 +668,7 @@ pub fn comp_process_msg3(
         let t_mul_share =
             match multiply_shares_output(&mul_state, &msg3.mul_open_t_r2[i], false, params) {
                 Ok(v) => v,
-                Err(_) => return Err(CompError::InvalidOpen),
+                Err(e) => return Err(CompError::InvalidOpen("mul_open_t_r2", i, e)),
             };
         t_shares_next[i] = t_mul_share.add_share(&t_shares[i * 2 + 1], params);
     }
@@ -751,7 +751,7 @@ pub fn comp_process_msg4(
             params,
         ) {
             Ok(v) => v,
-            Err(_) => return Err(CompError::InvalidOpen),
+            Err(e) => return Err(CompError::InvalidOpen("mul_open_z_r2", i - 1, e)),
         };
         z_shares[i] = z_share;
     }
@@ -765,7 +765,7 @@ pub fn comp_process_msg4(
             params,
         ) {
             Ok(v) => v,
-            Err(_) => return Err(CompError::InvalidOpen),
+            Err(e) => return Err(CompError::InvalidOpen("mul_open_t_r2", i, e)),
         };
         t_shares[i] = t_share.add_share(&state.t_shares[i * 2 + 1], params);
     }
@@ -790,7 +790,7 @@ pub fn comp_process_msg4(
         let z_share =
             match multiply_shares_output(&mul_state, &msg4.mul_open_z_r3[i - 1], true, params) {
                 Ok(v) => v,
-                Err(_) => return Err(CompError::InvalidOpen),
+                Err(e) => return Err(CompError::InvalidOpen("mul_open_z_r3", i - 1, e)),
             };
         z_shares_next[i] = z_share;
     }
@@ -810,7 +810,7 @@ pub fn comp_process_msg4(
         let t_mul_share =
             match multiply_shares_output(&mul_state, &msg4.mul_open_t_r3[i], true, params) {
                 Ok(v) => v,
-                Err(_) => return Err(CompError::InvalidOpen),
+                Err(e) => return Err(CompError::InvalidOpen("mul_open_t_r3", i, e)),
             };
         t_shares_next[i] = t_mul_share.add_share(&t_shares[i * 2 + 1], params);
     }
@@ -896,7 +896,7 @@ pub fn comp_process_msg5(
             params,
         ) {
             Ok(v) => v,
-            Err(_) => return Err(CompError::InvalidOpen),
+            Err(e) => return Err(CompError::InvalidOpen("mul_open_z_r3", i - 1, e)),
         };
         z_shares[i] = z_share;
     }
@@ -910,7 +910,7 @@ pub fn comp_process_msg5(
             params,
         ) {
             Ok(v) => v,
-            Err(_) => return Err(CompError::InvalidOpen),
+            Err(e) => return Err(CompError::InvalidOpen("mul_open_t_r3", i, e)),
         };
         t_shares[i] = t_share.add_share(&state.t_shares[i * 2 + 1], params);
     }
@@ -935,7 +935,7 @@ pub fn comp_process_msg5(
         let z_share =
             match multiply_shares_output(&mul_state, &msg5.mul_open_z_r4[i - 1], false, params) {
                 Ok(v) => v,
-                Err(_) => return Err(CompError::InvalidOpen),
+                Err(e) => return Err(CompError::InvalidOpen("mul_open_z_r4", i - 1, e)),
             };
         z_shares_next[i] = z_share;
     }
@@ -955,9 +955,9 @@ pub fn comp_process_msg5(
         let t_mul_share =
             match multiply_shares_output(&mul_state, &msg5.mul_open_t_r4[i], false, params) {
                 Ok(v) => v,
-                Err(_) => {
+                Err(e) => {
                     println!("err4");
-                    return Err(CompError::InvalidOpen);
+                    return Err(CompError::InvalidOpen("mul_open_t_r4", i, e));
                 }
             };
         t_shares_next[i] = t_mul_share.add_share(&t_shares[i * 2 + 1], params);
@@ -1044,7 +1044,7 @@ pub fn comp_process_msg6(
             params,
         ) {
             Ok(v) => v,
-            Err(_) => return Err(CompError::InvalidOpen),
+            Err(e) => return Err(CompError::InvalidOpen("mul_open_z_r4", i - 1, e)),
         };
         z_shares[i] = z_share;
     }
@@ -1058,7 +1058,7 @@ pub fn comp_process_msg6(
             params,
         ) {
             Ok(v) => v,
-            Err(_) => return Err(CompError::InvalidOpen),
+            Err(e) => return Err(CompError::InvalidOpen("mul_open_t_r4", i, e)),
         };
         t_shares[i] = t_share.add_share(&state.t_shares[i * 2 + 1], params);
     }
@@ -1083,7 +1083,7 @@ pub fn comp_process_msg6(
         let z_share =
             match multiply_shares_output(&mul_state, &msg6.mul_open_z_r5[i - 1], true, params) {
                 Ok(v) => v,
-                Err(_) => return Err(CompError::InvalidOpen),
+                Err(e) => return Err(CompError::InvalidOpen("mul_open_z_r5", i - 1, e)),
             };
         z_shares_next[i] = z_share;
     }
@@ -1103,7 +1103,7 @@ pub fn comp_process_msg6(
         let t_mul_share =
             match multiply_shares_output(&mul_state, &msg6.mul_open_t_r5[i], true, params) {
                 Ok(v) => v,
-                Err(_) => return Err(CompError::InvalidOpen),
+                Err(e) => return Err(CompError::InvalidOpen("mul_open_t_r5", i, e)),
             };
         t_shares_next[i] = t_mul_share.add_share(&t_shares[i * 2 + 1], params);
     }
@@ -1189,7 +1189,7 @@ pub fn comp_process_msg7(
             params,
         ) {
             Ok(v) => v,
-            Err(_) => return Err(CompError::InvalidOpen),
+            Err(e) => return Err(CompError::InvalidOpen("mul_open_z_r5", i - 1, e)),
         };
         z_shares[i] = z_share;
     }
@@ -1203,7 +1203,7 @@ pub fn comp_process_msg7(
             params,
         ) {
             Ok(v) => v,
-            Err(_) => return Err(CompError::InvalidOpen),
+            Err(e) => return Err(CompError::InvalidOpen("mul_open_t_r5", i, e)),
         };
         t_shares[i] = t_share.add_share(&state.t_shares[i * 2 + 1], params);
     }
@@ -1228,7 +1228,7 @@ pub fn comp_process_msg7(
         let z_share =
             match multiply_shares_output(&mul_state, &msg7.mul_open_z_r6[i - 1], false, params) {
                 Ok(v) => v,
-                Err(_) => return Err(CompError::InvalidOpen),
+                Err(e) => return Err(CompError::InvalidOpen("mul_open_z_r6", i - 1, e)),
             };
         z_shares_next[i] = z_share;
     }
@@ -1248,7 +1248,7 @@ pub fn comp_process_msg7(
         let t_mul_share =
             match multiply_shares_output(&mul_state, &msg7.mul_open_t_r6[i], false, params) {
                 Ok(v) => v,
-                Err(_) => return Err(CompError::InvalidOpen),
+                Err(e) => return Err(CompError::InvalidOpen("mul_open_t_r6", i, e)),
             };
         t_shares_next[i] = t_mul_share.add_share(&t_shares[i * 2 + 1], params);
     }
@@ -1316,7 +1316,7 @@ pub fn comp_process_msg8(
             params,
         ) {
             Ok(v) => v,
-            Err(_) => return Err(CompError::InvalidOpen),
+            Err(e) => return Err(CompError::InvalidOpen("mul_open_z_r6", i - 1, e)),
         };
         z_shares[i] = z_share;
     }
@@ -1330,7 +1330,7 @@ pub fn comp_process_msg8(
             params,
         ) {
             Ok(v) => v,
-            Err(_) => return Err(CompError::InvalidOpen),
+            Err(e) => return Err(CompError::InvalidOpen("mul_open_t_r6", i, e)),
         };
         t_shares[i] = t_share.add_share(&state.t_shares[i * 2 + 1], params);
     }
@@ -1354,7 +1354,7 @@ pub fn comp_process_msg8(
         let t_mul_share =
             match multiply_shares_output(&mul_state, &msg8.mul_open_t_r7[i], true, params) {
                 Ok(v) => v,
-                Err(_) => return Err(CompError::InvalidOpen),
+                Err(e) => return Err(CompError::InvalidOpen("mul_open_t_r7", i, e)),
             };
         t_shares_next[i] = t_mul_share.add_share(&t_shares[i * 2 + 1], params);
     }
@@ -1392,7 +1392,7 @@ pub fn comp_process_msg9(state: &CompStateP2R7, msg9: &CompMsg9) -> Result<Share
             params,
         ) {
             Ok(v) => v,
-            Err(_) => return Err(CompError::InvalidOpen),
+            Err(e) => return Err(CompError::InvalidOpen("mul_open_t_r7", i, e)),
         };
         t_shares[i] = t_share.add_share(&state.t_shares[i * 2 + 1], params);
     }
@@ -1436,7 +1436,7 @@ mod tests {
 
         let eta_i = 0;
         let (state_cb_r1, msg1) =
-            abt_create_msg1(&session_id, &ot_seeds_cb, p_prime, eta_i, eta_m, &mut rng);
+            abt_create_msg1(&session_id, &ot_seeds_cb, p_prime, eta_i, eta_m, &mut rng).unwrap();
         let (state_ob_r1, mut input_shares_ob, mut triple_shares_ob, msg2) = abt_process_msg1(
             &session_id,
             &ot_seeds_ob,
@@ -16,6 +16,13 @@ pub const MASK: usize = LAMBDA_S + B_PARAMETER + 1;
 /// Masking parameter in bytes
 pub const MASK_BYTES: usize = MASK / 8;
 
+/// Number of escrowed pairs in the cut-and-choose of the fair release, each
+/// takes one extra authenticated share of ABT
+pub const FAIR_RELEASE_PAIRS: usize = LAMBDA_S;
+
+/// Number of MASK_BYTES words of a PSIT record
+pub const PSIT_RECORD_WORDS: usize = 6;
+
 /// LABEL for H1 random oracle function
 pub const H1_RO_LABEL: &str = "SL-PSC-H1-function";
 
@@ -27,3 +34,27 @@ pub const DLOG_LABEL: &str = "SL-PSC-DLog-proof";
 
 /// LABEL for CFM protocol
 pub const CFM_LABEL: &str = "SL-CFM-protocol";
+
+/// LABEL for triple pool session ids
+pub const TRIPLE_POOL_LABEL: &str = "SL-CFM-triple-pool";
+
+/// LABEL for ABT session ids derived from the OT seeds
+pub const SEED_STORE_LABEL: &str = "SL-CFM-seed-store";
+
+/// LABEL for Schnorr signatures
+pub const SCHNORR_LABEL: &str = "SL-CFM-schnorr-signature";
+
+/// LABEL for the running transcript hash of a session
+pub const TRANSCRIPT_LABEL: &str = "SL-CFM-transcript";
+
+/// LABEL for abort reports
+pub const ABORT_REPORT_LABEL: &str = "SL-CFM-abort-report";
+
+/// LABEL for receipts of CFM outcomes
+pub const RECEIPT_LABEL: &str = "SL-CFM-receipt";
+
+/// LABEL for the channel binding handshake
+pub const IDENTITY_LABEL: &str = "SL-CFM-identity";
+
+/// LABEL for the fair release of the CFM result
+pub const FAIR_RELEASE_LABEL: &str = "SL-CFM-fair-release";
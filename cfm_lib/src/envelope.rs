@@ -0,0 +1,620 @@
+//! Versioned tagged envelope for CFM init, ABT, comparison, CFM and fair
+//! release messages.
+//!
+//! Wire format: a fixed size header followed by the bincode encoded message.
+//!
+//! | offset | size | field      |
+//! |--------|------|------------|
+//! | 0      | 1    | version    |
+//! | 1      | 1    | protocol   |
+//! | 2      | 1    | round      |
+//! | 3      | 1    | sender     |
+//! | 4      | 32   | session id |
+//!
+//! The header can be read with [`decode_header`] without touching the payload,
+//! [`decode`] validates the header before deserializing the message.
+
+use crate::auth_beaver_triples::{ABTMsg1, ABTMsg2, ABTMsg3, ABTMsg4, ABTMsg5};
+use crate::cfm_init_protocol::{CFMInitMsg1, CFMInitMsg2, CFMInitMsg3};
+use crate::cfm_protocol::{
+    CFMMessage, CFMMsg1, CFMMsg10, CFMMsg11, CFMMsg12, CFMMsg13, CFMMsg14, CFMMsg15, CFMMsg16,
+    CFMMsg17, CFMMsg18, CFMMsg2, CFMMsg3, CFMMsg4, CFMMsg5, CFMMsg6, CFMMsg7, CFMMsg8, CFMMsg9,
+};
+use crate::comparison::{
+    CompMsg1, CompMsg2, CompMsg3, CompMsg4, CompMsg5, CompMsg6, CompMsg7, CompMsg8, CompMsg9,
+};
+use crate::errors::EnvelopeError;
+use crate::fair_release::{FairMsg1, FairMsg2, FairMsg3, FairMsg4, FairMsg5, FairMsg6};
+use serde::{Deserialize, Serialize};
+
+/// Current envelope format version
+pub const ENVELOPE_VERSION: u8 = 1;
+
+/// Size of the envelope header in bytes
+pub const HEADER_SIZE: usize = 36;
+
+/// Sub-protocol a message belongs to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum ProtocolId {
+    /// OT seeds creation, cfm_init_protocol
+    CFMInit = 1,
+    /// Authenticated beaver triples, auth_beaver_triples
+    ABT = 2,
+    /// Comparison protocol, comparison
+    Comparison = 3,
+    /// CFM protocol, cfm_protocol
+    CFM = 4,
+    /// Fair release of the CFM result, fair_release
+    FairRelease = 5,
+}
+
+impl ProtocolId {
+    /// Number of messages in the sub-protocol
+    pub fn rounds(&self) -> u8 {
+        match self {
+            ProtocolId::CFMInit => 3,
+            ProtocolId::ABT => 5,
+            ProtocolId::Comparison => 9,
+            ProtocolId::CFM => 18,
+            ProtocolId::FairRelease => 6,
+        }
+    }
+
+    /// Party sending message `round` of the sub-protocol
+    pub fn sender(&self, round: u8) -> Role {
+        let odd = round % 2 == 1;
+        match self {
+            // OB starts OT seeds creation, CFM and the fair release
+            ProtocolId::CFMInit | ProtocolId::CFM | ProtocolId::FairRelease if odd => Role::OB,
+            ProtocolId::CFMInit | ProtocolId::CFM | ProtocolId::FairRelease => Role::CB,
+            // CB starts ABT, and is P1 in comparison
+            ProtocolId::ABT | ProtocolId::Comparison if odd => Role::CB,
+            ProtocolId::ABT | ProtocolId::Comparison => Role::OB,
+        }
+    }
+}
+
+impl TryFrom<u8> for ProtocolId {
+    type Error = EnvelopeError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(ProtocolId::CFMInit),
+            2 => Ok(ProtocolId::ABT),
+            3 => Ok(ProtocolId::Comparison),
+            4 => Ok(ProtocolId::CFM),
+            5 => Ok(ProtocolId::FairRelease),
+            _ => Err(EnvelopeError::UnknownProtocol(value)),
+        }
+    }
+}
+
+/// Protocol party
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum Role {
+    /// Originating bank
+    OB = 1,
+    /// Correspondent bank
+    CB = 2,
+}
+
+impl TryFrom<u8> for Role {
+    type Error = EnvelopeError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(Role::OB),
+            2 => Ok(Role::CB),
+            _ => Err(EnvelopeError::UnknownRole(value)),
+        }
+    }
+}
+
+/// Envelope header
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnvelopeHeader {
+    /// envelope format version
+    pub version: u8,
+
+    /// sub-protocol
+    pub protocol: ProtocolId,
+
+    /// message number within the sub-protocol, starting from 1
+    pub round: u8,
+
+    /// sending party
+    pub sender: Role,
+
+    /// session id
+    pub session_id: [u8; 32],
+}
+
+impl EnvelopeHeader {
+    /// Serialize the header
+    pub fn to_bytes(&self) -> [u8; HEADER_SIZE] {
+        let mut bytes = [0u8; HEADER_SIZE];
+        bytes[0] = self.version;
+        bytes[1] = self.protocol as u8;
+        bytes[2] = self.round;
+        bytes[3] = self.sender as u8;
+        bytes[4..].copy_from_slice(&self.session_id);
+        bytes
+    }
+
+    /// Deserialize and validate the header
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, EnvelopeError> {
+        if bytes.len() < HEADER_SIZE {
+            return Err(EnvelopeError::InvalidMessage);
+        }
+        let version = bytes[0];
+        if version != ENVELOPE_VERSION {
+            return Err(EnvelopeError::UnsupportedVersion(version));
+        }
+        let protocol = ProtocolId::try_from(bytes[1])?;
+        let round = bytes[2];
+        if round == 0 || round > protocol.rounds() {
+            return Err(EnvelopeError::UnknownRound { protocol, round });
+        }
+        let sender = Role::try_from(bytes[3])?;
+        if sender != protocol.sender(round) {
+            return Err(EnvelopeError::UnexpectedSender { protocol, round });
+        }
+        let mut session_id = [0u8; 32];
+        session_id.copy_from_slice(&bytes[4..HEADER_SIZE]);
+
+        Ok(EnvelopeHeader {
+            version,
+            protocol,
+            round,
+            sender,
+            session_id,
+        })
+    }
+}
+
+/// Any message of the CFM protocol stack
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Message {
+    /// CFMInitMsg1
+    CFMInitMsg1(Box<CFMInitMsg1>),
+    /// CFMInitMsg2
+    CFMInitMsg2(Box<CFMInitMsg2>),
+    /// CFMInitMsg3
+    CFMInitMsg3(Box<CFMInitMsg3>),
+    /// ABTMsg1
+    ABTMsg1(Box<ABTMsg1>),
+    /// ABTMsg2
+    ABTMsg2(Box<ABTMsg2>),
+    /// ABTMsg3
+    ABTMsg3(Box<ABTMsg3>),
+    /// ABTMsg4
+    ABTMsg4(Box<ABTMsg4>),
+    /// ABTMsg5
+    ABTMsg5(Box<ABTMsg5>),
+    /// CompMsg1
+    CompMsg1(Box<CompMsg1>),
+    /// CompMsg2
+    CompMsg2(Box<CompMsg2>),
+    /// CompMsg3
+    CompMsg3(Box<CompMsg3>),
+    /// CompMsg4
+    CompMsg4(Box<CompMsg4>),
+    /// CompMsg5
+    CompMsg5(Box<CompMsg5>),
+    /// CompMsg6
+    CompMsg6(Box<CompMsg6>),
+    /// CompMsg7
+    CompMsg7(Box<CompMsg7>),
+    /// CompMsg8
+    CompMsg8(Box<CompMsg8>),
+    /// CompMsg9
+    CompMsg9(Box<CompMsg9>),
+    /// CFMMsg1
+    CFMMsg1(Box<CFMMsg1>),
+    /// CFMMsg2
+    CFMMsg2(Box<CFMMsg2>),
+    /// CFMMsg3
+    CFMMsg3(Box<CFMMsg3>),
+    /// CFMMsg4
+    CFMMsg4(Box<CFMMsg4>),
+    /// CFMMsg5
+    CFMMsg5(Box<CFMMsg5>),
+    /// CFMMsg6
+    CFMMsg6(Box<CFMMsg6>),
+    /// CFMMsg7
+    CFMMsg7(Box<CFMMsg7>),
+    /// CFMMsg8
+    CFMMsg8(Box<CFMMsg8>),
+    /// CFMMsg9
+    CFMMsg9(Box<CFMMsg9>),
+    /// CFMMsg10
+    CFMMsg10(Box<CFMMsg10>),
+    /// CFMMsg11
+    CFMMsg11(Box<CFMMsg11>),
+    /// CFMMsg12
+    CFMMsg12(Box<CFMMsg12>),
+    /// CFMMsg13
+    CFMMsg13(Box<CFMMsg13>),
+    /// CFMMsg14
+    CFMMsg14(Box<CFMMsg14>),
+    /// CFMMsg15
+    CFMMsg15(Box<CFMMsg15>),
+    /// CFMMsg16
+    CFMMsg16(Box<CFMMsg16>),
+    /// CFMMsg17
+    CFMMsg17(Box<CFMMsg17>),
+    /// CFMMsg18
+    CFMMsg18(Box<CFMMsg18>),
+    /// FairMsg1
+    FairMsg1(Box<FairMsg1>),
+    /// FairMsg2
+    FairMsg2(Box<FairMsg2>),
+    /// FairMsg3
+    FairMsg3(Box<FairMsg3>),
+    /// FairMsg4
+    FairMsg4(Box<FairMsg4>),
+    /// FairMsg5
+    FairMsg5(Box<FairMsg5>),
+    /// FairMsg6
+    FairMsg6(Box<FairMsg6>),
+}
+
+impl Message {
+    /// Sub-protocol and round number of the message
+    pub fn tag(&self) -> (ProtocolId, u8) {
+        match self {
+            Message::CFMInitMsg1(_) => (ProtocolId::CFMInit, 1),
+            Message::CFMInitMsg2(_) => (ProtocolId::CFMInit, 2),
+            Message::CFMInitMsg3(_) => (ProtocolId::CFMInit, 3),
+            Message::ABTMsg1(_) => (ProtocolId::ABT, 1),
+            Message::ABTMsg2(_) => (ProtocolId::ABT, 2),
+            Message::ABTMsg3(_) => (ProtocolId::ABT, 3),
+            Message::ABTMsg4(_) => (ProtocolId::ABT, 4),
+            Message::ABTMsg5(_) => (ProtocolId::ABT, 5),
+            Message::CompMsg1(_) => (ProtocolId::Comparison, 1),
+            Message::CompMsg2(_) => (ProtocolId::Comparison, 2),
+            Message::CompMsg3(_) => (ProtocolId::Comparison, 3),
+            Message::CompMsg4(_) => (ProtocolId::Comparison, 4),
+            Message::CompMsg5(_) => (ProtocolId::Comparison, 5),
+            Message::CompMsg6(_) => (ProtocolId::Comparison, 6),
+            Message::CompMsg7(_) => (ProtocolId::Comparison, 7),
+            Message::CompMsg8(_) => (ProtocolId::Comparison, 8),
+            Message::CompMsg9(_) => (ProtocolId::Comparison, 9),
+            Message::CFMMsg1(_) => (ProtocolId::CFM, 1),
+            Message::CFMMsg2(_) => (ProtocolId::CFM, 2),
+            Message::CFMMsg3(_) => (ProtocolId::CFM, 3),
+            Message::CFMMsg4(_) => (ProtocolId::CFM, 4),
+            Message::CFMMsg5(_) => (ProtocolId::CFM, 5),
+            Message::CFMMsg6(_) => (ProtocolId::CFM, 6),
+            Message::CFMMsg7(_) => (ProtocolId::CFM, 7),
+            Message::CFMMsg8(_) => (ProtocolId::CFM, 8),
+            Message::CFMMsg9(_) => (ProtocolId::CFM, 9),
+            Message::CFMMsg10(_) => (ProtocolId::CFM, 10),
+            Message::CFMMsg11(_) => (ProtocolId::CFM, 11),
+            Message::CFMMsg12(_) => (ProtocolId::CFM, 12),
+            Message::CFMMsg13(_) => (ProtocolId::CFM, 13),
+            Message::CFMMsg14(_) => (ProtocolId::CFM, 14),
+            Message::CFMMsg15(_) => (ProtocolId::CFM, 15),
+            Message::CFMMsg16(_) => (ProtocolId::CFM, 16),
+            Message::CFMMsg17(_) => (ProtocolId::CFM, 17),
+            Message::CFMMsg18(_) => (ProtocolId::CFM, 18),
+            Message::FairMsg1(_) => (ProtocolId::FairRelease, 1),
+            Message::FairMsg2(_) => (ProtocolId::FairRelease, 2),
+            Message::FairMsg3(_) => (ProtocolId::FairRelease, 3),
+            Message::FairMsg4(_) => (ProtocolId::FairRelease, 4),
+            Message::FairMsg5(_) => (ProtocolId::FairRelease, 5),
+            Message::FairMsg6(_) => (ProtocolId::FairRelease, 6),
+        }
+    }
+
+    /// Session id carried by the message
+    pub fn session_id(&self) -> &[u8; 32] {
+        match self {
+            Message::CFMInitMsg1(msg) => &msg.session_id,
+            Message::CFMInitMsg2(msg) => &msg.session_id,
+            Message::CFMInitMsg3(msg) => &msg.session_id,
+            Message::ABTMsg1(msg) => &msg.session_id,
+            Message::ABTMsg2(msg) => &msg.session_id,
+            Message::ABTMsg3(msg) => &msg.session_id,
+            Message::ABTMsg4(msg) => &msg.session_id,
+            Message::ABTMsg5(msg) => &msg.session_id,
+            Message::CompMsg1(msg) => &msg.session_id,
+            Message::CompMsg2(msg) => &msg.session_id,
+            Message::CompMsg3(msg) => &msg.session_id,
+            Message::CompMsg4(msg) => &msg.session_id,
+            Message::CompMsg5(msg) => &msg.session_id,
+            Message::CompMsg6(msg) => &msg.session_id,
+            Message::CompMsg7(msg) => &msg.session_id,
+            Message::CompMsg8(msg) => &msg.session_id,
+            Message::CompMsg9(msg) => &msg.session_id,
+            Message::CFMMsg1(msg) => &msg.session_id,
+            Message::CFMMsg2(msg) => &msg.session_id,
+            Message::CFMMsg3(msg) => &msg.session_id,
+            Message::CFMMsg4(msg) => &msg.session_id,
+            Message::CFMMsg5(msg) => &msg.session_id,
+            Message::CFMMsg6(msg) => &msg.session_id,
+            Message::CFMMsg7(msg) => &msg.session_id,
+            Message::CFMMsg8(msg) => &msg.session_id,
+            Message::CFMMsg9(msg) => &msg.session_id,
+            Message::CFMMsg10(msg) => &msg.session_id,
+            Message::CFMMsg11(msg) => &msg.session_id,
+            Message::CFMMsg12(msg) => &msg.session_id,
+            Message::CFMMsg13(msg) => &msg.session_id,
+            Message::CFMMsg14(msg) => &msg.session_id,
+            Message::CFMMsg15(msg) => &msg.session_id,
+            Message::CFMMsg16(msg) => &msg.session_id,
+            Message::CFMMsg17(msg) => &msg.session_id,
+            Message::CFMMsg18(msg) => &msg.session_id,
+            Message::FairMsg1(msg) => &msg.escrow.session_id,
+            Message::FairMsg2(msg) => &msg.escrow.session_id,
+            Message::FairMsg3(msg) => &msg.session_id,
+            Message::FairMsg4(msg) => &msg.session_id,
+            Message::FairMsg5(msg) => &msg.session_id,
+            Message::FairMsg6(msg) => &msg.session_id,
+        }
+    }
+
+    /// Envelope header for the message
+    pub fn header(&self) -> EnvelopeHeader {
+        let (protocol, round) = self.tag();
+        EnvelopeHeader {
+            version: ENVELOPE_VERSION,
+            protocol,
+            round,
+            sender: protocol.sender(round),
+            session_id: *self.session_id(),
+        }
+    }
+
+    /// Convert into a CFMMessage, None for messages of other sub-protocols
+    pub fn into_cfm_message(self) -> Option<CFMMessage> {
+        let msg = match self {
+            Message::CFMMsg1(msg) => CFMMessage::Msg1(msg),
+            Message::CFMMsg2(msg) => CFMMessage::Msg2(msg),
+            Message::CFMMsg3(msg) => CFMMessage::Msg3(msg),
+            Message::CFMMsg4(msg) => CFMMessage::Msg4(msg),
+            Message::CFMMsg5(msg) => CFMMessage::Msg5(msg),
+            Message::CFMMsg6(msg) => CFMMessage::Msg6(msg),
+            Message::CFMMsg7(msg) => CFMMessage::Msg7(msg),
+            Message::CFMMsg8(msg) => CFMMessage::Msg8(msg),
+            Message::CFMMsg9(msg) => CFMMessage::Msg9(msg),
+            Message::CFMMsg10(msg) => CFMMessage::Msg10(msg),
+            Message::CFMMsg11(msg) => CFMMessage::Msg11(msg),
+            Message::CFMMsg12(msg) => CFMMessage::Msg12(msg),
+            Message::CFMMsg13(msg) => CFMMessage::Msg13(msg),
+            Message::CFMMsg14(msg) => CFMMessage::Msg14(msg),
+            Message::CFMMsg15(msg) => CFMMessage::Msg15(*msg),
+            Message::CFMMsg16(msg) => CFMMessage::Msg16(*msg),
+            Message::CFMMsg17(msg) => CFMMessage::Msg17(*msg),
+            Message::CFMMsg18(msg) => CFMMessage::Msg18(*msg),
+            _ => return None,
+        };
+        Some(msg)
+    }
+}
+
+impl From<CFMMessage> for Message {
+    fn from(msg: CFMMessage) -> Self {
+        match msg {
+            CFMMessage::Msg1(msg) => Message::CFMMsg1(msg),
+            CFMMessage::Msg2(msg) => Message::CFMMsg2(msg),
+            CFMMessage::Msg3(msg) => Message::CFMMsg3(msg),
+            CFMMessage::Msg4(msg) => Message::CFMMsg4(msg),
+            CFMMessage::Msg5(msg) => Message::CFMMsg5(msg),
+            CFMMessage::Msg6(msg) => Message::CFMMsg6(msg),
+            CFMMessage::Msg7(msg) => Message::CFMMsg7(msg),
+            CFMMessage::Msg8(msg) => Message::CFMMsg8(msg),
+            CFMMessage::Msg9(msg) => Message::CFMMsg9(msg),
+            CFMMessage::Msg10(msg) => Message::CFMMsg10(msg),
+            CFMMessage::Msg11(msg) => Message::CFMMsg11(msg),
+            CFMMessage::Msg12(msg) => Message::CFMMsg12(msg),
+            CFMMessage::Msg13(msg) => Message::CFMMsg13(msg),
+            CFMMessage::Msg14(msg) => Message::CFMMsg14(msg),
+            CFMMessage::Msg15(msg) => Message::CFMMsg15(Box::new(msg)),
+            CFMMessage::Msg16(msg) => Message::CFMMsg16(Box::new(msg)),
+            CFMMessage::Msg17(msg) => Message::CFMMsg17(Box::new(msg)),
+            CFMMessage::Msg18(msg) => Message::CFMMsg18(Box::new(msg)),
+        }
+    }
+}
+
+/// Decoded envelope
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Envelope {
+    /// header
+    pub header: EnvelopeHeader,
+
+    /// message
+    pub message: Message,
+}
+
+/// Encode a message with its envelope header
+pub fn encode(msg: &Message) -> Result<Vec<u8>, EnvelopeError> {
+    let mut bytes = msg.header().to_bytes().to_vec();
+    match msg {
+        Message::CFMInitMsg1(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::CFMInitMsg2(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::CFMInitMsg3(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::ABTMsg1(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::ABTMsg2(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::ABTMsg3(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::ABTMsg4(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::ABTMsg5(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::CompMsg1(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::CompMsg2(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::CompMsg3(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::CompMsg4(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::CompMsg5(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::CompMsg6(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::CompMsg7(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::CompMsg8(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::CompMsg9(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::CFMMsg1(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::CFMMsg2(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::CFMMsg3(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::CFMMsg4(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::CFMMsg5(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::CFMMsg6(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::CFMMsg7(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::CFMMsg8(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::CFMMsg9(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::CFMMsg10(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::CFMMsg11(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::CFMMsg12(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::CFMMsg13(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::CFMMsg14(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::CFMMsg15(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::CFMMsg16(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::CFMMsg17(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::CFMMsg18(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::FairMsg1(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::FairMsg2(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::FairMsg3(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::FairMsg4(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::FairMsg5(msg) => bincode::serialize_into(&mut bytes, msg),
+        Message::FairMsg6(msg) => bincode::serialize_into(&mut bytes, msg),
+    }?;
+
+    Ok(bytes)
+}
+
+/// Read and validate the envelope header without deserializing the message
+pub fn decode_header(bytes: &[u8]) -> Result<EnvelopeHeader, EnvelopeError> {
+    EnvelopeHeader::from_bytes(bytes)
+}
+
+/// Validate the envelope header and deserialize the message it tags
+pub fn decode(bytes: &[u8]) -> Result<Envelope, EnvelopeError> {
+    let header = EnvelopeHeader::from_bytes(bytes)?;
+    let mut payload = &bytes[HEADER_SIZE..];
+    let message = match (header.protocol, header.round) {
+        (ProtocolId::CFMInit, 1) => Message::CFMInitMsg1(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::CFMInit, 2) => Message::CFMInitMsg2(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::CFMInit, 3) => Message::CFMInitMsg3(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::ABT, 1) => Message::ABTMsg1(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::ABT, 2) => Message::ABTMsg2(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::ABT, 3) => Message::ABTMsg3(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::ABT, 4) => Message::ABTMsg4(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::ABT, 5) => Message::ABTMsg5(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::Comparison, 1) => Message::CompMsg1(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::Comparison, 2) => Message::CompMsg2(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::Comparison, 3) => Message::CompMsg3(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::Comparison, 4) => Message::CompMsg4(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::Comparison, 5) => Message::CompMsg5(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::Comparison, 6) => Message::CompMsg6(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::Comparison, 7) => Message::CompMsg7(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::Comparison, 8) => Message::CompMsg8(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::Comparison, 9) => Message::CompMsg9(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::CFM, 1) => Message::CFMMsg1(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::CFM, 2) => Message::CFMMsg2(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::CFM, 3) => Message::CFMMsg3(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::CFM, 4) => Message::CFMMsg4(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::CFM, 5) => Message::CFMMsg5(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::CFM, 6) => Message::CFMMsg6(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::CFM, 7) => Message::CFMMsg7(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::CFM, 8) => Message::CFMMsg8(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::CFM, 9) => Message::CFMMsg9(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::CFM, 10) => Message::CFMMsg10(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::CFM, 11) => Message::CFMMsg11(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::CFM, 12) => Message::CFMMsg12(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::CFM, 13) => Message::CFMMsg13(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::CFM, 14) => Message::CFMMsg14(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::CFM, 15) => Message::CFMMsg15(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::CFM, 16) => Message::CFMMsg16(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::CFM, 17) => Message::CFMMsg17(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::CFM, 18) => Message::CFMMsg18(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::FairRelease, 1) => Message::FairMsg1(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::FairRelease, 2) => Message::FairMsg2(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::FairRelease, 3) => Message::FairMsg3(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::FairRelease, 4) => Message::FairMsg4(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::FairRelease, 5) => Message::FairMsg5(bincode::deserialize_from(&mut payload)?),
+        (ProtocolId::FairRelease, 6) => Message::FairMsg6(bincode::deserialize_from(&mut payload)?),
+        _ => {
+            return Err(EnvelopeError::UnknownRound {
+                protocol: header.protocol,
+                round: header.round,
+            })
+        }
+    };
+    if !payload.is_empty() {
+        return Err(EnvelopeError::InvalidMessage);
+    }
+    if *message.session_id() != header.session_id {
+        return Err(EnvelopeError::InvalidSessionID);
+    }
+
+    Ok(Envelope { header, message })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, decode_header, encode, Message, ProtocolId, Role, HEADER_SIZE};
+    use crate::cfm_init_protocol::{CFMInitMsg1, CFMInitMsg2};
+    use crate::cfm_protocol::CFMMsg18;
+    use crate::errors::EnvelopeError;
+    use crypto_bigint::U128;
+    use rand::Rng;
+
+    #[test]
+    fn test_envelope() {
+        let mut rng = rand::thread_rng();
+        let session_id: [u8; 32] = rng.gen();
+
+        let msg = Message::CFMMsg18(Box::new(CFMMsg18 {
+            session_id,
+            open_b: Some((U128::from_u8(1), U128::from_u8(2))),
+        }));
+        let bytes = encode(&msg).unwrap();
+
+        let header = decode_header(&bytes).unwrap();
+        assert_eq!(header.protocol, ProtocolId::CFM);
+        assert_eq!(header.round, 18);
+        assert_eq!(header.sender, Role::CB);
+        assert_eq!(header.session_id, session_id);
+
+        let envelope = decode(&bytes).unwrap();
+        assert_eq!(envelope.header, header);
+        match envelope.message {
+            Message::CFMMsg18(msg18) => assert_eq!(msg18.open_b.unwrap().1, U128::from_u8(2)),
+            _ => panic!("wrong message type"),
+        }
+
+        let mut init_msg = CFMInitMsg1::default();
+        init_msg.session_id = session_id;
+        let bytes = encode(&Message::CFMInitMsg1(Box::new(init_msg))).unwrap();
+        assert!(matches!(
+            decode(&bytes).unwrap().message,
+            Message::CFMInitMsg1(_)
+        ));
+
+        // tag of CFMInitMsg1 does not describe a CFMInitMsg2 payload
+        let mut init_msg2 = CFMInitMsg2::default();
+        init_msg2.session_id = session_id;
+        let mut forged = encode(&Message::CFMInitMsg2(Box::new(init_msg2))).unwrap();
+        forged[..HEADER_SIZE].copy_from_slice(&bytes[..HEADER_SIZE]);
+        assert!(decode(&forged).is_err());
+
+        let mut bad_version = bytes.clone();
+        bad_version[0] = 2;
+        assert!(matches!(
+            decode(&bad_version),
+            Err(EnvelopeError::UnsupportedVersion(2))
+        ));
+
+        let mut bad_sender = bytes.clone();
+        bad_sender[3] = Role::CB as u8;
+        assert!(matches!(
+            decode(&bad_sender),
+            Err(EnvelopeError::UnexpectedSender { .. })
+        ));
+
+        let mut bad_session = bytes.clone();
+        bad_session[4] ^= 1;
+        assert!(matches!(
+            decode(&bad_session),
+            Err(EnvelopeError::InvalidSessionID)
+        ));
+    }
+}
@@ -1,3 +1,7 @@
+use crate::abort_report::{FailedCheck, Opening, SubProtocol};
+use crate::envelope::{ProtocolId, Role};
+use crate::sl_oblivious::soft_spoken::SoftSpokenOTError;
+
 #[derive(Debug, thiserror::Error)]
 /// PSC BB errors
 pub enum PSCBBError {
@@ -58,6 +62,50 @@ pub enum PSITOBError {
     NotInList,
 }
 
+#[derive(Debug, thiserror::Error)]
+/// Oblivious transfer errors
+pub enum OTError {
+    /// point in an EndemicOT message is not a valid Ristretto encoding
+    #[error("Invalid point in EndemicOT message")]
+    InvalidPoint,
+
+    /// all-but-one PPRF output does not match its proof
+    #[error("Invalid PPRF proof")]
+    InvalidPPRFProof,
+}
+
+#[derive(Debug, thiserror::Error)]
+/// VOLE errors
+pub enum VOLEError {
+    /// RVOLE output of the sender fails the consistency check
+    #[error("RVOLE consistency check failed")]
+    ConsistencyCheck,
+
+    /// opened value does not match its VOLE MAC
+    #[error("Opened value does not match its MAC")]
+    InvalidMAC(Box<Opening>),
+
+    /// OT extension of the RVOLE receiver fails the consistency check
+    #[error(transparent)]
+    SoftSpokenOT(#[from] SoftSpokenOTError),
+}
+
+#[derive(Debug, thiserror::Error)]
+/// CFM init errors, each names the message that failed and so its sender
+pub enum CFMInitError {
+    /// CFMInitMsg1 of OB failed the EndemicOT checks
+    #[error("Invalid CFMInitMsg1 from OB: {0}")]
+    InvalidMsg1(#[source] OTError),
+
+    /// CFMInitMsg2 of CB failed the EndemicOT or PPRF checks
+    #[error("Invalid CFMInitMsg2 from CB: {0}")]
+    InvalidMsg2(#[source] OTError),
+
+    /// CFMInitMsg3 of OB failed the EndemicOT or PPRF checks
+    #[error("Invalid CFMInitMsg3 from OB: {0}")]
+    InvalidMsg3(#[source] OTError),
+}
+
 #[derive(Debug, thiserror::Error)]
 /// ABT CB errors
 pub enum ABTCBError {
@@ -78,8 +126,16 @@ pub enum ABTCBError {
     InvalidOpen,
 
     /// Abort the protocol and ban other party
-    #[error("Abort the protocol and ban other party")]
-    AbortProtocolAndBanOtherParty,
+    #[error("Abort the protocol and ban other party: {0}")]
+    AbortProtocolAndBanOtherParty(#[source] VOLEError),
+
+    /// p is not a prime of the supported size
+    #[error("Invalid modulus")]
+    InvalidModulus,
+
+    /// the frame size cannot hold an ABT frame
+    #[error("Invalid frame size")]
+    InvalidFrameSize,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -106,8 +162,20 @@ pub enum ABTOBError {
     InvalidOpen,
 
     /// Abort the protocol and ban other party
-    #[error("Abort the protocol and ban other party")]
-    AbortProtocolAndBanOtherParty,
+    #[error("Abort the protocol and ban other party: {0}")]
+    AbortProtocolAndBanOtherParty(#[source] VOLEError),
+
+    /// p is not a prime of the supported size
+    #[error("Invalid modulus")]
+    InvalidModulus,
+
+    /// CB runs ABT over a different prime
+    #[error("Modulus mismatch")]
+    ModulusMismatch,
+
+    /// the frame size cannot hold an ABT frame
+    #[error("Invalid frame size")]
+    InvalidFrameSize,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -117,9 +185,9 @@ pub enum CompError {
     #[error("Invalid SessionID")]
     InvalidSessionID,
 
-    /// invalid open
-    #[error("Invalid Open")]
-    InvalidOpen,
+    /// opened value `field[index]` does not match its MAC
+    #[error("Invalid Open of {0}[{1}]: {2}")]
+    InvalidOpen(&'static str, usize, #[source] VOLEError),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -133,19 +201,449 @@ pub enum CFMError {
     #[error("Error while deserializing message")]
     InvalidMessage,
 
-    /// invalid open
-    #[error("Invalid Open")]
-    InvalidOpen,
+    /// opened value does not match its MAC
+    #[error("Invalid Open of {0}: {1}")]
+    InvalidOpen(FailedCheck, #[source] VOLEError),
 
     /// error in PSIT protocol
-    #[error("PSIT Error")]
-    PSITError,
+    #[error("PSIT Error: {0}")]
+    PSITError(#[source] PSITCBError),
+
+    /// opened value has the wrong value
+    #[error("Abort protocol: wrong value of {0}")]
+    AbortProtocol(FailedCheck, Box<Opening>),
+
+    /// error in comparison instance 0 or 1
+    #[error("Comparison {0} error: {1}")]
+    Comparison(u8, #[source] CompError),
+    /// message does not belong to the current round
+    #[error("Unexpected message: expected round {expected}, received round {received}")]
+    UnexpectedMessage {
+        /// round number of the expected message
+        expected: u8,
+        /// round number of the received message
+        received: u8,
+    },
+
+    /// session has already finished or aborted
+    #[error("Session closed")]
+    SessionClosed,
+
+    /// output policies of OB and CB differ, or an opening of [b] does not
+    /// follow the policy
+    #[error("Output policy mismatch")]
+    OutputPolicyMismatch,
+
+    /// OB and CB disagree on whether L is public or private
+    #[error("Limit mode mismatch")]
+    LimitModeMismatch,
+
+    /// shares, triples or inputs of this party do not fit the protocol
+    #[error("Invalid input")]
+    InvalidInput,
+
+    /// p is not a prime of the supported size
+    #[error("Invalid modulus")]
+    InvalidModulus,
+
+    /// error in the VOLE that refreshes the ledger tags and keys
+    #[error("Ledger VOLE error in {0}: {1}")]
+    LedgerVOLE(FailedCheck, #[source] VOLEError),
+}
+
+impl CFMError {
+    /// Opened value a check of a message of the other party failed on, None
+    /// if the error does not point at the other party
+    pub fn failed_check(&self) -> Option<FailedCheck> {
+        match self {
+            CFMError::InvalidOpen(check, _)
+            | CFMError::AbortProtocol(check, _)
+            | CFMError::LedgerVOLE(check, _) => Some(*check),
+            CFMError::Comparison(instance, CompError::InvalidOpen(field, index, _)) => {
+                Some(FailedCheck {
+                    sub_protocol: SubProtocol::Comparison(*instance),
+                    field,
+                    index: *index,
+                })
+            }
+            CFMError::PSITError(PSITCBError::InvalidMessage) => Some(FailedCheck {
+                sub_protocol: SubProtocol::PSIT,
+                field: "psit_msg1",
+                index: 0,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Opening the failed check is about, with the MAC key or share of this
+    /// party that shows the failure
+    pub fn opening(&self) -> Option<Opening> {
+        match self {
+            CFMError::InvalidOpen(_, VOLEError::InvalidMAC(opening))
+            | CFMError::AbortProtocol(_, opening)
+            | CFMError::Comparison(
+                _,
+                CompError::InvalidOpen(_, _, VOLEError::InvalidMAC(opening)),
+            ) => Some(**opening),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+/// Message envelope errors
+pub enum EnvelopeError {
+    /// envelope version is not supported
+    #[error("Unsupported envelope version {0}")]
+    UnsupportedVersion(u8),
+
+    /// unknown protocol id
+    #[error("Unknown protocol id {0}")]
+    UnknownProtocol(u8),
+
+    /// unknown sender role
+    #[error("Unknown sender role {0}")]
+    UnknownRole(u8),
+
+    /// round number does not exist in the protocol
+    #[error("Unknown round {round} for protocol {protocol:?}")]
+    UnknownRound {
+        /// protocol id
+        protocol: ProtocolId,
+        /// round number
+        round: u8,
+    },
+
+    /// message is not sent by the role in the header
+    #[error("Unexpected sender for round {round} of protocol {protocol:?}")]
+    UnexpectedSender {
+        /// protocol id
+        protocol: ProtocolId,
+        /// round number
+        round: u8,
+    },
+
+    /// session id of the header does not match the message
+    #[error("Invalid SessionID")]
+    InvalidSessionID,
+
+    /// error while serializing or deserializing or invalid message data length
+    #[error("Error while deserializing message")]
+    InvalidMessage,
+}
+
+impl From<bincode::Error> for EnvelopeError {
+    fn from(_: bincode::Error) -> Self {
+        EnvelopeError::InvalidMessage
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+/// Triple pool errors
+pub enum TriplePoolError {
+    /// number of shares and triples does not match whole CFM sessions
+    #[error("Invalid number of shares or triples")]
+    InvalidSize,
+
+    /// no sessions left in the pool
+    #[error("Triple pool exhausted")]
+    Exhausted,
+
+    /// session has already been handed out
+    #[error("Session {0} already consumed")]
+    AlreadyConsumed(u64),
+
+    /// pool file version is not supported
+    #[error("Unsupported pool file version {0}")]
+    UnsupportedVersion(u8),
+
+    /// error while encrypting pool file
+    #[error("Error while encrypting pool file")]
+    Encryption,
+
+    /// wrong key or corrupted pool file
+    #[error("Error while decrypting pool file")]
+    Decryption,
+
+    /// error while serializing or deserializing
+    #[error("Error while deserializing pool")]
+    InvalidMessage,
+
+    /// error while reading or writing pool file
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+/// OT seed store errors
+pub enum SeedStoreError {
+    /// seeds are older than the max age of the rotation policy
+    #[error("OT seeds expired, run CFM init again")]
+    Expired,
+
+    /// seeds have been used for the max number of ABT sessions of the policy
+    #[error("OT seeds exhausted, run CFM init again")]
+    Exhausted,
+
+    /// an ABT session already ran on the seeds under this session id
+    #[error("ABT session id already used on these OT seeds")]
+    SessionIdReused,
+}
+
+#[derive(Debug, thiserror::Error)]
+/// Ledger errors
+pub enum LedgerError {
+    /// X + Z_y^OB does not fit into B bits
+    #[error("Amount too large")]
+    AmountTooLarge,
+
+    /// a transfer is still waiting for the result of its CFM session
+    #[error("Transfer pending, settle it first")]
+    PendingTransfer,
+
+    /// there is no transfer to settle
+    #[error("No pending transfer")]
+    NoPendingTransfer,
+}
+
+#[derive(Debug, thiserror::Error)]
+/// Session snapshot errors
+pub enum SnapshotError {
+    /// snapshot format version is not supported
+    #[error("Unsupported snapshot version {0}")]
+    UnsupportedVersion(u8),
+
+    /// error while encrypting snapshot
+    #[error("Error while encrypting snapshot")]
+    Encryption,
+
+    /// wrong key or corrupted snapshot
+    #[error("Error while decrypting snapshot")]
+    Decryption,
+
+    /// error while serializing or deserializing
+    #[error("Error while deserializing snapshot")]
+    InvalidMessage,
+
+    /// error while reading or writing snapshot file
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl From<SnapshotError> for TriplePoolError {
+    fn from(e: SnapshotError) -> Self {
+        match e {
+            SnapshotError::UnsupportedVersion(version) => {
+                TriplePoolError::UnsupportedVersion(version)
+            }
+            SnapshotError::Encryption => TriplePoolError::Encryption,
+            SnapshotError::Decryption => TriplePoolError::Decryption,
+            SnapshotError::InvalidMessage => TriplePoolError::InvalidMessage,
+            SnapshotError::Io(e) => TriplePoolError::Io(e),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+/// Framing errors
+pub enum FrameError {
+    /// length prefix is above `MAX_MESSAGE_SIZE`
+    #[error("Frame of {0} bytes exceeds the maximum message size")]
+    TooLarge(usize),
+
+    /// peer closed the stream before a frame
+    #[error("Connection closed by peer")]
+    Closed,
+
+    /// error while reading or writing the stream
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+/// Party identity and channel binding errors
+pub enum IdentityError {
+    /// verifying key of the other party is not the expected one
+    #[error("Unknown peer identity key")]
+    UnknownPeer,
+
+    /// handshake signature does not verify
+    #[error("Invalid handshake signature")]
+    InvalidSignature,
+
+    /// message MAC does not verify
+    #[error("Invalid message MAC")]
+    InvalidMac,
+
+    /// message is too short or cannot be deserialized
+    #[error("Invalid handshake or channel message")]
+    InvalidMessage,
+}
+
+#[derive(Debug, thiserror::Error)]
+/// Fair release errors
+pub enum FairReleaseError {
+    /// session id of the message is not the one of the session
+    #[error("Invalid session id")]
+    InvalidSessionID,
+
+    /// escrow or acceptance is not signed by the named party
+    #[error("Invalid signature of {0:?}")]
+    InvalidSignature(Role),
+
+    /// revealed opening is not the escrowed one
+    #[error("Reveal does not match the escrow")]
+    InvalidReveal,
+
+    /// escrow of the named party is for another session, has the wrong
+    /// number of pairs or cannot be decrypted under the arbiter key
+    #[error("Invalid escrow of {0:?}")]
+    InvalidEscrow(Role),
+
+    /// opening of b fails the MAC check
+    #[error("Invalid open of b: {0}")]
+    InvalidOpen(#[source] VOLEError),
+}
+
+#[derive(Debug, thiserror::Error)]
+/// Transport errors
+pub enum TransportError {
+    /// no message arrived within the timeout
+    #[error("Timeout while waiting for a message")]
+    Timeout,
+
+    /// peer closed the connection
+    #[error("Connection closed by peer")]
+    Closed,
+
+    /// error in the framing or the underlying stream
+    #[error(transparent)]
+    Frame(FrameError),
+
+    /// message of an authenticated transport fails its MAC
+    #[error(transparent)]
+    Identity(#[from] IdentityError),
+}
+
+impl From<FrameError> for TransportError {
+    fn from(e: FrameError) -> Self {
+        match e {
+            FrameError::Closed => TransportError::Closed,
+            FrameError::Io(e)
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) =>
+            {
+                TransportError::Timeout
+            }
+            e => TransportError::Frame(e),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+/// Errors of the `run_ob` and `run_cb` drivers
+pub enum RunError {
+    /// error while sending or receiving a message
+    #[error(transparent)]
+    Transport(#[from] TransportError),
+
+    /// error in the session or one of its sub-protocols
+    #[error(transparent)]
+    Session(#[from] SessionError),
+
+    /// first frame of OB does not hold the session ids
+    #[error("Invalid session ids frame")]
+    InvalidSessionIDs,
+
+    /// channel binding handshake failed
+    #[error("Handshake error: {0}")]
+    Handshake(#[from] IdentityError),
+
+    /// message exchange ended before CFM produced a result
+    #[error("CFM finished without a result")]
+    Unfinished,
+}
+
+impl From<EnvelopeError> for RunError {
+    fn from(e: EnvelopeError) -> Self {
+        RunError::Session(e.into())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+/// Session handle errors
+pub enum SessionError {
+    /// call is not valid for the role or stage of the session
+    #[error("Invalid session state")]
+    InvalidState,
+
+    /// invalid input parameter
+    #[error("Invalid input")]
+    InvalidInput,
+
+    /// message session id does not match the running sub-protocol
+    #[error("Invalid SessionID")]
+    InvalidSessionID,
+
+    /// message is not expected at the current stage, the session is unchanged
+    #[error("Unexpected message for protocol {protocol:?} round {round}")]
+    UnexpectedMessage {
+        /// protocol id
+        protocol: ProtocolId,
+        /// round number
+        round: u8,
+    },
+
+    /// message repeats a round the session has already accepted, the session
+    /// is unchanged
+    #[error("Duplicate message for protocol {protocol:?} round {round}")]
+    DuplicateMessage {
+        /// protocol id
+        protocol: ProtocolId,
+        /// round number
+        round: u8,
+    },
+
+    /// session has aborted on an earlier error
+    #[error("Session aborted")]
+    Aborted,
+
+    /// signature of the other party over the transcript hash after its
+    /// message does not verify, the session is unchanged
+    #[error("Invalid message signature")]
+    InvalidSignature,
+
+    /// error in the message envelope
+    #[error(transparent)]
+    Envelope(#[from] EnvelopeError),
+
+    /// error in the CFM init protocol
+    #[error(transparent)]
+    CFMInit(#[from] CFMInitError),
+
+    /// OT seeds may not be used for another ABT session
+    #[error(transparent)]
+    SeedStore(#[from] SeedStoreError),
+
+    /// error in the ABT protocol on OB side
+    #[error(transparent)]
+    ABTOB(#[from] ABTOBError),
+
+    /// error in the ABT protocol on CB side
+    #[error(transparent)]
+    ABTCB(#[from] ABTCBError),
+
+    /// error in the CFM protocol
+    #[error(transparent)]
+    CFM(#[from] CFMError),
 
-    /// abort protocol
-    #[error("Abort protocol")]
-    AbortProtocol,
+    /// error in the fair release of b
+    #[error(transparent)]
+    FairRelease(#[from] FairReleaseError),
 
-    /// Comparison error
-    #[error("Comparison error")]
-    Comparison,
+    /// error in the ledger of OB
+    #[error(transparent)]
+    Ledger(#[from] LedgerError),
 }
@@ -0,0 +1,1149 @@
+//! Fair release of the CFM result bit.
+//!
+//! In the final rounds of CFM the party that opens `[b]` first gives the other
+//! party the result, which can then withhold its own opening. The fair release
+//! replaces these rounds: CFM runs with `OutputPolicy::Shared` and the parties
+//! exchange the openings of their shares of b in six messages:
+//!
+//! 1. OB -> CB: OB's signed escrow
+//! 2. CB -> OB: CB's signed escrow and its challenge for the escrow of OB
+//! 3. OB -> CB: OB's challenged openings and its challenge for CB
+//! 4. CB -> OB: CB's challenged openings and its acceptance of both escrows
+//! 5. OB -> CB: OB's acceptance of both escrows and its opening of [b]
+//! 6. CB -> OB: CB's opening of [b]
+//!
+//! An escrow is verifiable by the other party. Each party takes
+//! `FAIR_RELEASE_PAIRS` authenticated random shares [ρ_i] of the ABT run of
+//! the session and encrypts, for each i, its openings of [ρ_i] and of
+//! [b] + [ρ_i] to the public key of an arbiter both banks trust. The escrow is
+//! signed together with the session id and the CFM transcript hash. The other
+//! party picks one opening of each pair, which is revealed with its
+//! encryption nonce and checked against the escrow and the MAC keys of the
+//! other party. A single opening is ρ_i or b + ρ_i masked, and a party that
+//! escrows a wrong opening in every pair is caught except with probability
+//! 2^-FAIR_RELEASE_PAIRS. The same masks serve both escrows, each party opens
+//! one half of each pair and neither learns both.
+//!
+//! Neither party learns b before both escrows are accepted, and an accepted
+//! escrow holds a valid pair. The two escrows with both acceptances,
+//! [`FairEvidence`], are what the arbiter needs, see [`arbiter_resolve`]. OB
+//! holds them once message 4 arrives, CB only once message 5 arrives, so OB
+//! could stop after message 4 and go to the arbiter alone. The [`Arbiter`]
+//! therefore keeps the resolution of a session for both parties, whichever
+//! brought the evidence: CB fetches it with [`Arbiter::resolution`] and learns
+//! b from the pairs of OB, as OB does from those of CB.
+
+use crate::auth_beaver_triples::Share;
+use crate::constants::{FAIR_RELEASE_LABEL, FAIR_RELEASE_PAIRS};
+use crate::envelope::Role;
+use crate::errors::FairReleaseError;
+use crate::identity::PartyIdentity;
+use crate::proto::{
+    decode_point, decode_scalar, encode_point, encode_scalar, PointBytes, ScalarBytes,
+};
+use crate::schnorr::{Signature, SigningKey, VerifyingKey};
+use crypto_bigint::modular::runtime_mod::{DynResidue, DynResidueParams};
+use crypto_bigint::{Encoding, U128};
+use curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
+use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::scalar::Scalar;
+use merlin::Transcript;
+use rand_core::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Size of a challenge, one bit for each escrowed pair
+pub const CHALLENGE_SIZE: usize = FAIR_RELEASE_PAIRS / 8;
+
+/// Opening of a share encrypted to the arbiter: R = r·G and the opening
+/// xored with a key stream derived from r·A
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Escrow {
+    /// ephemeral point R
+    pub big_r: PointBytes,
+    /// encrypted opening (value, MAC)
+    pub ciphertext: [u8; 32],
+}
+
+/// Escrowed pairs of openings of [ρ_i] and [b] + [ρ_i], signed by the party
+/// that made them
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedEscrow {
+    /// session id of the CFM session
+    pub session_id: [u8; 32],
+    /// transcript hash of the CFM session
+    pub transcript_hash: [u8; 32],
+    /// role of the signer
+    pub role: Role,
+    /// escrowed openings of [ρ_i] and [b] + [ρ_i]
+    pub pairs: Vec<[Escrow; 2]>,
+    /// signature of the signer over the escrow digest
+    pub signature: Signature,
+}
+
+/// Opening of one half of an escrowed pair with its nonce r
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct HalfOpening {
+    /// Open([ρ_i]) or Open([b] + [ρ_i]) of the sender
+    pub open: (U128, U128),
+    /// escrow nonce r
+    pub nonce: ScalarBytes,
+}
+
+/// Escrows of both parties with the acceptance of each party, signed over
+/// both escrows. All the arbiter needs to resolve the release.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FairEvidence {
+    /// escrow of OB
+    pub ob_escrow: SignedEscrow,
+    /// escrow of CB
+    pub cb_escrow: SignedEscrow,
+    /// acceptance of OB
+    pub ob_acceptance: Signature,
+    /// acceptance of CB
+    pub cb_acceptance: Signature,
+}
+
+/// Pairs of both parties, decrypted by the arbiter
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Resolution {
+    /// pairs of OB, for CB
+    pub ob_pairs: Vec<[(U128, U128); 2]>,
+    /// pairs of CB, for OB
+    pub cb_pairs: Vec<[(U128, U128); 2]>,
+}
+
+/// FairMsg1, OB -> CB
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FairMsg1 {
+    /// escrow of OB
+    pub escrow: SignedEscrow,
+}
+
+/// FairMsg2, CB -> OB
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FairMsg2 {
+    /// escrow of CB
+    pub escrow: SignedEscrow,
+    /// halves of the pairs of OB to open
+    pub challenge: [u8; CHALLENGE_SIZE],
+}
+
+/// FairMsg3, OB -> CB
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FairMsg3 {
+    /// session id
+    pub session_id: [u8; 32],
+    /// challenged halves of the pairs of OB
+    pub openings: Vec<HalfOpening>,
+    /// halves of the pairs of CB to open
+    pub challenge: [u8; CHALLENGE_SIZE],
+}
+
+/// FairMsg4, CB -> OB
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FairMsg4 {
+    /// session id
+    pub session_id: [u8; 32],
+    /// challenged halves of the pairs of CB
+    pub openings: Vec<HalfOpening>,
+    /// acceptance of CB
+    pub acceptance: Signature,
+}
+
+/// FairMsg5, OB -> CB
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FairMsg5 {
+    /// session id
+    pub session_id: [u8; 32],
+    /// acceptance of OB
+    pub acceptance: Signature,
+    /// Open([b]) of OB
+    pub open_b: (U128, U128),
+}
+
+/// FairMsg6, CB -> OB
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FairMsg6 {
+    /// session id
+    pub session_id: [u8; 32],
+    /// Open([b]) of CB
+    pub open_b: (U128, U128),
+}
+
+/// Shares of a party in the fair release
+#[derive(Clone, Serialize, Deserialize)]
+struct FairShares {
+    p: U128,
+    arbiter: VerifyingKey,
+    b_share: Share,
+    masks: Vec<Share>,
+}
+
+/// State of OB after FairMsg1
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FairStateOBR1 {
+    shares: FairShares,
+    escrow: SignedEscrow,
+    openings: Vec<[HalfOpening; 2]>,
+}
+
+/// State of OB after FairMsg3
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FairStateOBR2 {
+    shares: FairShares,
+    escrow: SignedEscrow,
+    peer_escrow: SignedEscrow,
+    challenge: [u8; CHALLENGE_SIZE],
+}
+
+/// State of OB after FairMsg5
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FairStateOBR3 {
+    shares: FairShares,
+    evidence: FairEvidence,
+}
+
+/// State of CB after FairMsg2
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FairStateCBR1 {
+    shares: FairShares,
+    escrow: SignedEscrow,
+    peer_escrow: SignedEscrow,
+    openings: Vec<[HalfOpening; 2]>,
+    challenge: [u8; CHALLENGE_SIZE],
+}
+
+/// State of CB after FairMsg4
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FairStateCBR2 {
+    shares: FairShares,
+    escrow: SignedEscrow,
+    peer_escrow: SignedEscrow,
+    acceptance: Signature,
+}
+
+impl SignedEscrow {
+    /// Digest of the escrow, the signed message
+    pub fn digest(&self) -> [u8; 32] {
+        escrow_digest(
+            &self.session_id,
+            &self.transcript_hash,
+            self.role,
+            &self.pairs,
+        )
+    }
+
+    /// True if the escrow is signed by the holder of `key`
+    pub fn verify(&self, key: &VerifyingKey) -> bool {
+        key.verify(&self.digest(), &self.signature)
+    }
+}
+
+impl FairEvidence {
+    /// Digest of both escrows, the message each party signs to accept them
+    pub fn acceptance_digest(&self) -> [u8; 32] {
+        acceptance_digest(&self.ob_escrow, &self.cb_escrow)
+    }
+}
+
+impl FairStateOBR3 {
+    /// Evidence for the arbiter if CB withholds FairMsg6
+    pub fn evidence(&self) -> &FairEvidence {
+        &self.evidence
+    }
+
+    /// Result bit from the pairs of CB in the arbiter's resolution
+    pub fn resolve(&self, resolution: &Resolution) -> Result<bool, FairReleaseError> {
+        self.shares.resolve(&resolution.cb_pairs, Role::CB)
+    }
+}
+
+impl FairStateCBR2 {
+    /// Evidence for the arbiter, once FairMsg5 brings the acceptance of OB
+    pub fn evidence(
+        &self,
+        msg5: &FairMsg5,
+        peer_key: &VerifyingKey,
+    ) -> Result<FairEvidence, FairReleaseError> {
+        if msg5.session_id != self.escrow.session_id {
+            return Err(FairReleaseError::InvalidSessionID);
+        }
+        let evidence = FairEvidence {
+            ob_escrow: self.peer_escrow.clone(),
+            cb_escrow: self.escrow.clone(),
+            ob_acceptance: msg5.acceptance,
+            cb_acceptance: self.acceptance,
+        };
+        if !peer_key.verify(&evidence.acceptance_digest(), &evidence.ob_acceptance) {
+            return Err(FairReleaseError::InvalidSignature(Role::OB));
+        }
+        Ok(evidence)
+    }
+
+    /// Result bit from the pairs of OB in the arbiter's resolution
+    pub fn resolve(&self, resolution: &Resolution) -> Result<bool, FairReleaseError> {
+        self.shares.resolve(&resolution.ob_pairs, Role::OB)
+    }
+}
+
+impl FairShares {
+    fn params(&self) -> DynResidueParams<2> {
+        DynResidueParams::new(&self.p)
+    }
+
+    /// Own shares of [ρ_i] and [b] + [ρ_i]
+    fn pair(&self, i: usize) -> [Share; 2] {
+        let mask = self.masks[i];
+        [mask, self.b_share.add_share(&mask, self.params())]
+    }
+
+    /// Result bit from the own share of b and the opening of the other party
+    fn output_b(&self, open_b: &(U128, U128)) -> Result<bool, FairReleaseError> {
+        let b_value = self
+            .b_share
+            .validate_open(&open_b.0, &open_b.1, self.params())
+            .map_err(FairReleaseError::InvalidOpen)?;
+        Ok(b_value == U128::ONE)
+    }
+
+    /// Result bit from the first pair of `peer` whose openings both pass the
+    /// MAC check, b = (b + ρ_i) - ρ_i
+    fn resolve(&self, pairs: &[[(U128, U128); 2]], peer: Role) -> Result<bool, FairReleaseError> {
+        if pairs.len() != FAIR_RELEASE_PAIRS {
+            return Err(FairReleaseError::InvalidEscrow(peer));
+        }
+        let params = self.params();
+        for (i, [open_mask, open_masked_b]) in pairs.iter().enumerate() {
+            let [mask, masked_b] = self.pair(i);
+            let mask = mask.validate_open(&open_mask.0, &open_mask.1, params);
+            let masked_b = masked_b.validate_open(&open_masked_b.0, &open_masked_b.1, params);
+            if let (Ok(mask), Ok(masked_b)) = (mask, masked_b) {
+                let b = DynResidue::new(&masked_b, params).sub(&DynResidue::new(&mask, params));
+                return Ok(b.retrieve() == U128::ONE);
+            }
+        }
+        Err(FairReleaseError::InvalidEscrow(peer))
+    }
+}
+
+fn escrow_digest(
+    session_id: &[u8; 32],
+    transcript_hash: &[u8; 32],
+    role: Role,
+    pairs: &[[Escrow; 2]],
+) -> [u8; 32] {
+    let mut t = Transcript::new(FAIR_RELEASE_LABEL.as_ref());
+    t.append_message(b"session-id", session_id);
+    t.append_message(b"transcript-hash", transcript_hash);
+    t.append_u64(b"role", role as u64);
+    for escrow in pairs.iter().flatten() {
+        t.append_message(b"big-r", &escrow.big_r);
+        t.append_message(b"ciphertext", &escrow.ciphertext);
+    }
+
+    let mut digest = [0u8; 32];
+    t.challenge_bytes(b"escrow-digest", &mut digest);
+    digest
+}
+
+fn acceptance_digest(ob_escrow: &SignedEscrow, cb_escrow: &SignedEscrow) -> [u8; 32] {
+    let mut t = Transcript::new(FAIR_RELEASE_LABEL.as_ref());
+    t.append_message(b"ob-escrow", &ob_escrow.digest());
+    t.append_message(b"cb-escrow", &cb_escrow.digest());
+
+    let mut digest = [0u8; 32];
+    t.challenge_bytes(b"acceptance-digest", &mut digest);
+    digest
+}
+
+/// Key stream for half `half` of pair `index` of `role`, from the shared
+/// point r·A = a·R
+fn key_stream(
+    shared: &RistrettoPoint,
+    big_r: &PointBytes,
+    session_id: &[u8; 32],
+    role: Role,
+    index: usize,
+    half: usize,
+) -> [u8; 32] {
+    let mut t = Transcript::new(FAIR_RELEASE_LABEL.as_ref());
+    t.append_message(b"shared-point", shared.compress().as_bytes());
+    t.append_message(b"big-r", big_r);
+    t.append_message(b"session-id", session_id);
+    t.append_u64(b"role", role as u64);
+    t.append_u64(b"index", index as u64);
+    t.append_u64(b"half", half as u64);
+
+    let mut stream = [0u8; 32];
+    t.challenge_bytes(b"key-stream", &mut stream);
+    stream
+}
+
+fn xor_opening(bytes: &[u8; 32], stream: &[u8; 32]) -> [u8; 32] {
+    let mut bytes = *bytes;
+    for (b, s) in bytes.iter_mut().zip(stream) {
+        *b ^= s;
+    }
+    bytes
+}
+
+fn opening_bytes(open: &(U128, U128)) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    bytes[..16].copy_from_slice(&open.0.to_be_bytes());
+    bytes[16..].copy_from_slice(&open.1.to_be_bytes());
+    bytes
+}
+
+/// Encryption of `open` under the arbiter key with nonce r
+fn encrypt(
+    arbiter: &VerifyingKey,
+    session_id: &[u8; 32],
+    role: Role,
+    (index, half): (usize, usize),
+    open: &(U128, U128),
+    nonce: &Scalar,
+) -> Result<Escrow, FairReleaseError> {
+    // the arbiter key is an input of this party, not of the other
+    let big_a = decode_point(&arbiter.0).ok_or(FairReleaseError::InvalidEscrow(role))?;
+    let big_r = encode_point(&(RISTRETTO_BASEPOINT_POINT * nonce));
+    let stream = key_stream(&(big_a * nonce), &big_r, session_id, role, index, half);
+
+    Ok(Escrow {
+        big_r,
+        ciphertext: xor_opening(&opening_bytes(open), &stream),
+    })
+}
+
+/// Pairs in `escrow`, decrypted with the arbiter key a
+fn decrypt(
+    arbiter: &SigningKey,
+    escrow: &SignedEscrow,
+) -> Result<Vec<[(U128, U128); 2]>, FairReleaseError> {
+    let decrypt_half = |index: usize, half: usize| {
+        let encrypted = &escrow.pairs[index][half];
+        let big_r =
+            decode_point(&encrypted.big_r).ok_or(FairReleaseError::InvalidEscrow(escrow.role))?;
+        let shared = arbiter.diffie_hellman(&big_r);
+        let stream = key_stream(
+            &shared,
+            &encrypted.big_r,
+            &escrow.session_id,
+            escrow.role,
+            index,
+            half,
+        );
+        let bytes = xor_opening(&encrypted.ciphertext, &stream);
+        Ok((
+            U128::from_be_slice(&bytes[..16]),
+            U128::from_be_slice(&bytes[16..]),
+        ))
+    };
+
+    (0..escrow.pairs.len())
+        .map(|i| Ok([decrypt_half(i, 0)?, decrypt_half(i, 1)?]))
+        .collect()
+}
+
+/// Half of pair `i` the challenge asks for
+fn challenged(challenge: &[u8; CHALLENGE_SIZE], i: usize) -> usize {
+    ((challenge[i / 8] >> (i % 8)) & 1) as usize
+}
+
+/// Signed escrow of the pairs of this party and the openings behind it
+fn make_escrow<R: CryptoRng + RngCore>(
+    session_id: &[u8; 32],
+    transcript_hash: &[u8; 32],
+    role: Role,
+    shares: &FairShares,
+    key: &SigningKey,
+    rng: &mut R,
+) -> Result<(SignedEscrow, Vec<[HalfOpening; 2]>), FairReleaseError> {
+    if shares.masks.len() != FAIR_RELEASE_PAIRS {
+        return Err(FairReleaseError::InvalidEscrow(role));
+    }
+
+    let mut pairs = Vec::with_capacity(FAIR_RELEASE_PAIRS);
+    let mut openings = Vec::with_capacity(FAIR_RELEASE_PAIRS);
+    for i in 0..FAIR_RELEASE_PAIRS {
+        let [mask, masked_b] = shares.pair(i).map(|share| share.open());
+        let nonces = [Scalar::random(rng), Scalar::random(rng)];
+        pairs.push([
+            encrypt(&shares.arbiter, session_id, role, (i, 0), &mask, &nonces[0])?,
+            encrypt(
+                &shares.arbiter,
+                session_id,
+                role,
+                (i, 1),
+                &masked_b,
+                &nonces[1],
+            )?,
+        ]);
+        openings.push([
+            HalfOpening {
+                open: mask,
+                nonce: encode_scalar(&nonces[0]),
+            },
+            HalfOpening {
+                open: masked_b,
+                nonce: encode_scalar(&nonces[1]),
+            },
+        ]);
+    }
+
+    let digest = escrow_digest(session_id, transcript_hash, role, &pairs);
+    let escrow = SignedEscrow {
+        session_id: *session_id,
+        transcript_hash: *transcript_hash,
+        role,
+        pairs,
+        signature: key.sign(&digest, rng),
+    };
+    Ok((escrow, openings))
+}
+
+/// Checks that `escrow` is the one of the other party in this session
+fn check_escrow(
+    escrow: &SignedEscrow,
+    own: &SignedEscrow,
+    role: Role,
+    key: &VerifyingKey,
+) -> Result<(), FairReleaseError> {
+    if escrow.session_id != own.session_id {
+        return Err(FairReleaseError::InvalidSessionID);
+    }
+    if escrow.role != role || escrow.pairs.len() != FAIR_RELEASE_PAIRS {
+        return Err(FairReleaseError::InvalidEscrow(role));
+    }
+    if escrow.transcript_hash != own.transcript_hash || !escrow.verify(key) {
+        return Err(FairReleaseError::InvalidSignature(role));
+    }
+    Ok(())
+}
+
+/// Checks that the challenged `openings` re-encrypt to `escrow` and pass the
+/// MAC check against the own shares of [ρ_i] and [b] + [ρ_i]
+fn check_openings(
+    openings: &[HalfOpening],
+    escrow: &SignedEscrow,
+    challenge: &[u8; CHALLENGE_SIZE],
+    shares: &FairShares,
+) -> Result<(), FairReleaseError> {
+    if openings.len() != FAIR_RELEASE_PAIRS {
+        return Err(FairReleaseError::InvalidReveal);
+    }
+    let params = shares.params();
+    for (i, opening) in openings.iter().enumerate() {
+        let half = challenged(challenge, i);
+        let nonce = decode_scalar(&opening.nonce).ok_or(FairReleaseError::InvalidReveal)?;
+        let expected = encrypt(
+            &shares.arbiter,
+            &escrow.session_id,
+            escrow.role,
+            (i, half),
+            &opening.open,
+            &nonce,
+        )?;
+        if expected != escrow.pairs[i][half] {
+            return Err(FairReleaseError::InvalidReveal);
+        }
+        shares.pair(i)[half]
+            .validate_open(&opening.open.0, &opening.open.1, params)
+            .map_err(FairReleaseError::InvalidOpen)?;
+    }
+    Ok(())
+}
+
+fn random_challenge<R: CryptoRng + RngCore>(rng: &mut R) -> [u8; CHALLENGE_SIZE] {
+    let mut challenge = [0u8; CHALLENGE_SIZE];
+    rng.fill_bytes(&mut challenge);
+    challenge
+}
+
+fn open_challenged(
+    openings: &[[HalfOpening; 2]],
+    challenge: &[u8; CHALLENGE_SIZE],
+) -> Vec<HalfOpening> {
+    openings
+        .iter()
+        .enumerate()
+        .map(|(i, pair)| pair[challenged(challenge, i)])
+        .collect()
+}
+
+/// OB escrows its pairs for `b_share`, its output of CFM under
+/// `OutputPolicy::Shared`, masked by `masks`, FAIR_RELEASE_PAIRS unused
+/// shares of the same ABT run
+#[allow(clippy::too_many_arguments)]
+pub fn fair_create_msg1<R: CryptoRng + RngCore>(
+    session_id: &[u8; 32],
+    transcript_hash: &[u8; 32],
+    p: U128,
+    b_share: &Share,
+    masks: Vec<Share>,
+    identity: &PartyIdentity,
+    arbiter: &VerifyingKey,
+    rng: &mut R,
+) -> Result<(FairStateOBR1, FairMsg1), FairReleaseError> {
+    let shares = FairShares {
+        p,
+        arbiter: *arbiter,
+        b_share: *b_share,
+        masks,
+    };
+    let (escrow, openings) = make_escrow(
+        session_id,
+        transcript_hash,
+        Role::OB,
+        &shares,
+        &identity.key,
+        rng,
+    )?;
+
+    let msg1 = FairMsg1 {
+        escrow: escrow.clone(),
+    };
+    let state = FairStateOBR1 {
+        shares,
+        escrow,
+        openings,
+    };
+    Ok((state, msg1))
+}
+
+/// CB checks the escrow of OB, escrows its own pairs and challenges OB
+#[allow(clippy::too_many_arguments)]
+pub fn fair_process_msg1<R: CryptoRng + RngCore>(
+    session_id: &[u8; 32],
+    transcript_hash: &[u8; 32],
+    p: U128,
+    b_share: &Share,
+    masks: Vec<Share>,
+    identity: &PartyIdentity,
+    arbiter: &VerifyingKey,
+    msg1: &FairMsg1,
+    rng: &mut R,
+) -> Result<(FairStateCBR1, FairMsg2), FairReleaseError> {
+    let shares = FairShares {
+        p,
+        arbiter: *arbiter,
+        b_share: *b_share,
+        masks,
+    };
+    let (escrow, openings) = make_escrow(
+        session_id,
+        transcript_hash,
+        Role::CB,
+        &shares,
+        &identity.key,
+        rng,
+    )?;
+    check_escrow(&msg1.escrow, &escrow, Role::OB, &identity.peer_key)?;
+
+    let msg2 = FairMsg2 {
+        escrow: escrow.clone(),
+        challenge: random_challenge(rng),
+    };
+    let state = FairStateCBR1 {
+        shares,
+        escrow,
+        peer_escrow: msg1.escrow.clone(),
+        openings,
+        challenge: msg2.challenge,
+    };
+    Ok((state, msg2))
+}
+
+/// OB checks the escrow of CB, opens the challenged halves of its pairs and
+/// challenges CB
+pub fn fair_process_msg2<R: CryptoRng + RngCore>(
+    state: FairStateOBR1,
+    identity: &PartyIdentity,
+    msg2: &FairMsg2,
+    rng: &mut R,
+) -> Result<(FairStateOBR2, FairMsg3), FairReleaseError> {
+    check_escrow(&msg2.escrow, &state.escrow, Role::CB, &identity.peer_key)?;
+
+    let msg3 = FairMsg3 {
+        session_id: state.escrow.session_id,
+        openings: open_challenged(&state.openings, &msg2.challenge),
+        challenge: random_challenge(rng),
+    };
+    let state = FairStateOBR2 {
+        shares: state.shares,
+        escrow: state.escrow,
+        peer_escrow: msg2.escrow.clone(),
+        challenge: msg3.challenge,
+    };
+    Ok((state, msg3))
+}
+
+/// CB checks the challenged halves of OB, opens its own and accepts both
+/// escrows
+pub fn fair_process_msg3<R: CryptoRng + RngCore>(
+    state: FairStateCBR1,
+    identity: &PartyIdentity,
+    msg3: &FairMsg3,
+    rng: &mut R,
+) -> Result<(FairStateCBR2, FairMsg4), FairReleaseError> {
+    if msg3.session_id != state.escrow.session_id {
+        return Err(FairReleaseError::InvalidSessionID);
+    }
+    check_openings(
+        &msg3.openings,
+        &state.peer_escrow,
+        &state.challenge,
+        &state.shares,
+    )?;
+
+    let digest = acceptance_digest(&state.peer_escrow, &state.escrow);
+    let msg4 = FairMsg4 {
+        session_id: state.escrow.session_id,
+        openings: open_challenged(&state.openings, &msg3.challenge),
+        acceptance: identity.key.sign(&digest, rng),
+    };
+    let state = FairStateCBR2 {
+        shares: state.shares,
+        escrow: state.escrow,
+        peer_escrow: state.peer_escrow,
+        acceptance: msg4.acceptance,
+    };
+    Ok((state, msg4))
+}
+
+/// OB checks the challenged halves and the acceptance of CB, accepts both
+/// escrows and opens its share of b
+pub fn fair_process_msg4<R: CryptoRng + RngCore>(
+    state: FairStateOBR2,
+    identity: &PartyIdentity,
+    msg4: &FairMsg4,
+    rng: &mut R,
+) -> Result<(FairStateOBR3, FairMsg5), FairReleaseError> {
+    if msg4.session_id != state.escrow.session_id {
+        return Err(FairReleaseError::InvalidSessionID);
+    }
+    check_openings(
+        &msg4.openings,
+        &state.peer_escrow,
+        &state.challenge,
+        &state.shares,
+    )?;
+    let digest = acceptance_digest(&state.escrow, &state.peer_escrow);
+    if !identity.peer_key.verify(&digest, &msg4.acceptance) {
+        return Err(FairReleaseError::InvalidSignature(Role::CB));
+    }
+
+    let msg5 = FairMsg5 {
+        session_id: state.escrow.session_id,
+        acceptance: identity.key.sign(&digest, rng),
+        open_b: state.shares.b_share.open(),
+    };
+    let state = FairStateOBR3 {
+        shares: state.shares,
+        evidence: FairEvidence {
+            ob_escrow: state.escrow,
+            cb_escrow: state.peer_escrow,
+            ob_acceptance: msg5.acceptance,
+            cb_acceptance: msg4.acceptance,
+        },
+    };
+    Ok((state, msg5))
+}
+
+/// CB checks the acceptance and the opening of OB, outputs b and opens its
+/// own share. If the opening fails, [`FairStateCBR2::evidence`] is what CB
+/// takes to the arbiter.
+pub fn fair_process_msg5(
+    state: &FairStateCBR2,
+    identity: &PartyIdentity,
+    msg5: &FairMsg5,
+) -> Result<(bool, FairMsg6), FairReleaseError> {
+    state.evidence(msg5, &identity.peer_key)?;
+    let b = state.shares.output_b(&msg5.open_b)?;
+
+    let msg6 = FairMsg6 {
+        session_id: state.escrow.session_id,
+        open_b: state.shares.b_share.open(),
+    };
+    Ok((b, msg6))
+}
+
+/// OB checks the opening of CB and outputs b. If it fails,
+/// [`FairStateOBR3::evidence`] is what OB takes to the arbiter.
+pub fn fair_process_msg6(state: &FairStateOBR3, msg6: &FairMsg6) -> Result<bool, FairReleaseError> {
+    if msg6.session_id != state.evidence.ob_escrow.session_id {
+        return Err(FairReleaseError::InvalidSessionID);
+    }
+    state.shares.output_b(&msg6.open_b)
+}
+
+/// Checks that `escrow` is signed by `role` for `session_id`
+fn check_signed_escrow(
+    escrow: &SignedEscrow,
+    session_id: &[u8; 32],
+    role: Role,
+    key: &VerifyingKey,
+) -> Result<(), FairReleaseError> {
+    if escrow.session_id != *session_id
+        || escrow.role != role
+        || escrow.pairs.len() != FAIR_RELEASE_PAIRS
+    {
+        return Err(FairReleaseError::InvalidEscrow(role));
+    }
+    if !escrow.verify(key) {
+        return Err(FairReleaseError::InvalidSignature(role));
+    }
+    Ok(())
+}
+
+/// The arbiter checks that both escrows of `evidence` are signed by OB and
+/// CB for `session_id` and accepted by both, and decrypts the pairs of both
+/// parties. Each party gets the pairs of the other, so the resolution goes to
+/// both of them, see [`Arbiter`]. An error names the party whose escrow or
+/// acceptance fails.
+pub fn arbiter_resolve(
+    arbiter: &SigningKey,
+    session_id: &[u8; 32],
+    ob_key: &VerifyingKey,
+    cb_key: &VerifyingKey,
+    evidence: &FairEvidence,
+) -> Result<Resolution, FairReleaseError> {
+    check_signed_escrow(&evidence.ob_escrow, session_id, Role::OB, ob_key)?;
+    check_signed_escrow(&evidence.cb_escrow, session_id, Role::CB, cb_key)?;
+    // each acceptance certifies the checked pairs and the transcript hash
+    // of the other escrow
+    let digest = evidence.acceptance_digest();
+    if !ob_key.verify(&digest, &evidence.ob_acceptance) {
+        return Err(FairReleaseError::InvalidSignature(Role::OB));
+    }
+    if !cb_key.verify(&digest, &evidence.cb_acceptance) {
+        return Err(FairReleaseError::InvalidSignature(Role::CB));
+    }
+
+    Ok(Resolution {
+        ob_pairs: decrypt(arbiter, &evidence.ob_escrow)?,
+        cb_pairs: decrypt(arbiter, &evidence.cb_escrow)?,
+    })
+}
+
+/// Arbiter of the fair release, which resolves each session once and keeps
+/// the resolution for both parties
+pub struct Arbiter {
+    key: SigningKey,
+    resolutions: BTreeMap<[u8; 32], Resolution>,
+}
+
+impl Arbiter {
+    /// Arbiter with the signing key whose verifying key the parties escrow to
+    pub fn new(key: SigningKey) -> Self {
+        Arbiter {
+            key,
+            resolutions: BTreeMap::new(),
+        }
+    }
+
+    /// Verifying key the parties escrow to
+    pub fn verifying_key(&self) -> VerifyingKey {
+        self.key.verifying_key()
+    }
+
+    /// Resolve session `session_id` on the evidence of either party, see
+    /// [`arbiter_resolve`]. The resolution is also kept for the other party.
+    pub fn resolve(
+        &mut self,
+        session_id: &[u8; 32],
+        ob_key: &VerifyingKey,
+        cb_key: &VerifyingKey,
+        evidence: &FairEvidence,
+    ) -> Result<Resolution, FairReleaseError> {
+        let resolution = arbiter_resolve(&self.key, session_id, ob_key, cb_key, evidence)?;
+        self.resolutions.insert(*session_id, resolution.clone());
+        Ok(resolution)
+    }
+
+    /// Resolution of session `session_id`, for the party that did not bring
+    /// the evidence. It reveals b, so the caller hands it only to OB or CB,
+    /// over a channel authenticated with their identity keys.
+    pub fn resolution(&self, session_id: &[u8; 32]) -> Option<&Resolution> {
+        self.resolutions.get(session_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::tests::identities;
+    use crate::P;
+    use rand::Rng;
+
+    /// Authenticated shares of OB and CB of the bit `b` and of
+    /// FAIR_RELEASE_PAIRS random masks, under one pair of MAC keys
+    fn b_shares(
+        b: bool,
+        rng: &mut rand::rngs::ThreadRng,
+    ) -> ((Share, Vec<Share>), (Share, Vec<Share>)) {
+        let params = DynResidueParams::new(&P);
+        let random =
+            |rng: &mut rand::rngs::ThreadRng| DynResidue::new(&U128::from_u64(rng.gen()), params);
+        let (delta_ob, delta_cb) = (random(rng), random(rng));
+
+        // M = x·Delta - delta under the keys of the other party
+        let share = |value: DynResidue<2>, rng: &mut rand::rngs::ThreadRng| {
+            let (key_ob, key_cb) = (random(rng), random(rng));
+            let value_ob = random(rng);
+            let value_cb = value.sub(&value_ob);
+            let ob = Share {
+                value: value_ob.retrieve(),
+                big_m: value_ob.mul(&delta_cb).sub(&key_cb).retrieve(),
+                big_delta: delta_ob.retrieve(),
+                delta: key_ob.retrieve(),
+            };
+            let cb = Share {
+                value: value_cb.retrieve(),
+                big_m: value_cb.mul(&delta_ob).sub(&key_ob).retrieve(),
+                big_delta: delta_cb.retrieve(),
+                delta: key_cb.retrieve(),
+            };
+            (ob, cb)
+        };
+
+        let (ob, cb) = share(DynResidue::new(&U128::from_u8(b as u8), params), rng);
+        let (ob_masks, cb_masks) = (0..FAIR_RELEASE_PAIRS)
+            .map(|_| {
+                let mask = random(rng);
+                share(mask, rng)
+            })
+            .unzip();
+        ((ob, ob_masks), (cb, cb_masks))
+    }
+
+    #[test]
+    fn test_fair_release() {
+        let mut rng = rand::thread_rng();
+        let (ob_identity, cb_identity) = identities(&mut rng);
+        let (ob_key, cb_key) = (
+            ob_identity.key.verifying_key(),
+            cb_identity.key.verifying_key(),
+        );
+        let arbiter_key = SigningKey::generate(&mut rng);
+        let arbiter = arbiter_key.verifying_key();
+        let session_id: [u8; 32] = rng.gen();
+        let transcript_hash: [u8; 32] = rng.gen();
+        let ((ob_share, ob_masks), (cb_share, cb_masks)) = b_shares(true, &mut rng);
+
+        let (state_ob, msg1) = fair_create_msg1(
+            &session_id,
+            &transcript_hash,
+            P,
+            &ob_share,
+            ob_masks.clone(),
+            &ob_identity,
+            &arbiter,
+            &mut rng,
+        )
+        .unwrap();
+        let (state_cb, msg2) = fair_process_msg1(
+            &session_id,
+            &transcript_hash,
+            P,
+            &cb_share,
+            cb_masks,
+            &cb_identity,
+            &arbiter,
+            &msg1,
+            &mut rng,
+        )
+        .unwrap();
+        let (state_ob, msg3) = fair_process_msg2(state_ob, &ob_identity, &msg2, &mut rng).unwrap();
+
+        // an opening other than the escrowed one is rejected
+        let mut forged = msg3.clone();
+        forged.openings[0].open.0 = forged.openings[0].open.0.wrapping_add(&U128::ONE);
+        let err = fair_process_msg3(state_cb.clone(), &cb_identity, &forged, &mut rng);
+        assert!(matches!(err, Err(FairReleaseError::InvalidReveal)));
+
+        let (state_cb, msg4) = fair_process_msg3(state_cb, &cb_identity, &msg3, &mut rng).unwrap();
+        let (state_ob, msg5) = fair_process_msg4(state_ob, &ob_identity, &msg4, &mut rng).unwrap();
+
+        // CB withholds FairMsg6 after learning b, OB goes to the arbiter
+        let resolution = arbiter_resolve(
+            &arbiter_key,
+            &session_id,
+            &ob_key,
+            &cb_key,
+            state_ob.evidence(),
+        )
+        .unwrap();
+        assert!(state_ob.resolve(&resolution).unwrap());
+        assert!(state_cb.resolve(&resolution).unwrap());
+
+        // CB holds the same evidence once it has the acceptance of OB
+        let evidence = state_cb.evidence(&msg5, &ob_key).unwrap();
+        assert_eq!(&evidence, state_ob.evidence());
+
+        let (b_cb, msg6) = fair_process_msg5(&state_cb, &cb_identity, &msg5).unwrap();
+        let b_ob = fair_process_msg6(&state_ob, &msg6).unwrap();
+        assert!(b_cb && b_ob);
+
+        // the arbiter names the party whose escrow or acceptance fails
+        let mut other = evidence.clone();
+        other.cb_escrow.session_id = rng.gen();
+        let err = arbiter_resolve(&arbiter_key, &session_id, &ob_key, &cb_key, &other);
+        assert!(matches!(
+            err,
+            Err(FairReleaseError::InvalidEscrow(Role::CB))
+        ));
+        let mut forged = evidence.clone();
+        forged.ob_escrow.pairs[3][1].ciphertext[0] ^= 1;
+        let err = arbiter_resolve(&arbiter_key, &session_id, &ob_key, &cb_key, &forged);
+        assert!(matches!(
+            err,
+            Err(FairReleaseError::InvalidSignature(Role::OB))
+        ));
+        let mut unaccepted = evidence.clone();
+        unaccepted.cb_acceptance = unaccepted.ob_acceptance;
+        let err = arbiter_resolve(&arbiter_key, &session_id, &ob_key, &cb_key, &unaccepted);
+        assert!(matches!(
+            err,
+            Err(FairReleaseError::InvalidSignature(Role::CB))
+        ));
+    }
+
+    #[test]
+    fn test_fair_release_ob_stops() {
+        let mut rng = rand::thread_rng();
+        let (ob_identity, cb_identity) = identities(&mut rng);
+        let mut arbiter = Arbiter::new(SigningKey::generate(&mut rng));
+        let arbiter_key = arbiter.verifying_key();
+        let session_id: [u8; 32] = rng.gen();
+        let transcript_hash: [u8; 32] = rng.gen();
+        let ((ob_share, ob_masks), (cb_share, cb_masks)) = b_shares(true, &mut rng);
+
+        let (state_ob, msg1) = fair_create_msg1(
+            &session_id,
+            &transcript_hash,
+            P,
+            &ob_share,
+            ob_masks,
+            &ob_identity,
+            &arbiter_key,
+            &mut rng,
+        )
+        .unwrap();
+        let (state_cb, msg2) = fair_process_msg1(
+            &session_id,
+            &transcript_hash,
+            P,
+            &cb_share,
+            cb_masks,
+            &cb_identity,
+            &arbiter_key,
+            &msg1,
+            &mut rng,
+        )
+        .unwrap();
+        let (state_ob, msg3) = fair_process_msg2(state_ob, &ob_identity, &msg2, &mut rng).unwrap();
+        let (state_cb, msg4) = fair_process_msg3(state_cb, &cb_identity, &msg3, &mut rng).unwrap();
+
+        // OB withholds FairMsg5 and goes to the arbiter, CB has no evidence
+        let (state_ob, _msg5) = fair_process_msg4(state_ob, &ob_identity, &msg4, &mut rng).unwrap();
+        assert!(arbiter.resolution(&session_id).is_none());
+        let resolution = arbiter
+            .resolve(
+                &session_id,
+                &ob_identity.key.verifying_key(),
+                &cb_identity.key.verifying_key(),
+                state_ob.evidence(),
+            )
+            .unwrap();
+        assert!(state_ob.resolve(&resolution).unwrap());
+
+        // the arbiter kept the resolution for CB, which learns b as well
+        let delivered = arbiter.resolution(&session_id).unwrap();
+        assert_eq!(delivered, &resolution);
+        assert!(state_cb.resolve(delivered).unwrap());
+    }
+
+    #[test]
+    fn test_fair_release_invalid_escrow() {
+        let mut rng = rand::thread_rng();
+        let (ob_identity, cb_identity) = identities(&mut rng);
+        let arbiter = SigningKey::generate(&mut rng).verifying_key();
+        let session_id: [u8; 32] = rng.gen();
+        let transcript_hash: [u8; 32] = rng.gen();
+        let ((ob_share, ob_masks), (cb_share, cb_masks)) = b_shares(false, &mut rng);
+
+        // OB escrows a wrong opening in each pair and signs it, CB finds one
+        // in the challenged halves, except with probability 2^-FAIR_RELEASE_PAIRS
+        let (state_ob, msg1) = fair_create_msg1(
+            &session_id,
+            &transcript_hash,
+            P,
+            &ob_share,
+            ob_masks,
+            &ob_identity,
+            &arbiter,
+            &mut rng,
+        )
+        .unwrap();
+        let (state_cb, msg2) = fair_process_msg1(
+            &session_id,
+            &transcript_hash,
+            P,
+            &cb_share,
+            cb_masks,
+            &cb_identity,
+            &arbiter,
+            &msg1,
+            &mut rng,
+        )
+        .unwrap();
+        let mut cheat = state_ob.clone();
+        let broken: Vec<usize> = (0..FAIR_RELEASE_PAIRS)
+            .map(|_| rng.gen_range(0..2))
+            .collect();
+        for (i, half) in broken.iter().enumerate() {
+            let opening = &mut cheat.openings[i][*half];
+            opening.open.0 = opening.open.0.wrapping_add(&U128::ONE);
+            let nonce = decode_scalar(&opening.nonce).unwrap();
+            cheat.escrow.pairs[i][*half] = encrypt(
+                &arbiter,
+                &session_id,
+                Role::OB,
+                (i, *half),
+                &opening.open,
+                &nonce,
+            )
+            .unwrap();
+        }
+        let digest = cheat.escrow.digest();
+        cheat.escrow.signature = ob_identity.key.sign(&digest, &mut rng);
+
+        let mut msg1 = msg1;
+        msg1.escrow = cheat.escrow.clone();
+        let mut state_cb = state_cb;
+        state_cb.peer_escrow = cheat.escrow.clone();
+        let (_, msg3) = fair_process_msg2(cheat, &ob_identity, &msg2, &mut rng).unwrap();
+        let err = fair_process_msg3(state_cb, &cb_identity, &msg3, &mut rng);
+        assert!(matches!(err, Err(FairReleaseError::InvalidOpen(_))));
+
+        // an escrow of another session or signed by another key is refused
+        let mut other = msg1.clone();
+        other.escrow.session_id = rng.gen();
+        let err = fair_process_msg2(
+            state_ob.clone(),
+            &ob_identity,
+            &FairMsg2 {
+                escrow: other.escrow,
+                challenge: msg2.challenge,
+            },
+            &mut rng,
+        );
+        assert!(matches!(err, Err(FairReleaseError::InvalidSessionID)));
+        let err = fair_process_msg2(state_ob, &cb_identity, &msg2, &mut rng);
+        assert!(matches!(
+            err,
+            Err(FairReleaseError::InvalidSignature(Role::CB))
+        ));
+    }
+}
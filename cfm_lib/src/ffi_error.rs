@@ -0,0 +1,406 @@
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::ptr;
+
+use crate::errors::{
+    ABTCBError, ABTOBError, CFMError, CompError, EnvelopeError, FairReleaseError, LedgerError,
+    PSCOBError, PSITOBError, SeedStoreError, SessionError, SnapshotError,
+};
+
+/// Status code returned by every FFI entry point.
+///
+/// Codes below 100 are raised by the FFI layer itself, the other ranges map
+/// one to one to the variants of the protocol error enums:
+/// 100 `CFMError`, 200 `ABTOBError`, 300 `ABTCBError`, 400 `CompError`,
+/// 500 `PSITOBError`, 600 `PSCOBError` and 700 `FairReleaseError`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CFMStatus {
+    /// success
+    Ok = 0,
+    /// null pointer, empty buffer or invalid argument
+    InvalidInput = 1,
+    /// input bytes could not be deserialized
+    Deserialization = 2,
+    /// output could not be serialized
+    Serialization = 3,
+    /// memory allocation failed
+    AllocationFailed = 4,
+    /// invalid message envelope
+    InvalidEnvelope = 5,
+    /// CFM init message of the other party failed an OT or PPRF check
+    CFMInitFailed = 6,
+    /// signature does not verify under the given key
+    InvalidSignature = 7,
+    /// call is not valid for the role or stage of the session handle
+    SessionInvalidState = 10,
+    /// message session id does not match the session handle
+    SessionInvalidSessionID = 11,
+    /// message is not expected at the current stage of the session handle
+    SessionUnexpectedMessage = 12,
+    /// session handle has aborted on an earlier error
+    SessionAborted = 13,
+    /// message repeats a round the session handle has already accepted
+    SessionDuplicateMessage = 14,
+    /// session snapshot could not be written or restored
+    SessionSnapshotFailed = 15,
+    /// `SeedStoreError::Expired`, run CFM init again
+    SessionSeedsExpired = 16,
+    /// `SeedStoreError::Exhausted`, run CFM init again
+    SessionSeedsExhausted = 17,
+    /// `SeedStoreError::SessionIdReused`, start ABT under a fresh session id
+    SessionSeedsSessionIdReused = 18,
+    /// `LedgerError::AmountTooLarge`
+    SessionLedgerAmountTooLarge = 19,
+    /// `LedgerError::PendingTransfer`, settle the last transfer first
+    SessionLedgerPendingTransfer = 20,
+    /// `LedgerError::NoPendingTransfer`
+    SessionLedgerNoPendingTransfer = 21,
+
+    /// `CFMError::InvalidSessionID`
+    CFMInvalidSessionID = 100,
+    /// `CFMError::InvalidMessage`
+    CFMInvalidMessage = 101,
+    /// `CFMError::InvalidOpen`
+    CFMInvalidOpen = 102,
+    /// `CFMError::PSITError`
+    CFMPSITError = 103,
+    /// `CFMError::AbortProtocol`
+    CFMAbortProtocol = 104,
+    /// `CFMError::Comparison`
+    CFMComparison = 105,
+    /// `CFMError::UnexpectedMessage`
+    CFMUnexpectedMessage = 106,
+    /// `CFMError::SessionClosed`
+    CFMSessionClosed = 107,
+    /// `CFMError::OutputPolicyMismatch`
+    CFMOutputPolicyMismatch = 108,
+    /// `CFMError::LimitModeMismatch`
+    CFMLimitModeMismatch = 109,
+    /// `CFMError::InvalidInput`
+    CFMInvalidInput = 110,
+    /// `CFMError::InvalidModulus`
+    CFMInvalidModulus = 111,
+    /// `CFMError::LedgerVOLE`
+    CFMLedgerVOLE = 112,
+
+    /// `ABTOBError::InvalidSessionID`
+    ABTOBInvalidSessionID = 200,
+    /// `ABTOBError::InvalidMessage`
+    ABTOBInvalidMessage = 201,
+    /// `ABTOBError::InvalidState`
+    ABTOBInvalidState = 202,
+    /// `ABTOBError::InvalidCommitment`
+    ABTOBInvalidCommitment = 203,
+    /// `ABTOBError::InvalidOpen`
+    ABTOBInvalidOpen = 204,
+    /// `ABTOBError::AbortProtocolAndBanOtherParty`
+    ABTOBAbortProtocolAndBanOtherParty = 205,
+    /// `ABTOBError::InvalidModulus`
+    ABTOBInvalidModulus = 206,
+    /// `ABTOBError::ModulusMismatch`
+    ABTOBModulusMismatch = 207,
+    /// `ABTOBError::InvalidFrameSize`
+    ABTOBInvalidFrameSize = 208,
+
+    /// `ABTCBError::InvalidSessionID`
+    ABTCBInvalidSessionID = 300,
+    /// `ABTCBError::InvalidMessage`
+    ABTCBInvalidMessage = 301,
+    /// `ABTCBError::InvalidState`
+    ABTCBInvalidState = 302,
+    /// `ABTCBError::InvalidOpen`
+    ABTCBInvalidOpen = 303,
+    /// `ABTCBError::AbortProtocolAndBanOtherParty`
+    ABTCBAbortProtocolAndBanOtherParty = 304,
+    /// `ABTCBError::InvalidModulus`
+    ABTCBInvalidModulus = 305,
+    /// `ABTCBError::InvalidFrameSize`
+    ABTCBInvalidFrameSize = 306,
+
+    /// `CompError::InvalidSessionID`
+    CompInvalidSessionID = 400,
+    /// `CompError::InvalidOpen`
+    CompInvalidOpen = 401,
+
+    /// `PSITOBError::InvalidSessionID`
+    PSITOBInvalidSessionID = 500,
+    /// `PSITOBError::InvalidMessage`
+    PSITOBInvalidMessage = 501,
+    /// `PSITOBError::InvalidDLogProof`
+    PSITOBInvalidDLogProof = 502,
+    /// `PSITOBError::NotInList`
+    PSITOBNotInList = 503,
+
+    /// `PSCOBError::InvalidSessionID`
+    PSCOBInvalidSessionID = 600,
+    /// `PSCOBError::InvalidMessage`
+    PSCOBInvalidMessage = 601,
+    /// `PSCOBError::InvalidDLogProof`
+    PSCOBInvalidDLogProof = 602,
+
+    /// `FairReleaseError::InvalidSessionID`
+    FairInvalidSessionID = 700,
+    /// `FairReleaseError::InvalidSignature`
+    FairInvalidSignature = 701,
+    /// `FairReleaseError::InvalidReveal`
+    FairInvalidReveal = 702,
+    /// `FairReleaseError::InvalidEscrow`
+    FairInvalidEscrow = 703,
+    /// `FairReleaseError::InvalidOpen`
+    FairInvalidOpen = 704,
+}
+
+impl CFMStatus {
+    /// True if the other party sent data that failed a consistency check
+    /// (open, commitment, MAC or proof), i.e. it has to be treated as cheating.
+    pub fn is_cheating(self) -> bool {
+        matches!(
+            self,
+            CFMStatus::CFMInitFailed
+                | CFMStatus::CFMInvalidOpen
+                | CFMStatus::CFMPSITError
+                | CFMStatus::CFMAbortProtocol
+                | CFMStatus::CFMComparison
+                | CFMStatus::CFMLedgerVOLE
+                | CFMStatus::ABTOBInvalidCommitment
+                | CFMStatus::ABTOBInvalidOpen
+                | CFMStatus::ABTOBAbortProtocolAndBanOtherParty
+                | CFMStatus::ABTCBInvalidOpen
+                | CFMStatus::ABTCBAbortProtocolAndBanOtherParty
+                | CFMStatus::CompInvalidOpen
+                | CFMStatus::PSITOBInvalidDLogProof
+                | CFMStatus::PSCOBInvalidDLogProof
+                | CFMStatus::FairInvalidSignature
+                | CFMStatus::FairInvalidReveal
+                | CFMStatus::FairInvalidEscrow
+                | CFMStatus::FairInvalidOpen
+        )
+    }
+}
+
+impl From<&CFMError> for CFMStatus {
+    fn from(e: &CFMError) -> Self {
+        match e {
+            CFMError::InvalidSessionID => CFMStatus::CFMInvalidSessionID,
+            CFMError::InvalidMessage => CFMStatus::CFMInvalidMessage,
+            CFMError::InvalidOpen(..) => CFMStatus::CFMInvalidOpen,
+            CFMError::PSITError(_) => CFMStatus::CFMPSITError,
+            CFMError::AbortProtocol(..) => CFMStatus::CFMAbortProtocol,
+            CFMError::Comparison(..) => CFMStatus::CFMComparison,
+            CFMError::UnexpectedMessage { .. } => CFMStatus::CFMUnexpectedMessage,
+            CFMError::SessionClosed => CFMStatus::CFMSessionClosed,
+            CFMError::OutputPolicyMismatch => CFMStatus::CFMOutputPolicyMismatch,
+            CFMError::LimitModeMismatch => CFMStatus::CFMLimitModeMismatch,
+            CFMError::InvalidInput => CFMStatus::CFMInvalidInput,
+            CFMError::InvalidModulus => CFMStatus::CFMInvalidModulus,
+            CFMError::LedgerVOLE(..) => CFMStatus::CFMLedgerVOLE,
+        }
+    }
+}
+
+impl From<&ABTOBError> for CFMStatus {
+    fn from(e: &ABTOBError) -> Self {
+        match e {
+            ABTOBError::InvalidSessionID => CFMStatus::ABTOBInvalidSessionID,
+            ABTOBError::InvalidMessage => CFMStatus::ABTOBInvalidMessage,
+            ABTOBError::InvalidState => CFMStatus::ABTOBInvalidState,
+            ABTOBError::InvalidCommitment => CFMStatus::ABTOBInvalidCommitment,
+            ABTOBError::InvalidOpen => CFMStatus::ABTOBInvalidOpen,
+            ABTOBError::AbortProtocolAndBanOtherParty(_) => {
+                CFMStatus::ABTOBAbortProtocolAndBanOtherParty
+            }
+            ABTOBError::InvalidModulus => CFMStatus::ABTOBInvalidModulus,
+            ABTOBError::ModulusMismatch => CFMStatus::ABTOBModulusMismatch,
+            ABTOBError::InvalidFrameSize => CFMStatus::ABTOBInvalidFrameSize,
+        }
+    }
+}
+
+impl From<&ABTCBError> for CFMStatus {
+    fn from(e: &ABTCBError) -> Self {
+        match e {
+            ABTCBError::InvalidSessionID => CFMStatus::ABTCBInvalidSessionID,
+            ABTCBError::InvalidMessage => CFMStatus::ABTCBInvalidMessage,
+            ABTCBError::InvalidState => CFMStatus::ABTCBInvalidState,
+            ABTCBError::InvalidOpen => CFMStatus::ABTCBInvalidOpen,
+            ABTCBError::AbortProtocolAndBanOtherParty(_) => {
+                CFMStatus::ABTCBAbortProtocolAndBanOtherParty
+            }
+            ABTCBError::InvalidModulus => CFMStatus::ABTCBInvalidModulus,
+            ABTCBError::InvalidFrameSize => CFMStatus::ABTCBInvalidFrameSize,
+        }
+    }
+}
+
+impl From<&CompError> for CFMStatus {
+    fn from(e: &CompError) -> Self {
+        match e {
+            CompError::InvalidSessionID => CFMStatus::CompInvalidSessionID,
+            CompError::InvalidOpen(..) => CFMStatus::CompInvalidOpen,
+        }
+    }
+}
+
+impl From<&PSITOBError> for CFMStatus {
+    fn from(e: &PSITOBError) -> Self {
+        match e {
+            PSITOBError::InvalidSessionID => CFMStatus::PSITOBInvalidSessionID,
+            PSITOBError::InvalidMessage => CFMStatus::PSITOBInvalidMessage,
+            PSITOBError::InvalidDLogProof => CFMStatus::PSITOBInvalidDLogProof,
+            PSITOBError::NotInList => CFMStatus::PSITOBNotInList,
+        }
+    }
+}
+
+impl From<&PSCOBError> for CFMStatus {
+    fn from(e: &PSCOBError) -> Self {
+        match e {
+            PSCOBError::InvalidSessionID => CFMStatus::PSCOBInvalidSessionID,
+            PSCOBError::InvalidMessage => CFMStatus::PSCOBInvalidMessage,
+            PSCOBError::InvalidDLogProof => CFMStatus::PSCOBInvalidDLogProof,
+        }
+    }
+}
+
+impl From<&EnvelopeError> for CFMStatus {
+    fn from(_: &EnvelopeError) -> Self {
+        CFMStatus::InvalidEnvelope
+    }
+}
+
+impl From<&SessionError> for CFMStatus {
+    fn from(e: &SessionError) -> Self {
+        match e {
+            SessionError::InvalidState => CFMStatus::SessionInvalidState,
+            SessionError::InvalidInput => CFMStatus::InvalidInput,
+            SessionError::InvalidSessionID => CFMStatus::SessionInvalidSessionID,
+            SessionError::UnexpectedMessage { .. } => CFMStatus::SessionUnexpectedMessage,
+            SessionError::Aborted => CFMStatus::SessionAborted,
+            SessionError::InvalidSignature => CFMStatus::InvalidSignature,
+            SessionError::DuplicateMessage { .. } => CFMStatus::SessionDuplicateMessage,
+            SessionError::Envelope(e) => e.into(),
+            SessionError::CFMInit(_) => CFMStatus::CFMInitFailed,
+            SessionError::SeedStore(e) => e.into(),
+            SessionError::ABTOB(e) => e.into(),
+            SessionError::ABTCB(e) => e.into(),
+            SessionError::CFM(e) => e.into(),
+            SessionError::FairRelease(e) => e.into(),
+            SessionError::Ledger(e) => e.into(),
+        }
+    }
+}
+
+impl From<&SeedStoreError> for CFMStatus {
+    fn from(e: &SeedStoreError) -> Self {
+        match e {
+            SeedStoreError::Expired => CFMStatus::SessionSeedsExpired,
+            SeedStoreError::Exhausted => CFMStatus::SessionSeedsExhausted,
+            SeedStoreError::SessionIdReused => CFMStatus::SessionSeedsSessionIdReused,
+        }
+    }
+}
+
+impl From<&FairReleaseError> for CFMStatus {
+    fn from(e: &FairReleaseError) -> Self {
+        match e {
+            FairReleaseError::InvalidSessionID => CFMStatus::FairInvalidSessionID,
+            FairReleaseError::InvalidSignature(_) => CFMStatus::FairInvalidSignature,
+            FairReleaseError::InvalidReveal => CFMStatus::FairInvalidReveal,
+            FairReleaseError::InvalidEscrow(_) => CFMStatus::FairInvalidEscrow,
+            FairReleaseError::InvalidOpen(_) => CFMStatus::FairInvalidOpen,
+        }
+    }
+}
+
+impl From<&LedgerError> for CFMStatus {
+    fn from(e: &LedgerError) -> Self {
+        match e {
+            LedgerError::AmountTooLarge => CFMStatus::SessionLedgerAmountTooLarge,
+            LedgerError::PendingTransfer => CFMStatus::SessionLedgerPendingTransfer,
+            LedgerError::NoPendingTransfer => CFMStatus::SessionLedgerNoPendingTransfer,
+        }
+    }
+}
+
+impl From<&SnapshotError> for CFMStatus {
+    fn from(_: &SnapshotError) -> Self {
+        CFMStatus::SessionSnapshotFailed
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<(CFMStatus, CString)>> = const { RefCell::new(None) };
+}
+
+/// Result of an FFI entry point that can carry a failure status
+pub(crate) trait FFIResult {
+    /// Result with `status` and no outputs
+    fn error(status: CFMStatus) -> Self;
+}
+
+impl FFIResult for CFMStatus {
+    fn error(status: CFMStatus) -> Self {
+        status
+    }
+}
+
+/// Records `message` as the last error of this thread and returns
+/// the failure result for `status`.
+pub(crate) fn ffi_fail<T: FFIResult>(status: CFMStatus, message: String) -> T {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some((status, message)));
+    T::error(status)
+}
+
+/// Status of the last failed FFI call on this thread, `Ok` if none failed.
+#[no_mangle]
+pub extern "C" fn cfm_last_error_code() -> CFMStatus {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(CFMStatus::Ok, |(s, _)| *s))
+}
+
+/// Message of the last failed FFI call on this thread, null if none failed.
+/// The string is owned by the library and stays valid until the next
+/// failing call on the same thread, do not free it.
+#[no_mangle]
+pub extern "C" fn cfm_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |(_, message)| message.as_ptr())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::VOLEError;
+    use std::ffi::CStr;
+
+    #[test]
+    fn test_last_error() {
+        assert_eq!(cfm_last_error_code(), CFMStatus::Ok);
+        assert!(cfm_last_error_message().is_null());
+
+        let e = ABTOBError::AbortProtocolAndBanOtherParty(VOLEError::ConsistencyCheck);
+        let status: CFMStatus = ffi_fail((&e).into(), format!("Error in abt_process_msg5: {}", e));
+        assert_eq!(status, CFMStatus::ABTOBAbortProtocolAndBanOtherParty);
+        assert!(status.is_cheating());
+        assert_eq!(cfm_last_error_code(), status);
+        let message = unsafe { CStr::from_ptr(cfm_last_error_message()) };
+        assert_eq!(
+            message.to_str().unwrap(),
+            "Error in abt_process_msg5: Abort the protocol and ban other party: \
+             RVOLE consistency check failed"
+        );
+
+        let e = SessionError::CFM(CFMError::InvalidMessage);
+        assert_eq!(CFMStatus::from(&e), CFMStatus::CFMInvalidMessage);
+        assert!(!CFMStatus::from(&e).is_cheating());
+
+        // the last error is per thread
+        let other = std::thread::spawn(|| cfm_last_error_code()).join().unwrap();
+        assert_eq!(other, CFMStatus::Ok);
+    }
+}
@@ -0,0 +1,168 @@
+use std::slice;
+
+use rand::thread_rng;
+use rand_chacha::ChaCha20Rng;
+use rand_core::{CryptoRng, CryptoRngCore, RngCore, SeedableRng};
+
+use crate::ffi_error::{ffi_fail, CFMStatus};
+
+/// Length of the seed of `create_seeded_rng`
+pub const RNG_SEED_SIZE: usize = 32;
+
+/// Random number generator behind the `rng_ptr` argument of the FFI
+/// entry points, either `ThreadRng` or a seeded `ChaCha20Rng`.
+///
+/// The trait object is boxed inside the holder so that the pointer handed
+/// out to C is a thin pointer.
+pub struct RngHolder {
+    rng: Box<dyn CryptoRngCore>,
+}
+
+impl RngHolder {
+    /// Non-deterministic RNG, backed by `thread_rng()`
+    pub fn from_thread_rng() -> Self {
+        Self {
+            rng: Box::new(thread_rng()),
+        }
+    }
+
+    /// Deterministic RNG, the same seed gives the same output stream
+    pub fn from_seed(seed: [u8; RNG_SEED_SIZE]) -> Self {
+        Self {
+            rng: Box::new(ChaCha20Rng::from_seed(seed)),
+        }
+    }
+}
+
+impl RngCore for RngHolder {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+// both backends are CSPRNGs
+impl CryptoRng for RngHolder {}
+
+/// Creates a non-deterministic RNG, free it with `free_rng`.
+#[no_mangle]
+pub extern "C" fn create_rng() -> *mut RngHolder {
+    Box::into_raw(Box::new(RngHolder::from_thread_rng()))
+}
+
+/// Creates a ChaCha20 RNG from a `RNG_SEED_SIZE` byte seed, free it with
+/// `free_rng`. Only meant for tests and for replaying a transcript, the
+/// output of two RNGs with the same seed is identical.
+#[no_mangle]
+pub extern "C" fn create_seeded_rng(
+    seed_ptr: *const u8,
+    seed_size: usize,
+    out_rng: *mut *mut RngHolder,
+) -> CFMStatus {
+    if seed_ptr.is_null() || out_rng.is_null() {
+        return ffi_fail(
+            CFMStatus::InvalidInput,
+            "Invalid input pointers in create_seeded_rng".to_string(),
+        );
+    }
+    let seed: [u8; RNG_SEED_SIZE] =
+        match unsafe { slice::from_raw_parts(seed_ptr, seed_size) }.try_into() {
+            Ok(seed) => seed,
+            Err(_) => {
+                return ffi_fail(
+                    CFMStatus::InvalidInput,
+                    format!(
+                        "Invalid seed size {}, expected {}",
+                        seed_size, RNG_SEED_SIZE
+                    ),
+                );
+            }
+        };
+
+    unsafe {
+        *out_rng = Box::into_raw(Box::new(RngHolder::from_seed(seed)));
+    }
+    CFMStatus::Ok
+}
+
+/// Frees an RNG created by `create_rng` or `create_seeded_rng`.
+#[no_mangle]
+pub extern "C" fn free_rng(rng_ptr: *mut RngHolder) {
+    if rng_ptr.is_null() {
+        return;
+    }
+
+    unsafe {
+        drop(Box::from_raw(rng_ptr));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::c_void;
+    use std::ptr;
+
+    use crate::cfm_init_protocol::{cfm_init_create_msg1, CFMInitMsg1};
+    use crate::generate_init_session_id;
+
+    fn seeded_rng(seed: [u8; RNG_SEED_SIZE]) -> *mut RngHolder {
+        let mut rng = ptr::null_mut();
+        assert_eq!(
+            create_seeded_rng(seed.as_ptr(), seed.len(), &mut rng),
+            CFMStatus::Ok
+        );
+        rng
+    }
+
+    fn session_id(rng: *mut RngHolder) -> Vec<u8> {
+        let mut out_ptr = ptr::null_mut();
+        let mut out_size = 0;
+        let status = generate_init_session_id(rng as *mut c_void, &mut out_ptr, &mut out_size);
+        assert_eq!(status, CFMStatus::Ok);
+        let bytes = unsafe { slice::from_raw_parts(out_ptr, out_size) }.to_vec();
+        crate::free_buffer(out_ptr, out_size);
+        bytes
+    }
+
+    #[test]
+    fn test_seeded_rng() {
+        let rng_a = seeded_rng([7; RNG_SEED_SIZE]);
+        let rng_b = seeded_rng([7; RNG_SEED_SIZE]);
+        let rng_c = seeded_rng([8; RNG_SEED_SIZE]);
+        let session_id_a = session_id(rng_a);
+        assert_eq!(session_id_a, session_id(rng_b));
+        assert_ne!(session_id_a, session_id(rng_c));
+
+        // the protocol messages are replayed byte for byte as well
+        let msg1 = |rng: *mut RngHolder| {
+            let mut msg1 = CFMInitMsg1::default();
+            cfm_init_create_msg1(&[1; 32], &mut msg1, unsafe { &mut *rng });
+            bincode::serialize(&msg1).unwrap()
+        };
+        assert_eq!(msg1(rng_a), msg1(rng_b));
+
+        let mut rng = ptr::null_mut();
+        let status = create_seeded_rng([0u8; 16].as_ptr(), 16, &mut rng);
+        assert_eq!(status, CFMStatus::InvalidInput);
+        assert!(rng.is_null());
+
+        let thread_rng = create_rng();
+        assert_ne!(session_id(thread_rng), session_id_a);
+
+        for rng in [rng_a, rng_b, rng_c, thread_rng] {
+            free_rng(rng);
+        }
+    }
+}
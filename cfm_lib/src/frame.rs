@@ -0,0 +1,84 @@
+//! Length-prefixed framing of envelope encoded messages over a byte stream.
+//!
+//! Each frame is the message length as u32 big endian followed by the
+//! message bytes. The large ABT messages are sent as the frames of
+//! `abt_stream`, so no message is larger than one of those.
+
+use std::io::{ErrorKind, Read, Write};
+
+use crate::abt_stream::MAX_FRAME_SIZE;
+use crate::errors::FrameError;
+
+/// Size of the length prefix in bytes
+pub const LENGTH_PREFIX_SIZE: usize = 4;
+
+/// Maximum size of a framed message
+pub const MAX_MESSAGE_SIZE: usize = MAX_FRAME_SIZE;
+
+/// Writes `msg` as one frame, returns the number of bytes written
+pub fn write_frame<W: Write>(writer: &mut W, msg: &[u8]) -> Result<usize, FrameError> {
+    if msg.len() > MAX_MESSAGE_SIZE {
+        return Err(FrameError::TooLarge(msg.len()));
+    }
+    writer.write_all(&(msg.len() as u32).to_be_bytes())?;
+    writer.write_all(msg)?;
+    writer.flush()?;
+    Ok(LENGTH_PREFIX_SIZE + msg.len())
+}
+
+/// Reads one frame, returns the message bytes
+pub fn read_frame<R: Read>(reader: &mut R) -> Result<Vec<u8>, FrameError> {
+    let mut prefix = [0u8; LENGTH_PREFIX_SIZE];
+    if let Err(e) = reader.read_exact(&mut prefix) {
+        return Err(match e.kind() {
+            ErrorKind::UnexpectedEof => FrameError::Closed,
+            _ => e.into(),
+        });
+    }
+    let size = u32::from_be_bytes(prefix) as usize;
+    if size > MAX_MESSAGE_SIZE {
+        return Err(FrameError::TooLarge(size));
+    }
+
+    let mut msg = vec![0u8; size];
+    reader.read_exact(&mut msg)?;
+    Ok(msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_frames() {
+        let mut stream = vec![];
+        assert_eq!(write_frame(&mut stream, b"msg1").unwrap(), 8);
+        assert_eq!(write_frame(&mut stream, b"").unwrap(), 4);
+        assert_eq!(write_frame(&mut stream, &[7; 300]).unwrap(), 304);
+
+        let mut reader = Cursor::new(stream);
+        assert_eq!(read_frame(&mut reader).unwrap(), b"msg1");
+        assert!(read_frame(&mut reader).unwrap().is_empty());
+        assert_eq!(read_frame(&mut reader).unwrap(), vec![7; 300]);
+        assert!(matches!(read_frame(&mut reader), Err(FrameError::Closed)));
+
+        // truncated frame
+        let mut reader = Cursor::new(vec![0, 0, 0, 5, 1, 2]);
+        assert!(matches!(read_frame(&mut reader), Err(FrameError::Io(_))));
+
+        // no message is larger than an ABT frame
+        let msg = vec![0u8; MAX_MESSAGE_SIZE + 1];
+        assert!(matches!(
+            write_frame(&mut vec![], &msg),
+            Err(FrameError::TooLarge(size)) if size == MAX_MESSAGE_SIZE + 1
+        ));
+
+        // the length is checked before allocating
+        let mut reader = Cursor::new(u32::MAX.to_be_bytes().to_vec());
+        assert!(matches!(
+            read_frame(&mut reader),
+            Err(FrameError::TooLarge(size)) if size == u32::MAX as usize
+        ));
+    }
+}
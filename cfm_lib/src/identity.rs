@@ -0,0 +1,442 @@
+//! Long-term party identities and the channel binding handshake.
+//!
+//! Each bank holds a long-term Ristretto signing key and knows the verifying
+//! key of its correspondent. Before CFM init the parties run a three message
+//! handshake:
+//!
+//! 1. OB -> CB: OB's verifying key, nonce n_OB and ephemeral point E_OB
+//! 2. CB -> OB: CB's verifying key, nonce n_CB, ephemeral point E_CB and
+//!    CB's signature
+//! 3. OB -> CB: OB's signature
+//!
+//! Both signatures are over h = H(X_OB, X_CB, n_OB, n_CB, E_OB, E_CB) and the
+//! signer's role. Each party checks that the key of the other party is the
+//! expected one and that its signature verifies. The resulting
+//! [`ChannelBinding`] derives the session ids of CFM init, ABT and CFM from h,
+//! so the triples and the CFM run are bound to both keys. The MAC key of the
+//! channel comes from h and the Diffie-Hellman secret e_OB·E_CB = e_CB·E_OB,
+//! which nobody who only sees the handshake messages can compute.
+//!
+//! After the handshake every message carries a MAC over the sender role, a
+//! per-direction counter and the message bytes, see [`ChannelBinding::seal`].
+
+use crate::constants::IDENTITY_LABEL;
+use crate::envelope::{ProtocolId, Role};
+use crate::errors::IdentityError;
+use crate::proto::{decode_point, encode_point, PointBytes};
+use crate::schnorr::{Signature, SigningKey, VerifyingKey};
+use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::traits::Identity;
+use merlin::Transcript;
+use rand_core::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Size of the MAC appended to each message
+pub const MAC_SIZE: usize = 32;
+
+/// Long-term key of this party and the expected key of the other party
+#[derive(Clone)]
+pub struct PartyIdentity {
+    /// signing key of this party
+    pub key: SigningKey,
+    /// verifying key of the other party
+    pub peer_key: VerifyingKey,
+}
+
+/// HandshakeMsg1, OB -> CB
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HandshakeMsg1 {
+    /// verifying key of OB
+    pub verifying_key: VerifyingKey,
+    /// nonce of OB
+    pub nonce: [u8; 32],
+    /// ephemeral Diffie-Hellman point E_OB of OB
+    pub ephemeral: PointBytes,
+}
+
+/// HandshakeMsg2, CB -> OB
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HandshakeMsg2 {
+    /// verifying key of CB
+    pub verifying_key: VerifyingKey,
+    /// nonce of CB
+    pub nonce: [u8; 32],
+    /// ephemeral Diffie-Hellman point E_CB of CB
+    pub ephemeral: PointBytes,
+    /// signature of CB over the handshake hash
+    pub signature: Signature,
+}
+
+/// HandshakeMsg3, OB -> CB
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HandshakeMsg3 {
+    /// signature of OB over the handshake hash
+    pub signature: Signature,
+}
+
+/// State of OB after HandshakeMsg1
+pub struct HandshakeStateOB {
+    identity: PartyIdentity,
+    nonce: [u8; 32],
+    ephemeral: SigningKey,
+}
+
+/// State of CB after HandshakeMsg2, the channel waits for the signature of OB
+pub struct HandshakeStateCB {
+    peer_key: VerifyingKey,
+    binding: ChannelBinding,
+}
+
+/// Session ids and MAC key of an authenticated channel
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+pub struct ChannelBinding {
+    #[zeroize(skip)]
+    role: Role,
+    hash: [u8; 32],
+    mac_key: [u8; 32],
+    sent: u64,
+    received: u64,
+}
+
+/// h = H(X_OB, X_CB, n_OB, n_CB, E_OB, E_CB)
+fn handshake_hash(
+    ob_key: &VerifyingKey,
+    cb_key: &VerifyingKey,
+    ob_nonce: &[u8; 32],
+    cb_nonce: &[u8; 32],
+    ob_ephemeral: &PointBytes,
+    cb_ephemeral: &PointBytes,
+) -> [u8; 32] {
+    let mut t = Transcript::new(IDENTITY_LABEL.as_ref());
+    t.append_message(b"ob-key", &ob_key.0);
+    t.append_message(b"cb-key", &cb_key.0);
+    t.append_message(b"ob-nonce", ob_nonce);
+    t.append_message(b"cb-nonce", cb_nonce);
+    t.append_message(b"ob-ephemeral", ob_ephemeral);
+    t.append_message(b"cb-ephemeral", cb_ephemeral);
+
+    let mut hash = [0u8; 32];
+    t.challenge_bytes(b"handshake-hash", &mut hash);
+    hash
+}
+
+/// Message signed by `role` in the handshake
+fn signed_message(hash: &[u8; 32], role: Role) -> [u8; 33] {
+    let mut msg = [0u8; 33];
+    msg[0] = role as u8;
+    msg[1..].copy_from_slice(hash);
+    msg
+}
+
+/// e·E for the ephemeral key e of this party and the ephemeral point E of
+/// the other party, which must not be the identity
+fn shared_secret(
+    ephemeral: &SigningKey,
+    peer_ephemeral: &PointBytes,
+) -> Result<PointBytes, IdentityError> {
+    let point = decode_point(peer_ephemeral).ok_or(IdentityError::InvalidMessage)?;
+    if point == RistrettoPoint::identity() {
+        return Err(IdentityError::InvalidMessage);
+    }
+    Ok(encode_point(&ephemeral.diffie_hellman(&point)))
+}
+
+/// OB starts the handshake
+pub fn handshake_create_msg1<R: CryptoRng + RngCore>(
+    identity: &PartyIdentity,
+    rng: &mut R,
+) -> (HandshakeStateOB, HandshakeMsg1) {
+    let mut nonce = [0u8; 32];
+    rng.fill_bytes(&mut nonce);
+    let ephemeral = SigningKey::generate(rng);
+    let msg1 = HandshakeMsg1 {
+        verifying_key: identity.key.verifying_key(),
+        nonce,
+        ephemeral: ephemeral.verifying_key().0,
+    };
+    let state = HandshakeStateOB {
+        identity: identity.clone(),
+        nonce,
+        ephemeral,
+    };
+    (state, msg1)
+}
+
+/// CB checks the key of OB and signs the handshake hash
+pub fn handshake_process_msg1<R: CryptoRng + RngCore>(
+    identity: &PartyIdentity,
+    msg1: &HandshakeMsg1,
+    rng: &mut R,
+) -> Result<(HandshakeStateCB, HandshakeMsg2), IdentityError> {
+    if msg1.verifying_key != identity.peer_key {
+        return Err(IdentityError::UnknownPeer);
+    }
+
+    let mut nonce = [0u8; 32];
+    rng.fill_bytes(&mut nonce);
+    let ephemeral = SigningKey::generate(rng);
+    let shared = shared_secret(&ephemeral, &msg1.ephemeral)?;
+    let ephemeral = ephemeral.verifying_key().0;
+    let verifying_key = identity.key.verifying_key();
+    let hash = handshake_hash(
+        &msg1.verifying_key,
+        &verifying_key,
+        &msg1.nonce,
+        &nonce,
+        &msg1.ephemeral,
+        &ephemeral,
+    );
+    let signature = identity.key.sign(&signed_message(&hash, Role::CB), rng);
+
+    let msg2 = HandshakeMsg2 {
+        verifying_key,
+        nonce,
+        ephemeral,
+        signature,
+    };
+    let state = HandshakeStateCB {
+        peer_key: identity.peer_key,
+        binding: ChannelBinding::new(Role::CB, hash, &shared),
+    };
+    Ok((state, msg2))
+}
+
+/// OB checks the key and signature of CB and signs the handshake hash
+pub fn handshake_process_msg2<R: CryptoRng + RngCore>(
+    state: HandshakeStateOB,
+    msg2: &HandshakeMsg2,
+    rng: &mut R,
+) -> Result<(ChannelBinding, HandshakeMsg3), IdentityError> {
+    let identity = &state.identity;
+    if msg2.verifying_key != identity.peer_key {
+        return Err(IdentityError::UnknownPeer);
+    }
+
+    let verifying_key = identity.key.verifying_key();
+    let hash = handshake_hash(
+        &verifying_key,
+        &msg2.verifying_key,
+        &state.nonce,
+        &msg2.nonce,
+        &state.ephemeral.verifying_key().0,
+        &msg2.ephemeral,
+    );
+    if !identity
+        .peer_key
+        .verify(&signed_message(&hash, Role::CB), &msg2.signature)
+    {
+        return Err(IdentityError::InvalidSignature);
+    }
+    let shared = shared_secret(&state.ephemeral, &msg2.ephemeral)?;
+    let signature = identity.key.sign(&signed_message(&hash, Role::OB), rng);
+
+    Ok((
+        ChannelBinding::new(Role::OB, hash, &shared),
+        HandshakeMsg3 { signature },
+    ))
+}
+
+/// CB checks the signature of OB
+pub fn handshake_process_msg3(
+    state: HandshakeStateCB,
+    msg3: &HandshakeMsg3,
+) -> Result<ChannelBinding, IdentityError> {
+    let message = signed_message(&state.binding.hash, Role::OB);
+    if !state.peer_key.verify(&message, &msg3.signature) {
+        return Err(IdentityError::InvalidSignature);
+    }
+
+    Ok(state.binding)
+}
+
+impl ChannelBinding {
+    /// Binding of the handshake hash h, with the MAC key derived from h and
+    /// the Diffie-Hellman secret `shared`
+    fn new(role: Role, hash: [u8; 32], shared: &PointBytes) -> Self {
+        let mut t = Transcript::new(IDENTITY_LABEL.as_ref());
+        t.append_message(b"handshake-hash", &hash);
+        t.append_message(b"shared-secret", shared);
+        let mut mac_key = [0u8; 32];
+        t.challenge_bytes(b"mac-key", &mut mac_key);
+
+        ChannelBinding {
+            role,
+            hash,
+            mac_key,
+            sent: 0,
+            received: 0,
+        }
+    }
+
+    /// Role of this party
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    /// Session id of sub-protocol `protocol`, the same for both parties
+    pub fn session_id(&self, protocol: ProtocolId) -> [u8; 32] {
+        let mut t = Transcript::new(IDENTITY_LABEL.as_ref());
+        t.append_message(b"handshake-hash", &self.hash);
+        t.append_u64(b"protocol", protocol as u64);
+
+        let mut session_id = [0u8; 32];
+        t.challenge_bytes(b"session-id", &mut session_id);
+        session_id
+    }
+
+    fn mac(&self, sender: Role, counter: u64, msg: &[u8]) -> [u8; MAC_SIZE] {
+        let mut t = Transcript::new(IDENTITY_LABEL.as_ref());
+        t.append_message(b"mac-key", &self.mac_key);
+        t.append_u64(b"sender", sender as u64);
+        t.append_u64(b"counter", counter);
+        t.append_message(b"message", msg);
+
+        let mut mac = [0u8; MAC_SIZE];
+        t.challenge_bytes(b"mac", &mut mac);
+        mac
+    }
+
+    /// `msg` followed by its MAC, the next message this party sends
+    pub fn seal(&mut self, msg: &[u8]) -> Vec<u8> {
+        let mac = self.mac(self.role, self.sent, msg);
+        self.sent += 1;
+        [msg, &mac].concat()
+    }
+
+    /// The message of `sealed` if its MAC is the one of the next message of
+    /// the other party
+    pub fn open(&mut self, sealed: &[u8]) -> Result<Vec<u8>, IdentityError> {
+        if sealed.len() < MAC_SIZE {
+            return Err(IdentityError::InvalidMessage);
+        }
+        let (msg, mac) = sealed.split_at(sealed.len() - MAC_SIZE);
+        let sender = match self.role {
+            Role::OB => Role::CB,
+            Role::CB => Role::OB,
+        };
+        let expected = self.mac(sender, self.received, msg);
+        if !bool::from(expected.ct_eq(mac)) {
+            return Err(IdentityError::InvalidMac);
+        }
+        self.received += 1;
+        Ok(msg.to_vec())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Identities of OB and CB, each expecting the key of the other
+    pub(crate) fn identities(rng: &mut rand::rngs::ThreadRng) -> (PartyIdentity, PartyIdentity) {
+        let ob_key = SigningKey::generate(rng);
+        let cb_key = SigningKey::generate(rng);
+        let ob = PartyIdentity {
+            peer_key: cb_key.verifying_key(),
+            key: ob_key.clone(),
+        };
+        let cb = PartyIdentity {
+            peer_key: ob_key.verifying_key(),
+            key: cb_key,
+        };
+        (ob, cb)
+    }
+
+    #[test]
+    fn test_handshake() {
+        let mut rng = rand::thread_rng();
+        let (ob_identity, cb_identity) = identities(&mut rng);
+
+        let (state_ob, msg1) = handshake_create_msg1(&ob_identity, &mut rng);
+        let (state_cb, msg2) = handshake_process_msg1(&cb_identity, &msg1, &mut rng).unwrap();
+        let (mut ob, msg3) = handshake_process_msg2(state_ob, &msg2, &mut rng).unwrap();
+        let mut cb = handshake_process_msg3(state_cb, &msg3).unwrap();
+
+        assert_eq!(
+            ob.session_id(ProtocolId::CFMInit),
+            cb.session_id(ProtocolId::CFMInit)
+        );
+        assert_ne!(
+            ob.session_id(ProtocolId::CFMInit),
+            ob.session_id(ProtocolId::CFM)
+        );
+
+        // MACs in both directions, in order
+        let sealed = ob.seal(b"msg1");
+        assert_eq!(cb.open(&sealed).unwrap(), b"msg1");
+        let sealed = cb.seal(b"msg2");
+        assert_eq!(ob.open(&sealed).unwrap(), b"msg2");
+
+        // tampered, replayed and reflected messages fail
+        let mut sealed = ob.seal(b"msg3");
+        sealed[0] ^= 1;
+        assert!(matches!(cb.open(&sealed), Err(IdentityError::InvalidMac)));
+        let sealed = ob.seal(b"msg4");
+        assert!(matches!(ob.open(&sealed), Err(IdentityError::InvalidMac)));
+
+        // a third party with its own key is not the expected OB
+        let (mallory, _) = identities(&mut rng);
+        let (_, msg1) = handshake_create_msg1(&mallory, &mut rng);
+        let err = handshake_process_msg1(&cb_identity, &msg1, &mut rng);
+        assert!(matches!(err, Err(IdentityError::UnknownPeer)));
+
+        // nor can it sign for the key of OB
+        let (_, msg1) = handshake_create_msg1(&ob_identity, &mut rng);
+        let (state_cb, _) = handshake_process_msg1(&cb_identity, &msg1, &mut rng).unwrap();
+        let forged = HandshakeMsg3 {
+            signature: mallory
+                .key
+                .sign(&signed_message(&state_cb.binding.hash, Role::OB), &mut rng),
+        };
+        let err = handshake_process_msg3(state_cb, &forged);
+        assert!(matches!(err, Err(IdentityError::InvalidSignature)));
+    }
+
+    #[test]
+    fn test_handshake_eavesdropper() {
+        let mut rng = rand::thread_rng();
+        let (ob_identity, cb_identity) = identities(&mut rng);
+
+        let (state_ob, msg1) = handshake_create_msg1(&ob_identity, &mut rng);
+        let (state_cb, msg2) = handshake_process_msg1(&cb_identity, &msg1, &mut rng).unwrap();
+        let (mut ob, msg3) = handshake_process_msg2(state_ob, &msg2, &mut rng).unwrap();
+        let mut cb = handshake_process_msg3(state_cb, &msg3).unwrap();
+
+        // a third party holding msg1 to msg3 knows h and both ephemeral
+        // points, but not the Diffie-Hellman secret
+        let hash = handshake_hash(
+            &msg1.verifying_key,
+            &msg2.verifying_key,
+            &msg1.nonce,
+            &msg2.nonce,
+            &msg1.ephemeral,
+            &msg2.ephemeral,
+        );
+        let sum = decode_point(&msg1.ephemeral).unwrap() + decode_point(&msg2.ephemeral).unwrap();
+        for guess in [msg1.ephemeral, msg2.ephemeral, encode_point(&sum)] {
+            let mut forger = ChannelBinding::new(Role::OB, hash, &guess);
+            assert_eq!(
+                forger.session_id(ProtocolId::CFM),
+                cb.session_id(ProtocolId::CFM)
+            );
+            let sealed = forger.seal(b"msg1");
+            assert!(matches!(cb.open(&sealed), Err(IdentityError::InvalidMac)));
+        }
+        let sealed = ob.seal(b"msg1");
+        assert_eq!(cb.open(&sealed).unwrap(), b"msg1");
+
+        // replacing the ephemeral point of OB breaks the signature of CB
+        let (state_ob, mut msg1) = handshake_create_msg1(&ob_identity, &mut rng);
+        msg1.ephemeral = SigningKey::generate(&mut rng).verifying_key().0;
+        let (_, msg2) = handshake_process_msg1(&cb_identity, &msg1, &mut rng).unwrap();
+        let err = handshake_process_msg2(state_ob, &msg2, &mut rng);
+        assert!(matches!(err, Err(IdentityError::InvalidSignature)));
+
+        // the identity point would give a known secret
+        msg1.ephemeral = encode_point(&RistrettoPoint::identity());
+        let err = handshake_process_msg1(&cb_identity, &msg1, &mut rng);
+        assert!(matches!(err, Err(IdentityError::InvalidMessage)));
+    }
+}
@@ -0,0 +1,343 @@
+//! Running ledger of the capital flows approved by CFM.
+//!
+//! The flow total of customer y is split into two shares. CB holds Z_y^CB,
+//! the `big_z` entry it inputs to CFM. OB holds Z_y^OB in a [`Ledger`], the
+//! sum of its transfers to y that CFM approved. OB inputs X + Z_y^OB as its
+//! amount, so CFM checks X + Z_y^OB + Z_y^CB <= L_y against the running total.
+//!
+//! The share of OB is authenticated. CB gives each customer a slot of its
+//! [`LedgerKeys`], with a key μ_i under one long-term key λ, and OB keeps the
+//! tag u_i = λ·Z_i^OB + μ_i next to each share. CFM checks the tag of the
+//! share OB inputs, so OB cannot reset or drop its share: any other value
+//! fails the MAC check of CFM.
+//!
+//! Every CFM session refreshes all slots with a VOLE between the two ledgers:
+//! OB gets the tag updates c_i, CB the key updates d_i, with c_i + d_i = λ·δ_i
+//! for the transfer δ = X at the slot of y and 0 elsewhere. Neither the slot
+//! nor X reaches CB, and OB learns nothing of λ. Both parties hold their
+//! update back until b is known: the `SessionHandle` settles it when its
+//! party learns b at the end of CFM or of its fair release, otherwise the
+//! caller settles it once b is opened elsewhere. An aborted session drops it.
+//! Both must settle the same way, a ledger that missed or skipped an update
+//! fails the tag check of the next session.
+//!
+//! The ledgers live in the `SessionHandle` of each party and are kept across
+//! restarts by its snapshots, so the next CFM session starts from the settled
+//! totals.
+
+use crate::constants::B_PARAMETER;
+use crate::errors::LedgerError;
+use crypto_bigint::modular::runtime_mod::{DynResidue, DynResidueParams};
+use crypto_bigint::{Random, U128, U64};
+use rand_core::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Share Z_i^OB of one ledger slot with its tag λ·Z_i^OB + μ_i
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LedgerSlot {
+    /// share Z_i^OB
+    pub share: U64,
+    /// tag λ·Z_i^OB + μ_i
+    pub tag: U128,
+}
+
+/// Tag updates of OB from one CFM session
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TagUpdate {
+    /// p
+    pub(crate) p: U128,
+    /// slot of the customer of the transfer
+    pub(crate) slot: usize,
+    /// transfer X
+    pub(crate) big_x: U64,
+    /// c_i, added to the tag of every slot
+    pub(crate) tags: Vec<U128>,
+}
+
+/// Key updates of CB from one CFM session
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyUpdate {
+    /// p
+    pub(crate) p: U128,
+    /// d_i, subtracted from the key of every slot
+    pub(crate) keys: Vec<U128>,
+}
+
+/// Authenticated shares Z_i^OB of OB, with the transfer of the running CFM
+/// session
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ledger {
+    slots: Vec<LedgerSlot>,
+    customers: BTreeMap<[u8; 32], usize>,
+    pending: Option<([u8; 32], TagUpdate)>,
+}
+
+impl Ledger {
+    /// Empty ledger
+    pub fn new() -> Self {
+        Ledger::default()
+    }
+
+    /// Share Z_y^OB of customer hash `y`, zero if nothing was approved
+    pub fn share(&self, y: &[u8; 32]) -> U64 {
+        self.customers
+            .get(y)
+            .map_or(U64::ZERO, |slot| self.slot(*slot).share)
+    }
+
+    /// Share and tag of slot `index`, zero for a slot not refreshed yet
+    pub fn slot(&self, index: usize) -> LedgerSlot {
+        self.slots.get(index).copied().unwrap_or_default()
+    }
+
+    /// CFM amount X + Z_y^OB for a transfer of `big_x` to `y`. It must stay
+    /// below 2^B, a larger amount exceeds every limit.
+    pub fn amount(&self, y: &[u8; 32], big_x: U64) -> Result<U64, LedgerError> {
+        let amount = big_x.wrapping_add(&self.share(y));
+        if big_x >= U64::ONE.shl(B_PARAMETER) || amount >= U64::ONE.shl(B_PARAMETER) {
+            return Err(LedgerError::AmountTooLarge);
+        }
+        Ok(amount)
+    }
+
+    /// Customer hash and amount of the transfer waiting for b
+    pub fn pending(&self) -> Option<(&[u8; 32], U64)> {
+        self.pending.as_ref().map(|(y, update)| (y, update.big_x))
+    }
+
+    /// Hold back the tag updates of the transfer to `y` until b is known
+    pub(crate) fn hold(&mut self, y: &[u8; 32], update: TagUpdate) {
+        self.pending = Some((*y, update));
+    }
+
+    /// Apply the held back tag updates and add the transfer to the share of
+    /// its slot if it was `approved`, drop them otherwise
+    pub fn settle(&mut self, approved: bool) -> Result<(), LedgerError> {
+        let (y, update) = self.pending.take().ok_or(LedgerError::NoPendingTransfer)?;
+        if approved {
+            let params = DynResidueParams::new(&update.p);
+            if self.slots.len() < update.tags.len() {
+                self.slots.resize(update.tags.len(), LedgerSlot::default());
+            }
+            for (slot, c) in self.slots.iter_mut().zip(&update.tags) {
+                slot.tag = DynResidue::new(&slot.tag, params)
+                    .add(&DynResidue::new(c, params))
+                    .retrieve();
+            }
+            // X + Z_y^OB was below 2^B when the transfer was held back
+            let slot = &mut self.slots[update.slot];
+            slot.share = slot.share.wrapping_add(&update.big_x);
+            self.customers.insert(y, update.slot);
+        }
+        Ok(())
+    }
+}
+
+/// Ledger key λ of CB with the slot keys μ_i of its customers, and the key
+/// updates of the running CFM session
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LedgerKeys {
+    lambda: U128,
+    keys: Vec<U128>,
+    slots: BTreeMap<[u8; 32], usize>,
+    pending: Option<KeyUpdate>,
+}
+
+impl LedgerKeys {
+    /// Ledger without slots, under a fresh key λ mod `p`
+    pub fn new<R: CryptoRng + RngCore>(p: &U128, rng: &mut R) -> Self {
+        let params = DynResidueParams::new(p);
+        LedgerKeys {
+            lambda: DynResidue::new(&U128::random(rng), params).retrieve(),
+            keys: vec![],
+            slots: BTreeMap::new(),
+            pending: None,
+        }
+    }
+
+    /// Give each customer of `big_y` without a slot the next free one, with
+    /// key 0 to match the zero share and tag of OB
+    pub fn assign_slots(&mut self, big_y: &[[u8; 32]]) {
+        for y in big_y {
+            if !self.slots.contains_key(y) {
+                self.slots.insert(*y, self.keys.len());
+                self.keys.push(U128::ZERO);
+            }
+        }
+    }
+
+    /// Slot of customer hash `y`, if it has one
+    pub fn slot(&self, y: &[u8; 32]) -> Option<usize> {
+        self.slots.get(y).copied()
+    }
+
+    /// Number of slots
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// True if no customer has a slot yet
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// True if key updates wait for b
+    pub fn pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    pub(crate) fn lambda(&self) -> U128 {
+        self.lambda
+    }
+
+    pub(crate) fn key(&self, slot: usize) -> U128 {
+        self.keys[slot]
+    }
+
+    /// Hold back the key updates of a CFM session until b is known
+    pub(crate) fn hold(&mut self, update: KeyUpdate) {
+        self.pending = Some(update);
+    }
+
+    /// Apply the held back key updates if the transfer was `approved`, drop
+    /// them otherwise
+    pub fn settle(&mut self, approved: bool) -> Result<(), LedgerError> {
+        let update = self.pending.take().ok_or(LedgerError::NoPendingTransfer)?;
+        if approved {
+            let params = DynResidueParams::new(&update.p);
+            for (key, d) in self.keys.iter_mut().zip(&update.keys) {
+                *key = DynResidue::new(key, params)
+                    .sub(&DynResidue::new(d, params))
+                    .retrieve();
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::P;
+
+    /// Tag and key updates of a transfer of `big_x` at `slot`, as the VOLE
+    /// of CFM gives them
+    fn updates(
+        keys: &LedgerKeys,
+        slot: usize,
+        big_x: U64,
+        rng: &mut rand::rngs::ThreadRng,
+    ) -> (TagUpdate, KeyUpdate) {
+        let params = DynResidueParams::new(&P);
+        let lambda = DynResidue::new(&keys.lambda(), params);
+        let (tags, key_updates) = (0..keys.len())
+            .map(|i| {
+                let delta = if i == slot {
+                    big_x.resize()
+                } else {
+                    U128::ZERO
+                };
+                let d = DynResidue::new(&U128::random(&mut *rng), params);
+                let c = lambda.mul(&DynResidue::new(&delta, params)).sub(&d);
+                (c.retrieve(), d.retrieve())
+            })
+            .unzip();
+        let tag_update = TagUpdate {
+            p: P,
+            slot,
+            big_x,
+            tags,
+        };
+        let key_update = KeyUpdate {
+            p: P,
+            keys: key_updates,
+        };
+        (tag_update, key_update)
+    }
+
+    /// True if the tag of OB at every slot matches the keys of CB
+    fn tags_match(ledger: &Ledger, keys: &LedgerKeys) -> bool {
+        let params = DynResidueParams::new(&P);
+        (0..keys.len()).all(|i| {
+            let slot = ledger.slot(i);
+            let tag = DynResidue::new(&keys.lambda(), params)
+                .mul(&DynResidue::new(&slot.share.resize(), params))
+                .add(&DynResidue::new(&keys.key(i), params));
+            tag.retrieve() == slot.tag
+        })
+    }
+
+    #[test]
+    fn test_ledger() {
+        let mut rng = rand::thread_rng();
+        let y = [1u8; 32];
+        let other = [2u8; 32];
+        let mut ledger = Ledger::new();
+        let mut keys = LedgerKeys::new(&P, &mut rng);
+        keys.assign_slots(&[y, other]);
+        keys.assign_slots(&[other]);
+        assert_eq!(
+            (keys.slot(&y), keys.slot(&other), keys.len()),
+            (Some(0), Some(1), 2)
+        );
+        assert!(tags_match(&ledger, &keys));
+        assert_eq!(
+            ledger.amount(&y, U64::from_u32(100)).unwrap(),
+            U64::from_u32(100)
+        );
+        assert!(matches!(
+            ledger.settle(true),
+            Err(LedgerError::NoPendingTransfer)
+        ));
+
+        // an approved transfer moves the share of its customer only, the
+        // tags of all slots still match the refreshed keys
+        let (tag_update, key_update) = updates(&keys, 0, U64::from_u32(100), &mut rng);
+        ledger.hold(&y, tag_update);
+        keys.hold(key_update);
+        assert_eq!(ledger.pending(), Some((&y, U64::from_u32(100))));
+        assert!(keys.pending());
+        ledger.settle(true).unwrap();
+        keys.settle(true).unwrap();
+        assert_eq!(ledger.pending(), None);
+        assert!(!keys.pending());
+        assert_eq!(ledger.share(&y), U64::from_u32(100));
+        assert_eq!(ledger.share(&other), U64::ZERO);
+        assert!(tags_match(&ledger, &keys));
+        assert_eq!(
+            ledger.amount(&y, U64::from_u32(5)).unwrap(),
+            U64::from_u32(105)
+        );
+
+        // a refused transfer is dropped on both sides
+        let (tag_update, key_update) = updates(&keys, 0, U64::from_u32(7), &mut rng);
+        ledger.hold(&y, tag_update);
+        keys.hold(key_update);
+        ledger.settle(false).unwrap();
+        keys.settle(false).unwrap();
+        assert_eq!(ledger.share(&y), U64::from_u32(100));
+        assert!(tags_match(&ledger, &keys));
+
+        // a ledger that drops an approved transfer, or is reset, no longer
+        // matches the keys
+        let (tag_update, key_update) = updates(&keys, 1, U64::from_u32(9), &mut rng);
+        ledger.hold(&other, tag_update);
+        keys.hold(key_update);
+        let mut dropped = ledger.clone();
+        ledger.settle(true).unwrap();
+        dropped.settle(false).unwrap();
+        keys.settle(true).unwrap();
+        assert!(tags_match(&ledger, &keys));
+        assert!(!tags_match(&dropped, &keys));
+        assert!(!tags_match(&Ledger::new(), &keys));
+
+        // the amount stays below 2^B
+        let max = U64::ONE.shl(B_PARAMETER).wrapping_sub(&U64::ONE);
+        assert!(ledger.amount(&[3u8; 32], max).is_ok());
+        assert!(matches!(
+            ledger.amount(&y, max),
+            Err(LedgerError::AmountTooLarge)
+        ));
+    }
+}
@@ -1,3 +1,10 @@
+// The `extern "C"` functions below and in `ffi_rng` take the raw pointers of
+// C and Python callers. Each one checks its pointers for null and reads or
+// writes no more than the size passed along with them, and the contract of
+// every pointer is written in its doc comment. An `unsafe fn` would not carry
+// that contract to callers that are not Rust, so the lint is allowed instead.
+#![allow(clippy::not_unsafe_ptr_arg_deref)]
+
 use crypto_bigint::U128;
 
 /// Misc protocol helper functions
@@ -9,6 +16,9 @@ pub mod constants;
 /// DLog proof
 pub mod dlog_proof;
 
+/// Schnorr signatures over Ristretto
+pub mod schnorr;
+
 /// Private Sanction List Check protocol
 pub mod psc_protocol;
 
@@ -24,6 +34,9 @@ pub mod utils;
 /// auth_beaver_triples
 pub mod auth_beaver_triples;
 
+/// Chunked ABTMsg1 and ABTMsg2
+pub mod abt_stream;
+
 /// comparison protocol
 pub mod comparison;
 
@@ -36,6 +49,54 @@ pub mod cfm_protocol;
 /// Creation ot_seeds for cfm protocol
 pub mod cfm_init_protocol;
 
+/// Tagged message envelope
+pub mod envelope;
+
+/// Pool of pre-computed shares and triples
+pub mod triple_pool;
+
+/// Lifecycle of the base OT seeds
+pub mod seed_store;
+
+/// Running hash over the messages of a session
+pub mod transcript;
+
+/// Signed reports of identifiable aborts
+pub mod abort_report;
+
+/// Signed receipts of CFM outcomes
+pub mod receipt;
+
+/// Party identity keys and channel binding
+pub mod identity;
+
+/// Fair release of the CFM result through escrows
+pub mod fair_release;
+
+/// Running ledger of approved capital flows
+pub mod ledger;
+
+/// Owning session handle for the FFI
+pub mod session_handle;
+
+/// Encrypted snapshots of a session handle
+pub mod session_snapshot;
+
+/// Length-prefixed framing of wire messages
+pub mod frame;
+
+/// Message transports between the two parties
+pub mod transport;
+
+/// Drivers running init, ABT and CFM over a transport
+pub mod runner;
+
+/// FFI status codes and last error message
+pub mod ffi_error;
+
+/// RNG handles for the FFI
+pub mod ffi_rng;
+
 use utils::Customer;
 
 
@@ -44,9 +105,21 @@ const P_HEX: &str = "ccc87af2fe0b80db9924beecec1bc803";
 /// Prime integer p
 pub const P: U128 = U128::from_be_hex(P_HEX);
 
+/// Version of the C ABI, bumped on every change of an exported
+/// signature or `#[repr(C)]` layout. Also emitted as `CFM_ABI_VERSION`
+/// in `include/cfm_lib.h`.
+pub const CFM_ABI_VERSION: u32 = 10;
+
+/// ABI version of the loaded library, bindings compare it with the
+/// `CFM_ABI_VERSION` they were built against before any other call.
+#[no_mangle]
+pub extern "C" fn cfm_abi_version() -> u32 {
+    CFM_ABI_VERSION
+}
+
 
-use rand::{thread_rng, Rng, RngCore};
-use rand::rngs::{StdRng, ThreadRng};
+use rand::Rng;
+use rand::rngs::StdRng;
 use std::os::raw::{c_char, c_void, c_uchar};
 use std::ffi::CStr;
 use rand::SeedableRng;
@@ -70,8 +143,8 @@ use cfm_protocol::{
     cfm_create_msg1, cfm_process_msg1, cfm_process_msg10, cfm_process_msg11, cfm_process_msg12,
     cfm_process_msg13, cfm_process_msg14, cfm_process_msg15, cfm_process_msg16, cfm_process_msg17,
     cfm_process_msg18, cfm_process_msg2, cfm_process_msg3, cfm_process_msg4, cfm_process_msg5,
-    cfm_process_msg6, cfm_process_msg7, cfm_process_msg8, cfm_process_msg9,
-    NUMBER_OF_AUTH_BEAVER_TRIPLES, NUMBER_OF_SHARES, CFMMsg1, CFMStateOBR1,CFMMsg2, CFMStateCBR1, CFMMsg3,
+    cfm_process_msg6, cfm_process_msg7, cfm_process_msg8, cfm_process_msg9, valid_limits, OutputPolicy,
+    ABT_SHARES, NUMBER_OF_AUTH_BEAVER_TRIPLES, NUMBER_OF_SHARES, CFMMsg1, CFMStateOBR1,CFMMsg2, CFMStateCBR1, CFMMsg3,
     CFMStateOBR2, CFMMsg4, CFMStateCBR2, CFMMsg5, CFMStateOBR3, CFMMsg6, CFMStateCBR3, CFMMsg7,
     CFMStateOBR4, CFMMsg8, CFMStateCBR4, CFMMsg9, CFMStateOBR5, CFMMsg10, CFMStateCBR5, CFMMsg11,
     CFMStateOBR6, CFMMsg12, CFMStateCBR6, CFMMsg13,CFMStateOBR7, CFMMsg14, CFMStateCBR7, CFMMsg15,
@@ -79,6 +152,16 @@ use cfm_protocol::{
 };
 
 use crate::cfm_init_protocol::{CFMInitOTSeedsCB, CFMInitOTSeedsOB, CFMInitStateOB, CFMInitStateCB};
+use crate::session_handle::SessionHandle;
+use crate::ledger::{Ledger, LedgerKeys};
+use crate::seed_store::RotationPolicy;
+use crate::abort_report::SignedAbortReport;
+use crate::receipt::SignedReceipt;
+use crate::fair_release::{arbiter_resolve, FairEvidence, Resolution};
+use crate::identity::PartyIdentity;
+use crate::schnorr::{Signature, SigningKey, VerifyingKey};
+use crate::ffi_error::{ffi_fail, CFMStatus, FFIResult};
+use crate::ffi_rng::RngHolder;
 use std::alloc::{alloc, dealloc, Layout};
 use std::ptr;
 use std::slice;
@@ -90,6 +173,9 @@ enum TypeRegistry {
     CFMInitMessage,
     CFMInitMsg1,
     CFMInitMsg2,
+    Envelope,
+    SignedAbortReport,
+    SignedReceipt,
 }
 
 impl TypeRegistry {
@@ -98,35 +184,72 @@ impl TypeRegistry {
             "CFMInitMessage" => Some(TypeRegistry::CFMInitMessage),
             "CFMInitMsg1" => Some(TypeRegistry::CFMInitMsg1),
             "CFMInitMsg2" => Some(TypeRegistry::CFMInitMsg2),
+            "Envelope" => Some(TypeRegistry::Envelope),
+            "SignedAbortReport" => Some(TypeRegistry::SignedAbortReport),
+            "SignedReceipt" => Some(TypeRegistry::SignedReceipt),
             _ => None,
         }
     }
 }
 
 /// Generic deserialization function that converts binary (Bincode) to JSON
-fn deserialize_generic<T: for<'de> Deserialize<'de> + Serialize>(serialized: &[u8]) -> *mut c_char {
+fn deserialize_generic<T: for<'de> Deserialize<'de> + Serialize>(
+    serialized: &[u8],
+    out_json: *mut *mut c_char,
+) -> CFMStatus {
     match bincode::deserialize::<T>(serialized) {
-        Ok(msg) => match serde_json::to_string(&msg) {
-            Ok(json) => match CString::new(json) {
-                Ok(c_string) => c_string.into_raw(),
-                Err(_) => ptr::null_mut(),
-            },
-            Err(_) => ptr::null_mut(),
-        },
-        Err(_) => ptr::null_mut(),
+        Ok(msg) => to_json(&msg, out_json),
+        Err(e) => ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize message: {:?}", e)),
+    }
+}
+
+/// Validates an envelope and converts it to JSON
+fn deserialize_envelope(serialized: &[u8], out_json: *mut *mut c_char) -> CFMStatus {
+    match envelope::decode(serialized) {
+        Ok(envelope) => to_json(&envelope, out_json),
+        Err(e) => ffi_fail((&e).into(), format!("Invalid envelope: {}", e)),
+    }
+}
+
+/// Copies `bytes` into a new buffer at `out_ptr`, free it with `free_buffer`
+fn to_buffer(bytes: &[u8], out_ptr: *mut *mut u8, out_size: *mut usize) -> CFMStatus {
+    let size = bytes.len();
+    let layout = Layout::array::<u8>(size).unwrap();
+    let ptr = unsafe { alloc(layout) };
+    if ptr.is_null() {
+        return ffi_fail(CFMStatus::AllocationFailed, format!("Memory allocation failed, size: {}", size));
+    }
+    unsafe {
+        ptr.copy_from_nonoverlapping(bytes.as_ptr(), size);
+        *out_ptr = ptr;
+        *out_size = size;
     }
+
+    CFMStatus::Ok
 }
 
+/// Writes `value` as a JSON C string to `out_json`
+fn to_json<T: Serialize>(value: &T, out_json: *mut *mut c_char) -> CFMStatus {
+    match serde_json::to_string(value).ok().and_then(|json| CString::new(json).ok()) {
+        Some(c_string) => {
+            unsafe { *out_json = c_string.into_raw() };
+            CFMStatus::Ok
+        }
+        None => ffi_fail(CFMStatus::Serialization, "Failed to convert message to JSON".to_string()),
+    }
+}
 
 /// **FFI Wrapper function that accepts type name and deserializes accordingly**
+/// The JSON string is written to `out_json`, free it with `free_string`.
 #[no_mangle]
 pub extern "C" fn deserialize_msg(
     serialized_ptr: *const c_uchar,
     size: usize,
-    type_name_ptr: *const c_char
-) -> *mut c_char {
-    if serialized_ptr.is_null() || size == 0 || type_name_ptr.is_null() {
-        return ptr::null_mut();
+    type_name_ptr: *const c_char,
+    out_json: *mut *mut c_char,
+) -> CFMStatus {
+    if serialized_ptr.is_null() || size == 0 || type_name_ptr.is_null() || out_json.is_null() {
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in deserialize_msg".to_string());
     }
 
     let serialized = unsafe { slice::from_raw_parts(serialized_ptr, size) };
@@ -135,16 +258,19 @@ pub extern "C" fn deserialize_msg(
     let type_name = unsafe {
         match CStr::from_ptr(type_name_ptr).to_str() {
             Ok(s) => s,
-            Err(_) => return ptr::null_mut(),
+            Err(_) => return ffi_fail(CFMStatus::InvalidInput, "Failed to convert C string.".to_string()),
         }
     };
 
     // Match type name and call appropriate deserialization
     match TypeRegistry::from_str(type_name) {
-        Some(TypeRegistry::CFMInitMessage) => deserialize_generic::<CFMInitMessage>(serialized),
-        Some(TypeRegistry::CFMInitMsg1) => deserialize_generic::<CFMInitMsg1>(serialized),
-        Some(TypeRegistry::CFMInitMsg2) => deserialize_generic::<CFMInitMsg2>(serialized),
-        None => ptr::null_mut(), // Unknown type
+        Some(TypeRegistry::CFMInitMessage) => deserialize_generic::<CFMInitMessage>(serialized, out_json),
+        Some(TypeRegistry::CFMInitMsg1) => deserialize_generic::<CFMInitMsg1>(serialized, out_json),
+        Some(TypeRegistry::CFMInitMsg2) => deserialize_generic::<CFMInitMsg2>(serialized, out_json),
+        Some(TypeRegistry::Envelope) => deserialize_envelope(serialized, out_json),
+        Some(TypeRegistry::SignedAbortReport) => deserialize_generic::<SignedAbortReport>(serialized, out_json),
+        Some(TypeRegistry::SignedReceipt) => deserialize_generic::<SignedReceipt>(serialized, out_json),
+        None => ffi_fail(CFMStatus::InvalidInput, format!("Unknown type name: {}", type_name)),
     }
 }
 
@@ -176,53 +302,13 @@ pub extern "C" fn free_string(ptr: *mut c_char) {
 }
 
 
-// A holder for our RNG so that the pointer we return is a thin pointer.
-#[repr(C)]
-pub struct RngHolder {
-    // This field is a Box<dyn RngCore> (a fat pointer) but the struct itself
-    // is allocated on the heap and we return a pointer to the struct.
-    pub rng: Box<dyn RngCore>,
-}
-
 #[no_mangle]
-pub extern "C" fn free_rng(rng_ptr: *mut ThreadRng) {
-    if rng_ptr.is_null() {
-        return;
+pub extern "C" fn generate_init_session_id(rng_ptr: *mut c_void, out_ptr: *mut *mut u8, out_size: *mut usize) -> CFMStatus {
+    if rng_ptr.is_null() || out_ptr.is_null() || out_size.is_null() {
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid RNG pointer or output size.".to_string());
     }
 
-    // SAFELY reclaim the Box and drop it
-    unsafe {
-        drop(Box::from_raw(rng_ptr));
-    }
-}
-
-/// Creates an RNG.  
-/// If `seed_req` is null, uses `thread_rng()`.  
-/// Otherwise, uses `StdRng::seed_from_u64(seed)` with the provided seed.
-/// Returns a pointer to a RngHolder as an opaque pointer.
-#[no_mangle]
-pub extern "C" fn create_rng() -> *mut ThreadRng {
-    // Create a non-deterministic RNG using thread_rng.
-    let rng = Box::new(thread_rng()); // Now directly using `ThreadRng`
-    
-    // Convert the Box into a raw pointer (caller must free it later)
-    Box::into_raw(rng)
-}
-
-
-
-#[no_mangle]
-pub extern "C" fn generate_init_session_id(rng_ptr: *mut c_void, out_size: *mut usize) -> *mut u8 {
-    if rng_ptr.is_null() || out_size.is_null() {
-        eprintln!("Invalid RNG pointer or output size.");
-        return ptr::null_mut();
-    }
-
-    // SAFELY Extract RngHolder and access the RNG
-    // let rng_holder = unsafe { &mut *(rng_ptr as *mut Rng) };
-    // let rng = &mut rng_holder.rng; // Now correctly using the RNG
-    
-    let rng = unsafe { &mut *(rng_ptr as *mut ThreadRng) };
+    let rng = unsafe { &mut *(rng_ptr as *mut RngHolder) };
 
     // Generate a random 32-byte session ID
     let session_id: [u8; 32] = rng.gen();
@@ -231,7 +317,7 @@ pub extern "C" fn generate_init_session_id(rng_ptr: *mut c_void, out_size: *mut
     // Serialize the session ID using Bincode
     let serialized = match bincode::serialize(&session_id) {
         Ok(data) => data,
-        Err(_) => return ptr::null_mut(),
+        Err(e) => return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize session_id: {:?}", e)),
     };
 
     // Allocate heap memory for the serialized data
@@ -240,19 +326,17 @@ pub extern "C" fn generate_init_session_id(rng_ptr: *mut c_void, out_size: *mut
     let ptr = unsafe { alloc(layout) };
 
     if ptr.is_null() {
-        eprintln!("Memory allocation failed!");
-        return ptr::null_mut();
+        return ffi_fail(CFMStatus::AllocationFailed, "Memory allocation failed!".to_string());
     }
 
     // Copy serialized data to allocated memory
     unsafe {
         ptr.copy_from_nonoverlapping(serialized.as_ptr(), size);
+        *out_ptr = ptr;
         *out_size = size;
     }
 
-    // println!("Returning pointer: {:#?}", ptr);
-
-    ptr
+    CFMStatus::Ok
 }
 
 
@@ -265,11 +349,10 @@ pub enum CFMInitMessage {
 }
 
 #[no_mangle]
-pub extern "C" fn create_msg(msg_type: *const c_char, out_size: *mut usize) -> *mut u8 {
+pub extern "C" fn create_msg(msg_type: *const c_char, out_ptr: *mut *mut u8, out_size: *mut usize) -> CFMStatus {
     // Ensure input pointers are valid
-    if msg_type.is_null() || out_size.is_null() {
-        eprintln!("Invalid input to create_msg");
-        return ptr::null_mut();
+    if msg_type.is_null() || out_ptr.is_null() || out_size.is_null() {
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input to create_msg".to_string());
     }
 
     // Convert the C string to a Rust &str
@@ -289,8 +372,7 @@ pub extern "C" fn create_msg(msg_type: *const c_char, out_size: *mut usize) -> *
             bincode::serialize(&msg)
         }
         _ => {
-            eprintln!("Unexpected message type: {:?}", c_str);
-            return ptr::null_mut();
+            return ffi_fail(CFMStatus::InvalidInput, format!("Unexpected message type: {:?}", c_str));
         }
     };
 
@@ -301,8 +383,7 @@ pub extern "C" fn create_msg(msg_type: *const c_char, out_size: *mut usize) -> *
             data
         }
         Err(e) => {
-            eprintln!("Serialization failed: {:?}", e);
-            return ptr::null_mut();
+            return ffi_fail(CFMStatus::Serialization, format!("Serialization failed: {:?}", e));
         }
     };
 
@@ -312,17 +393,17 @@ pub extern "C" fn create_msg(msg_type: *const c_char, out_size: *mut usize) -> *
     let ptr = unsafe { alloc(layout) };
 
     if ptr.is_null() {
-        eprintln!("Memory allocation failed!");
-        return ptr::null_mut();
+        return ffi_fail(CFMStatus::AllocationFailed, "Memory allocation failed!".to_string());
     }
 
     // Copy serialized data to allocated memory
     unsafe {
         ptr.copy_from_nonoverlapping(serialized.as_ptr(), size);
+        *out_ptr = ptr;
         *out_size = size;
     }
 
-    ptr
+    CFMStatus::Ok
 }
 
 fn round_trip_check<T>(data: &T)
@@ -343,13 +424,13 @@ pub extern "C" fn ffi_cfm_init_create_msg1(
     msg1_bytes_ptr: *mut u8,  // Changed to `*mut u8` to allow modification
     msg1_size: usize,
     rng_ptr: *mut c_void,
+    out_state_ptr: *mut *mut u8,
     out_state_size: *mut usize,
-) -> *mut u8 {
+) -> CFMStatus {
     if session_id_bytes_ptr.is_null() || session_id_size == 0 || 
        msg1_bytes_ptr.is_null() || msg1_size == 0 || 
-       rng_ptr.is_null() || out_state_size.is_null() {
-        eprintln!("Invalid input pointers in ffi_cfm_init_create_msg1");
-        return ptr::null_mut();
+       rng_ptr.is_null() || out_state_ptr.is_null() || out_state_size.is_null() {
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_cfm_init_create_msg1".to_string());
     }
 
     // eprintln!(
@@ -362,8 +443,7 @@ pub extern "C" fn ffi_cfm_init_create_msg1(
     let session_id: [u8; 32] = match bincode::deserialize(session_id_bytes) {
         Ok(id) => id,
         Err(e) => {
-            eprintln!("Failed to deserialize session_id: {:?}", e);
-            return ptr::null_mut();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize session_id: {:?}", e));
         }
     };
 
@@ -374,15 +454,14 @@ pub extern "C" fn ffi_cfm_init_create_msg1(
     let mut msg1: CFMInitMsg1 = match bincode::deserialize(msg1_bytes) {
         Ok(msg) => msg,
         Err(e) => {
-            eprintln!("Failed to deserialize msg1: {:?}", e);
-            return ptr::null_mut();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize msg1: {:?}", e));
         }
     };
 
     // eprintln!("Message 1 successfully deserialized.");
 
     // Get the RNG reference
-    let rng = unsafe { &mut *(rng_ptr as *mut ThreadRng) };
+    let rng = unsafe { &mut *(rng_ptr as *mut RngHolder) };
 
     // Call the actual function
     let state_ob: CFMInitStateOB = cfm_init_create_msg1(&session_id, &mut msg1, rng);
@@ -391,18 +470,19 @@ pub extern "C" fn ffi_cfm_init_create_msg1(
     let updated_msg1_serialized = match bincode::serialize(&msg1) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize updated msg1: {:?}", e);
-            return ptr::null_mut();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize updated msg1: {:?}", e));
         }
     };
 
     // ✅ Copy updated msg1 back to original pointer
     if updated_msg1_serialized.len() != msg1_size {
-        // eprintln!(
-        //     "Updated msg1 size mismatch: expected {}, got {}",
-        //     msg1_size, updated_msg1_serialized.len()
-        // );
-        return ptr::null_mut();
+        return ffi_fail(
+            CFMStatus::InvalidInput,
+            format!(
+                "Updated msg1 size mismatch: expected {}, got {}",
+                msg1_size, updated_msg1_serialized.len()
+            ),
+        );
     }
 
     unsafe {
@@ -413,8 +493,7 @@ pub extern "C" fn ffi_cfm_init_create_msg1(
     let serialized_state = match bincode::serialize(&state_ob) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize state_ob: {:?}", e);
-            return ptr::null_mut();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize state_ob: {:?}", e));
         }
     };
 
@@ -424,17 +503,17 @@ pub extern "C" fn ffi_cfm_init_create_msg1(
     let ptr = unsafe { alloc(layout) };
 
     if ptr.is_null() {
-        eprintln!("Memory allocation failed for state_ob, size: {}", size);
-        return ptr::null_mut();
+        return ffi_fail(CFMStatus::AllocationFailed, format!("Memory allocation failed for state_ob, size: {}", size));
     }
 
     // ✅ Copy serialized state_ob to allocated memory
     unsafe {
         ptr.copy_from_nonoverlapping(serialized_state.as_ptr(), size);
+        *out_state_ptr = ptr;
         *out_state_size = size;
     }
 
-    ptr
+    CFMStatus::Ok
 }
 
 
@@ -447,14 +526,14 @@ pub extern "C" fn ffi_cfm_init_process_msg1(
     msg2_bytes_ptr: *mut u8,
     msg2_size: usize,
     rng_ptr: *mut c_void,
+    out_state_ptr: *mut *mut u8,
     out_state_size: *mut usize,
-) -> *mut u8 {
+) -> CFMStatus {
     if session_id_bytes_ptr.is_null() || session_id_size == 0 || 
        msg1_bytes_ptr.is_null() || msg1_size == 0 || 
        msg2_bytes_ptr.is_null() || msg2_size == 0 ||
-       rng_ptr.is_null() || out_state_size.is_null() {
-        eprintln!("Invalid input pointers in ffi_cfm_init_process_msg1");
-        return ptr::null_mut();
+       rng_ptr.is_null() || out_state_ptr.is_null() || out_state_size.is_null() {
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_cfm_init_process_msg1".to_string());
     }
 
     // eprintln!(
@@ -467,8 +546,7 @@ pub extern "C" fn ffi_cfm_init_process_msg1(
     let session_id: [u8; 32] = match bincode::deserialize(session_id_bytes) {
         Ok(id) => id,
         Err(e) => {
-            eprintln!("Failed to deserialize session_id: {:?}", e);
-            return ptr::null_mut();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize session_id: {:?}", e));
         }
     };
 
@@ -477,8 +555,7 @@ pub extern "C" fn ffi_cfm_init_process_msg1(
     let msg1: CFMInitMsg1 = match bincode::deserialize(msg1_bytes) {
         Ok(msg) => msg,
         Err(e) => {
-            eprintln!("Failed to deserialize msg1: {:?}", e);
-            return ptr::null_mut();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize msg1: {:?}", e));
         }
     };
 
@@ -487,20 +564,18 @@ pub extern "C" fn ffi_cfm_init_process_msg1(
     let mut msg2: CFMInitMsg2 = match bincode::deserialize(msg2_bytes) {
         Ok(msg) => msg,
         Err(e) => {
-            eprintln!("Failed to deserialize msg2: {:?}", e);
-            return ptr::null_mut();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize msg2: {:?}", e));
         }
     };
 
     // Get RNG reference
-    let rng = unsafe { &mut *(rng_ptr as *mut ThreadRng) };
+    let rng = unsafe { &mut *(rng_ptr as *mut RngHolder) };
 
     // Call the actual function
     let state_cb: CFMInitStateCB = match cfm_init_process_msg1(&session_id, &msg1, &mut msg2, rng) {
         Ok(state) => state,
         Err(e) => {
-            eprintln!("cfm_init_process_msg1 failed: {:?}", e);
-            return ptr::null_mut();
+            return ffi_fail(CFMStatus::CFMInitFailed, format!("cfm_init_process_msg1 failed: {}", e));
         }
     };
 
@@ -511,8 +586,7 @@ pub extern "C" fn ffi_cfm_init_process_msg1(
             data
         },
         Err(e) => {
-            eprintln!("Failed to serialize state_cb: {:?}", e);
-            return ptr::null_mut();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize state_cb: {:?}", e));
         }
     };
 
@@ -520,18 +594,19 @@ pub extern "C" fn ffi_cfm_init_process_msg1(
     let serialized_msg2 = match bincode::serialize(&msg2) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize updated msg2: {:?}", e);
-            return ptr::null_mut();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize updated msg2: {:?}", e));
         }
     };
 
     // Ensure the buffer size is correct before copying updated msg2 back
     if serialized_msg2.len() != msg2_size {
-        eprintln!(
-            "Error: Updated msg2 size mismatch! Expected {}, but got {}",
-            msg2_size, serialized_msg2.len()
+        return ffi_fail(
+            CFMStatus::InvalidInput,
+            format!(
+                "Error: Updated msg2 size mismatch! Expected {}, but got {}",
+                msg2_size, serialized_msg2.len()
+            ),
         );
-        return ptr::null_mut();
     }
 
     // Copy updated msg2 back into the provided buffer
@@ -545,17 +620,17 @@ pub extern "C" fn ffi_cfm_init_process_msg1(
     let ptr = unsafe { alloc(layout) };
 
     if ptr.is_null() {
-        eprintln!("Memory allocation failed for state_cb, size: {}", size);
-        return ptr::null_mut();
+        return ffi_fail(CFMStatus::AllocationFailed, format!("Memory allocation failed for state_cb, size: {}", size));
     }
 
     // Copy serialized state_cb to allocated memory
     unsafe {
         ptr.copy_from_nonoverlapping(serialized_state_cb.as_ptr(), size);
+        *out_state_ptr = ptr;
         *out_state_size = size;
     }
 
-    ptr
+    CFMStatus::Ok
 }
 
 #[no_mangle]
@@ -567,14 +642,14 @@ pub extern "C" fn ffi_cfm_init_process_msg2(
     msg3_ptr: *mut u8,
     msg3_size: usize,
     rng_ptr: *mut c_void,
+    out_ot_seeds_ptr: *mut *mut u8,
     out_ot_seeds_size: *mut usize,
-) -> *mut u8 {
+) -> CFMStatus {
     if state_ob_ptr.is_null() || state_ob_size == 0 ||
        msg2_ptr.is_null() || msg2_size == 0 ||
        msg3_ptr.is_null() || msg3_size == 0 ||
-       rng_ptr.is_null() || out_ot_seeds_size.is_null() {
-        eprintln!("Invalid input pointers in ffi_cfm_init_process_msg2");
-        return ptr::null_mut();
+       rng_ptr.is_null() || out_ot_seeds_ptr.is_null() || out_ot_seeds_size.is_null() {
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_cfm_init_process_msg2".to_string());
     }
 
     // eprintln!(
@@ -587,8 +662,7 @@ pub extern "C" fn ffi_cfm_init_process_msg2(
     let state_ob: CFMInitStateOB = match bincode::deserialize(state_ob_bytes) {
         Ok(state) => state,
         Err(e) => {
-            eprintln!("Failed to deserialize state_ob: {:?}", e);
-            return ptr::null_mut();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize state_ob: {:?}", e));
         }
     };
 
@@ -597,8 +671,7 @@ pub extern "C" fn ffi_cfm_init_process_msg2(
     let msg2: CFMInitMsg2 = match bincode::deserialize(msg2_bytes) {
         Ok(msg) => msg,
         Err(e) => {
-            eprintln!("Failed to deserialize msg2: {:?}", e);
-            return ptr::null_mut();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize msg2: {:?}", e));
         }
     };
 
@@ -607,20 +680,18 @@ pub extern "C" fn ffi_cfm_init_process_msg2(
     let mut msg3: CFMInitMsg3 = match bincode::deserialize(msg3_bytes) {
         Ok(msg) => msg,
         Err(e) => {
-            eprintln!("Failed to deserialize msg3: {:?}", e);
-            return ptr::null_mut();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize msg3: {:?}", e));
         }
     };
 
     // Get the RNG reference
-    let rng = unsafe { &mut *(rng_ptr as *mut ThreadRng) };
+    let rng = unsafe { &mut *(rng_ptr as *mut RngHolder) };
 
     // Call the actual function
     let ot_seeds_ob: CFMInitOTSeedsOB = match cfm_init_process_msg2(state_ob, &msg2, &mut msg3, rng) {
         Ok(seeds) => seeds,
         Err(e) => {
-            eprintln!("cfm_init_process_msg2 failed: {:?}", e);
-            return ptr::null_mut();
+            return ffi_fail(CFMStatus::CFMInitFailed, format!("cfm_init_process_msg2 failed: {}", e));
         }
     };
 
@@ -631,8 +702,7 @@ pub extern "C" fn ffi_cfm_init_process_msg2(
             data
         },
         Err(e) => {
-            eprintln!("Failed to serialize ot_seeds_ob: {:?}", e);
-            return ptr::null_mut();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize ot_seeds_ob: {:?}", e));
         }
     };
 
@@ -640,18 +710,19 @@ pub extern "C" fn ffi_cfm_init_process_msg2(
     let updated_msg3_serialized = match bincode::serialize(&msg3) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize updated msg3: {:?}", e);
-            return ptr::null_mut();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize updated msg3: {:?}", e));
         }
     };
 
     // Ensure updated msg3 is correctly sized before copying back
     if updated_msg3_serialized.len() != msg3_size {
-        eprintln!(
-            "Updated msg3 size mismatch: expected {}, got {}",
-            msg3_size, updated_msg3_serialized.len()
+        return ffi_fail(
+            CFMStatus::InvalidInput,
+            format!(
+                "Updated msg3 size mismatch: expected {}, got {}",
+                msg3_size, updated_msg3_serialized.len()
+            ),
         );
-        return ptr::null_mut();
     }
 
     // Copy updated msg3 back into provided buffer
@@ -665,17 +736,17 @@ pub extern "C" fn ffi_cfm_init_process_msg2(
     let ptr = unsafe { alloc(layout) };
 
     if ptr.is_null() {
-        eprintln!("Memory allocation failed for ot_seeds_ob, size: {}", size);
-        return ptr::null_mut();
+        return ffi_fail(CFMStatus::AllocationFailed, format!("Memory allocation failed for ot_seeds_ob, size: {}", size));
     }
 
     // Copy serialized ot_seeds_ob and return
     unsafe {
         ptr.copy_from_nonoverlapping(serialized_ot_seeds.as_ptr(), size);
+        *out_ot_seeds_ptr = ptr;
         *out_ot_seeds_size = size;
     }
 
-    ptr
+    CFMStatus::Ok
 }
 
 
@@ -686,13 +757,13 @@ pub extern "C" fn ffi_cfm_init_process_msg3(
     state_cb_size: usize,
     msg3_ptr: *const u8,
     msg3_size: usize,
+    out_ot_seeds_ptr: *mut *mut u8,
     out_ot_seeds_size: *mut usize,
-) -> *mut u8 {
+) -> CFMStatus {
     if state_cb_ptr.is_null() || state_cb_size == 0 ||
        msg3_ptr.is_null() || msg3_size == 0 ||
-       out_ot_seeds_size.is_null() {
-        eprintln!("Invalid input pointers in ffi_cfm_init_process_msg3");
-        return ptr::null_mut();
+       out_ot_seeds_ptr.is_null() || out_ot_seeds_size.is_null() {
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_cfm_init_process_msg3".to_string());
     }
 
     // eprintln!(
@@ -705,8 +776,7 @@ pub extern "C" fn ffi_cfm_init_process_msg3(
     let state_cb: CFMInitStateCB = match bincode::deserialize(state_cb_bytes) {
         Ok(state) => state,
         Err(e) => {
-            eprintln!("Failed to deserialize state_cb: {:?}", e);
-            return ptr::null_mut();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize state_cb: {:?}", e));
         }
     };
 
@@ -715,8 +785,7 @@ pub extern "C" fn ffi_cfm_init_process_msg3(
     let msg3: CFMInitMsg3 = match bincode::deserialize(msg3_bytes) {
         Ok(msg) => msg,
         Err(e) => {
-            eprintln!("Failed to deserialize msg3: {:?}", e);
-            return ptr::null_mut();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize msg3: {:?}", e));
         }
     };
 
@@ -724,8 +793,7 @@ pub extern "C" fn ffi_cfm_init_process_msg3(
     let ot_seeds_cb: CFMInitOTSeedsCB = match cfm_init_process_msg3(state_cb, &msg3) {
         Ok(seeds) => seeds,
         Err(e) => {
-            eprintln!("cfm_init_process_msg3 failed: {:?}", e);
-            return ptr::null_mut();
+            return ffi_fail(CFMStatus::CFMInitFailed, format!("cfm_init_process_msg3 failed: {}", e));
         }
     };
 
@@ -736,8 +804,7 @@ pub extern "C" fn ffi_cfm_init_process_msg3(
             data
         },
         Err(e) => {
-            eprintln!("Failed to serialize ot_seeds_cb: {:?}", e);
-            return ptr::null_mut();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize ot_seeds_cb: {:?}", e));
         }
     };
 
@@ -747,28 +814,40 @@ pub extern "C" fn ffi_cfm_init_process_msg3(
     let ptr = unsafe { alloc(layout) };
 
     if ptr.is_null() {
-        eprintln!("Memory allocation failed for ot_seeds_cb, size: {}", size);
-        return ptr::null_mut();
+        return ffi_fail(CFMStatus::AllocationFailed, format!("Memory allocation failed for ot_seeds_cb, size: {}", size));
     }
 
     // Copy serialized ot_seeds_cb and return
     unsafe {
         ptr.copy_from_nonoverlapping(serialized_ot_seeds.as_ptr(), size);
+        *out_ot_seeds_ptr = ptr;
         *out_ot_seeds_size = size;
     }
 
-    ptr
+    CFMStatus::Ok
 }
 
 
 #[repr(C)]
 pub struct FFI_AbtCreateMsg1Result {
+    status: CFMStatus,
     state_cb_r1_ptr: *mut u8,
     state_cb_r1_size: usize,
     msg1_ptr: *mut u8,
     msg1_size: usize,
 }
-use rand::rngs::OsRng;
+
+impl FFIResult for FFI_AbtCreateMsg1Result {
+    fn error(status: CFMStatus) -> Self {
+        FFI_AbtCreateMsg1Result {
+            status,
+            state_cb_r1_ptr: ptr::null_mut(),
+            state_cb_r1_size: 0,
+            msg1_ptr: ptr::null_mut(),
+            msg1_size: 0,
+        }
+    }
+}
 
 #[no_mangle]
 pub extern "C" fn ffi_abt_create_msg1(
@@ -781,13 +860,7 @@ pub extern "C" fn ffi_abt_create_msg1(
     if session_id_ptr.is_null() || session_id_size == 0 ||
        ot_seeds_cb_ptr.is_null() || ot_seeds_cb_size == 0 ||
        rng_ptr.is_null() {
-        eprintln!("Invalid input pointers in ffi_abt_create_msg1");
-        return FFI_AbtCreateMsg1Result {
-            state_cb_r1_ptr: ptr::null_mut(),
-            state_cb_r1_size: 0,
-            msg1_ptr: ptr::null_mut(),
-            msg1_size: 0,
-        };
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_abt_create_msg1".to_string());
     }
 
     // eprintln!(
@@ -800,13 +873,7 @@ pub extern "C" fn ffi_abt_create_msg1(
     let session_id: [u8; 32] = match bincode::deserialize(session_id_bytes) {
         Ok(id) => id,
         Err(e) => {
-            eprintln!("Failed to deserialize session_id: {:?}", e);
-            return FFI_AbtCreateMsg1Result {
-                state_cb_r1_ptr: ptr::null_mut(),
-                state_cb_r1_size: 0,
-                msg1_ptr: ptr::null_mut(),
-                msg1_size: 0,
-            };
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize session_id: {:?}", e));
         }
     };
 
@@ -815,24 +882,23 @@ pub extern "C" fn ffi_abt_create_msg1(
     let ot_seeds_cb: CFMInitOTSeedsCB = match bincode::deserialize(ot_seeds_cb_bytes) {
         Ok(seeds) => seeds,
         Err(e) => {
-            eprintln!("Failed to deserialize ot_seeds_cb: {:?}", e);
-            return FFI_AbtCreateMsg1Result {
-                state_cb_r1_ptr: ptr::null_mut(),
-                state_cb_r1_size: 0,
-                msg1_ptr: ptr::null_mut(),
-                msg1_size: 0,
-            };
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize ot_seeds_cb: {:?}", e));
         }
     };
 
     // Get RNG reference
-    let rng = unsafe { &mut *(rng_ptr as *mut OsRng) };
+    let rng = unsafe { &mut *(rng_ptr as *mut RngHolder) };
 
     // Call `abt_create_msg1` function (with P, eta_i, eta_m inside)
     let p = P;
-    let eta_i = NUMBER_OF_SHARES;
+    let eta_i = ABT_SHARES;
     let eta_m = NUMBER_OF_AUTH_BEAVER_TRIPLES;
-    let (state_cb_r1, msg1) = abt_create_msg1(&session_id, &ot_seeds_cb, p, eta_i, eta_m, rng);
+    let (state_cb_r1, msg1) = match abt_create_msg1(&session_id, &ot_seeds_cb, p, eta_i, eta_m, rng) {
+        Ok(result) => result,
+        Err(e) => {
+            return ffi_fail((&e).into(), format!("abt_create_msg1 failed: {}", e));
+        }
+    };
 
     // Serialize state_cb_r1
     let serialized_state_cb_r1 = match bincode::serialize(&state_cb_r1) {
@@ -841,13 +907,7 @@ pub extern "C" fn ffi_abt_create_msg1(
             data
         },
         Err(e) => {
-            eprintln!("Failed to serialize state_cb_r1: {:?}", e);
-            return FFI_AbtCreateMsg1Result {
-                state_cb_r1_ptr: ptr::null_mut(),
-                state_cb_r1_size: 0,
-                msg1_ptr: ptr::null_mut(),
-                msg1_size: 0,
-            };
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize state_cb_r1: {:?}", e));
         }
     };
 
@@ -858,13 +918,7 @@ pub extern "C" fn ffi_abt_create_msg1(
             data
         },
         Err(e) => {
-            eprintln!("Failed to serialize msg1: {:?}", e);
-            return FFI_AbtCreateMsg1Result {
-                state_cb_r1_ptr: ptr::null_mut(),
-                state_cb_r1_size: 0,
-                msg1_ptr: ptr::null_mut(),
-                msg1_size: 0,
-            };
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize msg1: {:?}", e));
         }
     };
 
@@ -873,13 +927,7 @@ pub extern "C" fn ffi_abt_create_msg1(
     let state_cb_r1_layout = Layout::array::<u8>(state_cb_r1_size).unwrap();
     let state_cb_r1_ptr = unsafe { alloc(state_cb_r1_layout) };
     if state_cb_r1_ptr.is_null() {
-        eprintln!("Memory allocation failed for state_cb_r1, size: {}", state_cb_r1_size);
-        return FFI_AbtCreateMsg1Result {
-            state_cb_r1_ptr: ptr::null_mut(),
-            state_cb_r1_size: 0,
-            msg1_ptr: ptr::null_mut(),
-            msg1_size: 0,
-        };
+        return ffi_fail(CFMStatus::AllocationFailed, format!("Memory allocation failed for state_cb_r1, size: {}", state_cb_r1_size));
     }
     unsafe {
         state_cb_r1_ptr.copy_from_nonoverlapping(serialized_state_cb_r1.as_ptr(), state_cb_r1_size);
@@ -890,13 +938,7 @@ pub extern "C" fn ffi_abt_create_msg1(
     let msg1_layout = Layout::array::<u8>(msg1_size).unwrap();
     let msg1_ptr = unsafe { alloc(msg1_layout) };
     if msg1_ptr.is_null() {
-        eprintln!("Memory allocation failed for msg1, size: {}", msg1_size);
-        return FFI_AbtCreateMsg1Result {
-            state_cb_r1_ptr: ptr::null_mut(),
-            state_cb_r1_size: 0,
-            msg1_ptr: ptr::null_mut(),
-            msg1_size: 0,
-        };
+        return ffi_fail(CFMStatus::AllocationFailed, format!("Memory allocation failed for msg1, size: {}", msg1_size));
     }
     unsafe {
         msg1_ptr.copy_from_nonoverlapping(serialized_msg1.as_ptr(), msg1_size);
@@ -904,6 +946,7 @@ pub extern "C" fn ffi_abt_create_msg1(
 
     // Return result struct with pointers and sizes
     FFI_AbtCreateMsg1Result {
+        status: CFMStatus::Ok,
         state_cb_r1_ptr,
         state_cb_r1_size,
         msg1_ptr,
@@ -914,6 +957,7 @@ pub extern "C" fn ffi_abt_create_msg1(
 
 #[repr(C)]
 pub struct FFI_AbtProcessMsg1Result {
+    status: CFMStatus,
     state_ob_r1_ptr: *mut u8,
     state_ob_r1_size: usize,
     shares_ob_ptr: *mut u8,
@@ -924,9 +968,10 @@ pub struct FFI_AbtProcessMsg1Result {
     msg2_size: usize,
 }
 
-impl Default for FFI_AbtProcessMsg1Result {
-    fn default() -> Self {
+impl FFIResult for FFI_AbtProcessMsg1Result {
+    fn error(status: CFMStatus) -> Self {
         FFI_AbtProcessMsg1Result {
+            status,
             state_ob_r1_ptr: ptr::null_mut(),
             state_ob_r1_size: 0,
             shares_ob_ptr: ptr::null_mut(),
@@ -954,8 +999,7 @@ pub extern "C" fn ffi_abt_process_msg1(
        ot_seeds_ob_ptr.is_null() || ot_seeds_ob_size == 0 ||
        msg1_ptr.is_null() || msg1_size == 0 ||
        rng_ptr.is_null() {
-        eprintln!("Invalid input pointers in ffi_abt_process_msg1");
-        return FFI_AbtProcessMsg1Result::default();
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_abt_process_msg1".to_string());
 
     }
 
@@ -969,8 +1013,7 @@ pub extern "C" fn ffi_abt_process_msg1(
     let session_id: [u8; 32] = match bincode::deserialize(session_id_bytes) {
         Ok(id) => id,
         Err(e) => {
-            eprintln!("Failed to deserialize session_id: {:?}", e);
-            return FFI_AbtProcessMsg1Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize session_id: {:?}", e));
         }
     };
 
@@ -979,8 +1022,7 @@ pub extern "C" fn ffi_abt_process_msg1(
     let ot_seeds_ob: CFMInitOTSeedsOB = match bincode::deserialize(ot_seeds_ob_bytes) {
         Ok(seeds) => seeds,
         Err(e) => {
-            eprintln!("Failed to deserialize ot_seeds_ob: {:?}", e);
-            return FFI_AbtProcessMsg1Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize ot_seeds_ob: {:?}", e));
         }
     };
 
@@ -989,24 +1031,22 @@ pub extern "C" fn ffi_abt_process_msg1(
     let msg1: ABTMsg1 = match bincode::deserialize(msg1_bytes) {
         Ok(msg) => msg,
         Err(e) => {
-            eprintln!("Failed to deserialize msg1: {:?}", e);
-            return FFI_AbtProcessMsg1Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize msg1: {:?}", e));
         }
     };
 
     // Get RNG reference
-    let rng = unsafe { &mut *(rng_ptr as *mut ThreadRng) };
+    let rng = unsafe { &mut *(rng_ptr as *mut RngHolder) };
 
     // Call `abt_process_msg1` function (with P, eta_i, eta_m inside)
     let p = P;
-    let eta_i = NUMBER_OF_SHARES;
+    let eta_i = ABT_SHARES;
     let eta_m = NUMBER_OF_AUTH_BEAVER_TRIPLES;
     let (state_ob_r1, mut shares_ob, mut auth_triples_ob, msg2) =
         match abt_process_msg1(&session_id, &ot_seeds_ob, p, eta_i, eta_m, &msg1, rng) {
             Ok(res) => res,
             Err(e) => {
-                eprintln!("Error in abt_process_msg1: {:?}", e);
-                return FFI_AbtProcessMsg1Result::default();
+                return ffi_fail((&e).into(), format!("Error in abt_process_msg1: {}", e));
             }
         };
 
@@ -1014,32 +1054,28 @@ pub extern "C" fn ffi_abt_process_msg1(
     let serialized_state_ob_r1 = match bincode::serialize(&state_ob_r1) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize state_ob_r1: {:?}", e);
-            return FFI_AbtProcessMsg1Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize state_ob_r1: {:?}", e));
         }
     };
 
     let serialized_shares_ob = match bincode::serialize(&shares_ob) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize shares_ob: {:?}", e);
-            return FFI_AbtProcessMsg1Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize shares_ob: {:?}", e));
         }
     };
 
     let serialized_auth_triples_ob = match bincode::serialize(&auth_triples_ob) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize auth_triples_ob: {:?}", e);
-            return FFI_AbtProcessMsg1Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize auth_triples_ob: {:?}", e));
         }
     };
 
     let serialized_msg2 = match bincode::serialize(&msg2) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize msg2: {:?}", e);
-            return FFI_AbtProcessMsg1Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize msg2: {:?}", e));
         }
     };
 
@@ -1050,8 +1086,7 @@ pub extern "C" fn ffi_abt_process_msg1(
     let msg2_ptr = unsafe { alloc(Layout::array::<u8>(serialized_msg2.len()).unwrap()) };
 
     if state_ob_r1_ptr.is_null() || shares_ob_ptr.is_null() || auth_triples_ob_ptr.is_null() || msg2_ptr.is_null() {
-        eprintln!("Memory allocation failed!");
-        return FFI_AbtProcessMsg1Result::default();
+        return ffi_fail(CFMStatus::AllocationFailed, "Memory allocation failed!".to_string());
     }
 
     unsafe {
@@ -1062,6 +1097,7 @@ pub extern "C" fn ffi_abt_process_msg1(
     }
 
     FFI_AbtProcessMsg1Result {
+        status: CFMStatus::Ok,
         state_ob_r1_ptr,
         state_ob_r1_size: serialized_state_ob_r1.len(),
         shares_ob_ptr,
@@ -1076,6 +1112,7 @@ pub extern "C" fn ffi_abt_process_msg1(
 
 #[repr(C)]
 pub struct FFI_AbtProcessMsg2Result {
+    status: CFMStatus,
     state_cb_r2_ptr: *mut u8,
     state_cb_r2_size: usize,
     shares_cb_ptr: *mut u8,
@@ -1086,9 +1123,10 @@ pub struct FFI_AbtProcessMsg2Result {
     msg3_size: usize,
 }
 
-impl Default for FFI_AbtProcessMsg2Result {
-    fn default() -> Self {
+impl FFIResult for FFI_AbtProcessMsg2Result {
+    fn error(status: CFMStatus) -> Self {
         FFI_AbtProcessMsg2Result {
+            status,
             state_cb_r2_ptr: ptr::null_mut(),
             state_cb_r2_size: 0,
             shares_cb_ptr: ptr::null_mut(),
@@ -1116,51 +1154,64 @@ pub extern "C" fn ffi_abt_process_msg2(
        ot_seeds_cb_ptr.is_null() || ot_seeds_cb_size == 0 ||
        msg2_ptr.is_null() || msg2_size == 0 ||
        rng_ptr.is_null() {
-        eprintln!("Invalid input pointers in ffi_abt_process_msg2");
-        return FFI_AbtProcessMsg2Result::default()
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_abt_process_msg2".to_string());
     }
 
     // Deserialize inputs
     let state_cb_r1_bytes = unsafe { slice::from_raw_parts(state_cb_r1_ptr, state_cb_r1_size) };
     let state_cb_r1: ABTStateCBR1 = match bincode::deserialize(state_cb_r1_bytes) {
         Ok(data) => data,
-        Err(_) => return FFI_AbtProcessMsg2Result::default(),
+        Err(e) => return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize state_cb_r1: {:?}", e)),
     };
 
     let ot_seeds_cb_bytes = unsafe { slice::from_raw_parts(ot_seeds_cb_ptr, ot_seeds_cb_size) };
     let ot_seeds_cb: CFMInitOTSeedsCB = match bincode::deserialize(ot_seeds_cb_bytes) {
         Ok(data) => data,
-        Err(_) => return FFI_AbtProcessMsg2Result::default(),
+        Err(e) => return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize ot_seeds_cb: {:?}", e)),
     };
 
     let msg2_bytes = unsafe { slice::from_raw_parts(msg2_ptr, msg2_size) };
     let msg2: ABTMsg2 = match bincode::deserialize(msg2_bytes) {
         Ok(data) => data,
-        Err(_) => return FFI_AbtProcessMsg2Result::default(),
+        Err(e) => return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize msg2: {:?}", e)),
     };
 
     // Get RNG reference
-    let rng = unsafe { &mut *(rng_ptr as *mut ThreadRng) };
+    let rng = unsafe { &mut *(rng_ptr as *mut RngHolder) };
 
     // Call Rust function
     let (state_cb_r2, shares_cb, auth_triples_cb, msg3) = match abt_process_msg2(&state_cb_r1, &ot_seeds_cb, &msg2, rng) {
         Ok(result) => result,
-        Err(_) => return FFI_AbtProcessMsg2Result::default(),
+        Err(e) => return ffi_fail((&e).into(), format!("Error in abt_process_msg2: {}", e)),
     };
 
     // Serialize output
-    let state_cb_r2_serialized = bincode::serialize(&state_cb_r2).unwrap_or_default();
-    let shares_cb_serialized = bincode::serialize(&shares_cb).unwrap_or_default();
-    let auth_triples_cb_serialized = bincode::serialize(&auth_triples_cb).unwrap_or_default();
-    let msg3_serialized = bincode::serialize(&msg3).unwrap_or_default();
+    let serialized = (
+        bincode::serialize(&state_cb_r2),
+        bincode::serialize(&shares_cb),
+        bincode::serialize(&auth_triples_cb),
+        bincode::serialize(&msg3),
+    );
+    let (state_cb_r2_serialized, shares_cb_serialized, auth_triples_cb_serialized, msg3_serialized) = match serialized {
+        (Ok(a), Ok(b), Ok(c), Ok(d)) => (a, b, c, d),
+        _ => return ffi_fail(CFMStatus::Serialization, "Failed to serialize abt_process_msg2 output".to_string()),
+    };
 
     // Allocate memory for output
     let state_cb_r2_ptr = allocate_memory(&state_cb_r2_serialized);
     let shares_cb_ptr = allocate_memory(&shares_cb_serialized);
     let auth_triples_cb_ptr = allocate_memory(&auth_triples_cb_serialized);
     let msg3_ptr = allocate_memory(&msg3_serialized);
+    if state_cb_r2_ptr.is_null() || shares_cb_ptr.is_null() || auth_triples_cb_ptr.is_null() || msg3_ptr.is_null() {
+        free_buffer(state_cb_r2_ptr, state_cb_r2_serialized.len());
+        free_buffer(shares_cb_ptr, shares_cb_serialized.len());
+        free_buffer(auth_triples_cb_ptr, auth_triples_cb_serialized.len());
+        free_buffer(msg3_ptr, msg3_serialized.len());
+        return ffi_fail(CFMStatus::AllocationFailed, "Memory allocation failed for abt_process_msg2 output".to_string());
+    }
 
     FFI_AbtProcessMsg2Result {
+        status: CFMStatus::Ok,
         state_cb_r2_ptr,
         state_cb_r2_size: state_cb_r2_serialized.len(),
         shares_cb_ptr,
@@ -1187,6 +1238,7 @@ fn allocate_memory(data: &[u8]) -> *mut u8 {
 
 #[repr(C)]
 pub struct FFI_AbtProcessMsg3Result {
+    status: CFMStatus,
     state_ob_r2_ptr: *mut u8,
     state_ob_r2_size: usize,
     shares_ob_ptr: *mut u8,
@@ -1197,9 +1249,10 @@ pub struct FFI_AbtProcessMsg3Result {
     msg4_size: usize,
 }
 
-impl Default for FFI_AbtProcessMsg3Result {
-    fn default() -> Self {
+impl FFIResult for FFI_AbtProcessMsg3Result {
+    fn error(status: CFMStatus) -> Self {
         FFI_AbtProcessMsg3Result {
+            status,
             state_ob_r2_ptr: std::ptr::null_mut(),
             state_ob_r2_size: 0,
             shares_ob_ptr: std::ptr::null_mut(),
@@ -1227,8 +1280,7 @@ pub extern "C" fn ffi_abt_process_msg3(
        shares_ob_ptr.is_null() || shares_ob_size == 0 ||
        auth_triples_ob_ptr.is_null() || auth_triples_ob_size == 0 ||
        msg3_ptr.is_null() || msg3_size == 0 {
-        eprintln!("Invalid input pointers in ffi_abt_process_msg3");
-        return FFI_AbtProcessMsg3Result::default()
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_abt_process_msg3".to_string());
     }
 
     // Deserialize state_ob_r1
@@ -1236,8 +1288,7 @@ pub extern "C" fn ffi_abt_process_msg3(
     let state_ob_r1: ABTStateOBR1 = match bincode::deserialize(state_ob_r1_bytes) {
         Ok(state) => state,
         Err(e) => {
-            eprintln!("Failed to deserialize state_ob_r1: {:?}", e);
-            return FFI_AbtProcessMsg3Result::default()
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize state_ob_r1: {:?}", e));
         }
     };
 
@@ -1246,8 +1297,7 @@ pub extern "C" fn ffi_abt_process_msg3(
     let mut shares_ob: Vec<Share>  = match bincode::deserialize(shares_ob_bytes) {
         Ok(shares) => shares,
         Err(e) => {
-            eprintln!("Failed to deserialize shares_ob: {:?}", e);
-            return FFI_AbtProcessMsg3Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize shares_ob: {:?}", e));
         }
     };
 
@@ -1256,8 +1306,7 @@ pub extern "C" fn ffi_abt_process_msg3(
     let mut auth_triples_ob: Vec<TripleShare> = match bincode::deserialize(auth_triples_ob_bytes) {
         Ok(triples) => triples,
         Err(e) => {
-            eprintln!("Failed to deserialize auth_triples_ob: {:?}", e);
-            return FFI_AbtProcessMsg3Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize auth_triples_ob: {:?}", e));
         }
     };
 
@@ -1266,8 +1315,7 @@ pub extern "C" fn ffi_abt_process_msg3(
     let msg3: ABTMsg3 = match bincode::deserialize(msg3_bytes) {
         Ok(msg) => msg,
         Err(e) => {
-            eprintln!("Failed to deserialize msg3: {:?}", e);
-            return FFI_AbtProcessMsg3Result::default()
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize msg3: {:?}", e));
         }
     };
 
@@ -1275,8 +1323,7 @@ pub extern "C" fn ffi_abt_process_msg3(
     let (state_ob_r2, msg4) = match abt_process_msg3(&state_ob_r1, &mut shares_ob, &mut auth_triples_ob, &msg3) {
         Ok(result) => result,
         Err(e) => {
-            eprintln!("Error in abt_process_msg3: {:?}", e);
-            return FFI_AbtProcessMsg3Result::default()
+            return ffi_fail((&e).into(), format!("Error in abt_process_msg3: {}", e));
         }
     };
 
@@ -1284,32 +1331,28 @@ pub extern "C" fn ffi_abt_process_msg3(
     let serialized_state_ob_r2 = match bincode::serialize(&state_ob_r2) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize state_ob_r2: {:?}", e);
-            return FFI_AbtProcessMsg3Result::default()
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize state_ob_r2: {:?}", e));
         }
     };
 
     let serialized_shares_ob = match bincode::serialize(&shares_ob) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize updated shares_ob: {:?}", e);
-            return FFI_AbtProcessMsg3Result::default()
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize updated shares_ob: {:?}", e));
         }
     };
 
     let serialized_auth_triples_ob = match bincode::serialize(&auth_triples_ob) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize updated auth_triples_ob: {:?}", e);
-            return FFI_AbtProcessMsg3Result::default()
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize updated auth_triples_ob: {:?}", e));
         }
     };
 
     let serialized_msg4 = match bincode::serialize(&msg4) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize msg4: {:?}", e);
-            return FFI_AbtProcessMsg3Result::default()
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize msg4: {:?}", e));
         }
     };
 
@@ -1320,8 +1363,7 @@ pub extern "C" fn ffi_abt_process_msg3(
     let msg4_ptr = unsafe { alloc(Layout::array::<u8>(serialized_msg4.len()).unwrap()) };
 
     if state_ob_r2_ptr.is_null() || shares_ob_ptr.is_null() || auth_triples_ob_ptr.is_null() || msg4_ptr.is_null() {
-        eprintln!("Memory allocation failed for one or more return values.");
-        return FFI_AbtProcessMsg3Result::default()
+        return ffi_fail(CFMStatus::AllocationFailed, "Memory allocation failed for one or more return values.".to_string());
     }
 
     // Copy serialized data
@@ -1334,6 +1376,7 @@ pub extern "C" fn ffi_abt_process_msg3(
 
     // Return struct with allocated memory
     FFI_AbtProcessMsg3Result {
+        status: CFMStatus::Ok,
         state_ob_r2_ptr,
         state_ob_r2_size: serialized_state_ob_r2.len(),
         shares_ob_ptr,
@@ -1348,13 +1391,15 @@ pub extern "C" fn ffi_abt_process_msg3(
 
 #[repr(C)]
 pub struct FFI_AbtProcessMsg4Result {
+    status: CFMStatus,
     msg5_ptr: *mut u8,
     msg5_size: usize,
 }
 
-impl Default for FFI_AbtProcessMsg4Result {
-    fn default() -> Self {
+impl FFIResult for FFI_AbtProcessMsg4Result {
+    fn error(status: CFMStatus) -> Self {
         FFI_AbtProcessMsg4Result {
+            status,
             msg5_ptr: std::ptr::null_mut(),
             msg5_size: 0,
         }
@@ -1373,8 +1418,7 @@ pub extern "C" fn ffi_abt_process_msg4(
     if state_cb_r2_ptr.is_null() || state_cb_r2_size == 0 ||
        auth_triples_cb_ptr.is_null() || auth_triples_cb_size == 0 ||
        msg4_ptr.is_null() || msg4_size == 0 {
-        eprintln!("Invalid input pointers in ffi_abt_process_msg4");
-        return FFI_AbtProcessMsg4Result::default();
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_abt_process_msg4".to_string());
     }
 
     // Deserialize state_cb_r2
@@ -1382,8 +1426,7 @@ pub extern "C" fn ffi_abt_process_msg4(
     let state_cb_r2: ABTStateCBR2 = match bincode::deserialize(state_cb_r2_bytes) {
         Ok(state) => state,
         Err(e) => {
-            eprintln!("Failed to deserialize state_cb_r2: {:?}", e);
-            return FFI_AbtProcessMsg4Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize state_cb_r2: {:?}", e));
         }
     };
 
@@ -1392,8 +1435,7 @@ pub extern "C" fn ffi_abt_process_msg4(
     let auth_triples_cb: Vec<TripleShare> = match bincode::deserialize(auth_triples_cb_bytes) {
         Ok(triples) => triples,
         Err(e) => {
-            eprintln!("Failed to deserialize auth_triples_cb: {:?}", e);
-            return FFI_AbtProcessMsg4Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize auth_triples_cb: {:?}", e));
         }
     };
 
@@ -1402,8 +1444,7 @@ pub extern "C" fn ffi_abt_process_msg4(
     let msg4: ABTMsg4 = match bincode::deserialize(msg4_bytes) {
         Ok(msg) => msg,
         Err(e) => {
-            eprintln!("Failed to deserialize msg4: {:?}", e);
-            return FFI_AbtProcessMsg4Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize msg4: {:?}", e));
         }
     };
 
@@ -1411,8 +1452,7 @@ pub extern "C" fn ffi_abt_process_msg4(
     let msg5 = match abt_process_msg4(&state_cb_r2, &auth_triples_cb, &msg4) {
         Ok(msg) => msg,
         Err(e) => {
-            eprintln!("Error in abt_process_msg4: {:?}", e);
-            return FFI_AbtProcessMsg4Result::default();
+            return ffi_fail((&e).into(), format!("Error in abt_process_msg4: {}", e));
         }
     };
 
@@ -1420,8 +1460,7 @@ pub extern "C" fn ffi_abt_process_msg4(
     let serialized_msg5 = match bincode::serialize(&msg5) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize msg5: {:?}", e);
-            return FFI_AbtProcessMsg4Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize msg5: {:?}", e));
         }
     };
 
@@ -1431,8 +1470,7 @@ pub extern "C" fn ffi_abt_process_msg4(
     let ptr = unsafe { alloc(layout) };
 
     if ptr.is_null() {
-        eprintln!("Memory allocation failed for msg5, size: {}", size);
-        return FFI_AbtProcessMsg4Result::default();
+        return ffi_fail(CFMStatus::AllocationFailed, format!("Memory allocation failed for msg5, size: {}", size));
     }
 
     // Copy serialized msg5 data
@@ -1441,6 +1479,7 @@ pub extern "C" fn ffi_abt_process_msg4(
     }
 
     FFI_AbtProcessMsg4Result {
+        status: CFMStatus::Ok,
         msg5_ptr: ptr,
         msg5_size: size,
     }
@@ -1453,11 +1492,10 @@ pub extern "C" fn ffi_abt_process_msg5(
     state_ob_r2_size: usize,
     msg5_ptr: *const u8,
     msg5_size: usize,
-) {
+) -> CFMStatus {
     if state_ob_r2_ptr.is_null() || state_ob_r2_size == 0 ||
        msg5_ptr.is_null() || msg5_size == 0 {
-        eprintln!("Invalid input pointers in ffi_abt_process_msg5");
-        return;
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_abt_process_msg5".to_string());
     }
 
     // Deserialize state_ob_r2
@@ -1465,8 +1503,7 @@ pub extern "C" fn ffi_abt_process_msg5(
     let state_ob_r2: ABTStateOBR2 = match bincode::deserialize(state_ob_r2_bytes) {
         Ok(state) => state,
         Err(e) => {
-            eprintln!("Failed to deserialize state_ob_r2: {:?}", e);
-            return;
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize state_ob_r2: {:?}", e));
         }
     };
 
@@ -1475,14 +1512,14 @@ pub extern "C" fn ffi_abt_process_msg5(
     let msg5: ABTMsg5 = match bincode::deserialize(msg5_bytes) {
         Ok(msg) => msg,
         Err(e) => {
-            eprintln!("Failed to deserialize msg5: {:?}", e);
-            return;
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize msg5: {:?}", e));
         }
     };
 
-    // Call the actual function
-    if let Err(e) = abt_process_msg5(&state_ob_r2, &msg5) {
-        eprintln!("Error in abt_process_msg5: {:?}", e);
+    // Call the actual function, the triples must be discarded if the MAC check fails
+    match abt_process_msg5(&state_ob_r2, &msg5) {
+        Ok(()) => CFMStatus::Ok,
+        Err(e) => ffi_fail((&e).into(), format!("Error in abt_process_msg5: {}", e)),
     }
 }
 
@@ -1491,11 +1528,11 @@ pub extern "C" fn ffi_abt_process_msg5(
 pub extern "C" fn ffi_hash_customers(
     customers_json_ptr: *const c_char,
     is_list: bool,
+    out_ptr: *mut *mut u8,
     out_size: *mut usize,
-) -> *mut u8 {
-    if customers_json_ptr.is_null() || out_size.is_null() {
-        eprintln!("Invalid input to ffi_hash_customers.");
-        return std::ptr::null_mut();
+) -> CFMStatus {
+    if customers_json_ptr.is_null() || out_ptr.is_null() || out_size.is_null() {
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input to ffi_hash_customers.".to_string());
     }
 
     // Convert C string to Rust String
@@ -1503,8 +1540,7 @@ pub extern "C" fn ffi_hash_customers(
     let customers_json = match c_str.to_str() {
         Ok(s) => s,
         Err(_) => {
-            eprintln!("Failed to convert C string.");
-            return std::ptr::null_mut();
+            return ffi_fail(CFMStatus::InvalidInput, "Failed to convert C string.".to_string());
         }
     };
 
@@ -1513,30 +1549,28 @@ pub extern "C" fn ffi_hash_customers(
         let customers: Vec<Customer> = match serde_json::from_str(customers_json) {
             Ok(list) => list,
             Err(e) => {
-                eprintln!("Failed to deserialize list of customers: {:?}", e);
-                return std::ptr::null_mut();
+                return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize list of customers: {:?}", e));
             }
         };
 
         let hashes: Vec<[u8; 32]> = customers.iter().map(|c| c.to_hash_bytes()).collect();
         match bincode::serialize(&hashes) {
             Ok(data) => data,
-            Err(_) => return std::ptr::null_mut(),
+            Err(e) => return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize hashes: {:?}", e)),
         }
     } else {
         // Deserialize JSON into a single Customer
         let customer: Customer = match serde_json::from_str(customers_json) {
             Ok(c) => c,
             Err(e) => {
-                eprintln!("Failed to deserialize single customer: {:?}", e);
-                return std::ptr::null_mut();
+                return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize single customer: {:?}", e));
             }
         };
 
         let hash_bytes = customer.to_hash_bytes();
         match bincode::serialize(&hash_bytes) {
             Ok(data) => data,
-            Err(_) => return std::ptr::null_mut(),
+            Err(e) => return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize hash: {:?}", e)),
         }
     };
 
@@ -1546,31 +1580,33 @@ pub extern "C" fn ffi_hash_customers(
     let ptr = unsafe { alloc(layout) };
 
     if ptr.is_null() {
-        eprintln!("Memory allocation failed.");
-        return std::ptr::null_mut();
+        return ffi_fail(CFMStatus::AllocationFailed, "Memory allocation failed.".to_string());
     }
 
     // Copy serialized data into allocated memory
     unsafe {
         ptr.copy_from_nonoverlapping(serialized_data.as_ptr(), size);
+        *out_ptr = ptr;
         *out_size = size;
     }
 
-    ptr
+    CFMStatus::Ok
 }
 
 
 #[repr(C)]
 pub struct FFI_CfmCreateMsg1Result {
+    status: CFMStatus,
     cfm_state_ob_r1_ptr: *mut u8,
     cfm_state_ob_r1_size: usize,
     msg1_ptr: *mut u8,
     msg1_size: usize,
 }
 
-impl Default for FFI_CfmCreateMsg1Result {
-    fn default() -> Self {
+impl FFIResult for FFI_CfmCreateMsg1Result {
+    fn error(status: CFMStatus) -> Self {
         FFI_CfmCreateMsg1Result {
+            status,
             cfm_state_ob_r1_ptr: std::ptr::null_mut(),
             cfm_state_ob_r1_size: 0,
             msg1_ptr: std::ptr::null_mut(),
@@ -1583,20 +1619,24 @@ impl Default for FFI_CfmCreateMsg1Result {
 pub extern "C" fn ffi_cfm_create_msg1(
     session_id_ptr: *const u8,
     session_id_size: usize,
+    output_policy: u8,
     big_l: u64,
+    private_limit: bool,
     big_x: u64,
     customer_y_bytes_ptr: *const u8,
     customer_y_bytes_size: usize,
+    ot_seeds_ob_ptr: *const u8,
+    ot_seeds_ob_size: usize,
     shares_ob_ptr: *const u8,
     shares_ob_size: usize,
     rng_ptr: *mut c_void,
 ) -> FFI_CfmCreateMsg1Result {
     if session_id_ptr.is_null() || session_id_size == 0 ||
        customer_y_bytes_ptr.is_null() || customer_y_bytes_size == 0 ||
+       ot_seeds_ob_ptr.is_null() || ot_seeds_ob_size == 0 ||
        shares_ob_ptr.is_null() || shares_ob_size == 0 ||
        rng_ptr.is_null() {
-        eprintln!("Invalid input pointers in ffi_cfm_create_msg1");
-        return FFI_CfmCreateMsg1Result::default()
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_cfm_create_msg1".to_string());
     }
 
     // Deserialize session_id
@@ -1604,13 +1644,17 @@ pub extern "C" fn ffi_cfm_create_msg1(
     let session_id: [u8; 32] = match bincode::deserialize(session_id_bytes) {
         Ok(id) => id,
         Err(e) => {
-            eprintln!("Failed to deserialize session_id: {:?}", e);
-            return FFI_CfmCreateMsg1Result::default()
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize session_id: {:?}", e));
         }
     };
 
-    // Convert big_l and big_x into U64
-    let big_l = U64::from(big_l);
+    let output_policy = match OutputPolicy::try_from(output_policy) {
+        Ok(policy) => policy,
+        Err(e) => return ffi_fail((&e).into(), format!("Invalid output policy {}", output_policy)),
+    };
+
+    // Convert big_l and big_x into U64, OB ignores big_l if the limit is private
+    let big_l = (!private_limit).then(|| U64::from(big_l));
     let big_x = U64::from(big_x);
 
     // Deserialize customer_y_bytes
@@ -1618,8 +1662,16 @@ pub extern "C" fn ffi_cfm_create_msg1(
     let customer_y_bytes: [u8; 32] = match bincode::deserialize(customer_y_bytes_bytes) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to deserialize customer_y_bytes: {:?}", e);
-            return FFI_CfmCreateMsg1Result::default()
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize customer_y_bytes: {:?}", e));
+        }
+    };
+
+    // Deserialize ot_seeds_ob
+    let ot_seeds_ob_bytes = unsafe { slice::from_raw_parts(ot_seeds_ob_ptr, ot_seeds_ob_size) };
+    let ot_seeds_ob: CFMInitOTSeedsOB = match bincode::deserialize(ot_seeds_ob_bytes) {
+        Ok(seeds) => seeds,
+        Err(e) => {
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize ot_seeds_ob: {:?}", e));
         }
     };
 
@@ -1628,31 +1680,40 @@ pub extern "C" fn ffi_cfm_create_msg1(
     let shares_ob: Vec<Share> = match bincode::deserialize(shares_ob_bytes) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to deserialize shares_ob: {:?}", e);
-            return FFI_CfmCreateMsg1Result::default()
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize shares_ob: {:?}", e));
         }
     };
+    let Some(shares_ob) = cfm_shares(&shares_ob) else {
+        return ffi_fail(CFMStatus::InvalidInput, "Wrong number of shares_ob".to_string());
+    };
 
     // Get RNG reference
-    let rng = unsafe { &mut *(rng_ptr as *mut ThreadRng) };
+    let rng = unsafe { &mut *(rng_ptr as *mut RngHolder) };
 
-    // Call the actual function
-    let (cfm_state_ob_r1, msg1) = cfm_create_msg1(
+    // Call the actual function, the per-message API keeps no ledger
+    let (cfm_state_ob_r1, msg1) = match cfm_create_msg1(
         &session_id,
         P,
+        output_policy,
         big_l,
         big_x,
         &customer_y_bytes,
-        &shares_ob,
+        &Ledger::new(),
+        &ot_seeds_ob,
+        shares_ob,
         rng,
-    );
+    ) {
+        Ok(result) => result,
+        Err(e) => {
+            return ffi_fail((&e).into(), format!("cfm_create_msg1 failed: {}", e));
+        }
+    };
 
     // Serialize cfm_state_ob_r1
     let serialized_cfm_state_ob_r1 = match bincode::serialize(&cfm_state_ob_r1) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize cfm_state_ob_r1: {:?}", e);
-            return FFI_CfmCreateMsg1Result::default()
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize cfm_state_ob_r1: {:?}", e));
         }
     };
 
@@ -1660,8 +1721,7 @@ pub extern "C" fn ffi_cfm_create_msg1(
     let serialized_msg1 = match bincode::serialize(&msg1) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize msg1: {:?}", e);
-            return FFI_CfmCreateMsg1Result::default()
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize msg1: {:?}", e));
         }
     };
 
@@ -1683,6 +1743,7 @@ pub extern "C" fn ffi_cfm_create_msg1(
 
     // Return the struct containing pointers and sizes
     FFI_CfmCreateMsg1Result {
+        status: CFMStatus::Ok,
         cfm_state_ob_r1_ptr,
         cfm_state_ob_r1_size,
         msg1_ptr,
@@ -1693,15 +1754,17 @@ pub extern "C" fn ffi_cfm_create_msg1(
 
 #[repr(C)]
 pub struct FFI_CfmProcessMsg1Result {
+    status: CFMStatus,
     cfm_state_cb_r1_ptr: *mut u8,
     cfm_state_cb_r1_size: usize,
     msg2_ptr: *mut u8,
     msg2_size: usize,
 }
 
-impl Default for FFI_CfmProcessMsg1Result {
-    fn default() -> Self {
+impl FFIResult for FFI_CfmProcessMsg1Result {
+    fn error(status: CFMStatus) -> Self {
         FFI_CfmProcessMsg1Result {
+            status,
             cfm_state_cb_r1_ptr: ptr::null_mut(),
             cfm_state_cb_r1_size: 0,
             msg2_ptr: ptr::null_mut(),
@@ -1714,11 +1777,17 @@ impl Default for FFI_CfmProcessMsg1Result {
 pub extern "C" fn ffi_cfm_process_msg1(
     session_id_ptr: *const u8,
     session_id_size: usize,
+    output_policy: u8,
     big_l: u64,
+    private_limit: bool,
     big_y_bytes_ptr: *const u8,
     big_y_bytes_size: usize,
     big_z_ptr: *const u64, // Now receiving a pointer to u64 integers (array)
     big_z_size: usize,     // Number of elements in big_z
+    big_l_y_ptr: *const u64, // limit of each customer, null if all have limit big_l
+    big_l_y_size: usize,
+    ot_seeds_cb_ptr: *const u8,
+    ot_seeds_cb_size: usize,
     shares_cb_ptr: *const u8,
     shares_cb_size: usize,
     msg1_ptr: *const u8,
@@ -1728,11 +1797,11 @@ pub extern "C" fn ffi_cfm_process_msg1(
     if session_id_ptr.is_null() || session_id_size == 0 ||
        big_y_bytes_ptr.is_null() || big_y_bytes_size == 0 ||
        big_z_ptr.is_null() || big_z_size == 0 ||
+       ot_seeds_cb_ptr.is_null() || ot_seeds_cb_size == 0 ||
        shares_cb_ptr.is_null() || shares_cb_size == 0 ||
        msg1_ptr.is_null() || msg1_size == 0 ||
        rng_ptr.is_null() {
-        eprintln!("Invalid input pointers in ffi_cfm_process_msg1");
-        return FFI_CfmProcessMsg1Result::default();
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_cfm_process_msg1".to_string());
     }
 
     // Deserialize session_id
@@ -1740,11 +1809,15 @@ pub extern "C" fn ffi_cfm_process_msg1(
     let session_id: [u8; 32] = match bincode::deserialize(session_id_bytes) {
         Ok(id) => id,
         Err(e) => {
-            eprintln!("Failed to deserialize session_id: {:?}", e);
-            return FFI_CfmProcessMsg1Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize session_id: {:?}", e));
         }
     };
 
+    let output_policy = match OutputPolicy::try_from(output_policy) {
+        Ok(policy) => policy,
+        Err(e) => return ffi_fail((&e).into(), format!("Invalid output policy {}", output_policy)),
+    };
+
     // Convert big_l into U64
     let big_l = U64::from(big_l);
 
@@ -1753,8 +1826,7 @@ pub extern "C" fn ffi_cfm_process_msg1(
     let big_y_bytes: Vec<[u8; 32]> = match bincode::deserialize(big_y_bytes_data) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to deserialize big_y_bytes: {:?}", e);
-            return FFI_CfmProcessMsg1Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize big_y_bytes: {:?}", e));
         }
     };
 
@@ -1790,44 +1862,68 @@ pub extern "C" fn ffi_cfm_process_msg1(
     let big_z_values = unsafe { slice::from_raw_parts(big_z_ptr, big_z_size) };
     let big_z: Vec<U64> = big_z_values.iter().map(|&val| U64::from(val)).collect();
 
+    // Per-customer limits, all big_l unless the limit is private
+    let big_l_y = big_l_y_from_ffi(big_l_y_ptr, big_l_y_size);
+    if !valid_limits(big_l, private_limit, &big_y_bytes, big_l_y.as_deref()) {
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid big_l_y in ffi_cfm_process_msg1".to_string());
+    }
+
+    // Deserialize ot_seeds_cb
+    let ot_seeds_cb_bytes = unsafe { slice::from_raw_parts(ot_seeds_cb_ptr, ot_seeds_cb_size) };
+    let ot_seeds_cb: CFMInitOTSeedsCB = match bincode::deserialize(ot_seeds_cb_bytes) {
+        Ok(seeds) => seeds,
+        Err(e) => {
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize ot_seeds_cb: {:?}", e));
+        }
+    };
+
     // Deserialize shares_cb
     let shares_cb_data = unsafe { slice::from_raw_parts(shares_cb_ptr, shares_cb_size) };
     let shares_cb: Vec<Share> = match bincode::deserialize(shares_cb_data) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to deserialize shares_cb: {:?}", e);
-            return FFI_CfmProcessMsg1Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize shares_cb: {:?}", e));
         }
     };
+    let Some(shares_cb) = cfm_shares(&shares_cb) else {
+        return ffi_fail(CFMStatus::InvalidInput, "Wrong number of shares_cb".to_string());
+    };
 
     // Deserialize msg1
     let msg1_data = unsafe { slice::from_raw_parts(msg1_ptr, msg1_size) };
     let msg1:  Box<CFMMsg1> = match bincode::deserialize(msg1_data) {
         Ok(msg) => msg,
         Err(e) => {
-            eprintln!("Failed to deserialize msg1: {:?}", e);
-            return FFI_CfmProcessMsg1Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize msg1: {:?}", e));
         }
     };
 
     // Get RNG reference
-    let rng = unsafe { &mut *(rng_ptr as *mut ThreadRng) };
+    let rng = unsafe { &mut *(rng_ptr as *mut RngHolder) };
+
+    // Fresh ledger keys with zero keys, matching the empty ledger of OB
+    let mut ledger_keys = LedgerKeys::new(&P, rng);
+    ledger_keys.assign_slots(&big_y_bytes);
 
     // Call the actual function
     let (cfm_state_cb_r1, msg2) = match cfm_process_msg1(
         &session_id,
         P,
+        output_policy,
         big_l,
+        private_limit,
         big_y_bytes,
         big_z,
-        &shares_cb,
+        big_l_y,
+        &ledger_keys,
+        &ot_seeds_cb,
+        shares_cb,
         &msg1,
         rng,
     ) {
         Ok(result) => result,
         Err(e) => {
-            eprintln!("cfm_process_msg1 failed: {:?}", e);
-            return FFI_CfmProcessMsg1Result::default();
+            return ffi_fail((&e).into(), format!("cfm_process_msg1 failed: {}", e));
         }
     };
 
@@ -1835,8 +1931,7 @@ pub extern "C" fn ffi_cfm_process_msg1(
     let serialized_cfm_state_cb_r1 = match bincode::serialize(&cfm_state_cb_r1) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize cfm_state_cb_r1: {:?}", e);
-            return FFI_CfmProcessMsg1Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize cfm_state_cb_r1: {:?}", e));
         }
     };
 
@@ -1844,8 +1939,7 @@ pub extern "C" fn ffi_cfm_process_msg1(
     let serialized_msg2 = match bincode::serialize(&msg2) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize msg2: {:?}", e);
-            return FFI_CfmProcessMsg1Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize msg2: {:?}", e));
         }
     };
 
@@ -1867,6 +1961,7 @@ pub extern "C" fn ffi_cfm_process_msg1(
 
     // Return the struct containing pointers and sizes
     FFI_CfmProcessMsg1Result {
+        status: CFMStatus::Ok,
         cfm_state_cb_r1_ptr,
         cfm_state_cb_r1_size,
         msg2_ptr,
@@ -1877,15 +1972,17 @@ pub extern "C" fn ffi_cfm_process_msg1(
 
 #[repr(C)]
 pub struct FFI_CfmProcessMsg2Result {
+    status: CFMStatus,
     pub cfm_state_ob_r2_ptr: *mut u8,
     pub cfm_state_ob_r2_size: usize,
     pub msg3_ptr: *mut u8,
     pub msg3_size: usize,
 }
 
-impl Default for FFI_CfmProcessMsg2Result {
-    fn default() -> Self {
+impl FFIResult for FFI_CfmProcessMsg2Result {
+    fn error(status: CFMStatus) -> Self {
         FFI_CfmProcessMsg2Result {
+            status,
             cfm_state_ob_r2_ptr: ptr::null_mut(),
             cfm_state_ob_r2_size: 0,
             msg3_ptr: ptr::null_mut(),
@@ -1911,8 +2008,7 @@ pub extern "C" fn ffi_cfm_process_msg2(
        auth_triples_ob_ptr.is_null() || auth_triples_ob_size == 0 ||
        msg2_ptr.is_null() || msg2_size == 0 ||
        rng_ptr.is_null() {
-        eprintln!("Invalid input pointers in ffi_cfm_process_msg2");
-        return FFI_CfmProcessMsg2Result::default();
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_cfm_process_msg2".to_string());
     }
 
     // ✅ Deserialize cfm_state_ob_r1
@@ -1920,8 +2016,7 @@ pub extern "C" fn ffi_cfm_process_msg2(
     let cfm_state_ob_r1: CFMStateOBR1 = match bincode::deserialize(cfm_state_ob_r1_bytes) {
         Ok(state) => state,
         Err(e) => {
-            eprintln!("Failed to deserialize cfm_state_ob_r1: {:?}", e);
-            return FFI_CfmProcessMsg2Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize cfm_state_ob_r1: {:?}", e));
         }
     };
 
@@ -1930,18 +2025,19 @@ pub extern "C" fn ffi_cfm_process_msg2(
     let shares_ob: Vec<Share> = match bincode::deserialize(shares_ob_bytes) {
         Ok(shares) => shares,
         Err(e) => {
-            eprintln!("Failed to deserialize shares_ob: {:?}", e);
-            return FFI_CfmProcessMsg2Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize shares_ob: {:?}", e));
         }
     };
+    let Some(shares_ob) = cfm_shares(&shares_ob) else {
+        return ffi_fail(CFMStatus::InvalidInput, "Wrong number of shares_ob".to_string());
+    };
 
     // ✅ Deserialize auth_triples_ob
     let auth_triples_ob_bytes = unsafe { slice::from_raw_parts(auth_triples_ob_ptr, auth_triples_ob_size) };
     let auth_triples_ob: Vec<TripleShare> = match bincode::deserialize(auth_triples_ob_bytes) {
         Ok(triples) => triples,
         Err(e) => {
-            eprintln!("Failed to deserialize auth_triples_ob: {:?}", e);
-            return FFI_CfmProcessMsg2Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize auth_triples_ob: {:?}", e));
         }
     };
 
@@ -1950,26 +2046,24 @@ pub extern "C" fn ffi_cfm_process_msg2(
     let msg2: Box<CFMMsg2> = match bincode::deserialize(msg2_bytes) {
         Ok(msg) => msg,
         Err(e) => {
-            eprintln!("Failed to deserialize msg2: {:?}", e);
-            return FFI_CfmProcessMsg2Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize msg2: {:?}", e));
         }
     };
 
     // ✅ Get RNG reference
-    let rng = unsafe { &mut *(rng_ptr as *mut ThreadRng) };
+    let rng = unsafe { &mut *(rng_ptr as *mut RngHolder) };
 
     // ✅ Call the actual function
     let (cfm_state_ob_r2, msg3) = match cfm_process_msg2(
         &cfm_state_ob_r1,
-        &shares_ob,
+        shares_ob,
         &auth_triples_ob,
         &msg2,
         rng,
     ) {
         Ok(result) => result,
         Err(e) => {
-            eprintln!("cfm_process_msg2 failed: {:?}", e);
-            return FFI_CfmProcessMsg2Result::default();
+            return ffi_fail((&e).into(), format!("cfm_process_msg2 failed: {}", e));
         }
     };
 
@@ -1977,8 +2071,7 @@ pub extern "C" fn ffi_cfm_process_msg2(
     let serialized_cfm_state_ob_r2 = match bincode::serialize(&cfm_state_ob_r2) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize cfm_state_ob_r2: {:?}", e);
-            return FFI_CfmProcessMsg2Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize cfm_state_ob_r2: {:?}", e));
         }
     };
 
@@ -1986,8 +2079,7 @@ pub extern "C" fn ffi_cfm_process_msg2(
     let serialized_msg3 = match bincode::serialize(&msg3) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize msg3: {:?}", e);
-            return FFI_CfmProcessMsg2Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize msg3: {:?}", e));
         }
     };
 
@@ -2007,6 +2099,7 @@ pub extern "C" fn ffi_cfm_process_msg2(
 
     // ✅ Return the struct containing pointers and sizes
     FFI_CfmProcessMsg2Result {
+        status: CFMStatus::Ok,
         cfm_state_ob_r2_ptr,
         cfm_state_ob_r2_size,
         msg3_ptr,
@@ -2017,15 +2110,17 @@ pub extern "C" fn ffi_cfm_process_msg2(
 
 #[repr(C)]
 pub struct FFI_CfmProcessMsg3Result {
+    status: CFMStatus,
     pub cfm_state_cb_r2_ptr: *mut u8,
     pub cfm_state_cb_r2_size: usize,
     pub msg4_ptr: *mut u8,
     pub msg4_size: usize,
 }
 
-impl Default for FFI_CfmProcessMsg3Result {
-    fn default() -> Self {
+impl FFIResult for FFI_CfmProcessMsg3Result {
+    fn error(status: CFMStatus) -> Self {
         FFI_CfmProcessMsg3Result {
+            status,
             cfm_state_cb_r2_ptr: std::ptr::null_mut(),
             cfm_state_cb_r2_size: 0,
             msg4_ptr: std::ptr::null_mut(),
@@ -2050,8 +2145,7 @@ pub extern "C" fn ffi_cfm_process_msg3(
        shares_cb_ptr.is_null() || shares_cb_size == 0 ||
        auth_triples_cb_ptr.is_null() || auth_triples_cb_size == 0 ||
        msg3_ptr.is_null() || msg3_size == 0 {
-        eprintln!("Invalid input pointers in ffi_cfm_process_msg3");
-        return FFI_CfmProcessMsg3Result::default();
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_cfm_process_msg3".to_string());
     }
 
     // Deserialize `cfm_state_cb_r1`
@@ -2059,8 +2153,7 @@ pub extern "C" fn ffi_cfm_process_msg3(
     let cfm_state_cb_r1: Box<CFMStateCBR1> = match bincode::deserialize(cfm_state_cb_r1_bytes) {
         Ok(state) => state,
         Err(e) => {
-            eprintln!("Failed to deserialize cfm_state_cb_r1: {:?}", e);
-            return FFI_CfmProcessMsg3Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize cfm_state_cb_r1: {:?}", e));
         }
     };
 
@@ -2069,18 +2162,19 @@ pub extern "C" fn ffi_cfm_process_msg3(
     let shares_cb: Vec<Share> = match bincode::deserialize(shares_cb_bytes) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to deserialize shares_cb: {:?}", e);
-            return FFI_CfmProcessMsg3Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize shares_cb: {:?}", e));
         }
     };
+    let Some(shares_cb) = cfm_shares(&shares_cb) else {
+        return ffi_fail(CFMStatus::InvalidInput, "Wrong number of shares_cb".to_string());
+    };
 
     // Deserialize `auth_triples_cb`
     let auth_triples_cb_bytes = unsafe { slice::from_raw_parts(auth_triples_cb_ptr, auth_triples_cb_size) };
     let auth_triples_cb: Vec<TripleShare> = match bincode::deserialize(auth_triples_cb_bytes) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to deserialize auth_triples_cb: {:?}", e);
-            return FFI_CfmProcessMsg3Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize auth_triples_cb: {:?}", e));
         }
     };
 
@@ -2089,17 +2183,15 @@ pub extern "C" fn ffi_cfm_process_msg3(
     let msg3: Box<CFMMsg3> = match bincode::deserialize(msg3_bytes) {
         Ok(msg) => msg,
         Err(e) => {
-            eprintln!("Failed to deserialize msg3: {:?}", e);
-            return FFI_CfmProcessMsg3Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize msg3: {:?}", e));
         }
     };
 
     // Call `cfm_process_msg3`
-    let (cfm_state_cb_r2, msg4) = match cfm_process_msg3(cfm_state_cb_r1, &shares_cb, &auth_triples_cb, &msg3) {
+    let (cfm_state_cb_r2, msg4) = match cfm_process_msg3(cfm_state_cb_r1, shares_cb, &auth_triples_cb, &msg3) {
         Ok(result) => result,
         Err(e) => {
-            eprintln!("cfm_process_msg3 failed: {:?}", e);
-            return FFI_CfmProcessMsg3Result::default();
+            return ffi_fail((&e).into(), format!("cfm_process_msg3 failed: {}", e));
         }
     };
 
@@ -2107,8 +2199,7 @@ pub extern "C" fn ffi_cfm_process_msg3(
     let serialized_cfm_state_cb_r2 = match bincode::serialize(&cfm_state_cb_r2) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize cfm_state_cb_r2: {:?}", e);
-            return FFI_CfmProcessMsg3Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize cfm_state_cb_r2: {:?}", e));
         }
     };
 
@@ -2116,8 +2207,7 @@ pub extern "C" fn ffi_cfm_process_msg3(
     let serialized_msg4 = match bincode::serialize(&msg4) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize msg4: {:?}", e);
-            return FFI_CfmProcessMsg3Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize msg4: {:?}", e));
         }
     };
 
@@ -2139,6 +2229,7 @@ pub extern "C" fn ffi_cfm_process_msg3(
 
     // Return FFI struct containing pointers and sizes
     FFI_CfmProcessMsg3Result {
+        status: CFMStatus::Ok,
         cfm_state_cb_r2_ptr,
         cfm_state_cb_r2_size,
         msg4_ptr,
@@ -2146,17 +2237,19 @@ pub extern "C" fn ffi_cfm_process_msg3(
     }
 }
 
-
+#[repr(C)]
 pub struct FFI_CfmProcessMsg4Result {
+    status: CFMStatus,
     cfm_state_ob_r3_ptr: *mut u8,
     cfm_state_ob_r3_size: usize,
     msg5_ptr: *mut u8,
     msg5_size: usize,
 }
 
-impl Default for FFI_CfmProcessMsg4Result {
-    fn default() -> Self {
+impl FFIResult for FFI_CfmProcessMsg4Result {
+    fn error(status: CFMStatus) -> Self {
         FFI_CfmProcessMsg4Result {
+            status,
             cfm_state_ob_r3_ptr: ptr::null_mut(),
             cfm_state_ob_r3_size: 0,
             msg5_ptr: ptr::null_mut(),
@@ -2174,8 +2267,7 @@ pub extern "C" fn ffi_cfm_process_msg4(
 ) -> FFI_CfmProcessMsg4Result {
     if cfm_state_ob_r2_ptr.is_null() || cfm_state_ob_r2_size == 0 || 
        msg4_ptr.is_null() || msg4_size == 0 {
-        eprintln!("Invalid input pointers in ffi_cfm_process_msg4");
-        return FFI_CfmProcessMsg4Result::default();
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_cfm_process_msg4".to_string());
     }
 
     // Deserialize cfm_state_ob_r2
@@ -2183,8 +2275,7 @@ pub extern "C" fn ffi_cfm_process_msg4(
     let cfm_state_ob_r2: Box<CFMStateOBR2> = match bincode::deserialize(cfm_state_ob_r2_bytes) {
         Ok(state) => state,
         Err(e) => {
-            eprintln!("Failed to deserialize cfm_state_ob_r2: {:?}", e);
-            return FFI_CfmProcessMsg4Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize cfm_state_ob_r2: {:?}", e));
         }
     };
 
@@ -2193,8 +2284,7 @@ pub extern "C" fn ffi_cfm_process_msg4(
     let msg4: Box<CFMMsg4> = match bincode::deserialize(msg4_bytes) {
         Ok(msg) => msg,
         Err(e) => {
-            eprintln!("Failed to deserialize msg4: {:?}", e);
-            return FFI_CfmProcessMsg4Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize msg4: {:?}", e));
         }
     };
 
@@ -2202,8 +2292,7 @@ pub extern "C" fn ffi_cfm_process_msg4(
     let (cfm_state_ob_r3, msg5) = match cfm_process_msg4(cfm_state_ob_r2, &msg4) {
         Ok(result) => result,
         Err(e) => {
-            eprintln!("Error in cfm_process_msg4: {:?}", e);
-            return FFI_CfmProcessMsg4Result::default();
+            return ffi_fail((&e).into(), format!("Error in cfm_process_msg4: {}", e));
         }
     };
 
@@ -2211,8 +2300,7 @@ pub extern "C" fn ffi_cfm_process_msg4(
     let serialized_cfm_state_ob_r3 = match bincode::serialize(&cfm_state_ob_r3) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize cfm_state_ob_r3: {:?}", e);
-            return FFI_CfmProcessMsg4Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize cfm_state_ob_r3: {:?}", e));
         }
     };
 
@@ -2220,8 +2308,7 @@ pub extern "C" fn ffi_cfm_process_msg4(
     let serialized_msg5 = match bincode::serialize(&msg5) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize msg5: {:?}", e);
-            return FFI_CfmProcessMsg4Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize msg5: {:?}", e));
         }
     };
 
@@ -2243,6 +2330,7 @@ pub extern "C" fn ffi_cfm_process_msg4(
 
     // Return the struct containing pointers and sizes
     FFI_CfmProcessMsg4Result {
+        status: CFMStatus::Ok,
         cfm_state_ob_r3_ptr,
         cfm_state_ob_r3_size,
         msg5_ptr,
@@ -2253,15 +2341,17 @@ pub extern "C" fn ffi_cfm_process_msg4(
 
 #[repr(C)]
 pub struct FFI_CfmProcessMsg5Result {
+    status: CFMStatus,
     cfm_state_cb_r3_ptr: *mut u8,
     cfm_state_cb_r3_size: usize,
     msg6_ptr: *mut u8,
     msg6_size: usize,
 }
 
-impl Default for FFI_CfmProcessMsg5Result {
-    fn default() -> Self {
+impl FFIResult for FFI_CfmProcessMsg5Result {
+    fn error(status: CFMStatus) -> Self {
         FFI_CfmProcessMsg5Result {
+            status,
             cfm_state_cb_r3_ptr: ptr::null_mut(),
             cfm_state_cb_r3_size: 0,
             msg6_ptr: ptr::null_mut(),
@@ -2282,8 +2372,7 @@ pub extern "C" fn ffi_cfm_process_msg5(
     if cfm_state_cb_r2_ptr.is_null() || cfm_state_cb_r2_size == 0 ||
        auth_triples_cb_ptr.is_null() || auth_triples_cb_size == 0 ||
        msg5_ptr.is_null() || msg5_size == 0 {
-        eprintln!("Invalid input pointers in ffi_cfm_process_msg5");
-        return FFI_CfmProcessMsg5Result::default();
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_cfm_process_msg5".to_string());
     }
 
     // Deserialize cfm_state_cb_r2
@@ -2291,8 +2380,7 @@ pub extern "C" fn ffi_cfm_process_msg5(
     let cfm_state_cb_r2: Box<CFMStateCBR2> = match bincode::deserialize(cfm_state_cb_r2_bytes) {
         Ok(state) => state,
         Err(e) => {
-            eprintln!("Failed to deserialize cfm_state_cb_r2: {:?}", e);
-            return FFI_CfmProcessMsg5Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize cfm_state_cb_r2: {:?}", e));
         }
     };
 
@@ -2301,8 +2389,7 @@ pub extern "C" fn ffi_cfm_process_msg5(
     let auth_triples_cb: Vec<TripleShare> = match bincode::deserialize(auth_triples_cb_bytes) {
         Ok(triples) => triples,
         Err(e) => {
-            eprintln!("Failed to deserialize auth_triples_cb: {:?}", e);
-            return FFI_CfmProcessMsg5Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize auth_triples_cb: {:?}", e));
         }
     };
 
@@ -2311,8 +2398,7 @@ pub extern "C" fn ffi_cfm_process_msg5(
     let msg5: Box<CFMMsg5> = match bincode::deserialize(msg5_bytes) {
         Ok(msg) => msg,
         Err(e) => {
-            eprintln!("Failed to deserialize msg5: {:?}", e);
-            return FFI_CfmProcessMsg5Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize msg5: {:?}", e));
         }
     };
 
@@ -2320,8 +2406,7 @@ pub extern "C" fn ffi_cfm_process_msg5(
     let (cfm_state_cb_r3, msg6) = match cfm_process_msg5(cfm_state_cb_r2, &auth_triples_cb, &msg5) {
         Ok(result) => result,
         Err(e) => {
-            eprintln!("Error in cfm_process_msg5: {:?}", e);
-            return FFI_CfmProcessMsg5Result::default();
+            return ffi_fail((&e).into(), format!("Error in cfm_process_msg5: {}", e));
         }
     };
 
@@ -2329,8 +2414,7 @@ pub extern "C" fn ffi_cfm_process_msg5(
     let serialized_cfm_state_cb_r3 = match bincode::serialize(&cfm_state_cb_r3) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize cfm_state_cb_r3: {:?}", e);
-            return FFI_CfmProcessMsg5Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize cfm_state_cb_r3: {:?}", e));
         }
     };
 
@@ -2338,8 +2422,7 @@ pub extern "C" fn ffi_cfm_process_msg5(
     let serialized_msg6 = match bincode::serialize(&msg6) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize msg6: {:?}", e);
-            return FFI_CfmProcessMsg5Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize msg6: {:?}", e));
         }
     };
 
@@ -2361,6 +2444,7 @@ pub extern "C" fn ffi_cfm_process_msg5(
 
     // Return the struct containing pointers and sizes
     FFI_CfmProcessMsg5Result {
+        status: CFMStatus::Ok,
         cfm_state_cb_r3_ptr,
         cfm_state_cb_r3_size,
         msg6_ptr,
@@ -2371,15 +2455,17 @@ pub extern "C" fn ffi_cfm_process_msg5(
 
 #[repr(C)]
 pub struct FFI_CfmProcessMsg6Result {
+    status: CFMStatus,
     cfm_state_ob_r4_ptr: *mut u8,
     cfm_state_ob_r4_size: usize,
     msg7_ptr: *mut u8,
     msg7_size: usize,
 }
 
-impl Default for FFI_CfmProcessMsg6Result {
-    fn default() -> Self {
+impl FFIResult for FFI_CfmProcessMsg6Result {
+    fn error(status: CFMStatus) -> Self {
         FFI_CfmProcessMsg6Result {
+            status,
             cfm_state_ob_r4_ptr: ptr::null_mut(),
             cfm_state_ob_r4_size: 0,
             msg7_ptr: ptr::null_mut(),
@@ -2400,8 +2486,7 @@ pub extern "C" fn ffi_cfm_process_msg6(
     if cfm_state_ob_r3_ptr.is_null() || cfm_state_ob_r3_size == 0 ||
        auth_triples_ob_ptr.is_null() || auth_triples_ob_size == 0 ||
        msg6_ptr.is_null() || msg6_size == 0 {
-        eprintln!("Invalid input pointers in ffi_cfm_process_msg6");
-        return FFI_CfmProcessMsg6Result::default();
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_cfm_process_msg6".to_string());
     }
 
     // Deserialize cfm_state_ob_r3
@@ -2409,8 +2494,7 @@ pub extern "C" fn ffi_cfm_process_msg6(
     let cfm_state_ob_r3: Box<CFMStateOBR3> = match bincode::deserialize(cfm_state_ob_r3_bytes) {
         Ok(state) => state,
         Err(e) => {
-            eprintln!("Failed to deserialize cfm_state_ob_r3: {:?}", e);
-            return FFI_CfmProcessMsg6Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize cfm_state_ob_r3: {:?}", e));
         }
     };
 
@@ -2419,8 +2503,7 @@ pub extern "C" fn ffi_cfm_process_msg6(
     let auth_triples_ob: Vec<TripleShare> = match bincode::deserialize(auth_triples_ob_bytes) {
         Ok(triples) => triples,
         Err(e) => {
-            eprintln!("Failed to deserialize auth_triples_ob: {:?}", e);
-            return FFI_CfmProcessMsg6Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize auth_triples_ob: {:?}", e));
         }
     };
 
@@ -2429,8 +2512,7 @@ pub extern "C" fn ffi_cfm_process_msg6(
     let msg6: Box<CFMMsg6> = match bincode::deserialize(msg6_bytes) {
         Ok(msg) => msg,
         Err(e) => {
-            eprintln!("Failed to deserialize msg6: {:?}", e);
-            return FFI_CfmProcessMsg6Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize msg6: {:?}", e));
         }
     };
 
@@ -2438,8 +2520,7 @@ pub extern "C" fn ffi_cfm_process_msg6(
     let (cfm_state_ob_r4, msg7) = match cfm_process_msg6(cfm_state_ob_r3, &auth_triples_ob, &msg6) {
         Ok(res) => res,
         Err(e) => {
-            eprintln!("Error in cfm_process_msg6: {:?}", e);
-            return FFI_CfmProcessMsg6Result::default();
+            return ffi_fail((&e).into(), format!("Error in cfm_process_msg6: {}", e));
         }
     };
 
@@ -2447,8 +2528,7 @@ pub extern "C" fn ffi_cfm_process_msg6(
     let serialized_cfm_state_ob_r4 = match bincode::serialize(&cfm_state_ob_r4) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize cfm_state_ob_r4: {:?}", e);
-            return FFI_CfmProcessMsg6Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize cfm_state_ob_r4: {:?}", e));
         }
     };
 
@@ -2456,8 +2536,7 @@ pub extern "C" fn ffi_cfm_process_msg6(
     let serialized_msg7 = match bincode::serialize(&msg7) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize msg7: {:?}", e);
-            return FFI_CfmProcessMsg6Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize msg7: {:?}", e));
         }
     };
 
@@ -2478,6 +2557,7 @@ pub extern "C" fn ffi_cfm_process_msg6(
     }
 
     FFI_CfmProcessMsg6Result {
+        status: CFMStatus::Ok,
         cfm_state_ob_r4_ptr,
         cfm_state_ob_r4_size,
         msg7_ptr,
@@ -2489,15 +2569,17 @@ pub extern "C" fn ffi_cfm_process_msg6(
 
 #[repr(C)]
 pub struct FFI_CfmProcessMsg7Result {
+    status: CFMStatus,
     cfm_state_cb_r4_ptr: *mut u8,
     cfm_state_cb_r4_size: usize,
     msg8_ptr: *mut u8,
     msg8_size: usize,
 }
 
-impl Default for FFI_CfmProcessMsg7Result {
-    fn default() -> Self {
+impl FFIResult for FFI_CfmProcessMsg7Result {
+    fn error(status: CFMStatus) -> Self {
         FFI_CfmProcessMsg7Result {
+            status,
             cfm_state_cb_r4_ptr: std::ptr::null_mut(),
             cfm_state_cb_r4_size: 0,
             msg8_ptr: std::ptr::null_mut(),
@@ -2518,8 +2600,7 @@ pub extern "C" fn ffi_cfm_process_msg7(
     if cfm_state_cb_r3_ptr.is_null() || cfm_state_cb_r3_size == 0 ||
        auth_triples_cb_ptr.is_null() || auth_triples_cb_size == 0 ||
        msg7_ptr.is_null() || msg7_size == 0 {
-        eprintln!("Invalid input pointers in ffi_cfm_process_msg7");
-        return FFI_CfmProcessMsg7Result::default();
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_cfm_process_msg7".to_string());
     }
 
     // Deserialize cfm_state_cb_r3
@@ -2527,8 +2608,7 @@ pub extern "C" fn ffi_cfm_process_msg7(
     let cfm_state_cb_r3: CFMStateCBR3 = match bincode::deserialize(cfm_state_cb_r3_bytes) {
         Ok(state) => state,
         Err(e) => {
-            eprintln!("Failed to deserialize cfm_state_cb_r3: {:?}", e);
-            return FFI_CfmProcessMsg7Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize cfm_state_cb_r3: {:?}", e));
         }
     };
 
@@ -2537,8 +2617,7 @@ pub extern "C" fn ffi_cfm_process_msg7(
     let auth_triples_cb: Vec<TripleShare> = match bincode::deserialize(auth_triples_cb_bytes) {
         Ok(triples) => triples,
         Err(e) => {
-            eprintln!("Failed to deserialize auth_triples_cb: {:?}", e);
-            return FFI_CfmProcessMsg7Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize auth_triples_cb: {:?}", e));
         }
     };
 
@@ -2547,8 +2626,7 @@ pub extern "C" fn ffi_cfm_process_msg7(
     let msg7: Box<CFMMsg7> = match bincode::deserialize(msg7_bytes) {
         Ok(msg) => msg,
         Err(e) => {
-            eprintln!("Failed to deserialize msg7: {:?}", e);
-            return FFI_CfmProcessMsg7Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize msg7: {:?}", e));
         }
     };
 
@@ -2556,8 +2634,7 @@ pub extern "C" fn ffi_cfm_process_msg7(
     let (cfm_state_cb_r4, msg8) = match cfm_process_msg7(cfm_state_cb_r3, &auth_triples_cb, &msg7) {
         Ok(result) => result,
         Err(e) => {
-            eprintln!("Error in cfm_process_msg7: {:?}", e);
-            return FFI_CfmProcessMsg7Result::default();
+            return ffi_fail((&e).into(), format!("Error in cfm_process_msg7: {}", e));
         }
     };
 
@@ -2565,8 +2642,7 @@ pub extern "C" fn ffi_cfm_process_msg7(
     let serialized_cfm_state_cb_r4 = match bincode::serialize(&cfm_state_cb_r4) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize cfm_state_cb_r4: {:?}", e);
-            return FFI_CfmProcessMsg7Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize cfm_state_cb_r4: {:?}", e));
         }
     };
 
@@ -2574,8 +2650,7 @@ pub extern "C" fn ffi_cfm_process_msg7(
     let serialized_msg8 = match bincode::serialize(&msg8) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize msg8: {:?}", e);
-            return FFI_CfmProcessMsg7Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize msg8: {:?}", e));
         }
     };
 
@@ -2597,6 +2672,7 @@ pub extern "C" fn ffi_cfm_process_msg7(
 
     // Return result
     FFI_CfmProcessMsg7Result {
+        status: CFMStatus::Ok,
         cfm_state_cb_r4_ptr,
         cfm_state_cb_r4_size,
         msg8_ptr,
@@ -2607,15 +2683,17 @@ pub extern "C" fn ffi_cfm_process_msg7(
 
 #[repr(C)]
 pub struct FFI_CfmProcessMsg8Result {
+    status: CFMStatus,
     cfm_state_ob_r5_ptr: *mut u8,
     cfm_state_ob_r5_size: usize,
     msg9_ptr: *mut u8,
     msg9_size: usize,
 }
 
-impl Default for FFI_CfmProcessMsg8Result {
-    fn default() -> Self {
+impl FFIResult for FFI_CfmProcessMsg8Result {
+    fn error(status: CFMStatus) -> Self {
         FFI_CfmProcessMsg8Result {
+            status,
             cfm_state_ob_r5_ptr: std::ptr::null_mut(),
             cfm_state_ob_r5_size: 0,
             msg9_ptr: std::ptr::null_mut(),
@@ -2636,8 +2714,7 @@ pub extern "C" fn ffi_cfm_process_msg8(
     if cfm_state_ob_r4_ptr.is_null() || cfm_state_ob_r4_size == 0 ||
        auth_triples_ob_ptr.is_null() || auth_triples_ob_size == 0 ||
        msg8_ptr.is_null() || msg8_size == 0 {
-        eprintln!("Invalid input pointers in ffi_cfm_process_msg8");
-        return FFI_CfmProcessMsg8Result::default();
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_cfm_process_msg8".to_string());
     }
 
     // Deserialize cfm_state_ob_r4
@@ -2645,8 +2722,7 @@ pub extern "C" fn ffi_cfm_process_msg8(
     let cfm_state_ob_r4: CFMStateOBR4 = match bincode::deserialize(cfm_state_ob_r4_bytes) {
         Ok(state) => state,
         Err(e) => {
-            eprintln!("Failed to deserialize cfm_state_ob_r4: {:?}", e);
-            return FFI_CfmProcessMsg8Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize cfm_state_ob_r4: {:?}", e));
         }
     };
 
@@ -2655,8 +2731,7 @@ pub extern "C" fn ffi_cfm_process_msg8(
     let auth_triples_ob: Vec<TripleShare> = match bincode::deserialize(auth_triples_ob_bytes) {
         Ok(triples) => triples,
         Err(e) => {
-            eprintln!("Failed to deserialize auth_triples_ob: {:?}", e);
-            return FFI_CfmProcessMsg8Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize auth_triples_ob: {:?}", e));
         }
     };
 
@@ -2665,8 +2740,7 @@ pub extern "C" fn ffi_cfm_process_msg8(
     let msg8:  Box<CFMMsg8> = match bincode::deserialize(msg8_bytes) {
         Ok(msg) => msg,
         Err(e) => {
-            eprintln!("Failed to deserialize msg8: {:?}", e);
-            return FFI_CfmProcessMsg8Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize msg8: {:?}", e));
         }
     };
 
@@ -2674,8 +2748,7 @@ pub extern "C" fn ffi_cfm_process_msg8(
     let (cfm_state_ob_r5, msg9) = match cfm_process_msg8(cfm_state_ob_r4, &auth_triples_ob, &msg8) {
         Ok(result) => result,
         Err(e) => {
-            eprintln!("Error in cfm_process_msg8: {:?}", e);
-            return FFI_CfmProcessMsg8Result::default();
+            return ffi_fail((&e).into(), format!("Error in cfm_process_msg8: {}", e));
         }
     };
 
@@ -2683,8 +2756,7 @@ pub extern "C" fn ffi_cfm_process_msg8(
     let serialized_cfm_state_ob_r5 = match bincode::serialize(&cfm_state_ob_r5) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize cfm_state_ob_r5: {:?}", e);
-            return FFI_CfmProcessMsg8Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize cfm_state_ob_r5: {:?}", e));
         }
     };
 
@@ -2692,8 +2764,7 @@ pub extern "C" fn ffi_cfm_process_msg8(
     let serialized_msg9 = match bincode::serialize(&msg9) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize msg9: {:?}", e);
-            return FFI_CfmProcessMsg8Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize msg9: {:?}", e));
         }
     };
 
@@ -2715,6 +2786,7 @@ pub extern "C" fn ffi_cfm_process_msg8(
 
     // Return result
     FFI_CfmProcessMsg8Result {
+        status: CFMStatus::Ok,
         cfm_state_ob_r5_ptr,
         cfm_state_ob_r5_size,
         msg9_ptr,
@@ -2726,15 +2798,17 @@ pub extern "C" fn ffi_cfm_process_msg8(
 
 #[repr(C)]
 pub struct FFI_CfmProcessMsg9Result {
+    status: CFMStatus,
     cfm_state_cb_r5_ptr: *mut u8,
     cfm_state_cb_r5_size: usize,
     msg10_ptr: *mut u8,
     msg10_size: usize,
 }
 
-impl Default for FFI_CfmProcessMsg9Result {
-    fn default() -> Self {
+impl FFIResult for FFI_CfmProcessMsg9Result {
+    fn error(status: CFMStatus) -> Self {
         FFI_CfmProcessMsg9Result {
+            status,
             cfm_state_cb_r5_ptr: std::ptr::null_mut(),
             cfm_state_cb_r5_size: 0,
             msg10_ptr: std::ptr::null_mut(),
@@ -2755,8 +2829,7 @@ pub extern "C" fn ffi_cfm_process_msg9(
     if cfm_state_cb_r4_ptr.is_null() || cfm_state_cb_r4_size == 0 ||
        auth_triples_cb_ptr.is_null() || auth_triples_cb_size == 0 ||
        msg9_ptr.is_null() || msg9_size == 0 {
-        eprintln!("Invalid input pointers in ffi_cfm_process_msg9");
-        return FFI_CfmProcessMsg9Result::default();
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_cfm_process_msg9".to_string());
     }
 
     // Deserialize cfm_state_cb_r4
@@ -2764,8 +2837,7 @@ pub extern "C" fn ffi_cfm_process_msg9(
     let cfm_state_cb_r4: CFMStateCBR4 = match bincode::deserialize(cfm_state_cb_r4_bytes) {
         Ok(state) => state,
         Err(e) => {
-            eprintln!("Failed to deserialize cfm_state_cb_r4: {:?}", e);
-            return FFI_CfmProcessMsg9Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize cfm_state_cb_r4: {:?}", e));
         }
     };
 
@@ -2774,8 +2846,7 @@ pub extern "C" fn ffi_cfm_process_msg9(
     let auth_triples_cb: Vec<TripleShare> = match bincode::deserialize(auth_triples_cb_bytes) {
         Ok(triples) => triples,
         Err(e) => {
-            eprintln!("Failed to deserialize auth_triples_cb: {:?}", e);
-            return FFI_CfmProcessMsg9Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize auth_triples_cb: {:?}", e));
         }
     };
 
@@ -2784,8 +2855,7 @@ pub extern "C" fn ffi_cfm_process_msg9(
     let msg9: Box<CFMMsg9> = match bincode::deserialize(msg9_bytes) {
         Ok(msg) => msg,
         Err(e) => {
-            eprintln!("Failed to deserialize msg9: {:?}", e);
-            return FFI_CfmProcessMsg9Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize msg9: {:?}", e));
         }
     };
 
@@ -2793,8 +2863,7 @@ pub extern "C" fn ffi_cfm_process_msg9(
     let (cfm_state_cb_r5, msg10) = match cfm_process_msg9(cfm_state_cb_r4, &auth_triples_cb, &msg9) {
         Ok(result) => result,
         Err(e) => {
-            eprintln!("Error in cfm_process_msg9: {:?}", e);
-            return FFI_CfmProcessMsg9Result::default();
+            return ffi_fail((&e).into(), format!("Error in cfm_process_msg9: {}", e));
         }
     };
 
@@ -2802,8 +2871,7 @@ pub extern "C" fn ffi_cfm_process_msg9(
     let serialized_cfm_state_cb_r5 = match bincode::serialize(&cfm_state_cb_r5) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize cfm_state_cb_r5: {:?}", e);
-            return FFI_CfmProcessMsg9Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize cfm_state_cb_r5: {:?}", e));
         }
     };
 
@@ -2811,8 +2879,7 @@ pub extern "C" fn ffi_cfm_process_msg9(
     let serialized_msg10 = match bincode::serialize(&msg10) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize msg10: {:?}", e);
-            return FFI_CfmProcessMsg9Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize msg10: {:?}", e));
         }
     };
 
@@ -2834,6 +2901,7 @@ pub extern "C" fn ffi_cfm_process_msg9(
 
     // Return result
     FFI_CfmProcessMsg9Result {
+        status: CFMStatus::Ok,
         cfm_state_cb_r5_ptr,
         cfm_state_cb_r5_size,
         msg10_ptr,
@@ -2845,15 +2913,17 @@ pub extern "C" fn ffi_cfm_process_msg9(
 
 #[repr(C)]
 pub struct FFI_CfmProcessMsg10Result {
+    status: CFMStatus,
     cfm_state_ob_r6_ptr: *mut u8,
     cfm_state_ob_r6_size: usize,
     msg11_ptr: *mut u8,
     msg11_size: usize,
 }
 
-impl Default for FFI_CfmProcessMsg10Result {
-    fn default() -> Self {
+impl FFIResult for FFI_CfmProcessMsg10Result {
+    fn error(status: CFMStatus) -> Self {
         FFI_CfmProcessMsg10Result {
+            status,
             cfm_state_ob_r6_ptr: std::ptr::null_mut(),
             cfm_state_ob_r6_size: 0,
             msg11_ptr: std::ptr::null_mut(),
@@ -2874,8 +2944,7 @@ pub extern "C" fn ffi_cfm_process_msg10(
     if cfm_state_ob_r5_ptr.is_null() || cfm_state_ob_r5_size == 0 ||
        auth_triples_ob_ptr.is_null() || auth_triples_ob_size == 0 ||
        msg10_ptr.is_null() || msg10_size == 0 {
-        eprintln!("Invalid input pointers in ffi_cfm_process_msg10");
-        return FFI_CfmProcessMsg10Result::default();
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_cfm_process_msg10".to_string());
     }
 
     // Deserialize cfm_state_ob_r5
@@ -2883,8 +2952,7 @@ pub extern "C" fn ffi_cfm_process_msg10(
     let cfm_state_ob_r5: CFMStateOBR5 = match bincode::deserialize(cfm_state_ob_r5_bytes) {
         Ok(state) => state,
         Err(e) => {
-            eprintln!("Failed to deserialize cfm_state_ob_r5: {:?}", e);
-            return FFI_CfmProcessMsg10Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize cfm_state_ob_r5: {:?}", e));
         }
     };
 
@@ -2893,8 +2961,7 @@ pub extern "C" fn ffi_cfm_process_msg10(
     let auth_triples_ob: Vec<TripleShare> = match bincode::deserialize(auth_triples_ob_bytes) {
         Ok(triples) => triples,
         Err(e) => {
-            eprintln!("Failed to deserialize auth_triples_ob: {:?}", e);
-            return FFI_CfmProcessMsg10Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize auth_triples_ob: {:?}", e));
         }
     };
 
@@ -2903,8 +2970,7 @@ pub extern "C" fn ffi_cfm_process_msg10(
     let msg10: Box<CFMMsg10> = match bincode::deserialize(msg10_bytes) {
         Ok(msg) => msg,
         Err(e) => {
-            eprintln!("Failed to deserialize msg10: {:?}", e);
-            return FFI_CfmProcessMsg10Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize msg10: {:?}", e));
         }
     };
 
@@ -2912,8 +2978,7 @@ pub extern "C" fn ffi_cfm_process_msg10(
     let (cfm_state_ob_r6, msg11) = match cfm_process_msg10(cfm_state_ob_r5, &auth_triples_ob, &msg10) {
         Ok(result) => result,
         Err(e) => {
-            eprintln!("Error in cfm_process_msg10: {:?}", e);
-            return FFI_CfmProcessMsg10Result::default();
+            return ffi_fail((&e).into(), format!("Error in cfm_process_msg10: {}", e));
         }
     };
 
@@ -2921,8 +2986,7 @@ pub extern "C" fn ffi_cfm_process_msg10(
     let serialized_cfm_state_ob_r6 = match bincode::serialize(&cfm_state_ob_r6) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize cfm_state_ob_r6: {:?}", e);
-            return FFI_CfmProcessMsg10Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize cfm_state_ob_r6: {:?}", e));
         }
     };
 
@@ -2930,8 +2994,7 @@ pub extern "C" fn ffi_cfm_process_msg10(
     let serialized_msg11 = match bincode::serialize(&msg11) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize msg11: {:?}", e);
-            return FFI_CfmProcessMsg10Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize msg11: {:?}", e));
         }
     };
 
@@ -2953,6 +3016,7 @@ pub extern "C" fn ffi_cfm_process_msg10(
 
     // Return result
     FFI_CfmProcessMsg10Result {
+        status: CFMStatus::Ok,
         cfm_state_ob_r6_ptr,
         cfm_state_ob_r6_size,
         msg11_ptr,
@@ -2963,15 +3027,17 @@ pub extern "C" fn ffi_cfm_process_msg10(
 
 #[repr(C)]
 pub struct FFI_CfmProcessMsg11Result {
+    status: CFMStatus,
     cfm_state_cb_r6_ptr: *mut u8,
     cfm_state_cb_r6_size: usize,
     msg12_ptr: *mut u8,
     msg12_size: usize,
 }
 
-impl Default for FFI_CfmProcessMsg11Result {
-    fn default() -> Self {
+impl FFIResult for FFI_CfmProcessMsg11Result {
+    fn error(status: CFMStatus) -> Self {
         FFI_CfmProcessMsg11Result {
+            status,
             cfm_state_cb_r6_ptr: std::ptr::null_mut(),
             cfm_state_cb_r6_size: 0,
             msg12_ptr: std::ptr::null_mut(),
@@ -2992,8 +3058,7 @@ pub extern "C" fn ffi_cfm_process_msg11(
     if cfm_state_cb_r5_ptr.is_null() || cfm_state_cb_r5_size == 0 ||
        auth_triples_cb_ptr.is_null() || auth_triples_cb_size == 0 ||
        msg11_ptr.is_null() || msg11_size == 0 {
-        eprintln!("Invalid input pointers in ffi_cfm_process_msg11");
-        return FFI_CfmProcessMsg11Result::default();
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_cfm_process_msg11".to_string());
     }
 
     // Deserialize cfm_state_cb_r5
@@ -3001,8 +3066,7 @@ pub extern "C" fn ffi_cfm_process_msg11(
     let cfm_state_cb_r5: CFMStateCBR5 = match bincode::deserialize(cfm_state_cb_r5_bytes) {
         Ok(state) => state,
         Err(e) => {
-            eprintln!("Failed to deserialize cfm_state_cb_r5: {:?}", e);
-            return FFI_CfmProcessMsg11Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize cfm_state_cb_r5: {:?}", e));
         }
     };
 
@@ -3011,8 +3075,7 @@ pub extern "C" fn ffi_cfm_process_msg11(
     let auth_triples_cb: Vec<TripleShare> = match bincode::deserialize(auth_triples_cb_bytes) {
         Ok(triples) => triples,
         Err(e) => {
-            eprintln!("Failed to deserialize auth_triples_cb: {:?}", e);
-            return FFI_CfmProcessMsg11Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize auth_triples_cb: {:?}", e));
         }
     };
 
@@ -3021,8 +3084,7 @@ pub extern "C" fn ffi_cfm_process_msg11(
     let msg11: Box<CFMMsg11> = match bincode::deserialize(msg11_bytes) {
         Ok(msg) => msg,
         Err(e) => {
-            eprintln!("Failed to deserialize msg11: {:?}", e);
-            return FFI_CfmProcessMsg11Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize msg11: {:?}", e));
         }
     };
 
@@ -3030,8 +3092,7 @@ pub extern "C" fn ffi_cfm_process_msg11(
     let (cfm_state_cb_r6, msg12) = match cfm_process_msg11(cfm_state_cb_r5, &auth_triples_cb, &msg11) {
         Ok(result) => result,
         Err(e) => {
-            eprintln!("Error in cfm_process_msg11: {:?}", e);
-            return FFI_CfmProcessMsg11Result::default();
+            return ffi_fail((&e).into(), format!("Error in cfm_process_msg11: {}", e));
         }
     };
 
@@ -3039,8 +3100,7 @@ pub extern "C" fn ffi_cfm_process_msg11(
     let serialized_cfm_state_cb_r6 = match bincode::serialize(&cfm_state_cb_r6) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize cfm_state_cb_r6: {:?}", e);
-            return FFI_CfmProcessMsg11Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize cfm_state_cb_r6: {:?}", e));
         }
     };
 
@@ -3048,8 +3108,7 @@ pub extern "C" fn ffi_cfm_process_msg11(
     let serialized_msg12 = match bincode::serialize(&msg12) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize msg12: {:?}", e);
-            return FFI_CfmProcessMsg11Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize msg12: {:?}", e));
         }
     };
 
@@ -3071,6 +3130,7 @@ pub extern "C" fn ffi_cfm_process_msg11(
 
     // Return result
     FFI_CfmProcessMsg11Result {
+        status: CFMStatus::Ok,
         cfm_state_cb_r6_ptr,
         cfm_state_cb_r6_size,
         msg12_ptr,
@@ -3082,15 +3142,17 @@ pub extern "C" fn ffi_cfm_process_msg11(
 
 #[repr(C)]
 pub struct FFI_CfmProcessMsg12Result {
+    status: CFMStatus,
     cfm_state_ob_r7_ptr: *mut u8,
     cfm_state_ob_r7_size: usize,
     msg13_ptr: *mut u8,
     msg13_size: usize,
 }
 
-impl Default for FFI_CfmProcessMsg12Result {
-    fn default() -> Self {
+impl FFIResult for FFI_CfmProcessMsg12Result {
+    fn error(status: CFMStatus) -> Self {
         FFI_CfmProcessMsg12Result {
+            status,
             cfm_state_ob_r7_ptr: std::ptr::null_mut(),
             cfm_state_ob_r7_size: 0,
             msg13_ptr: std::ptr::null_mut(),
@@ -3111,8 +3173,7 @@ pub extern "C" fn ffi_cfm_process_msg12(
     if cfm_state_ob_r6_ptr.is_null() || cfm_state_ob_r6_size == 0 ||
        auth_triples_ob_ptr.is_null() || auth_triples_ob_size == 0 ||
        msg12_ptr.is_null() || msg12_size == 0 {
-        eprintln!("Invalid input pointers in ffi_cfm_process_msg12");
-        return FFI_CfmProcessMsg12Result::default();
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_cfm_process_msg12".to_string());
     }
 
     // Deserialize cfm_state_ob_r6
@@ -3120,8 +3181,7 @@ pub extern "C" fn ffi_cfm_process_msg12(
     let cfm_state_ob_r6: CFMStateOBR6 = match bincode::deserialize(cfm_state_ob_r6_bytes) {
         Ok(state) => state,
         Err(e) => {
-            eprintln!("Failed to deserialize cfm_state_ob_r6: {:?}", e);
-            return FFI_CfmProcessMsg12Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize cfm_state_ob_r6: {:?}", e));
         }
     };
 
@@ -3130,8 +3190,7 @@ pub extern "C" fn ffi_cfm_process_msg12(
     let auth_triples_ob: Vec<TripleShare> = match bincode::deserialize(auth_triples_ob_bytes) {
         Ok(triples) => triples,
         Err(e) => {
-            eprintln!("Failed to deserialize auth_triples_ob: {:?}", e);
-            return FFI_CfmProcessMsg12Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize auth_triples_ob: {:?}", e));
         }
     };
 
@@ -3140,8 +3199,7 @@ pub extern "C" fn ffi_cfm_process_msg12(
     let msg12: Box<CFMMsg12> = match bincode::deserialize(msg12_bytes) {
         Ok(msg) => msg,
         Err(e) => {
-            eprintln!("Failed to deserialize msg12: {:?}", e);
-            return FFI_CfmProcessMsg12Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize msg12: {:?}", e));
         }
     };
 
@@ -3149,8 +3207,7 @@ pub extern "C" fn ffi_cfm_process_msg12(
     let (cfm_state_ob_r7, msg13) = match cfm_process_msg12(cfm_state_ob_r6, &auth_triples_ob, &msg12) {
         Ok(result) => result,
         Err(e) => {
-            eprintln!("Error in cfm_process_msg12: {:?}", e);
-            return FFI_CfmProcessMsg12Result::default();
+            return ffi_fail((&e).into(), format!("Error in cfm_process_msg12: {}", e));
         }
     };
 
@@ -3158,8 +3215,7 @@ pub extern "C" fn ffi_cfm_process_msg12(
     let serialized_cfm_state_ob_r7 = match bincode::serialize(&cfm_state_ob_r7) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize cfm_state_ob_r7: {:?}", e);
-            return FFI_CfmProcessMsg12Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize cfm_state_ob_r7: {:?}", e));
         }
     };
 
@@ -3167,8 +3223,7 @@ pub extern "C" fn ffi_cfm_process_msg12(
     let serialized_msg13 = match bincode::serialize(&msg13) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize msg13: {:?}", e);
-            return FFI_CfmProcessMsg12Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize msg13: {:?}", e));
         }
     };
 
@@ -3190,6 +3245,7 @@ pub extern "C" fn ffi_cfm_process_msg12(
 
     // Return result
     FFI_CfmProcessMsg12Result {
+        status: CFMStatus::Ok,
         cfm_state_ob_r7_ptr,
         cfm_state_ob_r7_size,
         msg13_ptr,
@@ -3200,15 +3256,17 @@ pub extern "C" fn ffi_cfm_process_msg12(
 
 #[repr(C)]
 pub struct FFI_CfmProcessMsg13Result {
+    status: CFMStatus,
     cfm_state_cb_r7_ptr: *mut u8,
     cfm_state_cb_r7_size: usize,
     msg14_ptr: *mut u8,
     msg14_size: usize,
 }
 
-impl Default for FFI_CfmProcessMsg13Result {
-    fn default() -> Self {
+impl FFIResult for FFI_CfmProcessMsg13Result {
+    fn error(status: CFMStatus) -> Self {
         FFI_CfmProcessMsg13Result {
+            status,
             cfm_state_cb_r7_ptr: std::ptr::null_mut(),
             cfm_state_cb_r7_size: 0,
             msg14_ptr: std::ptr::null_mut(),
@@ -3229,8 +3287,7 @@ pub extern "C" fn ffi_cfm_process_msg13(
     if cfm_state_cb_r6_ptr.is_null() || cfm_state_cb_r6_size == 0 ||
        auth_triples_cb_ptr.is_null() || auth_triples_cb_size == 0 ||
        msg13_ptr.is_null() || msg13_size == 0 {
-        eprintln!("Invalid input pointers in ffi_cfm_process_msg13");
-        return FFI_CfmProcessMsg13Result::default();
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_cfm_process_msg13".to_string());
     }
 
     // Deserialize cfm_state_cb_r6
@@ -3238,8 +3295,7 @@ pub extern "C" fn ffi_cfm_process_msg13(
     let cfm_state_cb_r6: CFMStateCBR6 = match bincode::deserialize(cfm_state_cb_r6_bytes) {
         Ok(state) => state,
         Err(e) => {
-            eprintln!("Failed to deserialize cfm_state_cb_r6: {:?}", e);
-            return FFI_CfmProcessMsg13Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize cfm_state_cb_r6: {:?}", e));
         }
     };
 
@@ -3248,8 +3304,7 @@ pub extern "C" fn ffi_cfm_process_msg13(
     let auth_triples_cb: Vec<TripleShare> = match bincode::deserialize(auth_triples_cb_bytes) {
         Ok(triples) => triples,
         Err(e) => {
-            eprintln!("Failed to deserialize auth_triples_cb: {:?}", e);
-            return FFI_CfmProcessMsg13Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize auth_triples_cb: {:?}", e));
         }
     };
 
@@ -3258,8 +3313,7 @@ pub extern "C" fn ffi_cfm_process_msg13(
     let msg13: Box<CFMMsg13> = match bincode::deserialize(msg13_bytes) {
         Ok(msg) => msg,
         Err(e) => {
-            eprintln!("Failed to deserialize msg13: {:?}", e);
-            return FFI_CfmProcessMsg13Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize msg13: {:?}", e));
         }
     };
 
@@ -3267,8 +3321,7 @@ pub extern "C" fn ffi_cfm_process_msg13(
     let (cfm_state_cb_r7, msg14) = match cfm_process_msg13(cfm_state_cb_r6, &auth_triples_cb, &msg13) {
         Ok(result) => result,
         Err(e) => {
-            eprintln!("Error in cfm_process_msg13: {:?}", e);
-            return FFI_CfmProcessMsg13Result::default();
+            return ffi_fail((&e).into(), format!("Error in cfm_process_msg13: {}", e));
         }
     };
 
@@ -3276,8 +3329,7 @@ pub extern "C" fn ffi_cfm_process_msg13(
     let serialized_cfm_state_cb_r7 = match bincode::serialize(&cfm_state_cb_r7) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize cfm_state_cb_r7: {:?}", e);
-            return FFI_CfmProcessMsg13Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize cfm_state_cb_r7: {:?}", e));
         }
     };
 
@@ -3285,8 +3337,7 @@ pub extern "C" fn ffi_cfm_process_msg13(
     let serialized_msg14 = match bincode::serialize(&msg14) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize msg14: {:?}", e);
-            return FFI_CfmProcessMsg13Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize msg14: {:?}", e));
         }
     };
 
@@ -3308,6 +3359,7 @@ pub extern "C" fn ffi_cfm_process_msg13(
 
     // Return result
     FFI_CfmProcessMsg13Result {
+        status: CFMStatus::Ok,
         cfm_state_cb_r7_ptr,
         cfm_state_cb_r7_size,
         msg14_ptr,
@@ -3318,15 +3370,17 @@ pub extern "C" fn ffi_cfm_process_msg13(
 
 #[repr(C)]
 pub struct FFI_CfmProcessMsg14Result {
+    status: CFMStatus,
     cfm_state_ob_r8_ptr: *mut u8,
     cfm_state_ob_r8_size: usize,
     msg15_ptr: *mut u8,
     msg15_size: usize,
 }
 
-impl Default for FFI_CfmProcessMsg14Result {
-    fn default() -> Self {
+impl FFIResult for FFI_CfmProcessMsg14Result {
+    fn error(status: CFMStatus) -> Self {
         FFI_CfmProcessMsg14Result {
+            status,
             cfm_state_ob_r8_ptr: std::ptr::null_mut(),
             cfm_state_ob_r8_size: 0,
             msg15_ptr: std::ptr::null_mut(),
@@ -3344,8 +3398,7 @@ pub extern "C" fn ffi_cfm_process_msg14(
 ) -> FFI_CfmProcessMsg14Result {
     if cfm_state_ob_r7_ptr.is_null() || cfm_state_ob_r7_size == 0 ||
        msg14_ptr.is_null() || msg14_size == 0 {
-        eprintln!("Invalid input pointers in ffi_cfm_process_msg14");
-        return FFI_CfmProcessMsg14Result::default();
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_cfm_process_msg14".to_string());
     }
 
     // Deserialize cfm_state_ob_r7
@@ -3353,8 +3406,7 @@ pub extern "C" fn ffi_cfm_process_msg14(
     let cfm_state_ob_r7: CFMStateOBR7 = match bincode::deserialize(cfm_state_ob_r7_bytes) {
         Ok(state) => state,
         Err(e) => {
-            eprintln!("Failed to deserialize cfm_state_ob_r7: {:?}", e);
-            return FFI_CfmProcessMsg14Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize cfm_state_ob_r7: {:?}", e));
         }
     };
 
@@ -3363,8 +3415,7 @@ pub extern "C" fn ffi_cfm_process_msg14(
     let msg14: Box<CFMMsg14> = match bincode::deserialize(msg14_bytes) {
         Ok(msg) => msg,
         Err(e) => {
-            eprintln!("Failed to deserialize msg14: {:?}", e);
-            return FFI_CfmProcessMsg14Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize msg14: {:?}", e));
         }
     };
 
@@ -3372,8 +3423,7 @@ pub extern "C" fn ffi_cfm_process_msg14(
     let (cfm_state_ob_r8, msg15) = match cfm_process_msg14(cfm_state_ob_r7, &msg14) {
         Ok(result) => result,
         Err(e) => {
-            eprintln!("Error in cfm_process_msg14: {:?}", e);
-            return FFI_CfmProcessMsg14Result::default();
+            return ffi_fail((&e).into(), format!("Error in cfm_process_msg14: {}", e));
         }
     };
 
@@ -3381,8 +3431,7 @@ pub extern "C" fn ffi_cfm_process_msg14(
     let serialized_cfm_state_ob_r8 = match bincode::serialize(&cfm_state_ob_r8) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize cfm_state_ob_r8: {:?}", e);
-            return FFI_CfmProcessMsg14Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize cfm_state_ob_r8: {:?}", e));
         }
     };
 
@@ -3390,8 +3439,7 @@ pub extern "C" fn ffi_cfm_process_msg14(
     let serialized_msg15 = match bincode::serialize(&msg15) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize msg15: {:?}", e);
-            return FFI_CfmProcessMsg14Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize msg15: {:?}", e));
         }
     };
 
@@ -3413,6 +3461,7 @@ pub extern "C" fn ffi_cfm_process_msg14(
 
     // Return result
     FFI_CfmProcessMsg14Result {
+        status: CFMStatus::Ok,
         cfm_state_ob_r8_ptr,
         cfm_state_ob_r8_size,
         msg15_ptr,
@@ -3423,15 +3472,17 @@ pub extern "C" fn ffi_cfm_process_msg14(
 
 #[repr(C)]
 pub struct FFI_CfmProcessMsg15Result {
+    status: CFMStatus,
     cfm_state_cb_r8_ptr: *mut u8,
     cfm_state_cb_r8_size: usize,
     msg16_ptr: *mut u8,
     msg16_size: usize,
 }
 
-impl Default for FFI_CfmProcessMsg15Result {
-    fn default() -> Self {
+impl FFIResult for FFI_CfmProcessMsg15Result {
+    fn error(status: CFMStatus) -> Self {
         FFI_CfmProcessMsg15Result {
+            status,
             cfm_state_cb_r8_ptr: std::ptr::null_mut(),
             cfm_state_cb_r8_size: 0,
             msg16_ptr: std::ptr::null_mut(),
@@ -3449,8 +3500,7 @@ pub extern "C" fn ffi_cfm_process_msg15(
 ) -> FFI_CfmProcessMsg15Result {
     if cfm_state_cb_r7_ptr.is_null() || cfm_state_cb_r7_size == 0 ||
        msg15_ptr.is_null() || msg15_size == 0 {
-        eprintln!("Invalid input pointers in ffi_cfm_process_msg15");
-        return FFI_CfmProcessMsg15Result::default();
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_cfm_process_msg15".to_string());
     }
 
     // Deserialize cfm_state_cb_r7
@@ -3458,8 +3508,7 @@ pub extern "C" fn ffi_cfm_process_msg15(
     let cfm_state_cb_r7: CFMStateCBR7 = match bincode::deserialize(cfm_state_cb_r7_bytes) {
         Ok(state) => state,
         Err(e) => {
-            eprintln!("Failed to deserialize cfm_state_cb_r7: {:?}", e);
-            return FFI_CfmProcessMsg15Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize cfm_state_cb_r7: {:?}", e));
         }
     };
 
@@ -3468,8 +3517,7 @@ pub extern "C" fn ffi_cfm_process_msg15(
     let msg15: CFMMsg15 = match bincode::deserialize(msg15_bytes) {
         Ok(msg) => msg,
         Err(e) => {
-            eprintln!("Failed to deserialize msg15: {:?}", e);
-            return FFI_CfmProcessMsg15Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize msg15: {:?}", e));
         }
     };
 
@@ -3477,8 +3525,7 @@ pub extern "C" fn ffi_cfm_process_msg15(
     let (cfm_state_cb_r8, msg16) = match cfm_process_msg15(cfm_state_cb_r7, &msg15) {
         Ok(result) => result,
         Err(e) => {
-            eprintln!("Error in cfm_process_msg15: {:?}", e);
-            return FFI_CfmProcessMsg15Result::default();
+            return ffi_fail((&e).into(), format!("Error in cfm_process_msg15: {}", e));
         }
     };
 
@@ -3486,8 +3533,7 @@ pub extern "C" fn ffi_cfm_process_msg15(
     let serialized_cfm_state_cb_r8 = match bincode::serialize(&cfm_state_cb_r8) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize cfm_state_cb_r8: {:?}", e);
-            return FFI_CfmProcessMsg15Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize cfm_state_cb_r8: {:?}", e));
         }
     };
 
@@ -3495,8 +3541,7 @@ pub extern "C" fn ffi_cfm_process_msg15(
     let serialized_msg16 = match bincode::serialize(&msg16) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize msg16: {:?}", e);
-            return FFI_CfmProcessMsg15Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize msg16: {:?}", e));
         }
     };
 
@@ -3518,6 +3563,7 @@ pub extern "C" fn ffi_cfm_process_msg15(
 
     // Return result
     FFI_CfmProcessMsg15Result {
+        status: CFMStatus::Ok,
         cfm_state_cb_r8_ptr,
         cfm_state_cb_r8_size,
         msg16_ptr,
@@ -3528,15 +3574,17 @@ pub extern "C" fn ffi_cfm_process_msg15(
 
 #[repr(C)]
 pub struct FFI_CfmProcessMsg16Result {
+    status: CFMStatus,
     cfm_state_ob_r9_ptr: *mut u8,
     cfm_state_ob_r9_size: usize,
     msg17_ptr: *mut u8,
     msg17_size: usize,
 }
 
-impl Default for FFI_CfmProcessMsg16Result {
-    fn default() -> Self {
+impl FFIResult for FFI_CfmProcessMsg16Result {
+    fn error(status: CFMStatus) -> Self {
         FFI_CfmProcessMsg16Result {
+            status,
             cfm_state_ob_r9_ptr: std::ptr::null_mut(),
             cfm_state_ob_r9_size: 0,
             msg17_ptr: std::ptr::null_mut(),
@@ -3554,8 +3602,7 @@ pub extern "C" fn ffi_cfm_process_msg16(
 ) -> FFI_CfmProcessMsg16Result {
     if cfm_state_ob_r8_ptr.is_null() || cfm_state_ob_r8_size == 0 ||
        msg16_ptr.is_null() || msg16_size == 0 {
-        eprintln!("Invalid input pointers in ffi_cfm_process_msg16");
-        return FFI_CfmProcessMsg16Result::default();
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_cfm_process_msg16".to_string());
     }
 
     // Deserialize cfm_state_ob_r8
@@ -3563,8 +3610,7 @@ pub extern "C" fn ffi_cfm_process_msg16(
     let cfm_state_ob_r8: CFMStateOBR8 = match bincode::deserialize(cfm_state_ob_r8_bytes) {
         Ok(state) => state,
         Err(e) => {
-            eprintln!("Failed to deserialize cfm_state_ob_r8: {:?}", e);
-            return FFI_CfmProcessMsg16Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize cfm_state_ob_r8: {:?}", e));
         }
     };
 
@@ -3573,8 +3619,7 @@ pub extern "C" fn ffi_cfm_process_msg16(
     let msg16: CFMMsg16 = match bincode::deserialize(msg16_bytes) {
         Ok(msg) => msg,
         Err(e) => {
-            eprintln!("Failed to deserialize msg16: {:?}", e);
-            return FFI_CfmProcessMsg16Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize msg16: {:?}", e));
         }
     };
 
@@ -3582,8 +3627,7 @@ pub extern "C" fn ffi_cfm_process_msg16(
     let (cfm_state_ob_r9, msg17) = match cfm_process_msg16(cfm_state_ob_r8, &msg16) {
         Ok(result) => result,
         Err(e) => {
-            eprintln!("Error in cfm_process_msg16: {:?}", e);
-            return FFI_CfmProcessMsg16Result::default();
+            return ffi_fail((&e).into(), format!("Error in cfm_process_msg16: {}", e));
         }
     };
 
@@ -3591,8 +3635,7 @@ pub extern "C" fn ffi_cfm_process_msg16(
     let serialized_cfm_state_ob_r9 = match bincode::serialize(&cfm_state_ob_r9) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize cfm_state_ob_r9: {:?}", e);
-            return FFI_CfmProcessMsg16Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize cfm_state_ob_r9: {:?}", e));
         }
     };
 
@@ -3600,8 +3643,7 @@ pub extern "C" fn ffi_cfm_process_msg16(
     let serialized_msg17 = match bincode::serialize(&msg17) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize msg17: {:?}", e);
-            return FFI_CfmProcessMsg16Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize msg17: {:?}", e));
         }
     };
 
@@ -3623,6 +3665,7 @@ pub extern "C" fn ffi_cfm_process_msg16(
 
     // Return result
     FFI_CfmProcessMsg16Result {
+        status: CFMStatus::Ok,
         cfm_state_ob_r9_ptr,
         cfm_state_ob_r9_size,
         msg17_ptr,
@@ -3633,15 +3676,17 @@ pub extern "C" fn ffi_cfm_process_msg16(
 
 #[repr(C)]
 pub struct FFI_CfmProcessMsg17Result {
+    status: CFMStatus,
     b_cb_value_ptr: *mut u8,
     b_cb_value_size: usize,
     msg18_ptr: *mut u8,
     msg18_size: usize,
 }
 
-impl Default for FFI_CfmProcessMsg17Result {
-    fn default() -> Self {
+impl FFIResult for FFI_CfmProcessMsg17Result {
+    fn error(status: CFMStatus) -> Self {
         FFI_CfmProcessMsg17Result {
+            status,
             b_cb_value_ptr: std::ptr::null_mut(),
             b_cb_value_size: 0,
             msg18_ptr: std::ptr::null_mut(),
@@ -3659,8 +3704,7 @@ pub extern "C" fn ffi_cfm_process_msg17(
 ) -> FFI_CfmProcessMsg17Result {
     if cfm_state_cb_r8_ptr.is_null() || cfm_state_cb_r8_size == 0 ||
        msg17_ptr.is_null() || msg17_size == 0 {
-        eprintln!("Invalid input pointers in ffi_cfm_process_msg17");
-        return FFI_CfmProcessMsg17Result::default();
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_cfm_process_msg17".to_string());
     }
 
     // Deserialize cfm_state_cb_r8
@@ -3668,8 +3712,7 @@ pub extern "C" fn ffi_cfm_process_msg17(
     let cfm_state_cb_r8: CFMStateCBR8 = match bincode::deserialize(cfm_state_cb_r8_bytes) {
         Ok(state) => state,
         Err(e) => {
-            eprintln!("Failed to deserialize cfm_state_cb_r8: {:?}", e);
-            return FFI_CfmProcessMsg17Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize cfm_state_cb_r8: {:?}", e));
         }
     };
 
@@ -3678,8 +3721,7 @@ pub extern "C" fn ffi_cfm_process_msg17(
     let msg17: CFMMsg17 = match bincode::deserialize(msg17_bytes) {
         Ok(msg) => msg,
         Err(e) => {
-            eprintln!("Failed to deserialize msg17: {:?}", e);
-            return FFI_CfmProcessMsg17Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize msg17: {:?}", e));
         }
     };
 
@@ -3687,8 +3729,7 @@ pub extern "C" fn ffi_cfm_process_msg17(
     let (b_cb_value, msg18) = match cfm_process_msg17(cfm_state_cb_r8, &msg17) {
         Ok(result) => result,
         Err(e) => {
-            eprintln!("Error in cfm_process_msg17: {:?}", e);
-            return FFI_CfmProcessMsg17Result::default();
+            return ffi_fail((&e).into(), format!("Error in cfm_process_msg17: {}", e));
         }
     };
 
@@ -3696,8 +3737,7 @@ pub extern "C" fn ffi_cfm_process_msg17(
     let serialized_b_cb_value = match bincode::serialize(&b_cb_value) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize b_cb_value: {:?}", e);
-            return FFI_CfmProcessMsg17Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize b_cb_value: {:?}", e));
         }
     };
 
@@ -3705,8 +3745,7 @@ pub extern "C" fn ffi_cfm_process_msg17(
     let serialized_msg18 = match bincode::serialize(&msg18) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize msg18: {:?}", e);
-            return FFI_CfmProcessMsg17Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize msg18: {:?}", e));
         }
     };
 
@@ -3728,6 +3767,7 @@ pub extern "C" fn ffi_cfm_process_msg17(
 
     // Return result
     FFI_CfmProcessMsg17Result {
+        status: CFMStatus::Ok,
         b_cb_value_ptr,
         b_cb_value_size,
         msg18_ptr,
@@ -3738,13 +3778,15 @@ pub extern "C" fn ffi_cfm_process_msg17(
 
 #[repr(C)]
 pub struct FFI_CfmProcessMsg18Result {
+    status: CFMStatus,
     b_ob_value_ptr: *mut u8,
     b_ob_value_size: usize,
 }
 
-impl Default for FFI_CfmProcessMsg18Result {
-    fn default() -> Self {
+impl FFIResult for FFI_CfmProcessMsg18Result {
+    fn error(status: CFMStatus) -> Self {
         FFI_CfmProcessMsg18Result {
+            status,
             b_ob_value_ptr: std::ptr::null_mut(),
             b_ob_value_size: 0,
         }
@@ -3760,8 +3802,7 @@ pub extern "C" fn ffi_cfm_process_msg18(
 ) -> FFI_CfmProcessMsg18Result {
     if cfm_state_ob_r9_ptr.is_null() || cfm_state_ob_r9_size == 0 ||
        msg18_ptr.is_null() || msg18_size == 0 {
-        eprintln!("Invalid input pointers in ffi_cfm_process_msg18");
-        return FFI_CfmProcessMsg18Result::default();
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_cfm_process_msg18".to_string());
     }
 
     // Deserialize cfm_state_ob_r9
@@ -3769,8 +3810,7 @@ pub extern "C" fn ffi_cfm_process_msg18(
     let cfm_state_ob_r9: CFMStateOBR9 = match bincode::deserialize(cfm_state_ob_r9_bytes) {
         Ok(state) => state,
         Err(e) => {
-            eprintln!("Failed to deserialize cfm_state_ob_r9: {:?}", e);
-            return FFI_CfmProcessMsg18Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize cfm_state_ob_r9: {:?}", e));
         }
     };
 
@@ -3779,8 +3819,7 @@ pub extern "C" fn ffi_cfm_process_msg18(
     let msg18: CFMMsg18 = match bincode::deserialize(msg18_bytes) {
         Ok(msg) => msg,
         Err(e) => {
-            eprintln!("Failed to deserialize msg18: {:?}", e);
-            return FFI_CfmProcessMsg18Result::default();
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize msg18: {:?}", e));
         }
     };
 
@@ -3788,8 +3827,7 @@ pub extern "C" fn ffi_cfm_process_msg18(
     let b_ob_value = match cfm_process_msg18(cfm_state_ob_r9, &msg18) {
         Ok(result) => result,
         Err(e) => {
-            eprintln!("Error in cfm_process_msg18: {:?}", e);
-            return FFI_CfmProcessMsg18Result::default();
+            return ffi_fail((&e).into(), format!("Error in cfm_process_msg18: {}", e));
         }
     };
 
@@ -3797,8 +3835,7 @@ pub extern "C" fn ffi_cfm_process_msg18(
     let serialized_b_ob_value = match bincode::serialize(&b_ob_value) {
         Ok(data) => data,
         Err(e) => {
-            eprintln!("Failed to serialize b_ob_value: {:?}", e);
-            return FFI_CfmProcessMsg18Result::default();
+            return ffi_fail(CFMStatus::Serialization, format!("Failed to serialize b_ob_value: {:?}", e));
         }
     };
 
@@ -3812,7 +3849,864 @@ pub extern "C" fn ffi_cfm_process_msg18(
 
     // Return result
     FFI_CfmProcessMsg18Result {
+        status: CFMStatus::Ok,
         b_ob_value_ptr,
         b_ob_value_size,
     }
 }
+
+
+/// Result of the session handle functions.
+/// `msg_ptr` is null on error or when there is no message to send,
+/// otherwise it holds an envelope encoded message to be freed with `free_buffer`.
+#[repr(C)]
+pub struct FFI_SessionResult {
+    status: CFMStatus,
+    msg_ptr: *mut u8,
+    msg_size: usize,
+}
+
+impl FFIResult for FFI_SessionResult {
+    fn error(status: CFMStatus) -> Self {
+        FFI_SessionResult {
+            status,
+            msg_ptr: ptr::null_mut(),
+            msg_size: 0,
+        }
+    }
+}
+
+impl FFI_SessionResult {
+    fn from_message(msg: Option<envelope::Message>) -> Self {
+        let msg = match msg {
+            Some(msg) => msg,
+            None => {
+                return FFI_SessionResult {
+                    status: CFMStatus::Ok,
+                    msg_ptr: ptr::null_mut(),
+                    msg_size: 0,
+                }
+            }
+        };
+
+        let serialized = match envelope::encode(&msg) {
+            Ok(data) => data,
+            Err(e) => {
+                return ffi_fail((&e).into(), format!("Failed to encode message: {}", e));
+            }
+        };
+
+        let msg_size = serialized.len();
+        let layout = Layout::array::<u8>(msg_size).unwrap();
+        let msg_ptr = unsafe { alloc(layout) };
+        if msg_ptr.is_null() {
+            return ffi_fail(CFMStatus::AllocationFailed, format!("Memory allocation failed for message, size: {}", msg_size));
+        }
+        unsafe {
+            msg_ptr.copy_from_nonoverlapping(serialized.as_ptr(), msg_size);
+        }
+
+        FFI_SessionResult {
+            status: CFMStatus::Ok,
+            msg_ptr,
+            msg_size,
+        }
+    }
+}
+
+/// The CFM shares of an ABT run, dropping the masks of the fair release that
+/// the per-message API does not use
+fn cfm_shares(shares: &[Share]) -> Option<&[Share]> {
+    match shares.len() {
+        NUMBER_OF_SHARES | ABT_SHARES => Some(&shares[..NUMBER_OF_SHARES]),
+        _ => None,
+    }
+}
+
+/// Deserialize a session id passed as serialized bytes
+fn session_id_from_ffi(session_id_ptr: *const u8, session_id_size: usize) -> Option<[u8; 32]> {
+    if session_id_ptr.is_null() || session_id_size == 0 {
+        return None;
+    }
+    let session_id_bytes = unsafe { slice::from_raw_parts(session_id_ptr, session_id_size) };
+    bincode::deserialize(session_id_bytes).ok()
+}
+
+/// Read the limits of the customers, None for a null pointer
+fn big_l_y_from_ffi(big_l_y_ptr: *const u64, big_l_y_size: usize) -> Option<Vec<U64>> {
+    if big_l_y_ptr.is_null() {
+        return None;
+    }
+    let big_l_y_values = unsafe { slice::from_raw_parts(big_l_y_ptr, big_l_y_size) };
+    Some(big_l_y_values.iter().map(|&val| U64::from(val)).collect())
+}
+
+/// Creates a session handle for `role` (1 = OB, 2 = CB) in `out_handle`.
+/// OT seeds, shares, triples and round states stay inside the handle.
+/// Free it with `ffi_session_free`.
+#[no_mangle]
+pub extern "C" fn ffi_session_new(role: u8, out_handle: *mut *mut SessionHandle) -> CFMStatus {
+    if out_handle.is_null() {
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_session_new".to_string());
+    }
+
+    match envelope::Role::try_from(role) {
+        Ok(role) => {
+            unsafe { *out_handle = Box::into_raw(Box::new(SessionHandle::new(role, P))) };
+            CFMStatus::Ok
+        }
+        Err(e) => ffi_fail(CFMStatus::InvalidInput, format!("Invalid role in ffi_session_new: {}", e)),
+    }
+}
+
+/// Frees a session handle and the secrets it holds
+#[no_mangle]
+pub extern "C" fn ffi_session_free(handle: *mut SessionHandle) {
+    if handle.is_null() {
+        return;
+    }
+
+    unsafe {
+        drop(Box::from_raw(handle));
+    }
+}
+
+/// Starts CFM init. Returns CFMInitMsg1 for OB, no message for CB.
+#[no_mangle]
+pub extern "C" fn ffi_session_start_init(
+    handle: *mut SessionHandle,
+    session_id_ptr: *const u8,
+    session_id_size: usize,
+    rng_ptr: *mut c_void,
+) -> FFI_SessionResult {
+    let session_id = session_id_from_ffi(session_id_ptr, session_id_size);
+    let (handle, session_id) = match (unsafe { handle.as_mut() }, session_id) {
+        (Some(handle), Some(session_id)) if !rng_ptr.is_null() => (handle, session_id),
+        _ => {
+            return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_session_start_init".to_string());
+        }
+    };
+    let rng = unsafe { &mut *(rng_ptr as *mut RngHolder) };
+
+    match handle.start_init(&session_id, rng) {
+        Ok(msg) => FFI_SessionResult::from_message(msg),
+        Err(e) => ffi_fail((&e).into(), format!("Error in ffi_session_start_init: {}", e)),
+    }
+}
+
+/// Starts ABT for one CFM session. Returns ABTMsg1 for CB, no message for OB.
+#[no_mangle]
+pub extern "C" fn ffi_session_start_abt(
+    handle: *mut SessionHandle,
+    session_id_ptr: *const u8,
+    session_id_size: usize,
+    rng_ptr: *mut c_void,
+) -> FFI_SessionResult {
+    let session_id = session_id_from_ffi(session_id_ptr, session_id_size);
+    let (handle, session_id) = match (unsafe { handle.as_mut() }, session_id) {
+        (Some(handle), Some(session_id)) if !rng_ptr.is_null() => (handle, session_id),
+        _ => {
+            return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_session_start_abt".to_string());
+        }
+    };
+    let rng = unsafe { &mut *(rng_ptr as *mut RngHolder) };
+
+    match handle.start_abt(&session_id, rng) {
+        Ok(msg) => FFI_SessionResult::from_message(msg),
+        Err(e) => ffi_fail((&e).into(), format!("Error in ffi_session_start_abt: {}", e)),
+    }
+}
+
+/// Starts CFM for OB with the shares and triples of the last ABT run.
+/// Returns CFMMsg1.
+#[no_mangle]
+pub extern "C" fn ffi_session_start_cfm_ob(
+    handle: *mut SessionHandle,
+    session_id_ptr: *const u8,
+    session_id_size: usize,
+    output_policy: u8,
+    big_l: u64,
+    private_limit: bool,
+    big_x: u64,
+    customer_y_bytes_ptr: *const u8,
+    customer_y_bytes_size: usize,
+    rng_ptr: *mut c_void,
+) -> FFI_SessionResult {
+    let session_id = session_id_from_ffi(session_id_ptr, session_id_size);
+    let (handle, session_id) = match (unsafe { handle.as_mut() }, session_id) {
+        (Some(handle), Some(session_id))
+            if !rng_ptr.is_null()
+                && !customer_y_bytes_ptr.is_null()
+                && customer_y_bytes_size != 0 =>
+        {
+            (handle, session_id)
+        }
+        _ => {
+            return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_session_start_cfm_ob".to_string());
+        }
+    };
+
+    let customer_y_bytes_data =
+        unsafe { slice::from_raw_parts(customer_y_bytes_ptr, customer_y_bytes_size) };
+    let customer_y_bytes: [u8; 32] = match bincode::deserialize(customer_y_bytes_data) {
+        Ok(data) => data,
+        Err(e) => {
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize customer_y_bytes: {:?}", e));
+        }
+    };
+    let output_policy = match OutputPolicy::try_from(output_policy) {
+        Ok(policy) => policy,
+        Err(e) => return ffi_fail((&e).into(), format!("Invalid output policy {}", output_policy)),
+    };
+    let rng = unsafe { &mut *(rng_ptr as *mut RngHolder) };
+
+    match handle.start_cfm_ob(
+        &session_id,
+        output_policy,
+        (!private_limit).then(|| U64::from(big_l)),
+        U64::from(big_x),
+        &customer_y_bytes,
+        rng,
+    ) {
+        Ok(msg) => FFI_SessionResult::from_message(Some(msg)),
+        Err(e) => ffi_fail((&e).into(), format!("Error in ffi_session_start_cfm_ob: {}", e)),
+    }
+}
+
+/// Starts CFM for CB with the shares and triples of the last ABT run.
+/// `big_l_y_ptr` holds the limit of each customer, null if all have limit
+/// `big_l`. CB waits for CFMMsg1, no message is returned.
+#[no_mangle]
+pub extern "C" fn ffi_session_start_cfm_cb(
+    handle: *mut SessionHandle,
+    session_id_ptr: *const u8,
+    session_id_size: usize,
+    output_policy: u8,
+    big_l: u64,
+    private_limit: bool,
+    big_y_bytes_ptr: *const u8,
+    big_y_bytes_size: usize,
+    big_z_ptr: *const u64,
+    big_z_size: usize,
+    big_l_y_ptr: *const u64,
+    big_l_y_size: usize,
+) -> FFI_SessionResult {
+    let session_id = session_id_from_ffi(session_id_ptr, session_id_size);
+    let (handle, session_id) = match (unsafe { handle.as_mut() }, session_id) {
+        (Some(handle), Some(session_id))
+            if !big_y_bytes_ptr.is_null()
+                && big_y_bytes_size != 0
+                && !big_z_ptr.is_null()
+                && big_z_size != 0 =>
+        {
+            (handle, session_id)
+        }
+        _ => {
+            return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_session_start_cfm_cb".to_string());
+        }
+    };
+
+    let big_y_bytes_data = unsafe { slice::from_raw_parts(big_y_bytes_ptr, big_y_bytes_size) };
+    let big_y_bytes: Vec<[u8; 32]> = match bincode::deserialize(big_y_bytes_data) {
+        Ok(data) => data,
+        Err(e) => {
+            return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize big_y_bytes: {:?}", e));
+        }
+    };
+    let big_z_values = unsafe { slice::from_raw_parts(big_z_ptr, big_z_size) };
+    let big_z: Vec<U64> = big_z_values.iter().map(|&val| U64::from(val)).collect();
+    let output_policy = match OutputPolicy::try_from(output_policy) {
+        Ok(policy) => policy,
+        Err(e) => return ffi_fail((&e).into(), format!("Invalid output policy {}", output_policy)),
+    };
+
+    match handle.start_cfm_cb(
+        &session_id,
+        output_policy,
+        U64::from(big_l),
+        private_limit,
+        big_y_bytes,
+        big_z,
+        big_l_y_from_ffi(big_l_y_ptr, big_l_y_size),
+    ) {
+        Ok(()) => FFI_SessionResult::from_message(None),
+        Err(e) => ffi_fail((&e).into(), format!("Error in ffi_session_start_cfm_cb: {}", e)),
+    }
+}
+
+/// Processes an envelope encoded message of the other party.
+/// Returns the answer, or no message when the sub-protocol is done on this side.
+#[no_mangle]
+pub extern "C" fn ffi_session_step(
+    handle: *mut SessionHandle,
+    msg_ptr: *const u8,
+    msg_size: usize,
+    rng_ptr: *mut c_void,
+) -> FFI_SessionResult {
+    let handle = match unsafe { handle.as_mut() } {
+        Some(handle) if !msg_ptr.is_null() && msg_size != 0 && !rng_ptr.is_null() => handle,
+        _ => {
+            return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_session_step".to_string());
+        }
+    };
+
+    let msg_bytes = unsafe { slice::from_raw_parts(msg_ptr, msg_size) };
+    let incoming = match envelope::decode(msg_bytes) {
+        Ok(envelope) => envelope.message,
+        Err(e) => {
+            return ffi_fail((&e).into(), format!("Invalid envelope: {}", e));
+        }
+    };
+    let rng = unsafe { &mut *(rng_ptr as *mut RngHolder) };
+
+    match handle.step(incoming, rng) {
+        Ok(msg) => FFI_SessionResult::from_message(msg),
+        Err(e) => ffi_fail((&e).into(), format!("Error in ffi_session_step: {}", e)),
+    }
+}
+
+/// Processes an envelope encoded message of the other party sent with its
+/// bincode `Signature` of `ffi_session_sign_outbound`, checked against the 32
+/// byte verifying key `peer_key_ptr`. A message whose signature does not
+/// verify fails with `InvalidSignature` and leaves the handle unchanged.
+/// The 32 byte signing key `key_ptr` of this party signs the escrows and
+/// acceptances of the fair release.
+#[no_mangle]
+pub extern "C" fn ffi_session_step_signed(
+    handle: *mut SessionHandle,
+    msg_ptr: *const u8,
+    msg_size: usize,
+    signature_ptr: *const u8,
+    signature_size: usize,
+    key_ptr: *const u8,
+    key_size: usize,
+    peer_key_ptr: *const u8,
+    peer_key_size: usize,
+    rng_ptr: *mut c_void,
+) -> FFI_SessionResult {
+    let handle = match unsafe { handle.as_mut() } {
+        Some(handle) if !msg_ptr.is_null() && msg_size != 0 && !signature_ptr.is_null() && signature_size != 0 && !key_ptr.is_null() && key_size == 32 && !peer_key_ptr.is_null() && peer_key_size == 32 && !rng_ptr.is_null() => handle,
+        _ => {
+            return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_session_step_signed".to_string());
+        }
+    };
+
+    let msg_bytes = unsafe { slice::from_raw_parts(msg_ptr, msg_size) };
+    let incoming = match envelope::decode(msg_bytes) {
+        Ok(envelope) => envelope.message,
+        Err(e) => {
+            return ffi_fail((&e).into(), format!("Invalid envelope: {}", e));
+        }
+    };
+    let signature_bytes = unsafe { slice::from_raw_parts(signature_ptr, signature_size) };
+    let signature: Signature = match bincode::deserialize(signature_bytes) {
+        Ok(signature) => signature,
+        Err(e) => return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize message signature: {:?}", e)),
+    };
+    let identity = party_identity(key_ptr, peer_key_ptr);
+    let rng = unsafe { &mut *(rng_ptr as *mut RngHolder) };
+
+    match handle.step_signed(incoming, &signature, &identity, rng) {
+        Ok(msg) => FFI_SessionResult::from_message(msg),
+        Err(e) => ffi_fail((&e).into(), format!("Error in ffi_session_step_signed: {}", e)),
+    }
+}
+
+/// Signs the transcript hash after the message this party has just sent with
+/// the 32 byte signing key `key_ptr` and writes the bincode `Signature` to
+/// `out_ptr`, to send along with the message. Free it with `free_buffer`.
+#[no_mangle]
+pub extern "C" fn ffi_session_sign_outbound(
+    handle: *const SessionHandle,
+    key_ptr: *const u8,
+    key_size: usize,
+    rng_ptr: *mut c_void,
+    out_ptr: *mut *mut u8,
+    out_size: *mut usize,
+) -> CFMStatus {
+    let handle = match unsafe { handle.as_ref() } {
+        Some(handle) if !key_ptr.is_null() && key_size == 32 && !rng_ptr.is_null() && !out_ptr.is_null() && !out_size.is_null() => handle,
+        _ => {
+            return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_session_sign_outbound".to_string());
+        }
+    };
+    let key = SigningKey::from_bytes(unsafe { &*(key_ptr as *const [u8; 32]) });
+    let rng = unsafe { &mut *(rng_ptr as *mut RngHolder) };
+
+    match bincode::serialize(&handle.sign_outbound(&key, rng)) {
+        Ok(bytes) => to_buffer(&bytes, out_ptr, out_size),
+        Err(e) => ffi_fail(CFMStatus::Serialization, format!("Failed to serialize message signature: {:?}", e)),
+    }
+}
+
+/// Starts ABT under the next session id derived from the OT seeds of the
+/// last CFM init. Returns ABTMsg1 for CB, no message for OB.
+#[no_mangle]
+pub extern "C" fn ffi_session_start_next_abt(handle: *mut SessionHandle, rng_ptr: *mut c_void) -> FFI_SessionResult {
+    let handle = match unsafe { handle.as_mut() } {
+        Some(handle) if !rng_ptr.is_null() => handle,
+        _ => {
+            return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_session_start_next_abt".to_string());
+        }
+    };
+    let rng = unsafe { &mut *(rng_ptr as *mut RngHolder) };
+
+    match handle.start_next_abt(rng) {
+        Ok(msg) => FFI_SessionResult::from_message(msg),
+        Err(e) => ffi_fail((&e).into(), format!("Error in ffi_session_start_next_abt: {}", e)),
+    }
+}
+
+/// Sets the rotation policy of the OT seeds: at most `max_abt_sessions` ABT
+/// sessions within `max_age_secs` seconds of CFM init
+#[no_mangle]
+pub extern "C" fn ffi_session_set_rotation_policy(
+    handle: *mut SessionHandle,
+    max_age_secs: u64,
+    max_abt_sessions: u64,
+) -> CFMStatus {
+    let handle = match unsafe { handle.as_mut() } {
+        Some(handle) => handle,
+        None => {
+            return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_session_set_rotation_policy".to_string());
+        }
+    };
+
+    handle.set_rotation_policy(RotationPolicy {
+        max_age: std::time::Duration::from_secs(max_age_secs),
+        max_abt_sessions,
+    });
+    CFMStatus::Ok
+}
+
+/// Settles the pending ledger update of the party with the result bit b, when
+/// it did not learn b from CFM, or drops it. OB and CB must settle the same
+/// way. Fails with `SessionLedgerNoPendingTransfer` unless a finished CFM session
+/// left an update pending.
+#[no_mangle]
+pub extern "C" fn ffi_session_settle_transfer(handle: *mut SessionHandle, approved: bool) -> CFMStatus {
+    let handle = match unsafe { handle.as_mut() } {
+        Some(handle) => handle,
+        None => {
+            return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_session_settle_transfer".to_string());
+        }
+    };
+
+    match handle.settle_transfer(approved) {
+        Ok(()) => CFMStatus::Ok,
+        Err(e) => ffi_fail((&e).into(), format!("Error in ffi_session_settle_transfer: {}", e)),
+    }
+}
+
+/// Identity from the 32 byte signing key `key_ptr` and the 32 byte verifying
+/// key `peer_key_ptr` of the other party, both checked non-null by the caller
+fn party_identity(key_ptr: *const u8, peer_key_ptr: *const u8) -> PartyIdentity {
+    PartyIdentity {
+        key: SigningKey::from_bytes(unsafe { &*(key_ptr as *const [u8; 32]) }),
+        peer_key: VerifyingKey(unsafe { *(peer_key_ptr as *const [u8; 32]) }),
+    }
+}
+
+/// Starts the fair release of b after a CFM session under the `Shared` output
+/// policy, with the 32 byte signing key `key_ptr`, the 32 byte verifying key
+/// `peer_key_ptr` of the other party and the 32 byte verifying key
+/// `arbiter_ptr` of the arbiter. Returns FairMsg1 for OB, no message for CB.
+/// Later messages go through `ffi_session_step_signed` with the same keys.
+#[no_mangle]
+pub extern "C" fn ffi_session_start_fair_release(
+    handle: *mut SessionHandle,
+    key_ptr: *const u8,
+    key_size: usize,
+    peer_key_ptr: *const u8,
+    peer_key_size: usize,
+    arbiter_ptr: *const u8,
+    arbiter_size: usize,
+    rng_ptr: *mut c_void,
+) -> FFI_SessionResult {
+    let handle = match unsafe { handle.as_mut() } {
+        Some(handle) if !key_ptr.is_null() && key_size == 32 && !peer_key_ptr.is_null() && peer_key_size == 32 && !arbiter_ptr.is_null() && arbiter_size == 32 && !rng_ptr.is_null() => handle,
+        _ => {
+            return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_session_start_fair_release".to_string());
+        }
+    };
+    let identity = party_identity(key_ptr, peer_key_ptr);
+    let arbiter = VerifyingKey(unsafe { *(arbiter_ptr as *const [u8; 32]) });
+    let rng = unsafe { &mut *(rng_ptr as *mut RngHolder) };
+
+    match handle.start_fair_release(&identity, &arbiter, rng) {
+        Ok(msg) => FFI_SessionResult::from_message(msg),
+        Err(e) => ffi_fail((&e).into(), format!("Error in ffi_session_start_fair_release: {}", e)),
+    }
+}
+
+/// Writes the bincode `FairEvidence` of a fair release to `out_ptr`, for the
+/// arbiter. Free it with `free_buffer`. Fails with `SessionInvalidState`
+/// unless both escrows are accepted and the other party has not released its
+/// share of b.
+#[no_mangle]
+pub extern "C" fn ffi_session_fair_evidence(
+    handle: *const SessionHandle,
+    out_ptr: *mut *mut u8,
+    out_size: *mut usize,
+) -> CFMStatus {
+    let handle = match unsafe { handle.as_ref() } {
+        Some(handle) if !out_ptr.is_null() && !out_size.is_null() => handle,
+        _ => {
+            return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_session_fair_evidence".to_string());
+        }
+    };
+    let evidence = match handle.fair_evidence() {
+        Some(evidence) => evidence,
+        None => return ffi_fail(CFMStatus::SessionInvalidState, "No fair release evidence in ffi_session_fair_evidence".to_string()),
+    };
+
+    match bincode::serialize(evidence) {
+        Ok(bytes) => to_buffer(&bytes, out_ptr, out_size),
+        Err(e) => ffi_fail(CFMStatus::Serialization, format!("Failed to serialize fair release evidence: {:?}", e)),
+    }
+}
+
+/// The arbiter checks a `FairEvidence` of `ffi_session_fair_evidence` for the
+/// 32 byte `session_id` against the 32 byte verifying keys of OB and CB and
+/// writes the bincode `Resolution` to `out_ptr`, decrypted with its 32 byte
+/// signing key `arbiter_ptr`. Free it with `free_buffer`. A failed check is
+/// `FairInvalidEscrow` or `FairInvalidSignature`, the message names the party.
+/// The arbiter sends the resolution to both OB and CB, whichever brought the
+/// evidence.
+#[no_mangle]
+pub extern "C" fn ffi_arbiter_resolve(
+    arbiter_ptr: *const u8,
+    arbiter_size: usize,
+    session_id_ptr: *const u8,
+    session_id_size: usize,
+    ob_key_ptr: *const u8,
+    ob_key_size: usize,
+    cb_key_ptr: *const u8,
+    cb_key_size: usize,
+    evidence_ptr: *const u8,
+    evidence_size: usize,
+    out_ptr: *mut *mut u8,
+    out_size: *mut usize,
+) -> CFMStatus {
+    if arbiter_ptr.is_null() || arbiter_size != 32 || session_id_ptr.is_null() || session_id_size != 32 || ob_key_ptr.is_null() || ob_key_size != 32 || cb_key_ptr.is_null() || cb_key_size != 32 || evidence_ptr.is_null() || evidence_size == 0 || out_ptr.is_null() || out_size.is_null() {
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_arbiter_resolve".to_string());
+    }
+    let evidence_bytes = unsafe { slice::from_raw_parts(evidence_ptr, evidence_size) };
+    let evidence: FairEvidence = match bincode::deserialize(evidence_bytes) {
+        Ok(evidence) => evidence,
+        Err(e) => return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize fair release evidence: {:?}", e)),
+    };
+    let arbiter = SigningKey::from_bytes(unsafe { &*(arbiter_ptr as *const [u8; 32]) });
+    let session_id: &[u8; 32] = unsafe { &*(session_id_ptr as *const [u8; 32]) };
+    let ob_key = VerifyingKey(unsafe { *(ob_key_ptr as *const [u8; 32]) });
+    let cb_key = VerifyingKey(unsafe { *(cb_key_ptr as *const [u8; 32]) });
+
+    let resolution = match arbiter_resolve(&arbiter, session_id, &ob_key, &cb_key, &evidence) {
+        Ok(resolution) => resolution,
+        Err(e) => return ffi_fail((&e).into(), format!("Error in ffi_arbiter_resolve: {}", e)),
+    };
+    match bincode::serialize(&resolution) {
+        Ok(bytes) => to_buffer(&bytes, out_ptr, out_size),
+        Err(e) => ffi_fail(CFMStatus::Serialization, format!("Failed to serialize resolution: {:?}", e)),
+    }
+}
+
+/// Finishes a fair release with the bincode `Resolution` of
+/// `ffi_arbiter_resolve`, b is then in `ffi_session_result`. Fails with
+/// `FairInvalidEscrow` if no pair of the other party passes the MAC check.
+#[no_mangle]
+pub extern "C" fn ffi_session_resolve_fair_release(
+    handle: *mut SessionHandle,
+    resolution_ptr: *const u8,
+    resolution_size: usize,
+) -> CFMStatus {
+    let handle = match unsafe { handle.as_mut() } {
+        Some(handle) if !resolution_ptr.is_null() && resolution_size != 0 => handle,
+        _ => {
+            return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_session_resolve_fair_release".to_string());
+        }
+    };
+    let resolution_bytes = unsafe { slice::from_raw_parts(resolution_ptr, resolution_size) };
+    let resolution: Resolution = match bincode::deserialize(resolution_bytes) {
+        Ok(resolution) => resolution,
+        Err(e) => return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize resolution: {:?}", e)),
+    };
+
+    match handle.resolve_fair_release(&resolution) {
+        Ok(_) => CFMStatus::Ok,
+        Err(e) => ffi_fail((&e).into(), format!("Error in ffi_session_resolve_fair_release: {}", e)),
+    }
+}
+
+/// Result bit of the last CFM session, from CFM or its fair release: 1 or
+/// 0, -1 if it has not finished or the output policy hides b from this party
+#[no_mangle]
+pub extern "C" fn ffi_session_result(handle: *const SessionHandle) -> i32 {
+    match unsafe { handle.as_ref() }.and_then(|handle| handle.result()) {
+        Some(true) => 1,
+        Some(false) => 0,
+        None => -1,
+    }
+}
+
+/// Writes the bincode `CFMOutput` of the last CFM session to `out_ptr`, the
+/// result bit, `Hidden` or this party's share of b. Free it with `free_buffer`.
+/// Fails with `SessionInvalidState` if the session has not finished.
+#[no_mangle]
+pub extern "C" fn ffi_session_output(
+    handle: *const SessionHandle,
+    out_ptr: *mut *mut u8,
+    out_size: *mut usize,
+) -> CFMStatus {
+    let handle = match unsafe { handle.as_ref() } {
+        Some(handle) if !out_ptr.is_null() && !out_size.is_null() => handle,
+        _ => {
+            return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_session_output".to_string());
+        }
+    };
+    let output = match handle.output() {
+        Some(output) => output,
+        None => return ffi_fail(CFMStatus::SessionInvalidState, "CFM has not finished in ffi_session_output".to_string()),
+    };
+
+    match bincode::serialize(output) {
+        Ok(bytes) => to_buffer(&bytes, out_ptr, out_size),
+        Err(e) => ffi_fail(CFMStatus::Serialization, format!("Failed to serialize output: {:?}", e)),
+    }
+}
+
+/// Resend buffer of the session handle: the last message it sent in the
+/// running sub-protocol, no message if it has nothing to resend.
+#[no_mangle]
+pub extern "C" fn ffi_session_last_outbound(handle: *const SessionHandle) -> FFI_SessionResult {
+    match unsafe { handle.as_ref() } {
+        Some(handle) => FFI_SessionResult::from_message(handle.last_outbound().cloned()),
+        None => ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_session_last_outbound".to_string()),
+    }
+}
+
+/// Encrypts the whole session handle under the 32 byte `key` into `out_ptr`.
+/// Free the snapshot with `free_buffer`.
+#[no_mangle]
+pub extern "C" fn ffi_session_snapshot(
+    handle: *const SessionHandle,
+    key_ptr: *const u8,
+    key_size: usize,
+    rng_ptr: *mut c_void,
+    out_ptr: *mut *mut u8,
+    out_size: *mut usize,
+) -> CFMStatus {
+    let handle = match unsafe { handle.as_ref() } {
+        Some(handle) if !key_ptr.is_null() && key_size == 32 && !rng_ptr.is_null() && !out_ptr.is_null() && !out_size.is_null() => handle,
+        _ => {
+            return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_session_snapshot".to_string());
+        }
+    };
+    let key: &[u8; 32] = unsafe { &*(key_ptr as *const [u8; 32]) };
+    let rng = unsafe { &mut *(rng_ptr as *mut RngHolder) };
+
+    match handle.snapshot(key, rng) {
+        Ok(snapshot) => to_buffer(&snapshot, out_ptr, out_size),
+        Err(e) => ffi_fail((&e).into(), format!("Error in ffi_session_snapshot: {}", e)),
+    }
+}
+
+/// Restores a session handle from a snapshot of `ffi_session_snapshot` into
+/// `out_handle`. Free it with `ffi_session_free`.
+#[no_mangle]
+pub extern "C" fn ffi_session_restore(
+    snapshot_ptr: *const u8,
+    snapshot_size: usize,
+    key_ptr: *const u8,
+    key_size: usize,
+    out_handle: *mut *mut SessionHandle,
+) -> CFMStatus {
+    if snapshot_ptr.is_null() || snapshot_size == 0 || key_ptr.is_null() || key_size != 32 || out_handle.is_null() {
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_session_restore".to_string());
+    }
+    let snapshot = unsafe { slice::from_raw_parts(snapshot_ptr, snapshot_size) };
+    let key: &[u8; 32] = unsafe { &*(key_ptr as *const [u8; 32]) };
+
+    match SessionHandle::restore(snapshot, key) {
+        Ok(handle) => {
+            unsafe { *out_handle = Box::into_raw(Box::new(handle)) };
+            CFMStatus::Ok
+        }
+        Err(e) => ffi_fail((&e).into(), format!("Error in ffi_session_restore: {}", e)),
+    }
+}
+
+/// Writes the 32 byte verifying key of the 32 byte signing key `key_ptr`
+/// to the 32 byte buffer `out_ptr`.
+#[no_mangle]
+pub extern "C" fn ffi_verifying_key(
+    key_ptr: *const u8,
+    key_size: usize,
+    out_ptr: *mut u8,
+    out_size: usize,
+) -> CFMStatus {
+    if key_ptr.is_null() || key_size != 32 || out_ptr.is_null() || out_size != 32 {
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_verifying_key".to_string());
+    }
+    let key = SigningKey::from_bytes(unsafe { &*(key_ptr as *const [u8; 32]) });
+    let verifying_key = key.verifying_key();
+    unsafe { out_ptr.copy_from_nonoverlapping(verifying_key.0.as_ptr(), 32) };
+
+    CFMStatus::Ok
+}
+
+/// Signs the abort report of the last CFM session with the 32 byte signing
+/// key `key_ptr` and writes the bincode `SignedAbortReport` to `out_ptr`.
+/// Free it with `free_buffer`. Fails with `SessionInvalidState` if no message
+/// of the other party failed a check.
+#[no_mangle]
+pub extern "C" fn ffi_session_abort_report(
+    handle: *const SessionHandle,
+    key_ptr: *const u8,
+    key_size: usize,
+    rng_ptr: *mut c_void,
+    out_ptr: *mut *mut u8,
+    out_size: *mut usize,
+) -> CFMStatus {
+    let handle = match unsafe { handle.as_ref() } {
+        Some(handle) if !key_ptr.is_null() && key_size == 32 && !rng_ptr.is_null() && !out_ptr.is_null() && !out_size.is_null() => handle,
+        _ => {
+            return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_session_abort_report".to_string());
+        }
+    };
+    let report = match handle.abort_report() {
+        Some(report) => report.clone(),
+        None => return ffi_fail(CFMStatus::SessionInvalidState, "No abort report in ffi_session_abort_report".to_string()),
+    };
+    let key = SigningKey::from_bytes(unsafe { &*(key_ptr as *const [u8; 32]) });
+    let rng = unsafe { &mut *(rng_ptr as *mut RngHolder) };
+
+    match bincode::serialize(&report.sign(&key, rng)) {
+        Ok(bytes) => to_buffer(&bytes, out_ptr, out_size),
+        Err(e) => ffi_fail(CFMStatus::Serialization, format!("Failed to serialize abort report: {:?}", e)),
+    }
+}
+
+/// Checks a `SignedAbortReport` of `ffi_session_abort_report` against the 32
+/// byte verifying key of the reporting party, `InvalidSignature` if it does
+/// not verify.
+#[no_mangle]
+pub extern "C" fn ffi_abort_report_verify(
+    report_ptr: *const u8,
+    report_size: usize,
+    key_ptr: *const u8,
+    key_size: usize,
+) -> CFMStatus {
+    if report_ptr.is_null() || report_size == 0 || key_ptr.is_null() || key_size != 32 {
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_abort_report_verify".to_string());
+    }
+    let report_bytes = unsafe { slice::from_raw_parts(report_ptr, report_size) };
+    let report: SignedAbortReport = match bincode::deserialize(report_bytes) {
+        Ok(report) => report,
+        Err(e) => return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize abort report: {:?}", e)),
+    };
+    let key = VerifyingKey(unsafe { *(key_ptr as *const [u8; 32]) });
+
+    if report.verify(&key) {
+        CFMStatus::Ok
+    } else {
+        ffi_fail(CFMStatus::InvalidSignature, "Abort report signature does not verify".to_string())
+    }
+}
+
+/// Signs the receipt of the last CFM session with the 32 byte signing key
+/// `key_ptr` and writes the bincode `Signature` to `out_ptr`, for the other
+/// party. Free it with `free_buffer`. Fails with `SessionInvalidState` if the
+/// session has not finished.
+#[no_mangle]
+pub extern "C" fn ffi_session_sign_receipt(
+    handle: *const SessionHandle,
+    key_ptr: *const u8,
+    key_size: usize,
+    rng_ptr: *mut c_void,
+    out_ptr: *mut *mut u8,
+    out_size: *mut usize,
+) -> CFMStatus {
+    let handle = match unsafe { handle.as_ref() } {
+        Some(handle) if !key_ptr.is_null() && key_size == 32 && !rng_ptr.is_null() && !out_ptr.is_null() && !out_size.is_null() => handle,
+        _ => {
+            return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_session_sign_receipt".to_string());
+        }
+    };
+    let receipt = match handle.receipt() {
+        Some(receipt) => receipt,
+        None => return ffi_fail(CFMStatus::SessionInvalidState, "No finished CFM session in ffi_session_sign_receipt".to_string()),
+    };
+    let key = SigningKey::from_bytes(unsafe { &*(key_ptr as *const [u8; 32]) });
+    let rng = unsafe { &mut *(rng_ptr as *mut RngHolder) };
+
+    match bincode::serialize(&receipt.sign(&key, rng)) {
+        Ok(bytes) => to_buffer(&bytes, out_ptr, out_size),
+        Err(e) => ffi_fail(CFMStatus::Serialization, format!("Failed to serialize receipt signature: {:?}", e)),
+    }
+}
+
+/// Writes the bincode `SignedReceipt` of the last CFM session to `out_ptr`,
+/// from the signature `own_ptr` of this party and `other_ptr` of the other
+/// party, both of `ffi_session_sign_receipt`. Free it with `free_buffer`.
+#[no_mangle]
+pub extern "C" fn ffi_session_signed_receipt(
+    handle: *const SessionHandle,
+    own_ptr: *const u8,
+    own_size: usize,
+    other_ptr: *const u8,
+    other_size: usize,
+    out_ptr: *mut *mut u8,
+    out_size: *mut usize,
+) -> CFMStatus {
+    let handle = match unsafe { handle.as_ref() } {
+        Some(handle) if !own_ptr.is_null() && own_size != 0 && !other_ptr.is_null() && other_size != 0 && !out_ptr.is_null() && !out_size.is_null() => handle,
+        _ => {
+            return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_session_signed_receipt".to_string());
+        }
+    };
+    let own_bytes = unsafe { slice::from_raw_parts(own_ptr, own_size) };
+    let other_bytes = unsafe { slice::from_raw_parts(other_ptr, other_size) };
+    let (own, other): (Signature, Signature) = match (bincode::deserialize(own_bytes), bincode::deserialize(other_bytes)) {
+        (Ok(own), Ok(other)) => (own, other),
+        (Err(e), _) | (_, Err(e)) => return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize receipt signature: {:?}", e)),
+    };
+    let signed = match handle.signed_receipt(own, other) {
+        Some(signed) => signed,
+        None => return ffi_fail(CFMStatus::SessionInvalidState, "No finished CFM session in ffi_session_signed_receipt".to_string()),
+    };
+
+    match bincode::serialize(&signed) {
+        Ok(bytes) => to_buffer(&bytes, out_ptr, out_size),
+        Err(e) => ffi_fail(CFMStatus::Serialization, format!("Failed to serialize receipt: {:?}", e)),
+    }
+}
+
+/// Checks a `SignedReceipt` of `ffi_session_signed_receipt` against the 32
+/// byte verifying keys of OB and CB, `InvalidSignature` if it does not verify.
+#[no_mangle]
+pub extern "C" fn ffi_receipt_verify(
+    receipt_ptr: *const u8,
+    receipt_size: usize,
+    ob_key_ptr: *const u8,
+    ob_key_size: usize,
+    cb_key_ptr: *const u8,
+    cb_key_size: usize,
+) -> CFMStatus {
+    if receipt_ptr.is_null() || receipt_size == 0 || ob_key_ptr.is_null() || ob_key_size != 32 || cb_key_ptr.is_null() || cb_key_size != 32 {
+        return ffi_fail(CFMStatus::InvalidInput, "Invalid input pointers in ffi_receipt_verify".to_string());
+    }
+    let receipt_bytes = unsafe { slice::from_raw_parts(receipt_ptr, receipt_size) };
+    let receipt: SignedReceipt = match bincode::deserialize(receipt_bytes) {
+        Ok(receipt) => receipt,
+        Err(e) => return ffi_fail(CFMStatus::Deserialization, format!("Failed to deserialize receipt: {:?}", e)),
+    };
+    let ob_key = VerifyingKey(unsafe { *(ob_key_ptr as *const [u8; 32]) });
+    let cb_key = VerifyingKey(unsafe { *(cb_key_ptr as *const [u8; 32]) });
+
+    if receipt.verify(&ob_key, &cb_key) {
+        CFMStatus::Ok
+    } else {
+        ffi_fail(CFMStatus::InvalidSignature, "Receipt signatures do not verify".to_string())
+    }
+}
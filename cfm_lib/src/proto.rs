@@ -1,4 +1,4 @@
-use crate::constants::{LAMBDA_BYTES, MASK_BYTES};
+use crate::constants::{LAMBDA_BYTES, MASK_BYTES, PSIT_RECORD_WORDS};
 use bytemuck::{AnyBitPattern, NoUninit};
 use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
 use curve25519_dalek::scalar::Scalar;
@@ -11,8 +11,8 @@ use serde::{Serialize, Deserialize};
 /// External H2 output representation
 pub type Hash2Bytes = [u8; LAMBDA_BYTES * 2];
 
-/// External H3 output representation
-pub type Hash3Bytes = [u8; MASK_BYTES * 2];
+/// External H3 output representation, one mask for a PSIT record
+pub type Hash3Bytes = [u8; MASK_BYTES * PSIT_RECORD_WORDS];
 
 /// POINT_BYTES_SIZE for RistrettoPoint representation
 pub const POINT_BYTES_SIZE: usize = 32;
@@ -1,6 +1,10 @@
 //! Private Set Intersection and Transfer protocol 4.2 implementation
+//!
+//! CB transfers a fixed-width [`PSITRecord`] for each list entry, OB learns
+//! the record of its own entry. All records have the same width, so adding a
+//! word to the record does not change what OB learns about the other entries.
 
-use crate::constants::MASK_BYTES;
+use crate::constants::{MASK_BYTES, PSIT_RECORD_WORDS};
 use crate::dlog_proof::DLogProof;
 use crate::errors::{PSITCBError, PSITOBError};
 use crate::proto::{decode_point, encode_point, xor_array, Hash2Bytes, Hash3Bytes, PointBytes};
@@ -12,6 +16,28 @@ use rand::prelude::SliceRandom;
 use rand_core::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
 
+/// Payload of one list entry, PSIT_RECORD_WORDS words of MASK_BYTES each
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PSITRecord(pub [U128; PSIT_RECORD_WORDS]);
+
+impl PSITRecord {
+    /// Big endian encoding of the words
+    pub fn to_bytes(&self) -> Hash3Bytes {
+        let mut bytes = [0u8; MASK_BYTES * PSIT_RECORD_WORDS];
+        for (chunk, word) in bytes.chunks_exact_mut(MASK_BYTES).zip(&self.0) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        bytes
+    }
+
+    /// Decode the words of `to_bytes`
+    pub fn from_bytes(bytes: &Hash3Bytes) -> Self {
+        PSITRecord(std::array::from_fn(|i| {
+            U128::from_be_slice(&bytes[i * MASK_BYTES..(i + 1) * MASK_BYTES])
+        }))
+    }
+}
+
 /// PSITMsg1
 #[derive(Clone, Serialize, Deserialize)]
 pub struct PSITMsg1 {
@@ -31,8 +57,8 @@ pub struct PSITMsg2 {
     /// hat(Y)
     pub hat_big_y: Vec<Hash2Bytes>,
 
-    /// hat(Z)
-    pub hat_big_z: Vec<Hash3Bytes>,
+    /// hat(Z), the masked records
+    pub hat_big_z: Vec<PSITRecord>,
 
     /// point B
     pub big_b: PointBytes,
@@ -83,12 +109,12 @@ pub fn psit_create_msg1<R: CryptoRng + RngCore>(
     (state, msg1)
 }
 
-/// CB processes PSCMsg1 from OB
+/// CB processes PSCMsg1 from OB, `records` holds the record of each entry of
+/// `big_y`
 pub fn psit_process_msg1<R: CryptoRng + RngCore>(
     session_id: &[u8; 32],
     big_y: Vec<[u8; 32]>,
-    big_z: Vec<U128>,
-    big_m: Vec<U128>,
+    records: Vec<PSITRecord>,
     msg1: &PSITMsg1,
     rng: &mut R,
 ) -> Result<PSITMsg2, PSITCBError> {
@@ -124,27 +150,19 @@ pub fn psit_process_msg1<R: CryptoRng + RngCore>(
         })
         .collect();
 
-    let mut shuffled_big_z = big_z.clone();
-    (0..shuffled_big_z.len()).for_each(|i| shuffled_big_z.swap(i, indices[i]));
-
-    let mut shuffled_big_m = big_m.clone();
-    (0..shuffled_big_m.len()).for_each(|i| shuffled_big_m.swap(i, indices[i]));
+    let mut shuffled_records = records.clone();
+    (0..shuffled_records.len()).for_each(|i| shuffled_records.swap(i, indices[i]));
 
     let mut shuffled_big_y = big_y.clone();
     (0..shuffled_big_y.len()).for_each(|i| shuffled_big_y.swap(i, indices[i]));
 
-    let mut hat_big_z: Vec<Hash3Bytes> = Vec::new();
+    let mut hat_big_z: Vec<PSITRecord> = Vec::new();
     for i in 0..shuffled_big_y.len() {
         let v = shuffled_big_y[i];
         let h1 = h1_function(session_id, &v);
         let h3 = h3_function(session_id, &h1, &(h1 * k));
-        let z: Hash3Bytes = {
-            let mut result = [0u8; 2 * MASK_BYTES];
-            result[..MASK_BYTES].copy_from_slice(&shuffled_big_z[i].to_be_bytes());
-            result[MASK_BYTES..].copy_from_slice(&shuffled_big_m[i].to_be_bytes());
-            result
-        };
-        hat_big_z.push(xor_array(h3, z));
+        let hat_z = xor_array(h3, shuffled_records[i].to_bytes());
+        hat_big_z.push(PSITRecord::from_bytes(&hat_z));
     }
 
     Ok(PSITMsg2 {
@@ -156,11 +174,8 @@ pub fn psit_process_msg1<R: CryptoRng + RngCore>(
     })
 }
 
-/// OB processes PSITMsg2 from CB
-pub fn psit_process_msg2(
-    state: &PSITStateOB,
-    msg2: &PSITMsg2,
-) -> Result<(U128, U128), PSITOBError> {
+/// OB processes PSITMsg2 from CB, returns the record of its entry
+pub fn psit_process_msg2(state: &PSITStateOB, msg2: &PSITMsg2) -> Result<PSITRecord, PSITOBError> {
     if state.session_id != msg2.session_id {
         return Err(PSITOBError::InvalidSessionID);
     }
@@ -182,25 +197,25 @@ pub fn psit_process_msg2(
     let r_inv = state.r.invert();
     let x_hat = h2_function(&state.session_id, &state.h1_y, &(big_b * r_inv));
 
-    let mut z_y: [u8; MASK_BYTES] = [0u8; MASK_BYTES];
-    let mut m_y: [u8; MASK_BYTES] = [0u8; MASK_BYTES];
-
-    if let Some(index) = msg2.hat_big_y.iter().position(|&x| x == x_hat) {
-        let z = xor_array(
-            msg2.hat_big_z[index],
-            h3_function(&state.session_id, &state.h1_y, &(big_b * r_inv)),
-        );
-        z_y.copy_from_slice(&z[..MASK_BYTES]);
-        m_y.copy_from_slice(&z[MASK_BYTES..]);
-    } else {
+    let Some(index) = msg2.hat_big_y.iter().position(|&x| x == x_hat) else {
         return Err(PSITOBError::NotInList);
-    }
-    Ok((U128::from_be_slice(&z_y), U128::from_be_slice(&m_y)))
+    };
+    let hat_z = msg2
+        .hat_big_z
+        .get(index)
+        .ok_or(PSITOBError::InvalidMessage)?;
+    let z = xor_array(
+        hat_z.to_bytes(),
+        h3_function(&state.session_id, &state.h1_y, &(big_b * r_inv)),
+    );
+    Ok(PSITRecord::from_bytes(&z))
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::psit_protocol::{psit_create_msg1, psit_process_msg1, psit_process_msg2};
+    use crate::psit_protocol::{
+        psit_create_msg1, psit_process_msg1, psit_process_msg2, PSITRecord,
+    };
     use crate::utils::Customer;
     use crypto_bigint::U128;
     use rand::Rng;
@@ -259,10 +274,20 @@ mod tests {
             U128::from_u8(20),
         ];
 
+        let records = big_z
+            .into_iter()
+            .zip(big_m)
+            .map(|(z, m)| {
+                let word = |i: u16| z.wrapping_add(&U128::from_u16(1000 * i));
+                PSITRecord([z, word(1), m, word(2), word(3), word(4)])
+            })
+            .collect();
+
         let (state, msg1) = psit_create_msg1(&session_id, &customer_x_bytes, &mut rng);
-        let msg2 =
-            psit_process_msg1(&session_id, big_y_bytes, big_z, big_m, &msg1, &mut rng).unwrap();
-        let (_z, _m) = psit_process_msg2(&state, &msg2).unwrap();
+        let msg2 = psit_process_msg1(&session_id, big_y_bytes, records, &msg1, &mut rng).unwrap();
+        let record = psit_process_msg2(&state, &msg2).unwrap();
+        let expected = [3, 1003, 13, 2003, 3003, 4003].map(U128::from_u16);
+        assert_eq!(record, PSITRecord(expected));
     }
 
     #[test]
@@ -319,9 +344,17 @@ mod tests {
             U128::from_u8(20),
         ];
 
+        let records = big_z
+            .into_iter()
+            .zip(big_m)
+            .map(|(z, m)| {
+                let word = |i: u16| z.wrapping_add(&U128::from_u16(1000 * i));
+                PSITRecord([z, word(1), m, word(2), word(3), word(4)])
+            })
+            .collect();
+
         let (state, msg1) = psit_create_msg1(&session_id, &customer_x_bytes, &mut rng);
-        let msg2 =
-            psit_process_msg1(&session_id, big_y_bytes, big_z, big_m, &msg1, &mut rng).unwrap();
+        let msg2 = psit_process_msg1(&session_id, big_y_bytes, records, &msg1, &mut rng).unwrap();
         let result = psit_process_msg2(&state, &msg2);
         assert!(result.is_err());
     }
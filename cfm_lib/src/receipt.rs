@@ -0,0 +1,85 @@
+//! Signed receipts of CFM outcomes.
+//!
+//! When a CFM session finishes, both parties hold the same [`Receipt`]: the
+//! session id, the limit L unless it is private, the transcript hash over all
+//! 18 messages, the output policy and, if both parties learn it, the result
+//! bit. All 18 messages are sent under every output policy, CFMMsg17 and
+//! CFMMsg18 only leave out the openings of [b] the policy withholds. Each
+//! party signs the receipt with its key and sends the signature to the other,
+//! together the two signatures form a [`SignedReceipt`].
+//!
+//! Neither party can later deny the outcome: an auditor checks the receipt
+//! against the verifying keys of OB and CB, and with the wire messages of the
+//! session also recomputes the transcript hash.
+
+use crate::cfm_protocol::OutputPolicy;
+use crate::constants::RECEIPT_LABEL;
+use crate::schnorr::{Signature, SigningKey, VerifyingKey};
+use crypto_bigint::{Encoding, U64};
+use merlin::Transcript;
+use rand_core::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+/// Outcome of a CFM session
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Receipt {
+    /// session id of the CFM session
+    pub session_id: [u8; 32],
+    /// capital flow limit L, None if it is a private input of CB
+    pub big_l: Option<U64>,
+    /// transcript hash over all messages of the session
+    pub transcript_hash: [u8; 32],
+    /// who learns the result bit
+    pub output_policy: OutputPolicy,
+    /// result bit b, true if the flow is allowed. None unless the output
+    /// policy is `Both`
+    pub outcome: Option<bool>,
+}
+
+/// Receipt signed by both parties
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedReceipt {
+    /// the receipt
+    pub receipt: Receipt,
+    /// signature of OB over the receipt digest
+    pub ob_signature: Signature,
+    /// signature of CB over the receipt digest
+    pub cb_signature: Signature,
+}
+
+impl Receipt {
+    /// Digest of the receipt, the signed message
+    pub fn digest(&self) -> [u8; 32] {
+        let mut t = Transcript::new(RECEIPT_LABEL.as_ref());
+        t.append_message(b"session-id", &self.session_id);
+        // empty for a private limit
+        let big_l = self.big_l.map(|big_l| big_l.to_be_bytes().to_vec());
+        t.append_message(b"big-l", &big_l.unwrap_or_default());
+        t.append_message(b"transcript-hash", &self.transcript_hash);
+        t.append_u64(b"output-policy", self.output_policy as u64);
+        // 0 for no outcome, 1 for false, 2 for true
+        t.append_u64(b"outcome", self.outcome.map_or(0, |b| b as u64 + 1));
+
+        let mut digest = [0u8; 32];
+        t.challenge_bytes(b"receipt-digest", &mut digest);
+        digest
+    }
+
+    /// Signature of one party over the receipt
+    pub fn sign<R: CryptoRng + RngCore>(&self, key: &SigningKey, rng: &mut R) -> Signature {
+        key.sign(&self.digest(), rng)
+    }
+
+    /// True if `signature` is a signature over the receipt by the holder of `key`
+    pub fn verify_signature(&self, key: &VerifyingKey, signature: &Signature) -> bool {
+        key.verify(&self.digest(), signature)
+    }
+}
+
+impl SignedReceipt {
+    /// True if the receipt is signed by the holders of `ob_key` and `cb_key`
+    pub fn verify(&self, ob_key: &VerifyingKey, cb_key: &VerifyingKey) -> bool {
+        self.receipt.verify_signature(ob_key, &self.ob_signature)
+            && self.receipt.verify_signature(cb_key, &self.cb_signature)
+    }
+}
@@ -0,0 +1,678 @@
+//! Runs CFM init, ABT and CFM for one party over a `Transport`.
+//!
+//! OB starts with a message holding the init, ABT and CFM session ids, then
+//! both parties exchange the envelope encoded messages of each sub-protocol
+//! through their `SessionHandle`. ABTMsg1, ABTMsg2 and ABTMsg3 go as the
+//! frames of `abt_stream` instead.
+//!
+//! `run_ob_authenticated` and `run_cb_authenticated` instead start with the
+//! channel binding handshake of `identity`. The session ids come from the
+//! handshake and every later message carries its MAC. Each envelope is
+//! followed by the signature of its sender over the transcript hash, see
+//! [`SessionHandle::sign_outbound`], so an abort report can name the sender.
+//! Under `OutputPolicy::Shared` with an arbiter in the inputs, the
+//! authenticated run ends with the fair release of b.
+
+use std::time::{Duration, Instant};
+
+use crypto_bigint::{U128, U64};
+use rand_core::{CryptoRng, RngCore};
+
+use crate::abt_stream::{abt_msg3_frames, ABTMsg3Consumer, MAX_FRAME_SIZE};
+use crate::cfm_protocol::{CFMOutput, OutputPolicy, ABT_SHARES, NUMBER_OF_AUTH_BEAVER_TRIPLES};
+use crate::envelope::{decode, encode, Message, ProtocolId, Role};
+use crate::errors::{EnvelopeError, IdentityError, RunError, SessionError};
+use crate::identity::{
+    handshake_create_msg1, handshake_process_msg1, handshake_process_msg2, handshake_process_msg3,
+    ChannelBinding, PartyIdentity,
+};
+use crate::schnorr::{Signature, VerifyingKey, SIGNATURE_SIZE};
+use crate::session_handle::SessionHandle;
+use crate::transport::{AuthenticatedTransport, Transport};
+
+/// Size of the session ids message of OB
+pub const SESSION_IDS_SIZE: usize = 96;
+
+/// Maximum size of the ABT frames, with room for the MAC of an
+/// authenticated transport
+const ABT_FRAME_SIZE: usize = MAX_FRAME_SIZE - 1024;
+
+/// Inputs of OB
+pub struct OBInputs {
+    /// who learns the result bit, the same for both parties
+    pub output_policy: OutputPolicy,
+    /// limit L, None if it is a private input of CB
+    pub big_l: Option<U64>,
+    /// amount X
+    pub big_x: U64,
+    /// customer hash y
+    pub y: [u8; 32],
+    /// arbiter of the fair release of b under `OutputPolicy::Shared`, the
+    /// same for both parties, authenticated runs only
+    pub arbiter: Option<VerifyingKey>,
+}
+
+/// Inputs of CB
+pub struct CBInputs {
+    /// who learns the result bit, the same for both parties
+    pub output_policy: OutputPolicy,
+    /// limit L
+    pub big_l: U64,
+    /// true if L is not disclosed to OB
+    pub private_limit: bool,
+    /// customer hashes Y
+    pub big_y: Vec<[u8; 32]>,
+    /// running totals Z, one for each customer
+    pub big_z: Vec<U64>,
+    /// limits L_y, one for each customer, None if all have limit L. Other
+    /// limits than L need `private_limit`
+    pub big_l_y: Option<Vec<U64>>,
+    /// arbiter of the fair release of b under `OutputPolicy::Shared`, the
+    /// same for both parties, authenticated runs only
+    pub arbiter: Option<VerifyingKey>,
+}
+
+/// Messages and bytes of one sub-protocol, as seen by one party
+#[derive(Debug, Clone)]
+pub struct ProtocolStats {
+    /// sub-protocol
+    pub protocol: ProtocolId,
+    /// wall time from the start to the last message
+    pub duration: Duration,
+    /// number of messages sent
+    pub messages_sent: usize,
+    /// encoded size of the messages sent
+    pub bytes_sent: usize,
+    /// number of messages received
+    pub messages_received: usize,
+    /// encoded size of the messages received
+    pub bytes_received: usize,
+}
+
+/// Outcome of a full run
+#[derive(Debug, Clone)]
+pub struct RunReport {
+    /// output of CFM, under the `Both` policy the result bit, true if
+    /// Z_y + X <= L
+    pub output: CFMOutput,
+    /// result bit of the fair release, if it ran
+    pub released: Option<bool>,
+    /// CFM init, ABT and CFM, then the fair release if it ran
+    pub stats: Vec<ProtocolStats>,
+}
+
+impl ProtocolStats {
+    fn new(protocol: ProtocolId) -> Self {
+        ProtocolStats {
+            protocol,
+            duration: Duration::ZERO,
+            messages_sent: 0,
+            bytes_sent: 0,
+            messages_received: 0,
+            bytes_received: 0,
+        }
+    }
+}
+
+fn send_counted<T: Transport>(
+    transport: &mut T,
+    stats: &mut ProtocolStats,
+    bytes: &[u8],
+) -> Result<(), RunError> {
+    transport.send(bytes)?;
+    stats.messages_sent += 1;
+    stats.bytes_sent += bytes.len();
+    Ok(())
+}
+
+fn recv_counted<T: Transport>(
+    transport: &mut T,
+    stats: &mut ProtocolStats,
+    timeout: Duration,
+) -> Result<Vec<u8>, RunError> {
+    let bytes = transport.recv(timeout)?;
+    stats.messages_received += 1;
+    stats.bytes_received += bytes.len();
+    Ok(bytes)
+}
+
+struct Driver<'a, T> {
+    transport: &'a mut T,
+    timeout: Duration,
+    handle: SessionHandle,
+    stats: Vec<ProtocolStats>,
+    identity: Option<&'a PartyIdentity>,
+}
+
+impl<T: Transport> Driver<'_, T> {
+    /// Exchanges the messages of one sub-protocol, `first` is the message
+    /// this party starts it with
+    fn run_protocol<R: CryptoRng + RngCore>(
+        &mut self,
+        protocol: ProtocolId,
+        first: Option<Message>,
+        rng: &mut R,
+    ) -> Result<(), RunError> {
+        let mut stats = ProtocolStats::new(protocol);
+        let start = Instant::now();
+        self.exchange(&mut stats, protocol, first, rng)?;
+        stats.duration = start.elapsed();
+        self.stats.push(stats);
+        Ok(())
+    }
+
+    /// Runs ABT with ABTMsg1, ABTMsg2 and ABTMsg3 sent as frames of
+    /// `abt_stream`, then exchanges the later ABT messages
+    fn run_abt<R: CryptoRng + RngCore>(
+        &mut self,
+        session_id: &[u8; 32],
+        rng: &mut R,
+    ) -> Result<(), RunError> {
+        let mut stats = ProtocolStats::new(ProtocolId::ABT);
+        let start = Instant::now();
+        self.handle.start_abt_streamed(session_id)?;
+
+        let first = match self.handle.role() {
+            Role::CB => {
+                let mut stream = self.handle.abt_stream_cb(ABT_FRAME_SIZE, rng)?;
+                loop {
+                    if let Some(frame) = stream.next_frame().map_err(SessionError::from)? {
+                        send_counted(self.transport, &mut stats, &frame)?;
+                    }
+                    if stream.is_complete() {
+                        break;
+                    }
+                    let frame = recv_counted(self.transport, &mut stats, self.timeout)?;
+                    stream.push(&frame).map_err(SessionError::from)?;
+                }
+                let (state, shares, auth_triples, msg3) =
+                    stream.finish(rng).map_err(SessionError::from)?;
+                let frames = abt_msg3_frames(&msg3, ABT_FRAME_SIZE).map_err(SessionError::from)?;
+                self.handle
+                    .resume_abt_cb(state, shares, auth_triples, msg3)?;
+                for frame in &frames {
+                    send_counted(self.transport, &mut stats, frame)?;
+                }
+                None
+            }
+            Role::OB => {
+                let mut stream = self.handle.abt_stream_ob(ABT_FRAME_SIZE, rng)?;
+                while !stream.is_complete() {
+                    let frame = recv_counted(self.transport, &mut stats, self.timeout)?;
+                    if let Some(answer) = stream.push(&frame).map_err(SessionError::from)? {
+                        send_counted(self.transport, &mut stats, &answer)?;
+                    }
+                }
+                let (state, shares, auth_triples, frames) =
+                    stream.finish(rng).map_err(SessionError::from)?;
+                for frame in &frames {
+                    send_counted(self.transport, &mut stats, frame)?;
+                }
+                self.handle.resume_abt_ob(state, shares, auth_triples)?;
+
+                let mut consumer =
+                    ABTMsg3Consumer::new(session_id, ABT_SHARES, NUMBER_OF_AUTH_BEAVER_TRIPLES);
+                while !consumer.is_complete() {
+                    let frame = recv_counted(self.transport, &mut stats, self.timeout)?;
+                    consumer.push(&frame).map_err(SessionError::from)?;
+                }
+                let msg3 = consumer.finish().map_err(SessionError::from)?;
+                self.handle.step(Message::ABTMsg3(Box::new(msg3)), rng)?
+            }
+        };
+
+        self.exchange(&mut stats, ProtocolId::ABT, first, rng)?;
+        stats.duration = start.elapsed();
+        self.stats.push(stats);
+        Ok(())
+    }
+
+    /// Sends `first` and answers the messages of the other party until the
+    /// last round of `protocol`
+    fn exchange<R: CryptoRng + RngCore>(
+        &mut self,
+        stats: &mut ProtocolStats,
+        protocol: ProtocolId,
+        first: Option<Message>,
+        rng: &mut R,
+    ) -> Result<(), RunError> {
+        let mut outgoing = first;
+        loop {
+            if let Some(msg) = outgoing.take() {
+                let (_, round) = msg.tag();
+                let mut bytes = encode(&msg)?;
+                if let Some(identity) = self.identity {
+                    let signature = self.handle.sign_outbound(&identity.key, rng);
+                    bytes.extend_from_slice(&signature.to_bytes());
+                }
+                send_counted(self.transport, stats, &bytes)?;
+                if round == protocol.rounds() {
+                    break;
+                }
+            }
+
+            let bytes = recv_counted(self.transport, stats, self.timeout)?;
+            let (incoming, signature) = self.decode_signed(&bytes)?;
+            let (_, round) = incoming.tag();
+            outgoing = match (self.identity, signature) {
+                (Some(identity), Some(signature)) => self
+                    .handle
+                    .step_signed(incoming, &signature, identity, rng)?,
+                _ => self.handle.step(incoming, rng)?,
+            };
+            if outgoing.is_none() && round == protocol.rounds() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Decodes a message of the other party and, in an authenticated run,
+    /// splits off the signature that follows the envelope
+    fn decode_signed(&self, bytes: &[u8]) -> Result<(Message, Option<Signature>), RunError> {
+        if self.identity.is_none() {
+            return Ok((decode(bytes)?.message, None));
+        }
+        let split = bytes
+            .len()
+            .checked_sub(SIGNATURE_SIZE)
+            .ok_or(EnvelopeError::InvalidMessage)?;
+        let (envelope, signature) = bytes.split_at(split);
+        let signature = Signature::from_bytes(signature.try_into().unwrap());
+        Ok((decode(envelope)?.message, Some(signature)))
+    }
+
+    /// Runs the fair release of b with `arbiter` after a CFM session under
+    /// `OutputPolicy::Shared`, if the run is authenticated and has an arbiter
+    fn run_fair_release<R: CryptoRng + RngCore>(
+        &mut self,
+        output_policy: OutputPolicy,
+        arbiter: Option<&VerifyingKey>,
+        rng: &mut R,
+    ) -> Result<(), RunError> {
+        let (Some(identity), Some(arbiter)) = (self.identity, arbiter) else {
+            return Ok(());
+        };
+        if output_policy != OutputPolicy::Shared {
+            return Ok(());
+        }
+        let first = self.handle.start_fair_release(identity, arbiter, rng)?;
+        self.run_protocol(ProtocolId::FairRelease, first, rng)
+    }
+
+    fn report(self) -> Result<RunReport, RunError> {
+        let output = *self.handle.output().ok_or(RunError::Unfinished)?;
+        Ok(RunReport {
+            output,
+            // under `Shared` the handle only has b from the fair release
+            released: match output {
+                CFMOutput::Shared(_) => self.handle.result(),
+                _ => None,
+            },
+            stats: self.stats,
+        })
+    }
+}
+
+/// Runs CFM init, ABT and CFM as OB over prime `p`. `timeout` bounds the
+/// wait for each message or ABT frame of CB until it is read whole, ABT
+/// frames take a while to compute.
+pub fn run_ob<T: Transport, R: CryptoRng + RngCore>(
+    transport: &mut T,
+    p: U128,
+    inputs: &OBInputs,
+    timeout: Duration,
+    rng: &mut R,
+) -> Result<RunReport, RunError> {
+    let mut session_ids = [[0u8; 32]; 3];
+    for session_id in &mut session_ids {
+        rng.fill_bytes(session_id);
+    }
+    transport.send(&session_ids.concat())?;
+
+    run_ob_sessions(transport, None, p, inputs, session_ids, timeout, rng)
+}
+
+/// Runs the sub-protocols of OB under the init, ABT and CFM session ids,
+/// signing each message with `identity` if given
+fn run_ob_sessions<T: Transport, R: CryptoRng + RngCore>(
+    transport: &mut T,
+    identity: Option<&PartyIdentity>,
+    p: U128,
+    inputs: &OBInputs,
+    [init_session_id, abt_session_id, cfm_session_id]: [[u8; 32]; 3],
+    timeout: Duration,
+    rng: &mut R,
+) -> Result<RunReport, RunError> {
+    let mut driver = Driver {
+        transport,
+        timeout,
+        handle: SessionHandle::new(Role::OB, p),
+        stats: vec![],
+        identity,
+    };
+
+    let first = driver.handle.start_init(&init_session_id, rng)?;
+    driver.run_protocol(ProtocolId::CFMInit, first, rng)?;
+
+    driver.run_abt(&abt_session_id, rng)?;
+
+    let first = driver.handle.start_cfm_ob(
+        &cfm_session_id,
+        inputs.output_policy,
+        inputs.big_l,
+        inputs.big_x,
+        &inputs.y,
+        rng,
+    )?;
+    driver.run_protocol(ProtocolId::CFM, Some(first), rng)?;
+    driver.run_fair_release(inputs.output_policy, inputs.arbiter.as_ref(), rng)?;
+
+    driver.report()
+}
+
+/// Runs CFM init, ABT and CFM as CB over prime `p`. `timeout` bounds the
+/// wait for each message or ABT frame of OB until it is read whole, ABT
+/// frames take a while to compute.
+pub fn run_cb<T: Transport, R: CryptoRng + RngCore>(
+    transport: &mut T,
+    p: U128,
+    inputs: &CBInputs,
+    timeout: Duration,
+    rng: &mut R,
+) -> Result<RunReport, RunError> {
+    let bytes = transport.recv(timeout)?;
+    if bytes.len() != SESSION_IDS_SIZE {
+        return Err(RunError::InvalidSessionIDs);
+    }
+    let session_id = |i: usize| -> [u8; 32] { bytes[32 * i..32 * (i + 1)].try_into().unwrap() };
+    let session_ids = [session_id(0), session_id(1), session_id(2)];
+
+    run_cb_sessions(transport, None, p, inputs, session_ids, timeout, rng)
+}
+
+/// Runs the sub-protocols of CB under the init, ABT and CFM session ids,
+/// signing each message with `identity` if given
+fn run_cb_sessions<T: Transport, R: CryptoRng + RngCore>(
+    transport: &mut T,
+    identity: Option<&PartyIdentity>,
+    p: U128,
+    inputs: &CBInputs,
+    [init_session_id, abt_session_id, cfm_session_id]: [[u8; 32]; 3],
+    timeout: Duration,
+    rng: &mut R,
+) -> Result<RunReport, RunError> {
+    let mut driver = Driver {
+        transport,
+        timeout,
+        handle: SessionHandle::new(Role::CB, p),
+        stats: vec![],
+        identity,
+    };
+
+    let first = driver.handle.start_init(&init_session_id, rng)?;
+    driver.run_protocol(ProtocolId::CFMInit, first, rng)?;
+
+    driver.run_abt(&abt_session_id, rng)?;
+
+    driver.handle.start_cfm_cb(
+        &cfm_session_id,
+        inputs.output_policy,
+        inputs.big_l,
+        inputs.private_limit,
+        inputs.big_y.clone(),
+        inputs.big_z.clone(),
+        inputs.big_l_y.clone(),
+    )?;
+    driver.run_protocol(ProtocolId::CFM, None, rng)?;
+    driver.run_fair_release(inputs.output_policy, inputs.arbiter.as_ref(), rng)?;
+
+    driver.report()
+}
+
+/// Session ids of CFM init, ABT and CFM derived from the handshake
+fn bound_session_ids(binding: &ChannelBinding) -> [[u8; 32]; 3] {
+    [ProtocolId::CFMInit, ProtocolId::ABT, ProtocolId::CFM].map(|p| binding.session_id(p))
+}
+
+fn recv_handshake<T: Transport, M: serde::de::DeserializeOwned>(
+    transport: &mut T,
+    timeout: Duration,
+) -> Result<M, RunError> {
+    let bytes = transport.recv(timeout)?;
+    Ok(bincode::deserialize(&bytes).map_err(|_| IdentityError::InvalidMessage)?)
+}
+
+fn send_handshake<T: Transport, M: serde::Serialize>(
+    transport: &mut T,
+    msg: &M,
+) -> Result<(), RunError> {
+    let bytes = bincode::serialize(msg).map_err(|_| IdentityError::InvalidMessage)?;
+    Ok(transport.send(&bytes)?)
+}
+
+/// Runs the channel binding handshake with CB, then CFM init, ABT and CFM as
+/// OB over the authenticated channel
+pub fn run_ob_authenticated<T: Transport, R: CryptoRng + RngCore>(
+    transport: &mut T,
+    identity: &PartyIdentity,
+    p: U128,
+    inputs: &OBInputs,
+    timeout: Duration,
+    rng: &mut R,
+) -> Result<RunReport, RunError> {
+    let (state, msg1) = handshake_create_msg1(identity, rng);
+    send_handshake(transport, &msg1)?;
+    let msg2 = recv_handshake(transport, timeout)?;
+    let (binding, msg3) = handshake_process_msg2(state, &msg2, rng)?;
+    send_handshake(transport, &msg3)?;
+
+    let session_ids = bound_session_ids(&binding);
+    let mut transport = AuthenticatedTransport::new(transport, binding);
+    run_ob_sessions(
+        &mut transport,
+        Some(identity),
+        p,
+        inputs,
+        session_ids,
+        timeout,
+        rng,
+    )
+}
+
+/// Runs the channel binding handshake with OB, then CFM init, ABT and CFM as
+/// CB over the authenticated channel
+pub fn run_cb_authenticated<T: Transport, R: CryptoRng + RngCore>(
+    transport: &mut T,
+    identity: &PartyIdentity,
+    p: U128,
+    inputs: &CBInputs,
+    timeout: Duration,
+    rng: &mut R,
+) -> Result<RunReport, RunError> {
+    let msg1 = recv_handshake(transport, timeout)?;
+    let (state, msg2) = handshake_process_msg1(identity, &msg1, rng)?;
+    send_handshake(transport, &msg2)?;
+    let msg3 = recv_handshake(transport, timeout)?;
+    let binding = handshake_process_msg3(state, &msg3)?;
+
+    let session_ids = bound_session_ids(&binding);
+    let mut transport = AuthenticatedTransport::new(transport, binding);
+    run_cb_sessions(
+        &mut transport,
+        Some(identity),
+        p,
+        inputs,
+        session_ids,
+        timeout,
+        rng,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::TransportError;
+    use crate::identity::tests::identities;
+    use crate::schnorr::SigningKey;
+    use crate::transport::{ChannelTransport, MemoryTransport};
+    use crate::utils::Customer;
+    use crate::P;
+    use std::thread;
+
+    const TIMEOUT: Duration = Duration::from_secs(600);
+
+    fn inputs(big_x: u32) -> (OBInputs, CBInputs) {
+        let customer = |i: u32| {
+            Customer::new(&format!("Customer{}", i), &format!("P{}", i), "Main St").to_hash_bytes()
+        };
+        let big_l = U64::from_u32(104);
+        let ob = OBInputs {
+            output_policy: OutputPolicy::Both,
+            big_l: Some(big_l),
+            big_x: U64::from_u32(big_x),
+            y: customer(3),
+            arbiter: None,
+        };
+        let cb = CBInputs {
+            output_policy: OutputPolicy::Both,
+            big_l,
+            private_limit: false,
+            big_y: (1..=10).map(customer).collect(),
+            big_z: (1..=10).map(U64::from_u32).collect(),
+            big_l_y: None,
+            arbiter: None,
+        };
+        (ob, cb)
+    }
+
+    #[test]
+    fn test_run_over_transports() {
+        // both parties in their own thread over framed in-memory streams
+        let (mut ob_transport, mut cb_transport) = MemoryTransport::pair();
+        let (ob_inputs, cb_inputs) = inputs(100);
+        let cb = thread::spawn(move || {
+            run_cb(
+                &mut cb_transport,
+                P,
+                &cb_inputs,
+                TIMEOUT,
+                &mut rand::thread_rng(),
+            )
+            .unwrap()
+        });
+        let ob = run_ob(
+            &mut ob_transport,
+            P,
+            &ob_inputs,
+            TIMEOUT,
+            &mut rand::thread_rng(),
+        )
+        .unwrap();
+        let cb = cb.join().unwrap();
+
+        assert_eq!(ob.output.result(), Some(true));
+        assert_eq!(cb.output.result(), Some(true));
+        for (ob, cb) in ob.stats.iter().zip(&cb.stats) {
+            assert_eq!(ob.protocol, cb.protocol);
+            assert_eq!(ob.bytes_sent, cb.bytes_received);
+            assert_eq!(ob.bytes_received, cb.bytes_sent);
+            let messages = ob.messages_sent + ob.messages_received;
+            if ob.protocol == ProtocolId::ABT {
+                // ABTMsg1, ABTMsg2 and ABTMsg3 go as several frames
+                assert!(messages > ob.protocol.rounds() as usize);
+            } else {
+                assert_eq!(messages, ob.protocol.rounds() as usize);
+            }
+        }
+
+        // CB gives up on a silent OB
+        let (_ob_transport, mut cb_transport) = ChannelTransport::pair();
+        let (_, cb_inputs) = inputs(100);
+        let err = run_cb(
+            &mut cb_transport,
+            P,
+            &cb_inputs,
+            Duration::from_millis(50),
+            &mut rand::thread_rng(),
+        );
+        assert!(matches!(
+            err,
+            Err(RunError::Transport(TransportError::Timeout))
+        ));
+    }
+
+    #[test]
+    fn test_run_authenticated() {
+        let mut rng = rand::thread_rng();
+        let (ob_identity, cb_identity) = identities(&mut rng);
+
+        // b stays shared in CFM and comes out of the fair release
+        let (mut ob_transport, mut cb_transport) = ChannelTransport::pair();
+        let (mut ob_inputs, mut cb_inputs) = inputs(100);
+        let arbiter = SigningKey::generate(&mut rng).verifying_key();
+        ob_inputs.output_policy = OutputPolicy::Shared;
+        ob_inputs.arbiter = Some(arbiter);
+        cb_inputs.output_policy = OutputPolicy::Shared;
+        cb_inputs.arbiter = Some(arbiter);
+        let identity = cb_identity.clone();
+        let cb = thread::spawn(move || {
+            run_cb_authenticated(
+                &mut cb_transport,
+                &identity,
+                P,
+                &cb_inputs,
+                TIMEOUT,
+                &mut rand::thread_rng(),
+            )
+            .unwrap()
+        });
+        let ob = run_ob_authenticated(
+            &mut ob_transport,
+            &ob_identity,
+            P,
+            &ob_inputs,
+            TIMEOUT,
+            &mut rng,
+        )
+        .unwrap();
+        let cb = cb.join().unwrap();
+        assert!(matches!(ob.output, CFMOutput::Shared(_)));
+        assert_eq!((ob.released, cb.released), (Some(true), Some(true)));
+        let fair = ob.stats.last().unwrap();
+        assert_eq!(fair.protocol, ProtocolId::FairRelease);
+        assert_eq!(fair.messages_sent + fair.messages_received, 6);
+
+        // CB refuses an OB with another key
+        let mallory = PartyIdentity {
+            key: SigningKey::generate(&mut rng),
+            peer_key: cb_identity.key.verifying_key(),
+        };
+        let (mut ob_transport, mut cb_transport) = ChannelTransport::pair();
+        let (ob_inputs, cb_inputs) = inputs(100);
+        let ob = thread::spawn(move || {
+            run_ob_authenticated(
+                &mut ob_transport,
+                &mallory,
+                P,
+                &ob_inputs,
+                Duration::from_secs(5),
+                &mut rand::thread_rng(),
+            )
+        });
+        let err = run_cb_authenticated(
+            &mut cb_transport,
+            &cb_identity,
+            P,
+            &cb_inputs,
+            TIMEOUT,
+            &mut rng,
+        );
+        assert!(matches!(
+            err,
+            Err(RunError::Handshake(IdentityError::UnknownPeer))
+        ));
+        drop(cb_transport);
+        assert!(ob.join().unwrap().is_err());
+    }
+}
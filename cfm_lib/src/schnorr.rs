@@ -0,0 +1,124 @@
+//! Schnorr signatures over the Ristretto group.
+//!
+//! A signature on `message` under the key pair (x, X = x·G) is (R, s) with
+//! R = r·G for a random nonce r, s = r + c·x and the challenge
+//! c = H(X, R, message) drawn from a merlin transcript. It verifies if
+//! s·G = R + c·X.
+
+use crate::constants::SCHNORR_LABEL;
+use crate::proto::{
+    decode_point, decode_scalar, encode_point, encode_scalar, PointBytes, ScalarBytes,
+};
+use curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
+use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::scalar::Scalar;
+use merlin::Transcript;
+use rand_core::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Size of an encoded signature, R followed by s
+pub const SIGNATURE_SIZE: usize = 64;
+
+/// Secret signing key x
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+pub struct SigningKey(Scalar);
+
+/// Public verifying key X = x·G
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerifyingKey(pub PointBytes);
+
+/// Schnorr signature (R, s)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Signature {
+    /// nonce commitment R
+    pub big_r: PointBytes,
+
+    /// challenge response s
+    pub s: ScalarBytes,
+}
+
+impl Signature {
+    /// R followed by s
+    pub fn to_bytes(&self) -> [u8; SIGNATURE_SIZE] {
+        let mut bytes = [0u8; SIGNATURE_SIZE];
+        bytes[..32].copy_from_slice(&self.big_r);
+        bytes[32..].copy_from_slice(&self.s);
+        bytes
+    }
+
+    /// Signature from R followed by s, checked only by `verify`
+    pub fn from_bytes(bytes: &[u8; SIGNATURE_SIZE]) -> Self {
+        Signature {
+            big_r: bytes[..32].try_into().unwrap(),
+            s: bytes[32..].try_into().unwrap(),
+        }
+    }
+}
+
+impl SigningKey {
+    /// Random signing key
+    pub fn generate<R: CryptoRng + RngCore>(rng: &mut R) -> Self {
+        SigningKey(Scalar::random(rng))
+    }
+
+    /// Signing key from 32 bytes, reduced mod the group order
+    pub fn from_bytes(bytes: &[u8; 32]) -> Self {
+        SigningKey(Scalar::from_bytes_mod_order(*bytes))
+    }
+
+    /// Verifying key of the signing key
+    pub fn verifying_key(&self) -> VerifyingKey {
+        VerifyingKey(encode_point(&(RISTRETTO_BASEPOINT_POINT * self.0)))
+    }
+
+    /// x·P, the Diffie-Hellman share of `point` under this key
+    pub(crate) fn diffie_hellman(&self, point: &RistrettoPoint) -> RistrettoPoint {
+        point * self.0
+    }
+
+    /// Sign `message`
+    pub fn sign<R: CryptoRng + RngCore>(&self, message: &[u8], rng: &mut R) -> Signature {
+        let r = Scalar::random(rng);
+        let big_r = RISTRETTO_BASEPOINT_POINT * r;
+        let big_x = RISTRETTO_BASEPOINT_POINT * self.0;
+        let c = challenge(&big_x, &big_r, message);
+
+        Signature {
+            big_r: encode_point(&big_r),
+            s: encode_scalar(&(r + c * self.0)),
+        }
+    }
+}
+
+impl VerifyingKey {
+    /// True if `signature` is a signature on `message` under this key
+    pub fn verify(&self, message: &[u8], signature: &Signature) -> bool {
+        let (Some(big_x), Some(big_r), Some(s)) = (
+            decode_point(&self.0),
+            decode_point(&signature.big_r),
+            decode_scalar(&signature.s),
+        ) else {
+            return false;
+        };
+        let c = challenge(&big_x, &big_r, message);
+        let lhs = RISTRETTO_BASEPOINT_POINT * s;
+        let rhs = big_r + big_x * c;
+
+        lhs.ct_eq(&rhs).into()
+    }
+}
+
+/// Fiat-Shamir challenge c = H(X, R, message)
+fn challenge(big_x: &RistrettoPoint, big_r: &RistrettoPoint, message: &[u8]) -> Scalar {
+    let mut t = Transcript::new(SCHNORR_LABEL.as_ref());
+    t.append_message(b"verifying-key", big_x.compress().as_bytes());
+    t.append_message(b"big-r", big_r.compress().as_bytes());
+    t.append_message(b"message", message);
+
+    let mut bytes = [0u8; 64];
+    t.challenge_bytes(b"challenge-bytes", &mut bytes);
+
+    Scalar::from_bytes_mod_order_wide(&bytes)
+}
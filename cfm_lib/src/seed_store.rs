@@ -0,0 +1,246 @@
+//! Lifecycle of the base OT seeds of CFM init.
+//!
+//! CFM init runs EndemicOT and the PPRF setup once, the resulting OT seeds
+//! are extended by every ABT run that follows. A [`SeedStore`] keeps the seeds
+//! with their creation time and the number of ABT sessions run on them, and
+//! refuses new ABT sessions once the [`RotationPolicy`] is reached. CFM init
+//! then has to run again.
+//!
+//! ABT session `k` on the seeds of CFM init session `init_session_id` gets
+//! the session id derived from (init_session_id, k), see [`abt_session_id`].
+//! Both parties count the ABT sessions they start, so they derive the same
+//! ids as long as they start them in step. The store remembers the ids of
+//! all ABT sessions run on the seeds and refuses to run one of them again,
+//! which would repeat the OT extension of that session.
+//!
+//! The store lives in the `SessionHandle` and is kept across restarts by its
+//! snapshots, so two banks run CFM init once per rotation period.
+
+use crate::cfm_init_protocol::{CFMInitOTSeedsCB, CFMInitOTSeedsOB};
+use crate::constants::SEED_STORE_LABEL;
+use crate::errors::SeedStoreError;
+use merlin::Transcript;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Limits on the reuse of one set of base OT seeds
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RotationPolicy {
+    /// age after which the seeds are no longer used
+    pub max_age: Duration,
+
+    /// number of ABT sessions after which the seeds are no longer used
+    pub max_abt_sessions: u64,
+}
+
+impl Default for RotationPolicy {
+    /// 30 days or 1000 ABT sessions, whichever comes first
+    fn default() -> Self {
+        RotationPolicy {
+            max_age: Duration::from_secs(30 * 24 * 60 * 60),
+            max_abt_sessions: 1000,
+        }
+    }
+}
+
+/// OT seeds of one party
+#[derive(Serialize, Deserialize)]
+pub(crate) enum OTSeeds {
+    OB(CFMInitOTSeedsOB),
+    CB(CFMInitOTSeedsCB),
+}
+
+/// Session id of ABT session `index` on the seeds of CFM init session
+/// `init_session_id`
+pub fn abt_session_id(init_session_id: &[u8; 32], index: u64) -> [u8; 32] {
+    let mut t = Transcript::new(SEED_STORE_LABEL.as_ref());
+    t.append_message(b"init-session-id", init_session_id);
+    t.append_u64(b"abt-session-index", index);
+    let mut session_id = [0u8; 32];
+    t.challenge_bytes(b"abt-session-id", &mut session_id);
+    session_id
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+/// Base OT seeds of one CFM init run with their usage
+#[derive(Serialize, Deserialize)]
+pub struct SeedStore {
+    init_session_id: [u8; 32],
+    seeds: OTSeeds,
+    created_at: u64,
+    abt_sessions: u64,
+    used_session_ids: BTreeSet<[u8; 32]>,
+    policy: RotationPolicy,
+}
+
+impl SeedStore {
+    /// Store for the seeds of CFM init session `init_session_id`
+    pub(crate) fn new(
+        init_session_id: &[u8; 32],
+        seeds: OTSeeds,
+        policy: RotationPolicy,
+        now: SystemTime,
+    ) -> Self {
+        SeedStore {
+            init_session_id: *init_session_id,
+            seeds,
+            created_at: unix_secs(now),
+            abt_sessions: 0,
+            used_session_ids: BTreeSet::new(),
+            policy,
+        }
+    }
+
+    /// Session id of the CFM init run that produced the seeds
+    pub fn init_session_id(&self) -> &[u8; 32] {
+        &self.init_session_id
+    }
+
+    /// Time the seeds were created, in whole seconds
+    pub fn created_at(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.created_at)
+    }
+
+    /// Number of ABT sessions started on the seeds
+    pub fn abt_sessions(&self) -> u64 {
+        self.abt_sessions
+    }
+
+    /// Rotation policy of the seeds
+    pub fn policy(&self) -> RotationPolicy {
+        self.policy
+    }
+
+    pub(crate) fn set_policy(&mut self, policy: RotationPolicy) {
+        self.policy = policy;
+    }
+
+    pub(crate) fn seeds(&self) -> &OTSeeds {
+        &self.seeds
+    }
+
+    /// Session id of the next ABT session
+    pub fn next_abt_session_id(&self) -> [u8; 32] {
+        abt_session_id(&self.init_session_id, self.abt_sessions)
+    }
+
+    /// Fails if the policy allows no further ABT session at `now`
+    pub fn check(&self, now: SystemTime) -> Result<(), SeedStoreError> {
+        let age = unix_secs(now).saturating_sub(self.created_at);
+        if age >= self.policy.max_age.as_secs() {
+            return Err(SeedStoreError::Expired);
+        }
+        if self.abt_sessions >= self.policy.max_abt_sessions {
+            return Err(SeedStoreError::Exhausted);
+        }
+        Ok(())
+    }
+
+    /// Count one more ABT session, under `session_id`, at `now`
+    pub(crate) fn use_seeds(
+        &mut self,
+        session_id: &[u8; 32],
+        now: SystemTime,
+    ) -> Result<(), SeedStoreError> {
+        self.check(now)?;
+        if self.used_session_ids.contains(session_id) {
+            return Err(SeedStoreError::SessionIdReused);
+        }
+        self.used_session_ids.insert(*session_id);
+        self.abt_sessions += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfm_init_protocol::generate_cfm_ot_seeds_for_test;
+    use crate::envelope::Role;
+    use crate::errors::SessionError;
+    use crate::session_handle::SessionHandle;
+    use crate::P;
+    use rand::Rng;
+
+    #[test]
+    fn test_seed_store() {
+        let mut rng = rand::thread_rng();
+        let mut ob = SessionHandle::new(Role::OB, P);
+        let mut cb = SessionHandle::new(Role::CB, P);
+        let policy = RotationPolicy {
+            max_age: Duration::from_secs(60),
+            max_abt_sessions: 1,
+        };
+        cb.set_rotation_policy(policy);
+
+        let init_session_id: [u8; 32] = rng.gen();
+        cb.start_init(&init_session_id, &mut rng).unwrap();
+        let msg1 = ob.start_init(&init_session_id, &mut rng).unwrap().unwrap();
+        let msg2 = cb.step(msg1, &mut rng).unwrap().unwrap();
+        let msg3 = ob.step(msg2, &mut rng).unwrap().unwrap();
+        assert!(cb.step(msg3, &mut rng).unwrap().is_none());
+
+        let store = cb.seed_store().unwrap();
+        assert_eq!(store.init_session_id(), &init_session_id);
+        assert_eq!(store.policy(), policy);
+        assert_eq!(ob.seed_store().unwrap().policy(), RotationPolicy::default());
+
+        // both parties derive the same id for the next ABT session
+        let session_id = ob.seed_store().unwrap().next_abt_session_id();
+        assert_eq!(session_id, abt_session_id(&init_session_id, 0));
+        assert_ne!(session_id, abt_session_id(&init_session_id, 1));
+        let msg1 = cb.start_next_abt(&mut rng).unwrap().unwrap();
+        assert_eq!(msg1.session_id(), &session_id);
+
+        let store = cb.seed_store().unwrap();
+        assert_eq!(store.abt_sessions(), 1);
+        assert!(matches!(
+            store.check(SystemTime::now()),
+            Err(SeedStoreError::Exhausted)
+        ));
+        let store = ob.seed_store().unwrap();
+        assert!(store.check(SystemTime::now()).is_ok());
+        let later = store.created_at() + RotationPolicy::default().max_age;
+        assert!(matches!(store.check(later), Err(SeedStoreError::Expired)));
+
+        // a party past its policy has to run CFM init again
+        ob.set_rotation_policy(RotationPolicy {
+            max_abt_sessions: 0,
+            ..policy
+        });
+        assert!(matches!(
+            ob.start_next_abt(&mut rng),
+            Err(SessionError::SeedStore(SeedStoreError::Exhausted))
+        ));
+        assert_eq!(ob.seed_store().unwrap().abt_sessions(), 0);
+    }
+
+    #[test]
+    fn test_session_id_reuse() {
+        let mut rng = rand::thread_rng();
+        let init_session_id: [u8; 32] = rng.gen();
+        let (_, ot_seeds_ob) = generate_cfm_ot_seeds_for_test(&init_session_id, &mut rng);
+        let now = SystemTime::now();
+        let mut store = SeedStore::new(
+            &init_session_id,
+            OTSeeds::OB(ot_seeds_ob),
+            RotationPolicy::default(),
+            now,
+        );
+
+        // an ABT session id runs at most once on the seeds
+        let session_id: [u8; 32] = rng.gen();
+        store.use_seeds(&session_id, now).unwrap();
+        assert!(matches!(
+            store.use_seeds(&session_id, now),
+            Err(SeedStoreError::SessionIdReused)
+        ));
+        store.use_seeds(&rng.gen(), now).unwrap();
+        assert_eq!(store.abt_sessions(), 2);
+    }
+}
@@ -0,0 +1,1569 @@
+//! Owning session handle behind the FFI session functions.
+//!
+//! A `SessionHandle` runs CFM init, ABT and CFM for one party. OT seeds,
+//! shares, triples and round states stay inside the handle between rounds,
+//! only envelope encoded wire messages go in and out.
+//!
+//! Each sub-protocol is started with a `start_*` call, which returns the first
+//! message if this party sends it, and is driven by `step` with the messages
+//! of the other party. CFM consumes the shares and triples of the last ABT run,
+//! or those of a [`PoolSession`] loaded with
+//! [`SessionHandle::load_pool_session_ob`] or
+//! [`SessionHandle::load_pool_session_cb`], which runs on the OT seeds of the
+//! pool.
+//!
+//! The handle remembers the last message it sent and the round of the last
+//! message it accepted. A message of an already accepted round is rejected
+//! with `DuplicateMessage`, the caller resends [`SessionHandle::last_outbound`]
+//! instead. Together with the snapshots of `session_snapshot` this lets a party
+//! resume a sub-protocol after a restart.
+//!
+//! Every message sent or accepted goes into the running [`TranscriptHash`] of
+//! the sub-protocol. Each party signs the hash after each message it sends,
+//! [`SessionHandle::sign_outbound`], and [`SessionHandle::step_signed`] checks
+//! that signature on the messages of the other party. When a CFM message of
+//! the other party fails a check, the handle keeps an [`AbortReport`] blaming
+//! it with the message and its signature, for the caller to sign. When a
+//! CFM session finishes, [`SessionHandle::receipt`] is the [`Receipt`] of its
+//! outcome, which both parties sign and exchange. Under an output policy other
+//! than `Both` the receipt records the policy but not the result bit.
+//!
+//! ABTMsg1 and ABTMsg2 can also be streamed as the frames of `abt_stream`
+//! instead, after [`SessionHandle::start_abt_streamed`]. The handle then
+//! takes ABTMsg3 and the later ABT messages through `step` as usual.
+//!
+//! OB keeps its share of the running flow totals in a [`Ledger`], with tags
+//! under the [`LedgerKeys`] of CB, and inputs X + Z_y^OB to CFM. A finished
+//! CFM session leaves the ledger updates of both parties pending. The handle
+//! settles them once its party learns b, otherwise
+//! [`SessionHandle::settle_transfer`] does.
+//!
+//! Each ABT run also produces the masks of a fair release. After a CFM session
+//! under `OutputPolicy::Shared`, [`SessionHandle::start_fair_release`] runs the
+//! protocol of `fair_release` on the shares of b, its messages go through
+//! `step_signed`. If the other party stops after both escrows are accepted,
+//! [`SessionHandle::fair_evidence`] goes to the arbiter. The arbiter delivers
+//! its resolution to both parties, each passes it to
+//! [`SessionHandle::resolve_fair_release`], also CB when OB stopped before
+//! CB held the evidence.
+
+use crate::abort_report::AbortReport;
+use crate::abt_stream::{ABTStreamCB, ABTStreamOB};
+use crate::auth_beaver_triples::{
+    abt_create_msg1, abt_process_msg1, abt_process_msg2, abt_process_msg3, abt_process_msg4,
+    abt_process_msg5, ABTMsg3, ABTStateCBR1, ABTStateCBR2, ABTStateOBR1, ABTStateOBR2, Share,
+    TripleShare,
+};
+use crate::cfm_init_protocol::{
+    cfm_init_create_msg1, cfm_init_process_msg1, cfm_init_process_msg2, cfm_init_process_msg3,
+    CFMInitMsg1, CFMInitMsg2, CFMInitMsg3, CFMInitOTSeedsCB, CFMInitOTSeedsOB, CFMInitStateCB,
+    CFMInitStateOB,
+};
+use crate::cfm_protocol::{
+    valid_limits, CBSession, CFMOutput, CFMStep, OBSession, OutputPolicy, ABT_SHARES,
+    NUMBER_OF_AUTH_BEAVER_TRIPLES, NUMBER_OF_SHARES,
+};
+use crate::constants::{B_PARAMETER, FAIR_RELEASE_PAIRS};
+use crate::envelope::{encode, Message, ProtocolId, Role};
+use crate::errors::{CFMError, LedgerError, SessionError};
+use crate::fair_release::{
+    fair_create_msg1, fair_process_msg1, fair_process_msg2, fair_process_msg3, fair_process_msg4,
+    fair_process_msg5, fair_process_msg6, FairEvidence, FairStateCBR1, FairStateCBR2,
+    FairStateOBR1, FairStateOBR2, FairStateOBR3, Resolution,
+};
+use crate::identity::PartyIdentity;
+use crate::ledger::{Ledger, LedgerKeys};
+use crate::receipt::{Receipt, SignedReceipt};
+use crate::schnorr::{Signature, SigningKey, VerifyingKey};
+use crate::seed_store::{OTSeeds, RotationPolicy, SeedStore};
+use crate::transcript::TranscriptHash;
+use crate::triple_pool::PoolSession;
+use crypto_bigint::{U128, U64};
+use rand_core::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
+
+#[derive(Serialize, Deserialize)]
+enum Stage {
+    Idle,
+    InitCBWait,
+    InitOB(Box<CFMInitStateOB>),
+    InitCB(Box<CFMInitStateCB>),
+    AbtOBWait,
+    AbtStream,
+    AbtCBR1(Box<ABTStateCBR1>),
+    AbtOBR1(Box<ABTStateOBR1>, Vec<Share>, Vec<TripleShare>),
+    AbtCBR2(Box<ABTStateCBR2>, Vec<Share>, Vec<TripleShare>),
+    AbtOBR2(Box<ABTStateOBR2>, Vec<Share>, Vec<TripleShare>),
+    CfmOB(Box<OBSession>),
+    CfmCB(Box<CBSession>),
+    Aborted,
+}
+
+/// Fair release of the last CFM session, next to its finished stage
+#[derive(Serialize, Deserialize)]
+enum FairStage {
+    Idle,
+    CBWait(VerifyingKey),
+    OBR1(Box<FairStateOBR1>),
+    OBR2(Box<FairStateOBR2>),
+    OBR3(Box<FairStateOBR3>),
+    CBR1(Box<FairStateCBR1>),
+    CBR2(Box<FairStateCBR2>, Option<Box<FairEvidence>>),
+    Released(bool),
+    Aborted,
+}
+
+/// CFM party state kept across FFI calls
+#[derive(Serialize, Deserialize)]
+pub struct SessionHandle {
+    role: Role,
+    p: U128,
+    session_id: [u8; 32],
+    stage: Stage,
+    seed_store: Option<SeedStore>,
+    rotation_policy: RotationPolicy,
+    shares: Vec<Share>,
+    auth_triples: Vec<TripleShare>,
+    pool_seeds: Option<OTSeeds>,
+    masks: Vec<Share>,
+    fair: FairStage,
+    last_inbound: Option<(ProtocolId, u8)>,
+    last_outbound: Option<Message>,
+    transcript: TranscriptHash,
+    abort_report: Option<AbortReport>,
+    big_l: Option<U64>,
+    output_policy: OutputPolicy,
+    ledger: Ledger,
+    ledger_keys: Option<LedgerKeys>,
+    customer: [u8; 32],
+}
+
+impl SessionHandle {
+    /// Create an idle handle for `role`, working over prime p
+    pub fn new(role: Role, p: U128) -> Self {
+        SessionHandle {
+            role,
+            p,
+            session_id: [0u8; 32],
+            stage: Stage::Idle,
+            seed_store: None,
+            rotation_policy: RotationPolicy::default(),
+            shares: vec![],
+            auth_triples: vec![],
+            pool_seeds: None,
+            masks: vec![],
+            fair: FairStage::Idle,
+            last_inbound: None,
+            last_outbound: None,
+            transcript: TranscriptHash::new(&[0u8; 32]),
+            abort_report: None,
+            big_l: None,
+            output_policy: OutputPolicy::Both,
+            ledger: Ledger::new(),
+            ledger_keys: None,
+            customer: [0u8; 32],
+        }
+    }
+
+    /// Role of the party
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    /// True once CFM init has produced the OT seeds
+    pub fn has_ot_seeds(&self) -> bool {
+        self.seed_store.is_some()
+    }
+
+    /// OT seeds of the last CFM init with their usage
+    pub fn seed_store(&self) -> Option<&SeedStore> {
+        self.seed_store.as_ref()
+    }
+
+    /// Rotation policy of the current OT seeds and of those of later CFM
+    /// init runs
+    pub fn set_rotation_policy(&mut self, policy: RotationPolicy) {
+        self.rotation_policy = policy;
+        if let Some(seed_store) = &mut self.seed_store {
+            seed_store.set_policy(policy);
+        }
+    }
+
+    /// True if an ABT run has produced the shares and triples for a CFM session
+    pub fn has_triples(&self) -> bool {
+        self.shares.len() == ABT_SHARES && self.auth_triples.len() == NUMBER_OF_AUTH_BEAVER_TRIPLES
+    }
+
+    /// Last message this party has sent in the running sub-protocol, the
+    /// answer to resend when the other party retransmits its message
+    pub fn last_outbound(&self) -> Option<&Message> {
+        self.last_outbound.as_ref()
+    }
+
+    /// Hash of the messages of the running sub-protocol so far
+    pub fn transcript_hash(&self) -> &TranscriptHash {
+        &self.transcript
+    }
+
+    /// Blame record of the last CFM session, if a message of the other party
+    /// failed a check
+    pub fn abort_report(&self) -> Option<&AbortReport> {
+        self.abort_report.as_ref()
+    }
+
+    /// Result bit of the last CFM session, if it has finished and this party
+    /// learns it, from CFM or from the fair release
+    pub fn result(&self) -> Option<bool> {
+        match self.fair {
+            FairStage::Released(b) => Some(b),
+            _ => self.output().and_then(CFMOutput::result),
+        }
+    }
+
+    /// Ledger of OB, always empty for CB
+    pub fn ledger(&self) -> &Ledger {
+        &self.ledger
+    }
+
+    /// Ledger keys of CB, created by its first CFM init. None for OB.
+    pub fn ledger_keys(&self) -> Option<&LedgerKeys> {
+        self.ledger_keys.as_ref()
+    }
+
+    /// Output of the last CFM session, if it has finished
+    pub fn output(&self) -> Option<&CFMOutput> {
+        match &self.stage {
+            Stage::CfmOB(session) => session.output(),
+            Stage::CfmCB(session) => session.output(),
+            _ => None,
+        }
+    }
+
+    /// Receipt of the last CFM session, if it has finished
+    pub fn receipt(&self) -> Option<Receipt> {
+        self.output().map(|output| Receipt {
+            session_id: self.session_id,
+            big_l: self.big_l,
+            transcript_hash: *self.transcript.as_bytes(),
+            output_policy: self.output_policy,
+            // both parties sign the same receipt, it holds b only if both learn it
+            outcome: match self.output_policy {
+                OutputPolicy::Both => output.result(),
+                _ => None,
+            },
+        })
+    }
+
+    /// Receipt of the last CFM session with the signature `own` of this party
+    /// and `other` of the other party, None if the session has not finished
+    pub fn signed_receipt(&self, own: Signature, other: Signature) -> Option<SignedReceipt> {
+        let (ob_signature, cb_signature) = match self.role {
+            Role::OB => (own, other),
+            Role::CB => (other, own),
+        };
+        self.receipt().map(|receipt| SignedReceipt {
+            receipt,
+            ob_signature,
+            cb_signature,
+        })
+    }
+
+    fn check_can_start(&self) -> Result<(), SessionError> {
+        // accepted escrows wait for the last message or the arbiter
+        if matches!(self.fair, FairStage::OBR3(_) | FairStage::CBR2(..)) {
+            return Err(SessionError::InvalidState);
+        }
+        match &self.stage {
+            Stage::Idle | Stage::Aborted => Ok(()),
+            Stage::CfmOB(session) if session.expected_round().is_none() => Ok(()),
+            Stage::CfmCB(session) if session.expected_round().is_none() => Ok(()),
+            _ => Err(SessionError::InvalidState),
+        }
+    }
+
+    /// Enter the first stage of a sub-protocol, `first` is the message this
+    /// party starts it with
+    fn begin(
+        &mut self,
+        session_id: &[u8; 32],
+        stage: Stage,
+        first: Option<Message>,
+    ) -> Result<Option<Message>, SessionError> {
+        let mut transcript = TranscriptHash::new(session_id);
+        if let Some(msg) = &first {
+            transcript = transcript.append(msg)?;
+        }
+        self.session_id = *session_id;
+        self.stage = stage;
+        self.fair = FairStage::Idle;
+        self.last_inbound = None;
+        self.last_outbound = first.clone();
+        self.transcript = transcript;
+        self.abort_report = None;
+        Ok(first)
+    }
+
+    /// Start CFM init. OB gets CFMInitMsg1, CB waits for it.
+    pub fn start_init<R: CryptoRng + RngCore>(
+        &mut self,
+        session_id: &[u8; 32],
+        rng: &mut R,
+    ) -> Result<Option<Message>, SessionError> {
+        self.check_can_start()?;
+
+        match self.role {
+            Role::OB => {
+                let mut msg1 = CFMInitMsg1::default();
+                let state = cfm_init_create_msg1(session_id, &mut msg1, rng);
+                self.begin(
+                    session_id,
+                    Stage::InitOB(Box::new(state)),
+                    Some(Message::CFMInitMsg1(Box::new(msg1))),
+                )
+            }
+            Role::CB => {
+                if self.ledger_keys.is_none() {
+                    self.ledger_keys = Some(LedgerKeys::new(&self.p, rng));
+                }
+                self.begin(session_id, Stage::InitCBWait, None)
+            }
+        }
+    }
+
+    /// Start ABT for one CFM session. CB gets ABTMsg1, OB waits for it.
+    /// Counts as one use of the OT seeds, fails once their rotation policy
+    /// is reached or if an ABT session already ran under `session_id`.
+    pub fn start_abt<R: CryptoRng + RngCore>(
+        &mut self,
+        session_id: &[u8; 32],
+        rng: &mut R,
+    ) -> Result<Option<Message>, SessionError> {
+        self.check_can_start()?;
+        let seed_store = self.seed_store.as_mut().ok_or(SessionError::InvalidState)?;
+        seed_store.use_seeds(session_id, SystemTime::now())?;
+
+        match seed_store.seeds() {
+            OTSeeds::OB(_) => self.begin(session_id, Stage::AbtOBWait, None),
+            OTSeeds::CB(ot_seeds_cb) => {
+                let (state, msg1) = abt_create_msg1(
+                    session_id,
+                    ot_seeds_cb,
+                    self.p,
+                    ABT_SHARES,
+                    NUMBER_OF_AUTH_BEAVER_TRIPLES,
+                    rng,
+                )?;
+                self.begin(
+                    session_id,
+                    Stage::AbtCBR1(Box::new(state)),
+                    Some(Message::ABTMsg1(Box::new(msg1))),
+                )
+            }
+        }
+    }
+
+    /// Start ABT with ABTMsg1 and ABTMsg2 streamed as frames. Counts as one
+    /// use of the OT seeds like `start_abt`. The caller runs the frames
+    /// through `abt_stream_cb` or `abt_stream_ob` and hands the result back.
+    pub fn start_abt_streamed(&mut self, session_id: &[u8; 32]) -> Result<(), SessionError> {
+        self.check_can_start()?;
+        let seed_store = self.seed_store.as_mut().ok_or(SessionError::InvalidState)?;
+        seed_store.use_seeds(session_id, SystemTime::now())?;
+        self.begin(session_id, Stage::AbtStream, None)?;
+        Ok(())
+    }
+
+    /// CB side of the streamed ABTMsg1 and ABTMsg2, frames of at most
+    /// `frame_size` bytes
+    pub(crate) fn abt_stream_cb<R: CryptoRng + RngCore>(
+        &self,
+        frame_size: usize,
+        rng: &mut R,
+    ) -> Result<ABTStreamCB<'_>, SessionError> {
+        match (&self.stage, self.seed_store.as_ref().map(SeedStore::seeds)) {
+            (Stage::AbtStream, Some(OTSeeds::CB(ot_seeds_cb))) => Ok(ABTStreamCB::new(
+                &self.session_id,
+                ot_seeds_cb,
+                self.p,
+                ABT_SHARES,
+                NUMBER_OF_AUTH_BEAVER_TRIPLES,
+                frame_size,
+                rng,
+            )?),
+            _ => Err(SessionError::InvalidState),
+        }
+    }
+
+    /// OB side of the streamed ABTMsg1 and ABTMsg2, frames of at most
+    /// `frame_size` bytes
+    pub(crate) fn abt_stream_ob<R: CryptoRng + RngCore>(
+        &self,
+        frame_size: usize,
+        rng: &mut R,
+    ) -> Result<ABTStreamOB<'_>, SessionError> {
+        match (&self.stage, self.seed_store.as_ref().map(SeedStore::seeds)) {
+            (Stage::AbtStream, Some(OTSeeds::OB(ot_seeds_ob))) => Ok(ABTStreamOB::new(
+                &self.session_id,
+                ot_seeds_ob,
+                self.p,
+                ABT_SHARES,
+                NUMBER_OF_AUTH_BEAVER_TRIPLES,
+                frame_size,
+                rng,
+            )?),
+            _ => Err(SessionError::InvalidState),
+        }
+    }
+
+    /// Continue ABT as CB with the output of the finished stream, returns
+    /// ABTMsg3
+    pub(crate) fn resume_abt_cb(
+        &mut self,
+        state: ABTStateCBR2,
+        shares: Vec<Share>,
+        auth_triples: Vec<TripleShare>,
+        msg3: ABTMsg3,
+    ) -> Result<Message, SessionError> {
+        if self.role != Role::CB || !matches!(self.stage, Stage::AbtStream) {
+            return Err(SessionError::InvalidState);
+        }
+        let msg3 = Message::ABTMsg3(Box::new(msg3));
+        self.transcript = self.transcript.append(&msg3)?;
+        self.stage = Stage::AbtCBR2(Box::new(state), shares, auth_triples);
+        self.last_outbound = Some(msg3.clone());
+        Ok(msg3)
+    }
+
+    /// Continue ABT as OB with the output of the finished stream, OB waits
+    /// for ABTMsg3
+    pub(crate) fn resume_abt_ob(
+        &mut self,
+        state: ABTStateOBR1,
+        shares: Vec<Share>,
+        auth_triples: Vec<TripleShare>,
+    ) -> Result<(), SessionError> {
+        if self.role != Role::OB || !matches!(self.stage, Stage::AbtStream) {
+            return Err(SessionError::InvalidState);
+        }
+        self.stage = Stage::AbtOBR1(Box::new(state), shares, auth_triples);
+        Ok(())
+    }
+
+    /// Start ABT under the next session id derived from the OT seeds, see
+    /// [`SeedStore::next_abt_session_id`]
+    pub fn start_next_abt<R: CryptoRng + RngCore>(
+        &mut self,
+        rng: &mut R,
+    ) -> Result<Option<Message>, SessionError> {
+        let seed_store = self.seed_store.as_ref().ok_or(SessionError::InvalidState)?;
+        let session_id = seed_store.next_abt_session_id();
+        self.start_abt(&session_id, rng)
+    }
+
+    /// Load pooled session `session` of OB, the next CFM session consumes its
+    /// shares and triples and runs on its OT seeds. Returns the CFM session id
+    /// of `session`.
+    pub fn load_pool_session_ob(
+        &mut self,
+        session: PoolSession<CFMInitOTSeedsOB>,
+    ) -> Result<[u8; 32], SessionError> {
+        if self.role != Role::OB {
+            return Err(SessionError::InvalidState);
+        }
+        self.load_pool_session(session, OTSeeds::OB)
+    }
+
+    /// Load pooled session `session` of CB, see
+    /// [`SessionHandle::load_pool_session_ob`]. CB still needs the ledger keys
+    /// of its first CFM init.
+    pub fn load_pool_session_cb(
+        &mut self,
+        session: PoolSession<CFMInitOTSeedsCB>,
+    ) -> Result<[u8; 32], SessionError> {
+        if self.role != Role::CB {
+            return Err(SessionError::InvalidState);
+        }
+        self.load_pool_session(session, OTSeeds::CB)
+    }
+
+    fn load_pool_session<S>(
+        &mut self,
+        session: PoolSession<S>,
+        ot_seeds: fn(S) -> OTSeeds,
+    ) -> Result<[u8; 32], SessionError> {
+        self.check_can_start()?;
+        if session.p != self.p
+            || session.shares.len() != ABT_SHARES
+            || session.auth_triples.len() != NUMBER_OF_AUTH_BEAVER_TRIPLES
+        {
+            return Err(SessionError::InvalidInput);
+        }
+        self.shares = session.shares;
+        self.auth_triples = session.auth_triples;
+        self.pool_seeds = Some(ot_seeds(session.ot_seeds));
+        Ok(session.session_id)
+    }
+
+    /// Start CFM as OB, consuming the shares and triples of the last ABT run.
+    /// `big_l` is None if L is a private input of CB. CFM checks `big_x` plus
+    /// the ledger share of `y`, once it finishes the transfer stays pending
+    /// until settled. `big_l` and `big_x` must be below 2^B. Returns CFMMsg1.
+    pub fn start_cfm_ob<R: CryptoRng + RngCore>(
+        &mut self,
+        session_id: &[u8; 32],
+        output_policy: OutputPolicy,
+        big_l: Option<U64>,
+        big_x: U64,
+        y: &[u8; 32],
+        rng: &mut R,
+    ) -> Result<Message, SessionError> {
+        self.check_can_start()?;
+        if self.role != Role::OB || !self.has_triples() {
+            return Err(SessionError::InvalidState);
+        }
+        if big_l.is_some_and(|big_l| !below_bound(big_l)) || !below_bound(big_x) {
+            return Err(SessionError::InvalidInput);
+        }
+        if self.ledger.pending().is_some() {
+            return Err(LedgerError::PendingTransfer.into());
+        }
+        self.ledger.amount(y, big_x)?;
+        let pool_seeds = self.pool_seeds.take();
+        let seeds = pool_seeds
+            .as_ref()
+            .or(self.seed_store.as_ref().map(SeedStore::seeds));
+        let ot_seeds = match seeds {
+            Some(OTSeeds::OB(ot_seeds)) => ot_seeds,
+            _ => return Err(SessionError::InvalidState),
+        };
+
+        let mut shares = std::mem::take(&mut self.shares);
+        self.masks = shares.split_off(NUMBER_OF_SHARES);
+        let (session, msg1) = OBSession::new(
+            session_id,
+            self.p,
+            output_policy,
+            big_l,
+            big_x,
+            y,
+            &self.ledger,
+            ot_seeds,
+            shares,
+            std::mem::take(&mut self.auth_triples),
+            rng,
+        )?;
+        let msg1 = Message::from(msg1);
+        self.begin(
+            session_id,
+            Stage::CfmOB(Box::new(session)),
+            Some(msg1.clone()),
+        )?;
+        self.big_l = big_l;
+        self.output_policy = output_policy;
+        self.customer = *y;
+        Ok(msg1)
+    }
+
+    /// Start CFM as CB, consuming the shares and triples of the last ABT run.
+    /// With `private_limit` L is not disclosed to OB. `big_l_y` holds the
+    /// limit of each customer, None if all have limit L, and needs a private
+    /// limit unless every entry is L. Limits must be below 2^B. Customers of
+    /// `big_y` without a ledger slot get one. CB waits for CFMMsg1.
+    #[allow(clippy::too_many_arguments)]
+    pub fn start_cfm_cb(
+        &mut self,
+        session_id: &[u8; 32],
+        output_policy: OutputPolicy,
+        big_l: U64,
+        private_limit: bool,
+        big_y: Vec<[u8; 32]>,
+        big_z: Vec<U64>,
+        big_l_y: Option<Vec<U64>>,
+    ) -> Result<(), SessionError> {
+        self.check_can_start()?;
+        if self.role != Role::CB || !self.has_triples() {
+            return Err(SessionError::InvalidState);
+        }
+        if big_y.is_empty()
+            || big_y.len() != big_z.len()
+            || !valid_limits(big_l, private_limit, &big_y, big_l_y.as_deref())
+        {
+            return Err(SessionError::InvalidInput);
+        }
+        let ledger_keys = self
+            .ledger_keys
+            .as_mut()
+            .ok_or(SessionError::InvalidState)?;
+        if ledger_keys.pending() {
+            return Err(LedgerError::PendingTransfer.into());
+        }
+        let pool_seeds = self.pool_seeds.take();
+        let seeds = pool_seeds
+            .as_ref()
+            .or(self.seed_store.as_ref().map(SeedStore::seeds));
+        let ot_seeds = match seeds {
+            Some(OTSeeds::CB(ot_seeds)) => ot_seeds,
+            _ => return Err(SessionError::InvalidState),
+        };
+        ledger_keys.assign_slots(&big_y);
+
+        let mut shares = std::mem::take(&mut self.shares);
+        self.masks = shares.split_off(NUMBER_OF_SHARES);
+        let session = CBSession::new(
+            session_id,
+            self.p,
+            output_policy,
+            big_l,
+            private_limit,
+            big_y,
+            big_z,
+            big_l_y,
+            ledger_keys,
+            ot_seeds,
+            shares,
+            std::mem::take(&mut self.auth_triples),
+        )?;
+        self.begin(session_id, Stage::CfmCB(Box::new(session)), None)?;
+        // both parties put the same limit into the receipt
+        self.big_l = (!private_limit).then_some(big_l);
+        self.output_policy = output_policy;
+        Ok(())
+    }
+
+    /// Start the fair release of b after a CFM session under
+    /// `OutputPolicy::Shared`, with the masks of its ABT run and the escrows
+    /// encrypted to `arbiter`. OB gets FairMsg1, CB waits for it. The messages
+    /// of the other party go through `step_signed` with the same `identity`.
+    pub fn start_fair_release<R: CryptoRng + RngCore>(
+        &mut self,
+        identity: &PartyIdentity,
+        arbiter: &VerifyingKey,
+        rng: &mut R,
+    ) -> Result<Option<Message>, SessionError> {
+        let b_share = self.shared_b()?;
+        if !matches!(self.fair, FairStage::Idle) || self.masks.len() != FAIR_RELEASE_PAIRS {
+            return Err(SessionError::InvalidState);
+        }
+
+        match self.role {
+            Role::OB => {
+                let (state, msg1) = fair_create_msg1(
+                    &self.session_id,
+                    self.transcript.as_bytes(),
+                    self.p,
+                    &b_share,
+                    std::mem::take(&mut self.masks),
+                    identity,
+                    arbiter,
+                    rng,
+                )?;
+                let msg1 = Message::FairMsg1(Box::new(msg1));
+                self.transcript = self.transcript.append(&msg1)?;
+                self.fair = FairStage::OBR1(Box::new(state));
+                self.last_outbound = Some(msg1.clone());
+                Ok(Some(msg1))
+            }
+            Role::CB => {
+                self.fair = FairStage::CBWait(*arbiter);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Evidence for the arbiter, see [`crate::fair_release::arbiter_resolve`],
+    /// once both escrows are accepted and the other party has not released
+    /// its share of b
+    pub fn fair_evidence(&self) -> Option<&FairEvidence> {
+        match &self.fair {
+            FairStage::OBR3(state) => Some(state.evidence()),
+            FairStage::CBR2(_, evidence) => evidence.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Finish a fair release with the resolution of the arbiter, returns b.
+    /// Also works for CB without evidence, when OB went to the arbiter.
+    pub fn resolve_fair_release(&mut self, resolution: &Resolution) -> Result<bool, SessionError> {
+        let b = match &self.fair {
+            FairStage::OBR3(state) => state.resolve(resolution)?,
+            FairStage::CBR2(state, _) => state.resolve(resolution)?,
+            _ => return Err(SessionError::InvalidState),
+        };
+        self.fair = FairStage::Released(b);
+        self.update_ledger();
+        Ok(b)
+    }
+
+    /// Share of b of a CFM session finished under `OutputPolicy::Shared`
+    fn shared_b(&self) -> Result<Share, SessionError> {
+        match self.output() {
+            Some(CFMOutput::Shared(b_share)) => Ok(*b_share),
+            _ => Err(SessionError::InvalidState),
+        }
+    }
+
+    /// Signature of this party over the transcript hash after the message it
+    /// has just sent, to send along with it
+    pub fn sign_outbound<R: CryptoRng + RngCore>(
+        &self,
+        key: &SigningKey,
+        rng: &mut R,
+    ) -> Signature {
+        self.transcript.sign(key, rng)
+    }
+
+    /// Process a message of the other party, returns the answer if there is one.
+    /// A message that does not fit the current stage or repeats an accepted
+    /// round is rejected and leaves the handle unchanged, a protocol error
+    /// aborts the running sub-protocol.
+    pub fn step<R: CryptoRng + RngCore>(
+        &mut self,
+        incoming: Message,
+        rng: &mut R,
+    ) -> Result<Option<Message>, SessionError> {
+        self.step_inner(incoming, None, rng)
+    }
+
+    /// Like [`SessionHandle::step`] for a message sent with its `signature`
+    /// of `sign_outbound`. A message whose signature does not verify under
+    /// the peer key of `identity` is rejected and leaves the handle unchanged.
+    /// The fair release signs its escrows and acceptances with the key of
+    /// `identity`, its messages only go through here.
+    pub fn step_signed<R: CryptoRng + RngCore>(
+        &mut self,
+        incoming: Message,
+        signature: &Signature,
+        identity: &PartyIdentity,
+        rng: &mut R,
+    ) -> Result<Option<Message>, SessionError> {
+        self.step_inner(incoming, Some((signature, identity)), rng)
+    }
+
+    fn step_inner<R: CryptoRng + RngCore>(
+        &mut self,
+        incoming: Message,
+        signed: Option<(&Signature, &PartyIdentity)>,
+        rng: &mut R,
+    ) -> Result<Option<Message>, SessionError> {
+        if let Stage::Aborted = self.stage {
+            return Err(SessionError::Aborted);
+        }
+        if *incoming.session_id() != self.session_id {
+            return Err(SessionError::InvalidSessionID);
+        }
+
+        let (protocol, round) = incoming.tag();
+        if let Some((last_protocol, last_round)) = self.last_inbound {
+            if protocol == last_protocol && round <= last_round {
+                return Err(SessionError::DuplicateMessage { protocol, round });
+            }
+        }
+
+        let bytes = encode(&incoming)?;
+        let mut transcript = self.transcript.append_bytes(&bytes);
+        if let Some((signature, identity)) = signed {
+            if !transcript.verify(&identity.peer_key, signature) {
+                return Err(SessionError::InvalidSignature);
+            }
+        }
+        let identity = signed.map(|(_, identity)| identity);
+        let outgoing = match self.process(incoming, identity, rng) {
+            Ok(outgoing) => outgoing,
+            Err(SessionError::CFM(e)) => {
+                let signature = signed.map(|(signature, _)| *signature);
+                self.record_abort(round, &e, bytes, &transcript, signature);
+                self.update_ledger();
+                return Err(SessionError::CFM(e));
+            }
+            Err(e) => return Err(e),
+        };
+        self.update_ledger();
+        if let Some(msg) = &outgoing {
+            transcript = transcript.append(msg)?;
+        }
+        self.last_inbound = Some((protocol, round));
+        self.last_outbound = outgoing.clone();
+        self.transcript = transcript;
+        Ok(outgoing)
+    }
+
+    /// Settle the pending ledger update of a finished CFM session by b, if
+    /// this party learned it from CFM or the fair release
+    fn update_ledger(&mut self) {
+        if let Some(approved) = self.result() {
+            // fails only if nothing is pending, e.g. already settled
+            let _ = self.settle_ledger(approved);
+        }
+    }
+
+    /// Settle the pending ledger update of this party with the result bit b,
+    /// when it did not learn b from CFM or a fair release of this handle.
+    /// Both parties must settle a CFM session the same way, otherwise the
+    /// next one fails the tag check of the ledger.
+    pub fn settle_transfer(&mut self, approved: bool) -> Result<(), SessionError> {
+        Ok(self.settle_ledger(approved)?)
+    }
+
+    fn settle_ledger(&mut self, approved: bool) -> Result<(), LedgerError> {
+        match (self.role, &mut self.ledger_keys) {
+            (Role::OB, _) => self.ledger.settle(approved),
+            (Role::CB, Some(ledger_keys)) => ledger_keys.settle(approved),
+            (Role::CB, None) => Err(LedgerError::NoPendingTransfer),
+        }
+    }
+
+    /// Keep the report blaming the sender of CFM message `round`, if `error`
+    /// is a failed check of that message. `message` is its envelope encoding,
+    /// `transcript` the hash after it and `signature` that of its sender.
+    fn record_abort(
+        &mut self,
+        round: u8,
+        error: &CFMError,
+        message: Vec<u8>,
+        transcript: &TranscriptHash,
+        signature: Option<Signature>,
+    ) {
+        if let Some(check) = error.failed_check() {
+            self.abort_report = Some(AbortReport {
+                session_id: self.session_id,
+                round,
+                sub_protocol: check.sub_protocol,
+                field: check.field.to_string(),
+                index: check.index as u64,
+                accused: ProtocolId::CFM.sender(round),
+                reason: error.to_string(),
+                transcript_hash: *transcript.as_bytes(),
+                previous_hash: *self.transcript.as_bytes(),
+                message,
+                message_signature: signature,
+                opening: error.opening(),
+            });
+        }
+    }
+
+    fn process<R: CryptoRng + RngCore>(
+        &mut self,
+        incoming: Message,
+        identity: Option<&PartyIdentity>,
+        rng: &mut R,
+    ) -> Result<Option<Message>, SessionError> {
+        if incoming.tag().0 == ProtocolId::FairRelease {
+            return self.process_fair(incoming, identity, rng);
+        }
+        // CFM sessions check the round themselves and stay in place
+        // and hold back their ledger update once they finish
+        match &mut self.stage {
+            Stage::CfmOB(session) => {
+                let step = session.handle(&cfm(incoming)?, rng);
+                if let (Ok(CFMStep::Finished { .. }), Some(update)) =
+                    (&step, session.ledger_update())
+                {
+                    self.ledger.hold(&self.customer, update.clone());
+                }
+                return Self::step_cfm(step);
+            }
+            Stage::CfmCB(session) => {
+                let step = session.handle(&cfm(incoming)?, rng);
+                if let (Ok(CFMStep::Finished { .. }), Some(update), Some(ledger_keys)) =
+                    (&step, session.ledger_update(), &mut self.ledger_keys)
+                {
+                    ledger_keys.hold(update.clone());
+                }
+                return Self::step_cfm(step);
+            }
+            _ => {}
+        }
+
+        let stage = std::mem::replace(&mut self.stage, Stage::Aborted);
+        let (stage, outgoing) = match (stage, incoming) {
+            (Stage::InitCBWait, Message::CFMInitMsg1(msg1)) => {
+                let mut msg2 = CFMInitMsg2::default();
+                let state = cfm_init_process_msg1(&self.session_id, &msg1, &mut msg2, rng)?;
+                (
+                    Stage::InitCB(Box::new(state)),
+                    Some(Message::CFMInitMsg2(Box::new(msg2))),
+                )
+            }
+            (Stage::InitOB(state), Message::CFMInitMsg2(msg2)) => {
+                let mut msg3 = CFMInitMsg3::default();
+                let ot_seeds = cfm_init_process_msg2(*state, &msg2, &mut msg3, rng)?;
+                self.seed_store = Some(self.new_seed_store(OTSeeds::OB(ot_seeds)));
+                (Stage::Idle, Some(Message::CFMInitMsg3(Box::new(msg3))))
+            }
+            (Stage::InitCB(state), Message::CFMInitMsg3(msg3)) => {
+                let ot_seeds = cfm_init_process_msg3(*state, &msg3)?;
+                self.seed_store = Some(self.new_seed_store(OTSeeds::CB(ot_seeds)));
+                (Stage::Idle, None)
+            }
+            (Stage::AbtOBWait, Message::ABTMsg1(msg1)) => {
+                let ot_seeds_ob = match self.seed_store.as_ref().map(SeedStore::seeds) {
+                    Some(OTSeeds::OB(ot_seeds_ob)) => ot_seeds_ob,
+                    _ => {
+                        self.stage = Stage::AbtOBWait;
+                        return Err(SessionError::InvalidState);
+                    }
+                };
+                let (state, shares, auth_triples, msg2) = abt_process_msg1(
+                    &self.session_id,
+                    ot_seeds_ob,
+                    self.p,
+                    ABT_SHARES,
+                    NUMBER_OF_AUTH_BEAVER_TRIPLES,
+                    &msg1,
+                    rng,
+                )?;
+                (
+                    Stage::AbtOBR1(Box::new(state), shares, auth_triples),
+                    Some(Message::ABTMsg2(Box::new(msg2))),
+                )
+            }
+            (Stage::AbtCBR1(state), Message::ABTMsg2(msg2)) => {
+                let ot_seeds_cb = match self.seed_store.as_ref().map(SeedStore::seeds) {
+                    Some(OTSeeds::CB(ot_seeds_cb)) => ot_seeds_cb,
+                    _ => {
+                        self.stage = Stage::AbtCBR1(state);
+                        return Err(SessionError::InvalidState);
+                    }
+                };
+                let (state, shares, auth_triples, msg3) =
+                    abt_process_msg2(&state, ot_seeds_cb, &msg2, rng)?;
+                (
+                    Stage::AbtCBR2(Box::new(state), shares, auth_triples),
+                    Some(Message::ABTMsg3(Box::new(msg3))),
+                )
+            }
+            (Stage::AbtOBR1(state, mut shares, mut auth_triples), Message::ABTMsg3(msg3)) => {
+                let (state, msg4) =
+                    abt_process_msg3(&state, &mut shares, &mut auth_triples, &msg3)?;
+                (
+                    Stage::AbtOBR2(Box::new(state), shares, auth_triples),
+                    Some(Message::ABTMsg4(Box::new(msg4))),
+                )
+            }
+            (Stage::AbtCBR2(state, shares, auth_triples), Message::ABTMsg4(msg4)) => {
+                let msg5 = abt_process_msg4(&state, &auth_triples, &msg4)?;
+                self.shares = shares;
+                self.auth_triples = auth_triples;
+                self.pool_seeds = None;
+                (Stage::Idle, Some(Message::ABTMsg5(Box::new(msg5))))
+            }
+            (Stage::AbtOBR2(state, shares, auth_triples), Message::ABTMsg5(msg5)) => {
+                abt_process_msg5(&state, &msg5)?;
+                self.shares = shares;
+                self.auth_triples = auth_triples;
+                self.pool_seeds = None;
+                (Stage::Idle, None)
+            }
+            (stage, incoming) => {
+                self.stage = stage;
+                let (protocol, round) = incoming.tag();
+                return Err(SessionError::UnexpectedMessage { protocol, round });
+            }
+        };
+
+        self.stage = stage;
+        Ok(outgoing)
+    }
+
+    /// Fair release rounds after a CFM session under `OutputPolicy::Shared`,
+    /// `identity` signs the escrows and acceptances. A failed check aborts the
+    /// release, unless both escrows are already accepted.
+    fn process_fair<R: CryptoRng + RngCore>(
+        &mut self,
+        incoming: Message,
+        identity: Option<&PartyIdentity>,
+        rng: &mut R,
+    ) -> Result<Option<Message>, SessionError> {
+        let identity = identity.ok_or(SessionError::InvalidState)?;
+        let b_share = self.shared_b()?;
+
+        let fair = std::mem::replace(&mut self.fair, FairStage::Aborted);
+        let (fair, outgoing) = match (fair, incoming) {
+            (FairStage::CBWait(arbiter), Message::FairMsg1(msg1)) => {
+                let (state, msg2) = fair_process_msg1(
+                    &self.session_id,
+                    self.transcript.as_bytes(),
+                    self.p,
+                    &b_share,
+                    std::mem::take(&mut self.masks),
+                    identity,
+                    &arbiter,
+                    &msg1,
+                    rng,
+                )?;
+                (
+                    FairStage::CBR1(Box::new(state)),
+                    Some(Message::FairMsg2(Box::new(msg2))),
+                )
+            }
+            (FairStage::OBR1(state), Message::FairMsg2(msg2)) => {
+                let (state, msg3) = fair_process_msg2(*state, identity, &msg2, rng)?;
+                (
+                    FairStage::OBR2(Box::new(state)),
+                    Some(Message::FairMsg3(Box::new(msg3))),
+                )
+            }
+            (FairStage::CBR1(state), Message::FairMsg3(msg3)) => {
+                let (state, msg4) = fair_process_msg3(*state, identity, &msg3, rng)?;
+                (
+                    FairStage::CBR2(Box::new(state), None),
+                    Some(Message::FairMsg4(Box::new(msg4))),
+                )
+            }
+            (FairStage::OBR2(state), Message::FairMsg4(msg4)) => {
+                let (state, msg5) = fair_process_msg4(*state, identity, &msg4, rng)?;
+                (
+                    FairStage::OBR3(Box::new(state)),
+                    Some(Message::FairMsg5(Box::new(msg5))),
+                )
+            }
+            (FairStage::CBR2(state, evidence), Message::FairMsg5(msg5)) => {
+                match fair_process_msg5(&state, identity, &msg5) {
+                    Ok((b, msg6)) => (
+                        FairStage::Released(b),
+                        Some(Message::FairMsg6(Box::new(msg6))),
+                    ),
+                    Err(e) => {
+                        // a valid acceptance of OB is the evidence for the arbiter
+                        let evidence = state
+                            .evidence(&msg5, &identity.peer_key)
+                            .ok()
+                            .map(Box::new)
+                            .or(evidence);
+                        self.fair = FairStage::CBR2(state, evidence);
+                        return Err(e.into());
+                    }
+                }
+            }
+            (FairStage::OBR3(state), Message::FairMsg6(msg6)) => {
+                match fair_process_msg6(&state, &msg6) {
+                    Ok(b) => (FairStage::Released(b), None),
+                    Err(e) => {
+                        self.fair = FairStage::OBR3(state);
+                        return Err(e.into());
+                    }
+                }
+            }
+            (fair, incoming) => {
+                self.fair = fair;
+                let (protocol, round) = incoming.tag();
+                return Err(SessionError::UnexpectedMessage { protocol, round });
+            }
+        };
+
+        self.fair = fair;
+        Ok(outgoing)
+    }
+
+    /// Store for the OT seeds of the CFM init run that just finished
+    fn new_seed_store(&self, seeds: OTSeeds) -> SeedStore {
+        SeedStore::new(
+            &self.session_id,
+            seeds,
+            self.rotation_policy,
+            SystemTime::now(),
+        )
+    }
+
+    fn step_cfm(step: Result<CFMStep, CFMError>) -> Result<Option<Message>, SessionError> {
+        match step? {
+            CFMStep::Outgoing(msg) => Ok(Some(msg.into())),
+            CFMStep::Finished { outgoing, .. } => Ok(outgoing.map(Message::from)),
+        }
+    }
+}
+
+/// True if an amount or limit fits in the B bits CFM compares
+fn below_bound(value: U64) -> bool {
+    value < U64::ONE.shl(B_PARAMETER)
+}
+
+fn cfm(incoming: Message) -> Result<crate::cfm_protocol::CFMMessage, SessionError> {
+    let (protocol, round) = incoming.tag();
+    incoming
+        .into_cfm_message()
+        .ok_or(SessionError::UnexpectedMessage { protocol, round })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::abort_report::{FailedCheck, OpeningFailure, SubProtocol};
+    use crate::envelope::{decode, HEADER_SIZE};
+    use crate::fair_release::{arbiter_resolve, Arbiter};
+    use crate::identity::tests::identities;
+    use crate::utils::Customer;
+    use crate::P;
+    use rand::Rng;
+
+    /// Deliver `msg` over the wire format and let `to` answer it
+    fn relay(
+        to: &mut SessionHandle,
+        msg: Message,
+        rng: &mut rand::rngs::ThreadRng,
+    ) -> Option<Message> {
+        let bytes = encode(&msg).unwrap();
+        to.step(decode(&bytes).unwrap().message, rng).unwrap()
+    }
+
+    /// Exchange messages until neither party has anything to send
+    fn run(
+        ob: &mut SessionHandle,
+        cb: &mut SessionHandle,
+        first: Option<Message>,
+        first_from_ob: bool,
+        rng: &mut rand::rngs::ThreadRng,
+    ) {
+        let mut next = first;
+        let mut to_cb = first_from_ob;
+        while let Some(msg) = next {
+            next = if to_cb {
+                relay(cb, msg, rng)
+            } else {
+                relay(ob, msg, rng)
+            };
+            to_cb = !to_cb;
+        }
+    }
+
+    #[test]
+    fn test_session_handle() {
+        let mut rng = rand::thread_rng();
+        let mut ob = SessionHandle::new(Role::OB, P);
+        let mut cb = SessionHandle::new(Role::CB, P);
+
+        // CFM init
+        let init_session_id: [u8; 32] = rng.gen();
+        assert!(cb.start_init(&init_session_id, &mut rng).unwrap().is_none());
+        let msg1 = ob.start_init(&init_session_id, &mut rng).unwrap();
+        run(&mut ob, &mut cb, msg1, true, &mut rng);
+        assert!(ob.has_ot_seeds() && cb.has_ot_seeds());
+
+        // ABT
+        let session_id: [u8; 32] = rng.gen();
+        assert!(ob.start_abt(&session_id, &mut rng).unwrap().is_none());
+        let msg1 = cb.start_abt(&session_id, &mut rng).unwrap();
+
+        // no other sub-protocol can start while ABT runs
+        let wrong = ob.start_init(&session_id, &mut rng);
+        assert!(matches!(wrong, Err(SessionError::InvalidState)));
+
+        // a message for another stage is rejected and leaves the handle unchanged
+        let wrong = cb.step(msg1.clone().unwrap(), &mut rng);
+        assert!(matches!(
+            wrong,
+            Err(SessionError::UnexpectedMessage { round: 1, .. })
+        ));
+
+        run(&mut ob, &mut cb, msg1, false, &mut rng);
+        assert!(ob.has_triples() && cb.has_triples());
+
+        // CFM, Z_y + X <= L
+        let big_l = U64::from_u32(104);
+        let big_y: Vec<[u8; 32]> = (1..=10)
+            .map(|i| {
+                Customer::new(&format!("Customer{}", i), &format!("P{}", i), "Main St")
+                    .to_hash_bytes()
+            })
+            .collect();
+        let big_z: Vec<U64> = (1..=10).map(U64::from_u8).collect();
+        let y = Customer::new("Customer3", "P3", "Main St").to_hash_bytes();
+        let big_x = U64::from_u32(100);
+
+        let session_id: [u8; 32] = rng.gen();
+        // a customer limit above L is rejected
+        let wrong = cb.start_cfm_cb(
+            &session_id,
+            OutputPolicy::Both,
+            big_l,
+            false,
+            big_y.clone(),
+            big_z.clone(),
+            Some(vec![big_l.wrapping_add(&U64::ONE); 10]),
+        );
+        assert!(matches!(wrong, Err(SessionError::InvalidInput)));
+        // so is a limit or amount of B bits or more, before the triples are used
+        let too_large = U64::ONE.shl(B_PARAMETER);
+        let wrong = cb.start_cfm_cb(
+            &session_id,
+            OutputPolicy::Both,
+            too_large,
+            false,
+            big_y.clone(),
+            big_z.clone(),
+            None,
+        );
+        assert!(matches!(wrong, Err(SessionError::InvalidInput)));
+        let wrong = ob.start_cfm_ob(
+            &session_id,
+            OutputPolicy::Both,
+            Some(big_l),
+            too_large,
+            &y,
+            &mut rng,
+        );
+        assert!(matches!(wrong, Err(SessionError::InvalidInput)));
+        assert!(ob.has_triples() && cb.has_triples());
+        cb.start_cfm_cb(
+            &session_id,
+            OutputPolicy::Both,
+            big_l,
+            false,
+            big_y,
+            big_z,
+            None,
+        )
+        .unwrap();
+        let msg1 = ob
+            .start_cfm_ob(
+                &session_id,
+                OutputPolicy::Both,
+                Some(big_l),
+                big_x,
+                &y,
+                &mut rng,
+            )
+            .unwrap();
+        run(&mut ob, &mut cb, Some(msg1), true, &mut rng);
+
+        assert_eq!(ob.result(), Some(true));
+        assert_eq!(cb.result(), Some(true));
+
+        // OB learned b and added the approved transfer to its ledger share
+        assert!(ob.ledger().pending().is_none());
+        assert_eq!(ob.ledger().share(&y), big_x);
+        assert_eq!(ob.ledger().amount(&y, big_x).unwrap(), U64::from_u32(200));
+        assert_eq!(cb.ledger(), &Ledger::new());
+        assert!(!cb.ledger_keys().unwrap().pending());
+        assert!(matches!(
+            ob.settle_transfer(true),
+            Err(SessionError::Ledger(LedgerError::NoPendingTransfer))
+        ));
+
+        // both parties sign the same receipt
+        let receipt = ob.receipt().unwrap();
+        assert_eq!(cb.receipt().as_ref(), Some(&receipt));
+        assert_eq!((receipt.big_l, receipt.outcome), (Some(big_l), Some(true)));
+        let ob_key = SigningKey::generate(&mut rng);
+        let cb_key = SigningKey::generate(&mut rng);
+        let ob_signature = receipt.sign(&ob_key, &mut rng);
+        let cb_signature = receipt.sign(&cb_key, &mut rng);
+        let signed = ob.signed_receipt(ob_signature, cb_signature).unwrap();
+        assert_eq!(
+            cb.signed_receipt(cb_signature, ob_signature),
+            Some(signed.clone())
+        );
+        assert!(signed.verify(&ob_key.verifying_key(), &cb_key.verifying_key()));
+        assert!(!signed.verify(&cb_key.verifying_key(), &ob_key.verifying_key()));
+
+        let mut forged = signed.clone();
+        forged.receipt.outcome = Some(false);
+        assert!(!forged.verify(&ob_key.verifying_key(), &cb_key.verifying_key()));
+
+        // shares and triples are used up
+        assert!(!ob.has_triples());
+        let again = ob.start_cfm_ob(
+            &session_id,
+            OutputPolicy::Both,
+            Some(big_l),
+            big_x,
+            &y,
+            &mut rng,
+        );
+        assert!(matches!(again, Err(SessionError::InvalidState)));
+    }
+
+    /// Deliver `msg` of the party of `from` signed over its transcript, and
+    /// let `to` answer it
+    fn relay_signed(
+        from: (&SessionHandle, &PartyIdentity),
+        to: (&mut SessionHandle, &PartyIdentity),
+        msg: Message,
+        rng: &mut rand::rngs::ThreadRng,
+    ) -> Result<Option<Message>, SessionError> {
+        let signature = from.0.sign_outbound(&from.1.key, rng);
+        let bytes = encode(&msg).unwrap();
+        to.0.step_signed(decode(&bytes).unwrap().message, &signature, to.1, rng)
+    }
+
+    /// OB and CB after a CFM session with b kept in shares, Z_y + X = 103 <= L,
+    /// with its session id
+    fn shared_session(
+        big_x: U64,
+        y: &[u8; 32],
+        rng: &mut rand::rngs::ThreadRng,
+    ) -> (SessionHandle, SessionHandle, [u8; 32]) {
+        let mut ob = SessionHandle::new(Role::OB, P);
+        let mut cb = SessionHandle::new(Role::CB, P);
+
+        let init_session_id: [u8; 32] = rng.gen();
+        cb.start_init(&init_session_id, rng).unwrap();
+        let msg1 = ob.start_init(&init_session_id, rng).unwrap();
+        run(&mut ob, &mut cb, msg1, true, rng);
+        let abt_session_id: [u8; 32] = rng.gen();
+        ob.start_abt(&abt_session_id, rng).unwrap();
+        let msg1 = cb.start_abt(&abt_session_id, rng).unwrap();
+        run(&mut ob, &mut cb, msg1, false, rng);
+
+        let big_l = U64::from_u32(104);
+        let session_id: [u8; 32] = rng.gen();
+        cb.start_cfm_cb(
+            &session_id,
+            OutputPolicy::Shared,
+            big_l,
+            false,
+            vec![*y],
+            vec![U64::from_u8(3)],
+            None,
+        )
+        .unwrap();
+        let msg1 = ob
+            .start_cfm_ob(
+                &session_id,
+                OutputPolicy::Shared,
+                Some(big_l),
+                big_x,
+                y,
+                rng,
+            )
+            .unwrap();
+        run(&mut ob, &mut cb, Some(msg1), true, rng);
+        assert_eq!((ob.result(), cb.result()), (None, None));
+        assert!(ob.ledger().pending().is_some());
+        (ob, cb, session_id)
+    }
+
+    #[test]
+    fn test_fair_release() {
+        let mut rng = rand::thread_rng();
+        let (ob_identity, cb_identity) = identities(&mut rng);
+        let arbiter = SigningKey::generate(&mut rng);
+        let y = Customer::new("Customer1", "P1", "Main St").to_hash_bytes();
+        let big_x = U64::from_u32(100);
+        let (mut ob, mut cb, session_id) = shared_session(big_x, &y, &mut rng);
+
+        let arbiter_key = arbiter.verifying_key();
+        assert!(cb
+            .start_fair_release(&cb_identity, &arbiter_key, &mut rng)
+            .unwrap()
+            .is_none());
+        let msg1 = ob
+            .start_fair_release(&ob_identity, &arbiter_key, &mut rng)
+            .unwrap()
+            .unwrap();
+        // the release needs the signing key of step_signed
+        let wrong = cb.step(msg1.clone(), &mut rng);
+        assert!(matches!(wrong, Err(SessionError::InvalidState)));
+
+        let mut next = Some(msg1);
+        let mut to_cb = true;
+        let mut msg6 = None;
+        while let Some(msg) = next.take() {
+            if msg.tag() == (ProtocolId::FairRelease, 6) {
+                // CB withholds FairMsg6
+                msg6 = Some(msg);
+                break;
+            }
+            next = if to_cb {
+                relay_signed((&ob, &ob_identity), (&mut cb, &cb_identity), msg, &mut rng)
+            } else {
+                relay_signed((&cb, &cb_identity), (&mut ob, &ob_identity), msg, &mut rng)
+            }
+            .unwrap();
+            to_cb = !to_cb;
+        }
+        assert!(msg6.is_some());
+        assert_eq!((ob.result(), cb.result()), (None, Some(true)));
+
+        // OB goes to the arbiter with the evidence, and waits for it
+        let evidence = ob.fair_evidence().unwrap().clone();
+        assert!(cb.fair_evidence().is_none());
+        let wrong = ob.start_next_abt(&mut rng);
+        assert!(matches!(wrong, Err(SessionError::InvalidState)));
+        let resolution = arbiter_resolve(
+            &arbiter,
+            &session_id,
+            &ob_identity.key.verifying_key(),
+            &cb_identity.key.verifying_key(),
+            &evidence,
+        )
+        .unwrap();
+        assert!(ob.resolve_fair_release(&resolution).unwrap());
+        assert_eq!(ob.result(), Some(true));
+
+        // the released b settles the pending transfer of OB and the key
+        // updates of CB
+        assert!(ob.ledger().pending().is_none());
+        assert_eq!(ob.ledger().share(&y), big_x);
+        assert!(!cb.ledger_keys().unwrap().pending());
+        assert!(matches!(
+            ob.resolve_fair_release(&resolution),
+            Err(SessionError::InvalidState)
+        ));
+    }
+
+    #[test]
+    fn test_fair_release_ob_stops() {
+        let mut rng = rand::thread_rng();
+        let (ob_identity, cb_identity) = identities(&mut rng);
+        let mut arbiter = Arbiter::new(SigningKey::generate(&mut rng));
+        let y = Customer::new("Customer1", "P1", "Main St").to_hash_bytes();
+        let big_x = U64::from_u32(100);
+        let (mut ob, mut cb, session_id) = shared_session(big_x, &y, &mut rng);
+
+        let arbiter_key = arbiter.verifying_key();
+        cb.start_fair_release(&cb_identity, &arbiter_key, &mut rng)
+            .unwrap();
+        let mut next = ob
+            .start_fair_release(&ob_identity, &arbiter_key, &mut rng)
+            .unwrap();
+        let mut to_cb = true;
+        while let Some(msg) = next.take() {
+            if msg.tag() == (ProtocolId::FairRelease, 5) {
+                // OB holds both acceptances and withholds FairMsg5
+                break;
+            }
+            next = if to_cb {
+                relay_signed((&ob, &ob_identity), (&mut cb, &cb_identity), msg, &mut rng)
+            } else {
+                relay_signed((&cb, &cb_identity), (&mut ob, &ob_identity), msg, &mut rng)
+            }
+            .unwrap();
+            to_cb = !to_cb;
+        }
+        assert!(cb.fair_evidence().is_none());
+        assert_eq!((ob.result(), cb.result()), (None, None));
+
+        // OB goes to the arbiter alone
+        let evidence = ob.fair_evidence().unwrap().clone();
+        let resolution = arbiter
+            .resolve(
+                &session_id,
+                &ob_identity.key.verifying_key(),
+                &cb_identity.key.verifying_key(),
+                &evidence,
+            )
+            .unwrap();
+        assert!(ob.resolve_fair_release(&resolution).unwrap());
+
+        // CB gets the resolution the arbiter kept for it and learns b too
+        let delivered = arbiter.resolution(&session_id).unwrap();
+        assert!(cb.resolve_fair_release(delivered).unwrap());
+        assert_eq!((ob.result(), cb.result()), (Some(true), Some(true)));
+        assert_eq!(ob.ledger().share(&y), big_x);
+        assert!(!cb.ledger_keys().unwrap().pending());
+    }
+
+    #[test]
+    fn test_abort_report() {
+        let mut rng = rand::thread_rng();
+        let mut ob = SessionHandle::new(Role::OB, P);
+        let mut cb = SessionHandle::new(Role::CB, P);
+
+        let init_session_id: [u8; 32] = rng.gen();
+        cb.start_init(&init_session_id, &mut rng).unwrap();
+        let msg1 = ob.start_init(&init_session_id, &mut rng).unwrap();
+        run(&mut ob, &mut cb, msg1, true, &mut rng);
+
+        let abt_session_id: [u8; 32] = rng.gen();
+        ob.start_abt(&abt_session_id, &mut rng).unwrap();
+        let msg1 = cb.start_abt(&abt_session_id, &mut rng).unwrap();
+        run(&mut ob, &mut cb, msg1, false, &mut rng);
+
+        // OB opens a wrong value of its input x
+        let big_l = U64::from_u32(104);
+        let y = Customer::new("Customer1", "P1", "Main St").to_hash_bytes();
+        let session_id: [u8; 32] = rng.gen();
+        cb.start_cfm_cb(
+            &session_id,
+            OutputPolicy::Both,
+            big_l,
+            false,
+            vec![y],
+            vec![U64::ONE],
+            None,
+        )
+        .unwrap();
+        let msg1 = ob
+            .start_cfm_ob(
+                &session_id,
+                OutputPolicy::Both,
+                Some(big_l),
+                U64::from_u8(3),
+                &y,
+                &mut rng,
+            )
+            .unwrap();
+        let Message::CFMMsg1(mut msg1) = msg1 else {
+            panic!("expected CFMMsg1");
+        };
+        msg1.open_0[2].0 = msg1.open_0[2].0.wrapping_add(&U128::ONE);
+        let msg1 = Message::CFMMsg1(msg1);
+        let transcript = cb.transcript_hash().append(&msg1).unwrap();
+
+        // OB signs the message it sends, CB rejects it under another key
+        let (ob_identity, cb_identity) = identities(&mut rng);
+        let ob_key = ob_identity.key;
+        let signature = transcript.sign(&ob_key, &mut rng);
+        let other = SigningKey::generate(&mut rng);
+        let wrong_identity = PartyIdentity {
+            key: cb_identity.key.clone(),
+            peer_key: other.verifying_key(),
+        };
+        assert!(matches!(
+            cb.step_signed(msg1.clone(), &signature, &wrong_identity, &mut rng),
+            Err(SessionError::InvalidSignature)
+        ));
+        assert!(cb.abort_report().is_none());
+
+        let err = cb
+            .step_signed(msg1.clone(), &signature, &cb_identity, &mut rng)
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err,
+            SessionError::CFM(CFMError::InvalidOpen(check, _)) if check == FailedCheck::cfm("open_0", 2)
+        ));
+        assert!(ob.abort_report().is_none());
+
+        // OB never hears back, the transfer was never held back
+        assert!(ob.ledger().pending().is_none());
+        assert!(matches!(
+            ob.settle_transfer(false),
+            Err(SessionError::Ledger(LedgerError::NoPendingTransfer))
+        ));
+        assert_eq!(ob.ledger(), &Ledger::new());
+
+        let report = cb.abort_report().unwrap().clone();
+        assert_eq!(report.session_id, session_id);
+        assert_eq!(report.round, 1);
+        assert_eq!(report.sub_protocol, SubProtocol::CFM);
+        assert_eq!((report.field.as_str(), report.index), ("open_0", 2));
+        assert_eq!(report.accused, Role::OB);
+        assert_eq!(&report.transcript_hash, transcript.as_bytes());
+        assert_eq!(report.message, encode(&msg1).unwrap());
+        assert_eq!(report.message_signature, Some(signature));
+
+        // the signed message binds OB, the opening shows the failed MAC
+        assert!(report.verify_message(&ob_key.verifying_key()));
+        assert!(!report.verify_message(&other.verifying_key()));
+        let opening = report.opening.unwrap();
+        assert!(matches!(opening.failure, OpeningFailure::Mac { .. }));
+        assert!(opening.fails());
+        let mut tampered = report.clone();
+        tampered.message[HEADER_SIZE] ^= 1;
+        assert!(!tampered.verify_message(&ob_key.verifying_key()));
+
+        // an arbitrator checks the report against the key of CB
+        let key = SigningKey::generate(&mut rng);
+        let signed = report.sign(&key, &mut rng);
+        assert!(signed.verify(&key.verifying_key()));
+        let other = SigningKey::generate(&mut rng);
+        assert!(!signed.verify(&other.verifying_key()));
+
+        let mut forged = signed.clone();
+        forged.report.accused = Role::CB;
+        assert!(!forged.verify(&key.verifying_key()));
+    }
+}
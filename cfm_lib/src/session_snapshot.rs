@@ -0,0 +1,210 @@
+//! Encrypted snapshots of a session handle.
+//!
+//! A snapshot holds the whole [`SessionHandle`]: the stage with its round
+//! state (`CFMInitStateOB`, `ABTStateOBR1`, `CBSession`, ...), the OT seeds,
+//! the shares and triples, and the last message sent and accepted. A party
+//! that takes a snapshot after each `start_*` and `step` call can restore it
+//! after a crash, resend [`SessionHandle::last_outbound`] and continue with
+//! the next message of the other party.
+//!
+//! The snapshot is `magic || version || nonce || ciphertext`, the plaintext is
+//! the bincode encoded handle, encrypted with ChaCha20-Poly1305 under the
+//! caller key with magic and version as associated data. The version is bumped
+//! on every change of the serialized layout of the handle or its states.
+//! `seal` and `open` also encrypt the files of `triple_pool`.
+
+use crate::errors::SnapshotError;
+use crate::session_handle::SessionHandle;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rand_core::{CryptoRng, RngCore};
+use std::fs;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+/// Magic bytes of a session snapshot
+const SNAPSHOT_MAGIC: &[u8; 8] = b"SLCFMSS\0";
+
+/// Current snapshot format version
+pub const SNAPSHOT_VERSION: u8 = 8;
+
+const NONCE_SIZE: usize = 12;
+
+/// magic and version
+const HEADER_SIZE: usize = SNAPSHOT_MAGIC.len() + 1;
+
+/// `magic || version || nonce || ciphertext` of `plaintext`, encrypted with
+/// ChaCha20-Poly1305 under `key` with magic and version as associated data
+pub(crate) fn seal<R: CryptoRng + RngCore>(
+    magic: &[u8; 8],
+    version: u8,
+    plaintext: &[u8],
+    key: &[u8; 32],
+    rng: &mut R,
+) -> Result<Vec<u8>, SnapshotError> {
+    let mut header = [0u8; HEADER_SIZE];
+    header[..magic.len()].copy_from_slice(magic);
+    header[magic.len()] = version;
+
+    let mut nonce = [0u8; NONCE_SIZE];
+    rng.fill_bytes(&mut nonce);
+
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    let ciphertext = cipher
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: plaintext,
+                aad: &header,
+            },
+        )
+        .map_err(|_| SnapshotError::Encryption)?;
+
+    let mut bytes = Vec::with_capacity(header.len() + nonce.len() + ciphertext.len());
+    bytes.extend_from_slice(&header);
+    bytes.extend_from_slice(&nonce);
+    bytes.extend_from_slice(&ciphertext);
+
+    Ok(bytes)
+}
+
+/// Plaintext of `bytes` sealed with [`seal`] under `magic` and `version`
+pub(crate) fn open(
+    magic: &[u8; 8],
+    version: u8,
+    bytes: &[u8],
+    key: &[u8; 32],
+) -> Result<Zeroizing<Vec<u8>>, SnapshotError> {
+    if bytes.len() < HEADER_SIZE + NONCE_SIZE || &bytes[..magic.len()] != magic {
+        return Err(SnapshotError::InvalidMessage);
+    }
+    if bytes[magic.len()] != version {
+        return Err(SnapshotError::UnsupportedVersion(bytes[magic.len()]));
+    }
+
+    let (header, rest) = bytes.split_at(HEADER_SIZE);
+    let (nonce, ciphertext) = rest.split_at(NONCE_SIZE);
+
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    let plaintext = cipher
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: header,
+            },
+        )
+        .map_err(|_| SnapshotError::Decryption)?;
+
+    Ok(Zeroizing::new(plaintext))
+}
+
+/// Write `bytes` to `path`, replacing the file atomically
+pub(crate) fn write_atomic(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, bytes)?;
+    fs::rename(&tmp_path, path)
+}
+
+impl SessionHandle {
+    /// Serialize and encrypt the handle under `key`
+    pub fn snapshot<R: CryptoRng + RngCore>(
+        &self,
+        key: &[u8; 32],
+        rng: &mut R,
+    ) -> Result<Vec<u8>, SnapshotError> {
+        let plaintext =
+            Zeroizing::new(bincode::serialize(self).map_err(|_| SnapshotError::InvalidMessage)?);
+        seal(SNAPSHOT_MAGIC, SNAPSHOT_VERSION, &plaintext, key, rng)
+    }
+
+    /// Decrypt a snapshot taken with [`SessionHandle::snapshot`]
+    pub fn restore(bytes: &[u8], key: &[u8; 32]) -> Result<Self, SnapshotError> {
+        let plaintext = open(SNAPSHOT_MAGIC, SNAPSHOT_VERSION, bytes, key)?;
+        bincode::deserialize(&plaintext).map_err(|_| SnapshotError::InvalidMessage)
+    }
+
+    /// Write a snapshot to `path`, replacing the previous one atomically
+    pub fn save<R: CryptoRng + RngCore>(
+        &self,
+        path: &Path,
+        key: &[u8; 32],
+        rng: &mut R,
+    ) -> Result<(), SnapshotError> {
+        let bytes = self.snapshot(key, rng)?;
+        write_atomic(path, &bytes)?;
+        Ok(())
+    }
+
+    /// Read a snapshot written with [`SessionHandle::save`]
+    pub fn load(path: &Path, key: &[u8; 32]) -> Result<Self, SnapshotError> {
+        Self::restore(&fs::read(path)?, key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::envelope::{decode, encode, Message, ProtocolId, Role};
+    use crate::errors::SessionError;
+    use crate::P;
+    use rand::Rng;
+
+    /// Wire round trip of a message
+    fn relay(msg: &Message) -> Message {
+        decode(&encode(msg).unwrap()).unwrap().message
+    }
+
+    #[test]
+    fn test_snapshot_resume() {
+        let mut rng = rand::thread_rng();
+        let key: [u8; 32] = rng.gen();
+        let mut ob = SessionHandle::new(Role::OB, P);
+        let mut cb = SessionHandle::new(Role::CB, P);
+
+        let session_id: [u8; 32] = rng.gen();
+        cb.start_init(&session_id, &mut rng).unwrap();
+        let msg1 = ob.start_init(&session_id, &mut rng).unwrap().unwrap();
+        let msg2 = cb.step(relay(&msg1), &mut rng).unwrap().unwrap();
+
+        // OB crashes after sending CFMInitMsg1, CB crashes after answering it
+        let ob_snapshot = ob.snapshot(&key, &mut rng).unwrap();
+        let path = std::env::temp_dir().join(format!("cfm_snapshot_{}.bin", std::process::id()));
+        cb.save(&path, &key, &mut rng).unwrap();
+        drop((ob, cb));
+
+        let mut ob = SessionHandle::restore(&ob_snapshot, &key).unwrap();
+        let mut cb = SessionHandle::load(&path, &key).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(ob.role(), Role::OB);
+
+        // OB resends its message, CB detects it and resends its answer
+        let resent = relay(ob.last_outbound().unwrap());
+        assert!(matches!(
+            cb.step(resent, &mut rng),
+            Err(SessionError::DuplicateMessage {
+                protocol: ProtocolId::CFMInit,
+                round: 1
+            })
+        ));
+        let resent = relay(cb.last_outbound().unwrap());
+        assert_eq!(encode(&resent).unwrap(), encode(&msg2).unwrap());
+
+        let msg3 = ob.step(resent, &mut rng).unwrap().unwrap();
+        assert!(cb.step(relay(&msg3), &mut rng).unwrap().is_none());
+        assert!(ob.has_ot_seeds() && cb.has_ot_seeds());
+        assert!(cb.last_outbound().is_none());
+
+        // a snapshot only opens with its key and version
+        let mut bytes = ob.snapshot(&key, &mut rng).unwrap();
+        assert!(matches!(
+            SessionHandle::restore(&bytes, &[0u8; 32]),
+            Err(SnapshotError::Decryption)
+        ));
+        bytes[SNAPSHOT_MAGIC.len()] = SNAPSHOT_VERSION + 1;
+        assert!(matches!(
+            SessionHandle::restore(&bytes, &key),
+            Err(SnapshotError::UnsupportedVersion(_))
+        ));
+    }
+}
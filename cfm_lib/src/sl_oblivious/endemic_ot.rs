@@ -12,6 +12,7 @@ use rand::prelude::*;
 
 use crate::sl_oblivious::{constants::ENDEMIC_OT_LABEL, params::consts::*, utils::ExtractBit};
 
+use crate::errors::OTError;
 use crate::proto::{decode_point, encode_point, PointBytes, POINT_BYTES_SIZE};
 use serde::{Deserialize, Serialize};
 use std::ops::Neg;
@@ -110,7 +111,7 @@ impl EndemicOTSender {
         msg1: &EndemicOTMsg1,
         msg2: &mut EndemicOTMsg2,
         rng: &mut R,
-    ) -> Result<SenderOutput, &'static str> {
+    ) -> Result<SenderOutput, OTError> {
         let mut error = false;
         let otp_enc_keys = array::from_fn(|idx| {
             let [r_0, r_1] = &msg1.r_list[idx];
@@ -150,7 +151,7 @@ impl EndemicOTSender {
         });
 
         if error {
-            return Err("Decode error");
+            return Err(OTError::InvalidPoint);
         }
 
         Ok(SenderOutput { otp_enc_keys })
@@ -206,7 +207,7 @@ impl EndemicOTReceiver {
         next_state
     }
 
-    pub fn process(self, msg2: &EndemicOTMsg2) -> Result<ReceiverOutput, &'static str> {
+    pub fn process(self, msg2: &EndemicOTMsg2) -> Result<ReceiverOutput, OTError> {
         let mut error = false;
         let rho_w_vec: [[u8; LAMBDA_C_BYTES]; LAMBDA_C] = array::from_fn(|idx| {
             let m_b_values = &msg2.m_b_list[idx];
@@ -226,7 +227,7 @@ impl EndemicOTReceiver {
         });
 
         if error {
-            return Err("Decode error");
+            return Err(OTError::InvalidPoint);
         }
 
         Ok(ReceiverOutput {
@@ -15,31 +15,31 @@ use std::ops::Mul;
 
 use merlin::Transcript;
 
-use crate::P;
-use crypto_bigint::{Encoding, U128};
+use crypto_bigint::{Encoding, Integer, U128};
 use rand::Rng;
 use rand_core::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
 use subtle::{Choice, ConditionallySelectable, ConstantTimeEq};
 
+use crate::errors::VOLEError;
 use crate::sl_oblivious::utils::scalar_from_bytes;
 use crate::sl_oblivious::{
     constants::{RANDOM_VOLE_GADGET_VECTOR_LABEL, RANDOM_VOLE_MU_LABEL, RANDOM_VOLE_THETA_LABEL},
     params::consts::*,
     soft_spoken::{
-        ReceiverExtendedOutput, ReceiverOTSeed, Round1Output, SenderOTSeed, SoftSpokenOTError,
-        SoftSpokenOTReceiver, SoftSpokenOTSender,
+        ReceiverExtendedOutput, ReceiverOTSeed, Round1Output, SenderOTSeed, SoftSpokenOTReceiver,
+        SoftSpokenOTSender,
     },
     utils::ExtractBit,
 };
 
 const XI: usize = L; // by definition
 
-fn generate_gadget_vec(session_id: &[u8]) -> Vec<U128> {
+fn generate_gadget_vec(session_id: &[u8], p: &U128) -> Vec<U128> {
     let mut t = Transcript::new(&RANDOM_VOLE_GADGET_VECTOR_LABEL);
     t.append_message(b"session-id", session_id);
 
-    let params = DynResidueParams::new(&P);
+    let params = DynResidueParams::new(p);
 
     let output: Vec<U128> = (0..XI)
         .map(move |i| {
@@ -55,6 +55,137 @@ fn generate_gadget_vec(session_id: &[u8]) -> Vec<U128> {
     output
 }
 
+/// Check that p can be used as RVOLE modulus: |p| = kappa and p is prime.
+/// p reaches the library through the FFI and configuration, so this is the
+/// Baillie-PSW test: a strong probable prime test to base 2 and a strong
+/// Lucas test, which no known composite passes together.
+pub fn is_valid_modulus(p: &U128) -> bool {
+    if p.bits() != KAPPA || !bool::from(p.is_odd()) {
+        return false;
+    }
+
+    is_strong_probable_prime(p, 2) && is_strong_lucas_probable_prime(p)
+}
+
+/// Miller-Rabin test of odd n > 2 to base `a`
+fn is_strong_probable_prime(n: &U128, a: u8) -> bool {
+    let params = DynResidueParams::new(n);
+    let n_minus_one = n.wrapping_sub(&U128::ONE);
+    let s = n_minus_one.trailing_zeros();
+    let d = n_minus_one.shr_vartime(s);
+
+    let mut x = DynResidue::new(&U128::from_u8(a), params).pow(&d).retrieve();
+    if x == U128::ONE || x == n_minus_one {
+        return true;
+    }
+    for _ in 1..s {
+        x = DynResidue::new(&x, params).square().retrieve();
+        if x == n_minus_one {
+            return true;
+        }
+    }
+    false
+}
+
+/// Jacobi symbol (a/n) of odd n
+fn jacobi(a: u128, n: u128) -> i8 {
+    let (mut a, mut n) = (a % n, n);
+    let mut t = 1;
+    while a != 0 {
+        while a % 2 == 0 {
+            a /= 2;
+            if n % 8 == 3 || n % 8 == 5 {
+                t = -t;
+            }
+        }
+        std::mem::swap(&mut a, &mut n);
+        if a % 4 == 3 && n % 4 == 3 {
+            t = -t;
+        }
+        a %= n;
+    }
+    if n == 1 {
+        t
+    } else {
+        0
+    }
+}
+
+/// Strong Lucas test of odd n > 2 with the Selfridge parameters: D is the
+/// first of 5, -7, 9, -11, ... with (D/n) = -1, P = 1 and Q = (1 - D) / 4
+fn is_strong_lucas_probable_prime(n: &U128) -> bool {
+    let n_native = u128::from(*n);
+    // no D exists for a square
+    let root = n.sqrt_vartime();
+    if root.wrapping_mul(&root) == *n {
+        return false;
+    }
+    let Some(n_plus_one) = n_native.checked_add(1) else {
+        return false;
+    };
+
+    let mut d_abs = 5u128;
+    let mut d_negative = false;
+    let d_mod_n = loop {
+        let d_mod_n = if d_negative {
+            (n_native - d_abs % n_native) % n_native
+        } else {
+            d_abs % n_native
+        };
+        match jacobi(d_mod_n, n_native) {
+            -1 => break d_mod_n,
+            0 if d_mod_n != 0 => return false,
+            _ => {}
+        }
+        d_abs += 2;
+        d_negative = !d_negative;
+    };
+
+    let params = DynResidueParams::new(n);
+    let residue = |v: u128| DynResidue::new(&U128::from_u128(v % n_native), params);
+    let big_d = residue(d_mod_n);
+    // Q = (1 - D) / 4, that is (1 + |D|) / 4 for negative D
+    let q = if d_negative {
+        residue((1 + d_abs) / 4)
+    } else {
+        residue((d_abs - 1) / 4).neg()
+    };
+    let half = residue(n_plus_one / 2);
+    let two = residue(2);
+
+    let s = n_plus_one.trailing_zeros();
+    let d = n_plus_one >> s;
+
+    // U_1 = 1, V_1 = P = 1, then double and add over the bits of d
+    let mut u = residue(1);
+    let mut v = residue(1);
+    let mut q_k = q;
+    for bit in (0..(127 - d.leading_zeros())).rev() {
+        u = u.mul(&v);
+        v = v.square().sub(&two.mul(&q_k));
+        q_k = q_k.square();
+        if (d >> bit) & 1 == 1 {
+            let u_next = u.add(&v).mul(&half);
+            v = big_d.mul(&u).add(&v).mul(&half);
+            u = u_next;
+            q_k = q_k.mul(&q);
+        }
+    }
+
+    let zero = U128::ZERO;
+    if u.retrieve() == zero || v.retrieve() == zero {
+        return true;
+    }
+    for _ in 1..s {
+        v = v.square().sub(&two.mul(&q_k));
+        q_k = q_k.square();
+        if v.retrieve() == zero {
+            return true;
+        }
+    }
+    false
+}
+
 /// RVOLEOutputATilde
 #[derive(Clone, Serialize, Deserialize)]
 pub struct RVOLEOutputATilde {
@@ -93,6 +224,40 @@ impl RVOLEOutput {
             mu_hash: [0u8; 2 * LAMBDA_C_BYTES],
         }
     }
+
+    /// Split into the a_tilde rows and the consistency check values,
+    /// so that a large output can be sent in parts
+    pub fn into_parts(self) -> (Vec<RVOLEOutputATilde>, RVOLEOutputCheck) {
+        let check = RVOLEOutputCheck {
+            eta: self.eta,
+            mu_hash: self.mu_hash,
+        };
+        (self.a_tilde, check)
+    }
+
+    /// Inverse of `into_parts`.
+    /// Returns None if the number of rows does not match l_batch.
+    pub fn from_parts(
+        a_tilde: Vec<RVOLEOutputATilde>,
+        check: RVOLEOutputCheck,
+        l_batch: usize,
+    ) -> Option<Self> {
+        if a_tilde.len() != l_batch + RHO {
+            return None;
+        }
+        Some(RVOLEOutput {
+            a_tilde,
+            eta: check.eta,
+            mu_hash: check.mu_hash,
+        })
+    }
+}
+
+/// Consistency check values of RVOLEOutput
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RVOLEOutputCheck {
+    eta: [[u8; KAPPA_BYTES]; RHO],
+    mu_hash: [u8; 2 * LAMBDA_C_BYTES],
 }
 
 /// RVOLEReceiver
@@ -100,6 +265,7 @@ impl RVOLEOutput {
 #[repr(C)]
 pub struct RVOLEReceiver {
     session_id: [u8; 32],
+    p: U128,
     #[serde(with = "serde_arrays")]
     beta: [u8; L_BYTES],
     receiver_extended_output: ReceiverExtendedOutput,
@@ -109,18 +275,19 @@ impl RVOLEReceiver {
     /// Create a new RVOLE receiver
     pub fn new<R: CryptoRng + RngCore>(
         session_id: [u8; 32],
+        p: U128,
         seed_ot_results: &SenderOTSeed,
         round1_output: &mut Round1Output,
         l_batch: usize,
         rng: &mut R,
     ) -> (Box<RVOLEReceiver>, U128) {
-        let params = DynResidueParams::new(&P);
+        let params = DynResidueParams::new(&p);
 
         let mut beta = [0u8; L_BYTES];
         rng.fill_bytes(&mut beta);
 
         // b = <g, /beta>
-        let b = generate_gadget_vec(&session_id).iter().enumerate().fold(
+        let b = generate_gadget_vec(&session_id, &p).iter().enumerate().fold(
             U128::ZERO,
             |option_0, (i, gv)| {
                 let i_bit = beta.extract_bit(i);
@@ -134,6 +301,7 @@ impl RVOLEReceiver {
 
         let mut next = Box::new(RVOLEReceiver {
             session_id,
+            p,
             beta,
             receiver_extended_output: *ReceiverExtendedOutput::new(&beta, l_batch),
         });
@@ -156,8 +324,8 @@ impl RVOLEReceiver {
         &self,
         rvole_output: &RVOLEOutput,
         l_batch: usize,
-    ) -> Result<Vec<U128>, &'static str> {
-        let params = DynResidueParams::new(&P);
+    ) -> Result<Vec<U128>, VOLEError> {
+        let params = DynResidueParams::new(&self.p);
 
         let mut t = Transcript::new(&RANDOM_VOLE_THETA_LABEL);
         t.append_message(b"session-id", &self.session_id);
@@ -247,11 +415,11 @@ impl RVOLEReceiver {
         t.challenge_bytes(b"mu-hash", &mut mu_prime_hash);
 
         if rvole_output.mu_hash.ct_ne(&mu_prime_hash).into() {
-            return Err("Consistency check failed");
+            return Err(VOLEError::ConsistencyCheck);
         }
 
         let mut d = vec![U128::ZERO; l_batch];
-        let gadget_vector = generate_gadget_vec(&self.session_id);
+        let gadget_vector = generate_gadget_vec(&self.session_id, &self.p);
         #[allow(clippy::needless_range_loop)]
         for i in 0..l_batch {
             let mut d_dyn_res = DynResidue::new(&d[i], params);
@@ -273,16 +441,18 @@ pub struct RVOLESender;
 
 impl RVOLESender {
     /// process Round1Output from RVOLEReceiver
+    #[allow(clippy::too_many_arguments)]
     pub fn process<R: CryptoRng + RngCore>(
         session_id: &[u8],
+        p: &U128,
         seed_ot_results: &ReceiverOTSeed,
         a: &[U128],
         round1_output: &Round1Output,
         output: &mut RVOLEOutput,
         l_batch: usize,
         rng: &mut R,
-    ) -> Result<Vec<U128>, SoftSpokenOTError> {
-        let params = DynResidueParams::new(&P);
+    ) -> Result<Vec<U128>, VOLEError> {
+        let params = DynResidueParams::new(p);
 
         let sender_extended_output =
             SoftSpokenOTSender::process(session_id, seed_ot_results, round1_output, l_batch)?;
@@ -294,7 +464,7 @@ impl RVOLESender {
             |i: usize, j: usize| scalar_from_bytes(params, sender_extended_output.v_1[i][j]);
 
         let mut c = vec![U128::ZERO; l_batch];
-        let gadget_vector = generate_gadget_vec(session_id);
+        let gadget_vector = generate_gadget_vec(session_id, p);
         #[allow(clippy::needless_range_loop)]
         for i in 0..l_batch {
             let mut el_dyn_res = DynResidue::new(&U128::ZERO, params);
@@ -416,11 +586,54 @@ mod tests {
     use super::*;
     use crate::sl_oblivious::soft_spoken::generate_all_but_one_seed_ot;
 
+    // 2^128 - 159
+    const SECOND_PRIME: U128 = U128::from_be_hex("ffffffffffffffffffffffffffffff61");
+
+    #[test]
+    fn modulus() {
+        assert!(is_valid_modulus(&crate::P));
+        assert!(is_valid_modulus(&SECOND_PRIME));
+        // 2^128 - 1 = 3 * 5 * 17 * 257 * ...
+        assert!(!is_valid_modulus(&U128::MAX));
+        // prime with less than kappa bits
+        assert!(!is_valid_modulus(&U128::from_u64(0xffffffffffffffc5)));
+        // (2^64 - 59) * (2^64 - 83)
+        let semiprime = U128::from_u64(0xffffffffffffffc5)
+            .wrapping_mul(&U128::from_u64(0xffffffffffffffad));
+        assert!(!is_valid_modulus(&semiprime));
+    }
+
+    #[test]
+    fn lucas() {
+        for prime in [3u64, 5, 7, 11, 13, 97, 7919, 0xffffffffffffffc5] {
+            assert!(is_strong_lucas_probable_prime(&U128::from_u64(prime)));
+        }
+        assert!(is_strong_lucas_probable_prime(&crate::P));
+        // strong pseudoprimes to base 2, 2047 = 23 * 89 and
+        // 3215031751 = 151 * 751 * 28351, also to bases 3, 5 and 7
+        for composite in [2047u64, 3215031751] {
+            let composite = U128::from_u64(composite);
+            assert!(is_strong_probable_prime(&composite, 2));
+            assert!(!is_strong_lucas_probable_prime(&composite));
+        }
+        // 5459 = 53 * 103 is a strong Lucas pseudoprime, but not to base 2
+        let composite = U128::from_u64(5459);
+        assert!(is_strong_lucas_probable_prime(&composite));
+        assert!(!is_strong_probable_prime(&composite, 2));
+        // squares have no Selfridge D
+        assert!(!is_strong_lucas_probable_prime(&U128::from_u64(9)));
+    }
+
     #[test]
     fn pairwise() {
+        pairwise_with_prime(crate::P);
+        pairwise_with_prime(SECOND_PRIME);
+    }
+
+    fn pairwise_with_prime(p: U128) {
         let mut rng = rand::thread_rng();
         let l_batch = 7usize;
-        let params = DynResidueParams::new(&P);
+        let params = DynResidueParams::new(&p);
 
         let (sender_ot_seed, receiver_ot_seed) = generate_all_but_one_seed_ot(&mut rng);
 
@@ -429,6 +642,7 @@ mod tests {
         let mut round1_output = Round1Output::default();
         let (receiver, beta) = RVOLEReceiver::new(
             session_id,
+            p,
             &sender_ot_seed,
             &mut round1_output,
             l_batch,
@@ -444,6 +658,7 @@ mod tests {
 
         let sender_shares = RVOLESender::process(
             &session_id,
+            &p,
             &receiver_ot_seed,
             &alpha,
             &round1_output,
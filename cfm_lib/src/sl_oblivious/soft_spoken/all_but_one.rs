@@ -4,6 +4,8 @@
 use merlin::Transcript;
 use subtle::{ConditionallySelectable, ConstantTimeEq};
 
+use crate::errors::OTError;
+
 use crate::sl_oblivious::{
     constants::*,
     endemic_ot::{ReceiverOutput, SenderOutput},
@@ -114,7 +116,7 @@ pub fn eval_pprf(
     receiver_ot_seed: &ReceiverOutput,
     PPRFOutput(output): &PPRFOutput,
     all_but_one_receiver_seed: &mut ReceiverOTSeed,
-) -> Result<(), &'static str> {
+) -> Result<(), OTError> {
     for (j, out) in output.iter().enumerate() {
         let x_star_0: u8 = receiver_ot_seed.choice_bits.extract_bit(j * SOFT_SPOKEN_K) as u8;
 
@@ -210,7 +212,7 @@ pub fn eval_pprf(
         s_tilda_hash.challenge_bytes(&ALL_BUT_ONE_PPRF_HASH_LABEL, &mut s_tilda_digest);
 
         if s_tilda_digest.ct_ne(s_tilda_expected).into() {
-            return Err("Invalid proof");
+            return Err(OTError::InvalidPPRFProof);
         }
 
         all_but_one_receiver_seed.random_choices[j] = y_star as u8;
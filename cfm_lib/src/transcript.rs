@@ -0,0 +1,67 @@
+//! Running hash over the messages of a session.
+//!
+//! h_0 = H(session id) and h_i = H(h_{i-1}, m_i), where m_i is the envelope
+//! encoding of the i-th message sent or accepted in the running sub-protocol.
+//! Both parties see the same messages in the same order, so they hold the same
+//! hash after each round, and anyone with the wire messages can recompute it.
+//!
+//! The sender of m_i signs h_i and sends the signature along with m_i, so
+//! each message is bound to its sender and to all messages before it.
+
+use crate::constants::TRANSCRIPT_LABEL;
+use crate::envelope::{encode, Message};
+use crate::errors::EnvelopeError;
+use crate::schnorr::{Signature, SigningKey, VerifyingKey};
+use merlin::Transcript;
+use rand_core::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+/// Hash of the messages of a session so far
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TranscriptHash([u8; 32]);
+
+impl TranscriptHash {
+    /// Hash of the empty transcript of session `session_id`
+    pub fn new(session_id: &[u8; 32]) -> Self {
+        let mut t = Transcript::new(TRANSCRIPT_LABEL.as_ref());
+        t.append_message(b"session-id", session_id);
+        let mut hash = [0u8; 32];
+        t.challenge_bytes(b"transcript-hash", &mut hash);
+        TranscriptHash(hash)
+    }
+
+    /// Hash with the value `bytes`, e.g. of an abort report
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        TranscriptHash(bytes)
+    }
+
+    /// Hash after the next message, given by its envelope encoding
+    pub fn append_bytes(&self, bytes: &[u8]) -> Self {
+        let mut t = Transcript::new(TRANSCRIPT_LABEL.as_ref());
+        t.append_message(b"transcript-hash", &self.0);
+        t.append_message(b"message", bytes);
+        let mut hash = [0u8; 32];
+        t.challenge_bytes(b"transcript-hash", &mut hash);
+        TranscriptHash(hash)
+    }
+
+    /// Hash after the next message
+    pub fn append(&self, msg: &Message) -> Result<Self, EnvelopeError> {
+        Ok(self.append_bytes(&encode(msg)?))
+    }
+
+    /// The hash value
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// Signature of the sender of the last message over the hash
+    pub fn sign<R: CryptoRng + RngCore>(&self, key: &SigningKey, rng: &mut R) -> Signature {
+        key.sign(&self.0, rng)
+    }
+
+    /// True if `signature` is a signature over the hash by the holder of `key`
+    pub fn verify(&self, key: &VerifyingKey, signature: &Signature) -> bool {
+        key.verify(&self.0, signature)
+    }
+}
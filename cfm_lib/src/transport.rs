@@ -0,0 +1,371 @@
+//! Message transports between OB and CB.
+//!
+//! A `Transport` sends and receives whole envelope encoded messages.
+//! `StreamTransport` frames them over a byte stream, a TCP or Unix socket
+//! or the in-memory `MemoryStream` pipe. `ChannelTransport` passes them
+//! over `std::sync::mpsc` channels between two threads of one process.
+//! `AuthenticatedTransport` adds the MACs of a channel binding handshake to
+//! any of them.
+
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::errors::TransportError;
+use crate::frame::{read_frame, write_frame};
+use crate::identity::ChannelBinding;
+
+/// Sends and receives the messages of one party
+pub trait Transport {
+    /// Sends one message
+    fn send(&mut self, msg: &[u8]) -> Result<(), TransportError>;
+
+    /// Waits at most `timeout` for the next message, until it is read whole
+    fn recv(&mut self, timeout: Duration) -> Result<Vec<u8>, TransportError>;
+}
+
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn send(&mut self, msg: &[u8]) -> Result<(), TransportError> {
+        (**self).send(msg)
+    }
+
+    fn recv(&mut self, timeout: Duration) -> Result<Vec<u8>, TransportError> {
+        (**self).recv(timeout)
+    }
+}
+impl<T: Transport + ?Sized> Transport for &mut T {
+    fn send(&mut self, msg: &[u8]) -> Result<(), TransportError> {
+        (**self).send(msg)
+    }
+
+    fn recv(&mut self, timeout: Duration) -> Result<Vec<u8>, TransportError> {
+        (**self).recv(timeout)
+    }
+}
+
+/// Byte stream with a read timeout
+pub trait TimeoutStream: Read + Write {
+    /// Read calls fail with `TimedOut` or `WouldBlock` after `timeout`
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()>;
+}
+
+impl TimeoutStream for TcpStream {
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+}
+
+#[cfg(unix)]
+impl TimeoutStream for UnixStream {
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        UnixStream::set_read_timeout(self, timeout)
+    }
+}
+
+/// Reads from a stream until a deadline. Each read waits at most for the
+/// time left, so a peer sending a frame byte by byte cannot stretch it.
+struct DeadlineReader<'a, S> {
+    stream: &'a mut S,
+    deadline: Instant,
+}
+
+impl<S: TimeoutStream> Read for DeadlineReader<'_, S> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let left = self.deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        self.stream.set_read_timeout(Some(left))?;
+        self.stream.read(out)
+    }
+}
+
+/// Length-prefixed frames over a byte stream. The timeout of `recv` bounds
+/// reading the whole frame. A timeout can leave a frame half read, drop the
+/// transport after it.
+pub struct StreamTransport<S> {
+    stream: S,
+}
+
+impl<S: TimeoutStream> StreamTransport<S> {
+    /// Transport over `stream`
+    pub fn new(stream: S) -> Self {
+        StreamTransport { stream }
+    }
+
+    /// The underlying stream
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<S: TimeoutStream> Transport for StreamTransport<S> {
+    fn send(&mut self, msg: &[u8]) -> Result<(), TransportError> {
+        write_frame(&mut self.stream, msg)?;
+        Ok(())
+    }
+
+    fn recv(&mut self, timeout: Duration) -> Result<Vec<u8>, TransportError> {
+        let mut reader = DeadlineReader {
+            stream: &mut self.stream,
+            deadline: Instant::now() + timeout,
+        };
+        Ok(read_frame(&mut reader)?)
+    }
+}
+
+/// Framed transport over TCP
+pub type TcpTransport = StreamTransport<TcpStream>;
+
+/// Framed transport over a Unix socket
+#[cfg(unix)]
+pub type UnixTransport = StreamTransport<UnixStream>;
+
+/// Framed transport over an in-memory pipe
+pub type MemoryTransport = StreamTransport<MemoryStream>;
+
+impl MemoryTransport {
+    /// Two connected transports, one for each party
+    pub fn pair() -> (Self, Self) {
+        let (a, b) = MemoryStream::pair();
+        (Self::new(a), Self::new(b))
+    }
+}
+
+#[derive(Default)]
+struct Pipe {
+    buf: Mutex<(VecDeque<u8>, bool)>,
+    ready: Condvar,
+}
+
+impl Pipe {
+    fn close(&self) {
+        self.buf.lock().unwrap_or_else(PoisonError::into_inner).1 = true;
+        self.ready.notify_all();
+    }
+}
+
+/// One end of an in-memory duplex byte pipe
+pub struct MemoryStream {
+    read: Arc<Pipe>,
+    write: Arc<Pipe>,
+    timeout: Option<Duration>,
+}
+
+impl MemoryStream {
+    /// Both ends of a pipe, dropping one end closes it
+    pub fn pair() -> (Self, Self) {
+        let (a, b) = (Arc::new(Pipe::default()), Arc::new(Pipe::default()));
+        (
+            MemoryStream {
+                read: a.clone(),
+                write: b.clone(),
+                timeout: None,
+            },
+            MemoryStream {
+                read: b,
+                write: a,
+                timeout: None,
+            },
+        )
+    }
+}
+
+impl Read for MemoryStream {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        let mut buf = self.read.buf.lock().unwrap_or_else(PoisonError::into_inner);
+        // wait for data, or end of stream once the writer is gone
+        while buf.0.is_empty() && !buf.1 {
+            buf = match deadline {
+                None => self
+                    .read
+                    .ready
+                    .wait(buf)
+                    .unwrap_or_else(PoisonError::into_inner),
+                Some(deadline) => {
+                    let left = deadline.saturating_duration_since(Instant::now());
+                    if left.is_zero() {
+                        return Err(io::ErrorKind::TimedOut.into());
+                    }
+                    self.read
+                        .ready
+                        .wait_timeout(buf, left)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0
+                }
+            };
+        }
+
+        let n = out.len().min(buf.0.len());
+        for (dst, src) in out.iter_mut().zip(buf.0.drain(..n)) {
+            *dst = src;
+        }
+        Ok(n)
+    }
+}
+
+impl Write for MemoryStream {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let mut buf = self
+            .write
+            .buf
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if buf.1 {
+            return Err(io::ErrorKind::BrokenPipe.into());
+        }
+        buf.0.extend(data);
+        self.write.ready.notify_all();
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl TimeoutStream for MemoryStream {
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.timeout = timeout;
+        Ok(())
+    }
+}
+
+impl Drop for MemoryStream {
+    fn drop(&mut self) {
+        self.write.close();
+        self.read.close();
+    }
+}
+
+/// Whole messages over a pair of channels, no framing
+pub struct ChannelTransport {
+    tx: Sender<Vec<u8>>,
+    rx: Receiver<Vec<u8>>,
+}
+
+impl ChannelTransport {
+    /// Two connected transports, one for each party
+    pub fn pair() -> (Self, Self) {
+        let (tx_a, rx_b) = mpsc::channel();
+        let (tx_b, rx_a) = mpsc::channel();
+        (
+            ChannelTransport { tx: tx_a, rx: rx_a },
+            ChannelTransport { tx: tx_b, rx: rx_b },
+        )
+    }
+}
+
+impl Transport for ChannelTransport {
+    fn send(&mut self, msg: &[u8]) -> Result<(), TransportError> {
+        self.tx
+            .send(msg.to_vec())
+            .map_err(|_| TransportError::Closed)
+    }
+
+    fn recv(&mut self, timeout: Duration) -> Result<Vec<u8>, TransportError> {
+        self.rx.recv_timeout(timeout).map_err(|e| match e {
+            RecvTimeoutError::Timeout => TransportError::Timeout,
+            RecvTimeoutError::Disconnected => TransportError::Closed,
+        })
+    }
+}
+
+/// Messages MAC-ed under a `ChannelBinding` over another transport
+pub struct AuthenticatedTransport<T> {
+    inner: T,
+    binding: ChannelBinding,
+}
+
+impl<T: Transport> AuthenticatedTransport<T> {
+    /// Transport over `inner`, which has run the handshake of `binding`
+    pub fn new(inner: T, binding: ChannelBinding) -> Self {
+        AuthenticatedTransport { inner, binding }
+    }
+
+    /// The channel binding
+    pub fn binding(&self) -> &ChannelBinding {
+        &self.binding
+    }
+}
+
+impl<T: Transport> Transport for AuthenticatedTransport<T> {
+    fn send(&mut self, msg: &[u8]) -> Result<(), TransportError> {
+        let sealed = self.binding.seal(msg);
+        self.inner.send(&sealed)
+    }
+
+    fn recv(&mut self, timeout: Duration) -> Result<Vec<u8>, TransportError> {
+        let sealed = self.inner.recv(timeout)?;
+        Ok(self.binding.open(&sealed)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    const TIMEOUT: Duration = Duration::from_millis(200);
+
+    /// Ping-pong between two threads, then a timeout and a closed peer
+    fn check<T: Transport + Send + 'static>(mut a: T, mut b: T) {
+        let peer = thread::spawn(move || {
+            let msg = b.recv(Duration::from_secs(5)).unwrap();
+            b.send(&[msg.as_slice(), b" pong"].concat()).unwrap();
+            b.send(&vec![9; 100_000]).unwrap();
+            // keep b open until a has seen the timeout
+            assert_eq!(b.recv(Duration::from_secs(5)).unwrap(), b"bye");
+        });
+
+        a.send(b"ping").unwrap();
+        assert_eq!(a.recv(Duration::from_secs(5)).unwrap(), b"ping pong");
+        assert_eq!(a.recv(Duration::from_secs(5)).unwrap(), vec![9; 100_000]);
+        assert!(matches!(a.recv(TIMEOUT), Err(TransportError::Timeout)));
+        a.send(b"bye").unwrap();
+
+        peer.join().unwrap();
+        assert!(matches!(
+            a.recv(Duration::from_secs(5)),
+            Err(TransportError::Closed)
+        ));
+    }
+
+    #[test]
+    fn test_stream_timeout() {
+        // a peer sending one byte per read timeout still hits the deadline
+        let (mut a, b) = MemoryStream::pair();
+        let mut b = StreamTransport::new(b);
+        let peer = thread::spawn(move || {
+            a.write_all(&4u32.to_be_bytes()).unwrap();
+            for byte in b"slow" {
+                thread::sleep(TIMEOUT / 2);
+                a.write_all(&[*byte]).unwrap();
+            }
+            a
+        });
+        assert!(matches!(b.recv(TIMEOUT), Err(TransportError::Timeout)));
+        drop(peer.join().unwrap());
+    }
+
+    #[test]
+    fn test_transports() {
+        let (a, b) = MemoryTransport::pair();
+        check(a, b);
+
+        let (a, b) = ChannelTransport::pair();
+        check(a, b);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let b = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (a, _) = listener.accept().unwrap();
+        check(TcpTransport::new(a), TcpTransport::new(b));
+    }
+}
@@ -0,0 +1,428 @@
+//! Pool of pre-computed shares and authenticated beaver triples.
+//!
+//! ABT runs offline once for many CFM sessions, with eta_i and eta_m from
+//! [`pool_sizes`]. Each party builds a [`TriplePool`] from its ABT output and
+//! the OT seeds of its CFM init, using the ABT session id as the pool id.
+//!
+//! Session `k` of the pool gets the k-th block of `ABT_SHARES` shares and
+//! `NUMBER_OF_AUTH_BEAVER_TRIPLES` triples, with the OT seeds its CFM session
+//! runs on. Its CFM session id is derived from (pool id, k). If OB and CB take
+//! different indices, their session ids differ and CFM fails with
+//! `InvalidSessionID`, so the parties cannot drift apart silently. Consumed
+//! blocks are removed from the pool and are never handed out again. A
+//! [`PoolSession`] goes to [`SessionHandle::load_pool_session_ob`] or
+//! [`SessionHandle::load_pool_session_cb`] before CFM starts.
+//!
+//! The pool file is sealed like a session snapshot, under its own magic and
+//! version.
+//!
+//! [`SessionHandle::load_pool_session_ob`]: crate::session_handle::SessionHandle::load_pool_session_ob
+//! [`SessionHandle::load_pool_session_cb`]: crate::session_handle::SessionHandle::load_pool_session_cb
+
+use crate::auth_beaver_triples::{abt_batch_sizes, Share, TripleShare};
+use crate::cfm_protocol::{ABT_SHARES, NUMBER_OF_AUTH_BEAVER_TRIPLES};
+use crate::constants::TRIPLE_POOL_LABEL;
+use crate::errors::TriplePoolError;
+use crate::session_snapshot::{open, seal, write_atomic};
+use crypto_bigint::U128;
+use merlin::Transcript;
+use rand_core::{CryptoRng, RngCore};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+/// Magic bytes of the pool file
+const POOL_FILE_MAGIC: &[u8; 8] = b"SLCFMTP\0";
+
+/// Current pool file format version
+const POOL_FILE_VERSION: u8 = 4;
+
+/// ABT parameters (eta_i, eta_m) producing material for `sessions` CFM sessions
+pub fn pool_sizes(sessions: usize) -> (usize, usize) {
+    abt_batch_sizes(sessions, ABT_SHARES, NUMBER_OF_AUTH_BEAVER_TRIPLES)
+}
+
+/// Shares and triples form the same number of whole CFM sessions
+fn is_whole_sessions(shares: usize, auth_triples: usize) -> bool {
+    shares.is_multiple_of(ABT_SHARES)
+        && auth_triples.is_multiple_of(NUMBER_OF_AUTH_BEAVER_TRIPLES)
+        && shares / ABT_SHARES == auth_triples / NUMBER_OF_AUTH_BEAVER_TRIPLES
+}
+
+/// CFM session id of session `index` of pool `pool_id`
+pub fn pool_session_id(pool_id: &[u8; 32], index: u64) -> [u8; 32] {
+    let mut t = Transcript::new(TRIPLE_POOL_LABEL.as_ref());
+    t.append_message(b"pool-id", pool_id);
+    t.append_u64(b"session-index", index);
+    let mut session_id = [0u8; 32];
+    t.challenge_bytes(b"cfm-session-id", &mut session_id);
+    session_id
+}
+
+/// Shares, triples and OT seeds for one CFM session
+pub struct PoolSession<S> {
+    /// index of the session in the pool
+    pub index: u64,
+
+    /// CFM session id
+    pub session_id: [u8; 32],
+
+    /// p
+    pub p: U128,
+
+    /// OT seeds of the party, `CFMInitOTSeedsOB` or `CFMInitOTSeedsCB`
+    pub ot_seeds: S,
+
+    /// ABT_SHARES shares
+    pub shares: Vec<Share>,
+
+    /// NUMBER_OF_AUTH_BEAVER_TRIPLES triples
+    pub auth_triples: Vec<TripleShare>,
+}
+
+/// Pre-computed shares and triples of one party, with the OT seeds `S` of
+/// that party
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TriplePool<S> {
+    pool_id: [u8; 32],
+    p: U128,
+    ot_seeds: S,
+    next_index: u64,
+    shares: Vec<Share>,
+    auth_triples: Vec<TripleShare>,
+}
+
+impl<S: Clone + Serialize + DeserializeOwned> TriplePool<S> {
+    /// Create a pool from the output of ABT session `pool_id`, run on
+    /// `ot_seeds`
+    pub fn new(
+        pool_id: &[u8; 32],
+        p: U128,
+        ot_seeds: S,
+        shares: Vec<Share>,
+        auth_triples: Vec<TripleShare>,
+    ) -> Result<Self, TriplePoolError> {
+        if !is_whole_sessions(shares.len(), auth_triples.len()) {
+            return Err(TriplePoolError::InvalidSize);
+        }
+
+        Ok(TriplePool {
+            pool_id: *pool_id,
+            p,
+            ot_seeds,
+            next_index: 0,
+            shares,
+            auth_triples,
+        })
+    }
+
+    /// Pool id
+    pub fn pool_id(&self) -> &[u8; 32] {
+        &self.pool_id
+    }
+
+    /// p
+    pub fn p(&self) -> U128 {
+        self.p
+    }
+
+    /// Index of the next session to be handed out
+    pub fn next_index(&self) -> u64 {
+        self.next_index
+    }
+
+    /// Number of sessions left in the pool
+    pub fn remaining(&self) -> usize {
+        self.shares.len() / ABT_SHARES
+    }
+
+    /// Take the next session
+    pub fn take(&mut self) -> Result<PoolSession<S>, TriplePoolError> {
+        self.take_at(self.next_index)
+    }
+
+    /// Take session `index`, used to follow the index chosen by the other party.
+    /// Sessions before `index` that were not taken yet are discarded.
+    pub fn take_at(&mut self, index: u64) -> Result<PoolSession<S>, TriplePoolError> {
+        if index < self.next_index {
+            return Err(TriplePoolError::AlreadyConsumed(index));
+        }
+        let skip = index - self.next_index;
+        if skip >= self.remaining() as u64 {
+            return Err(TriplePoolError::Exhausted);
+        }
+
+        let skip = skip as usize;
+        self.shares.drain(..skip * ABT_SHARES);
+        self.auth_triples
+            .drain(..skip * NUMBER_OF_AUTH_BEAVER_TRIPLES);
+
+        let shares: Vec<Share> = self.shares.drain(..ABT_SHARES).collect();
+        let auth_triples: Vec<TripleShare> = self
+            .auth_triples
+            .drain(..NUMBER_OF_AUTH_BEAVER_TRIPLES)
+            .collect();
+        self.next_index = index + 1;
+
+        Ok(PoolSession {
+            index,
+            session_id: pool_session_id(&self.pool_id, index),
+            p: self.p,
+            ot_seeds: self.ot_seeds.clone(),
+            shares,
+            auth_triples,
+        })
+    }
+
+    /// Take the next session and save the updated pool to `path`.
+    /// The session is returned only after the consumption is on disk,
+    /// so a crash can not hand out the same triples twice.
+    pub fn take_and_save<R: CryptoRng + RngCore>(
+        &mut self,
+        path: &Path,
+        key: &[u8; 32],
+        rng: &mut R,
+    ) -> Result<PoolSession<S>, TriplePoolError> {
+        let session = self.take()?;
+        self.save(path, key, rng)?;
+        Ok(session)
+    }
+
+    /// Encrypt the pool with `key` and write it to `path`, replacing the file atomically
+    pub fn save<R: CryptoRng + RngCore>(
+        &self,
+        path: &Path,
+        key: &[u8; 32],
+        rng: &mut R,
+    ) -> Result<(), TriplePoolError> {
+        let plaintext =
+            Zeroizing::new(bincode::serialize(self).map_err(|_| TriplePoolError::InvalidMessage)?);
+        let bytes = seal(POOL_FILE_MAGIC, POOL_FILE_VERSION, &plaintext, key, rng)?;
+        write_atomic(path, &bytes)?;
+        Ok(())
+    }
+
+    /// Read and decrypt a pool saved with [`TriplePool::save`]
+    pub fn load(path: &Path, key: &[u8; 32]) -> Result<Self, TriplePoolError> {
+        let plaintext = open(POOL_FILE_MAGIC, POOL_FILE_VERSION, &fs::read(path)?, key)?;
+        let pool: TriplePool<S> =
+            bincode::deserialize(&plaintext).map_err(|_| TriplePoolError::InvalidMessage)?;
+        if !is_whole_sessions(pool.shares.len(), pool.auth_triples.len()) {
+            return Err(TriplePoolError::InvalidSize);
+        }
+
+        Ok(pool)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{pool_session_id, pool_sizes, TriplePool, POOL_FILE_MAGIC, POOL_FILE_VERSION};
+    use crate::auth_beaver_triples::{
+        abt_create_msg1, abt_process_msg1, abt_process_msg2, abt_process_msg3, abt_process_msg4,
+        abt_process_msg5, Share, TripleShare,
+    };
+    use crate::cfm_protocol::{OutputPolicy, ABT_SHARES, NUMBER_OF_AUTH_BEAVER_TRIPLES};
+    use crate::envelope::{Message, Role};
+    use crate::errors::TriplePoolError;
+    use crate::seed_store::OTSeeds;
+    use crate::session_handle::SessionHandle;
+    use crate::utils::Customer;
+    use crate::P;
+    use crypto_bigint::{U128, U64};
+    use rand::Rng;
+
+    fn share(value: u64) -> Share {
+        Share {
+            value: U128::from_u64(value),
+            ..Share::default()
+        }
+    }
+
+    #[test]
+    fn test_triple_pool() {
+        let mut rng = rand::thread_rng();
+        let pool_id: [u8; 32] = rng.gen();
+        let key: [u8; 32] = rng.gen();
+
+        let (eta_i, eta_m) = pool_sizes(3);
+        let ot_seeds = [7u8; 4];
+        let shares: Vec<Share> = (0..eta_i as u64).map(share).collect();
+        let auth_triples: Vec<TripleShare> = (0..eta_m as u64)
+            .map(|i| TripleShare {
+                x: share(i),
+                y: share(i),
+                z: share(i),
+            })
+            .collect();
+
+        assert!(matches!(
+            TriplePool::new(
+                &pool_id,
+                P,
+                ot_seeds,
+                shares[1..].to_vec(),
+                auth_triples.clone()
+            ),
+            Err(TriplePoolError::InvalidSize)
+        ));
+        let mut pool = TriplePool::new(&pool_id, P, ot_seeds, shares, auth_triples).unwrap();
+        assert_eq!(pool.remaining(), 3);
+
+        let dir = std::env::temp_dir().join(format!("cfm-pool-{}", hex::encode(&pool_id[..8])));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("pool.bin");
+
+        let session = pool.take_and_save(&path, &key, &mut rng).unwrap();
+        assert_eq!(session.index, 0);
+        assert_eq!(session.session_id, pool_session_id(&pool_id, 0));
+        assert_eq!(session.ot_seeds, ot_seeds);
+        assert_eq!(session.shares.len(), ABT_SHARES);
+        assert_eq!(session.auth_triples.len(), NUMBER_OF_AUTH_BEAVER_TRIPLES);
+        assert_eq!(session.shares[0].value, U128::ZERO);
+
+        // consumption survives a reload
+        let mut reloaded = TriplePool::<[u8; 4]>::load(&path, &key).unwrap();
+        assert_eq!(reloaded.next_index(), 1);
+        assert_eq!(reloaded.remaining(), 2);
+        assert!(matches!(
+            reloaded.take_at(0),
+            Err(TriplePoolError::AlreadyConsumed(0))
+        ));
+
+        // session 1 is skipped
+        let session = reloaded.take_at(2).unwrap();
+        assert_eq!(session.session_id, pool_session_id(&pool_id, 2));
+        assert_eq!(
+            session.shares[0].value,
+            U128::from_u64(2 * ABT_SHARES as u64)
+        );
+        assert_eq!(
+            session.auth_triples[0].z.value,
+            U128::from_u64(2 * NUMBER_OF_AUTH_BEAVER_TRIPLES as u64)
+        );
+        assert!(matches!(reloaded.take(), Err(TriplePoolError::Exhausted)));
+
+        let wrong_key: [u8; 32] = rng.gen();
+        assert!(matches!(
+            TriplePool::<[u8; 4]>::load(&path, &wrong_key),
+            Err(TriplePoolError::Decryption)
+        ));
+
+        // a file of an older layout is rejected before decryption
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[POOL_FILE_MAGIC.len()] = POOL_FILE_VERSION - 1;
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(matches!(
+            TriplePool::<[u8; 4]>::load(&path, &key),
+            Err(TriplePoolError::UnsupportedVersion(version)) if version == POOL_FILE_VERSION - 1
+        ));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Deliver `msg` to `to` and return its answer
+    fn relay(
+        to: &mut SessionHandle,
+        msg: Message,
+        rng: &mut rand::rngs::ThreadRng,
+    ) -> Option<Message> {
+        to.step(msg, rng).unwrap()
+    }
+
+    #[test]
+    fn test_cfm_from_pool() {
+        let mut rng = rand::thread_rng();
+        let mut ob = SessionHandle::new(Role::OB, P);
+        let mut cb = SessionHandle::new(Role::CB, P);
+
+        // CFM init gives the OT seeds and the ledger keys of CB
+        let init_session_id: [u8; 32] = rng.gen();
+        cb.start_init(&init_session_id, &mut rng).unwrap();
+        let msg1 = ob.start_init(&init_session_id, &mut rng).unwrap().unwrap();
+        let msg2 = relay(&mut cb, msg1, &mut rng).unwrap();
+        let msg3 = relay(&mut ob, msg2, &mut rng).unwrap();
+        assert!(relay(&mut cb, msg3, &mut rng).is_none());
+        let ot_seeds_ob = match ob.seed_store().unwrap().seeds() {
+            OTSeeds::OB(ot_seeds) => ot_seeds.clone(),
+            _ => unreachable!(),
+        };
+        let ot_seeds_cb = match cb.seed_store().unwrap().seeds() {
+            OTSeeds::CB(ot_seeds) => ot_seeds.clone(),
+            _ => unreachable!(),
+        };
+
+        // one ABT run for two CFM sessions
+        let pool_id: [u8; 32] = rng.gen();
+        let (eta_i, eta_m) = pool_sizes(2);
+        let (state_cb_r1, msg1) =
+            abt_create_msg1(&pool_id, &ot_seeds_cb, P, eta_i, eta_m, &mut rng).unwrap();
+        let (state_ob_r1, mut shares_ob, mut auth_triples_ob, msg2) =
+            abt_process_msg1(&pool_id, &ot_seeds_ob, P, eta_i, eta_m, &msg1, &mut rng).unwrap();
+        let (state_cb_r2, shares_cb, auth_triples_cb, msg3) =
+            abt_process_msg2(&state_cb_r1, &ot_seeds_cb, &msg2, &mut rng).unwrap();
+        let (state_ob_r2, msg4) =
+            abt_process_msg3(&state_ob_r1, &mut shares_ob, &mut auth_triples_ob, &msg3).unwrap();
+        let msg5 = abt_process_msg4(&state_cb_r2, &auth_triples_cb, &msg4).unwrap();
+        abt_process_msg5(&state_ob_r2, &msg5).unwrap();
+
+        let mut pool_ob =
+            TriplePool::new(&pool_id, P, ot_seeds_ob, shares_ob, auth_triples_ob).unwrap();
+        let mut pool_cb =
+            TriplePool::new(&pool_id, P, ot_seeds_cb, shares_cb, auth_triples_cb).unwrap();
+
+        let big_l = U64::from_u32(104);
+        let big_y: Vec<[u8; 32]> = (1..=10)
+            .map(|i| {
+                Customer::new(&format!("Customer{}", i), &format!("P{}", i), "Main St")
+                    .to_hash_bytes()
+            })
+            .collect();
+        let big_z: Vec<U64> = (1..=10).map(U64::from_u8).collect();
+        let y = Customer::new("Customer3", "P3", "Main St").to_hash_bytes();
+
+        // the second session of the pool, OB's share of Z_y grows by the first
+        for (index, big_x, b) in [(0, 100, true), (1, 2, false)] {
+            let session_ob = pool_ob.take().unwrap();
+            let session_cb = pool_cb.take_at(session_ob.index).unwrap();
+            assert_eq!(session_ob.index, index);
+
+            let session_id = ob.load_pool_session_ob(session_ob).unwrap();
+            assert_eq!(cb.load_pool_session_cb(session_cb).unwrap(), session_id);
+            assert!(ob.has_triples() && cb.has_triples());
+
+            cb.start_cfm_cb(
+                &session_id,
+                OutputPolicy::Both,
+                big_l,
+                false,
+                big_y.clone(),
+                big_z.clone(),
+                None,
+            )
+            .unwrap();
+            let mut next = Some(
+                ob.start_cfm_ob(
+                    &session_id,
+                    OutputPolicy::Both,
+                    Some(big_l),
+                    U64::from_u32(big_x),
+                    &y,
+                    &mut rng,
+                )
+                .unwrap(),
+            );
+            let mut to_cb = true;
+            while let Some(msg) = next {
+                next = relay(if to_cb { &mut cb } else { &mut ob }, msg, &mut rng);
+                to_cb = !to_cb;
+            }
+
+            assert_eq!(ob.result(), Some(b));
+            assert_eq!(cb.result(), Some(b));
+        }
+        assert_eq!(pool_ob.remaining(), 0);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::constants::{H1_RO_LABEL, H2_RO_LABEL, LAMBDA_BYTES, MASK_BYTES};
+use crate::constants::{H1_RO_LABEL, H2_RO_LABEL, LAMBDA_BYTES, MASK_BYTES, PSIT_RECORD_WORDS};
 use crate::proto::{Hash2Bytes, Hash3Bytes};
 use curve25519_dalek::ristretto::RistrettoPoint;
 use merlin::Transcript;
@@ -47,7 +47,7 @@ pub(crate) fn h3_function(
     t.append_message(b"point1", point1.compress().as_bytes());
     t.append_message(b"point2", point2.compress().as_bytes());
 
-    let mut output: Hash3Bytes = [0u8; MASK_BYTES * 2];
+    let mut output: Hash3Bytes = [0u8; MASK_BYTES * PSIT_RECORD_WORDS];
     t.challenge_bytes(b"h2-ro-bytes", &mut output);
 
     output